@@ -5,6 +5,7 @@ impl fmt::Display for TypeAnnotation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TypeAnnotation::Name(s) => write!(f, "{}", s),
+            TypeAnnotation::Generic { name, args } => write!(f, "{}{}", name, TypeArgs(args)),
             TypeAnnotation::Function { params, return_type } => {
                 write!(f, "(")?;
                 for (i, p) in params.iter().enumerate() {
@@ -17,6 +18,50 @@ impl fmt::Display for TypeAnnotation {
     }
 }
 
+/// Argumentos de tipo entre `<>`; nada si no hay ninguno.
+pub struct TypeArgs<'a>(pub &'a [TypeAnnotation]);
+
+impl fmt::Display for TypeArgs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (i, arg) in self.0.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ">")
+    }
+}
+
+/// Parámetros de tipo de una declaración entre `<>`; nada si no es genérica.
+pub struct TypeParams<'a>(pub &'a [TypeParam]);
+
+impl fmt::Display for TypeParams<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        for (i, param) in self.0.iter().enumerate() {
+            if i > 0 { write!(f, ", ")?; }
+            write!(f, "{}", param)?;
+        }
+        write!(f, ">")
+    }
+}
+
+impl fmt::Display for TypeParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(bound) = &self.bound {
+            write!(f, ": {}", bound)?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for decl in &self.declarations {
@@ -33,13 +78,14 @@ impl fmt::Display for Declaration {
             Declaration::Type(ty) => write!(f, "{}", ty),
             Declaration::Protocol(proto) => write!(f, "{}", proto),
             Declaration::Macro(macro_decl) => write!(f, "{}", macro_decl),
+            Declaration::Variant(variant) => write!(f, "{}", variant),
         }
     }
 }
 
 impl fmt::Display for FunctionDecl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "function {}(", self.name)?;
+        write!(f, "function {}{}(", self.name, TypeParams(&self.type_params))?;
         for (i, param) in self.params.iter().enumerate() {
             if i > 0 { write!(f, ", ")?; }
             write!(f, "{}", param)?;
@@ -55,7 +101,7 @@ impl fmt::Display for FunctionDecl {
 
 impl fmt::Display for TypeDecl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "type {}", self.name)?;
+        write!(f, "type {}{}", self.name, TypeParams(&self.type_params))?;
         if !self.params.is_empty() {
             write!(f, "(")?;
             for (i, param) in self.params.iter().enumerate() {
@@ -80,7 +126,7 @@ impl fmt::Display for TypeDecl {
 
 impl fmt::Display for ProtocolDecl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "protocol {}", self.name)?;
+        write!(f, "protocol {}{}", self.name, TypeParams(&self.type_params))?;
         if let Some(parent) = &self.parent {
             write!(f, " extends {}", parent)?;
         }
@@ -92,6 +138,21 @@ impl fmt::Display for ProtocolDecl {
     }
 }
 
+impl fmt::Display for VariantDecl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "variant {} =", self.name)?;
+        for (i, case) in self.cases.iter().enumerate() {
+            if i > 0 { write!(f, " |")?; }
+            write!(f, " {}", case.name)?;
+            if !case.params.is_empty() {
+                let params: Vec<String> = case.params.iter().map(|p| p.to_string()).collect();
+                write!(f, "({})", params.join(", "))?;
+            }
+        }
+        writeln!(f, ";")
+    }
+}
+
 impl fmt::Display for Param {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)?;
@@ -104,7 +165,7 @@ impl fmt::Display for Param {
 
 impl fmt::Display for TypeInit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}(", self.name)?;
+        write!(f, "{}{}(", self.name, TypeArgs(&self.type_args))?;
         for (i, arg) in self.args.iter().enumerate() {
             if i > 0 { write!(f, ", ")?; }
             write!(f, "{}", arg.node)?;
@@ -196,8 +257,8 @@ impl fmt::Display for Expr {
             Expr::AttributeAccess { obj, attribute } => {
                 write!(f, "{}.{}", obj.node, attribute)
             },
-            Expr::Instantiation { ty, args } => {
-                write!(f, "new {}(", ty)?;
+            Expr::Instantiation { ty, type_args, args } => {
+                write!(f, "new {}{}(", ty, TypeArgs(type_args))?;
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 { write!(f, ", ")?; }
                     write!(f, "{}", arg.node)?;
@@ -217,6 +278,14 @@ impl fmt::Display for Expr {
             Expr::VectorGenerator { expr, var, iterable } => {
                 write!(f, "[{} | {} in {}]", expr.node, var, iterable.node)
             },
+            Expr::MapLiteral(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{} -> {}", key.node, value.node)?;
+                }
+                write!(f, "}}")
+            },
             Expr::Lambda { params, return_type, body } => {
                 write!(f, "(")?;
                 for (i, p) in params.iter().enumerate() {
@@ -230,6 +299,18 @@ impl fmt::Display for Expr {
                 write!(f, " => {}", body.node)
             },
             Expr::Indexing { obj, index } => write!(f, "{}[{}]", obj.node, index.node),
+            Expr::IndexAssignment { obj, index, value } => write!(f, "{}[{}] := {}", obj.node, index.node, value.node),
+            Expr::Slice { obj, start, end } => {
+                write!(f, "{}[", obj.node)?;
+                if let Some(start) = start {
+                    write!(f, "{}", start.node)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end.node)?;
+                }
+                write!(f, "]")
+            }
             Expr::Sqrt(e) => write!(f, "sqrt({})", e.node),
             Expr::Sin(e) => write!(f, "sin({})", e.node),
             Expr::Cos(e) => write!(f, "cos({})", e.node),
@@ -241,13 +322,34 @@ impl fmt::Display for Expr {
             Expr::Match { expr, cases, default } => {
                 write!(f, "match({}) {{\n", expr.node)?;
                 for case in cases {
-                    write!(f, "  case {} => {};\n", case.pattern, case.expr.node)?;
+                    write!(f, "  case {}", case.pattern)?;
+                    if let Some(guard) = &case.guard {
+                        write!(f, " if {}", guard.node)?;
+                    }
+                    write!(f, " => {};\n", case.expr.node)?;
                 }
                 if let Some(def) = default {
                     write!(f, "  default => {};\n", def.node)?;
                 }
                 write!(f, "}}")
             },
+            Expr::Try { body, catches } => {
+                write!(f, "try {}", body.node)?;
+                for c in catches {
+                    write!(f, " catch ({}", c.var)?;
+                    if let Some(ty) = &c.ty {
+                        write!(f, ": {}", ty)?;
+                    }
+                    write!(f, ") {}", c.body.node)?;
+                }
+                Ok(())
+            },
+            Expr::Throw(e) => write!(f, "throw {}", e.node),
+            Expr::Break(None) => write!(f, "break"),
+            Expr::Break(Some(e)) => write!(f, "break {}", e.node),
+            Expr::Continue(None) => write!(f, "continue"),
+            Expr::Continue(Some(e)) => write!(f, "continue {}", e.node),
+            Expr::Return(e) => write!(f, "return {}", e.node),
             Expr::Error => write!(f, "<error>"),
         }
     }
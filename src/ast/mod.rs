@@ -2,3 +2,4 @@ pub mod nodes;
 pub mod display;
 pub mod optimize;
 pub mod transform;
+pub mod walk;
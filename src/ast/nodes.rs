@@ -1,4 +1,4 @@
-use crate::utils::Spanned;
+use crate::utils::{Position, Spanned};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Program {
@@ -12,11 +12,17 @@ pub enum Declaration {
     Type(TypeDecl),
     Protocol(ProtocolDecl),
     Macro(MacroDecl),
+    Variant(VariantDecl),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeAnnotation {
     Name(String),
+    /// Aplicación de un tipo o protocolo genérico: `Stack<Number>`
+    Generic {
+        name: String,
+        args: Vec<TypeAnnotation>,
+    },
     Function {
         params: Vec<TypeAnnotation>,
         return_type: Box<TypeAnnotation>,
@@ -25,28 +31,57 @@ pub enum TypeAnnotation {
     Vector(Box<TypeAnnotation>),
 }
 
+/// Parámetro de tipo de una declaración genérica: `T` o `T: Cota`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeParam {
+    pub name: String,
+    pub bound: Option<TypeAnnotation>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionDecl {
     pub name: String,
+    pub type_params: Vec<TypeParam>,
     pub params: Vec<Param>,
     pub return_type: Option<TypeAnnotation>,
     pub body: Spanned<Expr>,
+    pub pos: Position, // posición del nombre declarado
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeDecl {
     pub name: String,
+    pub type_params: Vec<TypeParam>,
     pub params: Vec<Param>,      // Para argumentos del constructor
     pub parent: Option<TypeInit>, // Herencia con argumentos
     pub attributes: Vec<Attribute>,
     pub methods: Vec<FunctionDecl>,
+    pub pos: Position, // posición del nombre declarado
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProtocolDecl {
     pub name: String,
+    pub type_params: Vec<TypeParam>,
     pub parent: Option<String>,
     pub methods: Vec<MethodSignature>,
+    pub pos: Position, // posición del nombre declarado
+}
+
+/// Tipo suma: `variant Shape = Circle(r: Number) | Rect(w: Number, h: Number);`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDecl {
+    pub name: String,
+    pub cases: Vec<VariantCase>,
+    pub pos: Position, // posición del nombre declarado
+}
+
+/// Un caso de un `variant`: un constructor con sus campos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantCase {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub pos: Position,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -58,14 +93,27 @@ pub struct Param {
 #[derive(Debug, Clone, PartialEq)]
 pub struct TypeInit {
     pub name: String,
+    pub type_args: Vec<TypeAnnotation>,
     pub args: Vec<Spanned<Expr>>,
 }
 
+impl TypeInit {
+    /// Tipo del padre como anotación: `Base` o `Base<A, ...>`.
+    pub fn annotation(&self) -> TypeAnnotation {
+        if self.type_args.is_empty() {
+            TypeAnnotation::Name(self.name.clone())
+        } else {
+            TypeAnnotation::Generic { name: self.name.clone(), args: self.type_args.clone() }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Attribute {
     pub name: String,
     pub type_annotation: Option<TypeAnnotation>,
     pub init: Spanned<Expr>,
+    pub pos: Position, // posición del nombre declarado
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -73,6 +121,7 @@ pub struct MethodSignature {
     pub name: String,
     pub params: Vec<Param>,
     pub return_type: TypeAnnotation,
+    pub pos: Position, // posición del nombre declarado
 }
 
 // --- Macros ---
@@ -83,6 +132,7 @@ pub struct MacroDecl {
     pub params: Vec<MacroParam>,
     pub return_type: Option<TypeAnnotation>,
     pub body: Spanned<Expr>,
+    pub pos: Position, // posición del nombre declarado
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -100,9 +150,20 @@ pub enum MacroParam {
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchCase {
     pub pattern: Pattern,
+    /// Guarda opcional: `case d: Dog if d.age > 3 => ...`
+    pub guard: Option<Spanned<Expr>>,
     pub expr: Spanned<Expr>,
 }
 
+/// Manejador de un `try`: `catch (e: IndexError) expr`. Sin anotación
+/// atrapa cualquier `Error`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatchClause {
+    pub var: String,
+    pub ty: Option<String>,
+    pub body: Spanned<Expr>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Pattern {
     // Literal: 0, 1, "hello", true
@@ -120,7 +181,8 @@ pub enum Pattern {
         op: UnOp,
         operand: Box<Pattern>,
     },
-    // Llamada: f(x, y)
+    // Llamada: f(x, y). Fuera de las macros es un patrón de constructor:
+    // `Point(x, 0)` encaja con un `Point` y compara sus atributos
     Call {
         func: String,
         args: Vec<Pattern>,
@@ -198,6 +260,7 @@ pub enum Expr {
     },
     Instantiation {
         ty: String,
+        type_args: Vec<TypeAnnotation>,
         args: Vec<Spanned<Expr>>,
     },
 
@@ -223,6 +286,23 @@ pub enum Expr {
         obj: Box<Spanned<Expr>>,
         index: Box<Spanned<Expr>>,
     },
+    /// Asignación a un elemento: v[i] := expr
+    IndexAssignment {
+        obj: Box<Spanned<Expr>>,
+        index: Box<Spanned<Expr>>,
+        value: Box<Spanned<Expr>>,
+    },
+    /// `v[a:b]`: copia de los elementos desde `a` hasta antes de `b`. Sin
+    /// `a` empieza en el primero y sin `b` llega hasta el último.
+    Slice {
+        obj: Box<Spanned<Expr>>,
+        start: Option<Box<Spanned<Expr>>>,
+        end: Option<Box<Spanned<Expr>>>,
+    },
+
+    // --- Diccionarios ---
+    /// `{ clave -> valor, ... }`: pares en el orden en que se escriben.
+    MapLiteral(Vec<(Spanned<Expr>, Spanned<Expr>)>),
 
     // --- Funciones matemáticas integradas ---
     Sqrt(Box<Spanned<Expr>>),
@@ -234,12 +314,26 @@ pub enum Expr {
     PI,
     E,
     
-    // --- Pattern Matching (para macros) ---
+    // --- Pattern Matching ---
     Match {
         expr: Box<Spanned<Expr>>,
         cases: Vec<MatchCase>,
         default: Option<Box<Spanned<Expr>>>,
     },
+
+    // --- Excepciones ---
+    Try {
+        body: Box<Spanned<Expr>>,
+        catches: Vec<CatchClause>,
+    },
+    Throw(Box<Spanned<Expr>>),
+
+    // --- Saltos ---
+    /// Sale del bucle más cercano; el valor, si lo hay, es el del bucle.
+    Break(Option<Box<Spanned<Expr>>>),
+    /// Pasa a la siguiente iteración; el valor, si lo hay, es el de la iteración.
+    Continue(Option<Box<Spanned<Expr>>>),
+    Return(Box<Spanned<Expr>>),
     
     /// Nodo de error para recuperación de errores
     Error,
@@ -266,7 +360,7 @@ pub enum UnOp {
 pub trait ExprVisitor {
     /// Visita un nodo de expresión y retorna la expresión transformada
     fn visit_expr(&mut self, expr: Spanned<Expr>) -> Spanned<Expr> {
-        let pos = expr.pos;
+        let (pos, end) = (expr.pos, expr.end);
         let node = match expr.node {
             Expr::Number(_) | Expr::String(_) | Expr::Boolean(_) | 
             Expr::Rand | Expr::PI | Expr::E | Expr::Error => expr.node,
@@ -304,8 +398,8 @@ pub trait ExprVisitor {
             Expr::AttributeAccess { obj, attribute } => 
                 self.visit_attribute_access(*obj, attribute, pos),
             
-            Expr::Instantiation { ty, args } => 
-                self.visit_instantiation(ty, args, pos),
+            Expr::Instantiation { ty, type_args, args } => 
+                self.visit_instantiation(ty, type_args, args, pos),
             
             Expr::Lambda { params, return_type, body } => 
                 self.visit_lambda(params, return_type, *body, pos),
@@ -319,6 +413,14 @@ pub trait ExprVisitor {
                 self.visit_vector_generator(*expr, var, *iterable, pos),
             
             Expr::Indexing { obj, index } => self.visit_indexing(*obj, *index, pos),
+
+            Expr::IndexAssignment { obj, index, value } =>
+                self.visit_index_assignment(*obj, *index, *value, pos),
+
+            Expr::Slice { obj, start, end } =>
+                self.visit_slice(*obj, start.map(|s| *s), end.map(|e| *e), pos),
+
+            Expr::MapLiteral(entries) => self.visit_map_literal(entries, pos),
             
             Expr::Sqrt(e) => self.visit_sqrt(*e, pos),
             Expr::Sin(e) => self.visit_sin(*e, pos),
@@ -328,9 +430,15 @@ pub trait ExprVisitor {
             
             Expr::Match { expr, cases, default } => 
                 self.visit_match(*expr, cases, default, pos),
+
+            Expr::Try { body, catches } => self.visit_try(*body, catches, pos),
+            Expr::Throw(e) => self.visit_throw(*e, pos),
+            Expr::Break(value) => self.visit_break(value.map(|v| *v), pos),
+            Expr::Continue(value) => self.visit_continue(value.map(|v| *v), pos),
+            Expr::Return(value) => self.visit_return(*value, pos),
         };
         
-        Spanned::new(node, pos)
+        Spanned { node, pos, end }
     }
     
     // Métodos específicos para cada tipo de nodo
@@ -438,9 +546,10 @@ pub trait ExprVisitor {
         }
     }
     
-    fn visit_instantiation(&mut self, ty: String, args: Vec<Spanned<Expr>>, _pos: crate::utils::Position) -> Expr {
+    fn visit_instantiation(&mut self, ty: String, type_args: Vec<TypeAnnotation>, args: Vec<Spanned<Expr>>, _pos: crate::utils::Position) -> Expr {
         Expr::Instantiation {
             ty,
+            type_args,
             args: args.into_iter().map(|a| self.visit_expr(a)).collect(),
         }
     }
@@ -475,12 +584,34 @@ pub trait ExprVisitor {
         }
     }
     
+    fn visit_map_literal(&mut self, entries: Vec<(Spanned<Expr>, Spanned<Expr>)>, _pos: crate::utils::Position) -> Expr {
+        Expr::MapLiteral(entries.into_iter().map(|(k, v)| (self.visit_expr(k), self.visit_expr(v))).collect())
+    }
+
     fn visit_indexing(&mut self, obj: Spanned<Expr>, index: Spanned<Expr>, _pos: crate::utils::Position) -> Expr {
         Expr::Indexing {
             obj: Box::new(self.visit_expr(obj)),
             index: Box::new(self.visit_expr(index)),
         }
     }
+
+    fn visit_index_assignment(&mut self, obj: Spanned<Expr>, index: Spanned<Expr>,
+                              value: Spanned<Expr>, _pos: crate::utils::Position) -> Expr {
+        Expr::IndexAssignment {
+            obj: Box::new(self.visit_expr(obj)),
+            index: Box::new(self.visit_expr(index)),
+            value: Box::new(self.visit_expr(value)),
+        }
+    }
+
+    fn visit_slice(&mut self, obj: Spanned<Expr>, start: Option<Spanned<Expr>>,
+                   end: Option<Spanned<Expr>>, _pos: crate::utils::Position) -> Expr {
+        Expr::Slice {
+            obj: Box::new(self.visit_expr(obj)),
+            start: start.map(|s| Box::new(self.visit_expr(s))),
+            end: end.map(|e| Box::new(self.visit_expr(e))),
+        }
+    }
     
     fn visit_sqrt(&mut self, expr: Spanned<Expr>, _pos: crate::utils::Position) -> Expr {
         Expr::Sqrt(Box::new(self.visit_expr(expr)))
@@ -511,9 +642,38 @@ pub trait ExprVisitor {
             expr: Box::new(self.visit_expr(expr)),
             cases: cases.into_iter().map(|case| MatchCase {
                 pattern: case.pattern,
+                guard: case.guard.map(|g| self.visit_expr(g)),
                 expr: self.visit_expr(case.expr),
             }).collect(),
             default: default.map(|d| Box::new(self.visit_expr(*d))),
         }
     }
+
+    fn visit_try(&mut self, body: Spanned<Expr>, catches: Vec<CatchClause>,
+                 _pos: crate::utils::Position) -> Expr {
+        Expr::Try {
+            body: Box::new(self.visit_expr(body)),
+            catches: catches.into_iter().map(|c| CatchClause {
+                var: c.var,
+                ty: c.ty,
+                body: self.visit_expr(c.body),
+            }).collect(),
+        }
+    }
+
+    fn visit_throw(&mut self, expr: Spanned<Expr>, _pos: crate::utils::Position) -> Expr {
+        Expr::Throw(Box::new(self.visit_expr(expr)))
+    }
+
+    fn visit_break(&mut self, value: Option<Spanned<Expr>>, _pos: crate::utils::Position) -> Expr {
+        Expr::Break(value.map(|v| Box::new(self.visit_expr(v))))
+    }
+
+    fn visit_continue(&mut self, value: Option<Spanned<Expr>>, _pos: crate::utils::Position) -> Expr {
+        Expr::Continue(value.map(|v| Box::new(self.visit_expr(v))))
+    }
+
+    fn visit_return(&mut self, value: Spanned<Expr>, _pos: crate::utils::Position) -> Expr {
+        Expr::Return(Box::new(self.visit_expr(value)))
+    }
 }
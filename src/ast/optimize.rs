@@ -44,6 +44,11 @@ fn collect_assigned_vars(expr: &Expr, out: &mut std::collections::HashSet<String
             collect_assigned_vars(&obj.node, out);
             collect_assigned_vars(&value.node, out);
         }
+        Expr::IndexAssignment { obj, index, value } => {
+            collect_assigned_vars(&obj.node, out);
+            collect_assigned_vars(&index.node, out);
+            collect_assigned_vars(&value.node, out);
+        }
         Expr::Block(exprs) => {
             for e in exprs { collect_assigned_vars(&e.node, out); }
         }
@@ -77,9 +82,18 @@ fn collect_assigned_vars(expr: &Expr, out: &mut std::collections::HashSet<String
             for a in args { collect_assigned_vars(&a.node, out); }
         }
         Expr::Lambda { body, .. } => collect_assigned_vars(&body.node, out),
+        Expr::MapLiteral(entries) => {
+            for (k, v) in entries {
+                collect_assigned_vars(&k.node, out);
+                collect_assigned_vars(&v.node, out);
+            }
+        }
         Expr::Match { expr: e, cases, default } => {
             collect_assigned_vars(&e.node, out);
-            for c in cases { collect_assigned_vars(&c.expr.node, out); }
+            for c in cases {
+                if let Some(g) = &c.guard { collect_assigned_vars(&g.node, out); }
+                collect_assigned_vars(&c.expr.node, out);
+            }
             if let Some(d) = default { collect_assigned_vars(&d.node, out); }
         }
         _ => {}
@@ -107,41 +121,52 @@ fn optimize_declaration(decl: Declaration, interner: &mut StringInterner, env: &
         Declaration::Function(func) => {
             Declaration::Function(FunctionDecl {
                 name: interner.intern(func.name),
+                type_params: func.type_params.into_iter().map(|p| optimize_type_param(p, interner)).collect(),
                 params: func.params.into_iter().map(|p| optimize_param(p, interner)).collect(),
                 return_type: func.return_type.map(|t| optimize_type_annotation(t, interner)),
                 body: optimize_expr(func.body, interner, env),
+                pos: func.pos,
             })
         }
         Declaration::Type(ty) => {
             Declaration::Type(TypeDecl {
                 name: interner.intern(ty.name),
+                type_params: ty.type_params.into_iter().map(|p| optimize_type_param(p, interner)).collect(),
                 params: ty.params.into_iter().map(|p| optimize_param(p, interner)).collect(),
                 parent: ty.parent.map(|p| TypeInit {
                     name: interner.intern(p.name),
+                    type_args: p.type_args.into_iter().map(|t| optimize_type_annotation(t, interner)).collect(),
                     args: p.args.into_iter().map(|a| optimize_expr(a, interner, env)).collect(),
                 }),
                 attributes: ty.attributes.into_iter().map(|a| Attribute {
                     name: interner.intern(a.name),
                     type_annotation: a.type_annotation.map(|t| optimize_type_annotation(t, interner)),
                     init: optimize_expr(a.init, interner, env),
+                    pos: a.pos,
                 }).collect(),
                 methods: ty.methods.into_iter().map(|m| FunctionDecl {
                     name: interner.intern(m.name),
+                    type_params: m.type_params.into_iter().map(|p| optimize_type_param(p, interner)).collect(),
                     params: m.params.into_iter().map(|p| optimize_param(p, interner)).collect(),
                     return_type: m.return_type.map(|t| optimize_type_annotation(t, interner)),
                     body: optimize_expr(m.body, interner, env),
+                    pos: m.pos,
                 }).collect(),
+                pos: ty.pos,
             })
         }
         Declaration::Protocol(proto) => {
             Declaration::Protocol(ProtocolDecl {
                 name: interner.intern(proto.name),
+                type_params: proto.type_params.into_iter().map(|p| optimize_type_param(p, interner)).collect(),
                 parent: proto.parent.map(|p| interner.intern(p)),
                 methods: proto.methods.into_iter().map(|m| MethodSignature {
                     name: interner.intern(m.name),
                     params: m.params.into_iter().map(|p| optimize_param(p, interner)).collect(),
                     return_type: optimize_type_annotation(m.return_type, interner),
+                    pos: m.pos,
                 }).collect(),
+                pos: proto.pos,
             })
         }
         Declaration::Macro(macro_decl) => {
@@ -149,6 +174,8 @@ fn optimize_declaration(decl: Declaration, interner: &mut StringInterner, env: &
             // Por ahora, simplemente pasarlas sin optimizar
             Declaration::Macro(macro_decl)
         }
+        // Ya bajado a tipos: solo queda para el chequeo semántico
+        Declaration::Variant(variant) => Declaration::Variant(variant),
     }
 }
 
@@ -159,9 +186,20 @@ fn optimize_param(param: Param, interner: &mut StringInterner) -> Param {
     }
 }
 
+fn optimize_type_param(param: TypeParam, interner: &mut StringInterner) -> TypeParam {
+    TypeParam {
+        name: interner.intern(param.name),
+        bound: param.bound.map(|t| optimize_type_annotation(t, interner)),
+    }
+}
+
 fn optimize_type_annotation(ty: TypeAnnotation, interner: &mut StringInterner) -> TypeAnnotation {
     match ty {
         TypeAnnotation::Name(s) => TypeAnnotation::Name(interner.intern(s)),
+        TypeAnnotation::Generic { name, args } => TypeAnnotation::Generic {
+            name: interner.intern(name),
+            args: args.into_iter().map(|a| optimize_type_annotation(a, interner)).collect(),
+        },
         TypeAnnotation::Function { params, return_type } => {
             TypeAnnotation::Function {
                 params: params.into_iter().map(|p| optimize_type_annotation(p, interner)).collect(),
@@ -179,7 +217,7 @@ fn optimize_type_annotation(ty: TypeAnnotation, interner: &mut StringInterner) -
 
 /// Optimiza una expresión aplicando constant folding y dead code elimination.
 fn optimize_expr(expr: Spanned<Expr>, interner: &mut StringInterner, env: &ConstEnv) -> Spanned<Expr> {
-    let pos = expr.pos.clone();
+    let (pos, end) = (expr.pos, expr.end);
     let optimized_node = match expr.node {
         // CONSTANT PROPAGATION: reemplazar variable por su valor si es conocido
         Expr::Identifier(ref name) => {
@@ -213,6 +251,14 @@ fn optimize_expr(expr: Spanned<Expr>, interner: &mut StringInterner, env: &Const
                 (Expr::Number(a), Op::Gt, Expr::Number(b)) => Expr::Boolean(a > b),
                 (Expr::Number(a), Op::Le, Expr::Number(b)) => Expr::Boolean(a <= b),
                 (Expr::Number(a), Op::Ge, Expr::Number(b)) => Expr::Boolean(a >= b),
+
+                // Las cadenas se comparan por valor, byte a byte
+                (Expr::String(a), Op::Eq, Expr::String(b)) => Expr::Boolean(a == b),
+                (Expr::String(a), Op::Neq, Expr::String(b)) => Expr::Boolean(a != b),
+                (Expr::String(a), Op::Lt, Expr::String(b)) => Expr::Boolean(a < b),
+                (Expr::String(a), Op::Gt, Expr::String(b)) => Expr::Boolean(a > b),
+                (Expr::String(a), Op::Le, Expr::String(b)) => Expr::Boolean(a <= b),
+                (Expr::String(a), Op::Ge, Expr::String(b)) => Expr::Boolean(a >= b),
                 
                 // Booleanos
                 (Expr::Boolean(a), Op::And, Expr::Boolean(b)) => Expr::Boolean(*a && *b),
@@ -380,9 +426,10 @@ fn optimize_expr(expr: Spanned<Expr>, interner: &mut StringInterner, env: &Const
             }
         }
         
-        Expr::Instantiation { ty, args } => {
+        Expr::Instantiation { ty, type_args, args } => {
             Expr::Instantiation {
                 ty: interner.intern(ty),
+                type_args: type_args.into_iter().map(|t| optimize_type_annotation(t, interner)).collect(),
                 args: args.into_iter().map(|a| optimize_expr(a, interner, env)).collect(),
             }
         }
@@ -420,6 +467,29 @@ fn optimize_expr(expr: Spanned<Expr>, interner: &mut StringInterner, env: &Const
                 index: Box::new(optimize_expr(*index, interner, env)),
             }
         }
+
+        Expr::IndexAssignment { obj, index, value } => {
+            Expr::IndexAssignment {
+                obj: Box::new(optimize_expr(*obj, interner, env)),
+                index: Box::new(optimize_expr(*index, interner, env)),
+                value: Box::new(optimize_expr(*value, interner, env)),
+            }
+        }
+
+        Expr::Slice { obj, start, end } => {
+            Expr::Slice {
+                obj: Box::new(optimize_expr(*obj, interner, env)),
+                start: start.map(|s| Box::new(optimize_expr(*s, interner, env))),
+                end: end.map(|e| Box::new(optimize_expr(*e, interner, env))),
+            }
+        }
+
+        // Diccionarios
+        Expr::MapLiteral(entries) => {
+            Expr::MapLiteral(entries.into_iter()
+                .map(|(k, v)| (optimize_expr(k, interner, env), optimize_expr(v, interner, env)))
+                .collect())
+        }
         
         // Lambda
         Expr::Lambda { params, return_type, body } => {
@@ -481,5 +551,5 @@ fn optimize_expr(expr: Spanned<Expr>, interner: &mut StringInterner, env: &Const
         other => other,
     };
     
-    Spanned::new(optimized_node, pos)
+    Spanned { node: optimized_node, pos, end }
 }
@@ -2,7 +2,8 @@
 use crate::ast::nodes::*;
 use crate::semantic::Context;
 use crate::utils::{Spanned, Position};
-use std::collections::HashMap;
+use crate::ast::walk;
+use std::collections::{HashMap, HashSet};
 
 /// Transforma el AST para soportar implicit functor implementation
 pub fn transform_implicit_functors(program: &mut Program, _context: &Context) {
@@ -38,6 +39,86 @@ pub fn transform_implicit_functors(program: &mut Program, _context: &Context) {
     }
 }
 
+/// Baja cada `variant` a una jerarquía de clases: un tipo base sin miembros y,
+/// por caso, un tipo que hereda de él con un atributo por campo. La
+/// declaración original se conserva para que el chequeo semántico sepa que la
+/// jerarquía está cerrada.
+pub fn lower_variants(program: &mut Program) {
+    let mut i = 0;
+    while i < program.declarations.len() {
+        let Declaration::Variant(variant) = &program.declarations[i] else {
+            i += 1;
+            continue;
+        };
+        let lowered = variant_types(variant);
+        let n = lowered.len();
+        program.declarations.splice(i + 1..i + 1, lowered.into_iter().map(Declaration::Type));
+        i += n + 1;
+    }
+
+    let nullary: HashSet<String> = program.declarations.iter()
+        .filter_map(|d| match d { Declaration::Variant(v) => Some(v), _ => None })
+        .flat_map(|v| v.cases.iter().filter(|c| c.params.is_empty()).map(|c| c.name.clone()))
+        .collect();
+    if !nullary.is_empty() {
+        for expr in walk::root_exprs_mut(program) {
+            rewrite_nullary_patterns(expr, &nullary);
+        }
+    }
+}
+
+/// Un caso sin campos se escribe sin paréntesis (`case Empty => ...`), que el
+/// parser lee como una variable: se reescribe al patrón de constructor `Empty()`.
+fn rewrite_nullary_patterns(expr: &mut Spanned<Expr>, nullary: &HashSet<String>) {
+    fn rewrite(pattern: &mut Pattern, nullary: &HashSet<String>) {
+        match pattern {
+            Pattern::Variable { name, type_annotation: None } if nullary.contains(name) => {
+                *pattern = Pattern::Call { func: std::mem::take(name), args: vec![] };
+            }
+            Pattern::Call { args, .. } => args.iter_mut().for_each(|a| rewrite(a, nullary)),
+            Pattern::Binary { left, right, .. } => {
+                rewrite(left, nullary);
+                rewrite(right, nullary);
+            }
+            Pattern::Unary { operand, .. } => rewrite(operand, nullary),
+            _ => {}
+        }
+    }
+    if let Expr::Match { cases, .. } = &mut expr.node {
+        cases.iter_mut().for_each(|c| rewrite(&mut c.pattern, nullary));
+    }
+    for child in walk::children_mut(&mut expr.node) {
+        rewrite_nullary_patterns(child, nullary);
+    }
+}
+
+fn variant_types(variant: &VariantDecl) -> Vec<TypeDecl> {
+    let base = TypeDecl {
+        name: variant.name.clone(),
+        type_params: vec![],
+        params: vec![],
+        parent: None,
+        attributes: vec![],
+        methods: vec![],
+        pos: variant.pos,
+    };
+    let cases = variant.cases.iter().map(|case| TypeDecl {
+        name: case.name.clone(),
+        type_params: vec![],
+        params: case.params.clone(),
+        parent: Some(TypeInit { name: variant.name.clone(), type_args: vec![], args: vec![] }),
+        attributes: case.params.iter().map(|p| Attribute {
+            name: p.name.clone(),
+            type_annotation: p.type_annotation.clone(),
+            init: Spanned::new(Expr::Identifier(p.name.clone()), case.pos),
+            pos: case.pos,
+        }).collect(),
+        methods: vec![],
+        pos: case.pos,
+    });
+    std::iter::once(base).chain(cases).collect()
+}
+
 struct ImplicitFunctorTransformer {
     generated_wrappers: HashMap<String, TypeDecl>,
     next_wrapper_id: usize,
@@ -89,6 +170,7 @@ impl ImplicitFunctorTransformer {
                                 // Reemplazar por instanciación
                                 arg.node = Expr::Instantiation {
                                     ty: wrapper_name,
+                                    type_args: vec![],
                                     args: vec![],
                                 };
                                 break; // Solo generar un wrapper por función
@@ -113,6 +195,7 @@ impl ImplicitFunctorTransformer {
                                     );
                                     init_expr.node = Expr::Instantiation {
                                         ty: wrapper_name,
+                                        type_args: vec![],
                                         args: vec![],
                                     };
                                 }
@@ -157,7 +240,7 @@ impl ImplicitFunctorTransformer {
             Expr::Unary(_op, operand) => {
                 self.transform_expr(operand);
             }
-            Expr::Instantiation { ty: _, args } => {
+            Expr::Instantiation { args, .. } => {
                 for arg in args.iter_mut() {
                     self.transform_expr(arg);
                 }
@@ -205,31 +288,34 @@ impl ImplicitFunctorTransformer {
         let (invoke_params, invoke_ret_type) = protocol_sig;
         
         // Crear el método invoke que llama a la función original
-        let invoke_body = Spanned {
-            node: Expr::Call {
+        let invoke_body = Spanned::new(
+            Expr::Call {
                 func: func_name.to_string(),
-                args: invoke_params.iter().map(|p| Spanned {
-                    node: Expr::Identifier(p.name.clone()),
-                    pos: Position { line: 0, column: 0 },
-                }).collect(),
+                args: invoke_params.iter()
+                    .map(|p| Spanned::new(Expr::Identifier(p.name.clone()), Position::default()))
+                    .collect(),
             },
-            pos: Position { line: 0, column: 0 },
-        };
+            Position::default(),
+        );
         
         let method = FunctionDecl {
             name: "invoke".to_string(),
+            type_params: vec![],
             params: invoke_params.clone(),
             return_type: invoke_ret_type.clone(),
             body: invoke_body,
+            pos: Position::default(),
         };
         
         // Crear el TypeDecl para el wrapper
         let wrapper_decl = TypeDecl {
             name: wrapper_name.clone(),
+            type_params: vec![],
             params: vec![],
             parent: None,
             attributes: vec![],
             methods: vec![method],
+            pos: Position::default(),
         };
         
         self.generated_wrappers.insert(key, wrapper_decl);
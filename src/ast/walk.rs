@@ -0,0 +1,202 @@
+//! Recorrido genérico del AST: acceso a las subexpresiones directas de un nodo
+//! y a las expresiones raíz de un programa, sin reescribir un `match` completo.
+
+use crate::ast::nodes::{Declaration, Expr, Pattern, Program};
+use crate::utils::Spanned;
+
+/// Subexpresiones directas de `expr`, en orden de aparición en el código fuente.
+pub fn children(expr: &Expr) -> Vec<&Spanned<Expr>> {
+    match expr {
+        Expr::Binary(l, _, r) | Expr::Log(l, r) => vec![l, r],
+        Expr::Unary(_, e)
+        | Expr::Is(e, _)
+        | Expr::As(e, _)
+        | Expr::Sqrt(e)
+        | Expr::Sin(e)
+        | Expr::Cos(e)
+        | Expr::Exp(e)
+        | Expr::Throw(e)
+        | Expr::Return(e) => vec![e],
+        Expr::Break(value) | Expr::Continue(value) => value.iter().map(|v| &**v).collect(),
+        Expr::If { cond, then_expr, else_expr } => vec![cond, then_expr, else_expr],
+        Expr::While { cond, body } => vec![cond, body],
+        Expr::For { iterable, body, .. } => vec![iterable, body],
+        Expr::Block(exprs) | Expr::VectorLiteral(exprs) => exprs.iter().collect(),
+        Expr::Let { bindings, body } => {
+            let mut out: Vec<&Spanned<Expr>> = bindings.iter().map(|(_, _, e)| e).collect();
+            out.push(body);
+            out
+        }
+        Expr::Assignment { value, .. } => vec![value],
+        Expr::AttributeAssignment { obj, value, .. } => vec![obj, value],
+        Expr::Call { args, .. } | Expr::BaseCall { args } | Expr::Instantiation { args, .. } => {
+            args.iter().collect()
+        }
+        Expr::MethodCall { obj, args, .. } => {
+            let mut out: Vec<&Spanned<Expr>> = vec![obj];
+            out.extend(args);
+            out
+        }
+        Expr::AttributeAccess { obj, .. } => vec![obj],
+        Expr::Lambda { body, .. } => vec![body],
+        Expr::VectorGenerator { expr, iterable, .. } => vec![expr, iterable],
+        Expr::Indexing { obj, index } => vec![obj, index],
+        Expr::IndexAssignment { obj, index, value } => vec![obj, index, value],
+        Expr::Slice { obj, start, end } => {
+            let mut out: Vec<&Spanned<Expr>> = vec![obj];
+            out.extend(start.as_deref());
+            out.extend(end.as_deref());
+            out
+        }
+        Expr::MapLiteral(entries) => entries.iter().flat_map(|(k, v)| [k, v]).collect(),
+        Expr::Match { expr, cases, default } => {
+            let mut out: Vec<&Spanned<Expr>> = vec![expr];
+            for case in cases {
+                out.extend(case.guard.as_ref());
+                out.push(&case.expr);
+            }
+            out.extend(default.as_deref());
+            out
+        }
+        Expr::Try { body, catches } => {
+            let mut out: Vec<&Spanned<Expr>> = vec![body];
+            out.extend(catches.iter().map(|c| &c.body));
+            out
+        }
+        Expr::Number(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Identifier(_) |
+        Expr::Rand | Expr::PI | Expr::E | Expr::Error => Vec::new(),
+    }
+}
+
+/// Versión mutable de [`children`].
+pub fn children_mut(expr: &mut Expr) -> Vec<&mut Spanned<Expr>> {
+    match expr {
+        Expr::Binary(l, _, r) | Expr::Log(l, r) => vec![l, r],
+        Expr::Unary(_, e)
+        | Expr::Is(e, _)
+        | Expr::As(e, _)
+        | Expr::Sqrt(e)
+        | Expr::Sin(e)
+        | Expr::Cos(e)
+        | Expr::Exp(e)
+        | Expr::Throw(e)
+        | Expr::Return(e) => vec![e],
+        Expr::Break(value) | Expr::Continue(value) => value.iter_mut().map(|v| &mut **v).collect(),
+        Expr::If { cond, then_expr, else_expr } => vec![cond, then_expr, else_expr],
+        Expr::While { cond, body } => vec![cond, body],
+        Expr::For { iterable, body, .. } => vec![iterable, body],
+        Expr::Block(exprs) | Expr::VectorLiteral(exprs) => exprs.iter_mut().collect(),
+        Expr::Let { bindings, body } => {
+            let mut out: Vec<&mut Spanned<Expr>> = bindings.iter_mut().map(|(_, _, e)| e).collect();
+            out.push(body);
+            out
+        }
+        Expr::Assignment { value, .. } => vec![value],
+        Expr::AttributeAssignment { obj, value, .. } => vec![obj, value],
+        Expr::Call { args, .. } | Expr::BaseCall { args } | Expr::Instantiation { args, .. } => {
+            args.iter_mut().collect()
+        }
+        Expr::MethodCall { obj, args, .. } => {
+            let mut out: Vec<&mut Spanned<Expr>> = vec![obj];
+            out.extend(args);
+            out
+        }
+        Expr::AttributeAccess { obj, .. } => vec![obj],
+        Expr::Lambda { body, .. } => vec![body],
+        Expr::VectorGenerator { expr, iterable, .. } => vec![expr, iterable],
+        Expr::Indexing { obj, index } => vec![obj, index],
+        Expr::IndexAssignment { obj, index, value } => vec![obj, index, value],
+        Expr::Slice { obj, start, end } => {
+            let mut out: Vec<&mut Spanned<Expr>> = vec![obj];
+            out.extend(start.as_deref_mut());
+            out.extend(end.as_deref_mut());
+            out
+        }
+        Expr::MapLiteral(entries) => entries.iter_mut().flat_map(|(k, v)| [k, v]).collect(),
+        Expr::Match { expr, cases, default } => {
+            let mut out: Vec<&mut Spanned<Expr>> = vec![expr];
+            for case in cases {
+                out.extend(case.guard.as_mut());
+                out.push(&mut case.expr);
+            }
+            out.extend(default.as_deref_mut());
+            out
+        }
+        Expr::Try { body, catches } => {
+            let mut out: Vec<&mut Spanned<Expr>> = vec![body];
+            out.extend(catches.iter_mut().map(|c| &mut c.body));
+            out
+        }
+        Expr::Number(_) | Expr::String(_) | Expr::Boolean(_) | Expr::Identifier(_) |
+        Expr::Rand | Expr::PI | Expr::E | Expr::Error => Vec::new(),
+    }
+}
+
+/// Variables que enlaza `pattern`, en orden de aparición.
+pub fn pattern_vars(pattern: &Pattern) -> Vec<&str> {
+    match pattern {
+        Pattern::Variable { name, .. } => vec![name.as_str()],
+        Pattern::Binary { left, right, .. } => {
+            let mut out = pattern_vars(left);
+            out.extend(pattern_vars(right));
+            out
+        }
+        Pattern::Unary { operand, .. } => pattern_vars(operand),
+        Pattern::Call { args, .. } => args.iter().flat_map(pattern_vars).collect(),
+        Pattern::Literal(_) | Pattern::Wildcard => Vec::new(),
+    }
+}
+
+/// Expresiones raíz del programa: cuerpos de funciones, métodos y macros,
+/// inicializadores de atributos, argumentos al padre y la expresión principal.
+pub fn root_exprs(program: &Program) -> Vec<&Spanned<Expr>> {
+    let mut out = Vec::new();
+    for decl in &program.declarations {
+        match decl {
+            Declaration::Function(f) => out.push(&f.body),
+            Declaration::Macro(m) => out.push(&m.body),
+            Declaration::Type(t) => {
+                if let Some(parent) = &t.parent {
+                    out.extend(&parent.args);
+                }
+                out.extend(t.attributes.iter().map(|a| &a.init));
+                out.extend(t.methods.iter().map(|m| &m.body));
+            }
+            Declaration::Protocol(_) | Declaration::Variant(_) => {}
+        }
+    }
+    out.push(&program.expr);
+    out
+}
+
+/// Versión mutable de [`root_exprs`].
+pub fn root_exprs_mut(program: &mut Program) -> Vec<&mut Spanned<Expr>> {
+    let mut out = Vec::new();
+    for decl in &mut program.declarations {
+        match decl {
+            Declaration::Function(f) => out.push(&mut f.body),
+            Declaration::Macro(m) => out.push(&mut m.body),
+            Declaration::Type(t) => {
+                if let Some(parent) = &mut t.parent {
+                    out.extend(&mut parent.args);
+                }
+                out.extend(t.attributes.iter_mut().map(|a| &mut a.init));
+                out.extend(t.methods.iter_mut().map(|m| &mut m.body));
+            }
+            Declaration::Protocol(_) | Declaration::Variant(_) => {}
+        }
+    }
+    out.push(&mut program.expr);
+    out
+}
+
+/// Subexpresión más interna de `expr` cuyo rango contiene el byte `offset`.
+///
+/// Los hijos se recorren aunque el padre no contenga `offset`: los accesos
+/// `obj.m(...)`, `obj[i]` y `e is T` empiezan en el operador, no en `obj`.
+pub fn node_at(expr: &Spanned<Expr>, offset: usize) -> Option<&Spanned<Expr>> {
+    children(&expr.node)
+        .into_iter()
+        .find_map(|child| node_at(child, offset))
+        .or_else(|| expr.span().contains(offset).then_some(expr))
+}
@@ -0,0 +1,5 @@
+//! Servidor de lenguaje de HULK: habla LSP por stdin/stdout.
+
+fn main() {
+    std::process::exit(hulk_compiler::lsp::run_stdio());
+}
@@ -2,11 +2,7 @@ use actix_web::{web, App, HttpServer, HttpResponse};
 use actix_files::Files;
 use serde::{Deserialize, Serialize};
 
-use hulk_compiler::parser::Parser;
-use hulk_compiler::macros::expand_macros;
-use hulk_compiler::ast::optimize::optimize_program;
-use hulk_compiler::ast::transform::transform_implicit_functors;
-use hulk_compiler::codegen::{CodeGenerator, llvm_target::LlvmGenerator};
+use hulk_compiler::driver::{self, CompileError};
 
 use std::process::{Command, Stdio};
 use std::time::{Duration, Instant};
@@ -32,55 +28,25 @@ struct RunResponse {
 fn compile_and_run(code: &str) -> RunResponse {
     let start = Instant::now();
 
-    // 1) Parsing
-    let mut parser = Parser::new(code);
-    let program = match parser.parse_program() {
-        Ok(p) => p,
+    // 1) Frontend (prelude, macros, `variant`, chequeo semántico y
+    //    optimización) y generación de LLVM IR: el mismo pipeline que `hulk run`
+    let llvm_code = match driver::compile_to_llvm(code) {
+        Ok(llvm_code) => llvm_code,
         Err(e) => {
-            return RunResponse {
-                success: false,
-                output: String::new(),
-                errors: format!("❌ Error de parsing:\n{}", e),
-                llvm_ir: String::new(),
-                time_ms: start.elapsed().as_millis() as u64,
+            let stage = match e {
+                CompileError::Lex(_) | CompileError::Parse(_) => "Errores de parsing",
+                CompileError::Semantic(_) => "Errores semánticos",
             };
-        }
-    };
-
-    // Expansión de macros
-    let mut expanded = expand_macros(program);
-
-    //  Transformación de implicit functors (ANTES del semantic check)
-    // Usamos un contexto vacío solo para detectar protocolos del AST
-    let temp_ctx = hulk_compiler::semantic::Context::new();
-    transform_implicit_functors(&mut expanded, &temp_ctx);
-
-    //  Chequeo semántico
-    let context = match hulk_compiler::semantic::check_program(&expanded) {
-        Ok(ctx) => ctx,
-        Err(errors) => {
-            let msg = errors
-                .iter()
-                .map(|e| format!("  • {:?}", e))
-                .collect::<Vec<_>>()
-                .join("\n");
             return RunResponse {
                 success: false,
                 output: String::new(),
-                errors: format!("❌ Errores semánticos:\n{}", msg),
+                errors: format!("❌ {}:\n{}", stage, e.render(code, "main.hulk")),
                 llvm_ir: String::new(),
                 time_ms: start.elapsed().as_millis() as u64,
             };
         }
     };
 
-    //  Optimización
-    let optimized = optimize_program(expanded);
-
-    //  Generación LLVM IR
-    let generator = LlvmGenerator;
-    let llvm_code = generator.generate(&optimized, &context);
-
     //  Escribir IR a archivo temporal
     let temp_dir = std::env::temp_dir();
     let ll_path = temp_dir.join("hulk_playground.ll");
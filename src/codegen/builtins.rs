@@ -1,30 +1,53 @@
 use super::context::Ctx;
+use super::exceptions::{runtime_throw, runtime_throw_value};
+use super::gc::{frame_prologue, GC_RAW, GC_VALUES};
+use super::utils::ValTy;
+use crate::errors::runtime::INDEX_MESSAGE;
+use crate::utils::Position;
 use std::collections::HashMap;
 
+/// Bytes de un `%__Vector`: cinco palabras.
+const VECTOR_SIZE: u64 = 40;
+/// Capacidad mínima del array de datos cuando el vector crece.
+const MIN_CAP: u64 = 4;
+/// `printf` del mensaje de `RuntimeError::ReversedSlice`.
+const REVERSED_SLICE_FMT: &str = "slice start %lld is greater than end %lld";
+
 /// Emite el tipo implícito `__Vector` que envuelve un array de doubles.
-/// Estructura: [ type_id (i64), data_ptr (double*), length (double), index (double) ]
+/// Estructura: [ type_id (i64), data_ptr (double*), length (double), index (double), cap (i64) ]
 ///
 /// Métodos:
 /// - size(): Number - retorna el número de elementos
 /// - next(): Boolean - avanza el iterador, retorna true si hay más elementos
-/// - current(): Number - retorna el elemento actual (get_current en el protocolo)
-pub fn emit_vector_type(ctx: &mut Ctx) {
+/// - get_current(): Object - retorna el elemento actual, empaquetado
+/// - push(x), pop(), insert(i, x), remove_at(i) - ver `gen_vector_method`
+///
+/// Para crecer no se usa `@realloc`: el array es un bloque del GC, con su
+/// cabecera delante y enlazado en la lista del heap, así que se asigna uno
+/// nuevo con `@__hulk_gc_alloc` y se copian los elementos; el viejo queda
+/// como basura.
+pub fn emit_vector_type(ctx: &mut Ctx) -> u32 {
     // Asignar type_id único para Vector
     let type_id = ctx.next_type_id;
     ctx.next_type_id += 1;
     
-    // Estructura: %__Vector = type { i64, double*, double, double }
+    // Estructura: %__Vector = type { i64, double*, double, double, i64 }
     //   slot 0: type_id
     //   slot 1: puntero al array de datos (double*)
     //   slot 2: longitud (double)
     //   slot 3: índice de iteración actual (double)
-    ctx.preamble.push_str("%__Vector = type { i64, double*, double, double }\n");
+    //   slot 4: capacidad del array de datos, en elementos (i64)
+    ctx.preamble.push_str("%__Vector = type { i64, double*, double, double, i64 }\n");
     
     // Constructor: __Vector_new(double* data, double length)
     ctx.functions.push_str("\
 define i8* @__Vector_new(double* %data, double %length) {
 entry:
-  %obj = call i8* @malloc(i64 32)
+  %obj = call i8* @__hulk_gc_alloc(i64 ");
+    ctx.functions.push_str(&VECTOR_SIZE.to_string());
+    ctx.functions.push_str(", i32 ");
+    ctx.functions.push_str(&type_id.to_string());
+    ctx.functions.push_str(")
   %ptr = bitcast i8* %obj to %__Vector*
   
   ; Almacenar type_id
@@ -45,6 +68,11 @@ entry:
   %idx_ptr = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 3
   store double -1.0, double* %idx_ptr
   
+  ; El array tiene justo `length` elementos
+  %cap_ptr = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 4
+  %cap = fptosi double %length to i64
+  store i64 %cap, i64* %cap_ptr
+  
   ret i8* %obj
 }
 
@@ -64,7 +92,7 @@ entry:
 
     // Método next(): Boolean
     ctx.functions.push_str("\
-define double @__Vector_next(i8* %self) {
+define i1 @__Vector_next(i8* %self) {
 entry:
   %ptr = bitcast i8* %self to %__Vector*
   
@@ -74,21 +102,21 @@ entry:
   
   ; Incrementar índice
   %next_idx = fadd double %idx, 1.0
-  store double %next_idx, double* %idx_ptr
   
   ; Cargar length
   %len_ptr = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 2
   %len = load double, double* %len_ptr
   
-  ; Comparar: next_idx < len
+  ; Comparar: next_idx < len. Al terminar vuelve a -1 para el próximo recorrido
   %cond = fcmp olt double %next_idx, %len
-  %result = select i1 %cond, double 1.0, double 0.0
-  ret double %result
+  %stored = select i1 %cond, double %next_idx, double -1.0
+  store double %stored, double* %idx_ptr
+  ret i1 %cond
 }
 
 ");
 
-    // Método get_current(): Number (actual value at current index)
+    // Método get_current(): el elemento actual, empaquetado como se guarda en el array
     ctx.functions.push_str("\
 define double @__Vector_get_current(i8* %self) {
 entry:
@@ -111,12 +139,12 @@ entry:
 
 ");
 
+    emit_vector_growth(ctx);
+
     // Registrar en ctx.classes para que sea conocido por el sistema de tipos
     let mut attr_indices = HashMap::new();
     let mut attr_order = Vec::new();
     let mut method_names = HashMap::new();
-    let mut method_ret_types = HashMap::new();
-    let mut attr_types = HashMap::new();
     
     // No tiene atributos accesibles directamente (son internos)
     
@@ -124,10 +152,13 @@ entry:
     method_names.insert("size".to_string(), "@__Vector_size".to_string());
     method_names.insert("next".to_string(), "@__Vector_next".to_string());
     method_names.insert("get_current".to_string(), "@__Vector_get_current".to_string());
+    method_names.insert("current".to_string(), "@__Vector_get_current".to_string());
     
-    method_ret_types.insert("size".to_string(), "Number".to_string());
-    method_ret_types.insert("next".to_string(), "Boolean".to_string());
-    method_ret_types.insert("get_current".to_string(), "Number".to_string());
+    let mut method_sigs = HashMap::new();
+    method_sigs.insert("size".to_string(), (Vec::new(), ValTy::Num));
+    method_sigs.insert("next".to_string(), (Vec::new(), ValTy::Bool));
+    method_sigs.insert("get_current".to_string(), (Vec::new(), ValTy::object()));
+    method_sigs.insert("current".to_string(), (Vec::new(), ValTy::object()));
     
     use super::context::ClassLayout;
     ctx.classes.insert("__Vector".to_string(), ClassLayout {
@@ -136,9 +167,356 @@ entry:
         attr_indices,
         attr_order,
         method_names,
-        method_ret_types,
-        attr_types,
         parent_name: None,
-        total_fields: 4, // type_id + data_ptr + length + index
+        total_fields: 5, // type_id + data_ptr + length + index + cap
+        attr_tys: HashMap::new(),
+        method_sigs,
+        ctor_params: Vec::new(),
+        vtable: Vec::new(), // final: sus métodos se llaman directamente
     });
+    type_id
+}
+
+/// Helpers que modifican el vector o crean uno a partir de otros. No revisan
+/// los índices: `gen_vector_method`, `gen_vector_store` y `gen_vector_slice`
+/// lanzan `IndexError` antes de llamarlos, en el sitio de la llamada.
+fn emit_vector_growth(ctx: &mut Ctx) {
+    // ── @__Vector_reserve(%__Vector*, i64 needed)  ─  sitio para `needed`
+    //    elementos: duplica la capacidad (al menos MIN_CAP) si no alcanza. El
+    //    vector es raíz de quien llama y el array nuevo se guarda en él.
+    ctx.functions.push_str(&format!("\
+define void @__Vector_reserve(%__Vector* %v, i64 %needed) {{
+entry:
+  %cap_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 4
+  %cap = load i64, i64* %cap_p
+  %full = icmp sgt i64 %needed, %cap
+  br i1 %full, label %grow, label %done
+grow:
+  %doubled = shl i64 %cap, 1
+  %small = icmp slt i64 %doubled, {MIN_CAP}
+  %at_least = select i1 %small, i64 {MIN_CAP}, i64 %doubled
+  %short = icmp slt i64 %at_least, %needed
+  %new_cap = select i1 %short, i64 %needed, i64 %at_least
+  %new_bytes = mul i64 %new_cap, 8
+  %new_raw = call i8* @__hulk_gc_alloc(i64 %new_bytes, i32 {GC_VALUES})
+  %len_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 2
+  %len_d = load double, double* %len_p
+  %len = fptosi double %len_d to i64
+  %old_bytes = mul i64 %len, 8
+  %data_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 1
+  %old = load double*, double** %data_p
+  %old_raw = bitcast double* %old to i8*
+  call i8* @memcpy(i8* %new_raw, i8* %old_raw, i64 %old_bytes)
+  %new = bitcast i8* %new_raw to double*
+  store double* %new, double** %data_p
+  store i64 %new_cap, i64* %cap_p
+  br label %done
+done:
+  ret void
+}}
+
+"));
+
+    // ── @__Vector_push(i8*, double)  ─  agrega al final
+    ctx.functions.push_str("\
+define void @__Vector_push(i8* %self, double %x) {
+entry:
+  %v = bitcast i8* %self to %__Vector*
+  %len_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 2
+  %len_d = load double, double* %len_p
+  %len = fptosi double %len_d to i64
+  %after = add i64 %len, 1
+  call void @__Vector_reserve(%__Vector* %v, i64 %after)
+  %data_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 1
+  %data = load double*, double** %data_p
+  %slot = getelementptr double, double* %data, i64 %len
+  store double %x, double* %slot
+  %after_d = fadd double %len_d, 1.0
+  store double %after_d, double* %len_p
+  ret void
+}
+
+");
+
+    // ── @__Vector_insert(i8*, i64, double)  ─  corre los siguientes un lugar
+    ctx.functions.push_str("\
+define void @__Vector_insert(i8* %self, i64 %i, double %x) {
+entry:
+  %v = bitcast i8* %self to %__Vector*
+  %len_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 2
+  %len_d = load double, double* %len_p
+  %len = fptosi double %len_d to i64
+  %after = add i64 %len, 1
+  call void @__Vector_reserve(%__Vector* %v, i64 %after)
+  %data_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 1
+  %data = load double*, double** %data_p
+  %from = getelementptr double, double* %data, i64 %i
+  %to = getelementptr double, double* %from, i64 1
+  %from_raw = bitcast double* %from to i8*
+  %to_raw = bitcast double* %to to i8*
+  %moved = sub i64 %len, %i
+  %bytes = mul i64 %moved, 8
+  call i8* @memmove(i8* %to_raw, i8* %from_raw, i64 %bytes)
+  store double %x, double* %from
+  %after_d = fadd double %len_d, 1.0
+  store double %after_d, double* %len_p
+  ret void
+}
+
+");
+
+    // ── @__Vector_remove_at(i8*, i64) -> double  ─  corre los siguientes hacia
+    //    atrás; el slot que queda libre se limpia para no retener su valor
+    ctx.functions.push_str("\
+define double @__Vector_remove_at(i8* %self, i64 %i) {
+entry:
+  %v = bitcast i8* %self to %__Vector*
+  %len_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 2
+  %len_d = load double, double* %len_p
+  %len = fptosi double %len_d to i64
+  %data_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 1
+  %data = load double*, double** %data_p
+  %at = getelementptr double, double* %data, i64 %i
+  %x = load double, double* %at
+  %next = getelementptr double, double* %at, i64 1
+  %at_raw = bitcast double* %at to i8*
+  %next_raw = bitcast double* %next to i8*
+  %last = sub i64 %len, 1
+  %moved = sub i64 %last, %i
+  %bytes = mul i64 %moved, 8
+  call i8* @memmove(i8* %at_raw, i8* %next_raw, i64 %bytes)
+  %freed = getelementptr double, double* %data, i64 %last
+  store double 0.0, double* %freed
+  %last_d = fsub double %len_d, 1.0
+  store double %last_d, double* %len_p
+  ret double %x
+}
+
+");
+
+    // ── @__Vector_slice(i8*, i64 a, i64 b) -> i8*  ─  copia de [a, b)
+    ctx.functions.push_str(&format!("\
+define i8* @__Vector_slice(i8* %self, i64 %a, i64 %b) {{
+entry:
+{prologue}  %count = sub i64 %b, %a
+  %bytes = mul i64 %count, 8
+  %data_raw = call i8* @__hulk_gc_alloc(i64 %bytes, i32 {GC_VALUES})
+  %data_root = bitcast double* %gc.s0 to i8**
+  store i8* %data_raw, i8** %data_root
+  %v = bitcast i8* %self to %__Vector*
+  %src_p = getelementptr inbounds %__Vector, %__Vector* %v, i32 0, i32 1
+  %src = load double*, double** %src_p
+  %from = getelementptr double, double* %src, i64 %a
+  %from_raw = bitcast double* %from to i8*
+  call i8* @memcpy(i8* %data_raw, i8* %from_raw, i64 %bytes)
+  %data = bitcast i8* %data_raw to double*
+  %len = sitofp i64 %count to double
+  %vec = call i8* @__Vector_new(double* %data, double %len)
+  store i8* %gc.prev, i8** @__gc_frames
+  ret i8* %vec
+}}
+
+", prologue = frame_prologue(1)));
+
+    // ── @__Vector_slice_message(i64 a, i64 b) -> i8*  ─  mensaje del `IndexError`
+    //    de `v[a:b]` con `a > b`, el mismo que da el intérprete
+    ctx.globals.push_str(&format!(
+        "@.slice_fmt = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+        REVERSED_SLICE_FMT.len() + 1, REVERSED_SLICE_FMT
+    ));
+    ctx.functions.push_str(&format!("\
+define i8* @__Vector_slice_message(i64 %a, i64 %b) {{
+entry:
+  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* null, i64 0, i8* {fmt}, i64 %a, i64 %b)
+  %len64 = sext i32 %len to i64
+  %size = add i64 %len64, 1
+  %msg = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  call i32 (i8*, i64, i8*, ...) @snprintf(i8* %msg, i64 %size, i8* {fmt}, i64 %a, i64 %b)
+  ret i8* %msg
+}}
+
+", fmt = ctx.gep_string("@.slice_fmt", REVERSED_SLICE_FMT.len() + 1)));
+
+    // ── @__Vector_concat(i8*, i8*) -> i8*  ─  vector nuevo con los elementos
+    //    de los dos; quien llama mantiene ambos como raíces
+    ctx.functions.push_str(&format!("\
+define i8* @__Vector_concat(i8* %left, i8* %right) {{
+entry:
+{prologue}  %l = bitcast i8* %left to %__Vector*
+  %r = bitcast i8* %right to %__Vector*
+  %l_len_p = getelementptr inbounds %__Vector, %__Vector* %l, i32 0, i32 2
+  %l_len_d = load double, double* %l_len_p
+  %l_len = fptosi double %l_len_d to i64
+  %r_len_p = getelementptr inbounds %__Vector, %__Vector* %r, i32 0, i32 2
+  %r_len_d = load double, double* %r_len_p
+  %r_len = fptosi double %r_len_d to i64
+  %count = add i64 %l_len, %r_len
+  %bytes = mul i64 %count, 8
+  %data_raw = call i8* @__hulk_gc_alloc(i64 %bytes, i32 {GC_VALUES})
+  %data_root = bitcast double* %gc.s0 to i8**
+  store i8* %data_raw, i8** %data_root
+  %l_data_p = getelementptr inbounds %__Vector, %__Vector* %l, i32 0, i32 1
+  %l_data = load double*, double** %l_data_p
+  %l_raw = bitcast double* %l_data to i8*
+  %l_bytes = mul i64 %l_len, 8
+  call i8* @memcpy(i8* %data_raw, i8* %l_raw, i64 %l_bytes)
+  %r_data_p = getelementptr inbounds %__Vector, %__Vector* %r, i32 0, i32 1
+  %r_data = load double*, double** %r_data_p
+  %r_raw = bitcast double* %r_data to i8*
+  %tail = getelementptr i8, i8* %data_raw, i64 %l_bytes
+  %r_bytes = mul i64 %r_len, 8
+  call i8* @memcpy(i8* %tail, i8* %r_raw, i64 %r_bytes)
+  %data = bitcast i8* %data_raw to double*
+  %len = sitofp i64 %count to double
+  %vec = call i8* @__Vector_new(double* %data, double %len)
+  store i8* %gc.prev, i8** @__gc_frames
+  ret i8* %vec
+}}
+
+", prologue = frame_prologue(1)));
+}
+
+/// Representación de los argumentos y del retorno de los métodos de
+/// `Vector<T>` que no pasan por la tabla de la clase `__Vector`.
+pub fn vector_method_sig(method: &str, elem: &ValTy) -> Option<(Vec<ValTy>, ValTy)> {
+    let sig = match method {
+        "push" => (vec![elem.clone()], elem.clone()),
+        "pop" => (vec![], elem.clone()),
+        "insert" => (vec![ValTy::Num, elem.clone()], elem.clone()),
+        "remove_at" => (vec![ValTy::Num], elem.clone()),
+        _ => return None,
+    };
+    Some(sig)
+}
+
+/// Longitud del vector `v` como `i64`.
+fn vector_len(ctx: &mut Ctx, v: &str) -> String {
+    let vp = ctx.tmp();
+    ctx.emit(&format!("{} = bitcast i8* {} to %__Vector*", vp, v));
+    let len_p = ctx.tmp();
+    ctx.emit(&format!("{} = getelementptr inbounds %__Vector, %__Vector* {}, i32 0, i32 2", len_p, vp));
+    let len_d = ctx.tmp();
+    ctx.emit(&format!("{} = load double, double* {}", len_d, len_p));
+    let len = ctx.tmp();
+    ctx.emit(&format!("{} = fptosi double {} to i64", len, len_d));
+    len
+}
+
+/// Lanza `IndexError` si `i` (un `i64`) no está en `[0, limit)`.
+fn check_index(ctx: &mut Ctx, i: &str, limit: &str, pos: Position) {
+    let neg = ctx.tmp();
+    ctx.emit(&format!("{} = icmp slt i64 {}, 0", neg, i));
+    let over = ctx.tmp();
+    ctx.emit(&format!("{} = icmp sge i64 {}, {}", over, i, limit));
+    let oob = ctx.tmp();
+    ctx.emit(&format!("{} = or i1 {}, {}", oob, neg, over));
+    let ok = ctx.lbl("idx_ok");
+    let fail = ctx.lbl("idx_oob");
+    ctx.emit(&format!("br i1 {}, label %{}, label %{}", oob, fail, ok));
+    ctx.emit_label(&fail);
+    runtime_throw(ctx, "IndexError", INDEX_MESSAGE, pos);
+    ctx.emit_label(&ok);
+}
+
+/// Puntero al elemento `index` (un `double`) de `v`, tras revisar los límites.
+pub fn gen_element_ptr(ctx: &mut Ctx, v: &str, index: &str, pos: Position) -> String {
+    let len = vector_len(ctx, v);
+    let i = ctx.tmp();
+    ctx.emit(&format!("{} = fptosi double {} to i64", i, index));
+    check_index(ctx, &i, &len, pos);
+    let vp = ctx.tmp();
+    ctx.emit(&format!("{} = bitcast i8* {} to %__Vector*", vp, v));
+    let data_p = ctx.tmp();
+    ctx.emit(&format!("{} = getelementptr inbounds %__Vector, %__Vector* {}, i32 0, i32 1", data_p, vp));
+    let data = ctx.tmp();
+    ctx.emit(&format!("{} = load double*, double** {}", data, data_p));
+    let elem = ctx.tmp();
+    ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", elem, data, i));
+    elem
+}
+
+/// Llamada `v.method(args)` a uno de los métodos que modifican el vector.
+pub fn gen_vector_method(ctx: &mut Ctx, v: &str, elem: &ValTy, method: &str, args: &[(String, ValTy)], pos: Position) -> (String, ValTy) {
+    let Some((params, ret)) = vector_method_sig(method, elem) else {
+        ctx.emit(&format!("; WARNING: Vector has no method '{}'", method));
+        return ("0.0".into(), ValTy::Num);
+    };
+    ctx.root_ptr(v, "i8*");
+    let mut boxed = Vec::new();
+    for ((a, ty), param) in args.iter().zip(&params) {
+        let a = ctx.coerce(a, ty, param);
+        ctx.root(&a, param);
+        boxed.push((a.clone(), ctx.box_value(&a, param)));
+    }
+
+    match method {
+        "push" => {
+            ctx.emit(&format!("call void @__Vector_push(i8* {}, double {})", v, boxed[0].1));
+            return (boxed[0].0.clone(), ret);
+        }
+        "insert" => {
+            // Se puede insertar también al final
+            let len = vector_len(ctx, v);
+            let limit = ctx.tmp();
+            ctx.emit(&format!("{} = add i64 {}, 1", limit, len));
+            let i = ctx.tmp();
+            ctx.emit(&format!("{} = fptosi double {} to i64", i, boxed[0].0));
+            check_index(ctx, &i, &limit, pos);
+            ctx.emit(&format!("call void @__Vector_insert(i8* {}, i64 {}, double {})", v, i, boxed[1].1));
+            return (boxed[1].0.clone(), ret);
+        }
+        _ => {}
+    }
+
+    // `pop` saca el último; los dos revisan que el índice exista
+    let len = vector_len(ctx, v);
+    let i = if method == "pop" {
+        let last = ctx.tmp();
+        ctx.emit(&format!("{} = sub i64 {}, 1", last, len));
+        last
+    } else {
+        let i = ctx.tmp();
+        ctx.emit(&format!("{} = fptosi double {} to i64", i, boxed[0].0));
+        i
+    };
+    check_index(ctx, &i, &len, pos);
+    let r = ctx.tmp();
+    ctx.emit(&format!("{} = call double @__Vector_remove_at(i8* {}, i64 {})", r, v, i));
+    (ctx.unbox(&r, elem), ret)
+}
+
+/// `v[a:b]`: exige `0 <= a <= b <= v.size()`; los límites que faltan son el
+/// principio y el final.
+pub fn gen_vector_slice(ctx: &mut Ctx, v: &str, start: Option<&str>, end: Option<&str>, pos: Position) -> String {
+    let len = vector_len(ctx, v);
+    let bound = |ctx: &mut Ctx, b: Option<&str>, default: &str| match b {
+        Some(b) => {
+            let i = ctx.tmp();
+            ctx.emit(&format!("{} = fptosi double {} to i64", i, b));
+            i
+        }
+        None => default.to_string(),
+    };
+    let a = bound(ctx, start, "0");
+    let b = bound(ctx, end, &len);
+    // a y b en [0, len + 1), y a <= b
+    let len_limit = ctx.tmp();
+    ctx.emit(&format!("{} = add i64 {}, 1", len_limit, len));
+    check_index(ctx, &a, &len_limit, pos);
+    check_index(ctx, &b, &len_limit, pos);
+    let reversed = ctx.tmp();
+    ctx.emit(&format!("{} = icmp sgt i64 {}, {}", reversed, a, b));
+    let ok = ctx.lbl("slice_ok");
+    let fail = ctx.lbl("slice_reversed");
+    ctx.emit(&format!("br i1 {}, label %{}, label %{}", reversed, fail, ok));
+    ctx.emit_label(&fail);
+    let msg = ctx.tmp();
+    ctx.emit(&format!("{} = call i8* @__Vector_slice_message(i64 {}, i64 {})", msg, a, b));
+    // Construir el error asigna: el mensaje queda como raíz
+    ctx.root_ptr(&msg, "i8*");
+    runtime_throw_value(ctx, "IndexError", &msg, pos);
+    ctx.emit_label(&ok);
+    let r = ctx.tmp();
+    ctx.emit(&format!("{} = call i8* @__Vector_slice(i8* {}, i64 {}, i64 {})", r, v, a, b));
+    r
 }
@@ -1,8 +1,7 @@
 use std::collections::HashMap;
-use crate::ast::nodes::{TypeDecl, TypeAnnotation, Expr};
-use crate::utils::Spanned;
-use super::context::Ctx;
-use super::utils::{val_ty_from_annotation};
+use crate::ast::nodes::TypeDecl;
+use super::context::{ClassLayout, Ctx, GenExpr};
+use super::utils::ValTy;
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Ordenamiento topológico de clases para emitir padres antes que hijos
@@ -42,14 +41,20 @@ pub fn topo_sort_classes(
 // Emisión de Clases / Tipos  (con soporte de herencia profunda)
 // ─────────────────────────────────────────────────────────────────────────────
 
-pub fn emit_class(ctx: &mut Ctx, td: &TypeDecl, gen_expr: &dyn Fn(&mut Ctx, &Spanned<Expr>) -> String, infer_return_type_from_body: &dyn Fn(&Expr, &HashMap<String, String>) -> Option<String>) {
+/// Registra el layout de una clase: índices y representación de los atributos,
+/// struct LLVM y firmas de métodos y constructor. Se registran todas las clases
+/// (padres primero) antes de emitir cualquier cuerpo, para que las llamadas entre
+/// clases conozcan las firmas.
+pub fn register_class(ctx: &mut Ctx, td: &TypeDecl) {
     // ──  Recopilar atributos + métodos heredados del padre ────────────
     let mut attr_indices: HashMap<String, u32> = HashMap::new();
     let mut attr_order: Vec<String> = Vec::new();
     let mut method_names: HashMap<String, String> = HashMap::new();
-    let mut attr_types: HashMap<String, String> = HashMap::new();
+    let mut attr_tys: HashMap<String, ValTy> = HashMap::new();
+    let mut method_sigs: HashMap<String, (Vec<ValTy>, ValTy)> = HashMap::new();
+    let mut vtable: Vec<String> = Vec::new();
     let mut parent_name: Option<String> = None;
-    let mut idx: u32 = 1; // slot 0 = type-id (i32, rellenado a 8 bytes vía store i64)
+    let mut idx: u32 = 2; // slot 0 = type-id (i32, rellenado a 8 bytes vía store i64), slot 1 = vtable
 
     if let Some(ref pi) = td.parent {
         parent_name = Some(pi.name.clone());
@@ -65,107 +70,119 @@ pub fn emit_class(ctx: &mut Ctx, td: &TypeDecl, gen_expr: &dyn Fn(&mut Ctx, &Spa
             for (mname, mfn) in &parent_layout.method_names {
                 method_names.insert(mname.clone(), mfn.clone());
             }
-            // Heredar tipos de atributos
-            for (aname, atype) in &parent_layout.attr_types {
-                attr_types.insert(aname.clone(), atype.clone());
-            }
+            attr_tys.extend(parent_layout.attr_tys.clone());
+            method_sigs.extend(parent_layout.method_sigs.clone());
+            vtable = parent_layout.vtable.clone();
         }
     }
 
-    // Atributos propios
-    // Construir mapa de tipos de parámetros: nombre_param -> nombre_tipo  (ej., "name" -> "String")
-    let param_types: HashMap<String, String> = td.params.iter().filter_map(|p| {
-        if let Some(TypeAnnotation::Name(ref tn)) = p.type_annotation {
-            Some((p.name.clone(), tn.clone()))
-        } else {
-            None
-        }
-    }).collect();
+    let sem_ty = ctx.sem.types.get(&td.name).cloned();
 
+    // Atributos propios
     for attr in &td.attributes {
         if !attr_indices.contains_key(&attr.name) {
             attr_indices.insert(attr.name.clone(), idx);
             attr_order.push(attr.name.clone());
             idx += 1;
         }
-        // Inferir tipo de atributo desde:
-        //  Anotación de tipo explícita del atributo
-        //  Expresión de inicialización referenciando un parámetro tipado del constructor
-        //  Tipo literal de la expresión de inicialización
-        if !attr_types.contains_key(&attr.name) {
-            if let Some(TypeAnnotation::Name(ref tn)) = attr.type_annotation {
-                attr_types.insert(attr.name.clone(), tn.clone());
-            } else if let Expr::Identifier(ref pname) = attr.init.node {
-                if let Some(tn) = param_types.get(pname) {
-                    attr_types.insert(attr.name.clone(), tn.clone());
-                }
-            } else if matches!(attr.init.node, Expr::String(_)) {
-                attr_types.insert(attr.name.clone(), "String".to_string());
-            } else if matches!(attr.init.node, Expr::Number(_)) {
-                attr_types.insert(attr.name.clone(), "Number".to_string());
-            } else if matches!(attr.init.node, Expr::Boolean(_)) {
-                attr_types.insert(attr.name.clone(), "Boolean".to_string());
-            }
-        }
+        // La representación del campo sale del tipo semántico del atributo
+        attr_tys.entry(attr.name.clone()).or_insert_with(|| {
+            sem_ty.as_ref()
+                .and_then(|t| t.borrow().get_attribute(&attr.name))
+                .map_or_else(ValTy::object, |t| ValTy::from_type(&t.borrow()))
+        });
     }
 
-    //  Tipo struct { i64 typeid, double, double, ... } 
+    //  Tipo struct { i64 typeid, <un campo de 8 bytes por atributo> }
     let struct_name = format!("%T.{}", td.name);
-    let mut fields = "i64".to_string(); // slot 0 = id de tipo
-    for _ in 1..idx { fields.push_str(", double"); }
+    let mut fields = "i64, i8**".to_string(); // slot 0 = id de tipo, slot 1 = vtable
+    for attr in &attr_order {
+        fields.push_str(", ");
+        fields.push_str(attr_tys[attr].field_llvm());
+    }
 
     ctx.globals.push_str(&format!("{} = type {{ {} }}\n", struct_name, fields));
 
-    // Registrar métodos propios (sobreescribir heredados) 
-    let mut method_ret_types: HashMap<String, String> = HashMap::new();
-    // Heredar tipos de retorno de métodos del padre
-    if let Some(ref pi) = td.parent {
-        if let Some(parent_layout) = ctx.classes.get(&pi.name) {
-            for (mname, mret) in &parent_layout.method_ret_types {
-                method_ret_types.insert(mname.clone(), mret.clone());
-            }
-        }
-    }
+    // Registrar métodos propios (sobreescribir heredados)
     for m in &td.methods {
         method_names.insert(m.name.clone(), format!("@{}_{}", td.name, m.name));
-        // Inferir tipo de retorno del método desde anotación explícita o heurística del cuerpo
-        if let Some(TypeAnnotation::Name(ref tn)) = m.return_type {
-            method_ret_types.insert(m.name.clone(), tn.clone());
-        } else {
-            // Intentar inferir desde la expresión del cuerpo del método
-            if let Some(rt) = infer_return_type_from_body(&m.body.node, &attr_types) {
-                method_ret_types.insert(m.name.clone(), rt);
-            }
+        // Un método nuevo ocupa la siguiente entrada; uno que sobrescribe, la del padre
+        if !vtable.contains(&m.name) {
+            vtable.push(m.name.clone());
         }
+        // Un método que sobrescribe conserva la firma del padre
+        method_sigs.entry(m.name.clone()).or_insert_with(|| {
+            match sem_ty.as_ref().and_then(|t| t.borrow().methods.get(&m.name).cloned()) {
+                Some(info) => (
+                    info.params.iter().map(|(_, t)| ValTy::from_type(&t.borrow())).collect(),
+                    ValTy::from_type(&info.return_type.borrow()),
+                ),
+                None => (vec![ValTy::object(); m.params.len()], ValTy::object()),
+            }
+        });
     }
 
+    // Parámetros del constructor, con los tipos del chequeo semántico
+    let sem_params: Vec<ValTy> = sem_ty.as_ref()
+        .map(|t| t.borrow().params.iter().map(|(_, t)| ValTy::from_type(&t.borrow())).collect())
+        .unwrap_or_default();
+    let ctor_params = if sem_params.len() == td.params.len() {
+        sem_params
+    } else {
+        vec![ValTy::object(); td.params.len()]
+    };
+
     let type_id = ctx.alloc_type_id();
 
-    use super::context::ClassLayout;
     ctx.classes.insert(td.name.clone(), ClassLayout {
-        struct_name: struct_name.clone(),
+        struct_name,
         type_id,
-        attr_indices: attr_indices.clone(),
-        attr_order: attr_order.clone(),
-        method_names: method_names.clone(),
-        parent_name: parent_name.clone(),
+        attr_indices,
+        attr_order,
+        method_names,
+        parent_name,
         total_fields: idx,
-        attr_types: attr_types.clone(),
-        method_ret_types,
+        attr_tys,
+        method_sigs,
+        ctor_params,
+        vtable,
     });
+}
+
+/// Emite el constructor y los métodos de una clase ya registrada.
+pub fn emit_class(ctx: &mut Ctx, td: &TypeDecl, gen_expr: &GenExpr) {
+    let layout = &ctx.classes[&td.name];
+    let struct_name = layout.struct_name.clone();
+    let type_id = layout.type_id;
+    let total_fields = layout.total_fields;
+    let attr_indices = layout.attr_indices.clone();
+    let attr_tys = layout.attr_tys.clone();
+    let ctor_params = layout.ctor_params.clone();
+    let method_sigs = layout.method_sigs.clone();
 
-    //  Emitir constructor: @Tipo_new(args...) -> i8* 
+    // Vtable: una entrada por método (heredados primero), con la implementación
+    // que ve esta clase. Todas las implementaciones de un nombre comparten firma.
+    let entries: Vec<String> = layout.vtable.iter().map(|m| {
+        let (params, ret) = &layout.method_sigs[m];
+        let param_tys: Vec<&str> = std::iter::once("i8*").chain(params.iter().map(|p| p.llvm())).collect();
+        format!("i8* bitcast ({} ({})* {} to i8*)", ret.llvm(), param_tys.join(", "), layout.method_names[m])
+    }).collect();
+    let vtable_len = entries.len();
+    ctx.globals.push_str(&format!("@{}.vtable = private unnamed_addr constant [{} x i8*] [{}]\n",
+        td.name, vtable_len, entries.join(", ")));
+    let vtable_ptr = format!("getelementptr inbounds ([{n} x i8*], [{n} x i8*]* @{}.vtable, i64 0, i64 0)", td.name, n = vtable_len);
+
+    //  Emitir constructor: @Tipo_new(args...) -> i8*
     {
-        let mut sig = String::new();
-        for (i, p) in td.params.iter().enumerate() {
-            if i > 0 { sig.push_str(", "); }
-            sig.push_str(&format!("double %{}", p.name));
-        }
-        ctx.functions.push_str(&format!("define i8* @{}_new({}) {{\nentry:\n", td.name, sig));
+        let sig: Vec<String> = td.params.iter().zip(&ctor_params)
+            .map(|(p, ty)| format!("{} %{}", ty.llvm(), p.name))
+            .collect();
+        let outer = ctx.begin_function(&format!("define i8* @{}_new({})", td.name, sig.join(", ")), ValTy::Obj(td.name.clone()));
 
-        let sz = idx as u64 * 8;
+        let sz = total_fields as u64 * 8;
         let raw = ctx.tmp();
-        ctx.emit(&format!("{} = call i8* @malloc(i64 {})", raw, sz));
+        ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", raw, sz, type_id));
+        ctx.root_ptr(&raw, "i8*");
 
         let typed = ctx.tmp();
         ctx.emit(&format!("{} = bitcast i8* {} to {}*", typed, raw, struct_name));
@@ -176,97 +193,107 @@ pub fn emit_class(ctx: &mut Ctx, td: &TypeDecl, gen_expr: &dyn Fn(&mut Ctx, &Spa
             gep_tid, struct_name, struct_name, typed));
         ctx.emit(&format!("store i64 {}, i64* {}", type_id, gep_tid));
 
+        // Almacenar la vtable en slot 1
+        let gep_vt = ctx.tmp();
+        ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 1",
+            gep_vt, struct_name, struct_name, typed));
+        ctx.emit(&format!("store i8** {}, i8*** {}", vtable_ptr, gep_vt));
+
         // Parámetros del constructor en el ámbito
         ctx.enter_scope();
-        for p in &td.params {
-            let ptr = ctx.tmp();
-            ctx.emit(&format!("{} = alloca double", ptr));
-            ctx.emit(&format!("store double %{}, double* {}", p.name, ptr));
-            ctx.def_var(&p.name, &ptr, val_ty_from_annotation(&p.type_annotation));
+        for (p, ty) in td.params.iter().zip(&ctor_params) {
+            ctx.alloca_var(&p.name, &format!("%{}", p.name), ty.clone());
         }
 
         // Si el padre tiene constructor y se dan args de inicialización del padre, llamar
         // al constructor del padre para inicializar atributos heredados.
-        if let Some(ref pi) = td.parent {
-            if ctx.classes.contains_key(&pi.name) {
-                // Evaluar args del constructor padre
-                let mut parent_vals = Vec::new();
-                for a in &pi.args {
-                    parent_vals.push(gen_expr(ctx, a));
-                }
-                // Llamar al constructor padre para obtener un obj padre temporal, luego copiar sus atributos
-                let mut parent_arg_s = String::new();
-                for (i, v) in parent_vals.iter().enumerate() {
-                    if i > 0 { parent_arg_s.push_str(", "); }
-                    parent_arg_s.push_str("double "); parent_arg_s.push_str(v);
-                }
-                let parent_raw = ctx.tmp();
-                ctx.emit(&format!("{} = call i8* @{}_new({})", parent_raw, pi.name, parent_arg_s));
-
-                // Copiar atributos del padre desde parent_raw a nuestro objeto
-                let parent_sn = ctx.classes.get(&pi.name).map(|l| l.struct_name.clone())
-                    .unwrap_or_default();
-                let parent_typed = ctx.tmp();
-                ctx.emit(&format!("{} = bitcast i8* {} to {}*", parent_typed, parent_raw, parent_sn));
-
-                if let Some(parent_layout) = ctx.classes.get(&pi.name) {
-                    let parent_attr_order = parent_layout.attr_order.clone();
-                    let parent_attr_indices = parent_layout.attr_indices.clone();
-                    let parent_sn2 = parent_layout.struct_name.clone();
-                    for attr_name in &parent_attr_order {
-                        let pidx = parent_attr_indices[attr_name];
-                        let gep_src = ctx.tmp();
-                        ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
-                            gep_src, parent_sn2, parent_sn2, parent_typed, pidx));
-                        let val = ctx.tmp();
-                        ctx.emit(&format!("{} = load double, double* {}", val, gep_src));
-                        // Almacenar en el hijo con el mismo índice
-                        let child_idx = attr_indices[attr_name];
-                        let gep_dst = ctx.tmp();
-                        ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
-                            gep_dst, struct_name, struct_name, typed, child_idx));
-                        ctx.emit(&format!("store double {}, double* {}", val, gep_dst));
-                    }
-                }
+        if let Some(ref pi) = td.parent
+            && let Some(parent_layout) = ctx.classes.get(&pi.name)
+        {
+            let parent_params = parent_layout.ctor_params.clone();
+            let parent_sn = parent_layout.struct_name.clone();
+            let parent_attr_order = parent_layout.attr_order.clone();
+            let parent_attr_indices = parent_layout.attr_indices.clone();
+            let parent_attr_tys = parent_layout.attr_tys.clone();
+
+            // Evaluar args del constructor padre
+            let mut parent_vals = Vec::new();
+            for a in &pi.args {
+                parent_vals.push(gen_expr(ctx, a));
+            }
+            // Llamar al constructor padre para obtener un obj padre temporal, luego copiar sus atributos
+            let mut parent_args = Vec::new();
+            for (i, (v, ty)) in parent_vals.iter().enumerate() {
+                let pty = parent_params.get(i).cloned().unwrap_or_else(ValTy::object);
+                let c = ctx.coerce(v, ty, &pty);
+                parent_args.push(format!("{} {}", pty.llvm(), c));
+            }
+            let parent_raw = ctx.tmp();
+            ctx.emit(&format!("{} = call i8* @{}_new({})", parent_raw, pi.name, parent_args.join(", ")));
+
+            // Copiar atributos del padre desde parent_raw a nuestro objeto
+            let parent_typed = ctx.tmp();
+            ctx.emit(&format!("{} = bitcast i8* {} to {}*", parent_typed, parent_raw, parent_sn));
+
+            for attr_name in &parent_attr_order {
+                let pidx = parent_attr_indices[attr_name];
+                let gep_src = ctx.tmp();
+                ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
+                    gep_src, parent_sn, parent_sn, parent_typed, pidx));
+                let val = ctx.load_field(&gep_src, &parent_attr_tys[attr_name]);
+                // Almacenar en el hijo con el mismo índice
+                let child_idx = attr_indices[attr_name];
+                let gep_dst = ctx.tmp();
+                ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
+                    gep_dst, struct_name, struct_name, typed, child_idx));
+                ctx.store_field(&val, &gep_dst, &attr_tys[attr_name]);
             }
         }
 
         // Inicializar atributos propios
         for attr in &td.attributes {
-            let val = gen_expr(ctx, &attr.init);
+            let (val, ty) = gen_expr(ctx, &attr.init);
+            let field_ty = &attr_tys[&attr.name];
+            let val = ctx.coerce(&val, &ty, field_ty);
             let i = attr_indices[&attr.name];
             let gep = ctx.tmp();
             ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
                 gep, struct_name, struct_name, typed, i));
-            ctx.emit(&format!("store double {}, double* {}", val, gep));
+            ctx.store_field(&val, &gep, field_ty);
         }
 
         ctx.exit_scope();
-        ctx.emit(&format!("ret i8* {}", raw));
-        ctx.functions.push_str("}\n\n");
+        ctx.end_function(&format!("ret i8* {}", raw), outer);
     }
 
-    // Emitir métodos 
+    // Emitir métodos
     ctx.current_class = Some(td.name.clone());
     for m in &td.methods {
         let fname = format!("{}_{}", td.name, m.name);
+        let (abi_params, abi_ret) = method_sigs[&m.name].clone();
         let mut sig = String::from("i8* %self");
-        for p in &m.params {
-            sig.push_str(&format!(", double %{}", p.name));
+        for (i, p) in m.params.iter().enumerate() {
+            let ty = abi_params.get(i).cloned().unwrap_or_else(ValTy::object);
+            sig.push_str(&format!(", {} %{}", ty.llvm(), p.name));
         }
-        ctx.functions.push_str(&format!("define double @{}({}) {{\nentry:\n", fname, sig));
+        let outer = ctx.begin_function(&format!("define {} @{}({})", abi_ret.llvm(), fname, sig), abi_ret.clone());
         ctx.enter_scope();
 
-        for p in &m.params {
-            let ptr = ctx.tmp();
-            ctx.emit(&format!("{} = alloca double", ptr));
-            ctx.emit(&format!("store double %{}, double* {}", p.name, ptr));
-            ctx.def_var(&p.name, &ptr, val_ty_from_annotation(&p.type_annotation));
+        // Cada parámetro pasa de la firma heredada a su tipo propio (anotado o inferido)
+        let own_params: Vec<ValTy> = ctx.sem.types.get(&td.name)
+            .and_then(|t| t.borrow().methods.get(&m.name).cloned())
+            .map(|info| info.params.iter().map(|(_, t)| ValTy::from_type(&t.borrow())).collect())
+            .unwrap_or_default();
+        for (i, p) in m.params.iter().enumerate() {
+            let abi = abi_params.get(i).cloned().unwrap_or_else(ValTy::object);
+            let own = own_params.get(i).cloned().unwrap_or_else(|| ValTy::from_annotation(ctx.sem, &p.type_annotation));
+            let v = ctx.coerce(&format!("%{}", p.name), &abi, &own);
+            ctx.alloca_var(&p.name, &v, own);
         }
 
-        let res = gen_expr(ctx, &m.body);
-        ctx.emit(&format!("ret double {}", res));
-        ctx.functions.push_str("}\n\n");
+        let (res, ty) = gen_expr(ctx, &m.body);
+        let res = ctx.coerce(&res, &ty, &abi_ret);
+        ctx.end_function(&format!("ret {} {}", abi_ret.llvm(), res), outer);
         ctx.exit_scope();
     }
     ctx.current_class = None;
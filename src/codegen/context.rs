@@ -1,6 +1,10 @@
+use crate::ast::nodes::Expr;
 use crate::semantic::Context as SemanticContext;
+use crate::utils::Spanned;
 use std::collections::HashMap;
-use super::utils::{escape_llvm, ValTy};
+use super::exceptions::Handler;
+use super::gc;
+use super::utils::{box_tag, escape_llvm, ValTy, BOX_BOOL, BOX_PAYLOAD_MASK};
 
 /// Información completa del layout de una clase, incluyendo atributos/métodos heredados.
 #[allow(dead_code)]
@@ -15,13 +19,58 @@ pub struct ClassLayout {
     pub method_names: HashMap<String, String>,
     /// nombre de la clase padre (si existe)
     pub parent_name: Option<String>,
-    pub total_fields: u32,                      // incluyendo type-id en slot 0
-    /// Nombre de atributo -> nombre de tipo declarado (ej. "String", "Number", "Boolean")
-    pub attr_types: HashMap<String, String>,
-    /// nombre_método -> tipo de retorno ("Number", "String", "Boolean", o nombre de clase)
-    pub method_ret_types: HashMap<String, String>,
+    pub total_fields: u32,                      // incluyendo type-id (slot 0) y vtable (slot 1)
+    /// Representación de cada atributo en el struct
+    pub attr_tys: HashMap<String, ValTy>,
+    /// nombre_método -> (parámetros, retorno) de la función LLVM, sin `self`.
+    /// Un método que sobrescribe conserva la firma del que declaró el nombre primero.
+    pub method_sigs: HashMap<String, (Vec<ValTy>, ValTy)>,
+    /// Parámetros del constructor `@Clase_new`
+    pub ctor_params: Vec<ValTy>,
+    /// Métodos en el orden de la vtable (`@Clase.vtable`), heredados primero.
+    /// Una clase que sobrescribe un método reutiliza la entrada del padre.
+    pub vtable: Vec<String>,
 }
 
+/// Métodos de un protocolo y clases que lo implementan.
+pub struct ProtocolLayout {
+    /// nombre_método -> (parámetros, retorno) del thunk `@Protocolo_método_dispatch`,
+    /// sin el receptor. Incluye los métodos de los protocolos que extiende.
+    pub methods: HashMap<String, (Vec<ValTy>, ValTy)>,
+    /// Clases que conforman al protocolo, (type_id, clase), en orden de registro
+    pub implementations: Vec<(u32, String)>,
+}
+
+/// Nombre del thunk que despacha `method` de `proto` a cada implementación.
+pub fn protocol_thunk(proto: &str, method: &str) -> String {
+    format!("@{}_{}_dispatch", proto, method)
+}
+
+/// Destinos de `break` y `continue` de un bucle.
+pub struct LoopTarget {
+    /// Bloque que evalúa la condición de la siguiente iteración.
+    pub next: String,
+    pub end: String,
+    /// Slot del frame de raíces con el valor del bucle, empaquetado como `Object`.
+    pub result: String,
+    /// Manejadores de `try` abiertos al entrar al bucle.
+    pub handlers: usize,
+}
+
+/// Estado de la función que se está emitiendo. Las lambdas se emiten en medio
+/// de otra: `begin_function` guarda el de la función de afuera y
+/// `end_function` lo restaura.
+pub struct FunctionState {
+    slots: usize,
+    ret: ValTy,
+    loops: Vec<LoopTarget>,
+    handlers: Vec<Handler>,
+}
+
+/// Generador de expresiones que reciben las funciones de emisión de clases y
+/// funciones: devuelve el valor y su representación.
+pub type GenExpr = dyn Fn(&mut Ctx, &Spanned<Expr>) -> (String, ValTy);
+
 pub struct Ctx<'a> {
     pub preamble: String,
     pub globals: String,
@@ -36,12 +85,16 @@ pub struct Ctx<'a> {
     pub next_type_id: u32,
     /// Definiciones de funciones lambda diferidas (emitidas fuera de la función actual)
     pub lambda_defs: String,
-    /// Tipos de retorno de funciones con nombre
-    pub func_ret_types: HashMap<String, ValTy>,
-    /// Mapeo de protocolos a tipos que los implementan: protocolo -> vec[(type_id, clase)]
-    pub protocol_implementations: HashMap<String, Vec<(u32, String)>>,
-    /// Wrappers de functors generados automáticamente: función_nombre -> (protocolo, wrapper_type_name)
-    pub auto_functor_wrappers: HashMap<String, (String, String)>,
+    /// Protocolos del programa: nombre -> métodos e implementaciones
+    pub protocols: HashMap<String, ProtocolLayout>,
+    /// Slots de la shadow stack usados por la función que se está emitiendo
+    pub frame_slots: usize,
+    /// Tipo de retorno de la función que se está emitiendo
+    pub ret_ty: ValTy,
+    /// Bucles que encierran el código actual, el más interno al final
+    pub loops: Vec<LoopTarget>,
+    /// Manejadores de `try` activos en la función, el más interno al final
+    pub handlers: Vec<Handler>,
 }
 
 impl<'a> Ctx<'a> {
@@ -51,16 +104,34 @@ impl<'a> Ctx<'a> {
 declare i32 @printf(i8*, ...)\n\
 declare i32 @puts(i8*)\n\
 declare i8* @malloc(i64)\n\
+declare i8* @calloc(i64, i64)\n\
 declare i8* @realloc(i8*, i64)\n\
 declare void @free(i8*)\n\
+declare i8* @memcpy(i8*, i8*, i64)\n\
+declare i8* @memmove(i8*, i8*, i64)\n\
+declare i8* @memset(i8*, i32, i64)\n\
+declare i8* @getenv(i8*)\n\
+declare i32 @dprintf(i32, i8*, ...)\n\
 declare i64 @strlen(i8*)\n\
+declare i32 @strcmp(i8*, i8*)\n\
 declare i8* @strcpy(i8*, i8*)\n\
 declare i8* @strcat(i8*, i8*)\n\
+declare i8* @strstr(i8*, i8*)\n\
+declare i32 @strncmp(i8*, i8*, i64)\n\
+declare double @strtod(i8*, i8**)\n\
+declare i32 @isspace(i32)\n\
 declare i32 @snprintf(i8*, i64, i8*, ...)\n\
 declare i32 @rand()\n\
 declare void @srand(i32)\n\
 declare i64 @time(i64*)\n\
 declare void @abort()\n\
+declare i32 @fflush(i8*)\n\
+declare void (i32)* @signal(i32, void (i32)*)\n\
+declare i32 @raise(i32)\n\
+declare i32 @_setjmp(i8*) returns_twice\n\
+declare void @longjmp(i8*, i32) noreturn\n\
+declare i8* @llvm.stacksave()\n\
+declare void @llvm.stackrestore(i8*)\n\
 declare double @llvm.pow.f64(double, double)\n\
 declare double @llvm.sin.f64(double)\n\
 declare double @llvm.cos.f64(double)\n\
@@ -81,7 +152,6 @@ declare double @llvm.floor.f64(double)\n\
 @.vec_open = private unnamed_addr constant [2 x i8] c\"[\\00\"\n\
 @.vec_close = private unnamed_addr constant [2 x i8] c\"]\\00\"\n\
 @.vec_sep  = private unnamed_addr constant [3 x i8] c\", \\00\"\n\
-@.oob_msg  = private unnamed_addr constant [36 x i8] c\"Runtime error: index out of bounds\\0A\\00\"\n\
 @.rand_seeded = global i1 false\n\
 ".to_string();
 
@@ -96,9 +166,11 @@ declare double @llvm.floor.f64(double)\n\
             sem,
             next_type_id: 1, // 0 = reservado / desconocido
             lambda_defs: String::new(),
-            func_ret_types: HashMap::new(),
-            protocol_implementations: HashMap::new(),
-            auto_functor_wrappers: HashMap::new(),
+            protocols: HashMap::new(),
+            frame_slots: 0,
+            ret_ty: ValTy::object(),
+            loops: Vec::new(),
+            handlers: Vec::new(),
         }
     }
 
@@ -141,17 +213,208 @@ declare double @llvm.floor.f64(double)\n\
     /// Convierte de forma segura un valor `double` (puntero codificado como double) a un puntero tipado.
     /// Derrama el i64 a través de un alloca en la pila para forzar a LLVM a usar un GPR,
     /// evitando un problema de codegen donde `bitcast double → i64` + `inttoptr` puede
-    /// dejar el valor atrapado en un registro XMM.
+    /// dejar el valor atrapado en un registro XMM. La etiqueta de los 16 bits altos se
+    /// descarta, así que sirve tanto para valores empaquetados como para punteros crudos.
     /// Retorna el nombre del valor SSA puntero del `target_ty` dado (ej. "i8*", "double*", "i64*").
     pub fn decode_ptr(&mut self, double_val: &str, target_ty: &str) -> String {
         let i = self.tmp(); self.emit(&format!("{} = bitcast double {} to i64", i, double_val));
         let a = self.tmp(); self.emit(&format!("{} = alloca i64", a));
         self.emit(&format!("store i64 {}, i64* {}", i, a));
         let i2 = self.tmp(); self.emit(&format!("{} = load i64, i64* {}", i2, a));
-        let p = self.tmp(); self.emit(&format!("{} = inttoptr i64 {} to {}", p, i2, target_ty));
+        let bits = self.tmp(); self.emit(&format!("{} = and i64 {}, {}", bits, i2, BOX_PAYLOAD_MASK));
+        let p = self.tmp(); self.emit(&format!("{} = inttoptr i64 {} to {}", p, bits, target_ty));
         p
     }
 
+    /// Empaqueta un valor de tipo `ty` como `double`, etiquetado con su clase.
+    pub fn box_value(&mut self, val: &str, ty: &ValTy) -> String {
+        let i = match ty {
+            ValTy::Num | ValTy::Boxed(_) | ValTy::Never => return val.to_string(),
+            ValTy::Bool => {
+                let i = self.tmp(); self.emit(&format!("{} = zext i1 {} to i64", i, val));
+                i
+            }
+            _ => {
+                let i = self.tmp(); self.emit(&format!("{} = ptrtoint i8* {} to i64", i, val));
+                i
+            }
+        };
+        let tagged = self.tmp();
+        self.emit(&format!("{} = or i64 {}, {}", tagged, i, box_tag(ty.box_kind())));
+        let d = self.tmp(); self.emit(&format!("{} = bitcast i64 {} to double", d, tagged));
+        d
+    }
+
+    /// Desempaqueta un `double` a la representación de `ty`.
+    pub fn unbox(&mut self, val: &str, ty: &ValTy) -> String {
+        match ty {
+            ValTy::Num | ValTy::Boxed(_) | ValTy::Never => val.to_string(),
+            ValTy::Bool => {
+                let i = self.tmp(); self.emit(&format!("{} = bitcast double {} to i64", i, val));
+                let b = self.tmp();
+                self.emit(&format!("{} = icmp eq i64 {}, {}", b, i, box_tag(BOX_BOOL) | 1));
+                b
+            }
+            _ => self.decode_ptr(val, "i8*"),
+        }
+    }
+
+    /// Convierte `val` de la representación de `from` a la de `to`, pasando por
+    /// `double` solo si las dos difieren.
+    pub fn coerce(&mut self, val: &str, from: &ValTy, to: &ValTy) -> String {
+        // Tras un `throw` el bloque es inalcanzable: cualquier valor sirve
+        if *from == ValTy::Never {
+            return "undef".to_string();
+        }
+        if from.llvm() == to.llvm() {
+            return val.to_string();
+        }
+        // Entre número y booleano no hay etiqueta que conservar
+        match (from, to) {
+            (ValTy::Num, ValTy::Bool) => {
+                let b = self.tmp(); self.emit(&format!("{} = fcmp one double {}, 0.0", b, val));
+                return b;
+            }
+            (ValTy::Bool, ValTy::Num) => {
+                let d = self.tmp();
+                self.emit(&format!("{} = select i1 {}, double 1.0, double 0.0", d, val));
+                return d;
+            }
+            _ => {}
+        }
+        let boxed = self.box_value(val, from);
+        self.unbox(&boxed, to)
+    }
+
+    /// Tipo común de dos ramas; dos clases se unen en su ancestro común más cercano.
+    pub fn join(&self, a: &ValTy, b: &ValTy) -> ValTy {
+        if let (ValTy::Obj(x), ValTy::Obj(y)) = (a, b) {
+            let ancestors = self.ancestors(x);
+            if let Some(common) = self.ancestors(y).into_iter().find(|c| ancestors.contains(c)) {
+                return ValTy::Obj(common);
+            }
+        }
+        a.join(b)
+    }
+
+    /// La clase y sus ancestros con layout, de la más cercana a la raíz.
+    pub fn ancestors(&self, class: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut current = Some(class.to_string());
+        while let Some(name) = current {
+            current = self.classes.get(&name).and_then(|l| l.parent_name.clone());
+            chain.push(name);
+        }
+        chain
+    }
+
+    /// Carga el campo `gep` de un objeto.
+    pub fn load_field(&mut self, gep: &str, ty: &ValTy) -> String {
+        let v = self.tmp();
+        self.emit(&format!("{} = load {}, {}* {}", v, ty.field_llvm(), ty.field_llvm(), gep));
+        if *ty != ValTy::Bool {
+            return v;
+        }
+        let b = self.tmp();
+        self.emit(&format!("{} = icmp ne i64 {}, 0", b, v));
+        b
+    }
+
+    /// Guarda `val` en el campo `gep` de un objeto.
+    pub fn store_field(&mut self, val: &str, gep: &str, ty: &ValTy) {
+        let stored = if *ty == ValTy::Bool {
+            let w = self.tmp();
+            self.emit(&format!("{} = zext i1 {} to i64", w, val));
+            w
+        } else {
+            val.to_string()
+        };
+        self.emit(&format!("store {} {}, {}* {}", ty.field_llvm(), stored, ty.field_llvm(), gep));
+    }
+
+    /// Reserva una variable local de tipo `ty` con valor inicial `val`.
+    pub fn alloca_var(&mut self, name: &str, val: &str, ty: ValTy) {
+        let p = self.tmp();
+        self.emit(&format!("{} = alloca {}", p, ty.llvm()));
+        self.emit(&format!("store {} {}, {}* {}", ty.llvm(), val, ty.llvm(), p));
+        self.def_var(name, &p, ty);
+    }
+
+    /// Abre una función (`header` es la línea `define ...` sin la llave) que
+    /// retorna `ret` y deja sitio para su frame de raíces. Devuelve el estado de
+    /// la función que la contiene, para `end_function`.
+    pub fn begin_function(&mut self, header: &str, ret: ValTy) -> FunctionState {
+        self.functions.push_str(header);
+        self.functions.push_str(" {\nentry:\n");
+        self.functions.push_str(gc::FRAME_MARKER);
+        FunctionState {
+            slots: std::mem::replace(&mut self.frame_slots, 0),
+            ret: std::mem::replace(&mut self.ret_ty, ret),
+            loops: std::mem::take(&mut self.loops),
+            handlers: std::mem::take(&mut self.handlers),
+        }
+    }
+
+    /// Cierra la función con `ret`: desenlaza el frame antes de retornar y
+    /// reemplaza la marca de entrada por el prólogo con los slots usados, y las
+    /// de los `return` anticipados por el desenlace.
+    pub fn end_function(&mut self, ret: &str, outer: FunctionState) {
+        let slots = std::mem::replace(&mut self.frame_slots, outer.slots);
+        self.ret_ty = outer.ret;
+        self.loops = outer.loops;
+        self.handlers = outer.handlers;
+        self.emit_return(ret);
+        self.functions.push_str("}\n\n");
+        let prologue = if slots > 0 { gc::frame_prologue(slots) } else { String::new() };
+        let unlink = if slots > 0 { "  store i8* %gc.prev, i8** @__gc_frames\n" } else { "" };
+        if let Some(pos) = self.functions.rfind(gc::FRAME_MARKER) {
+            let body = self.functions.split_off(pos).replacen(gc::FRAME_MARKER, &prologue, 1);
+            self.functions.push_str(&body.replace(gc::UNLINK_MARKER, unlink));
+        }
+    }
+
+    /// `ret` de la función actual; el frame se desenlaza antes (ver `end_function`).
+    pub fn emit_return(&mut self, ret: &str) {
+        self.functions.push_str(gc::UNLINK_MARKER);
+        self.emit(ret);
+    }
+
+    fn new_slot(&mut self) -> String {
+        let slot = format!("%gc.s{}", self.frame_slots);
+        self.frame_slots += 1;
+        slot
+    }
+
+    /// Slot `double*` propio del frame, visto por el GC como raíz, para un valor
+    /// que cambia durante la función (p. ej. el resultado de un bucle).
+    pub fn root_slot(&mut self) -> String {
+        self.new_slot()
+    }
+
+    /// Guarda un valor en un slot propio del frame para que el GC lo vea como raíz.
+    /// Las constantes y los números o booleanos sin empaquetar no necesitan slot.
+    pub fn root(&mut self, val: &str, ty: &ValTy) {
+        if !val.starts_with('%') { return; }
+        match ty {
+            ValTy::Num | ValTy::Bool | ValTy::Never => {}
+            ValTy::Boxed(_) => {
+                let slot = self.new_slot();
+                self.emit(&format!("store double {}, double* {}", val, slot));
+            }
+            _ => { self.root_ptr(val, "i8*"); }
+        }
+    }
+
+    /// Como `root`, para un puntero de tipo LLVM `ty` (`i8*`, `double*`...).
+    /// Devuelve el slot, por si hay que actualizarlo.
+    pub fn root_ptr(&mut self, ptr: &str, ty: &str) -> String {
+        let slot = self.new_slot();
+        let cast = self.tmp();
+        self.emit(&format!("{} = bitcast double* {} to {}*", cast, slot, ty));
+        self.emit(&format!("store {} {}, {}* {}", ty, ptr, ty, cast));
+        cast
+    }
+
     /// Asignar un type-id para una clase.
     pub fn alloc_type_id(&mut self) -> u32 {
         let id = self.next_type_id;
@@ -0,0 +1,289 @@
+use crate::utils::Position;
+use super::context::Ctx;
+use super::utils::{escape_llvm, ValTy};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Excepciones: `try` / `catch` / `throw` con setjmp/longjmp
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Cada `try` reserva en la pila un manejador `%__Handler`:
+//
+//   { [JMP_BUF_WORDS x i64] jmp_buf, i8* prev, i8* gc_frames }
+//
+// y lo apila en `@__hulk_handler` (`prev` es el manejador que había). `_setjmp`
+// devuelve 0 al entrar al cuerpo; `@__hulk_throw` deja el error en
+// `@__hulk_exception` y salta con `longjmp` al manejador de arriba, por donde
+// `_setjmp` vuelve con 1 hacia los `catch`.
+//
+// El salto se salta los `ret` de las funciones intermedias, que no llegan a
+// desenlazar su frame de la shadow stack: el manejador guarda `@__gc_frames`
+// al entrar y el `catch` lo restaura.
+//
+// El manejador vive entre `llvm.stacksave` y `llvm.stackrestore`, así que un
+// `try` dentro de un bucle no hace crecer la pila en cada iteración.
+//
+// Sin manejador, `@__hulk_throw` imprime el error con su posición en stderr y
+// aborta, como hacían antes los errores de ejecución.
+//
+// `break`, `continue` y `return` también salen de un `try` sin pasar por el
+// final del cuerpo: desapilan los manejadores que dejan atrás (`leave_handlers`).
+
+/// Palabras reservadas para el `jmp_buf` de la plataforma (glibc usa 25 en
+/// x86-64 y 39 en AArch64).
+const JMP_BUF_WORDS: usize = 64;
+
+const HANDLER_TY: &str = "%__Handler";
+/// `SIGSEGV` en Linux y macOS.
+const SIGSEGV: i32 = 11;
+const UNCAUGHT_FMT: &str = "Uncaught %s: %s (line %.0f, column %.0f)\n";
+
+/// Manejador de un `try` en curso.
+#[derive(Clone)]
+pub struct Handler {
+    /// Pila guardada antes de reservar el manejador.
+    sp: String,
+    frame: String,
+}
+
+/// Apila un manejador y deja el bloque actual terminado: sigue en `body` al
+/// entrar y en `catch` cuando algo dentro del cuerpo lanza.
+pub fn push_handler(ctx: &mut Ctx, body: &str, catch: &str) -> Handler {
+    let sp = ctx.tmp();
+    ctx.emit(&format!("{} = call i8* @llvm.stacksave()", sp));
+    let frame = ctx.tmp();
+    ctx.emit(&format!("{} = alloca {}, align 16", frame, HANDLER_TY));
+    let handler = Handler { sp, frame };
+
+    let prev = ctx.tmp();
+    ctx.emit(&format!("{} = load i8*, i8** @__hulk_handler", prev));
+    let prev_slot = handler_field(ctx, &handler, 1);
+    ctx.emit(&format!("store i8* {}, i8** {}", prev, prev_slot));
+    let frames = ctx.tmp();
+    ctx.emit(&format!("{} = load i8*, i8** @__gc_frames", frames));
+    let frames_slot = handler_field(ctx, &handler, 2);
+    ctx.emit(&format!("store i8* {}, i8** {}", frames, frames_slot));
+
+    let buf = ctx.tmp();
+    ctx.emit(&format!("{} = bitcast {}* {} to i8*", buf, HANDLER_TY, handler.frame));
+    ctx.emit(&format!("store i8* {}, i8** @__hulk_handler", buf));
+    let jumped = ctx.tmp();
+    ctx.emit(&format!("{} = call i32 @_setjmp(i8* {})", jumped, buf));
+    let entered = ctx.tmp();
+    ctx.emit(&format!("{} = icmp eq i32 {}, 0", entered, jumped));
+    ctx.emit(&format!("br i1 {}, label %{}, label %{}", entered, body, catch));
+    ctx.handlers.push(handler.clone());
+    handler
+}
+
+/// Fin normal del cuerpo: desapila el manejador y libera su espacio.
+pub fn pop_handler(ctx: &mut Ctx, handler: &Handler) {
+    ctx.handlers.pop();
+    unlink(ctx, handler);
+    ctx.emit(&format!("call void @llvm.stackrestore(i8* {})", handler.sp));
+}
+
+/// Salto hacia afuera de los `try` abiertos después de los primeros `depth`:
+/// vuelve a dejar arriba el manejador que había antes del más externo.
+pub fn leave_handlers(ctx: &mut Ctx, depth: usize) {
+    let Some(outermost) = ctx.handlers.get(depth).cloned() else { return };
+    unlink(ctx, &outermost);
+    ctx.emit(&format!("call void @llvm.stackrestore(i8* {})", outermost.sp));
+}
+
+/// Entrada a los `catch`: desapila el manejador, recupera la shadow stack de
+/// esta función y devuelve el error lanzado (`i8*`).
+pub fn enter_catch(ctx: &mut Ctx, handler: &Handler) -> String {
+    unlink(ctx, handler);
+    let frames_slot = handler_field(ctx, handler, 2);
+    let frames = ctx.tmp();
+    ctx.emit(&format!("{} = load i8*, i8** {}", frames, frames_slot));
+    ctx.emit(&format!("store i8* {}, i8** @__gc_frames", frames));
+    ctx.emit(&format!("call void @llvm.stackrestore(i8* {})", handler.sp));
+    let err = ctx.tmp();
+    ctx.emit(&format!("{} = load i8*, i8** @__hulk_exception", err));
+    err
+}
+
+fn unlink(ctx: &mut Ctx, handler: &Handler) {
+    let prev_slot = handler_field(ctx, handler, 1);
+    let prev = ctx.tmp();
+    ctx.emit(&format!("{} = load i8*, i8** {}", prev, prev_slot));
+    ctx.emit(&format!("store i8* {}, i8** @__hulk_handler", prev));
+}
+
+fn handler_field(ctx: &mut Ctx, handler: &Handler, index: u32) -> String {
+    let p = ctx.tmp();
+    ctx.emit(&format!(
+        "{} = getelementptr inbounds {ty}, {ty}* {}, i32 0, i32 {}", p, handler.frame, index, ty = HANDLER_TY
+    ));
+    p
+}
+
+/// Lanza el error `err` (`i8*`); `pos` se anota en él si aún no tiene posición.
+/// El bloque actual queda terminado.
+pub fn throw_error(ctx: &mut Ctx, err: &str, pos: Position) {
+    let (line, column) = position_args(pos);
+    throw_error_at(ctx, err, &line, &column);
+}
+
+/// Como `throw_error`, con la posición en operandos `double` del IR (p. ej. los
+/// parámetros de un thunk, que la recibe de cada llamada).
+pub fn throw_error_at(ctx: &mut Ctx, err: &str, line: &str, column: &str) {
+    ctx.emit(&format!("call void @__hulk_throw(i8* {}, double {}, double {})", err, line, column));
+    ctx.emit("unreachable");
+}
+
+/// Línea y columna de `pos` como constantes `double` del IR.
+pub fn position_args(pos: Position) -> (String, String) {
+    (format!("{:.1}", pos.line as f64), format!("{:.1}", pos.column as f64))
+}
+
+/// Fallo en tiempo de ejecución: construye un `class` del prelude con
+/// `message` y lo lanza. Sin prelude (el backend usado por separado del
+/// driver) imprime el mensaje y aborta.
+pub fn runtime_throw(ctx: &mut Ctx, class: &str, message: &str, pos: Position) {
+    let (line, column) = position_args(pos);
+    runtime_throw_at(ctx, class, message, &line, &column);
+}
+
+/// Como `runtime_throw`, con la posición en operandos `double` del IR.
+pub fn runtime_throw_at(ctx: &mut Ctx, class: &str, message: &str, line: &str, column: &str) {
+    let msg_id = ctx.add_global_string(message);
+    let msg = ctx.gep_string(&msg_id, message.len() + 1);
+    let Some(param) = message_param(ctx, class) else {
+        let text = format!("Runtime error: {}", message);
+        let text_id = ctx.add_global_string(&text);
+        let text_gep = ctx.gep_string(&text_id, text.len() + 1);
+        abort_with(ctx, &text_gep);
+        return;
+    };
+    throw_message_at(ctx, class, &param, &msg, line, column);
+}
+
+/// Como `runtime_throw`, con un mensaje armado en ejecución (un `i8*` del IR).
+pub fn runtime_throw_value(ctx: &mut Ctx, class: &str, message: &str, pos: Position) {
+    let Some(param) = message_param(ctx, class) else {
+        abort_with(ctx, message);
+        return;
+    };
+    let (line, column) = position_args(pos);
+    throw_message_at(ctx, class, &param, message, &line, &column);
+}
+
+/// Representación del mensaje en el constructor de `class`, si el prelude la tiene.
+fn message_param(ctx: &Ctx, class: &str) -> Option<ValTy> {
+    ctx.classes.get(class).and_then(|l| l.ctor_params.first().cloned())
+}
+
+/// Construye un `class` del prelude con el mensaje `msg` (`i8*`) y lo lanza.
+fn throw_message_at(ctx: &mut Ctx, class: &str, param: &ValTy, msg: &str, line: &str, column: &str) {
+    let arg = ctx.coerce(msg, &ValTy::Str, param);
+    let err = ctx.tmp();
+    ctx.emit(&format!("{} = call i8* @{}_new({} {})", err, class, param.llvm(), arg));
+    throw_error_at(ctx, &err, line, column);
+}
+
+/// Sin prelude: imprime `text` (`i8*`) y aborta.
+fn abort_with(ctx: &mut Ctx, text: &str) {
+    ctx.emit(&format!("call i32 @puts(i8* {})", text));
+    ctx.emit("call void @abort()");
+    ctx.emit("unreachable");
+}
+
+/// Emite el estado de los manejadores y `@__hulk_throw`. Sin la clase `Error`
+/// del prelude no hay nada que lanzar y no se emite nada.
+pub fn emit_exception_runtime(ctx: &mut Ctx) {
+    let Some(error) = ctx.classes.get("Error") else { return };
+    let st = error.struct_name.clone();
+    let field = |name: &str| error.attr_indices[name];
+    let (message, line, column) = (field("message"), field("line"), field("column"));
+
+    // Nombre de cada clase de error, elegido por type-id para el mensaje final
+    let mut errors: Vec<(u32, String)> = ctx.classes.iter()
+        .filter(|(name, _)| ctx.ancestors(name).iter().any(|a| a == "Error"))
+        .map(|(name, layout)| (layout.type_id, name.clone()))
+        .collect();
+    errors.sort();
+
+    ctx.globals.push_str(&format!("\
+{HANDLER_TY} = type {{ [{JMP_BUF_WORDS} x i64], i8*, i8* }}
+@__hulk_handler = internal global i8* null
+@__hulk_exception = internal global i8* null
+@.exc_uncaught = private unnamed_addr constant [{len} x i8] c\"{fmt}\\00\"
+",
+        len = UNCAUGHT_FMT.len() + 1,
+        fmt = escape_llvm(UNCAUGHT_FMT),
+    ));
+    let mut names = String::new();
+    let mut name = "null".to_string();
+    for (i, (tid, class)) in errors.iter().enumerate() {
+        let id = format!("@.exc_name_{}", class);
+        ctx.globals.push_str(&format!(
+            "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n", id, class.len() + 1, escape_llvm(class)
+        ));
+        let ptr = ctx.gep_string(&id, class.len() + 1);
+        names.push_str(&format!("  %is{i} = icmp eq i64 %tid, {tid}\n"));
+        names.push_str(&format!("  %name{i} = select i1 %is{i}, i8* {ptr}, i8* {name}\n"));
+        name = format!("%name{}", i);
+    }
+
+    ctx.functions.push_str(&format!("\
+define void @__hulk_throw(i8* %err, double %line, double %column) noreturn {{
+entry:
+  %obj = bitcast i8* %err to {st}*
+  %line.p = getelementptr inbounds {st}, {st}* %obj, i32 0, i32 {line}
+  %column.p = getelementptr inbounds {st}, {st}* %obj, i32 0, i32 {column}
+  %old = load double, double* %line.p
+  %unset = fcmp oeq double %old, 0.0
+  br i1 %unset, label %stamp, label %raise
+stamp:
+  store double %line, double* %line.p
+  store double %column, double* %column.p
+  br label %raise
+raise:
+  store i8* %err, i8** @__hulk_exception
+  %handler = load i8*, i8** @__hulk_handler
+  %uncaught = icmp eq i8* %handler, null
+  br i1 %uncaught, label %report, label %unwind
+unwind:
+  call void @longjmp(i8* %handler, i32 1)
+  unreachable
+report:
+  %tid.p = bitcast i8* %err to i64*
+  %tid = load i64, i64* %tid.p
+{names}  %message.p = getelementptr inbounds {st}, {st}* %obj, i32 0, i32 {message}
+  %message = load i8*, i8** %message.p
+  %l = load double, double* %line.p
+  %c = load double, double* %column.p
+  call i32 @fflush(i8* null)
+  call i32 (i32, i8*, ...) @dprintf(i32 2, i8* getelementptr inbounds ([{len} x i8], [{len} x i8]* @.exc_uncaught, i64 0, i64 0), i8* {name}, i8* %message, double %l, double %c)
+  call void @abort()
+  unreachable
+}}
+
+",
+        len = UNCAUGHT_FMT.len() + 1,
+    ));
+}
+
+/// Emite `@__hulk_on_crash`, que `@main` instala para `SIGSEGV`: vacía los
+/// buffers de stdio antes de dejar morir al proceso con la señal, para que una
+/// violación de segmento no se lleve lo que el programa ya imprimió.
+pub fn emit_crash_handler(ctx: &mut Ctx) {
+    ctx.functions.push_str(&format!("\
+define internal void @__hulk_on_crash(i32 %sig) {{
+entry:
+  call i32 @fflush(i8* null)
+  call void (i32)* @signal(i32 %sig, void (i32)* null)
+  call i32 @raise(i32 %sig)
+  ret void
+}}
+
+define internal void @__hulk_install_crash_handler() {{
+entry:
+  call void (i32)* @signal(i32 {SIGSEGV}, void (i32)* @__hulk_on_crash)
+  ret void
+}}
+
+"));
+}
@@ -1,106 +1,553 @@
 use std::collections::HashMap;
 use crate::ast::nodes::{Expr, Op, UnOp, Pattern};
-use crate::utils::Spanned;
-use super::context::{Ctx, ClassLayout};
-use super::utils::{fmt_double, ValTy, val_ty_from_annotation};
+use crate::ast::walk::pattern_vars;
+use crate::utils::{Position, Spanned};
+use super::context::{protocol_thunk, Ctx, ClassLayout, LoopTarget};
+use super::utils::{fmt_double, ValTy, BOX_BOOL, BOX_NUM, BOX_STR};
 use super::functions::mangle_fn;
+use super::gc::{GC_RAW, GC_VALUES};
+use super::exceptions::{enter_catch, leave_handlers, pop_handler, position_args, push_handler, runtime_throw, throw_error};
+use super::strings::{compare_strings, gen_string_method};
+use super::maps::{gen_map_method, gen_map_new, gen_map_set};
+use super::builtins::{gen_element_ptr, gen_vector_method, gen_vector_slice, vector_method_sig};
+use crate::errors::runtime::{cast_message, MATCH_MESSAGE};
+
+/// Genera `expr` y guarda su valor en un slot de la shadow stack, para que el GC
+/// no libere un temporal mientras se evalúa el resto de la expresión que lo usa.
+/// Devuelve el valor junto con su tipo.
+pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> (String, ValTy) {
+    let (v, ty) = gen_expr_node(ctx, expr);
+    // Un resultado genérico viaja como `Object`; el chequeo semántico sabe
+    // con qué tipo se instanció y se desempaqueta a ese
+    let (v, ty) = match ctx.sem.generic_sites.get(&expr.span()) {
+        Some(t) if ty == ValTy::object() => {
+            let to = ValTy::from_type(&t.borrow());
+            (ctx.unbox(&v, &to), to)
+        }
+        _ => (v, ty),
+    };
+    // Dentro de la rama de un `is` la variable ya tiene el tipo comprobado:
+    // basta reinterpretar el valor, sin volver a comprobarlo en ejecución
+    let (v, ty) = match ctx.sem.narrowed_sites.get(&expr.span()) {
+        Some(t) => {
+            let to = ValTy::from_type(&t.borrow());
+            (ctx.coerce(&v, &ty, &to), to)
+        }
+        None => (v, ty),
+    };
+    if may_hold_reference(&expr.node) {
+        ctx.root(&v, &ty);
+    }
+    (v, ty)
+}
+
+/// Genera `expr` y convierte su valor a la representación de `ty`.
+pub fn gen_expr_as(ctx: &mut Ctx, expr: &Spanned<Expr>, ty: &ValTy) -> String {
+    let (v, from) = gen_expr(ctx, expr);
+    ctx.coerce(&v, &from, ty)
+}
+
+/// `false` para las expresiones cuyo valor nunca es un bloque del heap.
+fn may_hold_reference(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Boolean(_) | Expr::String(_) | Expr::Unary(..)
+        | Expr::Is(..) => false,
+        Expr::Binary(_, op, _) => matches!(op, Op::Concat | Op::ConcatSpace | Op::Add),
+        _ => true,
+    }
+}
+
+/// Cuerpo de un bucle: `continue` salta a `next` y `break` a `end`.
+fn gen_loop_body(ctx: &mut Ctx, body: &Spanned<Expr>, next: &str, end: &str, result: &str) {
+    let handlers = ctx.handlers.len();
+    ctx.loops.push(LoopTarget { next: next.to_string(), end: end.to_string(), result: result.to_string(), handlers });
+    let (v, ty) = gen_expr(ctx, body);
+    store_loop_result(ctx, &v, &ty, result);
+    ctx.loops.pop();
+}
+
+/// Reserva el slot con el valor de un bucle: el de su última iteración, o el
+/// de su `break`/`continue`; 0 si no llega a iterar.
+fn begin_loop_result(ctx: &mut Ctx) -> String {
+    let result = ctx.root_slot();
+    ctx.emit(&format!("store double 0.0, double* {}", result));
+    result
+}
+
+fn store_loop_result(ctx: &mut Ctx, v: &str, ty: &ValTy, result: &str) {
+    if *ty == ValTy::Never {
+        return;
+    }
+    let boxed = ctx.box_value(v, ty);
+    ctx.emit(&format!("store double {}, double* {}", boxed, result));
+}
+
+fn load_loop_result(ctx: &mut Ctx, result: &str) -> (String, ValTy) {
+    let r = ctx.tmp();
+    ctx.emit(&format!("{} = load double, double* {}", r, result));
+    (r, ValTy::object())
+}
+
+/// Cierra las ramas de un condicional: cada una salta a su bloque `fix`, donde su
+/// valor se convierte al tipo común, y todas se unen con un `phi` en `merge`.
+/// Los bloques `fix` se emiten al final porque el tipo común se conoce solo
+/// después de generar todas las ramas.
+fn merge_arms(ctx: &mut Ctx, arms: Vec<(String, ValTy, String)>, merge: &str) -> (String, ValTy) {
+    let ty = arms.iter().skip(1)
+        .fold(arms[0].1.clone(), |acc, (_, t, _)| ctx.join(&acc, t));
+    let mut incoming = Vec::new();
+    for (v, from, fix) in arms {
+        ctx.emit_label(&fix);
+        let c = ctx.coerce(&v, &from, &ty);
+        ctx.emit(&format!("br label %{}", merge));
+        incoming.push(format!("[ {}, %{} ]", c, fix));
+    }
+    ctx.emit_label(merge);
+    let r = ctx.tmp();
+    ctx.emit(&format!("{} = phi {} {}", r, ty.llvm(), incoming.join(", ")));
+    (r, ty)
+}
+
+/// Clase estática de un valor: la de su tipo, el protocolo si está tipado con
+/// uno o, si está empaquetado, la que sugiera el AST.
+fn static_class(ctx: &Ctx, expr: &Expr, ty: &ValTy) -> Option<String> {
+    match ty {
+        ValTy::Obj(cls) => Some(cls.clone()),
+        ValTy::Vector(_) => Some("__Vector".to_string()),
+        ValTy::Map(..) => Some("__Map".to_string()),
+        ValTy::Boxed(proto) if ctx.protocols.contains_key(proto) => Some(proto.clone()),
+        ValTy::Boxed(_) => resolve_obj_class_from_expr(ctx, expr),
+        _ => None,
+    }
+}
+
+/// Busca el atributo en la clase dada o, como respaldo, en cualquier clase.
+/// Devuelve (struct, índice, representación).
+fn find_field(ctx: &Ctx, cls: Option<&str>, attr: &str) -> Option<(String, u32, ValTy)> {
+    let from_layout = |layout: &ClassLayout| {
+        layout.attr_indices.get(attr).map(|&idx| {
+            let ty = layout.attr_tys.get(attr).cloned().unwrap_or_else(ValTy::object);
+            (layout.struct_name.clone(), idx, ty)
+        })
+    };
+    cls.and_then(|c| ctx.classes.get(c)).and_then(from_layout)
+        .or_else(|| ctx.classes.values().find_map(from_layout))
+}
+
+/// Dirección del campo `idx` del objeto `obj` (un `i8*`).
+fn field_ptr(ctx: &mut Ctx, obj: &str, struct_name: &str, idx: u32) -> String {
+    let cast = ctx.tmp();
+    ctx.emit(&format!("{} = bitcast i8* {} to {}*", cast, obj, struct_name));
+    let gep = ctx.tmp();
+    ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
+        gep, struct_name, struct_name, cast, idx));
+    gep
+}
+
+/// Busca el método en la clase dada o, como respaldo, en cualquier clase.
+/// Devuelve (función, parámetros, retorno).
+fn find_method(ctx: &Ctx, cls: Option<&str>, method: &str) -> Option<(String, Vec<ValTy>, ValTy)> {
+    let from_layout = |layout: &ClassLayout| {
+        layout.method_names.get(method).map(|fname| {
+            let (params, ret) = layout.method_sigs.get(method).cloned()
+                .unwrap_or_else(|| (Vec::new(), ValTy::object()));
+            (fname[1..].to_string(), params, ret)
+        })
+    };
+    cls.and_then(|c| ctx.classes.get(c)).and_then(from_layout)
+        .or_else(|| ctx.classes.values().find_map(from_layout))
+}
+
+/// Llama a un método ya resuelto (`callee` es `@función` o un puntero a función)
+/// con el receptor `obj` (un `i8*`), convirtiendo cada argumento al tipo de su
+/// parámetro.
+pub fn call_method(ctx: &mut Ctx, callee: &str, obj: &str, params: &[ValTy], ret: &ValTy, args: &[(String, ValTy)]) -> String {
+    let mut arg_s = format!("i8* {}", obj);
+    for (i, (v, ty)) in args.iter().enumerate() {
+        let pty = params.get(i).cloned().unwrap_or_else(ValTy::object);
+        let c = ctx.coerce(v, ty, &pty);
+        arg_s.push_str(&format!(", {} {}", pty.llvm(), c));
+    }
+    let r = ctx.tmp();
+    ctx.emit(&format!("{} = call {} {}({})", r, ret.llvm(), callee, arg_s));
+    r
+}
+
+/// Entrada de `method` en la vtable de la clase, si la tiene.
+fn vtable_slot(ctx: &Ctx, cls: &str, method: &str) -> Option<usize> {
+    ctx.classes.get(cls)?.vtable.iter().position(|m| m == method)
+}
+
+/// Llama a `method` sobre `obj`. Si el tipo estático es un protocolo, pasa por
+/// su thunk; si es una clase con el método en su vtable, la función se carga de
+/// la vtable del objeto, así que se ejecuta la versión de su clase dinámica; si
+/// no, se llama directamente. `pos` es la de la llamada: el thunk la usa si
+/// el objeto no implementa el método.
+fn dispatch_method(ctx: &mut Ctx, cls: Option<&str>, method: &str, obj: &str, args: &[(String, ValTy)], pos: Position) -> (String, ValTy) {
+    if let Some(proto) = cls
+        && let Some((mut params, ret)) = ctx.protocols.get(proto).and_then(|p| p.methods.get(method)).cloned()
+    {
+        let (line, column) = position_args(pos);
+        let mut args = args.to_vec();
+        args.extend([(line, ValTy::Num), (column, ValTy::Num)]);
+        params.extend([ValTy::Num, ValTy::Num]);
+        let r = call_method(ctx, &protocol_thunk(proto, method), obj, &params, &ret, &args);
+        return (r, ret);
+    }
+    let (func, params, ret) = find_method(ctx, cls, method)
+        .unwrap_or_else(|| (format!("unknown_{}", method), Vec::new(), ValTy::object()));
+    let callee = match cls.and_then(|c| vtable_slot(ctx, c, method)) {
+        Some(slot) => {
+            let header = ctx.tmp();
+            ctx.emit(&format!("{} = bitcast i8* {} to {{ i64, i8** }}*", header, obj));
+            let vt_ptr = ctx.tmp();
+            ctx.emit(&format!("{} = getelementptr inbounds {{ i64, i8** }}, {{ i64, i8** }}* {}, i32 0, i32 1", vt_ptr, header));
+            let vt = ctx.tmp();
+            ctx.emit(&format!("{} = load i8**, i8*** {}", vt, vt_ptr));
+            let entry = ctx.tmp();
+            ctx.emit(&format!("{} = getelementptr inbounds i8*, i8** {}, i64 {}", entry, vt, slot));
+            let raw = ctx.tmp();
+            ctx.emit(&format!("{} = load i8*, i8** {}", raw, entry));
+            let param_tys: Vec<&str> = std::iter::once("i8*").chain(params.iter().map(|p| p.llvm())).collect();
+            let fp = ctx.tmp();
+            ctx.emit(&format!("{} = bitcast i8* {} to {} ({})*", fp, raw, ret.llvm(), param_tys.join(", ")));
+            fp
+        }
+        None => format!("@{}", func),
+    };
+    let r = call_method(ctx, &callee, obj, &params, &ret, args);
+    (r, ret)
+}
+
+/// Genera los argumentos y los convierte a los tipos de los parámetros;
+/// devuelve la lista `tipo valor, ...` lista para un `call`.
+fn gen_args(ctx: &mut Ctx, args: &[Spanned<Expr>], params: &[ValTy]) -> String {
+    let mut vals = Vec::new();
+    for a in args { vals.push(gen_expr(ctx, a)); }
+    let mut arg_s = Vec::new();
+    for (i, (v, ty)) in vals.iter().enumerate() {
+        let pty = params.get(i).cloned().unwrap_or_else(ValTy::object);
+        let c = ctx.coerce(v, ty, &pty);
+        arg_s.push(format!("{} {}", pty.llvm(), c));
+    }
+    arg_s.join(", ")
+}
+
+/// Type-ids de la clase `type_name` y de todas sus descendientes.
+fn class_type_ids(ctx: &Ctx, type_name: &str) -> Option<Vec<u32>> {
+    let target_tid = ctx.classes.get(type_name)?.type_id;
+    let mut valid_ids = vec![target_tid];
+    for layout in ctx.classes.values() {
+        if layout.type_id != target_tid && class_inherits_from(&ctx.classes, layout, type_name) {
+            valid_ids.push(layout.type_id);
+        }
+    }
+    Some(valid_ids)
+}
+
+/// `i1` que dice si el type-id del objeto `obj` (un `i8*`) está en `ids`.
+fn type_id_in(ctx: &mut Ctx, obj: &str, ids: &[u32]) -> String {
+    let op = ctx.tmp(); ctx.emit(&format!("{} = bitcast i8* {} to i64*", op, obj));
+    let tid = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", tid, op));
+    tid_in(ctx, &tid, ids)
+}
+
+/// `i1` que dice si el type-id `tid` (un `i64`) está en `ids`.
+fn tid_in(ctx: &mut Ctx, tid: &str, ids: &[u32]) -> String {
+    let mut any = "false".to_string();
+    for vid in ids {
+        let c = ctx.tmp();
+        ctx.emit(&format!("{} = icmp eq i64 {}, {}", c, tid, vid));
+        let next = ctx.tmp();
+        ctx.emit(&format!("{} = or i1 {}, {}", next, any, c));
+        any = next;
+    }
+    any
+}
+
+/// `is` contra un tipo que no es clase, resuelto en compilación cuando el tipo
+/// estático lo permite. Un valor empaquetado se acepta como mejor esfuerzo.
+fn static_is(ctx: &Ctx, ty: &ValTy, type_name: &str) -> bool {
+    if type_name == "Object" {
+        return true;
+    }
+    match ty {
+        ValTy::Num => type_name == "Number",
+        ValTy::Bool => type_name == "Boolean",
+        ValTy::Str => type_name == "String",
+        ValTy::Obj(cls) => match (ctx.sem.types.get(cls), ctx.sem.types.get(type_name)) {
+            (Some(t), Some(target)) => t.borrow().conforms_to(target),
+            _ => false,
+        },
+        ValTy::Vector(_) | ValTy::Map(..) => type_name == "Iterable",
+        ValTy::Fn(_) => false,
+        ValTy::Boxed(_) | ValTy::Never => true,
+    }
+}
+
+/// Representación del tipo semántico `name`; `Object` si no se conoce.
+fn sem_val_ty(ctx: &Ctx, name: &str) -> ValTy {
+    ctx.sem.types.get(name).map_or_else(ValTy::object, |t| ValTy::from_type(&t.borrow()))
+}
+
+/// `i1` de `v is type_name`. Contra una clase compara el type-id del objeto con
+/// el del destino y sus descendientes; contra el resto decide en compilación,
+/// salvo para un valor empaquetado, que se mira en ejecución (ver `boxed_is`).
+fn type_test(ctx: &mut Ctx, v: &str, ty: &ValTy, type_name: &str) -> String {
+    if matches!(ty, ValTy::Boxed(_)) && type_name != "Object" {
+        return boxed_is(ctx, v, type_name);
+    }
+    match class_type_ids(ctx, type_name) {
+        Some(_) if matches!(ty, ValTy::Num | ValTy::Bool | ValTy::Str) => "false".into(),
+        Some(valid_ids) => {
+            let op = ctx.coerce(v, ty, &ValTy::Obj(String::new()));
+            type_id_in(ctx, &op, &valid_ids)
+        }
+        None => static_is(ctx, ty, type_name).to_string(),
+    }
+}
+
+/// `is` de un valor empaquetado: contra `Number`, `Boolean` o `String` mira la
+/// etiqueta; contra una clase o un protocolo, el type-id de la referencia, que
+/// es -1 si no lo es.
+fn boxed_is(ctx: &mut Ctx, v: &str, type_name: &str) -> String {
+    let kind = match type_name {
+        "Number" => Some(BOX_NUM),
+        "Boolean" => Some(BOX_BOOL),
+        "String" => Some(BOX_STR),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        let k = ctx.tmp();
+        ctx.emit(&format!("{} = call i32 @__hulk_box_kind(double {})", k, v));
+        let c = ctx.tmp();
+        ctx.emit(&format!("{} = icmp eq i32 {}, {}", c, k, kind));
+        return c;
+    }
+    let ids = match (class_type_ids(ctx, type_name), ctx.protocols.get(type_name)) {
+        (Some(ids), _) => ids,
+        (None, Some(proto)) => proto.implementations.iter().map(|(tid, _)| *tid).collect(),
+        // Tipos sin type-id propio (vectores, funciones): mejor esfuerzo
+        (None, None) => return "true".into(),
+    };
+    let tid = ctx.tmp();
+    ctx.emit(&format!("{} = call i64 @__hulk_box_type_id(double {})", tid, v));
+    tid_in(ctx, &tid, &ids)
+}
+
+/// Sigue en un bloque nuevo si `cond` es verdadera y salta a `fail` si no.
+fn branch_or_fail(ctx: &mut Ctx, cond: &str, fail: &str) {
+    let ok = ctx.lbl("mok");
+    ctx.emit(&format!("br i1 {}, label %{}, label %{}", cond, ok, fail));
+    ctx.emit_label(&ok);
+}
+
+/// Compara `v` (de tipo `ty`) con `pattern`: salta a `fail` si no encaja y,
+/// si encaja, declara en el ámbito actual las variables que enlaza.
+fn gen_pattern(ctx: &mut Ctx, pattern: &Pattern, v: &str, ty: &ValTy, fail: &str) {
+    match pattern {
+        Pattern::Wildcard => {}
+        Pattern::Variable { name, type_annotation: None } => ctx.alloca_var(name, v, ty.clone()),
+        Pattern::Variable { name, type_annotation: Some(ann) } => {
+            let type_name = ann.to_string();
+            let ok = type_test(ctx, v, ty, &type_name);
+            branch_or_fail(ctx, &ok, fail);
+            let target = match class_type_ids(ctx, &type_name) {
+                Some(_) => ValTy::Obj(type_name),
+                None => sem_val_ty(ctx, &type_name),
+            };
+            let bound = ctx.coerce(v, ty, &target);
+            ctx.alloca_var(name, &bound, target);
+        }
+        Pattern::Literal(lit) => {
+            // Un valor empaquetado solo se compara con literales de su clase
+            let lit_ty = match lit {
+                Expr::Number(_) => Some("Number"),
+                Expr::Boolean(_) => Some("Boolean"),
+                Expr::String(_) => Some("String"),
+                _ => None,
+            };
+            if let (ValTy::Boxed(_), Some(lit_ty)) = (ty, lit_ty) {
+                let ok = type_test(ctx, v, ty, lit_ty);
+                branch_or_fail(ctx, &ok, fail);
+            }
+            let c = ctx.tmp();
+            match lit {
+                Expr::Number(n) => {
+                    let n_v = ctx.coerce(v, ty, &ValTy::Num);
+                    ctx.emit(&format!("{} = fcmp oeq double {}, {}", c, n_v, fmt_double(*n)));
+                }
+                Expr::Boolean(b) => {
+                    let b_v = ctx.coerce(v, ty, &ValTy::Bool);
+                    ctx.emit(&format!("{} = icmp eq i1 {}, {}", c, b_v, b));
+                }
+                Expr::String(s) => {
+                    let s_v = ctx.coerce(v, ty, &ValTy::Str);
+                    let id = ctx.add_global_string(s);
+                    let lit_v = ctx.gep_string(&id, s.len() + 1);
+                    let cmp = ctx.tmp();
+                    ctx.emit(&format!("{} = call i32 @strcmp(i8* {}, i8* {})", cmp, s_v, lit_v));
+                    ctx.emit(&format!("{} = icmp eq i32 {}, 0", c, cmp));
+                }
+                _ => ctx.emit(&format!("{} = or i1 false, false", c)),
+            }
+            branch_or_fail(ctx, &c, fail);
+        }
+        // Constructor: la clase y, en el orden de los parámetros del
+        // constructor, los atributos del mismo nombre
+        Pattern::Call { func, args } => {
+            let ok = type_test(ctx, v, ty, func);
+            branch_or_fail(ctx, &ok, fail);
+            let obj = ctx.coerce(v, ty, &ValTy::Obj(String::new()));
+            let params: Vec<String> = ctx.sem.types.get(func)
+                .map(|t| t.borrow().params.iter().map(|(n, _)| n.clone()).collect())
+                .unwrap_or_default();
+            for (param, arg) in params.iter().zip(args) {
+                let Some((sn, idx, field_ty)) = find_field(ctx, Some(func), param) else {
+                    continue;
+                };
+                let gep = field_ptr(ctx, &obj, &sn, idx);
+                let field = ctx.load_field(&gep, &field_ty);
+                gen_pattern(ctx, arg, &field, &field_ty, fail);
+            }
+        }
+        // Los patrones de expresiones solo encajan con código, en las macros
+        Pattern::Binary { .. } | Pattern::Unary { .. } => ctx.emit(&format!("br label %{}", fail)),
+    }
+}
 
-pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
+fn gen_expr_node(ctx: &mut Ctx, expr: &Spanned<Expr>) -> (String, ValTy) {
     match &expr.node {
         //  Primitivos
-        Expr::Number(v) => fmt_double(*v),
+        Expr::Number(v) => (fmt_double(*v), ValTy::Num),
 
-        Expr::Boolean(v) => if *v { "1.0".into() } else { "0.0".into() },
+        Expr::Boolean(v) => (v.to_string(), ValTy::Bool),
 
         Expr::String(s) => {
             let id = ctx.add_global_string(s);
             let len = s.len() + 1;
-            let gep = ctx.gep_string(&id, len);
-            let i = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", i, gep));
-            let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, i));
-            d
+            (ctx.gep_string(&id, len), ValTy::Str)
         }
 
         Expr::Identifier(name) => {
             // Local / parámetro
-            if let Some((ptr, _ty)) = ctx.get_var(name) {
+            if let Some((ptr, ty)) = ctx.get_var(name) {
                 let r = ctx.tmp();
-                ctx.emit(&format!("{} = load double, double* {}", r, ptr));
-                return r;
+                ctx.emit(&format!("{} = load {}, {}* {}", r, ty.llvm(), ty.llvm(), ptr));
+                return (r, ty);
             }
-            // Referencia a `self` dentro de un método — codificar i8* como double
-            if name == "self" && ctx.current_class.is_some() {
-                let i = ctx.tmp();
-                ctx.emit(&format!("{} = ptrtoint i8* %self to i64", i));
-                let d = ctx.tmp();
-                ctx.emit(&format!("{} = bitcast i64 {} to double", d, i));
-                return d;
+            // Referencia a `self` dentro de un método
+            if name == "self" && let Some(cls) = ctx.current_class.clone() {
+                return ("%self".into(), ValTy::Obj(cls));
             }
             //  Atributo de self (dentro del cuerpo de un método)
             if let Some(cls) = ctx.current_class.clone() {
-                if let Some(layout) = ctx.classes.get(&cls) {
-                    if let Some(&idx) = layout.attr_indices.get(name.as_str()) {
-                        let sn = layout.struct_name.clone();
-                        let cast = ctx.tmp();
-                        ctx.emit(&format!("{} = bitcast i8* %self to {}*", cast, sn));
-                        let gep = ctx.tmp();
-                        ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
-                            gep, sn, sn, cast, idx));
-                        let v = ctx.tmp();
-                        ctx.emit(&format!("{} = load double, double* {}", v, gep));
-                        return v;
-                    }
+                let field = ctx.classes.get(&cls).and_then(|layout| {
+                    layout.attr_indices.get(name.as_str()).map(|&idx| (layout.struct_name.clone(), idx))
+                });
+                if let Some((sn, idx)) = field {
+                    let ty = ctx.classes[&cls].attr_tys.get(name.as_str()).cloned().unwrap_or_else(ValTy::object);
+                    let gep = field_ptr(ctx, "%self", &sn, idx);
+                    return (ctx.load_field(&gep, &ty), ty);
                 }
             }
             ctx.emit(&format!("; WARNING: variable '{}' not found", name));
-            "0.0".into()
+            ("0.0".into(), ValTy::Num)
         }
 
-        //  Binario 
+        //  Binario
         Expr::Binary(lhs_ast, op, rhs_ast) => {
-            let l = gen_expr(ctx, lhs_ast);
-            let r = gen_expr(ctx, rhs_ast);
-            let res = ctx.tmp();
+            let res = match op {
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod | Op::Pow => {
+                    let (lv, lt) = gen_expr(ctx, lhs_ast);
+                    // `v + w` concatena dos vectores en uno nuevo
+                    if let (Op::Add, ValTy::Vector(le)) = (op, &lt) {
+                        let (rv, rt) = gen_expr(ctx, rhs_ast);
+                        let elem = match &rt { ValTy::Vector(re) => ctx.join(le, re), _ => (**le).clone() };
+                        let rv = ctx.coerce(&rv, &rt, &ValTy::Obj(String::new()));
+                        let res = ctx.tmp();
+                        ctx.emit(&format!("{} = call i8* @__Vector_concat(i8* {}, i8* {})", res, lv, rv));
+                        return (res, ValTy::Vector(Box::new(elem)));
+                    }
+                    let l = ctx.coerce(&lv, &lt, &ValTy::Num);
+                    let r = gen_expr_as(ctx, rhs_ast, &ValTy::Num);
+                    let res = ctx.tmp();
+                    match op {
+                        Op::Add => ctx.emit(&format!("{} = fadd double {}, {}", res, l, r)),
+                        Op::Sub => ctx.emit(&format!("{} = fsub double {}, {}", res, l, r)),
+                        Op::Mul => ctx.emit(&format!("{} = fmul double {}, {}", res, l, r)),
+                        Op::Div => ctx.emit(&format!("{} = fdiv double {}, {}", res, l, r)),
+                        Op::Mod => ctx.emit(&format!("{} = frem double {}, {}", res, l, r)),
+                        _ => ctx.emit(&format!("{} = call double @llvm.pow.f64(double {}, double {})", res, l, r)),
+                    }
+                    return (res, ValTy::Num);
+                }
 
-            match op {
-                Op::Add => ctx.emit(&format!("{} = fadd double {}, {}", res, l, r)),
-                Op::Sub => ctx.emit(&format!("{} = fsub double {}, {}", res, l, r)),
-                Op::Mul => ctx.emit(&format!("{} = fmul double {}, {}", res, l, r)),
-                Op::Div => ctx.emit(&format!("{} = fdiv double {}, {}", res, l, r)),
-                Op::Mod => ctx.emit(&format!("{} = frem double {}, {}", res, l, r)),
-                Op::Pow => ctx.emit(&format!("{} = call double @llvm.pow.f64(double {}, double {})", res, l, r)),
-
-                Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge => {
+                Op::Lt | Op::Gt | Op::Le | Op::Ge => {
                     let pred = match op {
-                        Op::Eq  => "oeq", Op::Neq => "one",
-                        Op::Lt  => "olt", Op::Gt  => "ogt",
-                        Op::Le  => "ole", Op::Ge  => "oge",
-                        _ => unreachable!(),
+                        Op::Lt => "olt", Op::Gt => "ogt",
+                        Op::Le => "ole", _ => "oge",
                     };
-                    let c = ctx.tmp();
-                    ctx.emit(&format!("{} = fcmp {} double {}, {}", c, pred, l, r));
-                    ctx.emit(&format!("{} = select i1 {}, double 1.0, double 0.0", res, c));
+                    let (l, lt) = gen_expr(ctx, lhs_ast);
+                    let (r, rt) = gen_expr(ctx, rhs_ast);
+                    if lt == ValTy::Str && rt == ValTy::Str {
+                        return (compare_strings(ctx, &l, &r, &format!("s{}", &pred[1..])), ValTy::Bool);
+                    }
+                    let l = ctx.coerce(&l, &lt, &ValTy::Num);
+                    let r = ctx.coerce(&r, &rt, &ValTy::Num);
+                    let res = ctx.tmp();
+                    ctx.emit(&format!("{} = fcmp {} double {}, {}", res, pred, l, r));
+                    res
                 }
-                Op::And => {
-                    let lb = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", lb, l));
-                    let rb = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", rb, r));
-                    let ab = ctx.tmp(); ctx.emit(&format!("{} = and i1 {}, {}", ab, lb, rb));
-                    ctx.emit(&format!("{} = select i1 {}, double 1.0, double 0.0", res, ab));
+
+                Op::Eq | Op::Neq => {
+                    // Las cadenas se comparan por contenido; números, booleanos y
+                    // demás punteros, en su representación; si un lado está
+                    // empaquetado, `@__hulk_box_eq` compara según las etiquetas
+                    let eq = *op == Op::Eq;
+                    let (l, lt) = gen_expr(ctx, lhs_ast);
+                    let (r, rt) = gen_expr(ctx, rhs_ast);
+                    if lt == ValTy::Str && rt == ValTy::Str {
+                        return (compare_strings(ctx, &l, &r, if eq { "eq" } else { "ne" }), ValTy::Bool);
+                    }
+                    let res = ctx.tmp();
+                    if lt == ValTy::Bool && rt == ValTy::Bool {
+                        ctx.emit(&format!("{} = icmp {} i1 {}, {}", res, if eq { "eq" } else { "ne" }, l, r));
+                    } else if lt.is_ref() && rt.is_ref() {
+                        ctx.emit(&format!("{} = icmp {} i8* {}, {}", res, if eq { "eq" } else { "ne" }, l, r));
+                    } else if lt == ValTy::Num && rt == ValTy::Num {
+                        ctx.emit(&format!("{} = fcmp {} double {}, {}", res, if eq { "oeq" } else { "one" }, l, r));
+                    } else {
+                        let lb = ctx.box_value(&l, &lt);
+                        let rb = ctx.box_value(&r, &rt);
+                        let same = if eq { res.clone() } else { ctx.tmp() };
+                        ctx.emit(&format!("{} = call i1 @__hulk_box_eq(double {}, double {})", same, lb, rb));
+                        if !eq {
+                            ctx.emit(&format!("{} = xor i1 {}, true", res, same));
+                        }
+                    }
+                    res
                 }
-                Op::Or => {
-                    let lb = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", lb, l));
-                    let rb = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", rb, r));
-                    let ob = ctx.tmp(); ctx.emit(&format!("{} = or i1 {}, {}", ob, lb, rb));
-                    ctx.emit(&format!("{} = select i1 {}, double 1.0, double 0.0", res, ob));
+
+                Op::And | Op::Or => {
+                    let l = gen_expr_as(ctx, lhs_ast, &ValTy::Bool);
+                    let r = gen_expr_as(ctx, rhs_ast, &ValTy::Bool);
+                    let res = ctx.tmp();
+                    let inst = if *op == Op::And { "and" } else { "or" };
+                    ctx.emit(&format!("{} = {} i1 {}, {}", res, inst, l, r));
+                    res
                 }
+
                 Op::Concat | Op::ConcatSpace => {
-                    // Convertir cada operando a puntero i8* de cadena.
-                    // Si el operando ya es una cadena (puntero-como-double), bitcast.
-                    // Si es un número, llamar a @__hulk_num_to_str.
-                    // Si es un bool, llamar a @__hulk_bool_to_str.
-                    // Si es desconocido, usar @__hulk_num_to_str como heurística por defecto.
-                    let lp = gen_to_str_ptr(ctx, &lhs_ast.node, &l);
-                    let rp = gen_to_str_ptr(ctx, &rhs_ast.node, &r);
+                    // Cada operando se convierte a cadena según su tipo (ver
+                    // `gen_to_str_ptr`). Las conversiones asignan: cada lado queda
+                    // como raíz hasta copiarlo.
+                    let (l, lt) = gen_expr(ctx, lhs_ast);
+                    let (r, rt) = gen_expr(ctx, rhs_ast);
+                    let lp = gen_to_str_ptr(ctx, &l, &lt);
+                    ctx.root_ptr(&lp, "i8*");
+                    let rp = gen_to_str_ptr(ctx, &r, &rt);
+                    ctx.root_ptr(&rp, "i8*");
 
                     let ll = ctx.tmp(); ctx.emit(&format!("{} = call i64 @strlen(i8* {})", ll, lp));
                     let rl = ctx.tmp(); ctx.emit(&format!("{} = call i64 @strlen(i8* {})", rl, rp));
@@ -108,289 +555,191 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
                     let extra = if *op == Op::ConcatSpace { 2i64 } else { 1 };
                     let total2 = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, {}", total2, total, extra));
 
-                    let buf = ctx.tmp(); ctx.emit(&format!("{} = call i8* @malloc(i64 {})", buf, total2));
+                    let buf = ctx.tmp(); ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", buf, total2, GC_RAW));
                     ctx.emit(&format!("call i8* @strcpy(i8* {}, i8* {})", buf, lp));
                     if *op == Op::ConcatSpace {
                         ctx.emit(&format!("call i8* @strcat(i8* {}, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.space_s, i64 0, i64 0))", buf));
                     }
                     ctx.emit(&format!("call i8* @strcat(i8* {}, i8* {})", buf, rp));
-
-                    let i = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", i, buf));
-                    ctx.emit(&format!("{} = bitcast i64 {} to double", res, i));
+                    return (buf, ValTy::Str);
                 }
-            }
-            res
+            };
+            (res, ValTy::Bool)
         }
 
-        //  Unario 
+        //  Unario
         Expr::Unary(op, operand) => {
-            let v = gen_expr(ctx, operand);
             let res = ctx.tmp();
             match op {
-                UnOp::Neg => ctx.emit(&format!("{} = fneg double {}", res, v)),
+                UnOp::Neg => {
+                    let v = gen_expr_as(ctx, operand, &ValTy::Num);
+                    ctx.emit(&format!("{} = fneg double {}", res, v));
+                    (res, ValTy::Num)
+                }
                 UnOp::Not => {
-                    let c = ctx.tmp();
-                    ctx.emit(&format!("{} = fcmp oeq double {}, 0.0", c, v));
-                    ctx.emit(&format!("{} = select i1 {}, double 1.0, double 0.0", res, c));
+                    let v = gen_expr_as(ctx, operand, &ValTy::Bool);
+                    ctx.emit(&format!("{} = xor i1 {}, true", res, v));
+                    (res, ValTy::Bool)
                 }
             }
-            res
         }
 
-        //  If / Else (Condicional) 
+        //  If / Else (Condicional)
         Expr::If { cond, then_expr, else_expr } => {
-            let cv = gen_expr(ctx, cond);
-            let cb = ctx.tmp();
-            ctx.emit(&format!("{} = fcmp one double {}, 0.0", cb, cv));
-
-            let res_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = alloca double", res_ptr));
+            let cb = gen_expr_as(ctx, cond, &ValTy::Bool);
 
             let tl = ctx.lbl("then"); let el = ctx.lbl("else"); let ml = ctx.lbl("merge");
             ctx.emit(&format!("br i1 {}, label %{}, label %{}", cb, tl, el));
 
             ctx.emit_label(&tl);
-            let tv = gen_expr(ctx, then_expr);
-            ctx.emit(&format!("store double {}, double* {}", tv, res_ptr));
-            ctx.emit(&format!("br label %{}", ml));
+            let (tv, tt) = gen_expr(ctx, then_expr);
+            let tfix = ctx.lbl("then_end");
+            ctx.emit(&format!("br label %{}", tfix));
 
             ctx.emit_label(&el);
-            let ev = gen_expr(ctx, else_expr);
-            ctx.emit(&format!("store double {}, double* {}", ev, res_ptr));
-            ctx.emit(&format!("br label %{}", ml));
+            let (ev, et) = gen_expr(ctx, else_expr);
+            let efix = ctx.lbl("else_end");
+            ctx.emit(&format!("br label %{}", efix));
 
-            ctx.emit_label(&ml);
-            let r = ctx.tmp();
-            ctx.emit(&format!("{} = load double, double* {}", r, res_ptr));
-            r
+            merge_arms(ctx, vec![(tv, tt, tfix), (ev, et, efix)], &ml)
         }
 
-        // While 
+        // While
         Expr::While { cond, body } => {
             let lc = ctx.lbl("wcond"); let lb = ctx.lbl("wbody"); let le = ctx.lbl("wend");
+            let result = begin_loop_result(ctx);
             ctx.emit(&format!("br label %{}", lc));
 
             ctx.emit_label(&lc);
-            let cv = gen_expr(ctx, cond);
-            let cb = ctx.tmp();
-            ctx.emit(&format!("{} = fcmp one double {}, 0.0", cb, cv));
+            let cb = gen_expr_as(ctx, cond, &ValTy::Bool);
             ctx.emit(&format!("br i1 {}, label %{}, label %{}", cb, lb, le));
 
             ctx.emit_label(&lb);
-            gen_expr(ctx, body);
+            gen_loop_body(ctx, body, &lc, &le, &result);
             ctx.emit(&format!("br label %{}", lc));
 
             ctx.emit_label(&le);
-            "0.0".into()
+            load_loop_result(ctx, &result)
         }
 
-        // (For) 
-        // Transpila automáticamente según el tipo del iterable:
-        //   - Si es un objeto con métodos next()/get_current() (protocolo Iterable),
-        //     se transpila a: while(iterable.next()) let var = iterable.get_current() in { body }
-        //   - Si es un vector, se itera por índice como antes.
+        // (For)
+        // Se transpila a: while(iterable.next()) let var = iterable.current() in { body }
+        //   - Si el iterable es un objeto con métodos next()/current() (protocolo
+        //     Iterable; o get_current()), se llaman los de su clase.
+        //   - Si no, se trata como vector (los métodos de `__Vector`); sus elementos
+        //     se desempaquetan al tipo del vector.
         Expr::For { var, iterable, body } => {
-            // Intentar resolver la clase del iterable para ver si implementa Iterable
-            let iter_vty = infer_val_ty(ctx, &iterable.node);
-            let iterable_class = match &iter_vty {
-                ValTy::Obj(cls) => Some(cls.clone()),
-                _ => None,
-            };
-
-            // Determinar nombre del método "current" (get_current o current)
-            let current_method = if let Some(ref cls) = iterable_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if layout.method_names.contains_key("get_current") {
-                        Some("get_current")
-                    } else if layout.method_names.contains_key("current") {
-                        Some("current")
-                    } else {
-                        None
-                    }
-                } else { None }
-            } else { None };
-
-            let is_iterable = if let Some(ref cls) = iterable_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    layout.method_names.contains_key("next") && current_method.is_some()
-                } else { false }
-            } else { false };
-
-            if is_iterable {
-                // ── Protocolo Iterable: transpilación a while(iter.next()) ──
-                let cls = iterable_class.unwrap();
-                let cur_method = current_method.unwrap();
-                let layout = ctx.classes.get(&cls).unwrap();
-                let next_fn = layout.method_names.get("next").unwrap().clone();
-                let current_fn = layout.method_names.get(cur_method).unwrap().clone();
-
-                let iter_val = gen_expr(ctx, iterable);
-                let iter_ptr = ctx.tmp();
-                ctx.emit(&format!("{} = alloca double", iter_ptr));
-                ctx.emit(&format!("store double {}, double* {}", iter_val, iter_ptr));
-
-                let lc = ctx.lbl("icond"); let lb = ctx.lbl("ibody"); let le = ctx.lbl("iend");
-                ctx.emit(&format!("br label %{}", lc));
-
-                // Condición: call next()
-                ctx.emit_label(&lc);
-                let iv = ctx.tmp(); ctx.emit(&format!("{} = load double, double* {}", iv, iter_ptr));
-                let op = ctx.decode_ptr(&iv, "i8*");
-                let next_r = ctx.tmp();
-                ctx.emit(&format!("{} = call double {}(i8* {})", next_r, next_fn, op));
-                let cond = ctx.tmp();
-                ctx.emit(&format!("{} = fcmp one double {}, 0.0", cond, next_r));
-                ctx.emit(&format!("br i1 {}, label %{}, label %{}", cond, lb, le));
-
-                // Cuerpo: let var = iter.get_current() in body
-                ctx.emit_label(&lb);
-                ctx.enter_scope();
-                let iv2 = ctx.tmp(); ctx.emit(&format!("{} = load double, double* {}", iv2, iter_ptr));
-                let op2 = ctx.decode_ptr(&iv2, "i8*");
-                let cur_r = ctx.tmp();
-                ctx.emit(&format!("{} = call double {}(i8* {})", cur_r, current_fn, op2));
-                let vp = ctx.tmp(); ctx.emit(&format!("{} = alloca double", vp));
-                ctx.emit(&format!("store double {}, double* {}", cur_r, vp));
-                ctx.def_var(var, &vp, ValTy::Num);
-
-                gen_expr(ctx, body);
-                ctx.exit_scope();
-                ctx.emit(&format!("br label %{}", lc));
-
-                ctx.emit_label(&le);
-                "0.0".into()
-            } else {
-                // ── Vector: iteración por índice ──
-                let iter_val = gen_expr(ctx, iterable);
-                let vp = ctx.decode_ptr(&iter_val, "double*");
-
-                let len_ptr = vp.clone();
-                let len_d = ctx.tmp(); ctx.emit(&format!("{} = load double, double* {}", len_d, len_ptr));
-                let len_i = ctx.tmp(); ctx.emit(&format!("{} = fptosi double {} to i64", len_i, len_d));
+            let (iter_val, iter_ty) = gen_expr(ctx, iterable);
+            let (cls, current, elem_ty) = iterable_class(ctx, iterable, &iter_ty);
+            let op = ctx.coerce(&iter_val, &iter_ty, &ValTy::Obj(String::new()));
 
-                let idx_ptr = ctx.tmp(); ctx.emit(&format!("{} = alloca i64", idx_ptr));
-                ctx.emit(&format!("store i64 0, i64* {}", idx_ptr));
+            let lc = ctx.lbl("icond"); let lb = ctx.lbl("ibody"); let le = ctx.lbl("iend");
+            let result = begin_loop_result(ctx);
+            ctx.emit(&format!("br label %{}", lc));
 
-                let lc = ctx.lbl("fcond"); let lb = ctx.lbl("fbody"); let le = ctx.lbl("fend");
-                ctx.emit(&format!("br label %{}", lc));
+            // Condición: call next()
+            ctx.emit_label(&lc);
+            let (has_next, next_ty) = dispatch_method(ctx, Some(&cls), "next", &op, &[], expr.pos);
+            let cond = ctx.coerce(&has_next, &next_ty, &ValTy::Bool);
+            ctx.emit(&format!("br i1 {}, label %{}, label %{}", cond, lb, le));
 
-                ctx.emit_label(&lc);
-                let ci = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", ci, idx_ptr));
-                let cc = ctx.tmp(); ctx.emit(&format!("{} = icmp slt i64 {}, {}", cc, ci, len_i));
-                ctx.emit(&format!("br i1 {}, label %{}, label %{}", cc, lb, le));
+            // Cuerpo: let var = iter.current() in body
+            ctx.emit_label(&lb);
+            ctx.enter_scope();
+            let (cur, cur_ty) = dispatch_method(ctx, Some(&cls), current, &op, &[], expr.pos);
+            let cur = ctx.coerce(&cur, &cur_ty, &elem_ty);
+            ctx.alloca_var(var, &cur, elem_ty);
 
-                ctx.emit_label(&lb);
-                ctx.enter_scope();
-                let ci2 = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", ci2, idx_ptr));
-                let off = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, 1", off, ci2));
-                let ep = ctx.tmp(); ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", ep, vp, off));
-                let ev = ctx.tmp(); ctx.emit(&format!("{} = load double, double* {}", ev, ep));
-                let vp2 = ctx.tmp(); ctx.emit(&format!("{} = alloca double", vp2));
-                ctx.emit(&format!("store double {}, double* {}", ev, vp2));
-                ctx.def_var(var, &vp2, ValTy::Num);
-
-                gen_expr(ctx, body);
-
-                let ni = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", ni, idx_ptr));
-                let ni2 = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, 1", ni2, ni));
-                ctx.emit(&format!("store i64 {}, i64* {}", ni2, idx_ptr));
-                ctx.exit_scope();
-                ctx.emit(&format!("br label %{}", lc));
+            gen_loop_body(ctx, body, &lc, &le, &result);
+            ctx.exit_scope();
+            ctx.emit(&format!("br label %{}", lc));
 
-                ctx.emit_label(&le);
-                "0.0".into()
-            }
+            ctx.emit_label(&le);
+            load_loop_result(ctx, &result)
         }
 
-        //  Let (Enlace de variables) 
+        //  Let (Enlace de variables)
         Expr::Let { bindings, body } => {
             ctx.enter_scope();
-            for (name, _ann, init_expr) in bindings {
-                let vty = infer_val_ty(ctx, &init_expr.node);
-                let v = gen_expr(ctx, init_expr);
-                let p = ctx.tmp();
-                ctx.emit(&format!("{} = alloca double", p));
-                ctx.emit(&format!("store double {}, double* {}", v, p));
-                ctx.def_var(name, &p, vty);
+            for (name, ann, init_expr) in bindings {
+                let (v, ty) = gen_expr(ctx, init_expr);
+                let var_ty = if ann.is_some() { ValTy::from_annotation(ctx.sem, ann) } else { ty.clone() };
+                let v = ctx.coerce(&v, &ty, &var_ty);
+                ctx.alloca_var(name, &v, var_ty);
             }
             let res = gen_expr(ctx, body);
             ctx.exit_scope();
             res
         }
 
-        // Asignación 
+        // Asignación
         Expr::Assignment { target, value } => {
-            let v = gen_expr(ctx, value);
-            if let Some((ptr, _)) = ctx.get_var(target) {
-                ctx.emit(&format!("store double {}, double* {}", v, ptr));
+            let (v, ty) = gen_expr(ctx, value);
+            if let Some((ptr, var_ty)) = ctx.get_var(target) {
+                let c = ctx.coerce(&v, &ty, &var_ty);
+                ctx.emit(&format!("store {} {}, {}* {}", var_ty.llvm(), c, var_ty.llvm(), ptr));
+                return (c, var_ty);
             }
-            v
+            (v, ty)
         }
 
-        //  Asignación a atributo (obj.attr := expr) 
+        //  Asignación a atributo (obj.attr := expr)
         Expr::AttributeAssignment { obj, attribute, value } => {
-            let v = gen_expr(ctx, value);
-            // Determinar la clase del objeto
-            let obj_val = gen_expr(ctx, obj);
-            let op = ctx.decode_ptr(&obj_val, "i8*");
-            let cls_name = resolve_obj_class_from_expr(ctx, &obj.node);
-            if let Some(cls) = cls_name {
-                if let Some(layout) = ctx.classes.get(&cls) {
-                    if let Some(&idx) = layout.attr_indices.get(attribute.as_str()) {
-                        let sn = layout.struct_name.clone();
-                        let cast = ctx.tmp();
-                        ctx.emit(&format!("{} = bitcast i8* {} to {}*", cast, op, sn));
-                        let gep = ctx.tmp();
-                        ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
-                            gep, sn, sn, cast, idx));
-                        ctx.emit(&format!("store double {}, double* {}", v, gep));
-                    }
-                }
+            let (v, ty) = gen_expr(ctx, value);
+            let (ov, oty) = gen_expr(ctx, obj);
+            let cls = static_class(ctx, &obj.node, &oty);
+            if let Some((sn, idx, field_ty)) = find_field(ctx, cls.as_deref(), attribute) {
+                let op = ctx.coerce(&ov, &oty, &ValTy::Obj(String::new()));
+                let gep = field_ptr(ctx, &op, &sn, idx);
+                let c = ctx.coerce(&v, &ty, &field_ty);
+                ctx.store_field(&c, &gep, &field_ty);
+                return (c, field_ty);
             }
-            v
+            (v, ty)
         }
 
-        //  Bloque 
+        //  Bloque
         Expr::Block(exprs) => {
-            let mut last = "0.0".to_string();
+            let mut last = ("0.0".to_string(), ValTy::Num);
             for e in exprs { last = gen_expr(ctx, e); }
             last
         }
 
-        // función 
+        // función
         Expr::Call { func, args } => {
             if func == "print" {
                 gen_print(ctx, args);
-                return "0.0".into();
+                return ("0.0".into(), ValTy::Num);
             }
-            let mut vals = Vec::new();
-            for a in args { vals.push(gen_expr(ctx, a)); }
 
-            // Verificar si 'func' es una variable (lambda/clausura) en el ámbito
+            // Verificar si 'func' es una variable (lambda/clausura/functor) en el ámbito
             if let Some((ptr, vty)) = ctx.get_var(func) {
-                // Llamada functor: si la variable contiene un objeto de tipo protocolo con invoke,
-                // usar función dispatch
-                if let ValTy::Obj(ref type_name) = vty {
-                    // Verificar si este tipo es un protocolo con implementaciones registradas
-                    if ctx.protocol_implementations.contains_key(type_name) {
-                        let ov = ctx.tmp();
-                        ctx.emit(&format!("{} = load double, double* {}", ov, ptr));
-                        let op = ctx.decode_ptr(&ov, "i8*");
-                        
-                        let mut arg_s = format!("i8* {}", op);
-                        for v in &vals {
-                            arg_s.push_str(&format!(", double {}", v));
-                        }
-                        let r = ctx.tmp();
-                        ctx.emit(&format!("{} = call double @{}_dispatch({})", r, type_name, arg_s));
-                        return r;
-                    }
+                let mut vals = Vec::new();
+                for a in args { vals.push(gen_expr(ctx, a)); }
+                let fv = ctx.tmp();
+                ctx.emit(&format!("{} = load {}, {}* {}", fv, vty.llvm(), vty.llvm(), ptr));
+
+                // Functor de una clase conocida: llamar a su invoke
+                if let ValTy::Obj(ref cls) = vty
+                    && ctx.classes.get(cls).is_some_and(|l| l.method_names.contains_key("invoke"))
+                {
+                    return dispatch_method(ctx, Some(cls), "invoke", &fv, &vals, expr.pos);
                 }
 
-                // Llamada a clausura: la variable contiene un puntero double-encoded a [fn_ptr, env_ptr]
-                let cval = ctx.tmp();
-                ctx.emit(&format!("{} = load double, double* {}", cval, ptr));
-                let cp = ctx.decode_ptr(&cval, "double*");
+                // Functor tipado con un protocolo: su thunk de invoke elige la implementación
+                if let ValTy::Boxed(ref proto) = vty
+                    && ctx.protocols.get(proto).is_some_and(|p| p.methods.contains_key("invoke"))
+                {
+                    let op = ctx.unbox(&fv, &ValTy::Obj(String::new()));
+                    return dispatch_method(ctx, Some(proto), "invoke", &op, &vals, expr.pos);
+                }
+
+                // Llamada a clausura: la variable apunta a un par [fn_ptr, env_ptr] de doubles.
+                // Las lambdas reciben y retornan valores empaquetados.
+                let ret = match &vty { ValTy::Fn(ret) => (**ret).clone(), _ => ValTy::object() };
+                let craw = ctx.coerce(&fv, &vty, &ValTy::Fn(Box::new(ret.clone())));
+                let cp = ctx.tmp();
+                ctx.emit(&format!("{} = bitcast i8* {} to double*", cp, craw));
                 // Cargar fn_ptr (slot 0)
                 let fp_d = ctx.tmp();
                 ctx.emit(&format!("{} = load double, double* {}", fp_d, cp));
@@ -408,165 +757,127 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
                 let env_p = ctx.decode_ptr(&env_d, "double*");
                 // Construir args: env_ptr, val0, val1, ...
                 let mut arg_s = format!("double* {}", env_p);
-                for v in &vals {
-                    arg_s.push_str(&format!(", double {}", v));
+                for (v, ty) in &vals {
+                    let b = ctx.box_value(v, ty);
+                    arg_s.push_str(&format!(", double {}", b));
                 }
                 let r = ctx.tmp();
                 ctx.emit(&format!("{} = call double {}({})", r, fp, arg_s));
-                r
+                (ctx.unbox(&r, &ret), ret)
             } else {
-                // Llamada directa a función
-                
-                // IMPLICIT FUNCTOR: Si esta es una función y el contexto indica que se espera
-                // un protocolo functor, generar wrapper automáticamente
-                let mut arg_s = String::new();
-                for (i, v) in vals.iter().enumerate() {
-                    if i > 0 { arg_s.push_str(", "); }
-                    arg_s.push_str("double "); arg_s.push_str(v);
-                }
+                // Llamada directa a función, con la firma del chequeo semántico
+                let (params, ret) = match ctx.sem.get_function(func) {
+                    Some((params, ret)) => (
+                        params.iter().map(|p| ValTy::from_type(&p.borrow())).collect(),
+                        ValTy::from_type(&ret.borrow()),
+                    ),
+                    None => (Vec::new(), ValTy::object()),
+                };
+                let arg_s = gen_args(ctx, args, &params);
                 let r = ctx.tmp();
                 let ir_func = mangle_fn(func);
-                ctx.emit(&format!("{} = call double @{}({})", r, ir_func, arg_s));
-                r
+                ctx.emit(&format!("{} = call {} @{}({})", r, ret.llvm(), ir_func, arg_s));
+                (r, ret)
             }
         }
 
-        //  Instanciación 
-        Expr::Instantiation { ty, args } => {
-            let mut vals = Vec::new();
-            for a in args { vals.push(gen_expr(ctx, a)); }
-            let mut arg_s = String::new();
-            for (i, v) in vals.iter().enumerate() {
-                if i > 0 { arg_s.push_str(", "); }
-                arg_s.push_str("double "); arg_s.push_str(v);
-            }
+        //  Instanciación (los argumentos de tipo se borran: una clase por declaración)
+        Expr::Instantiation { ty, args, type_args } if ty == "Map" => {
+            for a in args { gen_expr(ctx, a); }
+            let [key, value] = [0, 1].map(|i| ValTy::from_annotation(ctx.sem, &type_args.get(i).cloned()));
+            (gen_map_new(ctx, &key), ValTy::Map(Box::new(key), Box::new(value)))
+        }
+        Expr::Instantiation { ty, args, .. } => {
+            let params = ctx.classes.get(ty).map(|l| l.ctor_params.clone()).unwrap_or_default();
+            let arg_s = gen_args(ctx, args, &params);
             let raw = ctx.tmp();
             ctx.emit(&format!("{} = call i8* @{}_new({})", raw, ty, arg_s));
-            let pi = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", pi, raw));
-            let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, pi));
-            d
+            (raw, ValTy::Obj(ty.clone()))
         }
 
-        //  Llamada a método 
+        //  Llamada a método
         Expr::MethodCall { obj, method, args } => {
             // Resolver la clase del objeto en tiempo de compilación
-            let obj_class = resolve_obj_class_from_expr(ctx, &obj.node);
-            let ov = gen_expr(ctx, obj);
-            let op = ctx.decode_ptr(&ov, "i8*");
+            let (ov, oty) = gen_expr(ctx, obj);
+            if oty == ValTy::Str {
+                let vals: Vec<_> = args.iter().map(|a| gen_expr(ctx, a)).collect();
+                return gen_string_method(ctx, &ov, method, &vals, expr.pos);
+            }
+            if let ValTy::Map(key, value) = &oty {
+                let vals: Vec<_> = args.iter().map(|a| gen_expr(ctx, a)).collect();
+                return gen_map_method(ctx, &ov, key, value, method, &vals, expr.pos);
+            }
+            if let ValTy::Vector(elem) = &oty
+                && vector_method_sig(method, elem).is_some()
+            {
+                let vals: Vec<_> = args.iter().map(|a| gen_expr(ctx, a)).collect();
+                return gen_vector_method(ctx, &ov, elem, method, &vals, expr.pos);
+            }
+            let cls = static_class(ctx, &obj.node, &oty);
+            let op = ctx.coerce(&ov, &oty, &ValTy::Obj(String::new()));
 
             let mut vals = Vec::new();
             for a in args { vals.push(gen_expr(ctx, a)); }
 
-            let mut func_name = format!("unknown_{}", method);
-            // Primero intentar con la clase resuelta
-            if let Some(ref cls) = obj_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if let Some(fname) = layout.method_names.get(method.as_str()) {
-                        func_name = fname[1..].to_string();
-                    }
-                }
-            }
-            // Respaldo: buscar en todas las clases
-            if func_name.starts_with("unknown_") {
-                for (_, layout) in &ctx.classes {
-                    if let Some(fname) = layout.method_names.get(method.as_str()) {
-                        func_name = fname[1..].to_string();
-                        break;
-                    }
-                }
-            }
+            let (r, ret) = dispatch_method(ctx, cls.as_deref(), method, &op, &vals, expr.pos);
 
-            let mut arg_s = format!("i8* {}", op);
-            for v in &vals {
-                arg_s.push_str(&format!(", double {}", v));
+            // Los elementos de un vector viajan empaquetados
+            match oty {
+                ValTy::Vector(elem) if method == "current" || method == "get_current" => (ctx.coerce(&r, &ret, &elem), *elem),
+                _ => (r, ret),
             }
-            let r = ctx.tmp();
-            ctx.emit(&format!("{} = call double @{}({})", r, func_name, arg_s));
-            r
         }
 
-        //  Acceso a atributo 
+        //  Acceso a atributo
         Expr::AttributeAccess { obj, attribute } => {
             // Resolver clase del objeto en tiempo de compilación para layout correcto del struct
-            let obj_class = resolve_obj_class_from_expr(ctx, &obj.node);
-            let ov = gen_expr(ctx, obj);
-            let op = ctx.decode_ptr(&ov, "i8*");
-
-            let mut found_idx: Option<(u32, String)> = None;
-            // Primero intentar con la clase resuelta
-            if let Some(ref cls) = obj_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if let Some(&idx) = layout.attr_indices.get(attribute.as_str()) {
-                        found_idx = Some((idx, layout.struct_name.clone()));
-                    }
-                }
-            }
-            // Respaldo: buscar en todas las clases
-            if found_idx.is_none() {
-                for (_, layout) in &ctx.classes {
-                    if let Some(&idx) = layout.attr_indices.get(attribute.as_str()) {
-                        found_idx = Some((idx, layout.struct_name.clone()));
-                        break;
-                    }
-                }
-            }
-
-            if let Some((idx, sn)) = found_idx {
-                let cast = ctx.tmp(); ctx.emit(&format!("{} = bitcast i8* {} to {}*", cast, op, sn));
-                let gep = ctx.tmp(); ctx.emit(&format!("{} = getelementptr inbounds {}, {}* {}, i32 0, i32 {}",
-                    gep, sn, sn, cast, idx));
-                let v = ctx.tmp(); ctx.emit(&format!("{} = load double, double* {}", v, gep));
-                v
+            let (ov, oty) = gen_expr(ctx, obj);
+            let cls = static_class(ctx, &obj.node, &oty);
+            if let Some((sn, idx, field_ty)) = find_field(ctx, cls.as_deref(), attribute) {
+                let op = ctx.coerce(&ov, &oty, &ValTy::Obj(String::new()));
+                let gep = field_ptr(ctx, &op, &sn, idx);
+                (ctx.load_field(&gep, &field_ty), field_ty)
             } else {
                 ctx.emit(&format!("; WARNING: attribute '{}' not found", attribute));
-                "0.0".into()
+                ("0.0".into(), ValTy::Num)
             }
         }
 
-        //  Llamada base (llama al constructor o método del padre) 
+        //  Llamada base (llama al constructor o método del padre)
         Expr::BaseCall { args } => {
             // Resolver la clase padre desde current_class
             let parent_info = ctx.current_class.as_ref().and_then(|cls| {
-                ctx.classes.get(cls).and_then(|layout| {
-                    layout.parent_name.as_ref().map(|pn| pn.clone())
-                })
+                ctx.classes.get(cls).and_then(|layout| layout.parent_name.clone())
             });
 
             if let Some(parent_class) = parent_info {
-                // Evaluar argumentos
-                let mut vals = Vec::new();
-                for a in args { vals.push(gen_expr(ctx, a)); }
-                let mut arg_s = String::new();
-                for (i, v) in vals.iter().enumerate() {
-                    if i > 0 { arg_s.push_str(", "); }
-                    arg_s.push_str("double "); arg_s.push_str(v);
-                }
                 // Llamar al constructor del padre
+                let params = ctx.classes.get(&parent_class).map(|l| l.ctor_params.clone()).unwrap_or_default();
+                let arg_s = gen_args(ctx, args, &params);
                 let raw = ctx.tmp();
                 ctx.emit(&format!("{} = call i8* @{}_new({})", raw, parent_class, arg_s));
-                let pi = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", pi, raw));
-                let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, pi));
-                d
+                (raw, ValTy::Obj(parent_class))
             } else {
                 for a in args { gen_expr(ctx, a); }
                 ctx.emit("; BaseCall – no parent class found");
-                "0.0".into()
+                ("0.0".into(), ValTy::Num)
             }
         }
 
-        //  Lambda (con captura de clausura) 
+        //  Lambda (con captura de clausura)
         Expr::Lambda { params, body, .. } => {
             //  Identificar variables libres en el cuerpo que no son parámetros de la lambda
             let param_names: Vec<String> = params.iter().map(|p| p.name.clone()).collect();
             let free_vars = collect_free_vars(body, &param_names);
 
-            // Capturar valores actuales de variables libres
-            let mut captured: Vec<(String, String)> = Vec::new(); // (nombre, valor_actual)
+            // Capturar valores actuales de variables libres, empaquetados
+            let mut captured: Vec<(String, String, ValTy)> = Vec::new(); // (nombre, valor, tipo)
             for fv in &free_vars {
-                if let Some((ptr, _)) = ctx.get_var(fv) {
+                if let Some((ptr, ty)) = ctx.get_var(fv) {
                     let val = ctx.tmp();
-                    ctx.emit(&format!("{} = load double, double* {}", val, ptr));
-                    captured.push((fv.clone(), val));
+                    ctx.emit(&format!("{} = load {}, {}* {}", val, ty.llvm(), ty.llvm(), ptr));
+                    let boxed = ctx.box_value(&val, &ty);
+                    captured.push((fv.clone(), boxed, ty));
                 }
             }
 
@@ -575,11 +886,12 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
             let env_ptr = if env_size > 0 {
                 let bytes = env_size as u64 * 8;
                 let raw = ctx.tmp();
-                ctx.emit(&format!("{} = call i8* @malloc(i64 {})", raw, bytes));
+                ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", raw, bytes, GC_VALUES));
+                ctx.root_ptr(&raw, "i8*");
                 let arr = ctx.tmp();
                 ctx.emit(&format!("{} = bitcast i8* {} to double*", arr, raw));
                 // Almacenar valores capturados
-                for (i, (_, val)) in captured.iter().enumerate() {
+                for (i, (_, val, _)) in captured.iter().enumerate() {
                     let gep = ctx.tmp();
                     ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", gep, arr, i));
                     ctx.emit(&format!("store double {}, double* {}", val, gep));
@@ -598,32 +910,31 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
 
             // Guardar buffer de funciones actual e iniciar uno nuevo para la lambda
             let saved_functions = std::mem::take(&mut ctx.functions);
-            ctx.functions.push_str(&format!("define double @{}({}) {{\nentry:\n", fname, sig));
+            let outer = ctx.begin_function(&format!("define double @{}({})", fname, sig), ValTy::object());
             ctx.enter_scope();
 
-            // Cargar variables capturadas desde env
-            for (i, (name, _)) in captured.iter().enumerate() {
+            // Cargar variables capturadas desde env, con el tipo que tenían afuera
+            for (i, (name, _, ty)) in captured.iter().enumerate() {
                 let gep = ctx.tmp();
                 ctx.emit(&format!("{} = getelementptr double, double* %__env, i64 {}", gep, i));
                 let val = ctx.tmp();
                 ctx.emit(&format!("{} = load double, double* {}", val, gep));
-                let ptr = ctx.tmp();
-                ctx.emit(&format!("{} = alloca double", ptr));
-                ctx.emit(&format!("store double {}, double* {}", val, ptr));
-                ctx.def_var(name, &ptr, ValTy::Num);
+                let val = ctx.unbox(&val, ty);
+                ctx.alloca_var(name, &val, ty.clone());
             }
 
-            // Parámetros de la lambda
-            for p in params {
-                let ptr = ctx.tmp();
-                ctx.emit(&format!("{} = alloca double", ptr));
-                ctx.emit(&format!("store double %{}, double* {}", p.name, ptr));
-                ctx.def_var(&p.name, &ptr, val_ty_from_annotation(&p.type_annotation));
+            // Parámetros de la lambda, con los tipos anotados o inferidos
+            let param_tys = ctx.sem.lambda_types.get(&expr.span()).cloned().unwrap_or_default();
+            for (i, p) in params.iter().enumerate() {
+                let ty = param_tys.get(i)
+                    .map_or_else(|| ValTy::from_annotation(ctx.sem, &p.type_annotation), |t| ValTy::from_type(&t.borrow()));
+                let val = ctx.unbox(&format!("%{}", p.name), &ty);
+                ctx.alloca_var(&p.name, &val, ty);
             }
 
-            let r = gen_expr(ctx, body);
-            ctx.emit(&format!("ret double {}", r));
-            ctx.functions.push_str("}\n\n");
+            let (r, ret_ty) = gen_expr(ctx, body);
+            let r = ctx.box_value(&r, &ret_ty);
+            ctx.end_function(&format!("ret double {}", r), outer);
             ctx.exit_scope();
 
             // Mover la definición lambda a lambda_defs y restaurar el buffer del llamador
@@ -633,7 +944,7 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
             //  Codificar clausura como par: { fn_ptr, env_ptr } empaquetado en dos doubles
             // Por simplicidad empaquetamos [fn_ptr_as_double, env_ptr_as_double] en un buffer en el heap
             let closure_buf = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @malloc(i64 16)", closure_buf)); // 2 * 8 bytes
+            ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 16, i32 {})", closure_buf, GC_VALUES)); // 2 * 8 bytes
             let closure_arr = ctx.tmp();
             ctx.emit(&format!("{} = bitcast i8* {} to double*", closure_arr, closure_buf));
 
@@ -661,214 +972,140 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
             ctx.emit(&format!("{} = getelementptr double, double* {}, i64 1", env_slot, closure_arr));
             ctx.emit(&format!("store double {}, double* {}", env_val, env_slot));
 
-            // Retornar buffer de clausura como double
-            let pi = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint double* {} to i64", pi, closure_arr));
-            let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, pi));
-            d
+            (closure_buf, ValTy::Fn(Box::new(ret_ty)))
         }
 
-        //  Is (verificación de tipo en tiempo de ejecución) 
+        //  Is (verificación de tipo en tiempo de ejecución)
         Expr::Is(expr, type_name) => {
-            let v = gen_expr(ctx, expr);
-            // Buscar el type-id del tipo destino y todos sus descendientes
-            if let Some(target_layout) = ctx.classes.get(type_name.as_str()) {
-                let target_tid = target_layout.type_id;
-                // Recopilar destino + todos los type-ids hijos
-                let mut valid_ids = vec![target_tid];
-                // Recorrer todas las clases y verificar si heredan de type_name
-                for (_, layout) in &ctx.classes {
-                    if layout.type_id != target_tid && class_inherits_from(&ctx.classes, layout, type_name) {
-                        valid_ids.push(layout.type_id);
-                    }
-                }
-
-                // Decodificar puntero de objeto y leer type-id desde slot 0
-                let op = ctx.decode_ptr(&v, "i64*");
-                let tid = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", tid, op));
-
-                // Verificar si tid coincide con algún id válido usando select acumulativo
-                let mut current = "0.0".to_string();
-                for vid in &valid_ids {
-                    let c = ctx.tmp();
-                    ctx.emit(&format!("{} = icmp eq i64 {}, {}", c, tid, vid));
-                    let next = ctx.tmp();
-                    ctx.emit(&format!("{} = select i1 {}, double 1.0, double {}", next, c, current));
-                    current = next;
-                }
-
-                current
-            } else {
-                // Tipo no encontrado en clases – verificar primitivos
-                // Para Number/Boolean/String no podemos verificar en runtime (todo es double)
-                // Retornar 1.0 como mejor esfuerzo
-                gen_expr(ctx, expr);
-                "1.0".into()
-            }
-        }
-
-        //  As (cast de tipo con verificación en runtime) 
-        Expr::As(expr, type_name) => {
-            let v = gen_expr(ctx, expr);
-            // Si el tipo tiene un type-id, verificar y abortar en caso de fallo
-            if let Some(target_layout) = ctx.classes.get(type_name.as_str()) {
-                let target_tid = target_layout.type_id;
-                let mut valid_ids = vec![target_tid];
-                for (_, layout) in &ctx.classes {
-                    if layout.type_id != target_tid && class_inherits_from(&ctx.classes, layout, type_name) {
-                        valid_ids.push(layout.type_id);
-                    }
-                }
-
-                let op = ctx.decode_ptr(&v, "i64*");
-                let tid = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", tid, op));
-                let mut any = None;
-                for vid in &valid_ids {
-                    let c = ctx.tmp();
-                    ctx.emit(&format!("{} = icmp eq i64 {}, {}", c, tid, vid));
-                    any = Some(if let Some(prev) = any {
-                        let combined = ctx.tmp();
-                        ctx.emit(&format!("{} = or i1 {}, {}", combined, prev, c));
-                        combined
-                    } else {
-                        c
-                    });
-                }
-
-                if let Some(ok_cond) = any {
-                    let ok_lbl = ctx.lbl("as_ok");
-                    let fail_lbl = ctx.lbl("as_fail");
-                    ctx.emit(&format!("br i1 {}, label %{}, label %{}", ok_cond, ok_lbl, fail_lbl));
-                    ctx.emit_label(&fail_lbl);
-                    // Imprimir error y abortar
-                    let msg_id = ctx.add_global_string("Runtime error: invalid cast");
-                    let msg_len = "Runtime error: invalid cast".len() + 1;
-                    let msg_gep = ctx.gep_string(&msg_id, msg_len);
-                    ctx.emit(&format!("call i32 @puts(i8* {})", msg_gep));
-                    ctx.emit("call void @abort()");
-                    ctx.emit("unreachable");
-                    ctx.emit_label(&ok_lbl);
-                }
+            let (v, ty) = gen_expr(ctx, expr);
+            (type_test(ctx, &v, &ty, type_name), ValTy::Bool)
+        }
+
+        //  As (cast de tipo con verificación en runtime)
+        Expr::As(inner, type_name) => {
+            let (v, ty) = gen_expr(ctx, inner);
+            // Verificar como `is` (en ejecución si hace falta) y lanzar
+            // `CastError` si falla; después solo cambia la representación
+            let ok_cond = type_test(ctx, &v, &ty, type_name);
+            if ok_cond != "true" {
+                let ok_lbl = ctx.lbl("as_ok");
+                let fail_lbl = ctx.lbl("as_fail");
+                ctx.emit(&format!("br i1 {}, label %{}, label %{}", ok_cond, ok_lbl, fail_lbl));
+                ctx.emit_label(&fail_lbl);
+                runtime_throw(ctx, "CastError", &cast_message(type_name), expr.pos);
+                ctx.emit_label(&ok_lbl);
             }
-            // Pasar el valor (sigue siendo el mismo objeto)
-            v
+            let target = match class_type_ids(ctx, type_name) {
+                Some(_) => ValTy::Obj(type_name.clone()),
+                None => sem_val_ty(ctx, type_name),
+            };
+            (ctx.coerce(&v, &ty, &target), target)
         }
 
-        //  Literal de vector 
+        //  Literal de vector
         Expr::VectorLiteral(elems) => {
             let count = elems.len();
-            // Crear array de datos: [elem0, elem1, ...]
+            // Crear array de datos: [elem0, elem1, ...], empaquetados
             let data_bytes = count as u64 * 8;
             let raw = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @malloc(i64 {})", raw, data_bytes));
+            ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", raw, data_bytes, GC_VALUES));
+            ctx.root_ptr(&raw, "i8*");
             let arr = ctx.tmp();
             ctx.emit(&format!("{} = bitcast i8* {} to double*", arr, raw));
-            
+
+            let mut elem_ty: Option<ValTy> = None;
             for (i, e) in elems.iter().enumerate() {
-                let v = gen_expr(ctx, e);
+                let (v, ty) = gen_expr(ctx, e);
+                let v = ctx.box_value(&v, &ty);
                 let gep = ctx.tmp();
                 ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", gep, arr, i));
                 ctx.emit(&format!("store double {}, double* {}", v, gep));
+                elem_ty = Some(match elem_ty { Some(prev) => ctx.join(&prev, &ty), None => ty });
             }
-            
+
             // Crear objeto __Vector con el array
             let len = fmt_double(count as f64);
             let obj = ctx.tmp();
             ctx.emit(&format!("{} = call i8* @__Vector_new(double* {}, double {})", obj, arr, len));
-            
-            // Retornar como double (puntero encoded)
-            let pi = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", pi, obj));
-            let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, pi));
-            d
+            (obj, ValTy::Vector(Box::new(elem_ty.unwrap_or_else(ValTy::object))))
+        }
+
+        //  Literal de diccionario: los pares se generan antes de crear el
+        //  diccionario, que necesita saber si las claves son cadenas
+        Expr::MapLiteral(entries) => {
+            let mut pairs = Vec::new();
+            let (mut key, mut value): (Option<ValTy>, Option<ValTy>) = (None, None);
+            for (k, v) in entries {
+                let (kv, kt) = gen_expr(ctx, k);
+                let (vv, vt) = gen_expr(ctx, v);
+                key = Some(match key { Some(prev) => ctx.join(&prev, &kt), None => kt.clone() });
+                value = Some(match value { Some(prev) => ctx.join(&prev, &vt), None => vt.clone() });
+                pairs.push((kv, kt, vv, vt));
+            }
+            let key = key.unwrap_or_else(ValTy::object);
+            let value = value.unwrap_or_else(ValTy::object);
+            let m = gen_map_new(ctx, &key);
+            ctx.root_ptr(&m, "i8*");
+            for (kv, kt, vv, vt) in pairs {
+                let kv = ctx.coerce(&kv, &kt, &key);
+                let vv = ctx.coerce(&vv, &vt, &value);
+                gen_map_set(ctx, &m, &kv, &key, &vv, &value);
+            }
+            (m, ValTy::Map(Box::new(key), Box::new(value)))
         }
 
-        //  VectorGenerator [expr | var in iterable] 
+        //  VectorGenerator [expr | var in iterable]
         Expr::VectorGenerator { expr: elem_expr, var, iterable } => {
-            let iter_val = gen_expr(ctx, iterable);
-            let iter_class = resolve_obj_class_from_expr(ctx, &iterable.node);
-            let iter_ptr = ctx.decode_ptr(&iter_val, "i8*");
-            
-            // Resolver nombres de métodos next() y get_current()
-            let mut next_fn = "unknown_next".to_string();
-            let mut get_current_fn = "unknown_get_current".to_string();
-            
-            if let Some(ref cls) = iter_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if let Some(fname) = layout.method_names.get("next") {
-                        next_fn = fname[1..].to_string();
-                    }
-                    if let Some(fname) = layout.method_names.get("get_current") {
-                        get_current_fn = fname[1..].to_string();
-                    }
-                }
-            }
-            
-            // Respaldo: buscar en todas las clases
-            if next_fn.starts_with("unknown_") || get_current_fn.starts_with("unknown_") {
-                for (_, layout) in &ctx.classes {
-                    if next_fn.starts_with("unknown_") {
-                        if let Some(fname) = layout.method_names.get("next") {
-                            next_fn = fname[1..].to_string();
-                        }
-                    }
-                    if get_current_fn.starts_with("unknown_") {
-                        if let Some(fname) = layout.method_names.get("get_current") {
-                            get_current_fn = fname[1..].to_string();
-                        }
-                    }
-                }
-            }
-            
+            let (iter_val, iter_ty) = gen_expr(ctx, iterable);
+            let (cls, current, var_ty) = iterable_class(ctx, iterable, &iter_ty);
+            let iter_ptr = ctx.coerce(&iter_val, &iter_ty, &ValTy::Obj(String::new()));
+
             // Crear vector temporal con capacidad inicial
             let cap_est = 100i64;
             let bytes_est = ctx.tmp(); ctx.emit(&format!("{} = mul i64 {}, 8", bytes_est, cap_est + 1));
-            let raw = ctx.tmp(); ctx.emit(&format!("{} = call i8* @malloc(i64 {})", raw, bytes_est));
+            let raw = ctx.tmp(); ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", raw, bytes_est, GC_VALUES));
             let dp = ctx.tmp(); ctx.emit(&format!("{} = bitcast i8* {} to double*", dp, raw));
-            
+
             // Contadores
             let count_ptr = ctx.tmp(); ctx.emit(&format!("{} = alloca i64", count_ptr));
             ctx.emit(&format!("store i64 0, i64* {}", count_ptr));
             let cap_ptr = ctx.tmp(); ctx.emit(&format!("{} = alloca i64", cap_ptr));
             ctx.emit(&format!("store i64 {}, i64* {}", cap_est, cap_ptr));
-            
-            // Puntero al vector (puede cambiar con realloc)
-            let dp_ptr = ctx.tmp(); ctx.emit(&format!("{} = alloca double*", dp_ptr));
-            ctx.emit(&format!("store double* {}, double** {}", dp, dp_ptr));
-            
+
+            // Puntero a los datos (cambia al crecer); su slot es también la raíz del buffer
+            let dp_ptr = ctx.root_ptr(&dp, "double*");
+
             // Loop: while (iter.next())
             let lc = ctx.lbl("vgc"); let lb = ctx.lbl("vgb"); let le = ctx.lbl("vge");
             ctx.emit(&format!("br label %{}", lc));
-            
+
             ctx.emit_label(&lc);
             // Llamar iter.next()
-            let has_next = ctx.tmp();
-            ctx.emit(&format!("{} = call double @{}(i8* {})", has_next, next_fn, iter_ptr));
-            let cond = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", cond, has_next));
+            let (has_next, next_ty) = dispatch_method(ctx, Some(&cls), "next", &iter_ptr, &[], expr.pos);
+            let cond = ctx.coerce(&has_next, &next_ty, &ValTy::Bool);
             ctx.emit(&format!("br i1 {}, label %{}, label %{}", cond, lb, le));
-            
+
             ctx.emit_label(&lb);
             ctx.enter_scope();
-            
-            // Llamar iter.get_current()
-            let current_val = ctx.tmp();
-            ctx.emit(&format!("{} = call double @{}(i8* {})", current_val, get_current_fn, iter_ptr));
-            
-            // Definir variable del loop
-            let vp = ctx.tmp(); ctx.emit(&format!("{} = alloca double", vp));
-            ctx.emit(&format!("store double {}, double* {}", current_val, vp));
-            ctx.def_var(var, &vp, ValTy::Num);
-            
+
+            // Llamar iter.current() y definir la variable del loop
+            let (current_val, current_ty) = dispatch_method(ctx, Some(&cls), current, &iter_ptr, &[], expr.pos);
+            let current_val = ctx.coerce(&current_val, &current_ty, &var_ty);
+            ctx.alloca_var(var, &current_val, var_ty);
+
             // Evaluar expresión
-            let mapped = gen_expr(ctx, elem_expr);
-            
+            let (mapped, mapped_ty) = gen_expr(ctx, elem_expr);
+            let mapped = ctx.box_value(&mapped, &mapped_ty);
+
             // Verificar capacidad
             let cnt = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", cnt, count_ptr));
             let cap = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", cap, cap_ptr));
             let need_grow = ctx.tmp(); ctx.emit(&format!("{} = icmp sge i64 {}, {}", need_grow, cnt, cap));
-            
+
             let grow_lbl = ctx.lbl("vg_grow");
             let store_lbl = ctx.lbl("vg_store");
             ctx.emit(&format!("br i1 {}, label %{}, label %{}", need_grow, grow_lbl, store_lbl));
-            
+
             // Expandir
             ctx.emit_label(&grow_lbl);
             let new_cap = ctx.tmp(); ctx.emit(&format!("{} = mul i64 {}, 2", new_cap, cap));
@@ -876,185 +1113,236 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
             let new_bytes2 = ctx.tmp(); ctx.emit(&format!("{} = mul i64 {}, 8", new_bytes2, new_bytes));
             let old_dp = ctx.tmp(); ctx.emit(&format!("{} = load double*, double** {}", old_dp, dp_ptr));
             let old_dp_i8 = ctx.tmp(); ctx.emit(&format!("{} = bitcast double* {} to i8*", old_dp_i8, old_dp));
-            let new_raw = ctx.tmp(); ctx.emit(&format!("{} = call i8* @realloc(i8* {}, i64 {})", new_raw, old_dp_i8, new_bytes2));
+            let old_bytes = ctx.tmp(); ctx.emit(&format!("{} = mul i64 {}, 8", old_bytes, cap));
+            let old_bytes2 = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, 8", old_bytes2, old_bytes));
+            let new_raw = ctx.tmp(); ctx.emit(&format!("{} = call i8* @__hulk_gc_alloc(i64 {}, i32 {})", new_raw, new_bytes2, GC_VALUES));
+            // El buffer viejo sigue en su slot durante la asignación; luego queda como basura
+            ctx.emit(&format!("call i8* @memcpy(i8* {}, i8* {}, i64 {})", new_raw, old_dp_i8, old_bytes2));
             let new_dp = ctx.tmp(); ctx.emit(&format!("{} = bitcast i8* {} to double*", new_dp, new_raw));
             ctx.emit(&format!("store double* {}, double** {}", new_dp, dp_ptr));
             ctx.emit(&format!("store i64 {}, i64* {}", new_cap, cap_ptr));
             ctx.emit(&format!("br label %{}", store_lbl));
-            
+
             // Guardar elemento
             ctx.emit_label(&store_lbl);
             let cur_dp = ctx.tmp(); ctx.emit(&format!("{} = load double*, double** {}", cur_dp, dp_ptr));
-            let off = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, 1", off, cnt));
-            let elem_ptr = ctx.tmp(); ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", elem_ptr, cur_dp, off));
+            let elem_ptr = ctx.tmp(); ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", elem_ptr, cur_dp, cnt));
             ctx.emit(&format!("store double {}, double* {}", mapped, elem_ptr));
-            
+
             // Incrementar contador
             let new_cnt = ctx.tmp(); ctx.emit(&format!("{} = add i64 {}, 1", new_cnt, cnt));
             ctx.emit(&format!("store i64 {}, i64* {}", new_cnt, count_ptr));
-            
+
             ctx.exit_scope();
             ctx.emit(&format!("br label %{}", lc));
-            
+
             ctx.emit_label(&le);
             // Crear objeto __Vector con los datos recolectados
             let final_cnt = ctx.tmp(); ctx.emit(&format!("{} = load i64, i64* {}", final_cnt, count_ptr));
             let final_cnt_d = ctx.tmp(); ctx.emit(&format!("{} = sitofp i64 {} to double", final_cnt_d, final_cnt));
             let final_dp = ctx.tmp(); ctx.emit(&format!("{} = load double*, double** {}", final_dp, dp_ptr));
-            
+
             // Crear objeto Vector
             let obj = ctx.tmp();
             ctx.emit(&format!("{} = call i8* @__Vector_new(double* {}, double {})", obj, final_dp, final_cnt_d));
-            
-            // Retornar como double
-            let pi = ctx.tmp(); ctx.emit(&format!("{} = ptrtoint i8* {} to i64", pi, obj));
-            let d = ctx.tmp(); ctx.emit(&format!("{} = bitcast i64 {} to double", d, pi));
-            d
+            (obj, ValTy::Vector(Box::new(mapped_ty)))
         }
 
-        //  Indexing (with bounds checking) 
+        //  Indexing (with bounds checking)
         Expr::Indexing { obj, index } => {
-            let ov = gen_expr(ctx, obj);
-            let iv = gen_expr(ctx, index);
-            
-            // Decodificar objeto Vector
-            let obj_ptr = ctx.decode_ptr(&ov, "i8*");
-            let vec_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = bitcast i8* {} to %__Vector*", vec_ptr, obj_ptr));
-            
-            // Cargar longitud del vector
-            let len_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = getelementptr inbounds %__Vector, %__Vector* {}, i32 0, i32 2", len_ptr, vec_ptr));
-            let len_d = ctx.tmp();
-            ctx.emit(&format!("{} = load double, double* {}", len_d, len_ptr));
-            let len_i = ctx.tmp();
-            ctx.emit(&format!("{} = fptosi double {} to i64", len_i, len_d));
-            
-            // Convertir índice a entero
-            let ii = ctx.tmp();
-            ctx.emit(&format!("{} = fptosi double {} to i64", ii, iv));
-            
-            //  Verificación de límites: 0 <= ii < len 
-            let neg_check = ctx.tmp(); ctx.emit(&format!("{} = icmp slt i64 {}, 0", neg_check, ii));
-            let upper_check = ctx.tmp(); ctx.emit(&format!("{} = icmp sge i64 {}, {}", upper_check, ii, len_i));
-            let oob = ctx.tmp(); ctx.emit(&format!("{} = or i1 {}, {}", oob, neg_check, upper_check));
-
-            let ok_lbl = ctx.lbl("idx_ok");
-            let fail_lbl = ctx.lbl("idx_oob");
-            ctx.emit(&format!("br i1 {}, label %{}, label %{}", oob, fail_lbl, ok_lbl));
-
-            ctx.emit_label(&fail_lbl);
-            ctx.emit("call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([36 x i8], [36 x i8]* @.oob_msg, i64 0, i64 0))");
-            ctx.emit("call void @abort()");
-            ctx.emit("unreachable");
-
-            ctx.emit_label(&ok_lbl);
-            // Cargar data pointer del vector
-            let data_ptr_loc = ctx.tmp();
-            ctx.emit(&format!("{} = getelementptr inbounds %__Vector, %__Vector* {}, i32 0, i32 1", data_ptr_loc, vec_ptr));
-            let data_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = load double*, double** {}", data_ptr, data_ptr_loc));
-            
-            // Acceder al elemento
-            let elem_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = getelementptr double, double* {}, i64 {}", elem_ptr, data_ptr, ii));
+            let (obj_ptr, oty) = gen_expr(ctx, obj);
+            let obj_ptr = ctx.coerce(&obj_ptr, &oty, &ValTy::Obj(String::new()));
+            let iv = gen_expr_as(ctx, index, &ValTy::Num);
+
+            // Acceder al elemento (empaquetado) y llevarlo al tipo del vector
+            let elem_ptr = gen_element_ptr(ctx, &obj_ptr, &iv, expr.pos);
             let v = ctx.tmp();
             ctx.emit(&format!("{} = load double, double* {}", v, elem_ptr));
-            v
-        }
-
-        //  Match (Coincidencia de patrones) 
+            let elem_ty = match oty { ValTy::Vector(elem) => *elem, _ => ValTy::object() };
+            (ctx.unbox(&v, &elem_ty), elem_ty)
+        }
+
+        //  Asignación a un elemento (v[i] := expr), con los mismos límites
+        Expr::IndexAssignment { obj, index, value } => {
+            let (obj_ptr, oty) = gen_expr(ctx, obj);
+            let obj_ptr = ctx.coerce(&obj_ptr, &oty, &ValTy::Obj(String::new()));
+            let iv = gen_expr_as(ctx, index, &ValTy::Num);
+            let (v, ty) = gen_expr(ctx, value);
+            let elem_ty = match oty { ValTy::Vector(elem) => *elem, _ => ValTy::object() };
+            let v = ctx.coerce(&v, &ty, &elem_ty);
+            let boxed = ctx.box_value(&v, &elem_ty);
+            let elem_ptr = gen_element_ptr(ctx, &obj_ptr, &iv, expr.pos);
+            ctx.emit(&format!("store double {}, double* {}", boxed, elem_ptr));
+            (v, elem_ty)
+        }
+
+        //  Slicing v[a:b]: vector nuevo del mismo tipo
+        Expr::Slice { obj, start, end } => {
+            let (obj_ptr, oty) = gen_expr(ctx, obj);
+            let obj_ptr = ctx.coerce(&obj_ptr, &oty, &ValTy::Obj(String::new()));
+            let a = start.as_ref().map(|s| gen_expr_as(ctx, s, &ValTy::Num));
+            let b = end.as_ref().map(|e| gen_expr_as(ctx, e, &ValTy::Num));
+            let r = gen_vector_slice(ctx, &obj_ptr, a.as_deref(), b.as_deref(), expr.pos);
+            let ty = match oty { ValTy::Vector(_) => oty, _ => ValTy::Vector(Box::new(ValTy::object())) };
+            (r, ty)
+        }
+
+        //  Match (Coincidencia de patrones)
         Expr::Match { expr: match_expr, cases, default } => {
-            let mv = gen_expr(ctx, match_expr);
-            let res_ptr = ctx.tmp();
-            ctx.emit(&format!("{} = alloca double", res_ptr));
+            let (mv, mty) = gen_expr(ctx, match_expr);
             let end_lbl = ctx.lbl("mend");
+            let mut arms = Vec::new();
+            let mut exhausted = false;
 
             for case in cases {
                 let next_lbl = ctx.lbl("mnext");
-                match &case.pattern {
-                    Pattern::Literal(lit) => {
-                        let lv = match lit {
-                            Expr::Number(n) => fmt_double(*n),
-                            Expr::Boolean(b) => if *b { "1.0".into() } else { "0.0".into() },
-                            _ => "0.0".into(),
-                        };
-                        let c = ctx.tmp();
-                        ctx.emit(&format!("{} = fcmp oeq double {}, {}", c, mv, lv));
-                        let body_lbl = ctx.lbl("mbody");
-                        ctx.emit(&format!("br i1 {}, label %{}, label %{}", c, body_lbl, next_lbl));
-                        ctx.emit_label(&body_lbl);
-                    }
-                    Pattern::Wildcard => { /* always match */ }
-                    Pattern::Variable { name, .. } => {
-                        ctx.enter_scope();
-                        let p = ctx.tmp();
-                        ctx.emit(&format!("{} = alloca double", p));
-                        ctx.emit(&format!("store double {}, double* {}", mv, p));
-                        ctx.def_var(name, &p, ValTy::Num);
-                    }
-                    _ => {
-                        ctx.emit(&format!("br label %{}", next_lbl));
-                    }
+                ctx.enter_scope();
+                gen_pattern(ctx, &case.pattern, &mv, &mty, &next_lbl);
+                if let Some(guard) = &case.guard {
+                    let g = gen_expr_as(ctx, guard, &ValTy::Bool);
+                    let body_lbl = ctx.lbl("mbody");
+                    ctx.emit(&format!("br i1 {}, label %{}, label %{}", g, body_lbl, next_lbl));
+                    ctx.emit_label(&body_lbl);
                 }
-                let cv = gen_expr(ctx, &case.expr);
-                ctx.emit(&format!("store double {}, double* {}", cv, res_ptr));
+                let (cv, cty) = gen_expr(ctx, &case.expr);
+                ctx.exit_scope();
 
-                if matches!(&case.pattern, Pattern::Variable { .. }) {
-                    ctx.exit_scope();
+                let fix = ctx.lbl("mcase_end");
+                ctx.emit(&format!("br label %{}", fix));
+                arms.push((cv, cty, fix));
+                ctx.emit_label(&next_lbl);
+                // Un caso sin guarda que encaja con todo deja muertos los siguientes
+                if case.guard.is_none() && matches!(case.pattern, Pattern::Wildcard | Pattern::Variable { type_annotation: None, .. }) {
+                    exhausted = true;
+                    break;
                 }
+            }
 
-                ctx.emit(&format!("br label %{}", end_lbl));
+            match default {
+                _ if exhausted => ctx.emit("unreachable"),
+                Some(def) => {
+                    let (dv, dty) = gen_expr(ctx, def);
+                    let fix = ctx.lbl("mdefault_end");
+                    ctx.emit(&format!("br label %{}", fix));
+                    arms.push((dv, dty, fix));
+                }
+                // El chequeo semántico garantiza que algún caso encaja
+                None => runtime_throw(ctx, "MatchError", MATCH_MESSAGE, expr.pos),
+            }
+            if arms.is_empty() {
+                ctx.emit_label(&end_lbl);
+                return ("0.0".to_string(), ValTy::Num);
+            }
+            merge_arms(ctx, arms, &end_lbl)
+        }
+
+        //  Excepciones
+        Expr::Try { body, catches } => {
+            let body_lbl = ctx.lbl("try");
+            let catch_lbl = ctx.lbl("catch");
+            let end_lbl = ctx.lbl("try_end");
+            let handler = push_handler(ctx, &body_lbl, &catch_lbl);
+
+            ctx.emit_label(&body_lbl);
+            let (bv, bty) = gen_expr(ctx, body);
+            pop_handler(ctx, &handler);
+            let fix = ctx.lbl("try_body_end");
+            ctx.emit(&format!("br label %{}", fix));
+            let mut arms = vec![(bv, bty, fix)];
+
+            ctx.emit_label(&catch_lbl);
+            let err = enter_catch(ctx, &handler);
+            ctx.root(&err, &ValTy::Obj("Error".to_string()));
+            for c in catches {
+                let class = c.ty.as_deref().unwrap_or("Error");
+                let next_lbl = ctx.lbl("catch_next");
+                let ids = class_type_ids(ctx, class).unwrap_or_default();
+                let ok = type_id_in(ctx, &err, &ids);
+                branch_or_fail(ctx, &ok, &next_lbl);
+                ctx.enter_scope();
+                ctx.alloca_var(&c.var, &err, ValTy::Obj(class.to_string()));
+                let (cv, cty) = gen_expr(ctx, &c.body);
+                ctx.exit_scope();
+                let fix = ctx.lbl("catch_end");
+                ctx.emit(&format!("br label %{}", fix));
+                arms.push((cv, cty, fix));
                 ctx.emit_label(&next_lbl);
             }
-
-            if let Some(def) = default {
-                let dv = gen_expr(ctx, def);
-                ctx.emit(&format!("store double {}, double* {}", dv, res_ptr));
-            } else {
-                ctx.emit(&format!("store double 0.0, double* {}", res_ptr));
+            // Ningún `catch` acepta el error: sigue subiendo con su posición
+            throw_error(ctx, &err, Position::default());
+            merge_arms(ctx, arms, &end_lbl)
+        }
+
+        Expr::Throw(value) => {
+            let (v, ty) = gen_expr(ctx, value);
+            let err = ctx.coerce(&v, &ty, &ValTy::Obj(String::new()));
+            throw_error(ctx, &err, expr.pos);
+            // Lo que se genere después en este bloque es inalcanzable
+            let dead = ctx.lbl("after_throw");
+            ctx.emit_label(&dead);
+            ("undef".to_string(), ValTy::Never)
+        }
+
+        // Saltos: el valor de `break` y `continue` pasa a ser el del bucle
+        Expr::Break(value) | Expr::Continue(value) => {
+            let value = value.as_ref().map(|value| gen_expr(ctx, value));
+            let Some(target) = ctx.loops.last() else { return ("undef".to_string(), ValTy::Never) };
+            let dest = if matches!(expr.node, Expr::Break(_)) { target.end.clone() } else { target.next.clone() };
+            let (depth, result) = (target.handlers, target.result.clone());
+            if let Some((v, ty)) = value {
+                store_loop_result(ctx, &v, &ty, &result);
             }
-            ctx.emit(&format!("br label %{}", end_lbl));
-
-            ctx.emit_label(&end_lbl);
-            let r = ctx.tmp();
-            ctx.emit(&format!("{} = load double, double* {}", r, res_ptr));
-            r
-        }
-
-        //  Funciones matemáticas integradas 
+            leave_handlers(ctx, depth);
+            ctx.emit(&format!("br label %{}", dest));
+            let dead = ctx.lbl("after_jump");
+            ctx.emit_label(&dead);
+            ("undef".to_string(), ValTy::Never)
+        }
+        Expr::Return(value) => {
+            let (v, ty) = gen_expr(ctx, value);
+            let ret = ctx.ret_ty.clone();
+            let v = ctx.coerce(&v, &ty, &ret);
+            leave_handlers(ctx, 0);
+            ctx.emit_return(&format!("ret {} {}", ret.llvm(), v));
+            let dead = ctx.lbl("after_return");
+            ctx.emit_label(&dead);
+            ("undef".to_string(), ValTy::Never)
+        }
+
+        //  Funciones matemáticas integradas
         Expr::Sqrt(a) => {
-            let v = gen_expr(ctx, a);
+            let v = gen_expr_as(ctx, a, &ValTy::Num);
             let r = ctx.tmp();
             ctx.emit(&format!("{} = call double @llvm.sqrt.f64(double {})", r, v));
-            r
+            (r, ValTy::Num)
         }
         Expr::Sin(a) => {
-            let v = gen_expr(ctx, a);
+            let v = gen_expr_as(ctx, a, &ValTy::Num);
             let r = ctx.tmp();
             ctx.emit(&format!("{} = call double @llvm.sin.f64(double {})", r, v));
-            r
+            (r, ValTy::Num)
         }
         Expr::Cos(a) => {
-            let v = gen_expr(ctx, a);
+            let v = gen_expr_as(ctx, a, &ValTy::Num);
             let r = ctx.tmp();
             ctx.emit(&format!("{} = call double @llvm.cos.f64(double {})", r, v));
-            r
+            (r, ValTy::Num)
         }
         Expr::Exp(a) => {
-            let v = gen_expr(ctx, a);
+            let v = gen_expr_as(ctx, a, &ValTy::Num);
             let r = ctx.tmp();
             ctx.emit(&format!("{} = call double @llvm.exp.f64(double {})", r, v));
-            r
+            (r, ValTy::Num)
         }
         Expr::Log(base, val) => {
-            let bv = gen_expr(ctx, base);
-            let vv = gen_expr(ctx, val);
+            let bv = gen_expr_as(ctx, base, &ValTy::Num);
+            let vv = gen_expr_as(ctx, val, &ValTy::Num);
             let ln_v = ctx.tmp(); ctx.emit(&format!("{} = call double @llvm.log.f64(double {})", ln_v, vv));
             let ln_b = ctx.tmp(); ctx.emit(&format!("{} = call double @llvm.log.f64(double {})", ln_b, bv));
             let r = ctx.tmp(); ctx.emit(&format!("{} = fdiv double {}, {}", r, ln_v, ln_b));
-            r
+            (r, ValTy::Num)
         }
+
         Expr::Rand => {
             // Sembrar una vez, luego llamar rand() de libc
             let seeded = ctx.tmp();
@@ -1080,13 +1368,13 @@ pub fn gen_expr(ctx: &mut Ctx, expr: &Spanned<Expr>) -> String {
             ctx.emit(&format!("{} = sitofp i32 {} to double", rf, ri));
             let r = ctx.tmp();
             ctx.emit(&format!("{} = fdiv double {}, 2.147483647e9", r, rf));
-            r
+            (r, ValTy::Num)
         }
-        Expr::PI => fmt_double(std::f64::consts::PI),
-        Expr::E  => fmt_double(std::f64::consts::E),
+        Expr::PI => (fmt_double(std::f64::consts::PI), ValTy::Num),
+        Expr::E  => (fmt_double(std::f64::consts::E), ValTy::Num),
 
         //  Nodo de error 
-        Expr::Error => { "0.0".into() }
+        Expr::Error => ("0.0".into(), ValTy::Num),
     }
 }
 
@@ -1189,6 +1477,12 @@ fn collect_free_vars_inner(expr: &Expr, bound: &[String], free: &mut Vec<String>
         Expr::VectorLiteral(elems) => {
             for e in elems { collect_free_vars_inner(&e.node, bound, free); }
         }
+        Expr::MapLiteral(entries) => {
+            for (k, v) in entries {
+                collect_free_vars_inner(&k.node, bound, free);
+                collect_free_vars_inner(&v.node, bound, free);
+            }
+        }
         Expr::VectorGenerator { expr: e, var, iterable } => {
             collect_free_vars_inner(&iterable.node, bound, free);
             let mut inner_bound: Vec<String> = bound.to_vec();
@@ -1199,13 +1493,25 @@ fn collect_free_vars_inner(expr: &Expr, bound: &[String], free: &mut Vec<String>
             collect_free_vars_inner(&obj.node, bound, free);
             collect_free_vars_inner(&index.node, bound, free);
         }
+        Expr::IndexAssignment { obj, index, value } => {
+            collect_free_vars_inner(&obj.node, bound, free);
+            collect_free_vars_inner(&index.node, bound, free);
+            collect_free_vars_inner(&value.node, bound, free);
+        }
+        Expr::Slice { obj, start, end } => {
+            collect_free_vars_inner(&obj.node, bound, free);
+            for e in start.iter().chain(end) {
+                collect_free_vars_inner(&e.node, bound, free);
+            }
+        }
         Expr::Match { expr: e, cases, default } => {
             collect_free_vars_inner(&e.node, bound, free);
             for c in cases {
                 // Los patrones pueden enlazar variables
                 let mut inner_bound: Vec<String> = bound.to_vec();
-                if let Pattern::Variable { name, .. } = &c.pattern {
-                    inner_bound.push(name.clone());
+                inner_bound.extend(pattern_vars(&c.pattern).into_iter().map(String::from));
+                if let Some(g) = &c.guard {
+                    collect_free_vars_inner(&g.node, &inner_bound, free);
                 }
                 collect_free_vars_inner(&c.expr.node, &inner_bound, free);
             }
@@ -1213,9 +1519,22 @@ fn collect_free_vars_inner(expr: &Expr, bound: &[String], free: &mut Vec<String>
                 collect_free_vars_inner(&d.node, bound, free);
             }
         }
-        Expr::Sqrt(a) | Expr::Sin(a) | Expr::Cos(a) | Expr::Exp(a) => {
+        Expr::Try { body, catches } => {
+            collect_free_vars_inner(&body.node, bound, free);
+            for c in catches {
+                let mut inner_bound: Vec<String> = bound.to_vec();
+                inner_bound.push(c.var.clone());
+                collect_free_vars_inner(&c.body.node, &inner_bound, free);
+            }
+        }
+        Expr::Sqrt(a) | Expr::Sin(a) | Expr::Cos(a) | Expr::Exp(a) | Expr::Throw(a) | Expr::Return(a) => {
             collect_free_vars_inner(&a.node, bound, free);
         }
+        Expr::Break(value) | Expr::Continue(value) => {
+            if let Some(v) = value {
+                collect_free_vars_inner(&v.node, bound, free);
+            }
+        }
         Expr::Log(a, b) => {
             collect_free_vars_inner(&a.node, bound, free);
             collect_free_vars_inner(&b.node, bound, free);
@@ -1227,39 +1546,9 @@ fn collect_free_vars_inner(expr: &Expr, bound: &[String], free: &mut Vec<String>
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
-// Auxiliares: inferir tipos de retorno y resolver clase de objeto en tiempo de compilación
+// Auxiliares: resolver clase de objeto en tiempo de compilación
 // ─────────────────────────────────────────────────────────────────────────────
 
-/// Heurística: inferir el nombre del tipo de retorno de una expresión del cuerpo de un método.
-/// Usa tipos de atributos para accesos `self.attr`, y análisis de literales/operaciones.
-pub fn infer_return_type_from_body(body: &Expr, attr_types: &HashMap<String, String>) -> Option<String> {
-    match body {
-        Expr::String(_) => Some("String".into()),
-        Expr::Number(_) | Expr::PI | Expr::E | Expr::Rand => Some("Number".into()),
-        Expr::Boolean(_) => Some("Boolean".into()),
-        Expr::Binary(_, Op::Concat, _) | Expr::Binary(_, Op::ConcatSpace, _) => Some("String".into()),
-        Expr::Binary(_, op, _) if matches!(op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod | Op::Pow) => Some("Number".into()),
-        Expr::Binary(_, op, _) if matches!(op, Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge | Op::And | Op::Or) => Some("Boolean".into()),
-        Expr::Unary(UnOp::Neg, _) => Some("Number".into()),
-        Expr::Unary(UnOp::Not, _) => Some("Boolean".into()),
-        Expr::Sqrt(_) | Expr::Sin(_) | Expr::Cos(_) | Expr::Exp(_) | Expr::Log(_, _) => Some("Number".into()),
-        Expr::Is(_, _) => Some("Boolean".into()),
-        Expr::AttributeAccess { attribute, .. } => {
-            attr_types.get(attribute.as_str()).cloned()
-        }
-        Expr::Identifier(name) => {
-            // Atajo self.attr: buscar en attr_types
-            attr_types.get(name.as_str()).cloned()
-        }
-        Expr::Block(stmts) => {
-            stmts.last().and_then(|s| infer_return_type_from_body(&s.node, attr_types))
-        }
-        Expr::If { then_expr, .. } => infer_return_type_from_body(&then_expr.node, attr_types),
-        Expr::Let { body, .. } => infer_return_type_from_body(&body.node, attr_types),
-        _ => None,
-    }
-}
-
 /// Resolver el nombre de clase HULK de una expresión en tiempo de compilación.
 /// Retorna Some("NombreClase") si es determinable, None en caso contrario.
 fn resolve_obj_class_from_expr(ctx: &Ctx, expr: &Expr) -> Option<String> {
@@ -1282,260 +1571,72 @@ fn resolve_obj_class_from_expr(ctx: &Ctx, expr: &Expr) -> Option<String> {
     }
 }
 
-/// Inferir el ValTy correcto para una variable que se enlaza a una expresión.
-fn infer_val_ty(ctx: &Ctx, init_expr: &Expr) -> ValTy {
-    match init_expr {
-        Expr::Instantiation { ty, .. } => ValTy::Obj(ty.clone()),
-        Expr::String(_) => ValTy::Str,
-        Expr::Boolean(_) => ValTy::Bool,
-        Expr::Number(_) | Expr::PI | Expr::E | Expr::Rand => ValTy::Num,
-        Expr::Binary(_, Op::Concat, _) | Expr::Binary(_, Op::ConcatSpace, _) => ValTy::Str,
-        Expr::Binary(_, op, _) if matches!(op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod | Op::Pow) => ValTy::Num,
-        Expr::Binary(_, op, _) if matches!(op, Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge | Op::And | Op::Or) => ValTy::Bool,
-        Expr::Unary(UnOp::Neg, _) => ValTy::Num,
-        Expr::Unary(UnOp::Not, _) => ValTy::Bool,
-        Expr::Sqrt(_) | Expr::Sin(_) | Expr::Cos(_) | Expr::Exp(_) | Expr::Log(_, _) => ValTy::Num,
-        Expr::Is(_, _) => ValTy::Bool,
-        Expr::As(_, ty_name) => ValTy::Obj(ty_name.clone()),
-        Expr::Identifier(name) => {
-            if let Some((_, vt)) = ctx.get_var(name) {
-                return vt;
-            }
-            ValTy::Num
-        }
-        Expr::If { then_expr, .. } => infer_val_ty(ctx, &then_expr.node),
-        Expr::Block(stmts) => {
-            stmts.last().map_or(ValTy::Num, |s| infer_val_ty(ctx, &s.node))
-        }
-        Expr::Let { body, .. } => infer_val_ty(ctx, &body.node),
-        Expr::Lambda { body, .. } => {
-            let ret_ty = infer_val_ty(ctx, &body.node);
-            ValTy::Fn(Box::new(ret_ty))
-        }
-        Expr::Call { func, .. } => {
-            // Verificar funciones con nombre
-            if let Some(ret_vty) = ctx.func_ret_types.get(func.as_str()) {
-                return ret_vty.clone();
-            }
-            // Verificar lambdas
-            if let Some((_, ValTy::Fn(ret_ty))) = ctx.get_var(func) {
-                return *ret_ty;
-            }
-            ValTy::Num
-        }
-        Expr::VectorLiteral(_) | Expr::VectorGenerator { .. } => ValTy::Obj("__Vector".to_string()),
-        _ => ValTy::Num,
-    }
-}
-
-/// Convertir la anotación de tipo de un parámetro a un ValTy.
-// ─────────────────────────────────────────────────────────────────────────────
-// Auxiliar de impresión – detecta Number vs Boolean vs String desde el AST + metadatos de clase
-// ─────────────────────────────────────────────────────────────────────────────
-
-/// Resultado de pista de tipo para una expresión.
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum ExprTyHint { Str, Num, Bool, Vector, Unknown }
-
-/// Determinar el tipo probable en tiempo de ejecución de una expresión usando la estructura del AST
-/// y metadatos de atributos de clase.
-fn expr_type_hint(ctx: &Ctx, expr: &Expr) -> ExprTyHint {
-    match expr {
-        // Literales
-        Expr::String(_) => ExprTyHint::Str,
-        Expr::Number(_) | Expr::PI | Expr::E | Expr::Rand => ExprTyHint::Num,
-        Expr::Boolean(_) => ExprTyHint::Bool,
-        
-        // Vectores
-        Expr::VectorLiteral(_) | Expr::VectorGenerator { .. } => ExprTyHint::Vector,
-
-        // Operaciones binarias
-        Expr::Binary(_, Op::Concat, _) | Expr::Binary(_, Op::ConcatSpace, _) => ExprTyHint::Str,
-        Expr::Binary(_, op, _) if matches!(op, Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod | Op::Pow) => ExprTyHint::Num,
-        Expr::Binary(_, op, _) if matches!(op, Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge | Op::And | Op::Or) => ExprTyHint::Bool,
-
-        // Unario
-        Expr::Unary(UnOp::Neg, _) => ExprTyHint::Num,
-        Expr::Unary(UnOp::Not, _) => ExprTyHint::Bool,
-
-        // Funciones matemáticas integradas
-        Expr::Sqrt(_) | Expr::Sin(_) | Expr::Cos(_) | Expr::Exp(_) | Expr::Log(_, _) => ExprTyHint::Num,
-
-        // Verificaciones de tipo
-        Expr::Is(_, _) => ExprTyHint::Bool,
-
-        // AccesoAtributo: buscar el tipo del atributo en la clase resuelta primero
-        Expr::AttributeAccess { obj, attribute, .. } => {
-            let obj_class = resolve_obj_class_from_expr(ctx, &obj.node);
-            if let Some(ref cls) = obj_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if let Some(type_name) = layout.attr_types.get(attribute.as_str()) {
-                        return match type_name.as_str() {
-                            "String" => ExprTyHint::Str,
-                            "Number" => ExprTyHint::Num,
-                            "Boolean" => ExprTyHint::Bool,
-                            _ => ExprTyHint::Unknown,
-                        };
-                    }
-                }
-            }
-            // Respaldo: buscar en todas las clases
-            for (_, layout) in &ctx.classes {
-                if let Some(type_name) = layout.attr_types.get(attribute.as_str()) {
-                    return match type_name.as_str() {
-                        "String" => ExprTyHint::Str,
-                        "Number" => ExprTyHint::Num,
-                        "Boolean" => ExprTyHint::Bool,
-                        _ => ExprTyHint::Unknown,
-                    };
-                }
-            }
-            ExprTyHint::Unknown
-        }
-
-        // Llamadas a métodos: verificar method_ret_types desde la clase resuelta o cualquier clase
-        Expr::MethodCall { obj, method, .. } => {
-            // Primero intentar con la clase resuelta del objeto
-            let obj_class = resolve_obj_class_from_expr(ctx, &obj.node);
-            if let Some(ref cls) = obj_class {
-                if let Some(layout) = ctx.classes.get(cls.as_str()) {
-                    if let Some(ret_type) = layout.method_ret_types.get(method.as_str()) {
-                        return match ret_type.as_str() {
-                            "String" => ExprTyHint::Str,
-                            "Number" => ExprTyHint::Num,
-                            "Boolean" => ExprTyHint::Bool,
-                            _ => ExprTyHint::Unknown,
-                        };
-                    }
-                }
-            }
-            // Respaldo: buscar en todas las clases
-            for (_, layout) in &ctx.classes {
-                if let Some(ret_type) = layout.method_ret_types.get(method.as_str()) {
-                    return match ret_type.as_str() {
-                        "String" => ExprTyHint::Str,
-                        "Number" => ExprTyHint::Num,
-                        "Boolean" => ExprTyHint::Bool,
-                        _ => ExprTyHint::Unknown,
-                    };
-                }
-            }
-            ExprTyHint::Unknown
-        }
-
-        // Flujo de control
-        Expr::If { then_expr, else_expr, .. } => {
-            let t = expr_type_hint(ctx, &then_expr.node);
-            let e = expr_type_hint(ctx, &else_expr.node);
-            if t == e { t } else if t != ExprTyHint::Unknown { t } else { e }
-        }
-        Expr::Block(stmts) => {
-            stmts.last().map_or(ExprTyHint::Unknown, |s| expr_type_hint(ctx, &s.node))
-        }
-        Expr::Let { body, .. } => expr_type_hint(ctx, &body.node),
-
-        // Identificadores: verificar los val_types rastreados
-        Expr::Identifier(name) => {
-            match ctx.get_var(name).map(|(_, vt)| vt) {
-                Some(ValTy::Str) => ExprTyHint::Str,
-                Some(ValTy::Bool) => ExprTyHint::Bool,
-                Some(ValTy::Num) => ExprTyHint::Num,
-                Some(ValTy::Obj(ref ty_name)) if ty_name == "__Vector" => ExprTyHint::Vector,
-                _ => ExprTyHint::Unknown,
-            }
-        }
-
-        // Llamadas a funciones: verificar tipo de retorno desde funciones o val_types (para lambdas)
-        Expr::Call { func, .. } => {
-            // Primero verificar funciones con nombre con anotaciones de tipo de retorno
-            if let Some(ret_vty) = ctx.func_ret_types.get(func.as_str()) {
-                return match ret_vty {
-                    ValTy::Str => ExprTyHint::Str,
-                    ValTy::Bool => ExprTyHint::Bool,
-                    ValTy::Num => ExprTyHint::Num,
-                    _ => ExprTyHint::Unknown,
-                };
-            }
-            // Luego verificar si es una lambda almacenada en val_types
-            match ctx.get_var(func).map(|(_, vt)| vt) {
-                Some(ValTy::Fn(ret_ty)) => match *ret_ty {
-                    ValTy::Str => ExprTyHint::Str,
-                    ValTy::Bool => ExprTyHint::Bool,
-                    ValTy::Num => ExprTyHint::Num,
-                    _ => ExprTyHint::Unknown,
-                },
-                Some(ValTy::Str) => ExprTyHint::Str,
-                Some(ValTy::Bool) => ExprTyHint::Bool,
-                Some(ValTy::Num) => ExprTyHint::Num,
-                _ => ExprTyHint::Unknown,
-            }
-        }
-
-        _ => ExprTyHint::Unknown,
-    }
-}
-
-/// Dado un valor double compilado y su nodo AST, retorna un puntero `i8*` de cadena.
-/// Si la expresión ya es una cadena, bitcast el double a un puntero.
-/// Si es un número, llamar a @__hulk_num_to_str. Si es bool, llamar a @__hulk_bool_to_str.
-/// Si el tipo es desconocido, llamar a @__hulk_to_str (auto-detección en runtime).
-fn gen_to_str_ptr(ctx: &mut Ctx, expr: &Expr, val: &str) -> String {
-    let hint = expr_type_hint(ctx, expr);
-    match hint {
-        ExprTyHint::Str => {
-            ctx.decode_ptr(val, "i8*")
-        }
-        ExprTyHint::Bool => {
-            let p = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @__hulk_bool_to_str(double {})", p, val));
-            p
-        }
-        ExprTyHint::Num => {
-            let p = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @__hulk_num_to_str(double {})", p, val));
-            p
-        }
-        ExprTyHint::Vector => {
-            // Para vectores, convertir a representación string
-            let p = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @__hulk_vector_to_str(double {})", p, val));
-            p
-        }
-        ExprTyHint::Unknown => {
-            // Tipo desconocido en tiempo de compilación — auto-detección en runtime (puntero de cadena vs número)
-            let p = ctx.tmp();
-            ctx.emit(&format!("{} = call i8* @__hulk_to_str(double {})", p, val));
-            p
+/// Convierte un valor a un puntero `i8*` de cadena para `@`: las cadenas se usan
+/// tal cual, números y booleanos tienen su conversión propia y el resto pasa
+/// empaquetado por `@__hulk_to_str`, que lo convierte según su etiqueta.
+fn gen_to_str_ptr(ctx: &mut Ctx, val: &str, ty: &ValTy) -> String {
+    let p = ctx.tmp();
+    match ty {
+        ValTy::Str => return val.to_string(),
+        ValTy::Num => ctx.emit(&format!("{} = call i8* @__hulk_num_to_str(double {})", p, val)),
+        ValTy::Bool => ctx.emit(&format!("{} = call i8* @__hulk_bool_to_str(i1 {})", p, val)),
+        ValTy::Vector(_) => ctx.emit(&format!("{} = call i8* @__hulk_vector_to_str(i8* {}, i1 false)", p, val)),
+        _ => {
+            let b = ctx.box_value(val, ty);
+            ctx.emit(&format!("{} = call i8* @__hulk_to_str(double {})", p, b));
         }
     }
+    p
 }
 
 fn gen_print(ctx: &mut Ctx, args: &[Spanned<Expr>]) {
     if let Some(arg) = args.first() {
-        let hint = expr_type_hint(ctx, &arg.node);
-        let val = gen_expr(ctx, arg);
-
-        match hint {
-            ExprTyHint::Str => {
-                let p = ctx.decode_ptr(&val, "i8*");
-                ctx.emit(&format!("call i32 @puts(i8* {})", p));
+        let (val, ty) = gen_expr(ctx, arg);
+        match ty {
+            ValTy::Str => {
+                ctx.emit(&format!("call i32 @puts(i8* {})", val));
             }
-            ExprTyHint::Bool => {
-                let cb = ctx.tmp(); ctx.emit(&format!("{} = fcmp one double {}, 0.0", cb, val));
+            ValTy::Bool => {
                 let ts = ctx.tmp();
-                ctx.emit(&format!("{} = select i1 {}, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true_s, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false_s, i64 0, i64 0)", ts, cb));
+                ctx.emit(&format!("{} = select i1 {}, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true_s, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false_s, i64 0, i64 0)", ts, val));
                 ctx.emit(&format!("call i32 @puts(i8* {})", ts));
             }
-            ExprTyHint::Num => {
+            ValTy::Num => {
                 ctx.emit(&format!("call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double {})", val));
                 ctx.emit("call i32 @puts(i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.empty_s, i64 0, i64 0))");
             }
-            ExprTyHint::Vector => {
-                ctx.emit(&format!("call void @__hulk_print_vector(double {})", val));
-            }
-            ExprTyHint::Unknown => {
-                ctx.emit(&format!("call void @__hulk_print_val(double {})", val));
+            // El resto (vectores, diccionarios, objetos y valores empaquetados)
+            // se imprime según su etiqueta
+            _ => {
+                let b = ctx.box_value(&val, &ty);
+                ctx.emit(&format!("call void @__hulk_print_val(double {})", b));
             }
         }
     }
 }
+
+/// Clase (o protocolo) cuyos `next` y `current` (o `get_current`) recorren un
+/// iterable, el nombre del método que da el elemento y el tipo de los
+/// elementos. Un objeto sin esos métodos en su tipo se recorre como vector.
+fn iterable_class(ctx: &Ctx, expr: &Spanned<Expr>, ty: &ValTy) -> (String, &'static str, ValTy) {
+    let has = |c: &String, m: &str| match (ctx.classes.get(c), ctx.protocols.get(c)) {
+        (Some(l), _) => l.method_names.contains_key(m),
+        (None, Some(p)) => p.methods.contains_key(m),
+        (None, None) => false,
+    };
+    let cls = static_class(ctx, &expr.node, ty)
+        .filter(|c| has(c, "next") && (has(c, "current") || has(c, "get_current")))
+        .unwrap_or_else(|| "__Vector".to_string());
+    let current = if has(&cls, "current") { "current" } else { "get_current" };
+    // Los genéricos devuelven sus elementos empaquetados; el chequeo semántico
+    // sabe de qué tipo son
+    let elem = match (ty, ctx.sem.element_sites.get(&expr.span())) {
+        (ValTy::Vector(elem) | ValTy::Map(elem, _), _) => (**elem).clone(),
+        (_, Some(t)) => ValTy::from_type(&t.borrow()),
+        _ => match ctx.protocols.get(&cls).and_then(|p| p.methods.get(current)) {
+            Some((_, ret)) => ret.clone(),
+            None => find_method(ctx, Some(&cls), current).map_or_else(ValTy::object, |(_, _, ret)| ret),
+        },
+    };
+    (cls, current, elem)
+}
@@ -1,7 +1,8 @@
-use crate::ast::nodes::{FunctionDecl, MacroDecl, TypeAnnotation, MacroParam, Expr};
+use crate::ast::nodes::{FunctionDecl, MacroDecl, MacroParam, Expr};
 use crate::utils::Spanned;
-use super::context::Ctx;
-use super::utils::{ValTy, val_ty_from_annotation};
+use super::context::{Ctx, GenExpr};
+use super::gc::GC_RAW;
+use super::utils::{box_tag, escape_llvm, ValTy, BOX_BOOL, BOX_FN, BOX_NUM, BOX_PAYLOAD_MASK, BOX_REF, BOX_STR, BOX_TAG_BASE};
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Emisión de funciones helper (conversión de tipos, impresión)
@@ -25,212 +26,326 @@ fmt_as_int:
   %ilen = call i32 (i8*, i64, i8*, ...) @snprintf(i8* null, i64 0, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_int, i64 0, i64 0), double %val)
   %ilen64 = sext i32 %ilen to i64
   %ibufsz = add i64 %ilen64, 1
-  %ibuf = call i8* @malloc(i64 %ibufsz)
+  %ibuf = call i8* @__hulk_gc_alloc(i64 %ibufsz, i32 0)
   call i32 (i8*, i64, i8*, ...) @snprintf(i8* %ibuf, i64 %ibufsz, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_int, i64 0, i64 0), double %val)
   ret i8* %ibuf
 fmt_as_dbl:
   %dlen = call i32 (i8*, i64, i8*, ...) @snprintf(i8* null, i64 0, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %val)
   %dlen64 = sext i32 %dlen to i64
   %dbufsz = add i64 %dlen64, 1
-  %dbuf = call i8* @malloc(i64 %dbufsz)
+  %dbuf = call i8* @__hulk_gc_alloc(i64 %dbufsz, i32 0)
   call i32 (i8*, i64, i8*, ...) @snprintf(i8* %dbuf, i64 %dbufsz, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %val)
   ret i8* %dbuf
 }
 
 ");
 
-    // ── @__hulk_bool_to_str(double) -> i8*  ─  retorna puntero a \"true\" o \"false\"
+    // ── @__hulk_bool_to_str(i1) -> i8*  ─  retorna puntero a \"true\" o \"false\"
     ctx.functions.push_str("\
-define i8* @__hulk_bool_to_str(double %val) {
+define i8* @__hulk_bool_to_str(i1 %val) {
 entry:
-  %cond = fcmp one double %val, 0.0
-  %res = select i1 %cond, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true_s, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false_s, i64 0, i64 0)
+  %res = select i1 %val, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.true_s, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.false_s, i64 0, i64 0)
   ret i8* %res
 }
 
 ");
 
-    // ── @__hulk_print_val(double)  ─  despachador de impresión en tiempo de ejecución
-    //    Usa una etiqueta pasada como segundo argumento:
-    //      0 = número, 1 = cadena (puntero), 2 = booleano
-    //    Respaldo: imprimir como número (por defecto seguro).
+    // ── @__hulk_num_print_str(double) -> i8*  ─  el número como lo escribe `print` (`%.6g`)
     ctx.functions.push_str("\
-define void @__hulk_print_val(double %val) {
+define i8* @__hulk_num_print_str(double %val) {
 entry:
-  ; Respaldo: imprimir como número (por defecto seguro para tipo Unknown)
-  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %val)
-  call i32 @puts(i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.empty_s, i64 0, i64 0))
-  ret void
+  %len = call i32 (i8*, i64, i8*, ...) @snprintf(i8* null, i64 0, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %val)
+  %len64 = sext i32 %len to i64
+  %bufsz = add i64 %len64, 1
+  %buf = call i8* @__hulk_gc_alloc(i64 %bufsz, i32 0)
+  call i32 (i8*, i64, i8*, ...) @snprintf(i8* %buf, i64 %bufsz, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %val)
+  ret i8* %buf
 }
 
 ");
 
-    // ── @__hulk_to_str(double) -> i8*  ─  convertir a cadena en tiempo de ejecución
-    //    Respaldo seguro: siempre tratar como número. Las cadenas nunca deberían llegar aquí
-    //    porque expr_type_hint debería detectarlas en tiempo de compilación.
+    emit_box_helpers(ctx);
+    emit_text_buffer(ctx);
+
+    // Nombre `<Clase>` de cada type-id, para convertir un objeto a texto
+    let count = ctx.next_type_id as usize;
+    let mut names = vec!["<Object>".to_string(); count];
+    for (name, layout) in &ctx.classes {
+        names[layout.type_id as usize] = format!("<{}>", name);
+    }
+    let mut entries = Vec::with_capacity(count);
+    for (tid, name) in names.iter().enumerate() {
+        let id = format!("@.type_name_{}", tid);
+        ctx.globals.push_str(&format!(
+            "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n", id, name.len() + 1, escape_llvm(name)
+        ));
+        entries.push(format!("i8* {}", ctx.gep_string(&id, name.len() + 1)));
+    }
+    ctx.globals.push_str(&format!(
+        "@__hulk_type_names = internal constant [{} x i8*] [{}]\n", count, entries.join(", ")
+    ));
+    ctx.globals.push_str("@.function_s = private unnamed_addr constant [11 x i8] c\"<function>\\00\"\n");
+
+    // ── @__hulk_value_to_str(double, i1 print) -> i8*  ─  texto de un valor
+    //    empaquetado según su etiqueta. Con `print` los números van con `%.6g`,
+    //    como en `print`; sin él, los enteros sin decimales, como en `@`.
+    let vector = ctx.classes["__Vector"].type_id;
+    let map = ctx.classes["__Map"].type_id;
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_value_to_str(double %val, i1 %print) {{
+entry:
+  %kind = call i32 @__hulk_box_kind(double %val)
+  switch i32 %kind, label %num [
+    i32 {BOX_BOOL}, label %bool
+    i32 {BOX_STR}, label %str
+    i32 {BOX_REF}, label %ref
+    i32 {BOX_FN}, label %fn
+  ]
+num:
+  br i1 %print, label %num_print, label %num_text
+num_print:
+  %np = call i8* @__hulk_num_print_str(double %val)
+  ret i8* %np
+num_text:
+  %nt = call i8* @__hulk_num_to_str(double %val)
+  ret i8* %nt
+bool:
+  %bits = bitcast double %val to i64
+  %b = icmp eq i64 %bits, {true_bits}
+  %bs = call i8* @__hulk_bool_to_str(i1 %b)
+  ret i8* %bs
+str:
+  %s = call i8* @__hulk_box_ptr(double %val)
+  ret i8* %s
+ref:
+  %obj = call i8* @__hulk_box_ptr(double %val)
+  %tid_p = bitcast i8* %obj to i64*
+  %tid = load i64, i64* %tid_p
+  switch i64 %tid, label %object [
+    i64 {vector}, label %vector
+    i64 {map}, label %map
+  ]
+vector:
+  %vs = call i8* @__hulk_vector_to_str(i8* %obj, i1 %print)
+  ret i8* %vs
+map:
+  %ms = call i8* @__Map_to_str(i8* %obj, i1 %print)
+  ret i8* %ms
+object:
+  %name_p = getelementptr inbounds [{count} x i8*], [{count} x i8*]* @__hulk_type_names, i64 0, i64 %tid
+  %name = load i8*, i8** %name_p
+  ret i8* %name
+fn:
+  ret i8* getelementptr inbounds ([11 x i8], [11 x i8]* @.function_s, i64 0, i64 0)
+}}
+
+", true_bits = box_tag(BOX_BOOL) | 1));
+
+    // ── @__hulk_print_val(double)  ─  `print` de un valor empaquetado
     ctx.functions.push_str("\
-define i8* @__hulk_to_str(double %val) {
+define void @__hulk_print_val(double %val) {
 entry:
-  %numstr = call i8* @__hulk_num_to_str(double %val)
-  ret i8* %numstr
+  %s = call i8* @__hulk_value_to_str(double %val, i1 true)
+  call i32 @puts(i8* %s)
+  ret void
 }
 
 ");
 
-    // ── @__hulk_print_vector(double)  ─  imprimir un vector
-    //    Decodifica el objeto __Vector y imprime sus elementos
+    // ── @__hulk_to_str(double) -> i8*  ─  texto de un valor empaquetado para `@`
     ctx.functions.push_str("\
-define void @__hulk_print_vector(double %val) {
+define i8* @__hulk_to_str(double %val) {
 entry:
-  ; Decodificar objeto Vector
-  %pi = bitcast double %val to i64
-  %obj_ptr = inttoptr i64 %pi to i8*
-  %ptr = bitcast i8* %obj_ptr to %__Vector*
-  
-  ; Leer longitud del vector
-  %len_ptr = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 2
-  %len_d = load double, double* %len_ptr
-  %len_i = fptosi double %len_d to i64
-  
-  ; Leer data pointer
-  %data_ptr_loc = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 1
-  %data_ptr = load double*, double** %data_ptr_loc
-  
-  ; Imprimir apertura
-  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.vec_open, i64 0, i64 0))
-  
-  ; Inicializar índice
-  %idx_ptr = alloca i64
-  store i64 0, i64* %idx_ptr
-  br label %loop_cond
-  
-loop_cond:
-  %i = load i64, i64* %idx_ptr
-  %cond = icmp slt i64 %i, %len_i
-  br i1 %cond, label %loop_body, label %loop_end
-  
-loop_body:
-  ; Leer elemento en posición i
-  %elem_ptr = getelementptr double, double* %data_ptr, i64 %i
-  %elem = load double, double* %elem_ptr
-  
-  ; Imprimir separador si no es el primero
-  %is_first = icmp eq i64 %i, 0
-  br i1 %is_first, label %print_elem, label %print_sep
-  
-print_sep:
-  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.vec_sep, i64 0, i64 0))
-  br label %print_elem
-  
-print_elem:
-  ; Imprimir el elemento
-  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.fmt_num, i64 0, i64 0), double %elem)
-  
-  ; Incrementar índice
-  %next_i = add i64 %i, 1
-  store i64 %next_i, i64* %idx_ptr
-  br label %loop_cond
-  
-loop_end:
-  ; Imprimir cierre
-  call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.vec_close, i64 0, i64 0))
-  call i32 @puts(i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.empty_s, i64 0, i64 0))
-  ret void
+  %s = call i8* @__hulk_value_to_str(double %val, i1 false)
+  ret i8* %s
 }
 
 ");
 
-    // ── @__hulk_vector_to_str(double) -> i8*  ─  convertir vector a string
+    // ── @__hulk_vector_to_str(i8*, i1 print) -> i8*  ─  `[a, b]`, con cada
+    //    elemento convertido según su etiqueta
     ctx.functions.push_str("\
-define i8* @__hulk_vector_to_str(double %val) {
+define i8* @__hulk_vector_to_str(i8* %obj_ptr, i1 %print) {
 entry:
-  ; Decodificar objeto Vector
-  %pi = bitcast double %val to i64
-  %obj_ptr = inttoptr i64 %pi to i8*
+  %buf = alloca %__Buf
+  store %__Buf zeroinitializer, %__Buf* %buf
   %ptr = bitcast i8* %obj_ptr to %__Vector*
-  
-  ; Leer longitud
   %len_ptr = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 2
-  %len_d = load double, double* %len_ptr
-  %len_i = fptosi double %len_d to i64
-  
-  ; Leer data pointer
   %data_ptr_loc = getelementptr inbounds %__Vector, %__Vector* %ptr, i32 0, i32 1
-  %data_ptr = load double*, double** %data_ptr_loc
-  
-  ; Calcular tamaño aproximado del buffer (20 chars por número + separadores)
-  %est_size = mul i64 %len_i, 20
-  %buf_size = add i64 %est_size, 100
-  %buf_raw = call i8* @malloc(i64 %buf_size)
-  
-  ; Copiar '['
-  %pos_ptr = alloca i64
-  store i64 0, i64* %pos_ptr
-  %open_ptr = getelementptr i8, i8* %buf_raw, i64 0
-  store i8 91, i8* %open_ptr  ; '[' = 91
-  store i64 1, i64* %pos_ptr
-  
-  ; Loop sobre elementos
-  %idx_ptr = alloca i64
-  store i64 0, i64* %idx_ptr
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.vec_open, i64 0, i64 0))
   br label %loop_cond
-  
+
 loop_cond:
-  %i = load i64, i64* %idx_ptr
+  %i = phi i64 [0, %entry], [%next_i, %get_elem]
+  %len_d = load double, double* %len_ptr
+  %len_i = fptosi double %len_d to i64
   %cond = icmp slt i64 %i, %len_i
   br i1 %cond, label %loop_body, label %loop_end
-  
+
 loop_body:
-  ; Agregar separador si no es el primero
   %is_first = icmp eq i64 %i, 0
   br i1 %is_first, label %get_elem, label %add_sep
-  
+
 add_sep:
-  %pos1 = load i64, i64* %pos_ptr
-  %sep1_ptr = getelementptr i8, i8* %buf_raw, i64 %pos1
-  store i8 44, i8* %sep1_ptr  ; ',' = 44
-  %pos2 = add i64 %pos1, 1
-  %sep2_ptr = getelementptr i8, i8* %buf_raw, i64 %pos2
-  store i8 32, i8* %sep2_ptr  ; ' ' = 32
-  %pos3 = add i64 %pos2, 1
-  store i64 %pos3, i64* %pos_ptr
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.vec_sep, i64 0, i64 0))
   br label %get_elem
-  
+
 get_elem:
-  ; Leer elemento y convertir a string
+  ; Los datos se releen en cada vuelta: convertir un elemento puede asignar
+  %data_ptr = load double*, double** %data_ptr_loc
   %elem_ptr = getelementptr double, double* %data_ptr, i64 %i
   %elem = load double, double* %elem_ptr
-  %elem_str = call i8* @__hulk_num_to_str(double %elem)
-  
-  ; Concatenar al buffer
-  %pos4 = load i64, i64* %pos_ptr
-  %dest_ptr = getelementptr i8, i8* %buf_raw, i64 %pos4
-  call i8* @strcpy(i8* %dest_ptr, i8* %elem_str)
-  %elem_len = call i64 @strlen(i8* %elem_str)
-  %pos5 = add i64 %pos4, %elem_len
-  store i64 %pos5, i64* %pos_ptr
-  call void @free(i8* %elem_str)
-  
-  ; Siguiente
+  %elem_str = call i8* @__hulk_value_to_str(double %elem, i1 %print)
+  call void @__hulk_buf_append(%__Buf* %buf, i8* %elem_str)
   %next_i = add i64 %i, 1
-  store i64 %next_i, i64* %idx_ptr
   br label %loop_cond
-  
+
 loop_end:
-  ; Agregar ']'
-  %pos6 = load i64, i64* %pos_ptr
-  %close_ptr = getelementptr i8, i8* %buf_raw, i64 %pos6
-  store i8 93, i8* %close_ptr  ; ']' = 93
-  %pos7 = add i64 %pos6, 1
-  %null_ptr = getelementptr i8, i8* %buf_raw, i64 %pos7
-  store i8 0, i8* %null_ptr    ; null terminator
-  
-  ret i8* %buf_raw
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.vec_close, i64 0, i64 0))
+  %str = call i8* @__hulk_buf_finish(%__Buf* %buf)
+  ret i8* %str
 }
 
 ");
 }
 
+/// Helpers de los valores empaquetados: su clase, su puntero, el type-id de la
+/// referencia y la igualdad de `==` entre dos valores de cualquier tipo.
+fn emit_box_helpers(ctx: &mut Ctx) {
+    let payload = BOX_PAYLOAD_MASK;
+    let base = BOX_TAG_BASE;
+
+    // ── @__hulk_box_kind(double) -> i32  ─  BOX_NUM, BOX_BOOL, BOX_STR, BOX_REF o BOX_FN
+    // ── @__hulk_box_ptr(double) -> i8*  ─  el puntero sin la etiqueta
+    // ── @__hulk_box_type_id(double) -> i64  ─  type-id de una referencia, o -1
+    ctx.functions.push_str(&format!("\
+define i32 @__hulk_box_kind(double %val) {{
+entry:
+  %bits = bitcast double %val to i64
+  %hi = lshr i64 %bits, 48
+  %tagged = icmp ugt i64 %hi, {base}
+  %k = sub i64 %hi, {base}
+  %k32 = trunc i64 %k to i32
+  %kind = select i1 %tagged, i32 %k32, i32 {BOX_NUM}
+  ret i32 %kind
+}}
+
+define i8* @__hulk_box_ptr(double %val) {{
+entry:
+  %bits = bitcast double %val to i64
+  %word = and i64 %bits, {payload}
+  %ptr = inttoptr i64 %word to i8*
+  ret i8* %ptr
+}}
+
+define i64 @__hulk_box_type_id(double %val) {{
+entry:
+  %kind = call i32 @__hulk_box_kind(double %val)
+  %is_ref = icmp eq i32 %kind, {BOX_REF}
+  br i1 %is_ref, label %ref, label %other
+ref:
+  %obj = call i8* @__hulk_box_ptr(double %val)
+  %tid_p = bitcast i8* %obj to i64*
+  %tid = load i64, i64* %tid_p
+  ret i64 %tid
+other:
+  ret i64 -1
+}}
+
+"));
+
+    // ── @__hulk_box_eq(double, double) -> i1  ─  `==`: números por valor,
+    //    cadenas por contenido y el resto por identidad; de clases distintas, falso
+    ctx.functions.push_str(&format!("\
+define i1 @__hulk_box_eq(double %a, double %b) {{
+entry:
+  %ka = call i32 @__hulk_box_kind(double %a)
+  %kb = call i32 @__hulk_box_kind(double %b)
+  %same_kind = icmp eq i32 %ka, %kb
+  br i1 %same_kind, label %check, label %differ
+differ:
+  ret i1 false
+check:
+  switch i32 %ka, label %bits [
+    i32 {BOX_NUM}, label %num
+    i32 {BOX_STR}, label %str
+  ]
+num:
+  %same_num = fcmp oeq double %a, %b
+  ret i1 %same_num
+str:
+  %sa = call i8* @__hulk_box_ptr(double %a)
+  %sb = call i8* @__hulk_box_ptr(double %b)
+  %cmp = call i32 @strcmp(i8* %sa, i8* %sb)
+  %same_str = icmp eq i32 %cmp, 0
+  ret i1 %same_str
+bits:
+  %a_bits = bitcast double %a to i64
+  %b_bits = bitcast double %b to i64
+  %same = icmp eq i64 %a_bits, %b_bits
+  ret i1 %same
+}}
+
+"));
+}
+
+/// `%__Buf`: texto en construcción en un bloque de `malloc` que crece al
+/// agregarle cadenas; `@__hulk_buf_finish` lo copia a una cadena del GC. Quien
+/// lo usa lo declara con `alloca` y lo pone a cero.
+fn emit_text_buffer(ctx: &mut Ctx) {
+    ctx.preamble.push_str("%__Buf = type { i8*, i64, i64 }\n");
+
+    // ── @__hulk_buf_append(%__Buf*, i8*)  ─  agrega una cadena, duplicando la capacidad si no cabe
+    // ── @__hulk_buf_finish(%__Buf*) -> i8*  ─  copia el texto al heap del GC y libera el buffer
+    ctx.functions.push_str(&format!("\
+define void @__hulk_buf_append(%__Buf* %b, i8* %s) {{
+entry:
+  %n = call i64 @strlen(i8* %s)
+  %data_p = getelementptr inbounds %__Buf, %__Buf* %b, i32 0, i32 0
+  %len_p = getelementptr inbounds %__Buf, %__Buf* %b, i32 0, i32 1
+  %cap_p = getelementptr inbounds %__Buf, %__Buf* %b, i32 0, i32 2
+  %data = load i8*, i8** %data_p
+  %len = load i64, i64* %len_p
+  %cap = load i64, i64* %cap_p
+  %new_len = add i64 %len, %n
+  %need = add i64 %new_len, 1
+  %fits = icmp ule i64 %need, %cap
+  br i1 %fits, label %copy, label %grow
+grow:
+  %doubled = shl i64 %cap, 1
+  %enough = icmp ugt i64 %doubled, %need
+  %wanted = select i1 %enough, i64 %doubled, i64 %need
+  %small = icmp ult i64 %wanted, {min}
+  %new_cap = select i1 %small, i64 {min}, i64 %wanted
+  %grown = call i8* @realloc(i8* %data, i64 %new_cap)
+  store i8* %grown, i8** %data_p
+  store i64 %new_cap, i64* %cap_p
+  br label %copy
+copy:
+  %buf = phi i8* [%data, %entry], [%grown, %grow]
+  %dst = getelementptr i8, i8* %buf, i64 %len
+  %n1 = add i64 %n, 1
+  call i8* @memcpy(i8* %dst, i8* %s, i64 %n1)
+  store i64 %new_len, i64* %len_p
+  ret void
+}}
+
+define i8* @__hulk_buf_finish(%__Buf* %b) {{
+entry:
+  %data_p = getelementptr inbounds %__Buf, %__Buf* %b, i32 0, i32 0
+  %len_p = getelementptr inbounds %__Buf, %__Buf* %b, i32 0, i32 1
+  %data = load i8*, i8** %data_p
+  %len = load i64, i64* %len_p
+  %size = add i64 %len, 1
+  %str = call i8* @__hulk_gc_alloc(i64 %size, i32 {raw})
+  call i8* @memcpy(i8* %str, i8* %data, i64 %size)
+  call void @free(i8* %data)
+  ret i8* %str
+}}
+
+", min = 64, raw = GC_RAW));
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Emisión de funciones de nivel superior
 // ─────────────────────────────────────────────────────────────────────────────
@@ -240,87 +355,52 @@ pub fn mangle_fn(name: &str) -> String {
     if name == "main" { "__hulk_main".to_string() } else { name.to_string() }
 }
 
-pub fn emit_function(ctx: &mut Ctx, fd: &FunctionDecl, gen_expr: &dyn Fn(&mut Ctx, &Spanned<Expr>) -> String) {
-    // Registrar tipo de retorno de la función
-    let ret_vty = match &fd.return_type {
-        Some(TypeAnnotation::Name(n)) => match n.as_str() {
-            "String" => ValTy::Str,
-            "Number" => ValTy::Num,
-            "Boolean" => ValTy::Bool,
-            other => ValTy::Obj(other.to_string()),
-        },
-        _ => ValTy::Num,
-    };
-    ctx.func_ret_types.insert(fd.name.clone(), ret_vty);
-
-    let ir_name = mangle_fn(&fd.name);
-    let mut sig = String::new();
-    for (i, p) in fd.params.iter().enumerate() {
-        if i > 0 { sig.push_str(", "); }
-        sig.push_str(&format!("double %{}", p.name));
+/// Firma LLVM de una función con nombre: la que le dio el chequeo semántico o,
+/// si no la hay, todo empaquetado.
+fn function_sig(ctx: &Ctx, name: &str, arity: usize) -> (Vec<ValTy>, ValTy) {
+    match ctx.sem.get_function(name) {
+        Some((params, ret)) if params.len() == arity => (
+            params.iter().map(|p| ValTy::from_type(&p.borrow())).collect(),
+            ValTy::from_type(&ret.borrow()),
+        ),
+        _ => (vec![ValTy::object(); arity], ValTy::object()),
     }
-    ctx.functions.push_str(&format!("define double @{}({}) {{\nentry:\n", ir_name, sig));
+}
+
+/// Emite `define <ret> @name(<params>)` con los parámetros en el ámbito y el
+/// valor del cuerpo convertido al tipo de retorno.
+fn emit_typed_function(ctx: &mut Ctx, name: &str, params: &[&String], body: &Spanned<Expr>, gen_expr: &GenExpr) {
+    let (param_tys, ret) = function_sig(ctx, name, params.len());
+    let sig: Vec<String> = params.iter().zip(&param_tys)
+        .map(|(p, ty)| format!("{} %{}", ty.llvm(), p))
+        .collect();
+    let outer = ctx.begin_function(&format!("define {} @{}({})", ret.llvm(), mangle_fn(name), sig.join(", ")), ret.clone());
     ctx.enter_scope();
 
-    for p in &fd.params {
-        let ptr = ctx.tmp();
-        ctx.emit(&format!("{} = alloca double", ptr));
-        ctx.emit(&format!("store double %{}, double* {}", p.name, ptr));
-        ctx.def_var(&p.name, &ptr, val_ty_from_annotation(&p.type_annotation));
+    for (p, ty) in params.iter().zip(param_tys) {
+        ctx.alloca_var(p, &format!("%{}", p), ty);
     }
 
-    let res = gen_expr(ctx, &fd.body);
-    ctx.emit(&format!("ret double {}", res));
-    ctx.functions.push_str("}\n\n");
+    let (res, ty) = gen_expr(ctx, body);
+    let res = ctx.coerce(&res, &ty, &ret);
+    ctx.end_function(&format!("ret {} {}", ret.llvm(), res), outer);
     ctx.exit_scope();
 }
 
+pub fn emit_function(ctx: &mut Ctx, fd: &FunctionDecl, gen_expr: &GenExpr) {
+    let params: Vec<&String> = fd.params.iter().map(|p| &p.name).collect();
+    emit_typed_function(ctx, &fd.name, &params, &fd.body, gen_expr);
+}
+
 /// Emitir un macro `def` como una función LLVM regular.
 /// Solo parámetros Normal son soportados a nivel de codegen; los Simbólicos (@), Placeholder ($)
 /// y Body (*) se tratan como paso por valor normal por ahora.
-pub fn emit_macro(ctx: &mut Ctx, md: &MacroDecl, gen_expr: &dyn Fn(&mut Ctx, &Spanned<Expr>) -> String) {
-    // Registrar tipo de retorno
-    let ret_vty = match &md.return_type {
-        Some(TypeAnnotation::Name(n)) => match n.as_str() {
-            "String" => ValTy::Str,
-            "Number" => ValTy::Num,
-            "Boolean" => ValTy::Bool,
-            other => ValTy::Obj(other.to_string()),
-        },
-        _ => ValTy::Num,
-    };
-    ctx.func_ret_types.insert(md.name.clone(), ret_vty);
-
-    let ir_name = mangle_fn(&md.name);
-    let mut sig = String::new();
-    for (i, p) in md.params.iter().enumerate() {
-        if i > 0 { sig.push_str(", "); }
-        let pname = match p {
-            MacroParam::Normal { name, .. } => name,
-            MacroParam::Symbolic { name, .. } => name,
-            MacroParam::Placeholder { name, .. } => name,
-            MacroParam::Body { name, .. } => name,
-        };
-        sig.push_str(&format!("double %{}", pname));
-    }
-    ctx.functions.push_str(&format!("define double @{}({}) {{\nentry:\n", ir_name, sig));
-    ctx.enter_scope();
-
-    for p in &md.params {
-        let (pname, ann) = match p {
-            MacroParam::Normal { name, type_annotation } => (name, Some(TypeAnnotation::Name(match type_annotation { TypeAnnotation::Name(n) => n.clone(), _ => "Number".to_string() }))),
-            MacroParam::Symbolic { name, type_annotation } => (name, Some(TypeAnnotation::Name(match type_annotation { TypeAnnotation::Name(n) => n.clone(), _ => "Number".to_string() }))),
-            MacroParam::Placeholder { name, type_annotation } => (name, Some(TypeAnnotation::Name(match type_annotation { TypeAnnotation::Name(n) => n.clone(), _ => "Number".to_string() }))),
-            MacroParam::Body { name, type_annotation } => (name, Some(TypeAnnotation::Name(match type_annotation { TypeAnnotation::Name(n) => n.clone(), _ => "Number".to_string() }))),
-        };
-        let ptr = ctx.tmp();
-        ctx.emit(&format!("{} = alloca double", ptr));
-        ctx.emit(&format!("store double %{}, double* {}", pname, ptr));
-        ctx.def_var(pname, &ptr, val_ty_from_annotation(&ann));
-    }
-
-    let res = gen_expr(ctx, &md.body);
-    ctx.emit(&format!("ret double {}", res));
-    ctx.functions.push_str("}\n\n");
-    ctx.exit_scope();
+pub fn emit_macro(ctx: &mut Ctx, md: &MacroDecl, gen_expr: &GenExpr) {
+    let params: Vec<&String> = md.params.iter().map(|p| match p {
+        MacroParam::Normal { name, .. }
+        | MacroParam::Symbolic { name, .. }
+        | MacroParam::Placeholder { name, .. }
+        | MacroParam::Body { name, .. } => name,
+    }).collect();
+    emit_typed_function(ctx, &md.name, &params, &md.body, gen_expr);
 }
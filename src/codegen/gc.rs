@@ -0,0 +1,588 @@
+use super::context::{ClassLayout, Ctx};
+use super::utils::{escape_llvm, ValTy, BOX_PAYLOAD_MASK};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Recolector de basura mark-and-sweep del runtime generado
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Cada bloque del heap lleva delante una cabecera `%__GcHeader`:
+//
+//   { i8* next, i64 size, i32 type_id, i32 mark }     (24 bytes)
+//
+// `next` enlaza todos los bloques, `size` es el tamaño del contenido y
+// `type_id` dice cómo recorrerlo:
+//
+// - `GC_RAW` (0): sin referencias (strings).
+// - type-id de una clase, del vector o del diccionario: solo los slots marcados en `@__gc_ptr_maps`.
+// - `GC_VALUES` (-1): todas las palabras (datos de vectores, entornos de clausuras).
+//
+// Una palabra puede ser un puntero crudo o uno empaquetado en un `double` (los
+// dos tienen los mismos bits), así que una candidata solo se marca si está en
+// `@__gc_table`, el conjunto de direcciones de bloques vivos.
+//
+// Con `HULK_GC_STRESS` se colecta en cada asignación y los bloques liberados
+// se rellenan con 0xAB, para que una raíz olvidada falle de forma visible.
+//
+// Las raíces son los frames de la *shadow stack*: cada función que lo necesita
+// reserva en su entrada un frame `{ i8* prev, i64 count, [N x double] slots }`
+// y lo enlaza en `@__gc_frames` hasta su `ret`.
+
+/// Bytes de la cabecera que precede a cada bloque del heap.
+pub const GC_HEADER_SIZE: u64 = 24;
+/// type-id de los bloques sin referencias.
+pub const GC_RAW: i32 = 0;
+/// type-id de los bloques cuyas palabras pueden ser todas referencias.
+pub const GC_VALUES: i32 = -1;
+/// Bytes que se pueden asignar entre colecciones mientras el heap vivo sea menor.
+pub const GC_MIN_THRESHOLD: u64 = 1 << 20;
+
+/// Marca en la entrada de cada función donde va el frame de raíces;
+/// `Ctx::end_function` la reemplaza cuando ya sabe cuántos slots hacen falta.
+pub const FRAME_MARKER: &str = "  ; gc.frame\n";
+
+/// Marca antes de cada `return` anticipado donde va el desenlace del frame;
+/// `Ctx::end_function` la reemplaza junto con `FRAME_MARKER`.
+pub const UNLINK_MARKER: &str = "  ; gc.unlink\n";
+
+const STRESS_ENV: &str = "HULK_GC_STRESS";
+const STATS_ENV: &str = "HULK_GC_STATS";
+const OOM_MSG: &str = "Runtime error: out of memory";
+const STATS_FMT: &str = "gc: %ld collections, %ld bytes freed, %ld bytes in heap at exit\n";
+
+/// Código de entrada de una función con `slots` raíces: reserva el frame,
+/// lo enlaza en la shadow stack y define `%gc.s0 .. %gc.s{N-1}`.
+pub fn frame_prologue(slots: usize) -> String {
+    let ty = format!("{{ i8*, i64, [{} x double] }}", slots);
+    let arr = format!("[{} x double]", slots);
+    let mut code = format!("  %gc.frame = alloca {}\n", ty);
+    code.push_str("  %gc.prev = load i8*, i8** @__gc_frames\n");
+    code.push_str(&format!("  %gc.link = getelementptr inbounds {ty}, {ty}* %gc.frame, i32 0, i32 0\n"));
+    code.push_str("  store i8* %gc.prev, i8** %gc.link\n");
+    code.push_str(&format!("  %gc.count = getelementptr inbounds {ty}, {ty}* %gc.frame, i32 0, i32 1\n"));
+    code.push_str(&format!("  store i64 {}, i64* %gc.count\n", slots));
+    code.push_str(&format!("  %gc.slots = getelementptr inbounds {ty}, {ty}* %gc.frame, i32 0, i32 2\n"));
+    code.push_str(&format!("  store {arr} zeroinitializer, {arr}* %gc.slots\n"));
+    for i in 0..slots {
+        code.push_str(&format!(
+            "  %gc.s{i} = getelementptr inbounds {arr}, {arr}* %gc.slots, i64 0, i64 {i}\n"
+        ));
+    }
+    code.push_str(&format!("  %gc.top = bitcast {ty}* %gc.frame to i8*\n"));
+    code.push_str("  store i8* %gc.top, i8** @__gc_frames\n");
+    code
+}
+
+/// Slots de la clase que pueden guardar referencias (bit i = slot i): todos
+/// menos los atributos que se guardan como número o booleano.
+fn pointer_mask(layout: &ClassLayout) -> u64 {
+    let mut mask = 0u64;
+    for attr in &layout.attr_order {
+        let idx = layout.attr_indices[attr];
+        if idx >= 64 {
+            continue; // el recorrido trata todo slot a partir del 64 como referencia
+        }
+        if !matches!(layout.attr_tys.get(attr), Some(ValTy::Num | ValTy::Bool)) {
+            mask |= 1 << idx;
+        }
+    }
+    mask
+}
+
+fn c_string(ctx: &mut Ctx, name: &str, value: &str) {
+    ctx.globals.push_str(&format!(
+        "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+        name, value.len() + 1, escape_llvm(value)
+    ));
+}
+
+fn c_string_ptr(name: &str, value: &str) -> String {
+    let len = value.len() + 1;
+    format!("i8* getelementptr inbounds ([{len} x i8], [{len} x i8]* {name}, i64 0, i64 0)")
+}
+
+/// Emite el estado del GC, `@__gc_ptr_maps` (indexada por type-id) y las funciones
+/// del runtime. Va al final, cuando todas las clases ya tienen type-id.
+/// `builtins` da la máscara de los tipos implícitos (vector, diccionario), cuyos
+/// slots no salen de atributos declarados.
+pub fn emit_gc_runtime(ctx: &mut Ctx, builtins: &[(u32, u64)]) {
+    let count = ctx.next_type_id as usize;
+    let mut masks = vec![0u64; count];
+    for layout in ctx.classes.values() {
+        masks[layout.type_id as usize] = pointer_mask(layout);
+    }
+    for &(type_id, mask) in builtins {
+        masks[type_id as usize] = mask;
+    }
+    let entries: Vec<String> = masks.iter().map(|m| format!("i64 {}", *m as i64)).collect();
+
+    ctx.globals.push_str(&format!("\
+%__GcHeader = type {{ i8*, i64, i32, i32 }}
+@__gc_ptr_maps = private unnamed_addr constant [{count} x i64] [{entries}]
+@__gc_objects = internal global i8* null
+@__gc_frames = internal global i8* null
+@__gc_table = internal global i64* null
+@__gc_table_cap = internal global i64 0
+@__gc_table_count = internal global i64 0
+@__gc_stack = internal global i8** null
+@__gc_stack_len = internal global i64 0
+@__gc_stack_cap = internal global i64 0
+@__gc_allocated = internal global i64 0
+@__gc_threshold = internal global i64 {threshold}
+@__gc_stress = internal global i1 false
+@__gc_collections = internal global i64 0
+@__gc_freed = internal global i64 0
+",
+        entries = entries.join(", "),
+        threshold = GC_MIN_THRESHOLD,
+    ));
+    c_string(ctx, "@.gc_stress_env", STRESS_ENV);
+    c_string(ctx, "@.gc_stats_env", STATS_ENV);
+    c_string(ctx, "@.gc_oom_msg", OOM_MSG);
+    c_string(ctx, "@.gc_stats_fmt", STATS_FMT);
+
+    let stress_env = c_string_ptr("@.gc_stress_env", STRESS_ENV);
+    let stats_env = c_string_ptr("@.gc_stats_env", STATS_ENV);
+    let oom_msg = c_string_ptr("@.gc_oom_msg", OOM_MSG);
+    let stats_fmt = c_string_ptr("@.gc_stats_fmt", STATS_FMT);
+    let header = GC_HEADER_SIZE;
+    let payload = BOX_PAYLOAD_MASK;
+    let min_threshold = GC_MIN_THRESHOLD;
+
+    // ── Arranque y errores
+    ctx.functions.push_str(&format!("\
+define void @__hulk_gc_init() {{
+entry:
+  %stress = call i8* @getenv({stress_env})
+  %is_stress = icmp ne i8* %stress, null
+  br i1 %is_stress, label %set_stress, label %done
+set_stress:
+  store i1 true, i1* @__gc_stress
+  store i64 0, i64* @__gc_threshold
+  br label %done
+done:
+  ret void
+}}
+
+define internal void @__gc_out_of_memory() {{
+entry:
+  call i32 @puts({oom_msg})
+  call void @abort()
+  unreachable
+}}
+
+"));
+
+    // ── Conjunto de bloques vivos: hash abierto con sondeo lineal, 0 = libre.
+    //    Se reconstruye tras cada barrido, así que no necesita borrados.
+    ctx.functions.push_str("\
+define internal i64 @__gc_hash(i64 %key) {
+entry:
+  %shifted = lshr i64 %key, 3
+  %mixed = mul i64 %shifted, -7046029254386353131
+  %high = lshr i64 %mixed, 32
+  %hash = xor i64 %mixed, %high
+  ret i64 %hash
+}
+
+define internal void @__gc_table_insert(i64 %key) {
+entry:
+  %table = load i64*, i64** @__gc_table
+  %cap = load i64, i64* @__gc_table_cap
+  %mask = sub i64 %cap, 1
+  %hash = call i64 @__gc_hash(i64 %key)
+  br label %probe
+probe:
+  %i = phi i64 [ %hash, %entry ], [ %i_next, %busy ]
+  %idx = and i64 %i, %mask
+  %slot = getelementptr i64, i64* %table, i64 %idx
+  %cur = load i64, i64* %slot
+  %empty = icmp eq i64 %cur, 0
+  br i1 %empty, label %insert, label %busy
+busy:
+  %i_next = add i64 %idx, 1
+  br label %probe
+insert:
+  store i64 %key, i64* %slot
+  %n = load i64, i64* @__gc_table_count
+  %n1 = add i64 %n, 1
+  store i64 %n1, i64* @__gc_table_count
+  ret void
+}
+
+define internal i1 @__gc_table_contains(i64 %key) {
+entry:
+  %cap = load i64, i64* @__gc_table_cap
+  %no_table = icmp eq i64 %cap, 0
+  %null = icmp eq i64 %key, 0
+  %low = and i64 %key, 7
+  %unaligned = icmp ne i64 %low, 0
+  %bad0 = or i1 %no_table, %null
+  %bad = or i1 %bad0, %unaligned
+  br i1 %bad, label %no, label %search
+search:
+  %table = load i64*, i64** @__gc_table
+  %mask = sub i64 %cap, 1
+  %hash = call i64 @__gc_hash(i64 %key)
+  br label %probe
+probe:
+  %i = phi i64 [ %hash, %search ], [ %i_next, %next ]
+  %idx = and i64 %i, %mask
+  %slot = getelementptr i64, i64* %table, i64 %idx
+  %cur = load i64, i64* %slot
+  %hit = icmp eq i64 %cur, %key
+  br i1 %hit, label %yes, label %miss
+miss:
+  %end = icmp eq i64 %cur, 0
+  br i1 %end, label %no, label %next
+next:
+  %i_next = add i64 %idx, 1
+  br label %probe
+yes:
+  ret i1 true
+no:
+  ret i1 false
+}
+
+define internal void @__gc_table_rebuild(i64 %cap) {
+entry:
+  %old = load i64*, i64** @__gc_table
+  %old_raw = bitcast i64* %old to i8*
+  call void @free(i8* %old_raw)
+  %mem = call i8* @calloc(i64 %cap, i64 8)
+  %failed = icmp eq i8* %mem, null
+  br i1 %failed, label %oom, label %ready
+oom:
+  call void @__gc_out_of_memory()
+  unreachable
+ready:
+  %table = bitcast i8* %mem to i64*
+  store i64* %table, i64** @__gc_table
+  store i64 %cap, i64* @__gc_table_cap
+  store i64 0, i64* @__gc_table_count
+  %first = load i8*, i8** @__gc_objects
+  br label %loop
+loop:
+  %cur = phi i8* [ %first, %ready ], [ %next, %body ]
+  %done = icmp eq i8* %cur, null
+  br i1 %done, label %exit, label %body
+body:
+  %obj = getelementptr i8, i8* %cur, i64 24
+  %key = ptrtoint i8* %obj to i64
+  call void @__gc_table_insert(i64 %key)
+  %link = bitcast i8* %cur to i8**
+  %next = load i8*, i8** %link
+  br label %loop
+exit:
+  ret void
+}
+
+define internal void @__gc_table_add(i8* %obj) {
+entry:
+  %n = load i64, i64* @__gc_table_count
+  %cap = load i64, i64* @__gc_table_cap
+  %n1 = add i64 %n, 1
+  %needed = shl i64 %n1, 1
+  %full = icmp ugt i64 %needed, %cap
+  br i1 %full, label %grow, label %insert
+grow:
+  %doubled = shl i64 %cap, 1
+  %small = icmp ult i64 %doubled, 1024
+  %new_cap = select i1 %small, i64 1024, i64 %doubled
+  call void @__gc_table_rebuild(i64 %new_cap)
+  br label %insert
+insert:
+  %key = ptrtoint i8* %obj to i64
+  call void @__gc_table_insert(i64 %key)
+  ret void
+}
+
+");
+
+    // ── Asignación
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_gc_alloc(i64 %size, i32 %type_id) {{
+entry:
+  %since = load i64, i64* @__gc_allocated
+  %after = add i64 %since, %size
+  %limit = load i64, i64* @__gc_threshold
+  %over = icmp ugt i64 %after, %limit
+  br i1 %over, label %collect, label %alloc
+collect:
+  call void @__hulk_gc_collect()
+  br label %alloc
+alloc:
+  %since2 = load i64, i64* @__gc_allocated
+  %after2 = add i64 %since2, %size
+  store i64 %after2, i64* @__gc_allocated
+  %total = add i64 %size, {header}
+  %raw = call i8* @calloc(i64 1, i64 %total)
+  %failed = icmp eq i8* %raw, null
+  br i1 %failed, label %oom, label %link
+oom:
+  call void @__gc_out_of_memory()
+  unreachable
+link:
+  %obj = getelementptr i8, i8* %raw, i64 {header}
+  call void @__gc_table_add(i8* %obj)
+  %h = bitcast i8* %raw to %__GcHeader*
+  %next_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 0
+  %head = load i8*, i8** @__gc_objects
+  store i8* %head, i8** %next_p
+  %size_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 1
+  store i64 %size, i64* %size_p
+  %tid_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 2
+  store i32 %type_id, i32* %tid_p
+  store i8* %raw, i8** @__gc_objects
+  ret i8* %obj
+}}
+
+"));
+
+    // ── Marcado: pila explícita de bloques grises, sin recursión nativa
+    ctx.functions.push_str(&format!("\
+define internal void @__gc_push(i8* %obj) {{
+entry:
+  %len = load i64, i64* @__gc_stack_len
+  %cap = load i64, i64* @__gc_stack_cap
+  %full = icmp eq i64 %len, %cap
+  br i1 %full, label %grow, label %push
+grow:
+  %doubled = shl i64 %cap, 1
+  %small = icmp ult i64 %doubled, 256
+  %new_cap = select i1 %small, i64 256, i64 %doubled
+  %old = load i8**, i8*** @__gc_stack
+  %old_raw = bitcast i8** %old to i8*
+  %bytes = mul i64 %new_cap, 8
+  %mem = call i8* @realloc(i8* %old_raw, i64 %bytes)
+  %failed = icmp eq i8* %mem, null
+  br i1 %failed, label %oom, label %grown
+oom:
+  call void @__gc_out_of_memory()
+  unreachable
+grown:
+  %new_stack = bitcast i8* %mem to i8**
+  store i8** %new_stack, i8*** @__gc_stack
+  store i64 %new_cap, i64* @__gc_stack_cap
+  br label %push
+push:
+  %stack = load i8**, i8*** @__gc_stack
+  %top = getelementptr i8*, i8** %stack, i64 %len
+  store i8* %obj, i8** %top
+  %len1 = add i64 %len, 1
+  store i64 %len1, i64* @__gc_stack_len
+  ret void
+}}
+
+define internal void @__gc_mark_word(i64 %tagged) {{
+entry:
+  %word = and i64 %tagged, {payload}
+  %live = call i1 @__gc_table_contains(i64 %word)
+  br i1 %live, label %check, label %done
+check:
+  %obj = inttoptr i64 %word to i8*
+  %raw = getelementptr i8, i8* %obj, i64 -{header}
+  %h = bitcast i8* %raw to %__GcHeader*
+  %mark_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 3
+  %mark = load i32, i32* %mark_p
+  %marked = icmp ne i32 %mark, 0
+  br i1 %marked, label %done, label %set
+set:
+  store i32 1, i32* %mark_p
+  call void @__gc_push(i8* %obj)
+  br label %done
+done:
+  ret void
+}}
+
+define internal void @__gc_scan(i8* %obj) {{
+entry:
+  %raw = getelementptr i8, i8* %obj, i64 -{header}
+  %h = bitcast i8* %raw to %__GcHeader*
+  %size_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 1
+  %size = load i64, i64* %size_p
+  %words = lshr i64 %size, 3
+  %tid_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 2
+  %tid = load i32, i32* %tid_p
+  %is_raw = icmp eq i32 %tid, {raw}
+  br i1 %is_raw, label %done, label %lookup
+lookup:
+  %tid64 = zext i32 %tid to i64
+  %known = icmp ult i64 %tid64, {count}
+  br i1 %known, label %typed, label %scan
+typed:
+  %map_p = getelementptr inbounds [{count} x i64], [{count} x i64]* @__gc_ptr_maps, i64 0, i64 %tid64
+  %map = load i64, i64* %map_p
+  br label %scan
+scan:
+  %mask = phi i64 [ -1, %lookup ], [ %map, %typed ]
+  %slots = bitcast i8* %obj to i64*
+  br label %loop
+loop:
+  %i = phi i64 [ 0, %scan ], [ %i_next, %next ]
+  %more = icmp ult i64 %i, %words
+  br i1 %more, label %test, label %done
+test:
+  %beyond = icmp uge i64 %i, 64
+  %bit_i = and i64 %i, 63
+  %bit = shl i64 1, %bit_i
+  %hit = and i64 %mask, %bit
+  %in_map = icmp ne i64 %hit, 0
+  %candidate = or i1 %beyond, %in_map
+  br i1 %candidate, label %mark, label %next
+mark:
+  %slot = getelementptr i64, i64* %slots, i64 %i
+  %word = load i64, i64* %slot
+  call void @__gc_mark_word(i64 %word)
+  br label %next
+next:
+  %i_next = add i64 %i, 1
+  br label %loop
+done:
+  ret void
+}}
+
+", raw = GC_RAW));
+
+    // ── Colección: raíces de la shadow stack, marcado y barrido de la lista
+    ctx.functions.push_str(&format!("\
+define void @__hulk_gc_collect() {{
+entry:
+  %top = load i8*, i8** @__gc_frames
+  br label %frames
+frames:
+  %frame = phi i8* [ %top, %entry ], [ %prev, %frame_done ]
+  %no_frame = icmp eq i8* %frame, null
+  br i1 %no_frame, label %drain, label %frame_scan
+frame_scan:
+  %link = bitcast i8* %frame to i8**
+  %prev = load i8*, i8** %link
+  %count_raw = getelementptr i8, i8* %frame, i64 8
+  %count_p = bitcast i8* %count_raw to i64*
+  %count = load i64, i64* %count_p
+  %slots_raw = getelementptr i8, i8* %frame, i64 16
+  %slots = bitcast i8* %slots_raw to i64*
+  br label %slot_loop
+slot_loop:
+  %i = phi i64 [ 0, %frame_scan ], [ %i_next, %slot_body ]
+  %more = icmp ult i64 %i, %count
+  br i1 %more, label %slot_body, label %frame_done
+slot_body:
+  %slot = getelementptr i64, i64* %slots, i64 %i
+  %word = load i64, i64* %slot
+  call void @__gc_mark_word(i64 %word)
+  %i_next = add i64 %i, 1
+  br label %slot_loop
+frame_done:
+  br label %frames
+drain:
+  %len = load i64, i64* @__gc_stack_len
+  %empty = icmp eq i64 %len, 0
+  br i1 %empty, label %sweep, label %pop
+pop:
+  %len1 = sub i64 %len, 1
+  store i64 %len1, i64* @__gc_stack_len
+  %stack = load i8**, i8*** @__gc_stack
+  %gray_p = getelementptr i8*, i8** %stack, i64 %len1
+  %gray = load i8*, i8** %gray_p
+  call void @__gc_scan(i8* %gray)
+  br label %drain
+sweep:
+  %at = phi i8** [ @__gc_objects, %drain ], [ %at, %dispose ], [ %next_p, %keep ]
+  %live = phi i64 [ 0, %drain ], [ %live, %dispose ], [ %live_next, %keep ]
+  %freed = phi i64 [ 0, %drain ], [ %freed_next, %dispose ], [ %freed, %keep ]
+  %cur = load i8*, i8** %at
+  %end = icmp eq i8* %cur, null
+  br i1 %end, label %finish, label %check
+check:
+  %h = bitcast i8* %cur to %__GcHeader*
+  %next_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 0
+  %size_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 1
+  %size = load i64, i64* %size_p
+  %mark_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 3
+  %mark = load i32, i32* %mark_p
+  %marked = icmp ne i32 %mark, 0
+  br i1 %marked, label %keep, label %release
+keep:
+  store i32 0, i32* %mark_p
+  %live_next = add i64 %live, %size
+  br label %sweep
+release:
+  %next = load i8*, i8** %next_p
+  store i8* %next, i8** %at
+  %freed_next = add i64 %freed, %size
+  %scrub = load i1, i1* @__gc_stress
+  br i1 %scrub, label %poison, label %dispose
+poison:
+  %block = add i64 %size, {header}
+  call i8* @memset(i8* %cur, i32 171, i64 %block)
+  br label %dispose
+dispose:
+  call void @free(i8* %cur)
+  br label %sweep
+finish:
+  %collections = load i64, i64* @__gc_collections
+  %collections1 = add i64 %collections, 1
+  store i64 %collections1, i64* @__gc_collections
+  %total_freed = load i64, i64* @__gc_freed
+  %total_freed1 = add i64 %total_freed, %freed
+  store i64 %total_freed1, i64* @__gc_freed
+  store i64 0, i64* @__gc_allocated
+  %stress = load i1, i1* @__gc_stress
+  br i1 %stress, label %rebuild, label %threshold
+threshold:
+  %big = icmp ugt i64 %live, {min_threshold}
+  %limit = select i1 %big, i64 %live, i64 {min_threshold}
+  store i64 %limit, i64* @__gc_threshold
+  br label %rebuild
+rebuild:
+  %cap = load i64, i64* @__gc_table_cap
+  %small = icmp ult i64 %cap, 1024
+  %new_cap = select i1 %small, i64 1024, i64 %cap
+  call void @__gc_table_rebuild(i64 %new_cap)
+  ret void
+}}
+
+"));
+
+    // ── Cierre: libera todo el heap y, con HULK_GC_STATS, informa por stderr
+    ctx.functions.push_str(&format!("\
+define void @__hulk_gc_shutdown() {{
+entry:
+  %first = load i8*, i8** @__gc_objects
+  br label %loop
+loop:
+  %cur = phi i8* [ %first, %entry ], [ %next, %body ]
+  %heap = phi i64 [ 0, %entry ], [ %heap_next, %body ]
+  %done = icmp eq i8* %cur, null
+  br i1 %done, label %report, label %body
+body:
+  %h = bitcast i8* %cur to %__GcHeader*
+  %next_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 0
+  %next = load i8*, i8** %next_p
+  %size_p = getelementptr inbounds %__GcHeader, %__GcHeader* %h, i32 0, i32 1
+  %size = load i64, i64* %size_p
+  %heap_next = add i64 %heap, %size
+  call void @free(i8* %cur)
+  br label %loop
+report:
+  store i8* null, i8** @__gc_objects
+  %stats = call i8* @getenv({stats_env})
+  %want = icmp ne i8* %stats, null
+  br i1 %want, label %print, label %exit
+print:
+  %collections = load i64, i64* @__gc_collections
+  %freed = load i64, i64* @__gc_freed
+  call i32 (i32, i8*, ...) @dprintf(i32 2, {stats_fmt}, i64 %collections, i64 %freed, i64 %heap)
+  br label %exit
+exit:
+  %table = load i64*, i64** @__gc_table
+  %table_raw = bitcast i64* %table to i8*
+  call void @free(i8* %table_raw)
+  %stack = load i8**, i8*** @__gc_stack
+  %stack_raw = bitcast i8** %stack to i8*
+  call void @free(i8* %stack_raw)
+  ret void
+}}
+
+"));
+}
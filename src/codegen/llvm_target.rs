@@ -2,51 +2,88 @@ use crate::ast::nodes::*;
 use crate::semantic::Context;
 use std::collections::HashMap;
 
-use super::context::Ctx;
-use super::classes::{topo_sort_classes, emit_class};
+use super::context::{protocol_thunk, Ctx, ProtocolLayout};
+use super::utils::ValTy;
+use super::classes::{topo_sort_classes, register_class, emit_class};
 use super::functions::{emit_function, emit_macro, emit_helper_functions};
-use super::expressions::{gen_expr, infer_return_type_from_body};
+use super::expressions::{call_method, gen_expr};
 use super::builtins::emit_vector_type;
+use super::gc::emit_gc_runtime;
+use super::exceptions::{emit_crash_handler, emit_exception_runtime, runtime_throw_at};
+use super::strings::emit_string_helpers;
+use super::maps::{emit_map_type, MAP_POINTER_MASK};
+use crate::errors::runtime::DISPATCH_MESSAGE;
 
-/// Genera una función dispatch para un protocolo functor
-fn emit_protocol_dispatch(ctx: &mut Ctx, proto_name: &str, implementations: &[(u32, String)]) {
-    // solo soportamos protocolos con un método invoke que toma un argumento
-    // Genera: double @{ProtoName}_dispatch(i8* obj, double arg) { switch... }
-    
+/// Registra el protocolo `name`: sus métodos (con los de los protocolos que
+/// extiende) y las clases que conforman a él estructuralmente.
+fn register_protocol(ctx: &mut Ctx, name: &str, classes: &[String]) {
+    let Some(proto) = ctx.sem.types.get(name).cloned() else { return };
+    let mut methods = HashMap::new();
+    let mut current = Some(proto.clone());
+    while let Some(p) = current {
+        for (m, info) in &p.borrow().methods {
+            methods.entry(m.clone()).or_insert_with(|| (
+                info.params.iter().map(|(_, t)| ValTy::from_type(&t.borrow())).collect(),
+                ValTy::from_type(&info.return_type.borrow()),
+            ));
+        }
+        current = p.borrow().parent.clone();
+    }
+
+    let implementations = classes.iter()
+        .filter(|c| ctx.sem.types.get(c.as_str()).is_some_and(|t| t.borrow().conforms_to(&proto)))
+        .filter_map(|c| ctx.classes.get(c).map(|l| (l.type_id, c.clone())))
+        .collect();
+    ctx.protocols.insert(name.to_string(), ProtocolLayout { methods, implementations });
+}
+
+/// Emite el thunk `@{P}_{m}_dispatch(i8* obj, <parámetros del protocolo>)`: elige
+/// la implementación según el type-id del objeto y convierte argumentos y
+/// retorno entre la firma del protocolo y la del método de cada clase.
+fn emit_protocol_dispatch(ctx: &mut Ctx, proto: &str, method: &str) {
+    let layout = &ctx.protocols[proto];
+    let (params, ret) = layout.methods[method].clone();
+    let cases: Vec<(u32, String, String, Vec<ValTy>, ValTy)> = layout.implementations.iter()
+        .filter_map(|(tid, class_name)| {
+            let class = ctx.classes.get(class_name)?;
+            let (cparams, cret) = class.method_sigs.get(method)?.clone();
+            Some((*tid, class_name.clone(), class.method_names[method].clone(), cparams, cret))
+        })
+        .collect();
+
+    let args: Vec<(String, ValTy)> = params.iter().enumerate()
+        .map(|(i, ty)| (format!("%a{}", i), ty.clone()))
+        .collect();
+    // La posición de la llamada va al final, para el `DispatchError`
+    let sig: Vec<String> = std::iter::once("i8* %obj".to_string())
+        .chain(args.iter().map(|(a, ty)| format!("{} {}", ty.llvm(), a)))
+        .chain(["double %line".to_string(), "double %column".to_string()])
+        .collect();
     ctx.functions.push_str(&format!(
-        "define double @{}_dispatch(i8* %obj, double %arg) {{\nentry:\n",
-        proto_name
+        "define {} {}({}) {{\nentry:\n", ret.llvm(), protocol_thunk(proto, method), sig.join(", ")
     ));
-    
-    // Cargar type_id del objeto (primer campo del struct)
+
+    // Cargar type_id del objeto (primer campo del struct) y saltar a su caso
     let obj_ptr = ctx.tmp();
-    ctx.functions.push_str(&format!("  {} = bitcast i8* %obj to i64*\n", obj_ptr));
-    let type_id_val = ctx.tmp();
-    ctx.functions.push_str(&format!("  {} = load i64, i64* {}\n", type_id_val, obj_ptr));
+    ctx.emit(&format!("{} = bitcast i8* %obj to i64*", obj_ptr));
     let type_id = ctx.tmp();
-    ctx.functions.push_str(&format!("  {} = trunc i64 {} to i32\n", type_id, type_id_val));
-    
-    // Switch basado en type_id
-    ctx.functions.push_str(&format!("  switch i32 {}, label %default [\n", type_id));
-    for (tid, class_name) in implementations {
-        let label = format!("case_{}", class_name);
-        ctx.functions.push_str(&format!("    i32 {}, label %{}\n", tid, label));
+    ctx.emit(&format!("{} = load i64, i64* {}", type_id, obj_ptr));
+    ctx.emit(&format!("switch i64 {}, label %default [", type_id));
+    for (tid, class_name, ..) in &cases {
+        ctx.emit(&format!("  i64 {}, label %case_{}", tid, class_name));
     }
-    ctx.functions.push_str("  ]\n\n");
-    
-    // Casos para cada implementación
-    for (_tid, class_name) in implementations {
-        let label = format!("case_{}", class_name);
-        let result = ctx.tmp();
-        ctx.functions.push_str(&format!("{}:\n", label));
-        ctx.functions.push_str(&format!("  {} = call double @{}_invoke(i8* %obj, double %arg)\n", result, class_name));
-        ctx.functions.push_str(&format!("  ret double {}\n\n", result));
+    ctx.emit("]");
+
+    for (_, class_name, func, cparams, cret) in &cases {
+        ctx.emit_label(&format!("case_{}", class_name));
+        let r = call_method(ctx, func, "%obj", cparams, cret, &args);
+        let r = ctx.coerce(&r, cret, &ret);
+        ctx.emit(&format!("ret {} {}", ret.llvm(), r));
     }
-    
-    // Caso por defecto: error
-    ctx.functions.push_str("default:\n");
-    ctx.functions.push_str("  call void @abort()\n");
-    ctx.functions.push_str("  unreachable\n");
+
+    // Caso por defecto: ningún tipo conocido implementa el método
+    ctx.emit_label("default");
+    runtime_throw_at(ctx, "DispatchError", DISPATCH_MESSAGE, "%line", "%column");
     ctx.functions.push_str("}\n\n");
 }
 
@@ -56,13 +93,13 @@ impl super::CodeGenerator for LlvmGenerator {
     fn generate(&self, program: &Program, context: &Context) -> String {
         let mut ctx = Ctx::new(context);
 
-        // Primero, registrar todos los protocolos
-        let mut protocols: HashMap<String, &ProtocolDecl> = HashMap::new();
-        for decl in &program.declarations {
-            if let Declaration::Protocol(pd) = decl {
-                protocols.insert(pd.name.clone(), pd);
-            }
-        }
+        // Primero, los nombres de todos los protocolos
+        let protocols: Vec<&String> = program.declarations.iter()
+            .filter_map(|decl| match decl {
+                Declaration::Protocol(pd) => Some(&pd.name),
+                _ => None,
+            })
+            .collect();
 
         let mut parent_map: HashMap<String, String> = HashMap::new();
         let mut decl_map: HashMap<String, &TypeDecl> = HashMap::new();
@@ -78,29 +115,35 @@ impl super::CodeGenerator for LlvmGenerator {
         let ordered = topo_sort_classes(&decl_map, &parent_map);
         
         // Emitir tipo Vector implícito antes de las clases del usuario
-        emit_vector_type(&mut ctx);
+        let vector_type_id = emit_vector_type(&mut ctx);
         
+        // Registrar todas las clases antes de emitir cuerpos: un método puede
+        // instanciar o llamar a una clase declarada después
         for name in &ordered {
             if let Some(td) = decl_map.get(name.as_str()) {
-                emit_class(&mut ctx, td, &gen_expr, &infer_return_type_from_body);
-                
-                // Detectar si este tipo implementa algún protocolo (tiene método invoke)
-                for method in &td.methods {
-                    if method.name == "invoke" {
-                        // Este tipo es un functor, registrarlo para todos los protocolos con invoke
-                        for (proto_name, proto) in &protocols {
-                            if proto.methods.iter().any(|m| m.name == "invoke") {
-                                if let Some(layout) = ctx.classes.get(name.as_str()) {
-                                    ctx.protocol_implementations
-                                        .entry(proto_name.clone())
-                                        .or_insert_with(Vec::new)
-                                        .push((layout.type_id, name.clone()));
-                                }
-                            }
-                        }
-                        break;
-                    }
-                }
+                register_class(&mut ctx, td);
+            }
+        }
+        // El diccionario toma su type-id después de las clases, que no cambian
+        // de número por usarlo o no
+        let map_type_id = emit_map_type(&mut ctx);
+
+        // Los protocolos se registran antes de emitir cuerpos: una llamada sobre
+        // un valor tipado con un protocolo pasa por su thunk
+        for name in protocols {
+            register_protocol(&mut ctx, name, &ordered);
+        }
+        // `Iterable` (el de `T*`) viene del contexto semántico; también lo
+        // implementan los vectores y los diccionarios
+        register_protocol(&mut ctx, "Iterable", &ordered);
+        if let Some(iterable) = ctx.protocols.get_mut("Iterable") {
+            iterable.implementations.push((vector_type_id, "__Vector".to_string()));
+            iterable.implementations.push((map_type_id, "__Map".to_string()));
+        }
+
+        for name in &ordered {
+            if let Some(td) = decl_map.get(name.as_str()) {
+                emit_class(&mut ctx, td, &gen_expr);
             }
         }
 
@@ -112,18 +155,35 @@ impl super::CodeGenerator for LlvmGenerator {
             }
         }
 
-        // Generar funciones dispatch para protocolos con invoke
-        let protocol_impls = ctx.protocol_implementations.clone();
-        for (proto_name, implementations) in &protocol_impls {
-            if !implementations.is_empty() {
-                emit_protocol_dispatch(&mut ctx, proto_name, implementations);
-            }
+        // Un thunk por método de cada protocolo
+        let mut thunks: Vec<(String, String)> = ctx.protocols.iter()
+            .flat_map(|(p, layout)| layout.methods.keys().map(move |m| (p.clone(), m.clone())))
+            .collect();
+        thunks.sort();
+        for (proto, method) in &thunks {
+            emit_protocol_dispatch(&mut ctx, proto, method);
         }
 
+        // Expresión principal del programa: @__hulk_main
+        let outer = ctx.begin_function("define double @__hulk_main()", ValTy::object());
+        ctx.enter_scope();
+        let (res, ty) = gen_expr(&mut ctx, &program.expr);
+        let res = ctx.box_value(&res, &ty);
+        ctx.end_function(&format!("ret double {}", res), outer);
+        ctx.exit_scope();
+
         emit_helper_functions(&mut ctx);
+        emit_string_helpers(&mut ctx);
+        // Del vector solo el puntero a los datos es referencia
+        emit_gc_runtime(&mut ctx, &[(vector_type_id, 1 << 1), (map_type_id, MAP_POINTER_MASK)]);
+        emit_exception_runtime(&mut ctx);
+        emit_crash_handler(&mut ctx);
 
         ctx.functions.push_str("define i32 @main() {\nentry:\n");
+        ctx.functions.push_str("  call void @__hulk_install_crash_handler()\n");
+        ctx.functions.push_str("  call void @__hulk_gc_init()\n");
         ctx.functions.push_str("  call double @__hulk_main()\n");
+        ctx.functions.push_str("  call void @__hulk_gc_shutdown()\n");
         ctx.functions.push_str("  ret i32 0\n}\n");
 
         format!("{}\n{}\n{}\n{}", ctx.preamble, ctx.globals, ctx.functions, ctx.lambda_defs)
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+
+use crate::errors::runtime::KEY_MESSAGE;
+use crate::utils::Position;
+use super::context::{ClassLayout, Ctx};
+use super::exceptions::runtime_throw;
+use super::gc::{frame_prologue, GC_RAW, GC_VALUES};
+use super::utils::{ValTy, BOX_PAYLOAD_MASK};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Diccionarios `Map<K, V>`
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Un `%__Map` guarda los pares en dos arrays paralelos de `double` empaquetados,
+// en orden de inserción, y un índice de dispersión con direccionamiento abierto
+// (sondeo lineal) cuyos slots valen `entrada + 1`, o 0 si están libres:
+//
+//   { i64 type_id, double* keys, double* vals, i64* index,
+//     i64 count, i64 cap, i64 mask, i64 cursor, i64 strings }
+//
+// El índice tiene al menos el doble de slots que pares, así que siempre queda
+// uno libre y no hacen falta lápidas: `remove` corre los pares siguientes y
+// reconstruye el índice. Con `strings` las claves son cadenas y se comparan y
+// dispersan por contenido (`strcmp`, `@__hulk_str_hash`); si no, por los bits
+// del `double` (con `-0.0` normalizado a `0.0`).
+//
+// `next()` / `get_current()` recorren las claves, como el iterador del vector.
+// Los helpers no lanzan: `get` de una clave que no está lanza `KeyError` en el
+// sitio de la llamada, que conoce la posición.
+
+/// Capacidad inicial de los arrays de pares.
+const INITIAL_CAP: u64 = 8;
+/// Bytes de un `%__Map`: nueve palabras.
+const MAP_SIZE: u64 = 72;
+/// Slots del `%__Map` que apuntan a bloques del heap: `keys`, `vals` e `index`.
+pub const MAP_POINTER_MASK: u64 = 0b1110;
+/// Multiplicador del finalizador de MurmurHash3 para dispersar los bits.
+const MIX: u64 = 0xff51afd7ed558ccd;
+
+/// Representación de los argumentos y del retorno de un método de `Map<K, V>`.
+pub fn map_method_sig(method: &str, key: &ValTy, value: &ValTy) -> Option<(Vec<ValTy>, ValTy)> {
+    let sig = match method {
+        "size" => (vec![], ValTy::Num),
+        "get" => (vec![key.clone()], value.clone()),
+        "set" => (vec![key.clone(), value.clone()], value.clone()),
+        "has" | "remove" => (vec![key.clone()], ValTy::Bool),
+        "keys" => (vec![], ValTy::Vector(Box::new(key.clone()))),
+        "values" => (vec![], ValTy::Vector(Box::new(value.clone()))),
+        "next" => (vec![], ValTy::Bool),
+        "current" | "get_current" => (vec![], key.clone()),
+        _ => return None,
+    };
+    Some(sig)
+}
+
+/// Diccionario vacío para claves de tipo `key`.
+pub fn gen_map_new(ctx: &mut Ctx, key: &ValTy) -> String {
+    let m = ctx.tmp();
+    ctx.emit(&format!("{} = call i8* @__Map_new(i1 {})", m, *key == ValTy::Str));
+    m
+}
+
+/// `m.set(k, v)` con la clave y el valor ya en su representación.
+pub fn gen_map_set(ctx: &mut Ctx, m: &str, k: &str, key: &ValTy, v: &str, value: &ValTy) {
+    let kb = ctx.box_value(k, key);
+    let vb = ctx.box_value(v, value);
+    ctx.emit(&format!("call void @__Map_set(i8* {}, double {}, double {})", m, kb, vb));
+}
+
+/// Llamada `m.method(args)` sobre un diccionario ya generado.
+pub fn gen_map_method(ctx: &mut Ctx, m: &str, key: &ValTy, value: &ValTy, method: &str, args: &[(String, ValTy)], pos: Position) -> (String, ValTy) {
+    let Some((params, ret)) = map_method_sig(method, key, value) else {
+        ctx.emit(&format!("; WARNING: Map has no method '{}'", method));
+        return ("0.0".into(), ValTy::Num);
+    };
+    ctx.root_ptr(m, "i8*");
+    let mut boxed = Vec::new();
+    for ((v, ty), param) in args.iter().zip(&params) {
+        let v = ctx.coerce(v, ty, param);
+        ctx.root(&v, param);
+        boxed.push((v.clone(), ctx.box_value(&v, param)));
+    }
+
+    let r = ctx.tmp();
+    match method {
+        "size" => ctx.emit(&format!("{} = call double @__Map_size(i8* {})", r, m)),
+        "get" => {
+            let e = ctx.tmp();
+            ctx.emit(&format!("{} = call i64 @__Map_find(i8* {}, double {})", e, m, boxed[0].1));
+            let missing = ctx.tmp();
+            ctx.emit(&format!("{} = icmp slt i64 {}, 0", missing, e));
+            let ok = ctx.lbl("map_ok");
+            let fail = ctx.lbl("map_missing");
+            ctx.emit(&format!("br i1 {}, label %{}, label %{}", missing, fail, ok));
+            ctx.emit_label(&fail);
+            runtime_throw(ctx, "KeyError", KEY_MESSAGE, pos);
+            ctx.emit_label(&ok);
+            let raw = ctx.tmp();
+            ctx.emit(&format!("{} = call double @__Map_value_at(i8* {}, i64 {})", raw, m, e));
+            return (ctx.unbox(&raw, value), ret);
+        }
+        "set" => {
+            ctx.emit(&format!("call void @__Map_set(i8* {}, double {}, double {})", m, boxed[0].1, boxed[1].1));
+            return (boxed[1].0.clone(), ret);
+        }
+        "has" => {
+            let e = ctx.tmp();
+            ctx.emit(&format!("{} = call i64 @__Map_find(i8* {}, double {})", e, m, boxed[0].1));
+            ctx.emit(&format!("{} = icmp sge i64 {}, 0", r, e));
+        }
+        "remove" => ctx.emit(&format!("{} = call i1 @__Map_remove(i8* {}, double {})", r, m, boxed[0].1)),
+        "keys" | "values" => {
+            let values = method == "values";
+            ctx.emit(&format!("{} = call i8* @__Map_to_vector(i8* {}, i1 {})", r, m, values));
+        }
+        "next" => ctx.emit(&format!("{} = call i1 @__Map_next(i8* {})", r, m)),
+        _ => {
+            ctx.emit(&format!("{} = call double @__Map_get_current(i8* {})", r, m));
+            return (ctx.unbox(&r, key), ret);
+        }
+    }
+    (r, ret)
+}
+
+/// Emite el tipo implícito `%__Map` y sus helpers, y lo registra como la clase
+/// `__Map` para que `for` recorra sus claves. Devuelve su type-id.
+pub fn emit_map_type(ctx: &mut Ctx) -> u32 {
+    let type_id = ctx.alloc_type_id();
+    let mix = MIX as i64;
+    let payload = BOX_PAYLOAD_MASK;
+    ctx.preamble.push_str("%__Map = type { i64, double*, double*, i64*, i64, i64, i64, i64, i64 }\n");
+    ctx.preamble.push_str("\
+@.map_open = private unnamed_addr constant [2 x i8] c\"{\\00\"
+@.map_close = private unnamed_addr constant [2 x i8] c\"}\\00\"
+@.map_arrow = private unnamed_addr constant [5 x i8] c\" -> \\00\"
+");
+    let slots = INITIAL_CAP * 2;
+
+    // ── @__Map_new(i1 strings) -> i8*  ─  diccionario vacío; cada array se
+    //    guarda en el struct, que es raíz, nada más asignarlo
+    ctx.functions.push_str(&format!("\
+define i8* @__Map_new(i1 %strings) {{
+entry:
+{prologue}  %obj = call i8* @__hulk_gc_alloc(i64 {MAP_SIZE}, i32 {type_id})
+  %obj_root = bitcast double* %gc.s0 to i8**
+  store i8* %obj, i8** %obj_root
+  %m = bitcast i8* %obj to %__Map*
+  %tid_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 0
+  store i64 {type_id}, i64* %tid_p
+  %keys_raw = call i8* @__hulk_gc_alloc(i64 {bytes}, i32 {GC_VALUES})
+  %keys = bitcast i8* %keys_raw to double*
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  store double* %keys, double** %keys_p
+  %vals_raw = call i8* @__hulk_gc_alloc(i64 {bytes}, i32 {GC_VALUES})
+  %vals = bitcast i8* %vals_raw to double*
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  store double* %vals, double** %vals_p
+  %index_raw = call i8* @__hulk_gc_alloc(i64 {index_bytes}, i32 {GC_RAW})
+  %index = bitcast i8* %index_raw to i64*
+  %index_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 3
+  store i64* %index, i64** %index_p
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  store i64 0, i64* %count_p
+  %cap_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 5
+  store i64 {INITIAL_CAP}, i64* %cap_p
+  %mask_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 6
+  store i64 {mask}, i64* %mask_p
+  %cursor_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 7
+  store i64 -1, i64* %cursor_p
+  %strings_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 8
+  %strings_i = zext i1 %strings to i64
+  store i64 %strings_i, i64* %strings_p
+  store i8* %gc.prev, i8** @__gc_frames
+  ret i8* %obj
+}}
+
+", prologue = frame_prologue(1), bytes = INITIAL_CAP * 8, index_bytes = slots * 8, mask = slots - 1));
+
+    // ── @__Map_hash(%__Map*, double) -> i64
+    ctx.functions.push_str(&format!("\
+define i64 @__Map_hash(%__Map* %m, double %k) {{
+entry:
+  %strings_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 8
+  %strings = load i64, i64* %strings_p
+  %is_str = icmp ne i64 %strings, 0
+  br i1 %is_str, label %str, label %bits
+str:
+  %k_bits = bitcast double %k to i64
+  %k_i = and i64 %k_bits, {payload}
+  %s = inttoptr i64 %k_i to i8*
+  %h32 = call i32 @__hulk_str_hash(i8* %s)
+  %h = zext i32 %h32 to i64
+  ret i64 %h
+bits:
+  %norm = fadd double %k, 0.0
+  %x0 = bitcast double %norm to i64
+  %x0_hi = lshr i64 %x0, 33
+  %x1 = xor i64 %x0, %x0_hi
+  %x2 = mul i64 %x1, {mix}
+  %x2_hi = lshr i64 %x2, 33
+  %x3 = xor i64 %x2, %x2_hi
+  ret i64 %x3
+}}
+
+"));
+
+    // ── @__Map_eq(%__Map*, double, double) -> i1
+    ctx.functions.push_str(&format!("\
+define i1 @__Map_eq(%__Map* %m, double %a, double %b) {{
+entry:
+  %strings_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 8
+  %strings = load i64, i64* %strings_p
+  %is_str = icmp ne i64 %strings, 0
+  br i1 %is_str, label %str, label %bits
+str:
+  %a_word = bitcast double %a to i64
+  %a_i = and i64 %a_word, {payload}
+  %a_s = inttoptr i64 %a_i to i8*
+  %b_word = bitcast double %b to i64
+  %b_i = and i64 %b_word, {payload}
+  %b_s = inttoptr i64 %b_i to i8*
+  %cmp = call i32 @strcmp(i8* %a_s, i8* %b_s)
+  %same_str = icmp eq i32 %cmp, 0
+  ret i1 %same_str
+bits:
+  %a_n = fadd double %a, 0.0
+  %b_n = fadd double %b, 0.0
+  %a_bits = bitcast double %a_n to i64
+  %b_bits = bitcast double %b_n to i64
+  %same = icmp eq i64 %a_bits, %b_bits
+  ret i1 %same
+}}
+
+"));
+
+    // ── @__Map_find(i8*, double) -> i64  ─  posición del par o -1
+    ctx.functions.push_str("\
+define i64 @__Map_find(i8* %self, double %k) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %h = call i64 @__Map_hash(%__Map* %m, double %k)
+  %mask_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 6
+  %mask = load i64, i64* %mask_p
+  %index_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 3
+  %index = load i64*, i64** %index_p
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %keys = load double*, double** %keys_p
+  br label %probe
+probe:
+  %at = phi i64 [%h, %entry], [%at_next, %next]
+  %pos = and i64 %at, %mask
+  %slot_p = getelementptr i64, i64* %index, i64 %pos
+  %slot = load i64, i64* %slot_p
+  %free = icmp eq i64 %slot, 0
+  br i1 %free, label %missing, label %check
+check:
+  %e = sub i64 %slot, 1
+  %key_p = getelementptr double, double* %keys, i64 %e
+  %key = load double, double* %key_p
+  %same = call i1 @__Map_eq(%__Map* %m, double %key, double %k)
+  br i1 %same, label %found, label %next
+next:
+  %at_next = add i64 %pos, 1
+  br label %probe
+found:
+  ret i64 %e
+missing:
+  ret i64 -1
+}
+
+");
+
+    // ── @__Map_index_entry(%__Map*, i64)  ─  apunta el primer slot libre a la entrada
+    ctx.functions.push_str("\
+define void @__Map_index_entry(%__Map* %m, i64 %e) {
+entry:
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %keys = load double*, double** %keys_p
+  %key_p = getelementptr double, double* %keys, i64 %e
+  %key = load double, double* %key_p
+  %h = call i64 @__Map_hash(%__Map* %m, double %key)
+  %mask_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 6
+  %mask = load i64, i64* %mask_p
+  %index_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 3
+  %index = load i64*, i64** %index_p
+  br label %probe
+probe:
+  %at = phi i64 [%h, %entry], [%at_next, %next]
+  %pos = and i64 %at, %mask
+  %slot_p = getelementptr i64, i64* %index, i64 %pos
+  %slot = load i64, i64* %slot_p
+  %free = icmp eq i64 %slot, 0
+  br i1 %free, label %store, label %next
+next:
+  %at_next = add i64 %pos, 1
+  br label %probe
+store:
+  %e1 = add i64 %e, 1
+  store i64 %e1, i64* %slot_p
+  ret void
+}
+
+");
+
+    // ── @__Map_reindex(%__Map*)  ─  vacía el índice y vuelve a indexar los pares
+    ctx.functions.push_str("\
+define void @__Map_reindex(%__Map* %m) {
+entry:
+  %mask_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 6
+  %mask = load i64, i64* %mask_p
+  %slots = add i64 %mask, 1
+  %bytes = mul i64 %slots, 8
+  %index_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 3
+  %index = load i64*, i64** %index_p
+  %index_raw = bitcast i64* %index to i8*
+  call i8* @memset(i8* %index_raw, i32 0, i64 %bytes)
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  br label %loop
+loop:
+  %e = phi i64 [0, %entry], [%e_next, %body]
+  %more = icmp slt i64 %e, %count
+  br i1 %more, label %body, label %done
+body:
+  call void @__Map_index_entry(%__Map* %m, i64 %e)
+  %e_next = add i64 %e, 1
+  br label %loop
+done:
+  ret void
+}
+
+");
+
+    // ── @__Map_reserve(%__Map*)  ─  sitio para un par más: dobla los arrays si
+    //    están llenos y el índice si quedaría a más de la mitad. El diccionario
+    //    es raíz de quien llama y cada array nuevo se guarda en él al asignarlo.
+    ctx.functions.push_str(&format!("\
+define void @__Map_reserve(%__Map* %m) {{
+entry:
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %cap_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 5
+  %cap = load i64, i64* %cap_p
+  %full = icmp eq i64 %count, %cap
+  br i1 %full, label %grow, label %check_index
+grow:
+  %new_cap = shl i64 %cap, 1
+  %new_bytes = mul i64 %new_cap, 8
+  %old_bytes = mul i64 %count, 8
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %new_keys_raw = call i8* @__hulk_gc_alloc(i64 %new_bytes, i32 {GC_VALUES})
+  %old_keys = load double*, double** %keys_p
+  %old_keys_raw = bitcast double* %old_keys to i8*
+  call i8* @memcpy(i8* %new_keys_raw, i8* %old_keys_raw, i64 %old_bytes)
+  %new_keys = bitcast i8* %new_keys_raw to double*
+  store double* %new_keys, double** %keys_p
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %new_vals_raw = call i8* @__hulk_gc_alloc(i64 %new_bytes, i32 {GC_VALUES})
+  %old_vals = load double*, double** %vals_p
+  %old_vals_raw = bitcast double* %old_vals to i8*
+  call i8* @memcpy(i8* %new_vals_raw, i8* %old_vals_raw, i64 %old_bytes)
+  %new_vals = bitcast i8* %new_vals_raw to double*
+  store double* %new_vals, double** %vals_p
+  store i64 %new_cap, i64* %cap_p
+  br label %check_index
+check_index:
+  %mask_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 6
+  %mask = load i64, i64* %mask_p
+  %slots = add i64 %mask, 1
+  %after = add i64 %count, 1
+  %needed = shl i64 %after, 1
+  %crowded = icmp sgt i64 %needed, %slots
+  br i1 %crowded, label %rehash, label %done
+rehash:
+  %new_slots = shl i64 %slots, 1
+  %index_bytes = mul i64 %new_slots, 8
+  %index_raw = call i8* @__hulk_gc_alloc(i64 %index_bytes, i32 {GC_RAW})
+  %index = bitcast i8* %index_raw to i64*
+  %index_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 3
+  store i64* %index, i64** %index_p
+  %new_mask = sub i64 %new_slots, 1
+  store i64 %new_mask, i64* %mask_p
+  call void @__Map_reindex(%__Map* %m)
+  br label %done
+done:
+  ret void
+}}
+
+"));
+
+    // ── @__Map_set(i8*, double k, double v)  ─  actualiza o agrega al final
+    ctx.functions.push_str("\
+define void @__Map_set(i8* %self, double %k, double %v) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %e = call i64 @__Map_find(i8* %self, double %k)
+  %found = icmp sge i64 %e, 0
+  br i1 %found, label %update, label %insert
+update:
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %vals = load double*, double** %vals_p
+  %val_p = getelementptr double, double* %vals, i64 %e
+  store double %v, double* %val_p
+  ret void
+insert:
+  call void @__Map_reserve(%__Map* %m)
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %keys = load double*, double** %keys_p
+  %new_key_p = getelementptr double, double* %keys, i64 %count
+  store double %k, double* %new_key_p
+  %vals_p2 = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %vals2 = load double*, double** %vals_p2
+  %new_val_p = getelementptr double, double* %vals2, i64 %count
+  store double %v, double* %new_val_p
+  %count_next = add i64 %count, 1
+  store i64 %count_next, i64* %count_p
+  call void @__Map_index_entry(%__Map* %m, i64 %count)
+  ret void
+}
+
+");
+
+    // ── @__Map_remove(i8*, double) -> i1  ─  corre los pares siguientes un lugar
+    ctx.functions.push_str("\
+define i1 @__Map_remove(i8* %self, double %k) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %e = call i64 @__Map_find(i8* %self, double %k)
+  %found = icmp sge i64 %e, 0
+  br i1 %found, label %shift, label %missing
+missing:
+  ret i1 false
+shift:
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %keys = load double*, double** %keys_p
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %vals = load double*, double** %vals_p
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %last = sub i64 %count, 1
+  br label %loop
+loop:
+  %i = phi i64 [%e, %shift], [%i_next, %body]
+  %more = icmp slt i64 %i, %last
+  br i1 %more, label %body, label %done
+body:
+  %i_next = add i64 %i, 1
+  %key_src = getelementptr double, double* %keys, i64 %i_next
+  %key = load double, double* %key_src
+  %key_dst = getelementptr double, double* %keys, i64 %i
+  store double %key, double* %key_dst
+  %val_src = getelementptr double, double* %vals, i64 %i_next
+  %val = load double, double* %val_src
+  %val_dst = getelementptr double, double* %vals, i64 %i
+  store double %val, double* %val_dst
+  br label %loop
+done:
+  %key_last = getelementptr double, double* %keys, i64 %last
+  store double 0.0, double* %key_last
+  %val_last = getelementptr double, double* %vals, i64 %last
+  store double 0.0, double* %val_last
+  store i64 %last, i64* %count_p
+  call void @__Map_reindex(%__Map* %m)
+  ret i1 true
+}
+
+");
+
+    // ── @__Map_value_at(i8*, i64) -> double  /  @__Map_size(i8*) -> double
+    ctx.functions.push_str("\
+define double @__Map_value_at(i8* %self, i64 %e) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %vals = load double*, double** %vals_p
+  %val_p = getelementptr double, double* %vals, i64 %e
+  %val = load double, double* %val_p
+  ret double %val
+}
+
+define double @__Map_size(i8* %self) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %size = sitofp i64 %count to double
+  ret double %size
+}
+
+");
+
+    // ── @__Map_next(i8*) -> i1  /  @__Map_get_current(i8*) -> double
+    //    Al terminar, el cursor vuelve al principio para el siguiente recorrido.
+    ctx.functions.push_str("\
+define i1 @__Map_next(i8* %self) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %cursor_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 7
+  %cursor = load i64, i64* %cursor_p
+  %cursor_next = add i64 %cursor, 1
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %more = icmp slt i64 %cursor_next, %count
+  %stored = select i1 %more, i64 %cursor_next, i64 -1
+  store i64 %stored, i64* %cursor_p
+  ret i1 %more
+}
+
+define double @__Map_get_current(i8* %self) {
+entry:
+  %m = bitcast i8* %self to %__Map*
+  %cursor_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 7
+  %cursor = load i64, i64* %cursor_p
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %keys = load double*, double** %keys_p
+  %key_p = getelementptr double, double* %keys, i64 %cursor
+  %key = load double, double* %key_p
+  ret double %key
+}
+
+");
+
+    // ── @__Map_to_vector(i8*, i1 values) -> i8*  ─  copia de las claves o los valores
+    ctx.functions.push_str(&format!("\
+define i8* @__Map_to_vector(i8* %self, i1 %values) {{
+entry:
+{prologue}  %m = bitcast i8* %self to %__Map*
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %count = load i64, i64* %count_p
+  %bytes = mul i64 %count, 8
+  %data_raw = call i8* @__hulk_gc_alloc(i64 %bytes, i32 {GC_VALUES})
+  %data_root = bitcast double* %gc.s0 to i8**
+  store i8* %data_raw, i8** %data_root
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  %src_p = select i1 %values, double** %vals_p, double** %keys_p
+  %src = load double*, double** %src_p
+  %src_raw = bitcast double* %src to i8*
+  call i8* @memcpy(i8* %data_raw, i8* %src_raw, i64 %bytes)
+  %data = bitcast i8* %data_raw to double*
+  %len = sitofp i64 %count to double
+  %vec = call i8* @__Vector_new(double* %data, double %len)
+  store i8* %gc.prev, i8** @__gc_frames
+  ret i8* %vec
+}}
+
+", prologue = frame_prologue(1)));
+
+    // ── @__Map_to_str(i8*, i1 print) -> i8*  ─  `{k -> v, ...}`, con cada clave y
+    //    valor convertidos según su etiqueta
+    ctx.functions.push_str("\
+define i8* @__Map_to_str(i8* %self, i1 %print) {
+entry:
+  %buf = alloca %__Buf
+  store %__Buf zeroinitializer, %__Buf* %buf
+  %m = bitcast i8* %self to %__Map*
+  %count_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 4
+  %keys_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 1
+  %vals_p = getelementptr inbounds %__Map, %__Map* %m, i32 0, i32 2
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.map_open, i64 0, i64 0))
+  br label %loop
+loop:
+  %i = phi i64 [0, %entry], [%i_next, %item]
+  %count = load i64, i64* %count_p
+  %more = icmp slt i64 %i, %count
+  br i1 %more, label %sep, label %done
+sep:
+  %first = icmp eq i64 %i, 0
+  br i1 %first, label %item, label %print_sep
+print_sep:
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.vec_sep, i64 0, i64 0))
+  br label %item
+item:
+  ; Los arrays se releen en cada vuelta: convertir un elemento puede asignar
+  %keys = load double*, double** %keys_p
+  %key_p = getelementptr double, double* %keys, i64 %i
+  %key = load double, double* %key_p
+  %key_s = call i8* @__hulk_value_to_str(double %key, i1 %print)
+  call void @__hulk_buf_append(%__Buf* %buf, i8* %key_s)
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.map_arrow, i64 0, i64 0))
+  %vals = load double*, double** %vals_p
+  %val_p = getelementptr double, double* %vals, i64 %i
+  %val = load double, double* %val_p
+  %val_s = call i8* @__hulk_value_to_str(double %val, i1 %print)
+  call void @__hulk_buf_append(%__Buf* %buf, i8* %val_s)
+  %i_next = add i64 %i, 1
+  br label %loop
+done:
+  call void @__hulk_buf_append(%__Buf* %buf, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @.map_close, i64 0, i64 0))
+  %str = call i8* @__hulk_buf_finish(%__Buf* %buf)
+  ret i8* %str
+}
+
+");
+
+    // Registrar en ctx.classes: `for` y el protocolo `Iterable` llaman a
+    // `next` / `current` como en cualquier iterable; el resto de los métodos
+    // se generan en el sitio
+    let mut method_names: HashMap<String, String> = ["size", "next", "get_current"].iter()
+        .map(|m| (m.to_string(), format!("@__Map_{}", m)))
+        .collect();
+    method_names.insert("current".to_string(), "@__Map_get_current".to_string());
+    let method_sigs = HashMap::from([
+        ("size".to_string(), (Vec::new(), ValTy::Num)),
+        ("next".to_string(), (Vec::new(), ValTy::Bool)),
+        ("current".to_string(), (Vec::new(), ValTy::object())),
+        ("get_current".to_string(), (Vec::new(), ValTy::object())),
+    ]);
+    ctx.classes.insert("__Map".to_string(), ClassLayout {
+        struct_name: "%__Map".to_string(),
+        type_id,
+        attr_indices: HashMap::new(),
+        attr_order: Vec::new(),
+        method_names,
+        parent_name: None,
+        total_fields: 9,
+        attr_tys: HashMap::new(),
+        method_sigs,
+        ctor_params: Vec::new(),
+        vtable: Vec::new(), // final: sus métodos se llaman directamente
+    });
+    type_id
+}
@@ -5,6 +5,10 @@ mod classes;
 mod functions;
 mod expressions;
 mod builtins;
+mod gc;
+mod exceptions;
+mod strings;
+mod maps;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,497 @@
+use crate::errors::runtime::{cast_message, INDEX_MESSAGE};
+use crate::utils::Position;
+use super::context::Ctx;
+use super::exceptions::runtime_throw;
+use super::gc::{frame_prologue, GC_RAW, GC_VALUES};
+use super::utils::{box_tag, ValTy, BOX_STR};
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Métodos de `String`: `size()`, `substring(i, n)`, `split(sep)`...
+// ─────────────────────────────────────────────────────────────────────────────
+//
+// Cada método llama a un helper `@__hulk_str_*` que recibe las cadenas como
+// `i8*`. Los índices y `size()` cuentan caracteres (code points de UTF-8),
+// igual que el intérprete: `@__hulk_str_count` y `@__hulk_str_offset` pasan de
+// bytes a caracteres y al revés. `upper()` y `lower()` solo cambian letras
+// ASCII, y `trim()` solo quita espacios ASCII. Los helpers que asignan solo lo
+// hacen una vez, salvo `split`, que guarda en su propio frame el array de las partes.
+// El receptor y los argumentos los deja como raíces quien llama.
+//
+// Los fallos se lanzan en el sitio de la llamada, que conoce la posición: los
+// helpers de `substring` y `at` devuelven `null` si el rango no cabe, y
+// `parse_number` pregunta antes a `@__hulk_str_is_number`.
+//
+// Las comparaciones `==`, `!=`, `<`... entre dos `String` usan `strcmp`, y
+// `hash()` es FNV-1a de 32 bits sobre los bytes (`@__hulk_str_hash`).
+
+/// Representación de los argumentos y del retorno de un método de `String`.
+pub fn string_method_sig(method: &str) -> Option<(Vec<ValTy>, ValTy)> {
+    let sig = match method {
+        "size" | "parse_number" | "hash" => (vec![], ValTy::Num),
+        "trim" | "upper" | "lower" => (vec![], ValTy::Str),
+        "substring" => (vec![ValTy::Num, ValTy::Num], ValTy::Str),
+        "at" => (vec![ValTy::Num], ValTy::Str),
+        "index_of" => (vec![ValTy::Str], ValTy::Num),
+        "starts_with" => (vec![ValTy::Str], ValTy::Bool),
+        "split" => (vec![ValTy::Str], ValTy::Vector(Box::new(ValTy::Str))),
+        "replace" => (vec![ValTy::Str, ValTy::Str], ValTy::Str),
+        _ => return None,
+    };
+    Some(sig)
+}
+
+/// Llamada `s.method(args)` sobre una cadena ya generada.
+pub fn gen_string_method(ctx: &mut Ctx, s: &str, method: &str, args: &[(String, ValTy)], pos: Position) -> (String, ValTy) {
+    let Some((params, ret)) = string_method_sig(method) else {
+        ctx.emit(&format!("; WARNING: String has no method '{}'", method));
+        return ("0.0".into(), ValTy::Num);
+    };
+    ctx.root(s, &ValTy::Str);
+    let mut vals = Vec::new();
+    for ((v, ty), param) in args.iter().zip(&params) {
+        let v = ctx.coerce(v, ty, param);
+        ctx.root(&v, param);
+        vals.push(format!("{} {}", param.llvm(), v));
+    }
+    let arg_s = std::iter::once(format!("i8* {}", s)).chain(vals).collect::<Vec<_>>().join(", ");
+
+    let r = ctx.tmp();
+    match method {
+        "size" => {
+            let bytes = ctx.tmp();
+            ctx.emit(&format!("{} = call i64 @strlen(i8* {})", bytes, s));
+            let len = ctx.tmp();
+            ctx.emit(&format!("{} = call i64 @__hulk_str_count(i8* {}, i64 {})", len, s, bytes));
+            ctx.emit(&format!("{} = uitofp i64 {} to double", r, len));
+        }
+        "at" | "substring" => {
+            let arg_s = if method == "at" { format!("{}, double 1.0", arg_s) } else { arg_s };
+            ctx.emit(&format!("{} = call i8* @__hulk_str_substring({})", r, arg_s));
+            let oob = ctx.tmp();
+            ctx.emit(&format!("{} = icmp eq i8* {}, null", oob, r));
+            let ok = ctx.lbl("str_ok");
+            let fail = ctx.lbl("str_oob");
+            ctx.emit(&format!("br i1 {}, label %{}, label %{}", oob, fail, ok));
+            ctx.emit_label(&fail);
+            runtime_throw(ctx, "IndexError", INDEX_MESSAGE, pos);
+            ctx.emit_label(&ok);
+        }
+        "parse_number" => {
+            let valid = ctx.tmp();
+            ctx.emit(&format!("{} = call i1 @__hulk_str_is_number(i8* {})", valid, s));
+            let ok = ctx.lbl("num_ok");
+            let fail = ctx.lbl("num_bad");
+            ctx.emit(&format!("br i1 {}, label %{}, label %{}", valid, ok, fail));
+            ctx.emit_label(&fail);
+            runtime_throw(ctx, "CastError", &cast_message("Number"), pos);
+            ctx.emit_label(&ok);
+            ctx.emit(&format!("{} = call double @strtod(i8* {}, i8** null)", r, s));
+        }
+        "hash" => {
+            let h = ctx.tmp();
+            ctx.emit(&format!("{} = call i32 @__hulk_str_hash(i8* {})", h, s));
+            ctx.emit(&format!("{} = uitofp i32 {} to double", r, h));
+        }
+        "upper" | "lower" => {
+            let upper = if method == "upper" { "true" } else { "false" };
+            ctx.emit(&format!("{} = call i8* @__hulk_str_case({}, i1 {})", r, arg_s, upper));
+        }
+        _ => ctx.emit(&format!("{} = call {} @__hulk_str_{}({})", r, ret.llvm(), method, arg_s)),
+    }
+    (r, ret)
+}
+
+/// `l <pred> r` por contenido: `strcmp` y su resultado contra 0 con el
+/// predicado de `icmp` (`eq`, `ne`, `slt`, `sle`...).
+pub fn compare_strings(ctx: &mut Ctx, l: &str, r: &str, pred: &str) -> String {
+    let cmp = ctx.tmp();
+    ctx.emit(&format!("{} = call i32 @strcmp(i8* {}, i8* {})", cmp, l, r));
+    let res = ctx.tmp();
+    ctx.emit(&format!("{} = icmp {} i32 {}, 0", res, pred, cmp));
+    res
+}
+
+/// Emite los helpers de los métodos de `String`.
+pub fn emit_string_helpers(ctx: &mut Ctx) {
+    // ── @__hulk_str_count(i8*, i64 n) -> i64  ─  caracteres en los primeros `n` bytes
+    //    (los que no son de continuación, 10xxxxxx)
+    ctx.functions.push_str("\
+define i64 @__hulk_str_count(i8* %s, i64 %n) {
+entry:
+  br label %cond
+cond:
+  %i = phi i64 [0, %entry], [%i_next, %body]
+  %k = phi i64 [0, %entry], [%k_next, %body]
+  %more = icmp slt i64 %i, %n
+  br i1 %more, label %body, label %done
+body:
+  %p = getelementptr i8, i8* %s, i64 %i
+  %c = load i8, i8* %p
+  %top = and i8 %c, -64
+  %cont = icmp eq i8 %top, -128
+  %inc = select i1 %cont, i64 0, i64 1
+  %k_next = add i64 %k, %inc
+  %i_next = add i64 %i, 1
+  br label %cond
+done:
+  ret i64 %k
+}
+
+");
+
+    // ── @__hulk_str_offset(i8*, i64 len, i64 n) -> i64  ─  byte donde empieza el
+    //    carácter `n` de los primeros `len` bytes (`len` si `n` es el final); -1 si no hay tantos
+    ctx.functions.push_str("\
+define i64 @__hulk_str_offset(i8* %s, i64 %len, i64 %n) {
+entry:
+  br label %cond
+cond:
+  %i = phi i64 [0, %entry], [%i_next, %next]
+  %k = phi i64 [0, %entry], [%k_next, %next]
+  %at_end = icmp sge i64 %i, %len
+  br i1 %at_end, label %end, label %body
+end:
+  %all = icmp eq i64 %k, %n
+  %res = select i1 %all, i64 %len, i64 -1
+  ret i64 %res
+body:
+  %p = getelementptr i8, i8* %s, i64 %i
+  %c = load i8, i8* %p
+  %top = and i8 %c, -64
+  %cont = icmp eq i8 %top, -128
+  %reached = icmp eq i64 %k, %n
+  %not_cont = xor i1 %cont, true
+  %hit = and i1 %not_cont, %reached
+  br i1 %hit, label %found, label %next
+found:
+  ret i64 %i
+next:
+  %inc = select i1 %cont, i64 0, i64 1
+  %k_next = add i64 %k, %inc
+  %i_next = add i64 %i, 1
+  br label %cond
+}
+
+");
+
+    // ── @__hulk_str_substring(i8*, double i, double n) -> i8*  ─  los `n` caracteres
+    //    desde el `i`; `null` si no caben
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_str_substring(i8* %s, double %i, double %n) {{
+entry:
+  %len = call i64 @strlen(i8* %s)
+  %start = fptosi double %i to i64
+  %count = fptosi double %n to i64
+  %neg_start = icmp slt i64 %start, 0
+  %neg_count = icmp slt i64 %count, 0
+  %bad_arg = or i1 %neg_start, %neg_count
+  br i1 %bad_arg, label %fail, label %find_start
+find_start:
+  %from = call i64 @__hulk_str_offset(i8* %s, i64 %len, i64 %start)
+  %no_start = icmp slt i64 %from, 0
+  br i1 %no_start, label %fail, label %find_end
+find_end:
+  %src = getelementptr i8, i8* %s, i64 %from
+  %rest = sub i64 %len, %from
+  %bytes = call i64 @__hulk_str_offset(i8* %src, i64 %rest, i64 %count)
+  %no_end = icmp slt i64 %bytes, 0
+  br i1 %no_end, label %fail, label %copy
+fail:
+  ret i8* null
+copy:
+  %size = add i64 %bytes, 1
+  %buf = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  call i8* @memcpy(i8* %buf, i8* %src, i64 %bytes)
+  ret i8* %buf
+}}
+
+"));
+
+    // ── @__hulk_str_index_of(i8*, i8*) -> double  ─  en caracteres; -1 si no aparece
+    ctx.functions.push_str("\
+define double @__hulk_str_index_of(i8* %s, i8* %t) {
+entry:
+  %hit = call i8* @strstr(i8* %s, i8* %t)
+  %none = icmp eq i8* %hit, null
+  br i1 %none, label %missing, label %found
+missing:
+  ret double -1.0
+found:
+  %hit_i = ptrtoint i8* %hit to i64
+  %s_i = ptrtoint i8* %s to i64
+  %offset = sub i64 %hit_i, %s_i
+  %chars = call i64 @__hulk_str_count(i8* %s, i64 %offset)
+  %res = sitofp i64 %chars to double
+  ret double %res
+}
+
+");
+
+    // ── @__hulk_str_starts_with(i8*, i8*) -> i1
+    ctx.functions.push_str("\
+define i1 @__hulk_str_starts_with(i8* %s, i8* %prefix) {
+entry:
+  %n = call i64 @strlen(i8* %prefix)
+  %cmp = call i32 @strncmp(i8* %s, i8* %prefix, i64 %n)
+  %res = icmp eq i32 %cmp, 0
+  ret i1 %res
+}
+
+");
+
+    // ── @__hulk_str_case(i8*, i1 upper) -> i8*  ─  `upper()` / `lower()`; solo letras ASCII
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_str_case(i8* %s, i1 %upper) {{
+entry:
+  %len = call i64 @strlen(i8* %s)
+  %size = add i64 %len, 1
+  %buf = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  %lo = select i1 %upper, i8 97, i8 65
+  %hi = select i1 %upper, i8 122, i8 90
+  %delta = select i1 %upper, i8 -32, i8 32
+  br label %cond
+cond:
+  %i = phi i64 [0, %entry], [%next, %body]
+  %more = icmp slt i64 %i, %len
+  br i1 %more, label %body, label %done
+body:
+  %src = getelementptr i8, i8* %s, i64 %i
+  %c = load i8, i8* %src
+  %ge = icmp uge i8 %c, %lo
+  %le = icmp ule i8 %c, %hi
+  %in_range = and i1 %ge, %le
+  %shifted = add i8 %c, %delta
+  %out = select i1 %in_range, i8 %shifted, i8 %c
+  %dst = getelementptr i8, i8* %buf, i64 %i
+  store i8 %out, i8* %dst
+  %next = add i64 %i, 1
+  br label %cond
+done:
+  ret i8* %buf
+}}
+
+"));
+
+    // ── @__hulk_str_trim(i8*) -> i8*  ─  sin los espacios (`isspace`) de los extremos
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_str_trim(i8* %s) {{
+entry:
+  %len = call i64 @strlen(i8* %s)
+  br label %lead
+lead:
+  %start = phi i64 [0, %entry], [%start_next, %lead_check]
+  %has_lead = icmp slt i64 %start, %len
+  br i1 %has_lead, label %lead_check, label %trail
+lead_check:
+  %lp = getelementptr i8, i8* %s, i64 %start
+  %lc = load i8, i8* %lp
+  %lci = zext i8 %lc to i32
+  %lsp = call i32 @isspace(i32 %lci)
+  %lead_space = icmp ne i32 %lsp, 0
+  %start_next = add i64 %start, 1
+  br i1 %lead_space, label %lead, label %trail
+trail:
+  %end = phi i64 [%len, %lead], [%len, %lead_check], [%end_prev, %trail_check]
+  %has_trail = icmp sgt i64 %end, %start
+  br i1 %has_trail, label %trail_check, label %copy
+trail_check:
+  %end_prev = sub i64 %end, 1
+  %tp = getelementptr i8, i8* %s, i64 %end_prev
+  %tc = load i8, i8* %tp
+  %tci = zext i8 %tc to i32
+  %tsp = call i32 @isspace(i32 %tci)
+  %trail_space = icmp ne i32 %tsp, 0
+  br i1 %trail_space, label %trail, label %copy
+copy:
+  %count = sub i64 %end, %start
+  %size = add i64 %count, 1
+  %buf = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  %src = getelementptr i8, i8* %s, i64 %start
+  call i8* @memcpy(i8* %buf, i8* %src, i64 %count)
+  ret i8* %buf
+}}
+
+"));
+
+    // ── @__hulk_str_replace(i8*, i8* old, i8* new) -> i8*  ─  todas las apariciones.
+    //    Primero cuenta las apariciones para asignar una sola vez; con `old`
+    //    vacío devuelve la misma cadena.
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_str_replace(i8* %s, i8* %old, i8* %new) {{
+entry:
+  %olen = call i64 @strlen(i8* %old)
+  %empty = icmp eq i64 %olen, 0
+  br i1 %empty, label %same, label %count
+same:
+  ret i8* %s
+count:
+  %nlen = call i64 @strlen(i8* %new)
+  %slen = call i64 @strlen(i8* %s)
+  br label %count_loop
+count_loop:
+  %p = phi i8* [%s, %count], [%after, %count_hit]
+  %hits = phi i64 [0, %count], [%hits_next, %count_hit]
+  %hit = call i8* @strstr(i8* %p, i8* %old)
+  %no_hit = icmp eq i8* %hit, null
+  br i1 %no_hit, label %alloc, label %count_hit
+count_hit:
+  %hits_next = add i64 %hits, 1
+  %after = getelementptr i8, i8* %hit, i64 %olen
+  br label %count_loop
+alloc:
+  %diff = sub i64 %nlen, %olen
+  %grow = mul i64 %hits, %diff
+  %rlen = add i64 %slen, %grow
+  %size = add i64 %rlen, 1
+  %buf = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  br label %copy_loop
+copy_loop:
+  %src = phi i8* [%s, %alloc], [%src_next, %copy_hit]
+  %dst = phi i8* [%buf, %alloc], [%dst_next, %copy_hit]
+  %at = call i8* @strstr(i8* %src, i8* %old)
+  %last = icmp eq i8* %at, null
+  br i1 %last, label %tail, label %copy_hit
+copy_hit:
+  %at_i = ptrtoint i8* %at to i64
+  %src_i = ptrtoint i8* %src to i64
+  %prefix = sub i64 %at_i, %src_i
+  call i8* @memcpy(i8* %dst, i8* %src, i64 %prefix)
+  %dst_mid = getelementptr i8, i8* %dst, i64 %prefix
+  call i8* @memcpy(i8* %dst_mid, i8* %new, i64 %nlen)
+  %dst_next = getelementptr i8, i8* %dst_mid, i64 %nlen
+  %src_next = getelementptr i8, i8* %at, i64 %olen
+  br label %copy_loop
+tail:
+  call i8* @strcpy(i8* %dst, i8* %src)
+  ret i8* %buf
+}}
+
+"));
+
+    // ── @__hulk_str_split(i8*, i8* sep) -> i8*  ─  `Vector<String>` con las partes.
+    //    Con `sep` vacío cada carácter es una parte. El array de las partes
+    //    queda como raíz mientras se asignan las cadenas y el vector.
+    ctx.functions.push_str(&format!("\
+define i8* @__hulk_str_split(i8* %s, i8* %sep) {{
+entry:
+{prologue}  %slen = call i64 @strlen(i8* %s)
+  %plen = call i64 @strlen(i8* %sep)
+  %chars = icmp eq i64 %plen, 0
+  %nchars = call i64 @__hulk_str_count(i8* %s, i64 %slen)
+  br i1 %chars, label %sized, label %count_loop
+count_loop:
+  %p = phi i8* [%s, %entry], [%after, %count_hit]
+  %pieces = phi i64 [1, %entry], [%pieces_next, %count_hit]
+  %hit = call i8* @strstr(i8* %p, i8* %sep)
+  %no_hit = icmp eq i8* %hit, null
+  br i1 %no_hit, label %sized, label %count_hit
+count_hit:
+  %pieces_next = add i64 %pieces, 1
+  %after = getelementptr i8, i8* %hit, i64 %plen
+  br label %count_loop
+sized:
+  %parts = phi i64 [%nchars, %entry], [%pieces, %count_loop]
+  %bytes = mul i64 %parts, 8
+  %data_raw = call i8* @__hulk_gc_alloc(i64 %bytes, i32 {GC_VALUES})
+  %data_root = bitcast double* %gc.s0 to i8**
+  store i8* %data_raw, i8** %data_root
+  %data = bitcast i8* %data_raw to double*
+  br label %piece
+piece:
+  %k = phi i64 [0, %sized], [%k_next, %cut]
+  %src = phi i8* [%s, %sized], [%src_next, %cut]
+  %more = icmp slt i64 %k, %parts
+  br i1 %more, label %find, label %done
+find:
+  br i1 %chars, label %char, label %search
+char:
+  %char_rest = call i64 @strlen(i8* %src)
+  %char_len = call i64 @__hulk_str_offset(i8* %src, i64 %char_rest, i64 1)
+  br label %cut
+search:
+  %at = call i8* @strstr(i8* %src, i8* %sep)
+  %at_none = icmp eq i8* %at, null
+  br i1 %at_none, label %rest, label %found
+rest:
+  %rest_len = call i64 @strlen(i8* %src)
+  br label %cut
+found:
+  %at_i = ptrtoint i8* %at to i64
+  %src_i = ptrtoint i8* %src to i64
+  %found_len = sub i64 %at_i, %src_i
+  br label %cut
+cut:
+  %piece_len = phi i64 [%char_len, %char], [%rest_len, %rest], [%found_len, %found]
+  %skip = phi i64 [0, %char], [0, %rest], [%plen, %found]
+  %size = add i64 %piece_len, 1
+  %str = call i8* @__hulk_gc_alloc(i64 %size, i32 {GC_RAW})
+  call i8* @memcpy(i8* %str, i8* %src, i64 %piece_len)
+  %str_i = ptrtoint i8* %str to i64
+  %str_tagged = or i64 %str_i, {str_tag}
+  %str_d = bitcast i64 %str_tagged to double
+  %slot = getelementptr double, double* %data, i64 %k
+  store double %str_d, double* %slot
+  %advance = add i64 %piece_len, %skip
+  %src_next = getelementptr i8, i8* %src, i64 %advance
+  %k_next = add i64 %k, 1
+  br label %piece
+done:
+  %len_d = sitofp i64 %parts to double
+  %vec = call i8* @__Vector_new(double* %data, double %len_d)
+  store i8* %gc.prev, i8** @__gc_frames
+  ret i8* %vec
+}}
+
+", prologue = frame_prologue(1), str_tag = box_tag(BOX_STR)));
+
+    // ── @__hulk_str_hash(i8*) -> i32  ─  FNV-1a de 32 bits
+    ctx.functions.push_str("\
+define i32 @__hulk_str_hash(i8* %s) {
+entry:
+  br label %cond
+cond:
+  %p = phi i8* [%s, %entry], [%p_next, %body]
+  %h = phi i32 [-2128831035, %entry], [%h_next, %body]
+  %c = load i8, i8* %p
+  %at_end = icmp eq i8 %c, 0
+  br i1 %at_end, label %done, label %body
+body:
+  %ci = zext i8 %c to i32
+  %mixed = xor i32 %h, %ci
+  %h_next = mul i32 %mixed, 16777619
+  %p_next = getelementptr i8, i8* %p, i64 1
+  br label %cond
+done:
+  ret i32 %h
+}
+
+");
+
+    // ── @__hulk_str_is_number(i8*) -> i1  ─  `strtod` consume todo salvo espacios
+    ctx.functions.push_str("\
+define i1 @__hulk_str_is_number(i8* %s) {
+entry:
+  %end = alloca i8*
+  call double @strtod(i8* %s, i8** %end)
+  %stop = load i8*, i8** %end
+  %nothing = icmp eq i8* %stop, %s
+  br i1 %nothing, label %no, label %trail
+trail:
+  %p = phi i8* [%stop, %entry], [%p_next, %check]
+  %c = load i8, i8* %p
+  %at_end = icmp eq i8 %c, 0
+  br i1 %at_end, label %yes, label %check
+check:
+  %ci = zext i8 %c to i32
+  %sp = call i32 @isspace(i32 %ci)
+  %space = icmp ne i32 %sp, 0
+  %p_next = getelementptr i8, i8* %p, i64 1
+  br i1 %space, label %trail, label %no
+yes:
+  ret i1 true
+no:
+  ret i1 false
+}
+
+");
+}
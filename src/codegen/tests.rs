@@ -2,6 +2,7 @@ use crate::parser::Parser;
 use crate::semantic::check_program;
 use super::llvm_target::LlvmGenerator;
 use super::CodeGenerator;
+use super::utils::{box_tag, BOX_BOOL};
 
 fn generate_code(input: &str) -> String {
     let mut parser = Parser::new(input);
@@ -151,8 +152,7 @@ fn test_generate_negation() {
 #[test]
 fn test_generate_not() {
     let code = generate_code("print(!true);");
-    assert!(code.contains("fcmp oeq double"));
-    assert!(code.contains("select"));
+    assert!(function_body(&code, "__hulk_main").contains("xor i1 true, true"));
 }
 
 // ── String concatenation tests ──────────────────────────────────────────
@@ -161,7 +161,7 @@ fn test_generate_not() {
 fn test_generate_string_concat() {
     let code = generate_code_no_sem("print(\"Hello\" @ \"World\");");
     assert!(code.contains("@strlen"));
-    assert!(code.contains("@malloc"));
+    assert!(code.contains("@__hulk_gc_alloc"));
     assert!(code.contains("@strcpy"));
     assert!(code.contains("@strcat"));
 }
@@ -230,4 +230,357 @@ fn test_generate_pi() {
 fn test_generate_e() {
     let code = generate_code("print(E);");
     assert!(code.contains("2.718281828459045e0"));
-}
\ No newline at end of file
+}
+
+// ── GC ──────────────────────────────────────────────────────────────────
+
+/// Cuerpo de la función `name` en el IR generado.
+fn function_body<'a>(code: &'a str, name: &str) -> &'a str {
+    let start = code.find(&format!(" @{}(", name)).expect("function not found");
+    let end = code[start..].find("\n}\n").unwrap();
+    &code[start..start + end]
+}
+
+#[test]
+fn test_gc_runtime_wraps_main() {
+    let code = generate_code("print(\"a\" @ 1);");
+    assert!(code.contains("define i8* @__hulk_gc_alloc(i64 %size, i32 %type_id)"));
+    assert!(code.contains("define void @__hulk_gc_collect()"));
+    let main = function_body(&code, "main");
+    let init = main.find("@__hulk_gc_init()").unwrap();
+    let run = main.find("@__hulk_main()").unwrap();
+    let shutdown = main.find("@__hulk_gc_shutdown()").unwrap();
+    assert!(init < run && run < shutdown);
+}
+
+#[test]
+fn test_gc_allocations_carry_type_ids() {
+    let code = generate_code("type P(x: Number) { x: Number = x; } print(new P(1));");
+    // __Vector tiene el type-id 1; P, el 2
+    assert!(function_body(&code, "P_new").contains("call i8* @__hulk_gc_alloc(i64 24, i32 2)"));
+    assert!(function_body(&code, "__Vector_new").contains("call i8* @__hulk_gc_alloc(i64 40, i32 1)"));
+    assert!(!code.contains("call i8* @malloc(i64 16)"));
+}
+
+#[test]
+fn test_gc_pointer_maps_skip_primitive_attributes() {
+    let code = generate_code(
+        "type P(x: Number, s: String) { x: Number = x; s: String = s; n: Object = 0; } print(1);",
+    );
+    // slot 1 = vtable, slot 2 = x (Number), slot 3 = s, slot 4 = n (Object, puede ser un string);
+    // el último type-id es el del diccionario: keys, vals e index
+    assert!(code.contains("@__gc_ptr_maps = private unnamed_addr constant [4 x i64] [i64 0, i64 2, i64 24, i64 14]"));
+}
+
+#[test]
+fn test_gc_frames_only_where_needed() {
+    let code = generate_code(
+        "function f(): Number => 2 * 3; function g(x: Number): String => \"v\" @ x; print(g(f()));",
+    );
+    assert!(!function_body(&code, "f").contains("%gc.frame"));
+    let g = function_body(&code, "g");
+    assert!(g.contains("%gc.frame = alloca { i8*, i64, ["));
+    assert!(g.contains("store i8* %gc.top, i8** @__gc_frames"));
+    let unlink = g.find("store i8* %gc.prev, i8** @__gc_frames").unwrap();
+    assert!(unlink < g.find("ret i8*").unwrap());
+    assert!(!code.contains(super::gc::FRAME_MARKER));
+}
+
+// ── Typed values ────────────────────────────────────────────────────────
+
+#[test]
+fn test_typed_function_signatures() {
+    let code = generate_code(
+        "function f(x: Number): Number => x + 1; function p(s: String): Boolean => s == s; print(f(1)); print(p(\"a\"));",
+    );
+    assert!(code.contains("define double @f(double %x)"));
+    assert!(code.contains("define i1 @p(i8* %s)"));
+    assert!(function_body(&code, "p").contains("call i32 @strcmp(i8* "));
+}
+
+#[test]
+fn test_object_params_are_boxed() {
+    let code = generate_code("function id(x: Object) => x; print(id(true));");
+    assert!(code.contains("define double @id(double %x)"));
+    let main = function_body(&code, "__hulk_main");
+    // El booleano viaja etiquetado y `print` lo convierte según la etiqueta
+    assert!(main.contains("zext i1 true to i64"));
+    assert!(main.contains(&format!(", {}", box_tag(BOX_BOOL))));
+    assert!(main.contains("call void @__hulk_print_val(double "));
+}
+
+#[test]
+fn test_inferred_signatures_are_typed() {
+    let code = generate_code(
+        "function id(x) => x; function twice(n) => n * 2; print(id(true)); print(twice(3));",
+    );
+    assert!(code.contains("define i1 @id(i1 %x)"));
+    assert!(code.contains("define double @twice(double %n)"));
+}
+
+#[test]
+fn test_narrowed_call_skips_cast_check() {
+    let code = generate_code(
+        "type Animal { speak() => \"...\"; } type Dog inherits Animal { bark() => \"woof\"; } \
+         function f(a: Animal): String => if (a is Dog) a.bark() else a.speak(); print(f(new Dog()));",
+    );
+    let body = function_body(&code, "f");
+    assert!(!body.contains("as_fail"));
+    assert!(!code.contains("invalid cast"));
+}
+
+#[test]
+fn test_match_lowers_to_type_tests_and_guards() {
+    let code = generate_code(
+        "type Shape { } type Circle(r: Number) inherits Shape { r = r; } \
+         function f(s: Shape): String => match (s) { case Circle(r) if r > 1 => \"big\"; case _ => \"other\"; }; \
+         function g(s: String) => match (s) { case \"x\" => 1; default => 0; }; \
+         print(f(new Circle(2)) @ g(\"x\"));",
+    );
+    let body = function_body(&code, "f");
+    assert!(body.contains("mnext"));
+    assert!(body.contains("fcmp ogt double"));
+    assert!(body.contains("phi i8*"));
+    assert!(function_body(&code, "g").contains("call i32 @strcmp("));
+    // Con un caso que siempre encaja no queda camino sin caso
+    assert!(!code.contains("no match case"));
+}
+
+#[test]
+fn test_struct_fields_use_attribute_types() {
+    let code = generate_code(
+        "type P(x: Number, s: String) { x: Number = x; s: String = s; n = 0; ok: Boolean = true; } print(1);",
+    );
+    assert!(code.contains("%T.P = type { i64, i8**, double, i8*, double, i64 }"));
+    assert!(code.contains("define i8* @P_new(double %x, i8* %s)"));
+    assert!(function_body(&code, "P_new").contains("zext i1"));
+}
+
+#[test]
+fn test_if_branches_merge_with_phi() {
+    let code = generate_code("let x = 1 in print(if (x > 0) \"pos\" else \"neg\");");
+    let main = function_body(&code, "__hulk_main");
+    assert!(main.contains("phi i8*"));
+    assert!(main.contains("call i32 @puts(i8*"));
+}
+
+// ── Vtables ─────────────────────────────────────────────────────────────
+
+#[test]
+fn test_vtable_per_class() {
+    let code = generate_code(
+        "type A { f(): Number => 1; g(): Number => 2; } type B inherits A { g(): Number => 3; h(): Number => 4; } print(1);",
+    );
+    assert!(code.contains("@A.vtable = private unnamed_addr constant [2 x i8*] [i8* bitcast (double (i8*)* @A_f to i8*), i8* bitcast (double (i8*)* @A_g to i8*)]"));
+    // B hereda f, sobrescribe g en la misma entrada y agrega h al final
+    assert!(code.contains("@B.vtable = private unnamed_addr constant [3 x i8*] [i8* bitcast (double (i8*)* @A_f to i8*), i8* bitcast (double (i8*)* @B_g to i8*), i8* bitcast (double (i8*)* @B_h to i8*)]"));
+    assert!(function_body(&code, "B_new").contains("store i8** getelementptr inbounds ([3 x i8*], [3 x i8*]* @B.vtable, i64 0, i64 0)"));
+}
+
+#[test]
+fn test_method_calls_load_through_vtable() {
+    let code = generate_code(
+        "type A { f(x: Number): Number => x; } type B inherits A { f(x: Number): Number => x * 2; } let a: A = new B() in print(a.f(1));",
+    );
+    let main = function_body(&code, "__hulk_main");
+    assert!(main.contains("getelementptr inbounds i8*, i8** "));
+    assert!(main.contains("to double (i8*, double)*"));
+    assert!(!main.contains("call double @A_f("));
+}
+
+#[test]
+fn test_vector_methods_are_called_directly() {
+    let code = generate_code("let v = [1, 2] in print(v.size());");
+    assert!(function_body(&code, "__hulk_main").contains("call double @__Vector_size(i8* "));
+}
+
+#[test]
+fn test_protocol_thunk_per_method() {
+    let code = generate_code(
+        "protocol P { f(a: Number, b: Number): Number; g(): Boolean; } \
+         type A { f(a: Number, b: Number): Number => a + b; g(): Boolean => true; } \
+         type B { h(): Number => 0; } \
+         function use(p: P): Number => p.f(1, 2); print(use(new A()));",
+    );
+    assert!(code.contains("define double @P_f_dispatch(i8* %obj, double %a0, double %a1, double %line, double %column)"));
+    assert!(code.contains("define i1 @P_g_dispatch(i8* %obj, double %line, double %column)"));
+    // Solo A conforma a P
+    assert!(code.contains("call double @A_f(i8* %obj, double %a0, double %a1)"));
+    assert!(!code.contains("case_B"));
+    assert!(function_body(&code, "use").contains("call double @P_f_dispatch(i8* "));
+    // La llamada pasa su posición, que el thunk usa si tiene que lanzar
+    let call = function_body(&code, "use").lines().find(|l| l.contains("@P_f_dispatch(")).unwrap();
+    assert!(call.ends_with(", double 1.0, double 197.0)"), "{}", call);
+}
+
+#[test]
+fn test_functor_protocol_calls_invoke_thunk() {
+    let code = generate_code(
+        "protocol F { invoke(x: Number): Boolean; } type Pos { invoke(x: Number): Boolean => x > 0; } \
+         function check(f: F): Boolean => f(2); print(check(new Pos()));",
+    );
+    assert!(code.contains("call i1 @Pos_invoke(i8* %obj, double %a0)"));
+    assert!(function_body(&code, "check").contains("call i1 @F_invoke_dispatch(i8* "));
+}
+
+#[test]
+fn test_generic_function_boxes_type_parameters() {
+    let code = generate_code("function id<T>(x: T): T => x; print(id(\"a\") @ \"b\");");
+    assert!(code.contains("define double @id(double %x)"));
+    // El resultado se instanció como String: se desempaqueta antes de concatenar
+    let main = function_body(&code, "__hulk_main");
+    let after_call = &main[main.find("call double @id(").unwrap()..];
+    assert!(after_call.contains("inttoptr i64"));
+}
+
+#[test]
+fn test_generic_protocol_dispatches_to_implementations() {
+    let code = generate_code(
+        "protocol Comparable<T> { compare(other: T): Number; } \
+         type Money(amount: Number) { amount: Number = amount; compare(other: Money): Number => self.amount - other.amount; } \
+         function max<T: Comparable<T>>(a: T, b: T): T => if (a.compare(b) > 0) a else b; \
+         print(max(new Money(1), new Money(2)).amount);",
+    );
+    assert!(code.contains("define double @Comparable_compare_dispatch(i8* %obj, double %a0, double %line, double %column)"));
+    assert!(code.contains("call double @Money_compare(i8* %obj, i8* "));
+    assert!(code.contains("define i8* @Money_new("));
+}
+
+#[test]
+fn test_try_sets_a_handler_and_throw_unwinds() {
+    let ir = crate::driver::emit_stage(
+        "function f(x: Number): Number => if (x < 0) throw new Error(\"neg\") else x; \
+         print(try f(1) catch (e: IndexError) 0 catch (e) 1);",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let main = function_body(&ir, "__hulk_main");
+    assert!(main.contains("call i32 @_setjmp("));
+    assert!(main.contains("call void @llvm.stackrestore("));
+    assert!(function_body(&ir, "f").contains("call void @__hulk_throw("));
+    assert!(ir.contains("define void @__hulk_throw("));
+    assert!(ir.contains("c\"IndexError\\00\""));
+}
+
+#[test]
+fn test_string_methods_call_runtime_helpers() {
+    let ir = crate::driver::emit_stage(
+        "let s = \" a,b \".trim() in print(s.split(\",\").size() + s.size() + s.substring(0, 1).parse_number());",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let main = function_body(&ir, "__hulk_main");
+    assert!(main.contains("call i8* @__hulk_str_trim(i8* "));
+    assert!(main.contains("call i8* @__hulk_str_split(i8* "));
+    assert!(main.contains("call i64 @strlen(i8* "));
+    // Un rango que no cabe o un texto que no es un número se lanzan en el sitio de la llamada
+    assert!(main.contains("call i8* @__hulk_str_substring(i8* ") && ir.contains("c\"IndexError\\00\""));
+    assert!(main.contains("call i1 @__hulk_str_is_number(i8* "));
+    assert!(ir.contains("define i8* @__hulk_str_split(i8* %s, i8* %sep)"));
+    // `split` guarda el array de las partes en su propio frame
+    assert!(function_body(&ir, "__hulk_str_split").contains("store i8* %gc.prev, i8** @__gc_frames"));
+}
+
+#[test]
+fn test_string_comparisons_use_strcmp() {
+    let ir = crate::driver::emit_stage(
+        "function f(a: String, b: String): Boolean => a == b | a < b; print(f(\"x\", \"y\") @ \"x\".hash());",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let body = function_body(&ir, "f");
+    // Se comparan los contenidos, no los punteros
+    assert_eq!(body.matches("call i32 @strcmp(i8* %").count(), 2);
+    assert!(body.contains("icmp eq i32 ") && body.contains("icmp slt i32 "));
+    assert!(!body.contains("icmp eq i8* ") && !body.contains("fcmp"));
+    assert!(function_body(&ir, "__hulk_main").contains("call i32 @__hulk_str_hash(i8* "));
+    assert!(ir.contains("define i32 @__hulk_str_hash(i8* %s)"));
+}
+
+#[test]
+fn test_jumps_branch_to_loop_labels_and_return_early() {
+    let ir = crate::driver::emit_stage(
+        "function f(n: Number): Number { let i = 0 in while (true) { i := i + 1; \
+         if (i == n) return i else if (i > 10) break else continue; }; try { return 0; } catch (e) 1; } \
+         print(f(3));",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let body = function_body(&ir, "f");
+    assert!(body.contains("br label %wend"));
+    assert!(body.contains("br label %wcond"));
+    // Un `return` temprano dentro de un `try` desengancha el handler antes de salir
+    assert_eq!(body.matches("ret double").count(), 3);
+    assert!(body.contains("call void @llvm.stackrestore("));
+    assert!(!body.contains("gc.unlink"));
+}
+
+#[test]
+fn test_maps_use_the_map_runtime() {
+    let ir = crate::driver::emit_stage(
+        "function f(m: Map<String, Number>): Number => m.get(\"a\") + m.size(); \
+         let m = {\"a\" -> 1} in { m.set(\"b\", 2); for (k in m) print(k); print(f(m)); print(m); };",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let body = function_body(&ir, "f");
+    assert!(body.contains("call i64 @__Map_find(i8* "));
+    assert!(body.contains("call double @__Map_value_at(i8* "));
+    // Una clave que no está lanza KeyError
+    assert!(body.contains("call i8* @KeyError_new("));
+    let main = function_body(&ir, "__hulk_main");
+    // Las claves del literal son cadenas: se comparan por contenido
+    assert!(main.contains("call i8* @__Map_new(i1 true)"));
+    assert_eq!(main.matches("call void @__Map_set(i8* ").count(), 2);
+    assert!(main.contains("call i1 @__Map_next(i8* ") && main.contains("call double @__Map_get_current(i8* "));
+    // `print(m)` convierte el diccionario según las etiquetas de sus pares
+    assert!(main.contains("call void @__hulk_print_val(double "));
+    assert!(ir.contains("define i8* @__Map_to_str(i8* %self, i1 %print)"));
+    assert!(ir.contains("%__Map = type { i64, double*, double*, i64*, i64, i64, i64, i64, i64 }"));
+}
+
+#[test]
+fn test_growable_vectors_use_the_vector_runtime() {
+    let ir = crate::driver::emit_stage(
+        "let v = [1, 2] in { v.push(3); v.insert(0, 4); v[1] := v.pop() + v.remove_at(0); print(v[1:] + v[:1]); };",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    let main = function_body(&ir, "__hulk_main");
+    assert!(main.contains("call void @__Vector_push(i8* "));
+    assert!(main.contains("call void @__Vector_insert(i8* "));
+    assert_eq!(main.matches("call double @__Vector_remove_at(i8* ").count(), 2);
+    assert_eq!(main.matches("call i8* @__Vector_slice(i8* ").count(), 2);
+    assert!(main.contains("call i8* @__Vector_concat(i8* "));
+    // Cada índice se revisa en el sitio de la llamada
+    assert!(main.matches("call i8* @IndexError_new(").count() >= 6);
+    // Un slice al revés lanza con el mensaje del intérprete, armado en ejecución
+    assert_eq!(main.matches("call i8* @__Vector_slice_message(i64 ").count(), 2);
+    // Crecer copia a un bloque nuevo del GC, no usa realloc
+    let reserve = function_body(&ir, "__Vector_reserve");
+    assert!(reserve.contains("call i8* @__hulk_gc_alloc(") && reserve.contains("call i8* @memcpy("));
+    assert!(!reserve.contains("@realloc("));
+    assert!(ir.contains("%__Vector = type { i64, double*, double, double, i64 }"));
+}
+
+#[test]
+fn test_for_calls_current_and_dispatches_iterable() {
+    let ir = crate::driver::emit_stage(
+        "function total(xs: Number*): Number => let s = 0 in { for (x in xs) s := s + x; s; }; \
+        for (i in range(0, 3)) print(total([i]) + total(range(0, i)));",
+        crate::driver::Emit::Llvm,
+    ).unwrap();
+    // `range` y `Range` son del prelude; el `for` llama a `next()` y `current()` por la vtable
+    assert!(function_body(&ir, "__hulk_main").contains("call i8* @range(double 0.0e0, double 3.0e0)"));
+    assert!(ir.contains("[i8* bitcast (i1 (i8*)* @Range_next to i8*), i8* bitcast (double (i8*)* @Range_current to i8*)]"));
+    // Un `Number*` pasa por los thunks del protocolo `Iterable`, que también
+    // despachan a los vectores y a los diccionarios
+    let total = function_body(&ir, "total");
+    assert!(total.contains("@Iterable_next_dispatch(i8* ") && total.contains("@Iterable_current_dispatch(i8* "));
+    let start = ir.find("define double @Iterable_current_dispatch(").unwrap();
+    let current = &ir[start..start + ir[start..].find("\n}\n").unwrap()];
+    assert!(current.contains("label %case_Range") && current.contains("label %case___Vector") && current.contains("label %case___Map"));
+    assert!(current.contains("call double @__Vector_get_current(i8* %obj)"));
+}
+
+#[test]
+fn test_main_flushes_stdout_on_a_crash() {
+    let code = generate_code("print(1);");
+    let main = function_body(&code, "main");
+    assert!(main.contains("call void @__hulk_install_crash_handler()"));
+    assert!(function_body(&code, "__hulk_on_crash").contains("call i32 @fflush(i8* null)"));
+}
@@ -1,4 +1,6 @@
 use crate::ast::nodes::*;
+use crate::semantic::Context as SemanticContext;
+use crate::semantic::types::{Type, TypeKind};
 
 /// Escapar caracteres especiales para LLVM IR.
 pub fn escape_llvm(s: &str) -> String {
@@ -26,21 +28,136 @@ pub fn fmt_double(v: f64) -> String {
     s
 }
 
-/// Etiqueta ligera para saber cómo cargar / imprimir un valor.
+/// Clase de un valor empaquetado, que se lee de sus 16 bits altos (ver `box_tag`).
+pub const BOX_NUM: u32 = 0;
+pub const BOX_BOOL: u32 = 1;
+pub const BOX_STR: u32 = 2;
+/// Instancia de una clase, vector o diccionario: la primera palabra es su type-id.
+pub const BOX_REF: u32 = 3;
+pub const BOX_FN: u32 = 4;
+
+/// 16 bits altos de los valores empaquetados de clase `kind` (`0xFFF8 + kind`).
+/// Son NaN que la aritmética no produce: la de x86 da `0xFFF8...` y la de
+/// AArch64 `0x7FF8...`.
+pub const BOX_TAG_BASE: u64 = 0xFFF8;
+
+/// Bits bajos de un valor empaquetado que no es número: el booleano o el puntero.
+pub const BOX_PAYLOAD_MASK: i64 = 0x0000_FFFF_FFFF_FFFF;
+
+/// Palabra de 64 bits con la etiqueta de `kind`, lista para un `or` del IR.
+pub fn box_tag(kind: u32) -> i64 {
+    ((BOX_TAG_BASE + kind as u64) << 48) as i64
+}
+
+/// Tipo estático de un valor en el IR, derivado del tipo semántico.
+///
+/// Cada variante tiene una representación fija (`llvm`). `Boxed` es la frontera con
+/// `Object` y los protocolos: un `double` que guarda un número tal cual y el resto
+/// como NaN etiquetado: la clase del valor en los 16 bits altos (`box_tag`) y el
+/// booleano o el puntero en los 48 bajos. Así `print`, `is`, `as` y `==` saben en
+/// ejecución qué hay dentro.
 #[derive(Clone, Debug, PartialEq)]
-#[allow(dead_code)]
-pub enum ValTy { Num, Bool, Str, Ptr, Obj(String), Fn(Box<ValTy>) }
+pub enum ValTy {
+    /// `double`
+    Num,
+    /// `i1`
+    Bool,
+    /// `i8*` a una cadena terminada en cero
+    Str,
+    /// `i8*` a una instancia de la clase
+    Obj(String),
+    /// `i8*` a un `%__Vector`, con el tipo de sus elementos
+    Vector(Box<ValTy>),
+    /// `i8*` a un `%__Map`, con el tipo de sus claves y el de sus valores
+    Map(Box<ValTy>, Box<ValTy>),
+    /// `i8*` a una clausura `{ fn, env }`, con el tipo que retorna
+    Fn(Box<ValTy>),
+    /// `double` empaquetado; el nombre es `Object` o el del protocolo
+    Boxed(String),
+    /// Resultado de una expresión que no termina (`throw`); viaja como `double`
+    /// pero nunca se lee
+    Never,
+}
 
-/// Convertir anotación de tipo a ValTy.
-pub fn val_ty_from_annotation(ann: &Option<TypeAnnotation>) -> ValTy {
-    match ann {
-        Some(TypeAnnotation::Name(n)) => match n.as_str() {
-            "String" => ValTy::Str,
+impl ValTy {
+    pub fn object() -> ValTy { ValTy::Boxed("Object".to_string()) }
+
+    /// Tipo LLVM con que viaja el valor.
+    pub fn llvm(&self) -> &'static str {
+        match self {
+            ValTy::Num | ValTy::Boxed(_) | ValTy::Never => "double",
+            ValTy::Bool => "i1",
+            ValTy::Str | ValTy::Obj(_) | ValTy::Vector(_) | ValTy::Map(..) | ValTy::Fn(_) => "i8*",
+        }
+    }
+
+    /// Clase con que se etiqueta el valor al empaquetarlo.
+    pub fn box_kind(&self) -> u32 {
+        match self {
+            ValTy::Num | ValTy::Boxed(_) | ValTy::Never => BOX_NUM,
+            ValTy::Bool => BOX_BOOL,
+            ValTy::Str => BOX_STR,
+            ValTy::Obj(_) | ValTy::Vector(_) | ValTy::Map(..) => BOX_REF,
+            ValTy::Fn(_) => BOX_FN,
+        }
+    }
+
+    /// `true` si el valor es un puntero sin empaquetar.
+    pub fn is_ref(&self) -> bool { self.llvm() == "i8*" }
+
+    /// Tipo LLVM de un campo de objeto: cada campo ocupa una palabra, así que
+    /// los booleanos se guardan extendidos a `i64`.
+    pub fn field_llvm(&self) -> &'static str {
+        if *self == ValTy::Bool { "i64" } else { self.llvm() }
+    }
+
+    /// Representación de un tipo del chequeo semántico.
+    pub fn from_type(ty: &Type) -> ValTy {
+        match ty.name.as_str() {
             "Number" => ValTy::Num,
             "Boolean" => ValTy::Bool,
-            other => ValTy::Obj(other.to_string()),
-        },
-        _ => ValTy::Num,
+            "String" => ValTy::Str,
+            "Object" => ValTy::object(),
+            name if name.starts_with("Vector<") => {
+                let elem = ty.get_attribute("__element_type")
+                    .map_or_else(ValTy::object, |e| ValTy::from_type(&e.borrow()));
+                ValTy::Vector(Box::new(elem))
+            }
+            // Genéricos por empaquetado uniforme: `T` es un `Object` y
+            // `Stack<Number>` comparte la clase de `Stack`
+            _ if ty.kind == TypeKind::Param => ValTy::object(),
+            _ if ty.kind == TypeKind::Never => ValTy::Never,
+            _ if let Some((base, args)) = &ty.instance_of && base == "Map" && args.len() == 2 => ValTy::Map(
+                Box::new(ValTy::from_type(&args[0].borrow())),
+                Box::new(ValTy::from_type(&args[1].borrow())),
+            ),
+            _ if let Some((base, _)) = &ty.instance_of => match ty.kind {
+                TypeKind::Protocol => ValTy::Boxed(base.clone()),
+                _ => ValTy::Obj(base.clone()),
+            },
+            name if ty.kind == TypeKind::Protocol => ValTy::Boxed(name.to_string()),
+            name => ValTy::Obj(name.to_string()),
+        }
+    }
+
+    /// Representación de una anotación; sin anotación el valor es `Object`.
+    pub fn from_annotation(sem: &SemanticContext, ann: &Option<TypeAnnotation>) -> ValTy {
+        match ann {
+            Some(ann) => sem.resolve_type(ann)
+                .map_or_else(|_| ValTy::object(), |t| ValTy::from_type(&t.borrow())),
+            None => ValTy::object(),
+        }
+    }
+
+    /// Tipo común de dos ramas: el mismo si coinciden y `Object` si no.
+    pub fn join(&self, other: &ValTy) -> ValTy {
+        match (self, other) {
+            (a, b) if a == b => a.clone(),
+            (ValTy::Never, t) | (t, ValTy::Never) => t.clone(),
+            (ValTy::Vector(a), ValTy::Vector(b)) => ValTy::Vector(Box::new(a.join(b))),
+            (ValTy::Map(ka, va), ValTy::Map(kb, vb)) => ValTy::Map(Box::new(ka.join(kb)), Box::new(va.join(vb))),
+            _ => ValTy::object(),
+        }
     }
 }
 
@@ -0,0 +1,303 @@
+use crate::ast::nodes::{Declaration, Expr, Program};
+use crate::ast::walk;
+use crate::ast::optimize::optimize_program;
+use crate::ast::transform::{lower_variants, transform_implicit_functors};
+use crate::codegen::{CodeGenerator, llvm_target::LlvmGenerator};
+use crate::errors::{Diagnostic, LexError, ParseError, RuntimeError, SemanticError};
+use crate::interp::Interpreter;
+use crate::lexer::Lexer;
+use crate::macros::expand_macros;
+use crate::parser::Parser;
+use crate::semantic::{check_program, Context};
+use crate::utils::{Position, Spanned};
+
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(test)]
+mod tests;
+
+/// Etapa del pipeline que se puede volcar con `--emit=<etapa>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Expanded,
+    Optimized,
+    Llvm,
+}
+
+impl Emit {
+    pub const NAMES: &'static str = "tokens|ast|expanded|optimized|llvm";
+
+    /// Convierte el valor de `--emit` en una etapa del pipeline.
+    pub fn parse(name: &str) -> Option<Emit> {
+        match name {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "expanded" => Some(Emit::Expanded),
+            "optimized" => Some(Emit::Optimized),
+            "llvm" => Some(Emit::Llvm),
+            _ => None,
+        }
+    }
+}
+
+/// Backend con el que `hulk run` ejecuta el programa (`--backend=<nombre>`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Genera LLVM IR, lo enlaza con clang y ejecuta el binario.
+    Llvm,
+    /// Recorre el AST optimizado con el intérprete, sin toolchain nativa.
+    Interp,
+}
+
+impl Backend {
+    pub const NAMES: &'static str = "llvm|interp";
+
+    /// Convierte el valor de `--backend` en un backend.
+    pub fn parse(name: &str) -> Option<Backend> {
+        match name {
+            "llvm" => Some(Backend::Llvm),
+            "interp" => Some(Backend::Interp),
+            _ => None,
+        }
+    }
+}
+
+/// Error de cualquiera de las fases del frontend.
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    Lex(LexError),
+    /// Todos los errores sintácticos encontrados (el parser se recupera tras cada uno).
+    Parse(Vec<ParseError>),
+    Semantic(Vec<SemanticError>),
+}
+
+impl CompileError {
+    /// Convierte el error en diagnósticos con código, posición y pistas.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            CompileError::Lex(e) => vec![Diagnostic::from(e)],
+            CompileError::Parse(errors) => errors.iter().map(Diagnostic::from).collect(),
+            CompileError::Semantic(errors) => errors.iter().map(Diagnostic::from).collect(),
+        }
+    }
+
+    /// Renderiza todos los diagnósticos sobre el código fuente `source`.
+    pub fn render(&self, source: &str, file: &str) -> String {
+        self.diagnostics()
+            .iter()
+            .map(|d| d.render(source, file))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diag) in self.diagnostics().iter().enumerate() {
+            if i > 0 { writeln!(f)?; }
+            write!(f, "{}", diag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Programa que superó el análisis semántico, junto con su contexto de tipos.
+pub struct Checked {
+    pub program: Program,
+    pub context: Context,
+}
+
+/// Vuelca la secuencia de tokens, uno por línea con su posición.
+pub fn dump_tokens(source: &str) -> Result<String, CompileError> {
+    let mut out = String::new();
+    for tok in Lexer::new(source) {
+        let (tok, pos) = tok.map_err(CompileError::Lex)?;
+        out.push_str(&format!("{}\t{:?}\n", pos, tok));
+    }
+    Ok(out)
+}
+
+/// Analiza sintácticamente el código fuente, reportando todos los errores de una pasada.
+pub fn parse(source: &str) -> Result<Program, CompileError> {
+    let (program, errors) = Parser::new(source).parse_program_recovering();
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(CompileError::Parse(errors))
+    }
+}
+
+/// Parsea y expande macros, `variant` e implicit functors (todo lo previo al chequeo semántico).
+pub fn expand(source: &str) -> Result<Program, CompileError> {
+    Ok(expand_program(parse(source)?))
+}
+
+/// Tipos predefinidos escritos en HULK (la jerarquía de `Error` y `Range`).
+const PRELUDE: &str = include_str!("prelude.hulk");
+
+/// Declaraciones del prelude. No tienen posición: no deben confundirse con
+/// nodos del programa del usuario en las tablas indexadas por rango.
+pub fn prelude() -> Vec<Declaration> {
+    fn clear(expr: &mut Spanned<Expr>) {
+        expr.pos = Position::default();
+        expr.end = Position::default();
+        walk::children_mut(&mut expr.node).into_iter().for_each(clear);
+    }
+    let mut program = Parser::new(PRELUDE).parse_program().expect("the prelude must parse");
+    walk::root_exprs_mut(&mut program).into_iter().for_each(clear);
+    for decl in &mut program.declarations {
+        match decl {
+            Declaration::Type(t) => {
+                t.pos = Position::default();
+                t.attributes.iter_mut().for_each(|a| a.pos = Position::default());
+                t.methods.iter_mut().for_each(|m| m.pos = Position::default());
+            }
+            Declaration::Function(f) => f.pos = Position::default(),
+            _ => {}
+        }
+    }
+    program.declarations
+}
+
+/// Quita del programa expandido las declaraciones del prelude (para `--emit`).
+fn without_prelude(mut program: Program) -> Program {
+    program.declarations.drain(..prelude().len());
+    program
+}
+
+/// Añade el prelude y expande macros, `variant` e implicit functors de un
+/// programa ya parseado.
+pub fn expand_program(mut program: Program) -> Program {
+    program.declarations.splice(0..0, prelude());
+    let mut expanded = expand_macros(program);
+    lower_variants(&mut expanded);
+    // Los implicit functors se transforman ANTES del semantic check
+    let temp_ctx = Context::new();
+    transform_implicit_functors(&mut expanded, &temp_ctx);
+    expanded
+}
+
+/// Ejecuta el frontend completo: parsing, expansión y chequeo semántico.
+pub fn check(source: &str) -> Result<Checked, CompileError> {
+    let program = expand(source)?;
+    let context = check_program(&program).map_err(CompileError::Semantic)?;
+    Ok(Checked { program, context })
+}
+
+/// Chequea y optimiza el programa: la entrada común a los dos backends.
+pub fn optimize(source: &str) -> Result<Checked, CompileError> {
+    let Checked { program, context } = check(source)?;
+    Ok(Checked { program: optimize_program(program), context })
+}
+
+/// Genera el LLVM IR del programa optimizado.
+pub fn compile_to_llvm(source: &str) -> Result<String, CompileError> {
+    let Checked { program, context } = optimize(source)?;
+    Ok(LlvmGenerator.generate(&program, &context))
+}
+
+/// Ejecuta con el intérprete un programa ya optimizado, escribiendo lo impreso en `out`.
+///
+/// El intérprete es recursivo: hay que llamarlo desde un hilo con
+/// `interp::STACK_SIZE` de pila para programas arbitrarios.
+pub fn run_interpreted(checked: &Checked, out: impl Write) -> Result<(), RuntimeError> {
+    Interpreter::new(&checked.program, &checked.context, out).run().map(|_| ())
+}
+
+/// Produce la representación textual de la etapa pedida.
+pub fn emit_stage(source: &str, stage: Emit) -> Result<String, CompileError> {
+    match stage {
+        Emit::Tokens => dump_tokens(source),
+        Emit::Ast => Ok(format!("{}\n", parse(source)?)),
+        Emit::Expanded => Ok(format!("{}\n", without_prelude(expand(source)?))),
+        Emit::Optimized => Ok(format!("{}\n", without_prelude(optimize(source)?.program))),
+        Emit::Llvm => compile_to_llvm(source),
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Compilación nativa con el clang del sistema
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Error al invocar la toolchain nativa.
+#[derive(Debug)]
+pub enum ToolchainError {
+    /// No se pudo lanzar el proceso (p. ej. `clang` no instalado).
+    Spawn(String, std::io::Error),
+    /// `clang` terminó con error; se adjunta su stderr.
+    Failed(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ToolchainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolchainError::Spawn(cmd, e) => write!(f, "could not run '{}': {}", cmd, e),
+            ToolchainError::Failed(stderr) => write!(f, "clang failed:\n{}", stderr),
+            ToolchainError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Compilador de C usado para enlazar; `$HULK_CC` permite sustituir a `clang`.
+fn cc() -> String {
+    std::env::var("HULK_CC").unwrap_or_else(|_| "clang".to_string())
+}
+
+/// Compila un archivo `.ll` a un ejecutable nativo.
+pub fn link_native(ll_path: &Path, out: &Path) -> Result<(), ToolchainError> {
+    let cc = cc();
+    let output = Command::new(&cc)
+        .arg(ll_path)
+        .arg("-o")
+        .arg(out)
+        .args(["-lm", "-Wno-override-module"])
+        .output()
+        .map_err(|e| ToolchainError::Spawn(cc.clone(), e))?;
+    if !output.status.success() {
+        return Err(ToolchainError::Failed(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(())
+}
+
+/// Directorio temporal nuevo para artefactos intermedios, distinto en cada
+/// llamada para que varias compilaciones del mismo proceso no se pisen.
+pub fn scratch_dir() -> Result<PathBuf, ToolchainError> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("hulk-{}-{}", std::process::id(), id));
+    std::fs::create_dir_all(&dir).map_err(ToolchainError::Io)?;
+    Ok(dir)
+}
+
+/// Compila el IR a un binario temporal y lo ejecuta heredando stdin/stdout/stderr.
+pub fn run_llvm(llvm_code: &str) -> Result<ExitStatus, ToolchainError> {
+    with_native_binary(llvm_code, |bin| Command::new(bin).status())
+}
+
+/// Como `run_llvm`, pero captura la salida del programa en lugar de heredarla.
+pub fn run_llvm_captured(llvm_code: &str) -> Result<Output, ToolchainError> {
+    with_native_binary(llvm_code, |bin| Command::new(bin).stdin(Stdio::null()).output())
+}
+
+/// Enlaza el IR en un directorio temporal, ejecuta `run` sobre el binario y limpia.
+fn with_native_binary<T>(
+    llvm_code: &str,
+    run: impl FnOnce(&Path) -> std::io::Result<T>,
+) -> Result<T, ToolchainError> {
+    let dir = scratch_dir()?;
+    let ll_path = dir.join("main.ll");
+    let bin_path = dir.join("main");
+    std::fs::write(&ll_path, llvm_code).map_err(ToolchainError::Io)?;
+    let result = link_native(&ll_path, &bin_path).and_then(|_| {
+        run(&bin_path).map_err(|e| ToolchainError::Spawn(bin_path.display().to_string(), e))
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
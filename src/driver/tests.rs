@@ -0,0 +1,158 @@
+use super::*;
+use crate::ast::nodes::Expr;
+
+#[test]
+fn test_emit_parse_names() {
+    assert_eq!(Emit::parse("tokens"), Some(Emit::Tokens));
+    assert_eq!(Emit::parse("ast"), Some(Emit::Ast));
+    assert_eq!(Emit::parse("expanded"), Some(Emit::Expanded));
+    assert_eq!(Emit::parse("optimized"), Some(Emit::Optimized));
+    assert_eq!(Emit::parse("llvm"), Some(Emit::Llvm));
+    assert_eq!(Emit::parse("asm"), None);
+}
+
+#[test]
+fn test_dump_tokens_has_positions() {
+    let out = dump_tokens("let x = 1;").unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "1:1\tLet");
+    assert!(lines[1].starts_with("1:5\tIdentifier"));
+}
+
+#[test]
+fn test_emit_optimized_folds_constants() {
+    let out = emit_stage("print(1 + 2);", Emit::Optimized).unwrap();
+    assert!(out.contains('3'));
+    assert!(!out.contains('+'));
+}
+
+#[test]
+fn test_emit_optimized_folds_string_comparisons() {
+    let out = emit_stage("print(\"abc\" == \"abc\"); print(\"b\" < \"a\");", Emit::Optimized).unwrap();
+    assert!(out.contains("true") && out.contains("false"));
+    assert!(!out.contains("==") && !out.contains('<'));
+}
+
+#[test]
+fn test_emit_llvm_defines_entry_points() {
+    let ir = emit_stage("print(42);", Emit::Llvm).unwrap();
+    assert!(ir.contains("define double @__hulk_main()"));
+    assert!(ir.contains("define i32 @main()"));
+}
+
+// `compile_to_llvm` es toda la entrada del playground: tiene que bajar los
+// `variant` igual que `hulk run`
+#[test]
+fn test_compile_to_llvm_lowers_variants() {
+    let ir = compile_to_llvm("variant Shape = Dot | Circle(r: Number); \
+        function radius(s: Shape): Number => match (s) { case Dot => 0; case Circle(r) => r; }; \
+        print(radius(new Circle(2)));").unwrap();
+    assert!(ir.contains("Circle"));
+}
+
+#[test]
+fn test_compile_to_llvm_includes_the_error_prelude() {
+    let ir = compile_to_llvm("print(try [1][5] catch (e: IndexError) e.message catch (e: Error) \"other\");").unwrap();
+    assert!(ir.contains("IndexError"));
+}
+
+#[test]
+fn test_compile_to_llvm_includes_range() {
+    let ir = compile_to_llvm("for (i in range(0, 3)) print(i);").unwrap();
+    assert!(ir.contains("Range"));
+}
+
+#[test]
+fn test_check_reports_semantic_errors() {
+    match check("print(y);") {
+        Err(CompileError::Semantic(errors)) => assert!(!errors.is_empty()),
+        _ => panic!("se esperaba un error semántico"),
+    }
+}
+
+#[test]
+fn test_parse_error_is_reported() {
+    assert!(matches!(check("let x = in x;"), Err(CompileError::Parse(_))));
+}
+
+#[test]
+fn test_spans_survive_expansion_and_optimization() {
+    let source = "def double(x: Number): Number => x * 2;\nlet y = rand() in print(double(y) + 1);";
+    let text = |p: &Program| source[p.expr.pos.offset..p.expr.end.offset].to_string();
+
+    let expanded = expand(source).unwrap();
+    assert_eq!(text(&expanded), "let y = rand() in print(double(y) + 1)");
+
+    let optimized = optimize_program(check(source).unwrap().program);
+    assert_eq!(text(&optimized), "let y = rand() in print(double(y) + 1)");
+    // `double(y)` se sustituye por el cuerpo de la macro, pero conserva el rango de la llamada
+    let Expr::Let { body, .. } = &optimized.expr.node else { panic!("expected let") };
+    let Expr::Call { args, .. } = &body.node else { panic!("expected print call") };
+    let Expr::Binary(call, _, _) = &args[0].node else { panic!("expected binary") };
+    assert_eq!(&source[call.pos.offset..call.end.offset], "double(y)");
+}
+
+#[test]
+fn test_backend_parse_names() {
+    assert_eq!(Backend::parse("llvm"), Some(Backend::Llvm));
+    assert_eq!(Backend::parse("interp"), Some(Backend::Interp));
+    assert_eq!(Backend::parse("clang"), None);
+}
+
+/// Ejecuta `source` de punta a punta con el intérprete, sin clang.
+fn run(source: &str) -> Result<String, RuntimeError> {
+    let checked = optimize(source).unwrap_or_else(|e| panic!("el programa no compila:\n{}", e));
+    let mut out = Vec::new();
+    run_interpreted(&checked, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn test_run_interpreted_executes_whole_programs() {
+    let out = run(r#"
+        protocol Shape {
+            area(): Number;
+        }
+        type Rect(w: Number, h: Number) {
+            w: Number = w;
+            h: Number = h;
+            area(): Number => self.w * self.h;
+            describe(): String => "rect " @ self.area();
+        }
+        type Square(side: Number) inherits Rect(side, side) {
+            describe(): String => base() @ " (square)";
+        }
+        function areaOf(s: Shape): Number => s.area();
+        let shapes = [new Rect(2, 3), new Square(4)], scale = 10, f = (x: Number) => x * scale in {
+            for (s in shapes) print((s as Rect).describe());
+            print(areaOf(shapes[0]) + areaOf(shapes[1]));
+            print(f(shapes[1].area()));
+            print(shapes[1] is Square);
+            print(shapes[0] is Square);
+            let r = rand() in print(0 <= r & r < 1);
+        }
+    "#).unwrap();
+    assert_eq!(out, "rect 6\nrect 16 (square)\n22\n160\ntrue\nfalse\ntrue\n");
+}
+
+#[test]
+fn test_run_interpreted_sees_the_optimized_program() {
+    // El plegado de constantes no cambia lo que se imprime
+    let source = "let x = 2 * 3 + 0, s = \"a\" @ \"b\" in print(s @ x ^ 1);";
+    assert!(!emit_stage(source, Emit::Optimized).unwrap().contains('*'));
+    assert_eq!(run(source).unwrap(), "ab6\n");
+}
+
+#[test]
+fn test_run_interpreted_reports_runtime_errors() {
+    let err = run("print(1); print([1, 2][5]);").unwrap_err();
+    assert_eq!(err.code(), "E0301");
+}
+
+#[test]
+fn test_scratch_dirs_are_unique() {
+    let (a, b) = (scratch_dir().unwrap(), scratch_dir().unwrap());
+    assert_ne!(a, b);
+    let _ = std::fs::remove_dir_all(a);
+    let _ = std::fs::remove_dir_all(b);
+}
@@ -0,0 +1,231 @@
+use crate::utils::Span;
+use super::{LexError, ParseError, RuntimeError, SemanticError};
+use std::fmt;
+
+/// Gravedad de un diagnóstico.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Diagnóstico listo para mostrar: código estable, mensaje, rango y pistas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Crea un error; `span` puede ser un `Span` o una `Position` (rango vacío).
+    pub fn error(code: &'static str, message: impl Into<String>, span: impl Into<Span>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: span.into(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Renderiza el diagnóstico con la línea afectada y un subrayado `^^^`:
+    ///
+    /// ```text
+    /// error[E0206]: variable 'y' not found
+    ///  --> main.hulk:1:7
+    ///   |
+    /// 1 | print(y);
+    ///   |       ^
+    ///   = help: declare it first, e.g. `let y = ... in ...`
+    /// ```
+    pub fn render(&self, source: &str, file: &str) -> String {
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        let start = self.span.start;
+        let line_text = if start.line > 0 { source.lines().nth(start.line - 1) } else { None };
+
+        let gutter = " ".repeat(start.line.to_string().len());
+        out.push_str(&format!("{}--> {}:{}\n", gutter, file, start));
+
+        if let Some(text) = line_text {
+            let col = start.column.max(1) - 1;
+            // Conservar tabulaciones para que el subrayado quede alineado
+            let padding: String = text.chars().take(col)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = self.underline_width(text, col);
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} | {}\n", start.line, text));
+            out.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(width)));
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+        if let Some(help) = &self.help {
+            out.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        out
+    }
+
+    /// Ancho del subrayado: el rango completo si acaba en la misma línea y no
+    /// está vacío; si no, el token que empieza en `col`.
+    fn underline_width(&self, line: &str, col: usize) -> usize {
+        let Span { start, end } = self.span;
+        if end.line == start.line && end.column > start.column {
+            (end.column - start.column).min(line.chars().count().saturating_sub(col)).max(1)
+        } else {
+            token_width(line, col)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {} at {}", self.severity, self.code, self.message, self.span.start)
+    }
+}
+
+/// Ancho (en caracteres) del token que empieza en `col`: identificadores y
+/// números completos, literales de string hasta la comilla de cierre, o 1.
+fn token_width(line: &str, col: usize) -> usize {
+    let rest: Vec<char> = line.chars().skip(col).collect();
+    match rest.first() {
+        Some(c) if c.is_alphanumeric() || *c == '_' => {
+            rest.iter().take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '.').count()
+        }
+        Some('"') => match rest[1..].iter().position(|c| *c == '"') {
+            Some(end) => end + 2,
+            None => rest.len(),
+        },
+        _ => 1,
+    }
+}
+
+/// Quita el `Some("...")` del token encontrado: `Some("Identifier(\"c\")")` -> `Identifier("c")`.
+fn describe_found(found: &str) -> String {
+    match found.strip_prefix("Some(\"").and_then(|s| s.strip_suffix("\")")) {
+        Some(inner) => inner.replace("\\\"", "\""),
+        None => found.to_string(),
+    }
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(err: &LexError) -> Self {
+        let diag = Diagnostic::error(err.code(), lex_message(err), err.pos());
+        match err {
+            LexError::UnterminatedString(_) => diag.with_help("add a closing '\"'"),
+            LexError::UnterminatedBlockComment(_) => diag.with_help("close the comment with '*/'"),
+            LexError::UnexpectedCharacter(..) => diag,
+        }
+    }
+}
+
+fn lex_message(err: &LexError) -> String {
+    match err {
+        LexError::UnterminatedString(_) => "unterminated string literal".to_string(),
+        LexError::UnterminatedBlockComment(_) => "unterminated block comment".to_string(),
+        LexError::UnexpectedCharacter(c, _) => format!("unexpected character '{}'", c),
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        match err {
+            ParseError::UnexpectedToken { expected, found, pos } => {
+                let found = describe_found(found);
+                // `consume` guarda el mensaje completo ("Expected ';' after ...") en `expected`
+                let message = if expected.starts_with("Expected") || expected.starts_with("Protocols") {
+                    format!("{}, found {}", expected, found)
+                } else {
+                    format!("expected {}, found {}", expected, found)
+                };
+                Diagnostic::error(err.code(), message, *pos)
+            }
+            ParseError::UnexpectedEOF(pos) => Diagnostic::error(err.code(), "unexpected end of file", *pos)
+                .with_help("check for a missing ')', '}' or ';'"),
+            ParseError::InvalidExpression(pos) => Diagnostic::error(err.code(), "invalid expression", *pos),
+            ParseError::Lex(lex) => Diagnostic::from(lex),
+        }
+    }
+}
+
+impl From<&SemanticError> for Diagnostic {
+    fn from(err: &SemanticError) -> Self {
+        let mut diag = Diagnostic::error(err.code(), err.to_string(), err.span());
+        match err {
+            SemanticError::VariableNotFound(name, _) | SemanticError::UndefinedVariable(name, _) => {
+                diag = diag.with_help(format!("declare it first, e.g. `let {} = ... in ...`", name));
+            }
+            SemanticError::TypeMismatch { expected, found, .. } => {
+                diag = diag.with_note(format!("'{}' does not conform to '{}'", found, expected));
+            }
+            SemanticError::ArgumentCountMismatch(name, expected, _, _) => {
+                diag = diag.with_note(format!("'{}' is declared with {} parameter(s)", name, expected));
+            }
+            SemanticError::BoundNotSatisfied { ty, bound, .. } => {
+                diag = diag.with_note(format!("'{}' does not conform to '{}'", ty, bound));
+            }
+            SemanticError::AmbiguousType { name, .. } => {
+                diag = diag.with_help(format!("add a type annotation to '{}'", name));
+            }
+            SemanticError::NonExhaustiveMatch { .. } => {
+                diag = diag.with_help("add the missing cases or a 'default' branch");
+            }
+            SemanticError::MisplacedJump { keyword, .. } if keyword != "return" => {
+                diag = diag.with_note("only valid inside a 'while' or 'for' body, not counting lambda bodies");
+            }
+            SemanticError::NotIterable { .. } => {
+                diag = diag.with_help("implement 'next(): Boolean' and 'current()', or count with 'range(a, b)'");
+            }
+            SemanticError::SelfReference(_) => {
+                diag = diag.with_help("'self' is only available inside methods and attribute initializers");
+            }
+            SemanticError::CircularInheritance(..) => {
+                diag = diag.with_help("remove one of the 'inherits' clauses to break the cycle");
+            }
+            SemanticError::AttributeDefined(..) | SemanticError::MethodDefined(..) => {
+                diag = diag.with_help("rename or remove one of the definitions");
+            }
+            _ => {}
+        }
+        diag
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(err: &RuntimeError) -> Self {
+        let diag = Diagnostic::error(err.code(), err.to_string(), err.span());
+        match err {
+            RuntimeError::IndexOutOfBounds { len: 0, .. } => diag.with_note("the vector is empty"),
+            RuntimeError::IndexOutOfBounds { len, .. } => {
+                diag.with_note(format!("valid indices go from 0 to {}", len - 1))
+            }
+            RuntimeError::StackOverflow(_) => diag.with_help("check that the recursion has a base case"),
+            _ => diag,
+        }
+    }
+}
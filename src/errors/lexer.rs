@@ -8,6 +8,26 @@ pub enum LexError {
     UnexpectedCharacter(char, Position),
 }
 
+impl LexError {
+    /// Posición del carácter problemático.
+    pub fn pos(&self) -> Position {
+        match self {
+            LexError::UnterminatedString(pos)
+            | LexError::UnterminatedBlockComment(pos)
+            | LexError::UnexpectedCharacter(_, pos) => *pos,
+        }
+    }
+
+    /// Código estable del error, usado en los diagnósticos (`E00xx`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexError::UnterminatedString(_) => "E0001",
+            LexError::UnterminatedBlockComment(_) => "E0002",
+            LexError::UnexpectedCharacter(..) => "E0003",
+        }
+    }
+}
+
 impl fmt::Display for LexError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -1,7 +1,14 @@
 mod lexer;
 mod parser;
 pub mod semantic;
+pub mod runtime;
+pub mod diagnostic;
+
+#[cfg(test)]
+mod tests;
 
 pub use lexer::LexError;
 pub use parser::ParseError;
 pub use semantic::SemanticError;
+pub use runtime::RuntimeError;
+pub use diagnostic::{Diagnostic, Severity};
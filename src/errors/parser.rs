@@ -13,6 +13,27 @@ pub enum ParseError {
     Lex(crate::errors::LexError),
 }
 
+impl ParseError {
+    /// Posición donde se detectó el error.
+    pub fn pos(&self) -> Position {
+        match self {
+            ParseError::UnexpectedToken { pos, .. } => *pos,
+            ParseError::UnexpectedEOF(pos) | ParseError::InvalidExpression(pos) => *pos,
+            ParseError::Lex(err) => err.pos(),
+        }
+    }
+
+    /// Código estable del error, usado en los diagnósticos (`E01xx`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0101",
+            ParseError::UnexpectedEOF(_) => "E0102",
+            ParseError::InvalidExpression(_) => "E0103",
+            ParseError::Lex(err) => err.code(),
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
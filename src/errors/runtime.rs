@@ -0,0 +1,129 @@
+use crate::utils::Span;
+use std::fmt;
+
+/// Mensajes de los errores de ejecución atrapables, iguales en el intérprete
+/// y en el código generado (los lee `e.message` en un `catch`).
+pub const INDEX_MESSAGE: &str = "index out of bounds";
+pub const MATCH_MESSAGE: &str = "no match case";
+pub const DISPATCH_MESSAGE: &str = "method not implemented by the value";
+pub const KEY_MESSAGE: &str = "key not found";
+
+pub fn cast_message(target: &str) -> String {
+    format!("invalid cast to '{}'", target)
+}
+
+/// Error al evaluar un programa ya chequeado con el intérprete.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    IndexOutOfBounds { index: f64, len: usize, span: Span },
+    /// `v[a:b]` con `a > b`. Se atrapa como un `IndexError` con el mismo
+    /// mensaje que se muestra sin atraparlo.
+    ReversedSlice { start: f64, end: f64, span: Span },
+    InvalidCast { found: String, target: String, span: Span },
+    /// `parse_number()` sobre un texto que no es un número. Se atrapa como
+    /// un `CastError`, igual que un `as Number` inválido.
+    InvalidNumber { text: String, span: Span },
+    /// `get` de una clave que el diccionario no tiene. Se atrapa como un `KeyError`.
+    KeyNotFound { key: String, span: Span },
+    OperationNotDefined(String, String, Span), // Operador, Tipo del valor
+    NotCallable(String, Span),
+    MemberNotFound(String, String, Span), // Miembro, Tipo
+    UndefinedName(String, Span),
+    BaseOutsideMethod(Span),
+    StackOverflow(Span),
+    /// Excepción (de `throw` o de un fallo convertido en `Error`) que ningún
+    /// `try` atrapó. El valor lanzado lo guarda el intérprete.
+    Uncaught { ty: String, message: String, span: Span },
+    /// `break`, `continue` o `return` camino de su bucle o función; el salto
+    /// lo guarda el intérprete. En un programa chequeado nunca sale de `run`.
+    Jump(Span),
+    /// No se pudo escribir la salida del programa.
+    Output(String),
+}
+
+impl RuntimeError {
+    /// Rango de la expresión que falló (vacío para errores de salida).
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::IndexOutOfBounds { span, .. }
+            | RuntimeError::ReversedSlice { span, .. }
+            | RuntimeError::InvalidCast { span, .. }
+            | RuntimeError::InvalidNumber { span, .. }
+            | RuntimeError::KeyNotFound { span, .. }
+            | RuntimeError::OperationNotDefined(_, _, span)
+            | RuntimeError::NotCallable(_, span)
+            | RuntimeError::MemberNotFound(_, _, span)
+            | RuntimeError::UndefinedName(_, span)
+            | RuntimeError::BaseOutsideMethod(span)
+            | RuntimeError::StackOverflow(span)
+            | RuntimeError::Uncaught { span, .. }
+            | RuntimeError::Jump(span) => *span,
+            RuntimeError::Output(_) => Span::default(),
+        }
+    }
+
+    /// Código estable del error, usado en los diagnósticos (`E03xx`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::IndexOutOfBounds { .. } => "E0301",
+            RuntimeError::InvalidCast { .. } => "E0302",
+            RuntimeError::OperationNotDefined(..) => "E0303",
+            RuntimeError::NotCallable(..) => "E0304",
+            RuntimeError::MemberNotFound(..) => "E0305",
+            RuntimeError::UndefinedName(..) => "E0306",
+            RuntimeError::BaseOutsideMethod(_) => "E0307",
+            RuntimeError::StackOverflow(_) => "E0308",
+            RuntimeError::Output(_) => "E0309",
+            RuntimeError::Uncaught { .. } => "E0310",
+            RuntimeError::Jump(_) => "E0311",
+            RuntimeError::InvalidNumber { .. } => "E0312",
+            RuntimeError::KeyNotFound { .. } => "E0313",
+            RuntimeError::ReversedSlice { .. } => "E0314",
+        }
+    }
+
+    /// Tipo del prelude y mensaje con los que un `try` atrapa este error, o
+    /// `None` si no es atrapable.
+    pub fn exception(&self) -> Option<(&str, String)> {
+        match self {
+            RuntimeError::IndexOutOfBounds { .. } => Some(("IndexError", INDEX_MESSAGE.to_string())),
+            RuntimeError::ReversedSlice { .. } => Some(("IndexError", self.to_string())),
+            RuntimeError::InvalidCast { target, .. } => Some(("CastError", cast_message(target))),
+            RuntimeError::InvalidNumber { .. } => Some(("CastError", cast_message("Number"))),
+            RuntimeError::KeyNotFound { .. } => Some(("KeyError", KEY_MESSAGE.to_string())),
+            RuntimeError::Uncaught { ty, message, .. } => Some((ty, message.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::IndexOutOfBounds { index, len, .. } => {
+                write!(f, "index {} out of bounds for a value of size {}", index, len)
+            }
+            RuntimeError::ReversedSlice { start, end, .. } => {
+                write!(f, "slice start {} is greater than end {}", start, end)
+            }
+            RuntimeError::InvalidCast { found, target, .. } => {
+                write!(f, "invalid cast: a value of type '{}' is not a '{}'", found, target)
+            }
+            RuntimeError::InvalidNumber { text, .. } => write!(f, "'{}' is not a valid number", text),
+            RuntimeError::KeyNotFound { key, .. } => write!(f, "key '{}' not found in the map", key),
+            RuntimeError::OperationNotDefined(op, ty, _) => {
+                write!(f, "operation '{}' is not defined for a value of type '{}'", op, ty)
+            }
+            RuntimeError::NotCallable(ty, _) => write!(f, "a value of type '{}' cannot be called", ty),
+            RuntimeError::MemberNotFound(member, ty, _) => {
+                write!(f, "'{}' has no member '{}' at runtime", ty, member)
+            }
+            RuntimeError::UndefinedName(name, _) => write!(f, "'{}' is not defined at runtime", name),
+            RuntimeError::BaseOutsideMethod(_) => write!(f, "'base' used outside of a method"),
+            RuntimeError::StackOverflow(_) => write!(f, "stack overflow: too many nested calls"),
+            RuntimeError::Output(e) => write!(f, "could not write the program output: {}", e),
+            RuntimeError::Uncaught { ty, message, .. } => write!(f, "uncaught {}: {}", ty, message),
+            RuntimeError::Jump(_) => write!(f, "'break', 'continue' or 'return' outside of its loop or function"),
+        }
+    }
+}
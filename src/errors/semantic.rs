@@ -1,26 +1,204 @@
-use crate::utils::Position;
+use crate::utils::{Position, Span};
+use std::fmt;
 
 #[derive(Debug, Clone)]
 pub enum SemanticError {
-    TypeDefined(String),
-    FunctionDefined(String),
-    TypeNotFound(String),
-    FunctionNotFound(String, usize), // Nombre, Cantidad de argumentos
+    TypeDefined(String, Position),
+    FunctionDefined(String, Position),
+    TypeNotFound(String, Position),
+    FunctionNotFound(String, usize, Position), // Nombre, Cantidad de argumentos
     UndefinedFunction(String, Position),
-    VariableNotFound(String),
+    VariableNotFound(String, Position),
     UndefinedVariable(String, Position),
-    OperationNotDefined(String, String), // Operador, Tipo
-    TypeMismatch { expected: String, found: String, pos: Position },
+    OperationNotDefined(String, String, Position), // Operador, Tipo
+    TypeMismatch { expected: String, found: String, span: Span },
     ArgumentMismatch { expected: usize, found: usize, pos: Position },
-    AttributeDefined(String),
-    MethodDefined(String),
-    MethodNotFound(String),
-    SignatureMismatch(String),
-    CircularInheritance(String),
-    ArgumentCountMismatch(String, usize, usize), // Metodo/Func, Esperado, Encontrado
-    AccessingPrivateMember(String),
-    NotAProtocol(String),
-    ProtocolMismatch(String, String), // Tipo, Protocolo
-    SelfReference, // 'self' usado fuera de un método
-    GenericError(String),
+    AttributeDefined(String, Position),
+    MethodDefined(String, Position),
+    MethodNotFound(String, Position),
+    SignatureMismatch(String, Position),
+    CircularInheritance(String, Position),
+    ArgumentCountMismatch(String, usize, usize, Position), // Metodo/Func, Esperado, Encontrado
+    AccessingPrivateMember(String, Position),
+    NotAProtocol(String, Position),
+    ProtocolMismatch(String, String, Position), // Tipo, Protocolo
+    SelfReference(Position), // 'self' usado fuera de un método
+    GenericError(String, Position),
+    TypeArgumentCountMismatch(String, usize, usize, Position), // Tipo genérico, Esperado, Encontrado
+    BoundNotSatisfied { ty: String, param: String, bound: String, pos: Position },
+    /// La inferencia no pudo elegir un único tipo para un parámetro, retorno o atributo sin anotar
+    AmbiguousType { name: String, candidates: Vec<String>, pos: Position },
+    /// Un `match` sin `default` cuyos casos no cubren todos los valores posibles
+    NonExhaustiveMatch { missing: Vec<String>, pos: Position },
+    /// `break` o `continue` fuera de un bucle, o `return` fuera de una función o método
+    MisplacedJump { keyword: String, pos: Position },
+    /// El iterable de un `for` o de una comprensión no tiene `next(): Boolean` y `current()`
+    NotIterable { ty: String, pos: Position },
+}
+
+impl SemanticError {
+    /// Posición del código fuente a la que se refiere el error.
+    pub fn pos(&self) -> Position {
+        self.span().start
+    }
+
+    /// Rango del código fuente al que se refiere el error. Solo `TypeMismatch`
+    /// conoce la expresión completa; el resto apunta a un único punto.
+    pub fn span(&self) -> Span {
+        let pos = match self {
+            SemanticError::TypeMismatch { span, .. } => return *span,
+            SemanticError::TypeDefined(_, pos)
+            | SemanticError::FunctionDefined(_, pos)
+            | SemanticError::TypeNotFound(_, pos)
+            | SemanticError::FunctionNotFound(_, _, pos)
+            | SemanticError::UndefinedFunction(_, pos)
+            | SemanticError::VariableNotFound(_, pos)
+            | SemanticError::UndefinedVariable(_, pos)
+            | SemanticError::OperationNotDefined(_, _, pos)
+            | SemanticError::ArgumentMismatch { pos, .. }
+            | SemanticError::AttributeDefined(_, pos)
+            | SemanticError::MethodDefined(_, pos)
+            | SemanticError::MethodNotFound(_, pos)
+            | SemanticError::SignatureMismatch(_, pos)
+            | SemanticError::CircularInheritance(_, pos)
+            | SemanticError::ArgumentCountMismatch(_, _, _, pos)
+            | SemanticError::AccessingPrivateMember(_, pos)
+            | SemanticError::NotAProtocol(_, pos)
+            | SemanticError::ProtocolMismatch(_, _, pos)
+            | SemanticError::SelfReference(pos)
+            | SemanticError::GenericError(_, pos)
+            | SemanticError::TypeArgumentCountMismatch(_, _, _, pos)
+            | SemanticError::BoundNotSatisfied { pos, .. }
+            | SemanticError::AmbiguousType { pos, .. }
+            | SemanticError::NonExhaustiveMatch { pos, .. }
+            | SemanticError::MisplacedJump { pos, .. }
+            | SemanticError::NotIterable { pos, .. } => *pos,
+        };
+        Span::point(pos)
+    }
+
+    /// Devuelve el mismo error anclado a `pos`. Lo usan los llamadores de
+    /// `Context`, que resuelve nombres sin conocer su ubicación en el código.
+    pub fn at(mut self, new_pos: Position) -> Self {
+        match &mut self {
+            SemanticError::TypeMismatch { span, .. } => *span = Span::point(new_pos),
+            SemanticError::TypeDefined(_, pos)
+            | SemanticError::FunctionDefined(_, pos)
+            | SemanticError::TypeNotFound(_, pos)
+            | SemanticError::FunctionNotFound(_, _, pos)
+            | SemanticError::UndefinedFunction(_, pos)
+            | SemanticError::VariableNotFound(_, pos)
+            | SemanticError::UndefinedVariable(_, pos)
+            | SemanticError::OperationNotDefined(_, _, pos)
+            | SemanticError::ArgumentMismatch { pos, .. }
+            | SemanticError::AttributeDefined(_, pos)
+            | SemanticError::MethodDefined(_, pos)
+            | SemanticError::MethodNotFound(_, pos)
+            | SemanticError::SignatureMismatch(_, pos)
+            | SemanticError::CircularInheritance(_, pos)
+            | SemanticError::ArgumentCountMismatch(_, _, _, pos)
+            | SemanticError::AccessingPrivateMember(_, pos)
+            | SemanticError::NotAProtocol(_, pos)
+            | SemanticError::ProtocolMismatch(_, _, pos)
+            | SemanticError::SelfReference(pos)
+            | SemanticError::GenericError(_, pos)
+            | SemanticError::TypeArgumentCountMismatch(_, _, _, pos)
+            | SemanticError::BoundNotSatisfied { pos, .. }
+            | SemanticError::AmbiguousType { pos, .. }
+            | SemanticError::NonExhaustiveMatch { pos, .. }
+            | SemanticError::MisplacedJump { pos, .. }
+            | SemanticError::NotIterable { pos, .. } => *pos = new_pos,
+        }
+        self
+    }
+
+    /// Código estable del error, usado en los diagnósticos (`E02xx`).
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemanticError::TypeDefined(..) => "E0201",
+            SemanticError::FunctionDefined(..) => "E0202",
+            SemanticError::TypeNotFound(..) => "E0203",
+            SemanticError::FunctionNotFound(..) => "E0204",
+            SemanticError::UndefinedFunction(..) => "E0205",
+            SemanticError::VariableNotFound(..) => "E0206",
+            SemanticError::UndefinedVariable(..) => "E0207",
+            SemanticError::OperationNotDefined(..) => "E0208",
+            SemanticError::TypeMismatch { .. } => "E0209",
+            SemanticError::ArgumentMismatch { .. } => "E0210",
+            SemanticError::AttributeDefined(..) => "E0211",
+            SemanticError::MethodDefined(..) => "E0212",
+            SemanticError::MethodNotFound(..) => "E0213",
+            SemanticError::SignatureMismatch(..) => "E0214",
+            SemanticError::CircularInheritance(..) => "E0215",
+            SemanticError::ArgumentCountMismatch(..) => "E0216",
+            SemanticError::AccessingPrivateMember(..) => "E0217",
+            SemanticError::NotAProtocol(..) => "E0218",
+            SemanticError::ProtocolMismatch(..) => "E0219",
+            SemanticError::SelfReference(..) => "E0220",
+            SemanticError::GenericError(..) => "E0221",
+            SemanticError::TypeArgumentCountMismatch(..) => "E0222",
+            SemanticError::BoundNotSatisfied { .. } => "E0223",
+            SemanticError::AmbiguousType { .. } => "E0224",
+            SemanticError::NonExhaustiveMatch { .. } => "E0225",
+            SemanticError::MisplacedJump { .. } => "E0226",
+            SemanticError::NotIterable { .. } => "E0227",
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::TypeDefined(name, _) => write!(f, "type '{}' is already defined", name),
+            SemanticError::FunctionDefined(name, _) => write!(f, "function '{}' is already defined", name),
+            SemanticError::TypeNotFound(name, _) => write!(f, "type '{}' not found", name),
+            SemanticError::FunctionNotFound(name, argc, _) => {
+                write!(f, "function '{}' with {} argument(s) not found", name, argc)
+            }
+            SemanticError::UndefinedFunction(name, _) => write!(f, "undefined function '{}'", name),
+            SemanticError::VariableNotFound(name, _) => write!(f, "variable '{}' not found", name),
+            SemanticError::UndefinedVariable(name, _) => write!(f, "undefined variable '{}'", name),
+            SemanticError::OperationNotDefined(op, ty, _) => {
+                write!(f, "operation '{}' is not defined for type '{}'", op, ty)
+            }
+            SemanticError::TypeMismatch { expected, found, .. } => {
+                write!(f, "type mismatch: expected '{}', found '{}'", expected, found)
+            }
+            SemanticError::ArgumentMismatch { expected, found, .. } => {
+                write!(f, "expected {} argument(s), found {}", expected, found)
+            }
+            SemanticError::AttributeDefined(name, _) => write!(f, "attribute '{}' is already defined", name),
+            SemanticError::MethodDefined(name, _) => write!(f, "method '{}' is already defined", name),
+            SemanticError::MethodNotFound(msg, _) => write!(f, "{}", msg),
+            SemanticError::SignatureMismatch(msg, _) => write!(f, "signature mismatch: {}", msg),
+            SemanticError::CircularInheritance(name, _) => write!(f, "circular inheritance involving '{}'", name),
+            SemanticError::ArgumentCountMismatch(name, expected, found, _) => {
+                write!(f, "'{}' expects {} argument(s), found {}", name, expected, found)
+            }
+            SemanticError::AccessingPrivateMember(name, _) => write!(f, "'{}' is private", name),
+            SemanticError::NotAProtocol(name, _) => write!(f, "'{}' is not a protocol", name),
+            SemanticError::ProtocolMismatch(ty, proto, _) => {
+                write!(f, "type '{}' does not implement protocol '{}'", ty, proto)
+            }
+            SemanticError::SelfReference(_) => write!(f, "'self' used outside of a method"),
+            SemanticError::GenericError(msg, _) => write!(f, "{}", msg),
+            SemanticError::TypeArgumentCountMismatch(name, expected, found, _) => {
+                write!(f, "'{}' expects {} type argument(s), found {}", name, expected, found)
+            }
+            SemanticError::BoundNotSatisfied { ty, param, bound, .. } => {
+                write!(f, "type '{}' does not satisfy the bound '{}: {}'", ty, param, bound)
+            }
+            SemanticError::AmbiguousType { name, candidates, .. } => {
+                write!(f, "cannot infer the type of '{}': it could be {}", name, candidates.join(" or "))
+            }
+            SemanticError::NonExhaustiveMatch { missing, .. } => {
+                write!(f, "non-exhaustive match: no case for {}", missing.join(", "))
+            }
+            SemanticError::MisplacedJump { keyword, .. } if keyword == "return" => {
+                write!(f, "'return' outside of a function or method")
+            }
+            SemanticError::MisplacedJump { keyword, .. } => write!(f, "'{}' outside of a loop", keyword),
+            SemanticError::NotIterable { ty, .. } => write!(f, "type '{}' is not iterable", ty),
+        }
+    }
 }
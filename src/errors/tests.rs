@@ -0,0 +1,116 @@
+use super::*;
+use crate::parser::Parser;
+use crate::semantic::check_program;
+use crate::utils::Position;
+
+fn semantic_diagnostics(input: &str) -> Vec<Diagnostic> {
+    let program = Parser::new(input).parse_program().expect("Failed to parse");
+    let errors = check_program(&program).err().expect("Expected semantic errors");
+    errors.iter().map(Diagnostic::from).collect()
+}
+
+#[test]
+fn test_render_snippet_with_caret() {
+    let diag = Diagnostic::error("E0206", "variable 'y' not found", Position { line: 2, column: 7, offset: 0 });
+    let out = diag.render("let x = 1 in\nprint(y);", "main.hulk");
+    let expected = "\
+error[E0206]: variable 'y' not found
+ --> main.hulk:2:7
+  |
+2 | print(y);
+  |       ^
+";
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn test_render_underlines_whole_identifier() {
+    let diag = Diagnostic::error("E0206", "variable 'value' not found", Position { line: 1, column: 7, offset: 0 });
+    let out = diag.render("print(value);", "main.hulk");
+    assert!(out.contains("  |       ^^^^^\n"));
+}
+
+#[test]
+fn test_render_notes_and_help() {
+    let diag = Diagnostic::error("E0209", "type mismatch", Position { line: 1, column: 1, offset: 0 })
+        .with_note("first note")
+        .with_help("try this");
+    let out = diag.render("x", "main.hulk");
+    assert!(out.contains("  = note: first note\n"));
+    assert!(out.ends_with("  = help: try this\n"));
+}
+
+#[test]
+fn test_render_unknown_position_has_no_snippet() {
+    let diag = Diagnostic::error("E0221", "something", Position::default());
+    let out = diag.render("print(1);", "main.hulk");
+    assert!(!out.contains(" | "));
+}
+
+#[test]
+fn test_lex_error_diagnostic() {
+    let err = Parser::new("let s = \"abc in s;").parse_program().unwrap_err();
+    let diag = Diagnostic::from(&err);
+    assert_eq!(diag.code, "E0001");
+    assert_eq!((diag.span.start.line, diag.span.start.column), (1, 9));
+    assert!(diag.help.is_some());
+}
+
+#[test]
+fn test_parse_error_diagnostic() {
+    let err = Parser::new("let x = 1 in (x + 2;").parse_program().unwrap_err();
+    let diag = Diagnostic::from(&err);
+    assert_eq!(diag.code, "E0101");
+    assert!(diag.message.ends_with("found Semicolon"), "{}", diag.message);
+}
+
+#[test]
+fn test_parse_error_shows_expressions_as_source() {
+    let err = Parser::new("1 + 2 := 3;").parse_program().unwrap_err();
+    let diag = Diagnostic::from(&err);
+    assert_eq!(diag.message, "expected identifier, attribute access or indexing, found (1 + 2)");
+
+    let err = Parser::new("print(1) 5;").parse_program().unwrap_err();
+    assert_eq!(Diagnostic::from(&err).message, "expected ';' or end of file, found Number(5.0)");
+}
+
+#[test]
+fn test_semantic_errors_have_codes_and_positions() {
+    let diags = semantic_diagnostics("let x = 1 in print(y);");
+    assert_eq!(diags[0].code, "E0206");
+    assert_eq!((diags[0].span.start.line, diags[0].span.start.column), (1, 20));
+    assert!(diags[0].help.as_deref().unwrap().contains("let y"));
+}
+
+#[test]
+fn test_type_not_found_points_at_declaration() {
+    let diags = semantic_diagnostics("type A inherits Missing {}\nprint(1);");
+    assert_eq!(diags[0].code, "E0203");
+    assert_eq!((diags[0].span.start.line, diags[0].span.start.column), (1, 6));
+}
+
+#[test]
+fn test_instantiation_of_unknown_type() {
+    let diags = semantic_diagnostics("print(new Foo());");
+    assert_eq!(diags[0].code, "E0203");
+    assert_eq!((diags[0].span.start.line, diags[0].span.start.column), (1, 7));
+}
+
+#[test]
+fn test_type_mismatch_underlines_whole_expression() {
+    let source = "if (1 + 22) print(1) else print(2);";
+    let diags = semantic_diagnostics(source);
+    assert_eq!(diags[0].code, "E0209");
+    let out = diags[0].render(source, "main.hulk");
+    assert!(out.contains("  |     ^^^^^^\n"), "{}", out);
+}
+
+#[test]
+fn test_runtime_error_diagnostic() {
+    let span = crate::utils::Span::new(Position::new(1, 9, 8), Position::new(1, 12, 11));
+    let diag = Diagnostic::from(&RuntimeError::IndexOutOfBounds { index: 5.0, len: 2, span });
+    assert_eq!(diag.code, "E0301");
+    let out = diag.render("print(v[5]);", "main.hulk");
+    assert!(out.contains("  |         ^^^\n"), "{}", out);
+    assert!(out.contains("= note: valid indices go from 0 to 1"), "{}", out);
+}
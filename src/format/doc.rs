@@ -0,0 +1,151 @@
+//! Motor de maquetación al estilo de Wadler/Oppen: el formateador describe el
+//! código como un `Doc` con puntos de corte opcionales y `render` decide, grupo
+//! a grupo, si cabe en una línea o hay que partirlo.
+
+/// Espacios por nivel de sangría.
+pub const INDENT: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    Text(String),
+    /// Un espacio si el grupo cabe en la línea; salto de línea si no.
+    Line,
+    /// Nada si el grupo cabe en la línea; salto de línea si no.
+    SoftLine,
+    /// Salto de línea incondicional: obliga a partir el grupo que lo contiene.
+    HardLine,
+    /// Aumenta la sangría de los saltos de `Doc` cuando su grupo se parte.
+    Nest(Box<Doc>),
+    /// Unidad de decisión: se imprime entera en una línea o con todos sus saltos.
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+pub fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+pub fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+pub fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+pub fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+/// Une `docs` intercalando `sep`.
+pub fn join(docs: Vec<Doc>, sep: Doc) -> Doc {
+    let mut out = Vec::with_capacity(docs.len() * 2);
+    for (i, doc) in docs.into_iter().enumerate() {
+        if i > 0 {
+            out.push(sep.clone());
+        }
+        out.push(doc);
+    }
+    Doc::Concat(out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Imprime `doc` intentando no superar `width` columnas.
+///
+/// Un grupo se imprime en una línea si su contenido, hasta el primer salto
+/// obligatorio de un grupo anidado, cabe en el espacio restante. Así
+/// `f(a, { ... })` mantiene los argumentos en línea y solo parte el bloque.
+/// Las sangrías de un grupo en línea no se aplican a los saltos obligatorios
+/// que contenga: el bloque anterior se sangra respecto a la línea de `f`.
+pub fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column = match s.rfind('\n') {
+                    Some(i) => s[i + 1..].chars().count(),
+                    None => column + s.chars().count(),
+                };
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                let trimmed = out.trim_end_matches(' ').len();
+                out.truncate(trimmed);
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::Nest(inner) => {
+                let indent = if mode == Mode::Break { indent + INDENT } else { indent };
+                stack.push((indent, mode, inner));
+            }
+            Doc::Group(inner) => {
+                let remaining = width as isize - column as isize;
+                let mode = if fits(remaining, inner, &stack) { Mode::Flat } else { Mode::Break };
+                stack.push((indent, mode, inner));
+            }
+            Doc::Concat(docs) => {
+                for d in docs.iter().rev() {
+                    stack.push((indent, mode, d));
+                }
+            }
+        }
+    }
+
+    let trimmed = out.trim_end_matches(' ').len();
+    out.truncate(trimmed);
+    out
+}
+
+/// Comprueba si `group` cabe en línea en `width` columnas, contando también
+/// lo que le sigue (`rest`) hasta el siguiente salto de línea.
+fn fits(mut width: isize, group: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    // (modo, dentro de un grupo anidado o ya fuera del grupo medido, doc)
+    let mut work: Vec<(Mode, bool, &Doc)> = vec![(Mode::Flat, false, group)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        if width < 0 {
+            return false;
+        }
+        let (mode, nested, doc) = match work.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, true, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) => match s.find('\n') {
+                Some(i) => return width >= s[..i].chars().count() as isize,
+                None => width -= s.chars().count() as isize,
+            },
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine => return true,
+            // Un salto obligatorio propio impide poner el grupo en línea; uno de
+            // un grupo anidado solo termina la línea que se está midiendo
+            Doc::HardLine => return nested,
+            Doc::Nest(inner) => work.push((mode, nested, inner)),
+            Doc::Group(inner) => work.push((Mode::Flat, true, inner)),
+            Doc::Concat(docs) => {
+                for d in docs.iter().rev() {
+                    work.push((mode, nested, d));
+                }
+            }
+        }
+    }
+}
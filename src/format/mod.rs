@@ -0,0 +1,806 @@
+//! Formateador canónico de código HULK (`hulk fmt`).
+//!
+//! Parsea el archivo, conserva los comentarios que el lexer guarda como trivia
+//! y reimprime el AST con un estilo fijo mediante el motor de `doc`. La salida
+//! es estable: formatear dos veces produce el mismo texto, y el programa
+//! formateado se parsea al mismo AST que el original.
+//!
+//! Los comentarios se reubican en los límites de sentencia más cercanos:
+//! delante de una declaración, miembro, sentencia de bloque o caso de `match`,
+//! al final de su línea, o antes del `}` que cierra el bloque. Un comentario en
+//! mitad de una expresión pasa a la línea anterior a la sentencia que lo contiene.
+
+pub mod doc;
+
+#[cfg(test)]
+mod tests;
+
+use crate::ast::display::{TypeArgs, TypeParams};
+use crate::ast::nodes::*;
+use crate::errors::ParseError;
+use crate::lexer::tokens::Comment;
+use crate::parser::Parser;
+use crate::utils::Spanned;
+use doc::{concat, group, join, nest, text, Doc};
+
+/// Ancho de línea por defecto.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Formatea `source` con el ancho por defecto. Falla si hay errores sintácticos.
+pub fn format_source(source: &str) -> Result<String, Vec<ParseError>> {
+    format_source_with_width(source, DEFAULT_WIDTH)
+}
+
+/// Formatea `source` intentando no superar `width` columnas.
+pub fn format_source_with_width(source: &str, width: usize) -> Result<String, Vec<ParseError>> {
+    let mut parser = Parser::new(source);
+    let (program, errors) = parser.parse_program_recovering();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let doc = Formatter::new(source, parser.comments()).program(&program);
+    let mut out = doc::render(&doc, width);
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+// ─── Precedencias (las mismas que usa el parser) ─────────────────────────────
+
+const OPEN: u8 = 0; // let, if, while, for, lambda: se extienden hasta donde pueden
+const ASSIGN: u8 = 1;
+const OR: u8 = 2;
+const AND: u8 = 3;
+const COMPARISON: u8 = 4;
+const CONCAT: u8 = 5;
+const SUM: u8 = 6;
+const PRODUCT: u8 = 7;
+const UNARY: u8 = 8;
+const POWER: u8 = 9;
+const ATOM: u8 = 10;
+
+fn op_precedence(op: &Op) -> u8 {
+    match op {
+        Op::Or => OR,
+        Op::And => AND,
+        Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge => COMPARISON,
+        Op::Concat | Op::ConcatSpace => CONCAT,
+        Op::Add | Op::Sub => SUM,
+        Op::Mul | Op::Div | Op::Mod => PRODUCT,
+        Op::Pow => POWER,
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Let { .. } | Expr::If { .. } | Expr::While { .. } | Expr::For { .. } | Expr::Lambda { .. }
+        | Expr::Try { .. } | Expr::Throw(_) | Expr::Break(_) | Expr::Continue(_) | Expr::Return(_) => OPEN,
+        Expr::Assignment { .. } | Expr::AttributeAssignment { .. } | Expr::IndexAssignment { .. } => ASSIGN,
+        Expr::Binary(_, op, _) => op_precedence(op),
+        Expr::Is(..) | Expr::As(..) => COMPARISON,
+        Expr::Unary(..) => UNARY,
+        _ => ATOM,
+    }
+}
+
+fn op_symbol(op: &Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::Mod => "%",
+        Op::Pow => "^",
+        Op::Eq => "==",
+        Op::Neq => "!=",
+        Op::Lt => "<",
+        Op::Gt => ">",
+        Op::Le => "<=",
+        Op::Ge => ">=",
+        Op::And => "&",
+        Op::Or => "|",
+        Op::Concat => "@",
+        Op::ConcatSpace => "@@",
+    }
+}
+
+fn unop_symbol(op: &UnOp) -> &'static str {
+    match op {
+        UnOp::Neg => "-",
+        UnOp::Not => "!",
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Lista separada por comas entre `open` y `close` que, si no cabe, pone un
+/// elemento por línea.
+fn delimited(open: &str, items: Vec<Doc>, close: &str) -> Doc {
+    if items.is_empty() {
+        return text(format!("{}{}", open, close));
+    }
+    group(concat(vec![
+        text(open),
+        nest(concat(vec![Doc::SoftLine, join(items, concat(vec![text(","), Doc::Line]))])),
+        Doc::SoftLine,
+        text(close),
+    ]))
+}
+
+/// Separador antes de un cuerpo: un espacio si es un bloque (que abre `{` en
+/// la misma línea) o un salto sangrado opcional en otro caso.
+fn body_separator(body: &Expr, doc: Doc) -> Doc {
+    match body {
+        Expr::Block(_) => concat(vec![text(" "), doc]),
+        _ => nest(concat(vec![Doc::Line, doc])),
+    }
+}
+
+// ─── Secuencias de elementos con comentarios ─────────────────────────────────
+
+/// Elementos de una secuencia (declaraciones, sentencias, miembros...) junto
+/// con si van precedidos de una línea en blanco.
+#[derive(Default)]
+struct Seq {
+    items: Vec<(bool, Doc)>,
+    /// Fin (byte) del último elemento emitido, para detectar líneas en blanco.
+    last_end: Option<usize>,
+}
+
+impl Seq {
+    fn into_doc(self) -> Doc {
+        let mut out = Vec::new();
+        for (i, (blank, doc)) in self.items.into_iter().enumerate() {
+            if i > 0 {
+                out.push(Doc::HardLine);
+                if blank {
+                    out.push(Doc::HardLine);
+                }
+            }
+            out.push(doc);
+        }
+        concat(out)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: &'a [Comment],
+    /// Índice del primer comentario aún no emitido.
+    next_comment: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source: &'a str, comments: &'a [Comment]) -> Self {
+        Formatter { source, comments, next_comment: 0 }
+    }
+
+    // ── Comentarios ────────────────────────────────────────────────────────
+
+    /// Extrae los comentarios pendientes que empiezan antes de `offset`.
+    fn take_comments_before(&mut self, offset: usize) -> &'a [Comment] {
+        let start = self.next_comment;
+        while self.next_comment < self.comments.len() && self.comments[self.next_comment].span.start.offset < offset {
+            self.next_comment += 1;
+        }
+        &self.comments[start..self.next_comment]
+    }
+
+    /// `true` si entre `from` y `to` hay una línea en blanco.
+    fn blank_between(&self, from: Option<usize>, to: usize) -> bool {
+        from.is_some_and(|from| from < to && self.source[from..to].matches('\n').count() >= 2)
+    }
+
+    /// Emite como líneas propias los comentarios anteriores a `offset`.
+    fn leading_comments(&mut self, seq: &mut Seq, offset: usize) {
+        for comment in self.take_comments_before(offset) {
+            let blank = self.blank_between(seq.last_end, comment.span.start.offset);
+            seq.items.push((blank, text(comment.text.clone())));
+            seq.last_end = Some(comment.span.end.offset);
+        }
+    }
+
+    /// Añade un elemento que ocupa `start..end` en el código original.
+    /// Los comentarios internos que ningún bloque anidado reclamó se mueven a
+    /// la línea anterior; los que siguen en la misma línea se dejan al final.
+    fn push_item(&mut self, seq: &mut Seq, start: usize, end: usize, doc: Doc) {
+        let blank = self.blank_between(seq.last_end, start);
+        let interior = self.take_comments_before(end);
+        let mut first = true;
+        for comment in interior {
+            seq.items.push((blank && first, text(comment.text.clone())));
+            first = false;
+        }
+
+        let mut line = vec![doc];
+        let mut last_end = end;
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            let gap = &self.source[last_end..comment.span.start.offset];
+            if gap.chars().any(|c| !c.is_whitespace() && c != ';') || gap.contains('\n') {
+                break;
+            }
+            line.push(text(format!(" {}", comment.text)));
+            last_end = comment.span.end.offset;
+            self.next_comment += 1;
+        }
+        seq.items.push((blank && first, concat(line)));
+        seq.last_end = Some(last_end);
+    }
+
+    // ── Exploración del código fuente ─────────────────────────────────────
+
+    /// Siguiente carácter significativo desde `from`, saltando espacios y comentarios.
+    fn next_significant(&self, mut from: usize) -> Option<(usize, char)> {
+        loop {
+            let (i, c) = self.source[from..].char_indices().find(|(_, c)| !c.is_whitespace())?;
+            let at = from + i;
+            match self.comments.iter().find(|comment| comment.span.start.offset == at) {
+                Some(comment) => from = comment.span.end.offset,
+                None => return Some((at, c)),
+            }
+        }
+    }
+
+    /// Posición del primer `target` significativo a partir de `from`.
+    fn find(&self, mut from: usize, target: char) -> usize {
+        while let Some((at, c)) = self.next_significant(from) {
+            if c == target {
+                return at;
+            }
+            from = at + c.len_utf8();
+        }
+        self.source.len()
+    }
+
+    /// Posición del `}` que cierra un cuerpo cuyo último miembro acaba en `from`.
+    fn closing_brace(&self, mut from: usize) -> usize {
+        while let Some((at, c)) = self.next_significant(from) {
+            if c != ';' {
+                return at;
+            }
+            from = at + 1;
+        }
+        self.source.len()
+    }
+
+    // ── Programa y declaraciones ──────────────────────────────────────────
+
+    fn program(&mut self, program: &Program) -> Doc {
+        let mut seq = Seq::default();
+        for decl in &program.declarations {
+            self.declaration(&mut seq, decl);
+        }
+
+        // El programa principal es un bloque implícito cuando tiene varias
+        // sentencias; un bloque escrito con llaves empieza antes que su primera sentencia
+        let main = &program.expr;
+        let statements = match &main.node {
+            Expr::Block(stmts) => {
+                // Un bloque implícito empieza donde su primera sentencia (o
+                // donde el paréntesis que la abre), nunca en una llave propia
+                let explicit = self.source[main.pos.offset..].starts_with('{')
+                    && stmts.first().is_none_or(|first| first.pos.offset != main.pos.offset);
+                if explicit { None } else { Some(stmts) }
+            }
+            _ => None,
+        };
+        match statements {
+            Some(stmts) => {
+                for stmt in stmts {
+                    self.statement(&mut seq, stmt);
+                }
+            }
+            None if matches!(main.node, Expr::Block(_)) => {
+                self.leading_comments(&mut seq, main.pos.offset);
+                let doc = self.expr(main, true);
+                self.push_item(&mut seq, main.pos.offset, main.end.offset, doc);
+            }
+            None => self.statement(&mut seq, main),
+        }
+
+        self.leading_comments(&mut seq, self.source.len());
+        seq.into_doc()
+    }
+
+    /// Sentencia `expr;` de un bloque o del programa principal.
+    fn statement(&mut self, seq: &mut Seq, stmt: &Spanned<Expr>) {
+        self.leading_comments(seq, stmt.pos.offset);
+        let doc = concat(vec![self.expr(stmt, true), text(";")]);
+        self.push_item(seq, stmt.pos.offset, stmt.end.offset, doc);
+    }
+
+    fn declaration(&mut self, seq: &mut Seq, decl: &Declaration) {
+        match decl {
+            Declaration::Function(f) => {
+                self.leading_comments(seq, f.pos.offset);
+                let doc = concat(vec![text("function "), self.function(f)]);
+                self.push_item(seq, f.pos.offset, f.body.end.offset, doc);
+            }
+            Declaration::Macro(m) => {
+                self.leading_comments(seq, m.pos.offset);
+                let doc = self.macro_decl(m);
+                self.push_item(seq, m.pos.offset, m.body.end.offset, doc);
+            }
+            Declaration::Type(t) => {
+                self.leading_comments(seq, t.pos.offset);
+                let (doc, end) = self.type_decl(t);
+                self.push_item(seq, t.pos.offset, end, doc);
+            }
+            Declaration::Protocol(p) => {
+                self.leading_comments(seq, p.pos.offset);
+                let (doc, end) = self.protocol(p);
+                self.push_item(seq, p.pos.offset, end, doc);
+            }
+            Declaration::Variant(v) => {
+                self.leading_comments(seq, v.pos.offset);
+                let (doc, end) = self.variant(v);
+                self.push_item(seq, v.pos.offset, end, doc);
+            }
+        }
+    }
+
+    /// `nombre(params): T => cuerpo;` o `nombre(params): T { ... }` (sin `function`).
+    fn function(&mut self, f: &FunctionDecl) -> Doc {
+        let mut parts = vec![text(format!("{}{}", f.name, TypeParams(&f.type_params))), params(&f.params)];
+        if let Some(ret) = &f.return_type {
+            parts.push(text(format!(": {}", ret)));
+        }
+        let body = self.expr(&f.body, true);
+        if matches!(f.body.node, Expr::Block(_)) {
+            parts.push(text(" "));
+            parts.push(body);
+        } else {
+            parts.push(text(" =>"));
+            parts.push(nest(concat(vec![Doc::Line, body])));
+            parts.push(text(";"));
+        }
+        group(concat(parts))
+    }
+
+    fn macro_decl(&mut self, m: &MacroDecl) -> Doc {
+        let params = m.params.iter().map(|p| {
+            let (prefix, name, ty) = match p {
+                MacroParam::Normal { name, type_annotation } => ("", name, type_annotation),
+                MacroParam::Symbolic { name, type_annotation } => ("@", name, type_annotation),
+                MacroParam::Placeholder { name, type_annotation } => ("$", name, type_annotation),
+                MacroParam::Body { name, type_annotation } => ("*", name, type_annotation),
+            };
+            text(format!("{}{}: {}", prefix, name, ty))
+        }).collect();
+        let mut parts = vec![text(format!("def {}", m.name)), delimited("(", params, ")")];
+        if let Some(ret) = &m.return_type {
+            parts.push(text(format!(": {}", ret)));
+        }
+        let body = self.expr(&m.body, true);
+        if matches!(m.body.node, Expr::Block(_)) {
+            parts.push(text(" "));
+            parts.push(body);
+        } else {
+            parts.push(text(" =>"));
+            parts.push(nest(concat(vec![Doc::Line, body])));
+            parts.push(text(";"));
+        }
+        group(concat(parts))
+    }
+
+    fn type_decl(&mut self, t: &TypeDecl) -> (Doc, usize) {
+        let mut header = vec![text(format!("type {}{}", t.name, TypeParams(&t.type_params)))];
+        if !t.params.is_empty() {
+            header.push(params(&t.params));
+        }
+        let mut header_end = t.pos.offset + t.name.len();
+        if let Some(parent) = &t.parent {
+            header.push(text(format!(" inherits {}{}", parent.name, TypeArgs(&parent.type_args))));
+            if !parent.args.is_empty() {
+                let args = parent.args.iter().map(|a| self.expr(a, true)).collect();
+                header.push(delimited("(", args, ")"));
+                header_end = parent.args.last().map_or(header_end, |a| a.end.offset);
+            }
+        }
+
+        // Atributos y métodos en el orden en que se escribieron
+        enum Member<'m> {
+            Attribute(&'m Attribute),
+            Method(&'m FunctionDecl),
+        }
+        let mut members: Vec<(usize, Member)> = t.attributes.iter().map(|a| (a.pos.offset, Member::Attribute(a)))
+            .chain(t.methods.iter().map(|m| (m.pos.offset, Member::Method(m))))
+            .collect();
+        members.sort_by_key(|(offset, _)| *offset);
+
+        let mut seq = Seq::default();
+        let mut last_end = self.find(header_end, '{') + 1;
+        for (start, member) in members {
+            self.leading_comments(&mut seq, start);
+            let (doc, end) = match member {
+                Member::Attribute(a) => {
+                    let mut parts = vec![text(a.name.clone())];
+                    if let Some(ty) = &a.type_annotation {
+                        parts.push(text(format!(": {}", ty)));
+                    }
+                    parts.push(text(" ="));
+                    parts.push(nest(concat(vec![Doc::Line, self.expr(&a.init, true)])));
+                    parts.push(text(";"));
+                    (group(concat(parts)), a.init.end.offset)
+                }
+                Member::Method(m) => (self.function(m), m.body.end.offset),
+            };
+            self.push_item(&mut seq, start, end, doc);
+            last_end = end;
+        }
+
+        let close = self.closing_brace(last_end);
+        header.push(text(" "));
+        header.push(self.braced(seq, close));
+        (concat(header), close + 1)
+    }
+
+    fn protocol(&mut self, p: &ProtocolDecl) -> (Doc, usize) {
+        let mut header = format!("protocol {}{}", p.name, TypeParams(&p.type_params));
+        if let Some(parent) = &p.parent {
+            header.push_str(&format!(" extends {}", parent));
+        }
+
+        let mut seq = Seq::default();
+        let mut last_end = self.find(p.pos.offset + p.name.len(), '{') + 1;
+        for m in &p.methods {
+            self.leading_comments(&mut seq, m.pos.offset);
+            let end = self.find(m.pos.offset, ';') + 1;
+            let doc = concat(vec![
+                text(m.name.clone()),
+                params(&m.params),
+                text(format!(": {};", m.return_type)),
+            ]);
+            self.push_item(&mut seq, m.pos.offset, end, doc);
+            last_end = end;
+        }
+
+        let close = self.closing_brace(last_end);
+        (concat(vec![text(header), text(" "), self.braced(seq, close)]), close + 1)
+    }
+
+    /// `variant Nombre = A(...) | B ...;`; si no cabe, un caso por línea.
+    fn variant(&mut self, v: &VariantDecl) -> (Doc, usize) {
+        let cases = v.cases.iter().map(|c| {
+            if c.params.is_empty() { text(c.name.clone()) } else { concat(vec![text(c.name.clone()), params(&c.params)]) }
+        }).collect();
+        let last = v.cases.last().map_or(v.pos.offset, |c| c.pos.offset);
+        let end = self.find(last, ';') + 1;
+        let doc = group(concat(vec![
+            text(format!("variant {} =", v.name)),
+            nest(concat(vec![Doc::Line, join(cases, concat(vec![Doc::Line, text("| ")]))])),
+            text(";"),
+        ]));
+        (doc, end)
+    }
+
+    /// `{`, los elementos de `seq` (uno por línea) y `}`. `close` es la
+    /// posición del `}` original: los comentarios anteriores quedan dentro.
+    fn braced(&mut self, mut seq: Seq, close: usize) -> Doc {
+        self.leading_comments(&mut seq, close);
+        if seq.is_empty() {
+            return text("{}");
+        }
+        group(concat(vec![
+            text("{"),
+            nest(concat(vec![Doc::HardLine, seq.into_doc()])),
+            Doc::HardLine,
+            text("}"),
+        ]))
+    }
+
+    // ── Expresiones ───────────────────────────────────────────────────────
+
+    /// Subexpresión que necesita al menos precedencia `min`. `rightmost` indica
+    /// que nada la sigue hasta un delimitador, de modo que un `let`, `if`,
+    /// `while`, `for` o lambda puede ir sin paréntesis aunque se extienda.
+    fn operand(&mut self, e: &Spanned<Expr>, min: u8, rightmost: bool) -> Doc {
+        let prec = precedence(&e.node);
+        if prec >= min || (rightmost && prec == OPEN) {
+            self.expr(e, rightmost)
+        } else {
+            concat(vec![text("("), self.expr(e, true), text(")")])
+        }
+    }
+
+    fn args(&mut self, args: &[Spanned<Expr>]) -> Doc {
+        let docs = args.iter().map(|a| self.expr(a, true)).collect();
+        delimited("(", docs, ")")
+    }
+
+    fn expr(&mut self, e: &Spanned<Expr>, rightmost: bool) -> Doc {
+        match &e.node {
+            Expr::Number(n) => text(format!("{}", n)),
+            Expr::String(s) => text(string_literal(s)),
+            Expr::Boolean(b) => text(b.to_string()),
+            Expr::Identifier(name) => text(name.clone()),
+            Expr::Rand => text("rand()"),
+            Expr::PI => text("PI"),
+            Expr::E => text("E"),
+            Expr::Error => text("<error>"),
+
+            Expr::Binary(left, op, right) => {
+                let prec = op_precedence(op);
+                let (left_min, right_min) = if *op == Op::Pow { (ATOM, UNARY) } else { (prec, prec + 1) };
+                group(concat(vec![
+                    self.operand(left, left_min, false),
+                    text(format!(" {}", op_symbol(op))),
+                    nest(concat(vec![Doc::Line, self.operand(right, right_min, rightmost)])),
+                ]))
+            }
+            Expr::Unary(op, operand) => concat(vec![text(unop_symbol(op)), self.operand(operand, UNARY, rightmost)]),
+            Expr::Is(inner, ty) => concat(vec![self.operand(inner, COMPARISON, false), text(format!(" is {}", ty))]),
+            Expr::As(inner, ty) => concat(vec![self.operand(inner, COMPARISON, false), text(format!(" as {}", ty))]),
+
+            Expr::If { .. } => self.if_chain(e),
+            Expr::While { cond, body } => {
+                let cond = self.expr(cond, true);
+                let body_doc = self.expr(body, true);
+                group(concat(vec![text("while ("), cond, text(")"), body_separator(&body.node, body_doc)]))
+            }
+            Expr::For { var, iterable, body } => {
+                let iterable = self.expr(iterable, true);
+                let body_doc = self.expr(body, true);
+                group(concat(vec![
+                    text(format!("for ({} in ", var)),
+                    iterable,
+                    text(")"),
+                    body_separator(&body.node, body_doc),
+                ]))
+            }
+            Expr::Block(stmts) => {
+                let mut seq = Seq::default();
+                for stmt in stmts {
+                    self.statement(&mut seq, stmt);
+                }
+                let close = e.end.offset.saturating_sub(1);
+                self.braced(seq, close)
+            }
+            Expr::Let { bindings, body } => {
+                let bindings = bindings.iter().map(|(name, ty, init)| {
+                    let head = match ty {
+                        Some(ty) => format!("{}: {} =", name, ty),
+                        None => format!("{} =", name),
+                    };
+                    group(concat(vec![text(head), nest(concat(vec![Doc::Line, self.expr(init, true)]))]))
+                }).collect();
+                let body_doc = self.expr(body, true);
+                group(concat(vec![
+                    text("let "),
+                    nest(group(join(bindings, concat(vec![text(","), Doc::Line])))),
+                    text(" in"),
+                    body_separator(&body.node, body_doc),
+                ]))
+            }
+            Expr::Assignment { target, value } => group(concat(vec![
+                text(format!("{} :=", target)),
+                nest(concat(vec![Doc::Line, self.operand(value, OR, rightmost)])),
+            ])),
+            Expr::AttributeAssignment { obj, attribute, value } => group(concat(vec![
+                self.operand(obj, ATOM, false),
+                text(format!(".{} :=", attribute)),
+                nest(concat(vec![Doc::Line, self.operand(value, OR, rightmost)])),
+            ])),
+
+            Expr::Call { func, args } => {
+                // `f(a) { ... }`: el bloque final se escribe fuera de los paréntesis
+                // (forma habitual de las macros); `print` no la admite
+                match args.split_last() {
+                    Some((last, rest)) if matches!(last.node, Expr::Block(_)) && func != "print" => {
+                        let rest = self.args(rest);
+                        let block = self.expr(last, true);
+                        concat(vec![text(func.clone()), rest, text(" "), block])
+                    }
+                    _ => concat(vec![text(func.clone()), self.args(args)]),
+                }
+            }
+            Expr::BaseCall { args } => concat(vec![text("base"), self.args(args)]),
+            Expr::Instantiation { ty, type_args, args } => {
+                concat(vec![text(format!("new {}{}", ty, TypeArgs(type_args))), self.args(args)])
+            }
+            Expr::MethodCall { obj, method, args } => concat(vec![
+                self.operand(obj, ATOM, false),
+                text(format!(".{}", method)),
+                self.args(args),
+            ]),
+            Expr::AttributeAccess { obj, attribute } => {
+                concat(vec![self.operand(obj, ATOM, false), text(format!(".{}", attribute))])
+            }
+            Expr::Indexing { obj, index } => concat(vec![
+                self.operand(obj, ATOM, false),
+                text("["),
+                self.expr(index, true),
+                text("]"),
+            ]),
+            Expr::IndexAssignment { obj, index, value } => group(concat(vec![
+                self.operand(obj, ATOM, false),
+                text("["),
+                self.expr(index, true),
+                text("] :="),
+                nest(concat(vec![Doc::Line, self.operand(value, OR, rightmost)])),
+            ])),
+            Expr::Slice { obj, start, end } => {
+                let mut parts = vec![self.operand(obj, ATOM, false), text("[")];
+                parts.extend(start.as_ref().map(|s| self.expr(s, true)));
+                parts.push(text(":"));
+                parts.extend(end.as_ref().map(|e| self.expr(e, true)));
+                parts.push(text("]"));
+                concat(parts)
+            }
+
+            Expr::Sqrt(x) => concat(vec![text("sqrt"), self.args(std::slice::from_ref(x))]),
+            Expr::Sin(x) => concat(vec![text("sin"), self.args(std::slice::from_ref(x))]),
+            Expr::Cos(x) => concat(vec![text("cos"), self.args(std::slice::from_ref(x))]),
+            Expr::Exp(x) => concat(vec![text("exp"), self.args(std::slice::from_ref(x))]),
+            Expr::Log(base, x) => {
+                let docs = vec![self.expr(base, true), self.expr(x, true)];
+                concat(vec![text("log"), delimited("(", docs, ")")])
+            }
+
+            Expr::Lambda { params: ps, return_type, body } => {
+                let mut parts = vec![params(ps)];
+                if let Some(ret) = return_type {
+                    parts.push(text(format!(": {}", ret)));
+                }
+                parts.push(text(" =>"));
+                let body_doc = self.expr(body, true);
+                parts.push(body_separator(&body.node, body_doc));
+                group(concat(parts))
+            }
+
+            Expr::VectorLiteral(items) => {
+                let docs = items.iter().map(|i| self.expr(i, true)).collect();
+                delimited("[", docs, "]")
+            }
+            Expr::MapLiteral(entries) => {
+                let docs = entries.iter()
+                    .map(|(k, v)| concat(vec![self.expr(k, true), text(" -> "), self.expr(v, true)]))
+                    .collect();
+                delimited("{", docs, "}")
+            }
+            Expr::VectorGenerator { expr, var, iterable } => group(concat(vec![
+                text("["),
+                nest(concat(vec![
+                    Doc::SoftLine,
+                    self.operand(expr, AND, false),
+                    text(format!(" | {} in", var)),
+                    Doc::Line,
+                    self.expr(iterable, true),
+                ])),
+                Doc::SoftLine,
+                text("]"),
+            ])),
+
+            Expr::Match { expr, cases, default } => {
+                let scrutinee = self.expr(expr, true);
+                let mut seq = Seq::default();
+                for case in cases {
+                    let start = case.expr.pos.offset;
+                    self.leading_comments(&mut seq, start);
+                    let head = match &case.guard {
+                        Some(guard) => concat(vec![
+                            text(format!("case {} if ", pattern(&case.pattern))),
+                            self.expr(guard, true),
+                            text(" =>"),
+                        ]),
+                        None => text(format!("case {} =>", pattern(&case.pattern))),
+                    };
+                    let doc = group(concat(vec![
+                        head,
+                        nest(concat(vec![Doc::Line, self.expr(&case.expr, true)])),
+                        text(";"),
+                    ]));
+                    self.push_item(&mut seq, start, case.expr.end.offset, doc);
+                }
+                if let Some(default) = default {
+                    self.leading_comments(&mut seq, default.pos.offset);
+                    let doc = group(concat(vec![
+                        text("default =>"),
+                        nest(concat(vec![Doc::Line, self.expr(default, true)])),
+                        text(";"),
+                    ]));
+                    self.push_item(&mut seq, default.pos.offset, default.end.offset, doc);
+                }
+                let close = e.end.offset.saturating_sub(1);
+                concat(vec![text("match ("), scrutinee, text(") "), self.braced(seq, close)])
+            }
+
+            Expr::Try { body, catches } => {
+                let body_doc = self.expr(body, true);
+                let mut parts = vec![text("try"), body_separator(&body.node, body_doc)];
+                let mut prev = &body.node;
+                for c in catches {
+                    parts.push(if matches!(prev, Expr::Block(_)) { text(" ") } else { Doc::Line });
+                    parts.push(match &c.ty {
+                        Some(ty) => text(format!("catch ({}: {})", c.var, ty)),
+                        None => text(format!("catch ({})", c.var)),
+                    });
+                    let doc = self.expr(&c.body, true);
+                    parts.push(body_separator(&c.body.node, doc));
+                    prev = &c.body.node;
+                }
+                group(concat(parts))
+            }
+            Expr::Throw(value) | Expr::Return(value) => {
+                let keyword = if matches!(&e.node, Expr::Throw(_)) { "throw" } else { "return" };
+                group(concat(vec![text(keyword), nest(concat(vec![Doc::Line, self.expr(value, true)]))]))
+            }
+            Expr::Break(value) | Expr::Continue(value) => {
+                let keyword = if matches!(&e.node, Expr::Break(_)) { "break" } else { "continue" };
+                match value {
+                    Some(value) => group(concat(vec![text(keyword), nest(concat(vec![Doc::Line, self.expr(value, true)]))])),
+                    None => text(keyword),
+                }
+            }
+        }
+    }
+
+    /// `if (c) a elif (d) b else c`: los `if` anidados en la rama `else` se
+    /// escriben como `elif`. Si una rama es un bloque, `elif`/`else` siguen a su `}`.
+    fn if_chain(&mut self, e: &Spanned<Expr>) -> Doc {
+        let mut parts = Vec::new();
+        let mut current = e;
+        let mut keyword = "if";
+        while let Expr::If { cond, then_expr, else_expr } = &current.node {
+            let cond = self.expr(cond, true);
+            let then_doc = self.expr(then_expr, true);
+            parts.push(text(format!("{} (", keyword)));
+            parts.push(cond);
+            parts.push(text(")"));
+            parts.push(body_separator(&then_expr.node, then_doc));
+            parts.push(if matches!(then_expr.node, Expr::Block(_)) { text(" ") } else { Doc::Line });
+            keyword = "elif";
+            current = else_expr;
+        }
+        let else_doc = self.expr(current, true);
+        parts.push(text("else"));
+        parts.push(body_separator(&current.node, else_doc));
+        group(concat(parts))
+    }
+}
+
+fn params(params: &[Param]) -> Doc {
+    let docs = params.iter().map(|p| match &p.type_annotation {
+        Some(ty) => text(format!("{}: {}", p.name, ty)),
+        None => text(p.name.clone()),
+    }).collect();
+    delimited("(", docs, ")")
+}
+
+/// Patrón de un `case`. Los patrones binarios van siempre entre paréntesis.
+fn pattern(p: &Pattern) -> String {
+    match p {
+        Pattern::Literal(Expr::String(s)) => string_literal(s),
+        Pattern::Literal(Expr::Number(n)) => format!("{}", n),
+        Pattern::Literal(Expr::Boolean(b)) => b.to_string(),
+        Pattern::Literal(other) => format!("{}", other),
+        Pattern::Variable { name, type_annotation: Some(ty) } => format!("{}: {}", name, ty),
+        Pattern::Variable { name, type_annotation: None } => name.clone(),
+        Pattern::Binary { left, op, right } => format!("({} {} {})", pattern(left), op_symbol(op), pattern(right)),
+        Pattern::Unary { op, operand } => format!("{}{}", unop_symbol(op), pattern(operand)),
+        Pattern::Call { func, args } => {
+            let args: Vec<String> = args.iter().map(pattern).collect();
+            format!("{}({})", func, args.join(", "))
+        }
+        Pattern::Wildcard => "_".to_string(),
+    }
+}
@@ -0,0 +1,242 @@
+use super::*;
+use crate::ast::walk;
+use crate::utils::Position;
+
+/// Programa sin posiciones, para comparar ASTs de textos distintos.
+fn without_spans(source: &str) -> Program {
+    fn strip_expr(expr: &mut Spanned<Expr>) {
+        expr.pos = Position::default();
+        expr.end = Position::default();
+        walk::children_mut(&mut expr.node).into_iter().for_each(strip_expr);
+    }
+    let mut program = Parser::new(source).parse_program().expect("el texto debe parsear");
+    for decl in &mut program.declarations {
+        match decl {
+            Declaration::Function(f) => f.pos = Position::default(),
+            Declaration::Macro(m) => m.pos = Position::default(),
+            Declaration::Protocol(p) => {
+                p.pos = Position::default();
+                p.methods.iter_mut().for_each(|m| m.pos = Position::default());
+            }
+            Declaration::Type(t) => {
+                t.pos = Position::default();
+                t.attributes.iter_mut().for_each(|a| a.pos = Position::default());
+                t.methods.iter_mut().for_each(|m| m.pos = Position::default());
+            }
+            Declaration::Variant(v) => {
+                v.pos = Position::default();
+                v.cases.iter_mut().for_each(|c| c.pos = Position::default());
+            }
+        }
+    }
+    walk::root_exprs_mut(&mut program).into_iter().for_each(strip_expr);
+    program
+}
+
+/// Formatea `input`, comprueba que el resultado es `expected`, que es estable
+/// y que describe el mismo programa.
+fn assert_formats(input: &str, expected: &str) {
+    let formatted = format_source(input).unwrap();
+    assert_eq!(formatted, expected);
+    assert_eq!(format_source(&formatted).unwrap(), formatted, "el formato no es idempotente");
+    assert_eq!(without_spans(&formatted), without_spans(input), "el formato cambió el AST");
+}
+
+#[test]
+fn normalizes_spacing_and_semicolons() {
+    assert_formats("let x=1,y:Number=2 in print(x+y*2)", "let x = 1, y: Number = 2 in print(x + y * 2);\n");
+    assert_formats("print(1);print(2)", "print(1);\nprint(2);\n");
+    assert_formats("", "");
+}
+
+#[test]
+fn keeps_only_needed_parentheses() {
+    assert_formats("((a + b)) * (c - (d / e));", "(a + b) * (c - d / e);\n");
+    assert_formats("a - (b - c); (a - b) - c;", "a - (b - c);\na - b - c;\n");
+    assert_formats("(a ^ b) ^ c; a ^ (b ^ c); (-a) ^ 2; -(a ^ 2);", "(a ^ b) ^ c;\na ^ b ^ c;\n(-a) ^ 2;\n-a ^ 2;\n");
+    assert_formats("x + (let y = 1 in y); (let y = 1 in y) + x;", "x + let y = 1 in y;\n(let y = 1 in y) + x;\n");
+    assert_formats("(if (c) a else b).m(); (x as T).y; !(a is T);", "(if (c) a else b).m();\n(x as T).y;\n!(a is T);\n");
+}
+
+#[test]
+fn formats_declarations() {
+    assert_formats(
+        "protocol P extends Q { m(x:Number):Boolean; } type A(x) inherits B(x,1) { f() => x; a = 1; function g() { a; } } function h(x) => x;",
+        "\
+protocol P extends Q {
+    m(x: Number): Boolean;
+}
+type A(x) inherits B(x, 1) {
+    f() => x;
+    a = 1;
+    g() {
+        a;
+    }
+}
+function h(x) => x;
+",
+    );
+    assert_formats("type E {} def twice(*body: Object) => { body; body; }", "type E {}\ndef twice(*body: Object) {\n    body;\n    body;\n}\n");
+    assert_formats("variant T=Leaf|Node(l:T,r:T);", "variant T = Leaf | Node(l: T, r: T);\n");
+    assert_formats(
+        "variant Command = Move(dx: Number, dy: Number) | Rotate(degrees: Number) | Scale(factor: Number) | Reset;",
+        "variant Command =\n    Move(dx: Number, dy: Number)\n    | Rotate(degrees: Number)\n    | Scale(factor: Number)\n    | Reset;\n",
+    );
+}
+
+#[test]
+fn writes_elif_chains_and_trailing_blocks() {
+    assert_formats(
+        "if (a) { x; } else if (b) y else { z; }",
+        "if (a) {\n    x;\n} elif (b) y else {\n    z;\n};\n",
+    );
+    assert_formats("repeat(3, { print(1); })", "repeat(3) {\n    print(1);\n};\n");
+}
+
+#[test]
+fn breaks_long_lines() {
+    let input = "function f(a) => g(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccc, d);";
+    assert_formats(
+        input,
+        "\
+function f(a) =>
+    g(aaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccc, d);
+",
+    );
+
+    let narrow = format_source_with_width(input, 40).unwrap();
+    assert_eq!(
+        narrow,
+        "\
+function f(a) =>
+    g(
+        aaaaaaaaaaaaaaaaaaaa,
+        bbbbbbbbbbbbbbbbbbbbbb,
+        cccccccccccccccccccccc,
+        d
+    );
+"
+    );
+    assert!(narrow.lines().all(|l| l.len() <= 40));
+}
+
+#[test]
+fn preserves_comments() {
+    let input = "\
+// cabecera
+
+
+/* bloque */ function f(x) {   // tras la llave
+    x;  // tras x
+    // antes del cierre
+}
+type A {
+    a = 1; /* attr */
+
+    // método
+    m() => 2;
+}
+print(f(1 /* uno */));
+// final
+";
+    assert_formats(
+        input,
+        "\
+// cabecera
+
+/* bloque */
+function f(x) {
+    // tras la llave
+    x; // tras x
+    // antes del cierre
+}
+type A {
+    a = 1; /* attr */
+
+    // método
+    m() => 2;
+}
+/* uno */
+print(f(1));
+// final
+",
+    );
+}
+
+#[test]
+fn keeps_explicit_main_block_and_match() {
+    assert_formats("{ print(1); }", "{\n    print(1);\n}\n");
+    assert_formats(
+        "def s(e: Number) => match(e) { case (x: Number + 0) => x; case -1 => 1; default => e; };",
+        "\
+def s(e: Number) => match (e) {
+    case (x: Number + 0) => x;
+    case -1 => 1;
+    default => e;
+};
+",
+    );
+}
+
+#[test]
+fn escapes_strings_and_prints_numbers_canonically() {
+    assert_formats(r#"print("a\"b\\c\n" @@ 1.50 @ 2.0);"#, "print(\"a\\\"b\\\\c\\n\" @@ 1.5 @ 2);\n");
+}
+
+#[test]
+fn rejects_programs_with_syntax_errors() {
+    let errors = format_source("print(1 +);\nlet in").unwrap_err();
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn keeps_type_parameters_and_arguments() {
+    assert_formats(
+        "protocol Cmp<T>{compare(o:T):Number;}\n\ntype Pair<A,B>(a:A,b:B) inherits Base<A>(a){swap():Pair<B,A> =>new Pair<B,A>(self.b,self.a);}\n\nfunction max<T:Cmp<T>>(a:T,b:T):T => a;\n\nprint(1);",
+        "\
+protocol Cmp<T> {
+    compare(o: T): Number;
+}
+
+type Pair<A, B>(a: A, b: B) inherits Base<A>(a) {
+    swap(): Pair<B, A> => new Pair<B, A>(self.b, self.a);
+}
+
+function max<T: Cmp<T>>(a: T, b: T): T => a;
+
+print(1);
+",
+    );
+}
+
+#[test]
+fn formats_try_catch_and_throw() {
+    assert_formats(
+        "let x=try{f(1);}catch(e:IndexError){0;}catch(e)throw e in x;",
+        "let x = try {\n    f(1);\n} catch (e: IndexError) {\n    0;\n} catch (e) throw e in x;\n",
+    );
+}
+
+#[test]
+fn formats_jumps() {
+    assert_formats(
+        "function f(x){while(x>0){if(x==1)break else continue x-1;};return  x;}",
+        "function f(x) {\n    while (x > 0) {\n        if (x == 1) break else continue x - 1;\n    };\n    return x;\n}\n",
+    );
+}
+
+#[test]
+fn formats_map_literals() {
+    assert_formats(
+        "let m={\"a\"->1,\"b\"->2+3} in m.get(\"a\");",
+        "let m = {\"a\" -> 1, \"b\" -> 2 + 3} in m.get(\"a\");\n",
+    );
+}
+
+#[test]
+fn formats_index_assignment_and_slices() {
+    assert_formats(
+        "{v[i+1]:=v[ : 2][0];print(v[1:n]+v[a :]+v[:]);}",
+        "{\n    v[i + 1] := v[:2][0];\n    print(v[1:n] + v[a:] + v[:]);\n}\n",
+    );
+}
@@ -0,0 +1,1165 @@
+//! Intérprete que recorre el AST de un programa ya chequeado. Permite ejecutar
+//! HULK sin LLVM ni clang (lo usa `hulk repl`) y sigue la semántica del código
+//! generado: mismos formatos de `print`, mismas reglas de construcción de
+//! objetos y clausuras que capturan por valor.
+
+mod value;
+
+#[cfg(test)]
+mod tests;
+
+pub use value::{format_number, Closure, Map, Object, Value, Vector};
+
+use crate::ast::nodes::*;
+use crate::errors::RuntimeError;
+use crate::errors::runtime::MATCH_MESSAGE;
+use crate::semantic::types::TypeKind;
+use crate::semantic::Context;
+use crate::utils::{Span, Spanned};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+type Eval<'p> = Result<Value<'p>, RuntimeError>;
+
+/// Máximo de llamadas anidadas antes de abortar con `StackOverflow`.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+/// Pila recomendada para el hilo que ejecute el intérprete con
+/// `MAX_CALL_DEPTH` llamadas anidadas.
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+/// Función o macro de nivel superior.
+struct Callable<'p> {
+    params: Vec<&'p str>,
+    body: &'p Spanned<Expr>,
+}
+
+/// Método en ejecución, necesario para resolver `base(...)`.
+struct MethodFrame<'p> {
+    owner: &'p TypeDecl,
+    method: &'p FunctionDecl,
+    this: Value<'p>,
+    args: Vec<Value<'p>>,
+}
+
+/// Salto pendiente mientras sube como `RuntimeError::Jump`. El valor de
+/// `break` o `continue` es opcional: sin él, el bucle conserva el que tenía.
+enum Jump<'p> {
+    Break(Option<Value<'p>>),
+    Continue(Option<Value<'p>>),
+    Return(Value<'p>),
+}
+
+/// Variables de una llamada: una lista de ámbitos anidados (`let`, `for`...).
+struct Frame<'p> {
+    scopes: Vec<Vec<(&'p str, Value<'p>)>>,
+    method: Option<MethodFrame<'p>>,
+}
+
+impl<'p> Frame<'p> {
+    fn new(vars: Vec<(&'p str, Value<'p>)>) -> Self {
+        Frame { scopes: vec![vars], method: None }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value<'p>> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.iter().rev().find(|(n, _)| *n == name))
+            .map(|(_, v)| v)
+    }
+
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Value<'p>> {
+        self.scopes.iter_mut().rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|(n, _)| *n == name))
+            .map(|(_, v)| v)
+    }
+
+    fn define(&mut self, name: &'p str, value: Value<'p>) {
+        self.scopes.last_mut().expect("frame sin ámbitos").push((name, value));
+    }
+
+    /// Copia de las variables visibles, para las clausuras.
+    fn snapshot(&self) -> Vec<(&'p str, Value<'p>)> {
+        self.scopes.iter().flatten().cloned().collect()
+    }
+}
+
+pub struct Interpreter<'p, W: Write> {
+    program: &'p Program,
+    context: &'p Context,
+    functions: HashMap<&'p str, Callable<'p>>,
+    types: HashMap<&'p str, &'p TypeDecl>,
+    frames: Vec<Frame<'p>>,
+    out: W,
+    rng: u64,
+    /// Valor lanzado con `throw` mientras sube como `RuntimeError::Uncaught`.
+    exception: Option<Value<'p>>,
+    /// Salto de `break`, `continue` o `return` hasta su bucle o llamada.
+    jump: Option<Jump<'p>>,
+}
+
+impl<'p, W: Write> Interpreter<'p, W> {
+    /// Prepara la ejecución de `program`, chequeado con `context`; lo que
+    /// imprima el programa se escribe en `out`.
+    pub fn new(program: &'p Program, context: &'p Context, out: W) -> Self {
+        let mut functions = HashMap::new();
+        let mut types = HashMap::new();
+        for decl in &program.declarations {
+            match decl {
+                Declaration::Function(f) => {
+                    let params = f.params.iter().map(|p| p.name.as_str()).collect();
+                    functions.insert(f.name.as_str(), Callable { params, body: &f.body });
+                }
+                Declaration::Macro(m) => {
+                    let params = m.params.iter().map(macro_param_name).collect();
+                    functions.insert(m.name.as_str(), Callable { params, body: &m.body });
+                }
+                Declaration::Type(t) => {
+                    types.insert(t.name.as_str(), t);
+                }
+                Declaration::Protocol(_) | Declaration::Variant(_) => {}
+            }
+        }
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        Interpreter {
+            program,
+            context,
+            functions,
+            types,
+            frames: vec![Frame::new(Vec::new())],
+            out,
+            rng: seed | 1,
+            exception: None,
+            jump: None,
+        }
+    }
+
+    /// Evalúa la expresión principal del programa y devuelve su valor.
+    pub fn run(&mut self) -> Eval<'p> {
+        let result = self.eval(&self.program.expr);
+        self.out.flush().map_err(|e| RuntimeError::Output(e.to_string()))?;
+        result
+    }
+
+    /// Devuelve el destino de la salida, p. ej. para leer lo que se imprimió.
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    fn frame(&self) -> &Frame<'p> {
+        self.frames.last().expect("pila de llamadas vacía")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame<'p> {
+        self.frames.last_mut().expect("pila de llamadas vacía")
+    }
+
+    /// Ejecuta `f` en un ámbito nuevo de la llamada actual.
+    fn scoped(&mut self, vars: Vec<(&'p str, Value<'p>)>, f: impl FnOnce(&mut Self) -> Eval<'p>) -> Eval<'p> {
+        self.frame_mut().scopes.push(vars);
+        let result = f(self);
+        self.frame_mut().scopes.pop();
+        result
+    }
+
+    /// Ejecuta `f` en una llamada nueva, con `frame` como variables visibles.
+    fn call_with<T>(
+        &mut self,
+        frame: Frame<'p>,
+        span: Span,
+        f: impl FnOnce(&mut Self) -> Result<T, RuntimeError>,
+    ) -> Result<T, RuntimeError> {
+        if self.frames.len() > MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow(span));
+        }
+        self.frames.push(frame);
+        let result = f(self);
+        self.frames.pop();
+        result
+    }
+
+    /// Despacha cada nodo a su propia función: así el marco de pila de `eval`,
+    /// que se repite en cada nivel de recursión, se mantiene pequeño.
+    fn eval(&mut self, expr: &'p Spanned<Expr>) -> Eval<'p> {
+        let span = expr.span();
+        match &expr.node {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::String(s) => Ok(Value::string(s)),
+            Expr::Boolean(b) => Ok(Value::Boolean(*b)),
+            Expr::PI => Ok(Value::Number(std::f64::consts::PI)),
+            Expr::E => Ok(Value::Number(std::f64::consts::E)),
+            Expr::Identifier(name) => self.eval_identifier(name, span),
+            Expr::Binary(left, op, right) => self.eval_binary(left, op, right, span),
+            Expr::Unary(op, operand) => self.eval_unary(op, operand, span),
+            Expr::If { cond, then_expr, else_expr } => {
+                if self.eval_condition(cond)? { self.eval(then_expr) } else { self.eval(else_expr) }
+            }
+            Expr::While { cond, body } => self.eval_while(cond, body),
+            Expr::For { var, iterable, body } => self.eval_for(var, iterable, body, span),
+            Expr::Block(exprs) => self.eval_block(exprs),
+            Expr::Let { bindings, body } => self.eval_let(bindings, body),
+            Expr::Assignment { target, value } => self.eval_assignment(target, value, span),
+            Expr::AttributeAssignment { obj, attribute, value } => {
+                self.eval_attribute_assignment(obj, attribute, value, span)
+            }
+            Expr::Call { func, args } => self.eval_call(func, args, span),
+            Expr::BaseCall { args } => self.eval_base_call(args, span),
+            Expr::MethodCall { obj, method, args } => self.eval_method_call(obj, method, args, span),
+            Expr::AttributeAccess { obj, attribute } => self.eval_attribute_access(obj, attribute, span),
+            Expr::Instantiation { ty, args, .. } => self.eval_instantiation(ty, args, span),
+            Expr::Lambda { params, body, .. } => Ok(self.make_closure(params, body)),
+            Expr::Is(e, ty) => self.eval_is(e, ty),
+            Expr::As(e, ty) => self.eval_as(e, ty, span),
+            Expr::VectorLiteral(elements) => Ok(Value::vector(self.eval_args(elements)?)),
+            Expr::VectorGenerator { expr: item, var, iterable } => self.eval_generator(item, var, iterable, span),
+            Expr::Indexing { obj, index } => self.eval_indexing(obj, index, span),
+            Expr::IndexAssignment { obj, index, value } => self.eval_index_assignment(obj, index, value, span),
+            Expr::Slice { obj, start, end } => self.eval_slice(obj, start.as_deref(), end.as_deref(), span),
+            // Como en el código generado, cada par pasa por `set`: una clave
+            // repetida se queda con el último valor
+            Expr::MapLiteral(entries) => {
+                let map = Map { entries: RefCell::new(Vec::new()), cursor: Cell::new(-1) };
+                for (key, value) in entries {
+                    map.insert(self.eval(key)?, self.eval(value)?);
+                }
+                Ok(Value::Map(Rc::new(map)))
+            }
+            Expr::Sqrt(e) => self.eval_number(e).map(|n| Value::Number(n.sqrt())),
+            Expr::Sin(e) => self.eval_number(e).map(|n| Value::Number(n.sin())),
+            Expr::Cos(e) => self.eval_number(e).map(|n| Value::Number(n.cos())),
+            Expr::Exp(e) => self.eval_number(e).map(|n| Value::Number(n.exp())),
+            Expr::Log(base, x) => self.eval_log(base, x),
+            Expr::Rand => Ok(Value::Number(self.next_random())),
+            Expr::Match { expr: scrutinee, cases, default } => self.eval_match(scrutinee, cases, default, span),
+            Expr::Try { body, catches } => self.eval_try(body, catches),
+            Expr::Throw(value) => self.eval_throw(value, span),
+            Expr::Break(value) => {
+                let value = value.as_deref().map(|v| self.eval(v)).transpose()?;
+                self.jump(Jump::Break(value), span)
+            }
+            Expr::Continue(value) => {
+                let value = value.as_deref().map(|v| self.eval(v)).transpose()?;
+                self.jump(Jump::Continue(value), span)
+            }
+            Expr::Return(value) => {
+                let value = self.eval(value)?;
+                self.jump(Jump::Return(value), span)
+            }
+            Expr::Error => Err(RuntimeError::OperationNotDefined("<error>".into(), "Object".into(), span)),
+        }
+    }
+
+    fn eval_identifier(&self, name: &str, span: Span) -> Eval<'p> {
+        self.frame().lookup(name).cloned().ok_or_else(|| RuntimeError::UndefinedName(name.to_string(), span))
+    }
+
+    fn eval_unary(&mut self, op: &UnOp, operand: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        match (op, self.eval(operand)?) {
+            (UnOp::Neg, Value::Number(n)) => Ok(Value::Number(-n)),
+            (UnOp::Not, Value::Boolean(b)) => Ok(Value::Boolean(!b)),
+            (UnOp::Neg, other) => Err(not_defined("-", &other, span)),
+            (UnOp::Not, other) => Err(not_defined("!", &other, span)),
+        }
+    }
+
+    fn eval_while(&mut self, cond: &'p Spanned<Expr>, body: &'p Spanned<Expr>) -> Eval<'p> {
+        let mut result = Value::Number(0.0);
+        while self.eval_condition(cond)? {
+            let iteration = self.eval(body);
+            if !self.iteration(iteration, &mut result)? {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    fn eval_for(&mut self, var: &'p str, iterable: &'p Spanned<Expr>, body: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let iterable = self.eval(iterable)?;
+        let mut result = Value::Number(0.0);
+        self.iterate(&iterable, span, |this, item| {
+            let iteration = this.scoped(vec![(var, item)], |this| this.eval(body));
+            this.iteration(iteration, &mut result)
+        })?;
+        Ok(result)
+    }
+
+    /// Lleva el resultado de una iteración al valor del bucle: el de la
+    /// iteración, o el de su `continue` o `break`. Devuelve si el bucle sigue.
+    fn iteration(&mut self, iteration: Eval<'p>, result: &mut Value<'p>) -> Result<bool, RuntimeError> {
+        let error = match iteration {
+            Ok(value) => {
+                *result = value;
+                return Ok(true);
+            }
+            Err(error) => error,
+        };
+        let (value, more) = match (&error, self.jump.take()) {
+            (RuntimeError::Jump(_), Some(Jump::Continue(value))) => (value, true),
+            (RuntimeError::Jump(_), Some(Jump::Break(value))) => (value, false),
+            (_, jump) => {
+                self.jump = jump;
+                return Err(error);
+            }
+        };
+        if let Some(value) = value {
+            *result = value;
+        }
+        Ok(more)
+    }
+
+    fn jump(&mut self, jump: Jump<'p>, span: Span) -> Eval<'p> {
+        self.jump = Some(jump);
+        Err(RuntimeError::Jump(span))
+    }
+
+    /// Evalúa el cuerpo de una función, método o lambda: un `return` termina aquí.
+    fn eval_body(&mut self, body: &'p Spanned<Expr>) -> Eval<'p> {
+        match self.eval(body) {
+            Err(RuntimeError::Jump(span)) => match self.jump.take() {
+                Some(Jump::Return(value)) => Ok(value),
+                jump => {
+                    self.jump = jump;
+                    Err(RuntimeError::Jump(span))
+                }
+            },
+            result => result,
+        }
+    }
+
+    fn eval_block(&mut self, exprs: &'p [Spanned<Expr>]) -> Eval<'p> {
+        let mut result = Value::Number(0.0);
+        for e in exprs {
+            result = self.eval(e)?;
+        }
+        Ok(result)
+    }
+
+    fn eval_let(&mut self, bindings: &'p [(String, Option<TypeAnnotation>, Spanned<Expr>)], body: &'p Spanned<Expr>) -> Eval<'p> {
+        self.scoped(Vec::new(), |this| {
+            for (name, _, init) in bindings {
+                let value = this.eval(init)?;
+                this.frame_mut().define(name, value);
+            }
+            this.eval(body)
+        })
+    }
+
+    fn eval_assignment(&mut self, target: &str, value: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let value = self.eval(value)?;
+        let slot = self.frame_mut().lookup_mut(target)
+            .ok_or_else(|| RuntimeError::UndefinedName(target.to_string(), span))?;
+        *slot = value.clone();
+        Ok(value)
+    }
+
+    fn eval_attribute_assignment(
+        &mut self,
+        obj: &'p Spanned<Expr>,
+        attribute: &'p str,
+        value: &'p Spanned<Expr>,
+        span: Span,
+    ) -> Eval<'p> {
+        let target = self.eval(obj)?;
+        let value = self.eval(value)?;
+        let Value::Object(object) = &target else {
+            return Err(RuntimeError::MemberNotFound(attribute.to_string(), target.type_name().to_string(), span));
+        };
+        object.fields.borrow_mut().insert(attribute, value.clone());
+        Ok(value)
+    }
+
+    fn eval_call(&mut self, func: &str, args: &'p [Spanned<Expr>], span: Span) -> Eval<'p> {
+        let args = self.eval_args(args)?;
+        self.call(func, args, span)
+    }
+
+    fn eval_base_call(&mut self, args: &'p [Spanned<Expr>], span: Span) -> Eval<'p> {
+        let args = self.eval_args(args)?;
+        self.call_base(args, span)
+    }
+
+    fn eval_method_call(&mut self, obj: &'p Spanned<Expr>, method: &str, args: &'p [Spanned<Expr>], span: Span) -> Eval<'p> {
+        let receiver = self.eval(obj)?;
+        let args = self.eval_args(args)?;
+        self.call_method(receiver, method, args, span)
+    }
+
+    fn eval_attribute_access(&mut self, obj: &'p Spanned<Expr>, attribute: &str, span: Span) -> Eval<'p> {
+        let target = self.eval(obj)?;
+        let field = match &target {
+            Value::Object(object) => object.fields.borrow().get(attribute).cloned(),
+            _ => None,
+        };
+        field.ok_or_else(|| RuntimeError::MemberNotFound(attribute.to_string(), target.type_name().to_string(), span))
+    }
+
+    fn eval_instantiation(&mut self, ty: &str, args: &'p [Spanned<Expr>], span: Span) -> Eval<'p> {
+        let args = self.eval_args(args)?;
+        if ty == "Map" {
+            return Ok(Value::map(Vec::new()));
+        }
+        self.instantiate(ty, args, span)
+    }
+
+    fn make_closure(&self, params: &'p [Param], body: &'p Spanned<Expr>) -> Value<'p> {
+        Value::Function(Rc::new(Closure { params, body, captured: self.frame().snapshot() }))
+    }
+
+    fn eval_is(&mut self, expr: &'p Spanned<Expr>, ty: &str) -> Eval<'p> {
+        let value = self.eval(expr)?;
+        Ok(Value::Boolean(self.conforms(&value, ty)))
+    }
+
+    fn eval_as(&mut self, expr: &'p Spanned<Expr>, ty: &str, span: Span) -> Eval<'p> {
+        let value = self.eval(expr)?;
+        if self.conforms(&value, ty) {
+            Ok(value)
+        } else {
+            Err(RuntimeError::InvalidCast { found: value.type_name().to_string(), target: ty.to_string(), span })
+        }
+    }
+
+    fn eval_generator(&mut self, item: &'p Spanned<Expr>, var: &'p str, iterable: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let iterable = self.eval(iterable)?;
+        let mut items = Vec::new();
+        self.iterate(&iterable, span, |this, x| {
+            items.push(this.scoped(vec![(var, x)], |this| this.eval(item))?);
+            Ok(true)
+        })?;
+        Ok(Value::vector(items))
+    }
+
+    fn eval_indexing(&mut self, obj: &'p Spanned<Expr>, index: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let target = self.eval(obj)?;
+        let index = self.eval(index)?;
+        match (&target, index) {
+            (Value::Vector(v), Value::Number(i)) => {
+                let items = v.items.borrow();
+                Ok(items[element_index(i, items.len(), span)?].clone())
+            }
+            (Value::Vector(_), other) => Err(not_defined("[]", &other, span)),
+            _ => Err(not_defined("[]", &target, span)),
+        }
+    }
+
+    fn eval_index_assignment(&mut self, obj: &'p Spanned<Expr>, index: &'p Spanned<Expr>, value: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let target = self.eval(obj)?;
+        let index = self.eval(index)?;
+        let value = self.eval(value)?;
+        match (&target, index) {
+            (Value::Vector(v), Value::Number(i)) => {
+                let mut items = v.items.borrow_mut();
+                let pos = element_index(i, items.len(), span)?;
+                items[pos] = value.clone();
+                Ok(value)
+            }
+            (Value::Vector(_), other) => Err(not_defined("[]", &other, span)),
+            _ => Err(not_defined("[]", &target, span)),
+        }
+    }
+
+    /// `v[a:b]` copia los elementos de `a` a `b` (sin incluirlo) en un vector
+    /// nuevo. Los límites que faltan son el principio y el final.
+    fn eval_slice(&mut self, obj: &'p Spanned<Expr>, start: Option<&'p Spanned<Expr>>, end: Option<&'p Spanned<Expr>>, span: Span) -> Eval<'p> {
+        let target = self.eval(obj)?;
+        let Value::Vector(v) = &target else {
+            return Err(not_defined("[:]", &target, span));
+        };
+        let start = start.map(|e| self.eval_number(e)).transpose()?;
+        let end = end.map(|e| self.eval_number(e)).transpose()?;
+        let items = v.items.borrow();
+        let len = items.len();
+        let a = start.map_or(0.0, f64::trunc);
+        let b = end.map_or(len as f64, f64::trunc);
+        for bound in [a, b] {
+            if bound < 0.0 || bound > len as f64 {
+                return Err(RuntimeError::IndexOutOfBounds { index: bound, len, span });
+            }
+        }
+        if a > b {
+            return Err(RuntimeError::ReversedSlice { start: a, end: b, span });
+        }
+        Ok(Value::vector(items[a as usize..b as usize].to_vec()))
+    }
+
+    fn eval_log(&mut self, base: &'p Spanned<Expr>, x: &'p Spanned<Expr>) -> Eval<'p> {
+        let base = self.eval_number(base)?;
+        let x = self.eval_number(x)?;
+        Ok(Value::Number(x.ln() / base.ln()))
+    }
+
+    /// Sin caso que encaje ni `default`, el resultado es 0 como en el código generado.
+    fn eval_match(&mut self, scrutinee: &'p Spanned<Expr>, cases: &'p [MatchCase], default: &'p Option<Box<Spanned<Expr>>>, span: Span) -> Eval<'p> {
+        let value = self.eval(scrutinee)?;
+        for case in cases {
+            let Some(bindings) = self.match_pattern(&case.pattern, &value) else {
+                continue;
+            };
+            let accepted = self.scoped(bindings.clone(), |this| match &case.guard {
+                Some(guard) => this.eval_condition(guard).map(Value::Boolean),
+                None => Ok(Value::Boolean(true)),
+            })?;
+            if matches!(accepted, Value::Boolean(true)) {
+                return self.scoped(bindings, |this| this.eval(&case.expr));
+            }
+        }
+        match default {
+            Some(d) => self.eval(d),
+            None => Err(RuntimeError::Uncaught { ty: "MatchError".into(), message: MATCH_MESSAGE.into(), span }),
+        }
+    }
+
+    /// Evalúa el cuerpo; si falla con un error atrapable, lo convierte en un
+    /// objeto `Error` y ejecuta el primer `catch` cuyo tipo le conforme.
+    fn eval_try(&mut self, body: &'p Spanned<Expr>, catches: &'p [CatchClause]) -> Eval<'p> {
+        let error = match self.eval(body) {
+            Err(error) => error,
+            ok => return ok,
+        };
+        let thrown = match (&error, self.exception.take()) {
+            (RuntimeError::Uncaught { .. }, Some(value)) => value,
+            _ => match error.exception() {
+                Some((ty, message)) => self.error_object(ty, &message, error.span())?,
+                None => return Err(error),
+            },
+        };
+        for c in catches {
+            if self.conforms(&thrown, c.ty.as_deref().unwrap_or("Error")) {
+                return self.scoped(vec![(c.var.as_str(), thrown)], |this| this.eval(&c.body));
+            }
+        }
+        // Ningún manejador lo acepta: sigue subiendo
+        if matches!(error, RuntimeError::Uncaught { .. }) {
+            self.exception = Some(thrown);
+        }
+        Err(error)
+    }
+
+    /// `throw e`: marca la posición en `e` si no la tenía y empieza a subir.
+    fn eval_throw(&mut self, value: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        let thrown = self.eval(value)?;
+        let message = match &thrown {
+            Value::Object(object) => {
+                stamp_position(object, span);
+                match object.fields.borrow().get("message") {
+                    Some(Value::String(s)) => s.to_string(),
+                    _ => String::new(),
+                }
+            }
+            _ => String::new(),
+        };
+        let ty = thrown.type_name().to_string();
+        self.exception = Some(thrown);
+        Err(RuntimeError::Uncaught { ty, message, span })
+    }
+
+    /// Instancia del prelude para un fallo de ejecución ocurrido en `span`.
+    fn error_object(&mut self, ty: &str, message: &str, span: Span) -> Eval<'p> {
+        let object = self.instantiate(ty, vec![Value::string(message)], span)?;
+        if let Value::Object(o) = &object {
+            stamp_position(o, span);
+        }
+        Ok(object)
+    }
+
+    fn eval_args(&mut self, args: &'p [Spanned<Expr>]) -> Result<Vec<Value<'p>>, RuntimeError> {
+        args.iter().map(|a| self.eval(a)).collect()
+    }
+
+    fn eval_number(&mut self, expr: &'p Spanned<Expr>) -> Result<f64, RuntimeError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(n),
+            other => Err(RuntimeError::InvalidCast {
+                found: other.type_name().to_string(),
+                target: "Number".into(),
+                span: expr.span(),
+            }),
+        }
+    }
+
+    /// Condición de `if` / `while`. Los números valen como en el código
+    /// generado: cualquier valor distinto de cero es verdadero.
+    fn eval_condition(&mut self, expr: &'p Spanned<Expr>) -> Result<bool, RuntimeError> {
+        match self.eval(expr)? {
+            Value::Boolean(b) => Ok(b),
+            Value::Number(n) => Ok(n != 0.0),
+            other => Err(RuntimeError::InvalidCast {
+                found: other.type_name().to_string(),
+                target: "Boolean".into(),
+                span: expr.span(),
+            }),
+        }
+    }
+
+    fn eval_binary(&mut self, left: &'p Spanned<Expr>, op: &Op, right: &'p Spanned<Expr>, span: Span) -> Eval<'p> {
+        // `&` y `|` no evalúan el lado derecho si el izquierdo ya decide
+        if matches!(op, Op::And | Op::Or) {
+            let l = self.eval_condition(left)?;
+            if l == (*op == Op::Or) {
+                return Ok(Value::Boolean(l));
+            }
+            return self.eval_condition(right).map(Value::Boolean);
+        }
+
+        let l = self.eval(left)?;
+        let r = self.eval(right)?;
+        match op {
+            Op::Eq => return Ok(Value::Boolean(l.equals(&r))),
+            Op::Neq => return Ok(Value::Boolean(!l.equals(&r))),
+            Op::Concat => return Ok(Value::string(&format!("{}{}", l.concat_text(), r.concat_text()))),
+            Op::ConcatSpace => return Ok(Value::string(&format!("{} {}", l.concat_text(), r.concat_text()))),
+            _ => {}
+        }
+
+        // `v + w` concatena en un vector nuevo
+        if let (Value::Vector(a), Value::Vector(b), Op::Add) = (&l, &r, op) {
+            let items = a.items.borrow().iter().chain(b.items.borrow().iter()).cloned().collect();
+            return Ok(Value::vector(items));
+        }
+
+        // Las cadenas se ordenan byte a byte, como `strcmp`
+        if let (Value::String(a), Value::String(b), Op::Lt | Op::Gt | Op::Le | Op::Ge) = (&l, &r, op) {
+            let order = a.as_bytes().cmp(b.as_bytes());
+            return Ok(Value::Boolean(match op {
+                Op::Lt => order.is_lt(),
+                Op::Gt => order.is_gt(),
+                Op::Le => order.is_le(),
+                _ => order.is_ge(),
+            }));
+        }
+
+        let (a, b) = match (&l, &r) {
+            (Value::Number(a), Value::Number(b)) => (*a, *b),
+            (Value::Number(_), other) | (other, _) => return Err(not_defined(op_symbol(op), other, span)),
+        };
+        Ok(match op {
+            Op::Add => Value::Number(a + b),
+            Op::Sub => Value::Number(a - b),
+            Op::Mul => Value::Number(a * b),
+            Op::Div => Value::Number(a / b),
+            Op::Mod => Value::Number(a % b),
+            Op::Pow => Value::Number(a.powf(b)),
+            Op::Lt => Value::Boolean(a < b),
+            Op::Gt => Value::Boolean(a > b),
+            Op::Le => Value::Boolean(a <= b),
+            Op::Ge => Value::Boolean(a >= b),
+            Op::Eq | Op::Neq | Op::And | Op::Or | Op::Concat | Op::ConcatSpace => unreachable!(),
+        })
+    }
+
+    // ─────────────────────────────────────────────────────────────────────
+    // Llamadas
+    // ─────────────────────────────────────────────────────────────────────
+
+    /// `f(args)`: primero una variable con una función u objeto invocable (como
+    /// en el código generado), luego las funciones declaradas y los builtins.
+    fn call(&mut self, func: &str, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        if func == "print" {
+            let value = args.into_iter().next().unwrap_or(Value::Number(0.0));
+            writeln!(self.out, "{}", value).map_err(|e| RuntimeError::Output(e.to_string()))?;
+            return Ok(value);
+        }
+        if let Some(callee) = self.frame().lookup(func).cloned() {
+            return self.call_value(callee, args, span);
+        }
+        if let Some(callable) = self.functions.get(func) {
+            let vars = callable.params.iter().copied().zip(args).collect();
+            let body = callable.body;
+            return self.call_with(Frame::new(vars), span, |this| this.eval_body(body));
+        }
+        self.call_builtin(func, args, span)
+    }
+
+    /// Funciones matemáticas del contexto semántico (`sqrt`, `sin`, `rand`...)
+    /// cuando se llaman por nombre.
+    fn call_builtin(&mut self, func: &str, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        let number = |i: usize| match args.get(i) {
+            Some(Value::Number(n)) => Some(*n),
+            _ => None,
+        };
+        let result = match (func, args.len()) {
+            ("sqrt", 1) => number(0).map(f64::sqrt),
+            ("sin", 1) => number(0).map(f64::sin),
+            ("cos", 1) => number(0).map(f64::cos),
+            ("exp", 1) => number(0).map(f64::exp),
+            ("log", 2) => number(0).zip(number(1)).map(|(base, x)| x.ln() / base.ln()),
+            ("rand", 0) => Some(self.next_random()),
+            _ => None,
+        };
+        result.map(Value::Number).ok_or_else(|| RuntimeError::UndefinedName(func.to_string(), span))
+    }
+
+    /// Llama a una lambda o a un objeto con método `invoke` (functor).
+    fn call_value(&mut self, callee: Value<'p>, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        match callee {
+            Value::Function(closure) => {
+                let mut vars = closure.captured.clone();
+                vars.extend(closure.params.iter().map(|p| p.name.as_str()).zip(args));
+                self.call_with(Frame::new(vars), span, |this| this.eval_body(closure.body))
+            }
+            Value::Object(_) => self.call_method(callee, "invoke", args, span),
+            other => Err(RuntimeError::NotCallable(other.type_name().to_string(), span)),
+        }
+    }
+
+    fn call_method(&mut self, receiver: Value<'p>, method: &str, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        match &receiver {
+            Value::Object(object) => {
+                let Some((owner, decl)) = self.find_method(object.ty, method) else {
+                    return Err(RuntimeError::MemberNotFound(method.to_string(), object.ty.to_string(), span));
+                };
+                self.invoke_method(owner, decl, receiver, args, span)
+            }
+            Value::Vector(v) => vector_method(v, method, args, span),
+            Value::Map(m) => map_method(m, method, args, span),
+            Value::String(s) => string_method(s, method, &args, span),
+            other => Err(RuntimeError::MemberNotFound(method.to_string(), other.type_name().to_string(), span)),
+        }
+    }
+
+    fn invoke_method(
+        &mut self,
+        owner: &'p TypeDecl,
+        decl: &'p FunctionDecl,
+        this: Value<'p>,
+        args: Vec<Value<'p>>,
+        span: Span,
+    ) -> Eval<'p> {
+        let mut vars = vec![("self", this.clone())];
+        vars.extend(decl.params.iter().map(|p| p.name.as_str()).zip(args.iter().cloned()));
+        let mut frame = Frame::new(vars);
+        frame.method = Some(MethodFrame { owner, method: decl, this, args });
+        self.call_with(frame, span, |this| this.eval_body(&decl.body))
+    }
+
+    /// `base(args)`: la implementación del método actual en el padre del tipo
+    /// que lo define. Sin argumentos reutiliza los de la llamada actual.
+    fn call_base(&mut self, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        let Some(current) = &self.frame().method else {
+            return Err(RuntimeError::BaseOutsideMethod(span));
+        };
+        let name = current.method.name.as_str();
+        let this = current.this.clone();
+        let args = if args.is_empty() { current.args.clone() } else { args };
+        let parent = current.owner.parent.as_ref().map(|p| p.name.as_str());
+
+        match parent.and_then(|p| self.find_method(p, name)) {
+            Some((owner, decl)) => self.invoke_method(owner, decl, this, args, span),
+            None => Err(RuntimeError::MemberNotFound(name.to_string(), parent.unwrap_or("Object").to_string(), span)),
+        }
+    }
+
+    /// Busca `method` en `ty` y sus ancestros; devuelve también el tipo que lo define.
+    fn find_method(&self, ty: &str, method: &str) -> Option<(&'p TypeDecl, &'p FunctionDecl)> {
+        let mut current = self.types.get(ty).copied();
+        while let Some(decl) = current {
+            if let Some(m) = decl.methods.iter().find(|m| m.name == method) {
+                return Some((decl, m));
+            }
+            current = decl.parent.as_ref().and_then(|p| self.types.get(p.name.as_str()).copied());
+        }
+        None
+    }
+
+    // ─────────────────────────────────────────────────────────────────────
+    // Objetos
+    // ─────────────────────────────────────────────────────────────────────
+
+    fn instantiate(&mut self, ty: &str, args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+        let Some(&decl) = self.types.get(ty) else {
+            return Err(RuntimeError::UndefinedName(ty.to_string(), span));
+        };
+        let object = Value::Object(Rc::new(Object { ty: &decl.name, fields: RefCell::new(HashMap::new()) }));
+        self.construct(decl, &object, args, span)?;
+        Ok(object)
+    }
+
+    /// Inicializa `object` como lo hace el constructor generado: los argumentos
+    /// del padre se evalúan con los parámetros propios, se construye la parte
+    /// del padre y después se inicializan los atributos propios en orden.
+    fn construct(&mut self, decl: &'p TypeDecl, object: &Value<'p>, args: Vec<Value<'p>>, span: Span) -> Result<(), RuntimeError> {
+        let mut vars = vec![("self", object.clone())];
+        vars.extend(decl.params.iter().map(|p| p.name.as_str()).zip(args.iter().cloned()));
+
+        self.call_with(Frame::new(vars), span, |this| {
+            if let Some(parent) = &decl.parent
+                && let Some(&parent_decl) = this.types.get(parent.name.as_str())
+            {
+                // `type B inherits A` sin parámetros propios hereda los de `A`
+                let parent_args = if parent.args.is_empty() && decl.params.is_empty() {
+                    args
+                } else {
+                    this.eval_args(&parent.args)?
+                };
+                this.construct(parent_decl, object, parent_args, span)?;
+            }
+            for attr in &decl.attributes {
+                let value = this.eval(&attr.init)?;
+                if let Value::Object(obj) = object {
+                    obj.fields.borrow_mut().insert(&attr.name, value);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// `value is ty`: herencia para los tipos declarados y conformidad
+    /// estructural (según el contexto semántico) para los protocolos.
+    fn conforms(&self, value: &Value<'p>, ty: &str) -> bool {
+        if ty == "Object" || value.type_name() == ty {
+            return true;
+        }
+        let protocol = self.context.get_type(ty).ok().filter(|t| t.borrow().kind == TypeKind::Protocol);
+        // Vectores y diccionarios solo conforman a los protocolos de iteración
+        let iterable = || protocol.as_ref().is_some_and(|p| {
+            p.borrow().methods.keys().all(|m| matches!(m.as_str(), "size" | "next" | "current" | "get_current"))
+        });
+        match value {
+            Value::Object(object) => {
+                let mut current = self.types.get(object.ty).copied();
+                while let Some(decl) = current {
+                    if decl.name == ty {
+                        return true;
+                    }
+                    current = decl.parent.as_ref().and_then(|p| self.types.get(p.name.as_str()).copied());
+                }
+                match (&protocol, self.context.get_type(object.ty)) {
+                    (Some(protocol), Ok(own)) => own.borrow().conforms_to(protocol),
+                    _ => false,
+                }
+            }
+            Value::Vector(_) => ty.starts_with("Vector<") || iterable(),
+            Value::Map(_) => ty.starts_with("Map<") || iterable(),
+            _ => false,
+        }
+    }
+
+    /// Recorre un vector elemento a elemento, o cualquier objeto con el
+    /// protocolo de iteración (`next()` y `current()` / `get_current()`).
+    /// `body` devuelve si hay que seguir recorriendo.
+    fn iterate(
+        &mut self,
+        iterable: &Value<'p>,
+        span: Span,
+        mut body: impl FnMut(&mut Self, Value<'p>) -> Result<bool, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        match iterable {
+            // Por posición, como `next()`: ve lo que el cuerpo agregue
+            Value::Vector(v) => {
+                for i in 0.. {
+                    let Some(item) = v.items.borrow().get(i).cloned() else { break };
+                    if !body(self, item)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            // Las claves que tiene al empezar el recorrido
+            Value::Map(m) => {
+                let keys: Vec<Value<'p>> = m.entries.borrow().iter().map(|(k, _)| k.clone()).collect();
+                for key in keys {
+                    if !body(self, key)? {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Value::Object(object) => {
+                let current = if self.find_method(object.ty, "current").is_some() { "current" } else { "get_current" };
+                loop {
+                    let more = self.call_method(iterable.clone(), "next", Vec::new(), span)?;
+                    if !matches!(more, Value::Boolean(true)) && !matches!(more, Value::Number(n) if n != 0.0) {
+                        return Ok(());
+                    }
+                    let item = self.call_method(iterable.clone(), current, Vec::new(), span)?;
+                    if !body(self, item)? {
+                        return Ok(());
+                    }
+                }
+            }
+            other => Err(not_defined("for", other, span)),
+        }
+    }
+
+    /// Variables que enlaza `pattern` si encaja con `value`.
+    fn match_pattern(&self, pattern: &'p Pattern, value: &Value<'p>) -> Option<Vec<(&'p str, Value<'p>)>> {
+        match pattern {
+            Pattern::Wildcard => Some(Vec::new()),
+            Pattern::Variable { name, type_annotation } => {
+                let fits = match type_annotation {
+                    Some(TypeAnnotation::Name(ty)) => self.conforms(value, ty),
+                    _ => true,
+                };
+                fits.then(|| vec![(name.as_str(), value.clone())])
+            }
+            Pattern::Literal(lit) => {
+                let literal = match lit {
+                    Expr::Number(n) => Value::Number(*n),
+                    Expr::Boolean(b) => Value::Boolean(*b),
+                    Expr::String(s) => Value::string(s),
+                    _ => return None,
+                };
+                literal.equals(value).then(Vec::new)
+            }
+            // Constructor: el objeto es del tipo y sus atributos encajan con
+            // los subpatrones, en el orden de los parámetros del constructor
+            Pattern::Call { func, args } => {
+                let Value::Object(object) = value else {
+                    return None;
+                };
+                let decl = self.types.get(func.as_str())?;
+                if !self.conforms(value, func) || decl.params.len() != args.len() {
+                    return None;
+                }
+                let mut bindings = Vec::new();
+                for (param, arg) in decl.params.iter().zip(args) {
+                    let field = object.fields.borrow().get(param.name.as_str())?.clone();
+                    bindings.extend(self.match_pattern(arg, &field)?);
+                }
+                Some(bindings)
+            }
+            // Los patrones de expresiones solo tienen sentido sobre el AST (macros)
+            Pattern::Binary { .. } | Pattern::Unary { .. } => None,
+        }
+    }
+
+    /// Número pseudoaleatorio en `[0, 1)` (xorshift64*).
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Ejecuta `program` y devuelve todo lo que imprimió.
+pub fn run_to_string(program: &Program, context: &Context) -> Result<String, RuntimeError> {
+    let mut interp = Interpreter::new(program, context, Vec::new());
+    interp.run()?;
+    Ok(String::from_utf8_lossy(&interp.into_output()).into_owned())
+}
+
+/// Métodos de los vectores: `size()`, el protocolo de iteración y los que
+/// lo modifican (`push`, `pop`, `insert`, `remove_at`).
+fn vector_method<'p>(v: &Vector<'p>, method: &str, mut args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+    let missing = || RuntimeError::MemberNotFound(method.to_string(), "Vector".into(), span);
+    let mut items = v.items.borrow_mut();
+    let len = items.len();
+    match method {
+        "size" => Ok(Value::Number(len as f64)),
+        "next" => {
+            let next = v.cursor.get() + 1;
+            let more = (next as usize) < len;
+            v.cursor.set(if more { next } else { -1 });
+            Ok(Value::Boolean(more))
+        }
+        "current" | "get_current" => {
+            let cursor = v.cursor.get();
+            usize::try_from(cursor).ok().and_then(|i| items.get(i)).cloned()
+                .ok_or(RuntimeError::IndexOutOfBounds { index: cursor as f64, len, span })
+        }
+        "push" => {
+            let value = args.pop().ok_or_else(missing)?;
+            items.push(value.clone());
+            Ok(value)
+        }
+        "pop" => items.pop().ok_or(RuntimeError::IndexOutOfBounds { index: 0.0, len, span }),
+        "insert" if args.len() == 2 => {
+            let value = args.pop().unwrap();
+            let Value::Number(i) = args[0] else {
+                return Err(not_defined("insert", &args[0], span));
+            };
+            // Se puede insertar también al final
+            let pos = element_index(i, len + 1, span).map_err(|_| RuntimeError::IndexOutOfBounds { index: i, len, span })?;
+            items.insert(pos, value.clone());
+            Ok(value)
+        }
+        "remove_at" => match args.first().ok_or_else(missing)? {
+            Value::Number(i) => Ok(items.remove(element_index(*i, len, span)?)),
+            other => Err(not_defined("remove_at", other, span)),
+        },
+        _ => Err(missing()),
+    }
+}
+
+/// Posición de `index` (truncado) en un vector de `len` elementos.
+fn element_index(index: f64, len: usize, span: Span) -> Result<usize, RuntimeError> {
+    let pos = index.trunc();
+    if pos < 0.0 || pos >= len as f64 {
+        return Err(RuntimeError::IndexOutOfBounds { index, len, span });
+    }
+    Ok(pos as usize)
+}
+
+/// Métodos de los diccionarios. El recorrido con `next()` vuelve al principio
+/// al terminar, como en el código generado.
+fn map_method<'p>(m: &Map<'p>, method: &str, mut args: Vec<Value<'p>>, span: Span) -> Eval<'p> {
+    let key = || args.first().cloned().ok_or(RuntimeError::MemberNotFound(method.to_string(), "Map".into(), span));
+    match method {
+        "size" => Ok(Value::Number(m.entries.borrow().len() as f64)),
+        "get" => {
+            let key = key()?;
+            match m.position(&key) {
+                Some(i) => Ok(m.entries.borrow()[i].1.clone()),
+                None => Err(RuntimeError::KeyNotFound { key: key.to_string(), span }),
+            }
+        }
+        "set" if args.len() == 2 => {
+            let value = args.pop().unwrap();
+            let key = args.pop().unwrap();
+            m.insert(key, value.clone());
+            Ok(value)
+        }
+        "has" => Ok(Value::Boolean(m.position(&key()?).is_some())),
+        "remove" => {
+            let found = m.position(&key()?);
+            if let Some(i) = found {
+                m.entries.borrow_mut().remove(i);
+            }
+            Ok(Value::Boolean(found.is_some()))
+        }
+        "keys" => Ok(Value::vector(m.entries.borrow().iter().map(|(k, _)| k.clone()).collect())),
+        "values" => Ok(Value::vector(m.entries.borrow().iter().map(|(_, v)| v.clone()).collect())),
+        "next" => {
+            let next = m.cursor.get() + 1;
+            let more = (next as usize) < m.entries.borrow().len();
+            m.cursor.set(if more { next } else { -1 });
+            Ok(Value::Boolean(more))
+        }
+        "current" | "get_current" => {
+            let cursor = m.cursor.get();
+            let entries = m.entries.borrow();
+            usize::try_from(cursor).ok().and_then(|i| entries.get(i)).map(|(k, _)| k.clone())
+                .ok_or(RuntimeError::IndexOutOfBounds { index: cursor as f64, len: entries.len(), span })
+        }
+        _ => Err(RuntimeError::MemberNotFound(method.to_string(), "Map".into(), span)),
+    }
+}
+
+/// Métodos de las cadenas. Los índices y `size()` cuentan caracteres, igual
+/// que el código generado; `upper()` y `lower()` solo cambian letras ASCII.
+fn string_method<'p>(s: &str, method: &str, args: &[Value<'p>], span: Span) -> Eval<'p> {
+    let chars: Vec<char> = s.chars().collect();
+    let len = chars.len();
+    let text = |t: &str| Value::String(t.into());
+    let number = |i: usize| match args.get(i) {
+        Some(Value::Number(n)) => Ok(*n),
+        Some(other) => Err(not_defined(method, other, span)),
+        None => Err(RuntimeError::MemberNotFound(method.to_string(), "String".into(), span)),
+    };
+    let string = |i: usize| match args.get(i) {
+        Some(Value::String(t)) => Ok(t.clone()),
+        Some(other) => Err(not_defined(method, other, span)),
+        None => Err(RuntimeError::MemberNotFound(method.to_string(), "String".into(), span)),
+    };
+    match method {
+        "size" => Ok(Value::Number(len as f64)),
+        "at" => {
+            let i = number(0)?;
+            let pos = i.trunc();
+            if pos < 0.0 || pos >= len as f64 {
+                return Err(RuntimeError::IndexOutOfBounds { index: i, len, span });
+            }
+            Ok(text(&chars[pos as usize].to_string()))
+        }
+        "substring" => {
+            // El error muestra el argumento tal como se pasó
+            let (i, n) = (number(0)?, number(1)?);
+            let (start, count) = (i.trunc(), n.trunc());
+            if start < 0.0 || start > len as f64 {
+                return Err(RuntimeError::IndexOutOfBounds { index: i, len, span });
+            }
+            if count < 0.0 || start + count > len as f64 {
+                return Err(RuntimeError::IndexOutOfBounds { index: n, len, span });
+            }
+            let (start, count) = (start as usize, count as usize);
+            Ok(text(&chars[start..start + count].iter().collect::<String>()))
+        }
+        "index_of" => {
+            let needle = string(0)?;
+            let found = s.find(&*needle).map_or(-1.0, |b| s[..b].chars().count() as f64);
+            Ok(Value::Number(found))
+        }
+        "split" => {
+            let sep = string(0)?;
+            let parts: Vec<Value<'p>> = if sep.is_empty() {
+                chars.iter().map(|c| text(&c.to_string())).collect()
+            } else {
+                s.split(&*sep).map(text).collect()
+            };
+            Ok(Value::vector(parts))
+        }
+        "trim" => Ok(text(s.trim_matches(is_space))),
+        "upper" => Ok(text(&s.to_ascii_uppercase())),
+        "lower" => Ok(text(&s.to_ascii_lowercase())),
+        "replace" => {
+            let (old, new) = (string(0)?, string(1)?);
+            Ok(if old.is_empty() { text(s) } else { text(&s.replace(&*old, &new)) })
+        }
+        "starts_with" => Ok(Value::Boolean(s.starts_with(&*string(0)?))),
+        "hash" => Ok(Value::Number(string_hash(s) as f64)),
+        "parse_number" => s.trim_matches(is_space).parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| RuntimeError::InvalidNumber { text: s.to_string(), span }),
+        _ => Err(RuntimeError::MemberNotFound(method.to_string(), "String".into(), span)),
+    }
+}
+
+/// FNV-1a de 32 bits sobre los bytes de la cadena, igual que `@__hulk_str_hash`.
+pub fn string_hash(s: &str) -> u32 {
+    s.bytes().fold(0x811C_9DC5, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Espacios que quitan `trim()` y `parse_number()`: los de `isspace` en C.
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0B' | '\x0C')
+}
+
+fn macro_param_name(param: &MacroParam) -> &str {
+    match param {
+        MacroParam::Normal { name, .. }
+        | MacroParam::Symbolic { name, .. }
+        | MacroParam::Placeholder { name, .. }
+        | MacroParam::Body { name, .. } => name,
+    }
+}
+
+fn not_defined(op: &str, value: &Value<'_>, span: Span) -> RuntimeError {
+    RuntimeError::OperationNotDefined(op.to_string(), value.type_name().to_string(), span)
+}
+
+fn op_symbol(op: &Op) -> &'static str {
+    match op {
+        Op::Add => "+",
+        Op::Sub => "-",
+        Op::Mul => "*",
+        Op::Div => "/",
+        Op::Mod => "%",
+        Op::Pow => "^",
+        Op::Eq => "==",
+        Op::Neq => "!=",
+        Op::Lt => "<",
+        Op::Gt => ">",
+        Op::Le => "<=",
+        Op::Ge => ">=",
+        Op::And => "&",
+        Op::Or => "|",
+        Op::Concat => "@",
+        Op::ConcatSpace => "@@",
+    }
+}
+
+/// Rellena `line` y `column` de un `Error` que todavía no tiene posición.
+fn stamp_position(object: &Object<'_>, span: Span) {
+    let mut fields = object.fields.borrow_mut();
+    if matches!(fields.get("line"), Some(Value::Number(n)) if *n == 0.0) {
+        fields.insert("line", Value::Number(span.start.line as f64));
+        fields.insert("column", Value::Number(span.start.column as f64));
+    }
+}
@@ -0,0 +1,334 @@
+use super::*;
+use crate::driver;
+
+/// Chequea y ejecuta `source`, devolviendo lo que imprimió o el error.
+fn eval(source: &str) -> Result<String, RuntimeError> {
+    let checked = driver::check(source).unwrap_or_else(|e| panic!("el programa no compila:\n{}", e));
+    run_to_string(&checked.program, &checked.context)
+}
+
+fn output(source: &str) -> String {
+    eval(source).unwrap_or_else(|e| panic!("error en tiempo de ejecución: {}", e))
+}
+
+#[test]
+fn formats_numbers_like_printf_g() {
+    let cases = [
+        (0.0, "0"),
+        (5.0, "5"),
+        (-0.5, "-0.5"),
+        (1.0 / 3.0, "0.333333"),
+        (3628800.0, "3.6288e+06"),
+        (123456789.0, "1.23457e+08"),
+        (999999.5, "1e+06"),
+        (0.0001, "0.0001"),
+        (0.00001234, "1.234e-05"),
+        (1e20, "1e+20"),
+        (f64::INFINITY, "inf"),
+    ];
+    for (n, expected) in cases {
+        assert_eq!(format_number(n), expected, "format_number({})", n);
+    }
+}
+
+#[test]
+fn prints_values_like_the_llvm_runtime() {
+    let out = output(r#"{
+        print(1 / 3);
+        print("hola");
+        print(3 < 4);
+        print([1, 2.5, 3]);
+        print("n" @ 2 @ 3.5 @@ true);
+        print(7 % 3 + 2 ^ 3);
+    }"#);
+    assert_eq!(out, "0.333333\nhola\ntrue\n[1, 2.5, 3]\nn23.5 true\n9\n");
+}
+
+#[test]
+fn runs_functions_loops_and_lets() {
+    let out = output(
+        "function fact(n: Number): Number => if (n <= 1) 1 else n * fact(n - 1);
+        {
+            print(fact(10));
+            let a = 3 in while (a > 0) { print(a); a := a - 1; };
+            for (x in [1, 2]) print(x * 10);
+            print([x ^ 2 | x in [1, 2, 3]]);
+        }",
+    );
+    assert_eq!(out, "3.6288e+06\n3\n2\n1\n10\n20\n[1, 4, 9]\n");
+}
+
+#[test]
+fn builds_objects_with_inheritance_and_base() {
+    let out = output(
+        r#"type Point(x, y) {
+            x = x;
+            y = y;
+            norm() => sqrt(self.x ^ 2 + self.y ^ 2);
+            str(): String => "(" @ self.x @ ", " @ self.y @ ")";
+        }
+        type Point3(x, y, z) inherits Point(x, y) {
+            z = z;
+            str(): String => base() @ " z=" @ self.z;
+        }
+        let p = new Point3(3, 4, 5) in {
+            print(p.norm());
+            print(p.str());
+            p.x := 6;
+            print(p.x);
+        }"#,
+    );
+    assert_eq!(out, "5\n(3, 4) z=5\n6\n");
+}
+
+#[test]
+fn closures_capture_by_value() {
+    let out = output(
+        "let n = 1, f = (x) => x + n in {
+            n := 100;
+            print(f(1));
+        }",
+    );
+    assert_eq!(out, "2\n");
+}
+
+#[test]
+fn calls_functions_passed_as_functors() {
+    let out = output(
+        "protocol NumberFilter { invoke(x: Number): Boolean; }
+        function even(x: Number): Boolean => x % 2 == 0;
+        function count(v: Number[], f: NumberFilter): Number {
+            let c = 0 in {
+                for (x in v) if (f(x)) c := c + 1 else c;
+                c;
+            };
+        }
+        print(count([1, 2, 3, 4], even));",
+    );
+    assert_eq!(out, "2\n");
+}
+
+#[test]
+fn iterates_objects_with_the_iteration_protocol() {
+    let out = output(
+        "type Counter(min: Number, max: Number) {
+            min = min;
+            max = max;
+            current = min - 1;
+            next(): Boolean => (self.current := self.current + 1) < self.max;
+            current() => self.current;
+        }
+        for (i in new Counter(0, 3)) print(i);",
+    );
+    assert_eq!(out, "0\n1\n2\n");
+}
+
+#[test]
+fn counts_with_range_and_iterable_parameters() {
+    let out = output(
+        "function total(xs: Number*): Number => let s = 0 in { for (x in xs) s := s + x; s; };
+        {
+            for (i in range(0, 3)) print(i);
+            print(\"sums\" @@ total(range(1, 5)) @@ total([10, 20]) @@ [i * 2 | i in range(0, 3)]);
+            let v = [1, 2] in print(total(v) + total(v));
+            print(range(0, 1) is Iterable);
+        }",
+    );
+    assert_eq!(out, "0\n1\n2\nsums 10 30 [0, 2, 4]\n6\ntrue\n");
+}
+
+#[test]
+fn checks_types_at_runtime() {
+    let out = output(
+        "type A { }
+        type B inherits A { }
+        let x: A = new B() in {
+            print(x is B);
+            print(x is A);
+            print(3 is String);
+            print((x as B) is B);
+        }",
+    );
+    assert_eq!(out, "true\ntrue\nfalse\ntrue\n");
+
+    let err = eval("type A { } type B inherits A { } let x: A = new A() in x as B;").unwrap_err();
+    assert_eq!(err.code(), "E0302");
+}
+
+#[test]
+fn matches_constructor_patterns_with_guards() {
+    let out = output(
+        "type Shape { }
+        type Circle(r: Number) inherits Shape { r = r; }
+        type Rect(w: Number, h: Number) inherits Shape { w = w; h = h; }
+        function f(s: Shape): String => match (s) {
+            case Circle(0) => \"dot\";
+            case Rect(w, h) if w == h => \"square\";
+            case Rect(_, _) => \"rect\";
+            case c: Circle => \"circle \" @ c.r;
+            case _ => \"shape\";
+        };
+        print(f(new Circle(0)) @@ f(new Circle(2)) @@ f(new Rect(1, 1)) @@ f(new Rect(1, 2)) @@ f(new Shape()));",
+    );
+    assert_eq!(out, "dot circle 2 square rect shape\n");
+}
+
+#[test]
+fn reports_index_out_of_bounds_at_the_indexing() {
+    let source = "let v = [1, 2] in print(v[2]);";
+    let err = eval(source).unwrap_err();
+    assert!(matches!(err, RuntimeError::IndexOutOfBounds { len: 2, .. }));
+    assert_eq!(&source[err.span().start.offset..err.span().end.offset], "v[2]");
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    let out = output("let v = [1] in print(false & v[5] == 1 | true);");
+    assert_eq!(out, "true\n");
+}
+
+#[test]
+fn deep_recursion_ends_in_stack_overflow() {
+    let result = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| eval("function f(n) => f(n + 1); f(0);"))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(result.unwrap_err().code(), "E0308");
+}
+
+#[test]
+fn catches_thrown_and_runtime_errors() {
+    let out = output(
+        "type Oops(n: Number) inherits Error(\"oops \" @ n) { n = n; }
+        function f(n) => if (n > 1) throw new Oops(n) else n;
+        print(try f(1) catch (e) -1);
+        print(try f(2) catch (e: Oops) e.message @@ e.line @ \":\" @ e.column);
+        print(try [1][3] catch (e: IndexError) e.message);
+        print(try { let o: Object = \"s\" in o as Number; } catch (e: CastError) e.message);
+        print(try { try f(3) catch (e: IndexError) 0; } catch (e: Oops) e.n);",
+    );
+    assert_eq!(out, "1\noops 2 2:37\nindex out of bounds\ninvalid cast to 'Number'\n3\n");
+}
+
+#[test]
+fn reports_uncaught_errors_with_their_type() {
+    let err = eval("print(1); throw new Error(\"boom\");").unwrap_err();
+    assert!(matches!(&err, RuntimeError::Uncaught { ty, message, .. } if ty == "Error" && message == "boom"));
+    assert_eq!(err.code(), "E0310");
+}
+
+#[test]
+fn runs_string_methods() {
+    let out = output(
+        "let s = \"  Hello, World \".trim() in {
+            print(\"size\" @@ s.size() @@ s.index_of(\"World\") @@ s.index_of(\"?\"));
+            print(s.substring(7, 5) @ s.at(5) @@ s.upper() @@ s.lower());
+            print(s.replace(\"o\", \"0\") @@ s.starts_with(\"Hell\") @@ s.starts_with(\"World\"));
+            for (p in \"a,,b\".split(\",\")) print(\"[\" @ p @ \"]\");
+            print(\"abc\".split(\"\").size() + \" 2.5 \".parse_number());
+            print(try s.at(12) catch (e: IndexError) e.message);
+            print(try s.substring(10, 5) catch (e: IndexError) e.message);
+            print(try \"1x\".parse_number() catch (e: CastError) e.message);
+        }",
+    );
+    assert_eq!(
+        out,
+        "size 12 7 -1\nWorld, HELLO, WORLD hello, world\nHell0, W0rld true false\n[a]\n[]\n[b]\n5.5\n\
+         index out of bounds\nindex out of bounds\ninvalid cast to 'Number'\n"
+    );
+    let err = eval("print(\"abc\".parse_number());").unwrap_err();
+    assert!(matches!(&err, RuntimeError::InvalidNumber { text, .. } if text == "abc"));
+    assert_eq!(err.code(), "E0312");
+}
+
+#[test]
+fn string_indices_count_characters() {
+    let out = output("let s = \"ñandú\" in print(s.size() @@ s.at(4) @@ s.substring(1, 3) @@ s.index_of(\"dú\") @@ s.upper());");
+    assert_eq!(out, "5 ú and 3 ñANDú\n");
+    // El error muestra el argumento que se pasó, no el final calculado
+    let err = eval("print(\"hello\".substring(2, 5));").unwrap_err();
+    assert!(matches!(err, RuntimeError::IndexOutOfBounds { index, len: 5, .. } if index == 5.0), "{:?}", err);
+}
+
+#[test]
+fn compares_strings_by_value() {
+    let out = output(
+        "let a = \"apple\", b = \"app\" @ \"le\" in {
+            print(\"cmp\" @@ (a == b) @@ (a != b) @@ (a < \"banana\") @@ (\"banana\" <= a) @@ (\"\" < a) @@ (\"Z\" < \"a\"));
+            print(\"hash\" @@ \"\".hash() @@ \"a\".hash() @@ (a.hash() == b.hash()));
+        }",
+    );
+    assert_eq!(out, "cmp true false true false true true\nhash 2166136261 3826002220 true\n");
+}
+
+#[test]
+fn jumps_out_of_loops_and_functions() {
+    let out = output(
+        "function find(xs: Number[], n: Number): Number { for (x in xs) if (x == n) return x * 10 else 0; -1; }
+        type Box(x: Number) { x = x; get(): Number { try { return self.x; } catch (e) 0; 1; } }
+        print(find([1, 2, 3], 2)); print(find([1], 5));
+        print(new Box(7).get());
+        print(let i = 0 in while (true) { i := i + 1; if (i == 3) break i * 2 else i; });
+        print(for (x in [1, 2, 3, 4]) if (x % 2 == 0) continue x else x + 100);
+        let g = (y: Number): Number => { return y + 1; 0; } in print(g(4));",
+    );
+    assert_eq!(out, "20\n-1\n7\n6\n4\n5\n");
+}
+
+#[test]
+fn runs_maps() {
+    let out = output(
+        "let m = {\"b\" -> 2, \"a\" -> 1}, e = new Map<Number, String>() in {
+            print(m @@ m.size() @@ m.get(\"a\") @@ m.has(\"c\"));
+            m.set(\"c\", 3); m.set(\"b\", 20);
+            for (k in m) print(k @ \"=\" @ m.get(k));
+            print(\"removed\" @@ m.remove(\"a\") @@ m.remove(\"a\") @@ m.keys() @@ m.values());
+            e.set(-0, \"zero\");
+            print(e.get(0) @@ e);
+            print(try m.get(\"z\") catch (err: KeyError) err.message);
+        }",
+    );
+    assert_eq!(
+        out,
+        "{b -> 2, a -> 1} 2 1 false\nb=20\na=1\nc=3\nremoved true false [b, c] [20, 3]\nzero {-0 -> zero}\nkey not found\n"
+    );
+    let err = eval("let m = {1 -> 2} in print(m.get(3));").unwrap_err();
+    assert!(matches!(&err, RuntimeError::KeyNotFound { key, .. } if key == "3"));
+    assert_eq!(err.code(), "E0313");
+}
+
+#[test]
+fn map_literal_keeps_last_value_of_repeated_key() {
+    let out = output("let m = {\"a\" -> 1, \"b\" -> 2, \"a\" -> 3} in print(m @@ m.size() @@ m.get(\"a\"));");
+    assert_eq!(out, "{a -> 3, b -> 2} 2 3\n");
+}
+
+#[test]
+fn runs_growable_vectors() {
+    let out = output(
+        "let v = [1, 2, 3] in {
+            v.push(4); v.insert(0, 0); v[1] := 10;
+            print(v);
+            print(\"got\" @@ v.pop() @@ v.remove_at(2) @@ v);
+            print(v[1:] @@ v[:1] @@ v[1:1] @@ v + [5]);
+            for (x in v) if (x < 5) v.push(x + 5) else 0;
+            print(v);
+            print(try v[7] := 0 catch (e: IndexError) e.message);
+        }",
+    );
+    assert_eq!(out, "[0, 10, 2, 3, 4]\ngot 4 2 [0, 10, 3]\n[10, 3] [0] [] [0, 10, 3, 5]\n[0, 10, 3, 5, 8]\nindex out of bounds\n");
+    for program in ["print([].pop());", "print([1, 2][1:3]);", "let v = [1] in v.insert(3, 1);"] {
+        assert!(matches!(eval(program), Err(RuntimeError::IndexOutOfBounds { .. })), "{}", program);
+    }
+    // Un rango al revés tiene su propio error, que se atrapa como `IndexError`
+    // con el mismo mensaje
+    let err = eval("print([1, 2, 3][2:1]);").unwrap_err();
+    assert_eq!(err.to_string(), "slice start 2 is greater than end 1");
+    assert_eq!(err.code(), "E0314");
+    assert_eq!(
+        output("print(try [1, 2, 3][2:1] catch (e: IndexError) e.message);"),
+        "slice start 2 is greater than end 1\n"
+    );
+}
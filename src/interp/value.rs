@@ -0,0 +1,199 @@
+use crate::ast::nodes::{Expr, Param};
+use crate::utils::Spanned;
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// Valor en tiempo de ejecución. Toma prestadas del `Program` evaluado las
+/// partes que no cambian (nombres de tipos, cuerpos de lambdas).
+#[derive(Debug, Clone)]
+pub enum Value<'p> {
+    Number(f64),
+    Boolean(bool),
+    String(Rc<str>),
+    Vector(Rc<Vector<'p>>),
+    Map(Rc<Map<'p>>),
+    Object(Rc<Object<'p>>),
+    Function(Rc<Closure<'p>>),
+}
+
+/// Vector que crece con `push` / `insert` y el cursor que usan `next()` /
+/// `current()`.
+#[derive(Debug)]
+pub struct Vector<'p> {
+    pub items: RefCell<Vec<Value<'p>>>,
+    /// Índice del elemento actual; empieza en -1, antes del primero.
+    pub cursor: Cell<isize>,
+}
+
+/// Diccionario con sus pares en el orden en que se insertaron. El cursor de
+/// `next()` / `current()` recorre las claves.
+#[derive(Debug)]
+pub struct Map<'p> {
+    pub entries: RefCell<Vec<(Value<'p>, Value<'p>)>>,
+    pub cursor: Cell<isize>,
+}
+
+impl<'p> Map<'p> {
+    /// Posición del par con la clave `key` (comparada con `==`).
+    pub fn position(&self, key: &Value<'p>) -> Option<usize> {
+        self.entries.borrow().iter().position(|(k, _)| k.equals(key))
+    }
+
+    /// Asocia `value` a `key`, reemplazando el valor si la clave ya estaba.
+    pub fn insert(&self, key: Value<'p>, value: Value<'p>) {
+        match self.position(&key) {
+            Some(i) => self.entries.borrow_mut()[i].1 = value,
+            None => self.entries.borrow_mut().push((key, value)),
+        }
+    }
+}
+
+/// Instancia de un tipo declarado en el programa.
+#[derive(Debug)]
+pub struct Object<'p> {
+    pub ty: &'p str,
+    pub fields: RefCell<HashMap<&'p str, Value<'p>>>,
+}
+
+/// Lambda con una copia de las variables visibles al crearla, como en el
+/// código generado (las clausuras capturan por valor).
+#[derive(Debug)]
+pub struct Closure<'p> {
+    pub params: &'p [Param],
+    pub body: &'p Spanned<Expr>,
+    pub captured: Vec<(&'p str, Value<'p>)>,
+}
+
+impl<'p> Value<'p> {
+    pub fn string(s: &str) -> Self {
+        Value::String(Rc::from(s))
+    }
+
+    pub fn vector(items: Vec<Value<'p>>) -> Self {
+        Value::Vector(Rc::new(Vector { items: RefCell::new(items), cursor: Cell::new(-1) }))
+    }
+
+    pub fn map(entries: Vec<(Value<'p>, Value<'p>)>) -> Self {
+        Value::Map(Rc::new(Map { entries: RefCell::new(entries), cursor: Cell::new(-1) }))
+    }
+
+    /// Nombre del tipo dinámico del valor.
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Number(_) => "Number",
+            Value::Boolean(_) => "Boolean",
+            Value::String(_) => "String",
+            Value::Vector(_) => "Vector",
+            Value::Map(_) => "Map",
+            Value::Object(obj) => obj.ty,
+            Value::Function(_) => "Function",
+        }
+    }
+
+    /// Igualdad de `==`: por valor para los primitivos y por identidad para el resto.
+    pub fn equals(&self, other: &Value<'p>) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Vector(a), Value::Vector(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Texto del valor como operando de `@` / `@@`: los números enteros se
+    /// escriben sin decimales.
+    pub fn concat_text(&self) -> String {
+        match self {
+            Value::Number(n) => format_integral(*n).unwrap_or_else(|| format_number(*n)),
+            Value::Vector(v) => {
+                let items: Vec<String> = v.items.borrow().iter().map(Value::concat_text).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Value::Map(m) => {
+                let entries: Vec<String> = m.entries.borrow().iter()
+                    .map(|(k, v)| format!("{} -> {}", k.concat_text(), v.concat_text()))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Formato de `print`, el mismo que el runtime de LLVM: números con `%.6g`,
+/// booleanos como `true` / `false`, vectores como `[a, b]` y diccionarios
+/// como `{k -> v}`.
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Vector(v) => {
+                write!(f, "[")?;
+                for (i, item) in v.items.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in m.entries.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} -> {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Object(obj) => write!(f, "<{}>", obj.ty),
+            Value::Function(_) => write!(f, "<function>"),
+        }
+    }
+}
+
+/// Equivalente a `printf("%.6g", n)`: seis cifras significativas, sin ceros
+/// finales, y notación científica si el exponente es menor que -4 o mayor que 5.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if n == 0.0 {
+        return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+
+    // El exponente que cuenta es el del número ya redondeado a 6 cifras
+    let scientific = format!("{:.5e}", n);
+    let (mantissa, exp) = scientific.split_once('e').expect("formato {:e} sin exponente");
+    let exp: i32 = exp.parse().expect("exponente no numérico");
+
+    if !(-4..6).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exp.abs())
+    } else {
+        let decimals = (5 - exp) as usize;
+        trim_zeros(&format!("{:.*}", decimals, n)).to_string()
+    }
+}
+
+/// `%.0f` para números enteros de magnitud menor que 1e15; `None` si no lo son.
+fn format_integral(n: f64) -> Option<String> {
+    (n.fract() == 0.0 && n.abs() < 1e15).then(|| format!("{:.0}", n))
+}
+
+fn trim_zeros(s: &str) -> &str {
+    if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.') } else { s }
+}
@@ -1,7 +1,7 @@
 pub mod tokens;
 
-use crate::utils::Position;
-use tokens::Token;
+use crate::utils::{Position, Span};
+use tokens::{Comment, Token};
 use crate::errors::LexError;
 use std::str::Chars;
 use std::iter::Peekable;
@@ -12,6 +12,10 @@ pub struct Lexer<'a> {
     chars: Peekable<Chars<'a>>,
     line: usize,
     column: usize,
+    offset: usize,
+    input: &'a str,
+    /// Comentarios encontrados hasta ahora, en orden de aparición.
+    comments: Vec<Comment>,
 }
 
 impl<'a> Lexer<'a> {
@@ -21,20 +25,48 @@ impl<'a> Lexer<'a> {
             chars: input.chars().peekable(),
             line: 1,
             column: 1,
+            offset: 0,
+            input,
+            comments: Vec::new(),
         }
     }
 
-    /// Devuelve la posición actual (línea y columna) del lexer.
+    /// Devuelve la posición actual (línea, columna y byte) del lexer.
     fn current_pos(&self) -> Position {
         Position {
             line: self.line,
             column: self.column,
+            offset: self.offset,
         }
     }
 
+    /// Posición justo después del último token producido: junto con la posición
+    /// de inicio que devuelve `next` forma el rango del token.
+    pub fn token_end(&self) -> Position {
+        self.current_pos()
+    }
+
+    /// Comentarios omitidos hasta el momento (trivia), en orden de aparición.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Extrae los comentarios acumulados, dejando la lista vacía.
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Registra como trivia el comentario que va de `start` a la posición actual.
+    fn record_comment(&mut self, start: Position) {
+        let end = self.current_pos();
+        let text = self.input[start.offset..end.offset].trim_end_matches('\r').to_string();
+        self.comments.push(Comment { text, span: Span::new(start, end) });
+    }
+
     /// Consume y devuelve el siguiente carácter, actualizando línea/columna.
     fn next_char(&mut self) -> Option<char> {
         let c = self.chars.next()?;
+        self.offset += c.len_utf8();
         if c == '\n' {
             self.line += 1;
             self.column = 1;
@@ -150,6 +182,7 @@ impl<'a> Lexer<'a> {
             "new" => Token::New,
             "inherits" => Token::Inherits,
             "protocol" => Token::Protocol,
+            "variant" => Token::Variant,
             "is" => Token::Is,
             "as" => Token::As,
             "print" => Token::Print,
@@ -163,6 +196,12 @@ impl<'a> Lexer<'a> {
             "match" => Token::Match,
             "case" => Token::Case,
             "default" => Token::Default,
+            "try" => Token::Try,
+            "catch" => Token::Catch,
+            "throw" => Token::Throw,
+            "break" => Token::Break,
+            "continue" => Token::Continue,
+            "return" => Token::Return,
             _ => Token::Identifier(ident),
         }
     }
@@ -185,10 +224,12 @@ impl<'a> Iterator for Lexer<'a> {
                 if next_c == '/' {
                     // Comentario de línea
                     self.next_char(); // consumir /
-                    // omitir hasta nueva línea
-                    while let Some(x) = self.next_char() {
+                    // omitir hasta nueva línea (el salto se trata como espacio)
+                    while let Some(&x) = self.peek_char() {
                         if x == '\n' { break; }
+                        self.next_char();
                     }
+                    self.record_comment(pos);
                     return self.next(); // Llamar recursivamente a next para obtener el token real
                 } else if next_c == '*' {
                     // Comentario de bloque
@@ -208,6 +249,7 @@ impl<'a> Iterator for Lexer<'a> {
                     if !terminated {
                         return Some(Err(LexError::UnterminatedBlockComment(pos))); // O devolver error
                     }
+                    self.record_comment(pos);
                     return self.next();
                 }
             }
@@ -294,6 +336,7 @@ impl<'a> Iterator for Lexer<'a> {
                 }
             },
             '$' => Ok(Token::Dollar),
+            '_' if !self.peek_char().is_some_and(|c| c.is_alphanumeric() || *c == '_') => Ok(Token::Underscore),
             '(' => Ok(Token::LParen),
             ')' => Ok(Token::RParen),
             '{' => Ok(Token::LBrace),
@@ -65,6 +65,24 @@ fn test_comments() {
     assert_eq!(lexer.next().unwrap().unwrap().0, Number(1.0));
 }
 
+#[test]
+fn test_comments_are_kept_as_trivia() {
+    let input = "let // comment \n x /* block\n */ = 1";
+    let mut lexer = Lexer::new(input);
+    let tokens: Vec<_> = lexer.by_ref().map(|t| t.unwrap().0).collect();
+    assert_eq!(tokens, vec![Let, Identifier("x".to_string()), Assign, Number(1.0)]);
+
+    let comments = lexer.comments();
+    assert_eq!(comments.len(), 2);
+    assert_eq!(comments[0].text, "// comment ");
+    assert!(!comments[0].is_block());
+    assert_eq!((comments[0].span.start.offset, comments[0].span.end.offset), (4, 15));
+    assert_eq!(comments[1].text, "/* block\n */");
+    assert!(comments[1].is_block());
+    assert_eq!(&input[comments[1].span.start.offset..comments[1].span.end.offset], "/* block\n */");
+    assert_eq!(comments[1].span.end.line, 3);
+}
+
 
 
 #[test]
@@ -641,6 +659,31 @@ impl BoolExt for bool { fn is_falsy(&self) -> bool { !*self } }
 
 // ============ MACRO TESTS ============
 
+#[test]
+fn test_variant_keyword() {
+    let mut lexer = Lexer::new("variant variants");
+    assert_eq!(lexer.next().unwrap().unwrap().0, Variant);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Identifier("variants".to_string()));
+}
+
+#[test]
+fn test_exception_keywords() {
+    let mut lexer = Lexer::new("try catch throw thrown");
+    assert_eq!(lexer.next().unwrap().unwrap().0, Try);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Catch);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Throw);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Identifier("thrown".to_string()));
+}
+
+#[test]
+fn test_jump_keywords() {
+    let mut lexer = Lexer::new("break continue return returns");
+    assert_eq!(lexer.next().unwrap().unwrap().0, Break);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Continue);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Return);
+    assert_eq!(lexer.next().unwrap().unwrap().0, Identifier("returns".to_string()));
+}
+
 #[test]
 fn test_macro_keywords() {
     let input = "def match case default";
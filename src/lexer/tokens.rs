@@ -1,3 +1,5 @@
+use crate::utils::Span;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     // Palabras clave
@@ -11,6 +13,7 @@ pub enum Token {
     New,
     Inherits,
     Protocol,
+    Variant,
     Extends,
     Is,
     As,
@@ -24,6 +27,12 @@ pub enum Token {
     Match,      // para pattern matching
     Case,       // para casos de match
     Default,    // para caso default en match
+    Try,        // para manejo de excepciones
+    Catch,      // para atrapar una excepción en un try
+    Throw,      // para lanzar una excepción
+    Break,      // sale del bucle actual
+    Continue,   // pasa a la siguiente iteración
+    Return,     // sale de la función o método actual
 
     // Identificadores
     Identifier(String),
@@ -76,6 +85,7 @@ pub enum Token {
     Dot,        // .
     Semicolon,  // ;
     Dollar,     // $ (para variable placeholders en macros)
+    Underscore, // _ (comodín en patrones de match)
     // @ se tokeniza como Concat, no At
     // At se usa solo conceptualmente en parsing de macros
 
@@ -83,3 +93,19 @@ pub enum Token {
     EOF,
     Unknown(char),
 }
+
+/// Comentario (`// ...` o `/* ... */`) que el lexer no emite como token pero
+/// conserva como trivia, p. ej. para que el formateador no lo pierda.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    /// Texto completo, incluidos los delimitadores (sin el salto de línea final).
+    pub text: String,
+    pub span: Span,
+}
+
+impl Comment {
+    /// `true` para los comentarios de bloque `/* ... */`.
+    pub fn is_block(&self) -> bool {
+        self.text.starts_with("/*")
+    }
+}
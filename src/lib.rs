@@ -6,3 +6,8 @@ pub mod codegen;
 pub mod errors;
 pub mod utils;
 pub mod macros;
+pub mod driver;
+pub mod format;
+pub mod lsp;
+pub mod interp;
+pub mod repl;
@@ -0,0 +1,583 @@
+use crate::ast::nodes::{Declaration, Expr, Program, TypeAnnotation, TypeDecl};
+use crate::ast::walk;
+use crate::driver;
+use crate::errors::Diagnostic;
+use crate::parser::Parser;
+use crate::semantic::types::Type;
+use crate::semantic::{analyze_program, Analysis, Context};
+use crate::utils::{Position, Span, Spanned};
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+const KEYWORDS: &[&str] = &[
+    "let", "in", "if", "elif", "else", "while", "for", "function", "type", "inherits",
+    "protocol", "extends", "variant", "new", "is", "as", "self", "base", "def", "match", "case",
+    "default", "try", "catch", "throw", "break", "continue", "return", "true", "false",
+];
+
+const BUILTINS: &[(&str, &str)] = &[
+    ("print", "print(value: Object): Object"),
+    ("sqrt", "sqrt(x: Number): Number"),
+    ("sin", "sin(x: Number): Number"),
+    ("cos", "cos(x: Number): Number"),
+    ("exp", "exp(x: Number): Number"),
+    ("log", "log(base: Number, x: Number): Number"),
+    ("rand", "rand(): Number"),
+    ("PI", "PI: Number"),
+    ("E", "E: Number"),
+];
+
+/// Categoría de un símbolo del documento (valores de `SymbolKind` de LSP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class = 5,
+    Method = 6,
+    Field = 8,
+    Enum = 10,
+    Interface = 11,
+    Function = 12,
+    EnumMember = 22,
+}
+
+/// Símbolo del esquema del documento (`textDocument/documentSymbol`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Rango completo de la declaración.
+    pub range: Span,
+    /// Rango del nombre declarado.
+    pub selection: Span,
+    pub children: Vec<Symbol>,
+}
+
+/// Categoría de una sugerencia (valores de `CompletionItemKind` de LSP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Method = 2,
+    Function = 3,
+    Field = 5,
+    Variable = 6,
+    Class = 7,
+    Interface = 8,
+    Keyword = 14,
+}
+
+/// Sugerencia de autocompletado.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+/// Documento abierto en el editor junto con el resultado de analizarlo.
+pub struct Document {
+    pub text: String,
+    line_starts: Vec<usize>,
+    /// Programa tal como se escribió (parcial si hubo errores sintácticos).
+    program: Program,
+    diagnostics: Vec<Diagnostic>,
+    /// Análisis semántico del texto actual; `None` si hubo errores sintácticos.
+    analysis: Option<Analysis>,
+    /// Último análisis completo y el texto del que salió. Permite completar
+    /// miembros mientras se escribe una expresión todavía incompleta.
+    last_good: Option<(String, Analysis)>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let (program, diagnostics, analysis) = analyze(&text);
+        Document {
+            line_starts: line_starts(&text),
+            text,
+            program,
+            diagnostics,
+            analysis,
+            last_good: None,
+        }
+    }
+
+    /// Reemplaza el texto y vuelve a analizarlo, conservando el último análisis válido.
+    pub fn update(&mut self, text: String) {
+        let mut next = Document::new(text);
+        let previous_text = std::mem::take(&mut self.text);
+        next.last_good = match self.analysis.take() {
+            Some(analysis) => Some((previous_text, analysis)),
+            None => self.last_good.take(),
+        };
+        *self = next;
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Desplazamiento en bytes de una posición LSP (línea base 0, columna en UTF-16).
+    pub fn offset_at(&self, line: usize, character: usize) -> usize {
+        let Some(&start) = self.line_starts.get(line) else { return self.text.len() };
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.text.len());
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= character || c == '\n' || c == '\r' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+
+    /// Posición LSP (línea base 0, columna en UTF-16) de un desplazamiento en bytes.
+    pub fn lsp_position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&s| s <= offset).saturating_sub(1);
+        let start = self.line_starts[line];
+        let character = self.text[start..offset].chars().map(char::len_utf16).sum();
+        (line, character)
+    }
+
+    /// Rango a mostrar para `span`: los rangos vacíos se extienden a la palabra que empiezan.
+    pub fn display_range(&self, span: Span) -> (usize, usize) {
+        let start = span.start.offset.min(self.text.len());
+        if !span.is_empty() {
+            return (start, span.end.offset.min(self.text.len()));
+        }
+        let rest = &self.text[start..];
+        let word = rest.find(|c: char| !is_ident(c)).unwrap_or(rest.len());
+        let width = if word > 0 { word } else { rest.chars().next().map_or(0, char::len_utf8) };
+        (start, start + width)
+    }
+
+    /// Texto de ayuda para `textDocument/hover`: la firma de la declaración
+    /// nombrada bajo el cursor o el tipo inferido de la expresión más interna.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let (start, end) = word_at(&self.text, offset)?;
+        let word = &self.text[start..end];
+
+        let member = start > 0 && self.text[..start].ends_with('.');
+        if !member && let Some(signature) = self.declaration_signature(word) {
+            return Some(signature);
+        }
+
+        let analysis = self.analysis.as_ref()?;
+        // El nombre de una variable de `let` tiene el tipo de la variable, no el
+        // de toda la expresión que la contiene
+        if !member && let Some(ty) = self.binding_type(analysis, start, word) {
+            return Some(format!("{}: {}", word, ty));
+        }
+        let (span, ty) = analysis.types.iter()
+            .filter(|(span, _)| !span.is_empty() && span.contains(offset))
+            .min_by_key(|(span, _)| span.len())?;
+        let text = &self.text[span.start.offset..span.end.offset];
+        let type_name = ty.borrow().name.clone();
+        if text.chars().all(is_ident) {
+            Some(format!("{}: {}", text, type_name))
+        } else {
+            Some(type_name)
+        }
+    }
+
+    /// Rango del nombre de la declaración a la que se refiere el identificador
+    /// bajo el cursor (`textDocument/definition`).
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        let (start, end) = word_at(&self.text, offset)?;
+        let word = &self.text[start..end];
+
+        if start > 0 && self.text[..start].ends_with('.') {
+            return self.member_definition(start - 1, word);
+        }
+
+        self.program.declarations.iter().find_map(|decl| match decl {
+            Declaration::Function(f) if f.name == word => Some(name_span(f.pos, &f.name)),
+            Declaration::Type(t) if t.name == word => Some(name_span(t.pos, &t.name)),
+            Declaration::Protocol(p) if p.name == word => Some(name_span(p.pos, &p.name)),
+            Declaration::Macro(m) if m.name == word => Some(name_span(m.pos, &m.name)),
+            Declaration::Variant(v) if v.name == word => Some(name_span(v.pos, &v.name)),
+            Declaration::Variant(v) => v.cases.iter().find(|c| c.name == word).map(|c| name_span(c.pos, &c.name)),
+            _ => None,
+        })
+    }
+
+    /// Esquema del documento: funciones, tipos con sus miembros, protocolos y macros.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        self.program.declarations.iter().map(|decl| match decl {
+            Declaration::Function(f) => leaf(&f.name, SymbolKind::Function, f.pos, Some(&f.body)),
+            Declaration::Macro(m) => leaf(&m.name, SymbolKind::Function, m.pos, Some(&m.body)),
+            Declaration::Type(t) => {
+                let mut children: Vec<Symbol> = t.attributes.iter()
+                    .map(|a| leaf(&a.name, SymbolKind::Field, a.pos, Some(&a.init)))
+                    .collect();
+                children.extend(t.methods.iter().map(|m| leaf(&m.name, SymbolKind::Method, m.pos, Some(&m.body))));
+                container(&t.name, SymbolKind::Class, t.pos, children)
+            }
+            Declaration::Protocol(p) => {
+                let children = p.methods.iter()
+                    .map(|m| leaf(&m.name, SymbolKind::Method, m.pos, None))
+                    .collect();
+                container(&p.name, SymbolKind::Interface, p.pos, children)
+            }
+            Declaration::Variant(v) => {
+                let children = v.cases.iter()
+                    .map(|c| leaf(&c.name, SymbolKind::EnumMember, c.pos, None))
+                    .collect();
+                container(&v.name, SymbolKind::Enum, v.pos, children)
+            }
+        }).collect()
+    }
+
+    /// Sugerencias en `offset`: miembros del tipo del receptor tras un `.`,
+    /// o palabras clave, funciones, tipos y variables visibles en otro caso.
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let offset = offset.min(self.text.len());
+        let prefix_start = self.text[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_ident(*c))
+            .last()
+            .map_or(offset, |(i, _)| i);
+
+        if prefix_start > 0 && self.text[..prefix_start].ends_with('.') {
+            return self.member_completions(prefix_start - 1);
+        }
+
+        let mut items: Vec<Completion> = KEYWORDS.iter()
+            .map(|k| completion(k, CompletionKind::Keyword, None))
+            .collect();
+        items.extend(BUILTINS.iter().map(|(name, sig)| completion(name, CompletionKind::Function, Some(sig.to_string()))));
+        for decl in &self.program.declarations {
+            match decl {
+                Declaration::Function(f) => items.push(completion(&f.name, CompletionKind::Function, self.declaration_signature(&f.name))),
+                Declaration::Macro(m) => items.push(completion(&m.name, CompletionKind::Function, None)),
+                Declaration::Type(t) => items.push(completion(&t.name, CompletionKind::Class, None)),
+                Declaration::Protocol(p) => items.push(completion(&p.name, CompletionKind::Interface, None)),
+                Declaration::Variant(v) => {
+                    items.push(completion(&v.name, CompletionKind::Class, None));
+                    items.extend(v.cases.iter().map(|c| completion(&c.name, CompletionKind::Class, None)));
+                }
+            }
+        }
+        for name in ["Number", "String", "Boolean", "Object"] {
+            items.push(completion(name, CompletionKind::Class, None));
+        }
+        let mut seen = HashSet::new();
+        for name in locals_at(&self.program, offset) {
+            if seen.insert(name.clone()) {
+                items.push(completion(&name, CompletionKind::Variable, None));
+            }
+        }
+        items
+    }
+
+    // ── Auxiliares ──────────────────────────────────────────────────────────
+
+    /// Firma de la función, tipo o protocolo declarado con nombre `name`.
+    fn declaration_signature(&self, name: &str) -> Option<String> {
+        let context = self.context();
+        for decl in &self.program.declarations {
+            match decl {
+                Declaration::Function(f) if f.name == name => {
+                    let resolved = context.and_then(|c| c.functions.get(name));
+                    let params: Vec<String> = f.params.iter().enumerate().map(|(i, p)| {
+                        match resolved {
+                            Some((types, _)) => format!("{}: {}", p.name, types[i].borrow().name),
+                            None => p.name.clone(),
+                        }
+                    }).collect();
+                    let ret = resolved.map(|(_, r)| format!(": {}", r.borrow().name)).unwrap_or_default();
+                    return Some(format!("function {}({}){}", f.name, params.join(", "), ret));
+                }
+                Declaration::Type(t) if t.name == name => {
+                    let ty = context.and_then(|c| c.types.get(name));
+                    let params: Vec<String> = match ty {
+                        Some(ty) => ty.borrow().params.iter().map(|(n, t)| format!("{}: {}", n, t.borrow().name)).collect(),
+                        None => t.params.iter().map(|p| p.name.clone()).collect(),
+                    };
+                    let params = if params.is_empty() { String::new() } else { format!("({})", params.join(", ")) };
+                    let parent = t.parent.as_ref().map(|p| format!(" inherits {}", p.name)).unwrap_or_default();
+                    return Some(format!("type {}{}{}", t.name, params, parent));
+                }
+                Declaration::Protocol(p) if p.name == name => {
+                    let parent = p.parent.as_ref().map(|p| format!(" extends {}", p)).unwrap_or_default();
+                    return Some(format!("protocol {}{}", p.name, parent));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Tipo de la variable de `let` cuyo nombre, `word`, empieza en `start`: el
+    /// anotado o, sin anotación, el inferido para su inicializador.
+    fn binding_type(&self, analysis: &Analysis, start: usize, word: &str) -> Option<String> {
+        let (annotation, init) = walk::root_exprs(&self.program).into_iter()
+            .find_map(|root| find_binding(root, start, word))?;
+        if let Some(annotation) = annotation {
+            let resolved = analysis.context.resolve_type(annotation).ok();
+            return Some(resolved.map_or_else(|| annotation.to_string(), |ty| ty.borrow().name.clone()));
+        }
+        analysis.types.iter()
+            .find(|(span, _)| *span == init.span())
+            .map(|(_, ty)| ty.borrow().name.clone())
+    }
+
+    fn context(&self) -> Option<&Context> {
+        self.analysis.as_ref().or(self.last_good.as_ref().map(|(_, a)| a)).map(|a| &a.context)
+    }
+
+    /// Tipo del receptor que termina justo antes del `.` en `dot`.
+    fn receiver_type(&self, dot: usize) -> Option<(Rc<RefCell<Type>>, bool)> {
+        let receiver_start = self.text[..dot]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_ident(*c))
+            .last()
+            .map(|(i, _)| i)?;
+        let receiver = &self.text[receiver_start..dot];
+
+        if receiver == "self" {
+            let ty = self.enclosing_type(dot)?;
+            return self.context()?.types.get(&ty.name).cloned().map(|t| (t, true));
+        }
+
+        // Tipo inferido de la última aparición de `receiver` antes del cursor
+        // (en el análisis actual o, si el texto no compila, en el último válido)
+        let lookup = |text: &str, analysis: &Analysis, limit: usize| {
+            analysis.types.iter()
+                .filter(|(span, _)| span.end.offset <= limit && text.get(span.start.offset..span.end.offset) == Some(receiver))
+                .max_by_key(|(span, _)| span.start.offset)
+                .map(|(_, ty)| ty.clone())
+        };
+        let current = self.analysis.as_ref().and_then(|a| lookup(&self.text, a, dot));
+        let ty = current.or_else(|| {
+            let (text, analysis) = self.last_good.as_ref()?;
+            lookup(text, analysis, text.len())
+        })?;
+        Some((ty, false))
+    }
+
+    /// Declaración de tipo que contiene `offset` (la última que empieza antes).
+    fn enclosing_type(&self, offset: usize) -> Option<&TypeDecl> {
+        match self.program.declarations.iter().rev().find(|d| decl_pos(d).offset < offset)? {
+            Declaration::Type(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    fn member_completions(&self, dot: usize) -> Vec<Completion> {
+        let Some((ty, is_self)) = self.receiver_type(dot) else { return Vec::new() };
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        let mut current = Some(ty);
+        while let Some(t) = current {
+            let t = t.borrow();
+            let mut methods: Vec<_> = t.methods.values().collect();
+            methods.sort_by(|a, b| a.name.cmp(&b.name));
+            for m in methods {
+                if seen.insert(m.name.clone()) {
+                    let params: Vec<String> = m.params.iter().map(|(n, t)| format!("{}: {}", n, t.borrow().name)).collect();
+                    let detail = format!("{}({}): {}", m.name, params.join(", "), m.return_type.borrow().name);
+                    items.push(completion(&m.name, CompletionKind::Method, Some(detail)));
+                }
+            }
+            // Los atributos son privados: solo se ofrecen sobre `self`
+            if is_self {
+                let mut attributes: Vec<_> = t.attributes.iter().collect();
+                attributes.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, attr_ty) in attributes {
+                    if seen.insert(name.clone()) {
+                        items.push(completion(name, CompletionKind::Field, Some(attr_ty.borrow().name.clone())));
+                    }
+                }
+            }
+            current = t.parent.clone();
+        }
+        items
+    }
+
+    fn member_definition(&self, dot: usize, member: &str) -> Option<Span> {
+        let declares = |t: &TypeDecl| {
+            t.methods.iter().find(|m| m.name == member).map(|m| name_span(m.pos, &m.name))
+                .or_else(|| t.attributes.iter().find(|a| a.name == member).map(|a| name_span(a.pos, &a.name)))
+        };
+        let type_decl = |name: &str| self.program.declarations.iter().find_map(|d| match d {
+            Declaration::Type(t) if t.name == name => Some(t),
+            _ => None,
+        });
+
+        // Subir por la jerarquía del receptor hasta el tipo que declara el miembro
+        if let Some((ty, _)) = self.receiver_type(dot) {
+            let mut current = Some(ty);
+            while let Some(t) = current {
+                if let Some(span) = type_decl(&t.borrow().name).and_then(declares) {
+                    return Some(span);
+                }
+                current = t.borrow().parent.clone();
+            }
+        }
+        self.program.declarations.iter().find_map(|d| match d {
+            Declaration::Type(t) => declares(t),
+            _ => None,
+        })
+    }
+}
+
+/// Parsea (con recuperación) y, si no hay errores sintácticos, expande y chequea el programa.
+fn analyze(text: &str) -> (Program, Vec<Diagnostic>, Option<Analysis>) {
+    let (program, parse_errors) = Parser::new(text).parse_program_recovering();
+    if !parse_errors.is_empty() {
+        let diagnostics = parse_errors.iter().map(Diagnostic::from).collect();
+        return (program, diagnostics, None);
+    }
+
+    // Un fallo interno del chequeo no debe tumbar el servidor, pero queda en
+    // stderr (el log del servidor para el editor)
+    let analysis = match panic::catch_unwind(AssertUnwindSafe(|| {
+        analyze_program(&driver::expand_program(program.clone()))
+    })) {
+        Ok(analysis) => Some(analysis),
+        Err(payload) => {
+            eprintln!("hulk-lsp: internal checker error: {}", panic_message(&*payload));
+            None
+        }
+    };
+    let diagnostics = analysis.iter().flat_map(|a| a.errors.iter().map(Diagnostic::from)).collect();
+    (program, diagnostics, analysis)
+}
+
+/// Texto de un pánico atrapado: el de `panic!` si lo tiene.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error")
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Rango en bytes del identificador que contiene (o termina en) `offset`.
+fn word_at(text: &str, offset: usize) -> Option<(usize, usize)> {
+    let offset = offset.min(text.len());
+    if !text.is_char_boundary(offset) {
+        return None;
+    }
+    let start = text[..offset].char_indices().rev()
+        .take_while(|(_, c)| is_ident(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..].find(|c: char| !is_ident(c)).map_or(text.len(), |i| offset + i);
+    (start < end).then_some((start, end))
+}
+
+/// Rango del nombre `name` declarado en `pos`.
+fn name_span(pos: Position, name: &str) -> Span {
+    let end = Position::new(pos.line, pos.column + name.chars().count(), pos.offset + name.len());
+    Span::new(pos, end)
+}
+
+fn decl_pos(decl: &Declaration) -> Position {
+    match decl {
+        Declaration::Function(f) => f.pos,
+        Declaration::Type(t) => t.pos,
+        Declaration::Protocol(p) => p.pos,
+        Declaration::Macro(m) => m.pos,
+        Declaration::Variant(v) => v.pos,
+    }
+}
+
+fn leaf(name: &str, kind: SymbolKind, pos: Position, body: Option<&Spanned<Expr>>) -> Symbol {
+    let selection = name_span(pos, name);
+    let range = match body {
+        Some(body) if body.end.offset > selection.end.offset => selection.to(body.span()),
+        _ => selection,
+    };
+    Symbol { name: name.to_string(), kind, range, selection, children: Vec::new() }
+}
+
+fn container(name: &str, kind: SymbolKind, pos: Position, children: Vec<Symbol>) -> Symbol {
+    let selection = name_span(pos, name);
+    let range = children.iter()
+        .map(|c| c.range)
+        .filter(|r| r.end.offset > selection.end.offset)
+        .max_by_key(|r| r.end.offset)
+        .map_or(selection, |r| selection.to(r));
+    Symbol { name: name.to_string(), kind, range, selection, children }
+}
+
+fn completion(label: &str, kind: CompletionKind, detail: Option<String>) -> Completion {
+    Completion { label: label.to_string(), kind, detail }
+}
+
+/// Anotación e inicializador de la variable de `let` llamada `name` cuyo nombre
+/// empieza en `start`: entre el `let` (o el inicializador anterior) y su propio
+/// inicializador.
+fn find_binding<'a>(expr: &'a Spanned<Expr>, start: usize, name: &str) -> Option<(Option<&'a TypeAnnotation>, &'a Spanned<Expr>)> {
+    if !expr.span().contains(start) {
+        return None;
+    }
+    if let Expr::Let { bindings, .. } = &expr.node {
+        let mut from = expr.pos.offset;
+        for (binder, annotation, init) in bindings {
+            if (from..init.pos.offset).contains(&start) {
+                return (binder == name).then_some((annotation.as_ref(), init));
+            }
+            from = init.end.offset;
+        }
+    }
+    walk::children(&expr.node).into_iter().find_map(|child| find_binding(child, start, name))
+}
+
+/// Parámetros y variables (`let`, lambdas, `for`, generadores) visibles en `offset`.
+fn locals_at(program: &Program, offset: usize) -> Vec<String> {
+    let mut names = Vec::new();
+    for decl in &program.declarations {
+        match decl {
+            Declaration::Function(f) if f.body.span().contains(offset) => {
+                names.extend(f.params.iter().map(|p| p.name.clone()));
+            }
+            Declaration::Type(t) => {
+                if t.attributes.iter().any(|a| a.init.span().contains(offset)) {
+                    names.extend(t.params.iter().map(|p| p.name.clone()));
+                }
+                for m in t.methods.iter().filter(|m| m.body.span().contains(offset)) {
+                    names.extend(m.params.iter().map(|p| p.name.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+    for root in walk::root_exprs(program) {
+        collect_locals(root, offset, &mut names);
+    }
+    names
+}
+
+fn collect_locals(expr: &Spanned<Expr>, offset: usize, names: &mut Vec<String>) {
+    match &expr.node {
+        Expr::Let { bindings, .. } if expr.span().contains(offset) => {
+            // Cada variable es visible a partir del final de su inicializador
+            names.extend(bindings.iter()
+                .filter(|(_, _, value)| value.end.offset <= offset)
+                .map(|(name, _, _)| name.clone()));
+        }
+        Expr::Lambda { params, body, .. } if body.span().contains(offset) => {
+            names.extend(params.iter().map(|p| p.name.clone()));
+        }
+        Expr::For { var, body, .. } if body.span().contains(offset) => names.push(var.clone()),
+        Expr::VectorGenerator { expr: e, var, .. } if e.span().contains(offset) => names.push(var.clone()),
+        _ => {}
+    }
+    for child in walk::children(&expr.node) {
+        collect_locals(child, offset, names);
+    }
+}
@@ -0,0 +1,264 @@
+//! Servidor del protocolo LSP (Language Server Protocol) para HULK.
+//!
+//! Reutiliza el parser con recuperación, la expansión de macros y el chequeo
+//! semántico para ofrecer diagnósticos, tipos al pasar el cursor, ir a la
+//! definición, esquema del documento y autocompletado de miembros.
+//! La comunicación es JSON-RPC sobre stdin/stdout (ver `transport`).
+
+mod document;
+mod transport;
+
+#[cfg(test)]
+mod tests;
+
+pub use document::{Completion, CompletionKind, Document, Symbol, SymbolKind};
+pub use transport::{read_message, write_message};
+
+use crate::errors::{Diagnostic, Severity};
+use crate::utils::Span;
+use transport::read_frame;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+const PARSE_ERROR: i64 = -32700;
+
+/// Estado del servidor: documentos abiertos indexados por URI.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown_requested: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+
+    /// Código de salida si el cliente envió `exit` (0 tras `shutdown`, 1 si no).
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    pub fn document(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    /// Procesa un mensaje del cliente y devuelve las respuestas y
+    /// notificaciones que hay que enviarle, en orden.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        match message.get("id") {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, msg)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": msg },
+                    }),
+                };
+                vec![response]
+            }
+            None => self.notification(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        if self.shutdown_requested && method != "shutdown" {
+            return Err((INVALID_REQUEST, "server is shutting down".to_string()));
+        }
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "hulk-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => Ok(self.at_position(params, |doc, offset| {
+                doc.hover(offset).map_or(Value::Null, |text| json!({
+                    "contents": { "kind": "markdown", "value": format!("```hulk\n{}\n```", text) },
+                }))
+            })),
+            "textDocument/definition" => {
+                let uri = params["textDocument"]["uri"].clone();
+                Ok(self.at_position(params, |doc, offset| {
+                    doc.definition(offset).map_or(Value::Null, |span| json!({
+                        "uri": uri,
+                        "range": range(doc, span),
+                    }))
+                }))
+            }
+            "textDocument/documentSymbol" => {
+                let symbols = self.documents.get(uri_of(params))
+                    .map(|doc| doc.symbols().iter().map(|s| symbol(doc, s)).collect())
+                    .unwrap_or_default();
+                Ok(Value::Array(symbols))
+            }
+            "textDocument/completion" => Ok(self.at_position(params, |doc, offset| {
+                doc.completions(offset).into_iter().map(|c| {
+                    let mut item = json!({ "label": c.label, "kind": c.kind as u8 });
+                    if let Some(detail) = c.detail {
+                        item["detail"] = json!(detail);
+                    }
+                    item
+                }).collect()
+            })),
+            _ => Err((METHOD_NOT_FOUND, format!("method not found: {}", method))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        match method {
+            "textDocument/didOpen" => {
+                let uri = uri_of(params).to_string();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                vec![self.publish(&uri)]
+            }
+            "textDocument/didChange" => {
+                let uri = uri_of(params).to_string();
+                // Sincronización completa: el último cambio trae el texto entero
+                let Some(text) = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                match self.documents.get_mut(&uri) {
+                    Some(doc) => doc.update(text.to_string()),
+                    None => {
+                        self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                    }
+                }
+                vec![self.publish(&uri)]
+            }
+            "textDocument/didClose" => {
+                let uri = uri_of(params).to_string();
+                self.documents.remove(&uri);
+                vec![publish_diagnostics(&uri, Vec::new())]
+            }
+            "exit" => {
+                self.exit_code = Some(if self.shutdown_requested { 0 } else { 1 });
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Aplica `f` al documento y al desplazamiento de `params.position`.
+    fn at_position(&self, params: &Value, f: impl FnOnce(&Document, usize) -> Value) -> Value {
+        let Some(doc) = self.documents.get(uri_of(params)) else { return Value::Null };
+        let line = params["position"]["line"].as_u64().unwrap_or(0) as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or(0) as usize;
+        f(doc, doc.offset_at(line, character))
+    }
+
+    fn publish(&self, uri: &str) -> Value {
+        let doc = &self.documents[uri];
+        let diagnostics = doc.diagnostics().iter().map(|d| diagnostic(doc, d)).collect();
+        publish_diagnostics(uri, diagnostics)
+    }
+}
+
+/// Atiende a un cliente hasta que envía `exit` o cierra la entrada.
+/// Devuelve el código de salida del proceso.
+pub fn run(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<i32> {
+    let mut server = Server::new();
+    while let Some(body) = read_frame(reader)? {
+        // Un cuerpo que no es JSON se contesta con un error y se sigue atendiendo
+        let replies = match serde_json::from_slice(&body) {
+            Ok(message) => server.handle(&message),
+            Err(e) => vec![json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": { "code": PARSE_ERROR, "message": format!("parse error: {}", e) },
+            })],
+        };
+        for reply in replies {
+            write_message(writer, &reply)?;
+        }
+        if let Some(code) = server.exit_code() {
+            return Ok(code);
+        }
+    }
+    Ok(1)
+}
+
+/// Punto de entrada del binario `hulk-lsp`: atiende al cliente por stdin/stdout.
+pub fn run_stdio() -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match run(&mut stdin.lock(), &mut stdout.lock()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("hulk-lsp: {}", e);
+            1
+        }
+    }
+}
+
+fn uri_of(params: &Value) -> &str {
+    params["textDocument"]["uri"].as_str().unwrap_or_default()
+}
+
+fn position(doc: &Document, offset: usize) -> Value {
+    let (line, character) = doc.lsp_position(offset);
+    json!({ "line": line, "character": character })
+}
+
+fn range(doc: &Document, span: Span) -> Value {
+    let (start, end) = doc.display_range(span);
+    json!({ "start": position(doc, start), "end": position(doc, end) })
+}
+
+fn symbol(doc: &Document, symbol: &Symbol) -> Value {
+    json!({
+        "name": symbol.name,
+        "kind": symbol.kind as u8,
+        "range": range(doc, symbol.range),
+        "selectionRange": range(doc, symbol.selection),
+        "children": symbol.children.iter().map(|c| self::symbol(doc, c)).collect::<Vec<_>>(),
+    })
+}
+
+fn diagnostic(doc: &Document, d: &Diagnostic) -> Value {
+    let mut message = d.message.clone();
+    for note in &d.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    if let Some(help) = &d.help {
+        message.push_str(&format!("\nhelp: {}", help));
+    }
+    json!({
+        "range": range(doc, d.span),
+        "severity": match d.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "code": d.code,
+        "source": "hulk",
+        "message": message,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
@@ -0,0 +1,241 @@
+use super::*;
+use serde_json::json;
+use std::io::Cursor;
+
+const URI: &str = "file:///main.hulk";
+
+const PROGRAM: &str = "\
+type Point(x: Number, y: Number) {
+    x = x;
+    y = y;
+    norm(): Number => sqrt(self.x * self.x + self.y * self.y);
+}
+
+function twice(n: Number): Number => n * 2;
+
+let p = new Point(3, 4) in print(twice(p.norm()));
+";
+
+fn open(text: &str) -> (Server, Vec<Value>) {
+    let mut server = Server::new();
+    let replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": { "textDocument": { "uri": URI, "languageId": "hulk", "version": 1, "text": text } },
+    }));
+    (server, replies)
+}
+
+fn request(server: &mut Server, method: &str, line: usize, character: usize) -> Value {
+    let replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": URI },
+            "position": { "line": line, "character": character },
+        },
+    }));
+    assert_eq!(replies.len(), 1);
+    replies[0]["result"].clone()
+}
+
+fn labels(completions: &Value) -> Vec<String> {
+    completions.as_array().unwrap().iter()
+        .map(|c| c["label"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn initialize_advertises_capabilities() {
+    let mut server = Server::new();
+    let replies = server.handle(&json!({ "jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {} }));
+    let caps = &replies[0]["result"]["capabilities"];
+    assert_eq!(caps["textDocumentSync"], 1);
+    assert_eq!(caps["hoverProvider"], true);
+    assert_eq!(caps["definitionProvider"], true);
+    assert_eq!(caps["documentSymbolProvider"], true);
+    assert_eq!(caps["completionProvider"]["triggerCharacters"], json!(["."]));
+}
+
+#[test]
+fn unknown_request_is_method_not_found() {
+    let mut server = Server::new();
+    let replies = server.handle(&json!({ "jsonrpc": "2.0", "id": 7, "method": "workspace/foo" }));
+    assert_eq!(replies[0]["id"], 7);
+    assert_eq!(replies[0]["error"]["code"], -32601);
+}
+
+#[test]
+fn valid_document_publishes_no_diagnostics() {
+    let (_, replies) = open(PROGRAM);
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn diagnostics_for_parse_and_semantic_errors() {
+    let (_, replies) = open("let x = in 1;\n");
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert!(!diagnostics.is_empty());
+    assert_eq!(diagnostics[0]["source"], "hulk");
+    assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 8 }));
+
+    let (mut server, replies) = open("print(y);");
+    let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0206");
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 6 }));
+    assert_eq!(diagnostics[0]["range"]["end"], json!({ "line": 0, "character": 7 }));
+
+    // Al corregir el texto, los diagnósticos se vacían
+    let replies = server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "print(1);" }] },
+    }));
+    assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn hover_shows_inferred_types_and_signatures() {
+    let (mut server, _) = open(PROGRAM);
+
+    // `p` en `p.norm()`
+    let hover = request(&mut server, "textDocument/hover", 8, 40);
+    assert_eq!(hover["contents"]["value"], "```hulk\np: Point\n```");
+
+    // `twice` en la llamada
+    let hover = request(&mut server, "textDocument/hover", 8, 33);
+    assert_eq!(hover["contents"]["value"], "```hulk\nfunction twice(n: Number): Number\n```");
+
+    // `Point` en la declaración
+    let hover = request(&mut server, "textDocument/hover", 0, 6);
+    assert_eq!(hover["contents"]["value"], "```hulk\ntype Point(x: Number, y: Number)\n```");
+}
+
+#[test]
+fn hover_on_a_let_binder_shows_the_binding_type() {
+    let (mut server, _) = open("let x = 5, s: Object = \"a\" in { print(s); new Object(); };");
+
+    // `x` en `let x = 5`: el tipo de la variable, no el de todo el `let`
+    let hover = request(&mut server, "textDocument/hover", 0, 4);
+    assert_eq!(hover["contents"]["value"], "```hulk\nx: Number\n```");
+
+    // `s` con anotación: el tipo anotado
+    let hover = request(&mut server, "textDocument/hover", 0, 11);
+    assert_eq!(hover["contents"]["value"], "```hulk\ns: Object\n```");
+}
+
+#[test]
+fn definition_of_functions_types_and_methods() {
+    let (mut server, _) = open(PROGRAM);
+
+    let def = request(&mut server, "textDocument/definition", 8, 33);
+    assert_eq!(def["uri"], URI);
+    assert_eq!(def["range"]["start"], json!({ "line": 6, "character": 9 }));
+    assert_eq!(def["range"]["end"], json!({ "line": 6, "character": 14 }));
+
+    let def = request(&mut server, "textDocument/definition", 8, 13);
+    assert_eq!(def["range"]["start"], json!({ "line": 0, "character": 5 }));
+
+    let def = request(&mut server, "textDocument/definition", 8, 43);
+    assert_eq!(def["range"]["start"], json!({ "line": 3, "character": 4 }));
+}
+
+#[test]
+fn document_symbols_nest_members() {
+    let (mut server, _) = open(PROGRAM);
+    let symbols = request(&mut server, "textDocument/documentSymbol", 0, 0);
+    let symbols = symbols.as_array().unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[0]["name"], "Point");
+    assert_eq!(symbols[0]["kind"], SymbolKind::Class as u8);
+    let members: Vec<&str> = symbols[0]["children"].as_array().unwrap().iter()
+        .map(|c| c["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(members, ["x", "y", "norm"]);
+    assert_eq!(symbols[1]["name"], "twice");
+    assert_eq!(symbols[1]["kind"], SymbolKind::Function as u8);
+    assert_eq!(symbols[1]["selectionRange"]["start"], json!({ "line": 6, "character": 9 }));
+}
+
+#[test]
+fn completion_of_members_after_dot() {
+    // `self.` dentro de un método ofrece atributos y métodos
+    let (mut server, _) = open(PROGRAM);
+    let items = labels(&request(&mut server, "textDocument/completion", 3, 32));
+    assert!(items.contains(&"x".to_string()));
+    assert!(items.contains(&"y".to_string()));
+    assert!(items.contains(&"norm".to_string()));
+
+    // `p.` sobre un valor de tipo `Point` solo ofrece métodos, aunque el texto
+    // todavía no compile (se usa el último análisis válido)
+    let text = PROGRAM.replace("print(twice(p.norm()))", "print(p.)");
+    server.handle(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": { "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text }] },
+    }));
+    let items = labels(&request(&mut server, "textDocument/completion", 8, 35));
+    assert_eq!(items, ["norm"]);
+}
+
+#[test]
+fn completion_of_names_in_scope() {
+    let (mut server, _) = open(PROGRAM);
+    let items = labels(&request(&mut server, "textDocument/completion", 8, 27));
+    for name in ["p", "twice", "Point", "print", "let"] {
+        assert!(items.contains(&name.to_string()), "missing {}", name);
+    }
+    assert!(!items.contains(&"n".to_string()));
+}
+
+#[test]
+fn run_speaks_over_streams() {
+    let mut input = Vec::new();
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ] {
+        write_message(&mut input, &message).unwrap();
+    }
+
+    let mut output = Vec::new();
+    let code = run(&mut Cursor::new(input), &mut output).unwrap();
+    assert_eq!(code, 0);
+
+    let mut reader = Cursor::new(output);
+    let first = read_message(&mut reader).unwrap().unwrap();
+    assert_eq!(first["id"], 1);
+    let second = read_message(&mut reader).unwrap().unwrap();
+    assert_eq!(second["id"], 2);
+    assert_eq!(second["result"], Value::Null);
+    assert!(read_message(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn malformed_message_gets_a_parse_error_and_the_server_keeps_going() {
+    let mut input = Vec::new();
+    let garbage = "{ not json";
+    write!(input, "Content-Length: {}\r\n\r\n{}", garbage.len(), garbage).unwrap();
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ] {
+        write_message(&mut input, &message).unwrap();
+    }
+
+    let mut output = Vec::new();
+    let code = run(&mut Cursor::new(input), &mut output).unwrap();
+    assert_eq!(code, 0);
+
+    let mut reader = Cursor::new(output);
+    let error = read_message(&mut reader).unwrap().unwrap();
+    assert_eq!(error["id"], Value::Null);
+    assert_eq!(error["error"]["code"], -32700);
+    let reply = read_message(&mut reader).unwrap().unwrap();
+    assert_eq!(reply["id"], 1);
+}
@@ -0,0 +1,48 @@
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Lee un mensaje JSON-RPC con el encabezado `Content-Length` de LSP.
+/// Devuelve `Ok(None)` cuando el cliente cierra la entrada.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    match read_frame(reader)? {
+        Some(body) => serde_json::from_slice(&body).map(Some).map_err(|e| invalid(&e.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Lee el cuerpo de un mensaje sin interpretarlo, para que un JSON mal
+/// formado no desincronice la lectura de los siguientes.
+pub fn read_frame(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| invalid("missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+/// Escribe un mensaje JSON-RPC con su encabezado `Content-Length`.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
@@ -1,7 +1,7 @@
 use crate::ast::nodes::{Declaration, Expr, ExprVisitor, MacroDecl, MacroParam, Pattern, Program};
 use crate::utils::{Position, Spanned};
 use std::collections::HashMap;
-use super::visitors::{MacroExpansionVisitor, SanitizationVisitor, SubstitutionVisitor};
+use super::visitors::{MacroExpansionVisitor, MatchResolutionVisitor, SanitizationVisitor, SubstitutionVisitor};
 use super::utils::gensym;
 
 /// Contexto de expansión de macro
@@ -120,6 +120,9 @@ impl MacroExpansionContext {
         // Clonar y sanitizar body de la macro
         let mut expanded_body = self.sanitize_expr(&macro_decl.body);
 
+        // Resolver sus `match` contra el AST de los argumentos
+        expanded_body = MatchResolutionVisitor::new(self).visit_expr(expanded_body);
+
         // Aplicar sustituciones
         expanded_body = self.apply_substitutions(expanded_body);
 
@@ -6,10 +6,7 @@ use super::utils::gensym;
 
 // Helper para crear Spanned 
 fn span<T>(node: T, line: usize, column: usize) -> Spanned<T> {
-    Spanned {
-        node,
-        pos: Position { line, column },
-    }
+    Spanned::new(node, Position { line, column, offset: 0 })
 }
 
 #[test]
@@ -193,10 +193,12 @@ impl<'a> ExprVisitor for SanitizationVisitor<'a> {
             let mut inner_scope = self.scope.clone();
             let new_pattern = self.context.sanitize_pattern(&case.pattern, &mut inner_scope);
             let mut inner_visitor = SanitizationVisitor::with_scope(self.context, inner_scope);
+            let new_guard = case.guard.map(|g| inner_visitor.visit_expr(g));
             let new_case_expr = inner_visitor.visit_expr(case.expr);
             
             MatchCase {
                 pattern: new_pattern,
+                guard: new_guard,
                 expr: new_case_expr,
             }
         }).collect();
@@ -266,48 +268,53 @@ impl<'a> ExprVisitor for MacroExpansionVisitor<'a> {
             args: expanded_args,
         }
     }
-    
+}
+
+/// Visitor que resuelve en compilación los `match` del cuerpo de una macro:
+/// cada uno se reemplaza por el primer caso cuyo patrón encaja con el AST del
+/// argumento. Los `match` del código del usuario no pasan por aquí y se
+/// evalúan en ejecución.
+pub struct MatchResolutionVisitor<'a> {
+    pub context: &'a mut MacroExpansionContext,
+}
+
+impl<'a> MatchResolutionVisitor<'a> {
+    pub fn new(context: &'a mut MacroExpansionContext) -> Self {
+        MatchResolutionVisitor { context }
+    }
+}
+
+impl<'a> ExprVisitor for MatchResolutionVisitor<'a> {
     fn visit_match(&mut self, expr: Spanned<Expr>, cases: Vec<MatchCase>, 
                   default: Option<Box<Spanned<Expr>>>, _pos: Position) -> Expr {
-        let expanded_expr = self.visit_expr(expr);
+        let scrutinee = MacroExpansionVisitor::new(self.context).visit_expr(expr.clone());
         
-        // Intentar Pattern Matching en tiempo de compilación
+        // Una guarda solo se conoce en ejecución: esos casos no se resuelven aquí
         for case in &cases {
-            if let Some(bindings) = self.context.pattern_match(&case.pattern, &expanded_expr) {
-                // Guardar contexto
-                let old_substitutions = self.context.substitutions.clone();
-                let old_expr_substitutions = self.context.expr_substitutions.clone();
-
-                // Inyectar bindings
+            if case.guard.is_none()
+                && let Some(bindings) = self.context.pattern_match(&case.pattern, &scrutinee)
+            {
+                // Los nombres de los patrones ya son únicos (gensym), así que
+                // los bindings pueden quedarse hasta que termine la expansión
                 self.context.expr_substitutions.extend(bindings);
-                
-                // Expandir el cuerpo del caso
-                let result = self.visit_expr(case.expr.clone());
-
-                // Restaurar contexto
-                self.context.substitutions = old_substitutions;
-                self.context.expr_substitutions = old_expr_substitutions;
-
-                return result.node;
+                return self.visit_expr(case.expr.clone()).node;
             }
         }
         
-        // Si hay default y no hubo match, expandirlo
         if let Some(def) = default {
             return self.visit_expr(*def).node;
         }
 
         // Fallback: generar nodo Match para runtime
-        let expanded_cases = cases.into_iter()
-            .map(|case| MatchCase {
-                pattern: case.pattern,
-                expr: self.visit_expr(case.expr),
-            })
-            .collect();
-
         Expr::Match {
-            expr: Box::new(expanded_expr),
-            cases: expanded_cases,
+            expr: Box::new(expr),
+            cases: cases.into_iter()
+                .map(|case| MatchCase {
+                    pattern: case.pattern,
+                    guard: case.guard.map(|g| self.visit_expr(g)),
+                    expr: self.visit_expr(case.expr),
+                })
+                .collect(),
             default: None,
         }
     }
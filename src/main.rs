@@ -1,60 +1,272 @@
-use hulk_compiler::parser::Parser;
-use hulk_compiler::macros::expand_macros;
-use hulk_compiler::ast::optimize::optimize_program;
-use hulk_compiler::ast::transform::transform_implicit_functors;
-use hulk_compiler::codegen::{CodeGenerator, llvm_target::LlvmGenerator};
+use hulk_compiler::driver::{self, Backend, CompileError, Emit};
+use hulk_compiler::errors::Diagnostic;
+use hulk_compiler::{format, interp, repl};
 use std::io::{self, Read};
-use std::env;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage:
+  hulk build <file> [-o <path>] [--emit=<stage>]
+  hulk check <file>
+  hulk run <file> [--backend=<backend>]
+  hulk fmt <file> [-o <path>] [--check]
+  hulk repl
+
+Stages for --emit: tokens|ast|expanded|optimized|llvm
+Options taking a value also accept it as the next argument (--emit llvm).
+Backends for --backend: llvm|interp (default llvm, which needs clang)
+Use '-' as <file> to read the program from stdin.
+'hulk fmt' rewrites <file> in place (stdin is formatted to stdout);
+with --check it only reports whether the file is formatted.
+'hulk repl' evaluates expressions and declarations interactively.";
+
+/// Subcomandos que leen un programa (todos menos `repl`).
+const COMMANDS: [&str; 4] = ["build", "check", "run", "fmt"];
+
+/// Argumentos comunes a todos los subcomandos.
+struct Options {
+    input: String,
+    output: Option<PathBuf>,
+    emit: Option<Emit>,
+    backend: Option<Backend>,
+    check: bool,
+}
 
 fn main() {
-    // Leer input de stdin o argumentos
-    let input = if env::args().len() > 1 {
-        let arg = env::args().nth(1).unwrap();
-        // Intentar leer como archivo, si falla usar como string literal
-        std::fs::read_to_string(&arg).unwrap_or(arg)
-    } else {
-        // Si no, leer de stdin
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer).expect("Failed to read from stdin");
-        buffer
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = args.split_first() else {
+        usage_error("missing subcommand");
     };
+    if command == "-h" || command == "--help" || command == "help" {
+        println!("{}", USAGE);
+        return;
+    }
+    if command == "repl" {
+        if let Some(arg) = rest.first() {
+            usage_error(&format!("unexpected argument '{}'", arg));
+        }
+        run_repl();
+        return;
+    }
+
+    if !COMMANDS.contains(&command.as_str()) {
+        usage_error(&format!("unknown subcommand '{}'", command));
+    }
+    let opts = parse_options(command, rest);
+    let source = read_source(&opts.input);
 
-    let mut parser = Parser::new(&input);
-    match parser.parse_program() {
-
-        Ok(program) => {
-            let mut expanded = expand_macros(program);
-            
-            // Transformar implicit functors ANTES del semantic check
-            let temp_ctx = hulk_compiler::semantic::Context::new();
-            transform_implicit_functors(&mut expanded, &temp_ctx);
-    
-            match hulk_compiler::semantic::check_program(&expanded) {
-                 Ok(context) => {
-                     eprintln!("Semantic check passed!");
-                     eprintln!("Defined types: {:?}", context.types.keys());
-                     
-                     // Optimizar
-                     let optimized = optimize_program(expanded);
-                     
-        
-                     let generator = LlvmGenerator;
-                     let llvm_code = generator.generate(&optimized, &context);
-                     
-                    std::fs::write("output.ll", &llvm_code).expect("Unable to write file");
-                 },
-                 Err(errors) => {
-                     eprintln!("Semantic errors found:");
-                     for err in errors {
-                         eprintln!("{:?}", err);
-                     }
-                     std::process::exit(1);
-                 }
+    match command.as_str() {
+        "build" => build(&opts, &source),
+        "check" => {
+            if let Err(e) = driver::check(&source) {
+                fail(&e, &opts.input, &source);
             }
         }
+        "run" => match opts.backend.unwrap_or(Backend::Llvm) {
+            Backend::Llvm => {
+                let llvm_code = driver::compile_to_llvm(&source)
+                    .unwrap_or_else(|e| fail(&e, &opts.input, &source));
+                match driver::run_llvm(&llvm_code) {
+                    Ok(status) => exit(status.code().unwrap_or(1)),
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        exit(1);
+                    }
+                }
+            }
+            Backend::Interp => run_interpreted(&opts.input, source),
+        },
+        "fmt" => fmt(&opts, &source),
+        other => usage_error(&format!("unknown subcommand '{}'", other)),
+    }
+}
+
+/// `hulk build`: vuelca una etapa con `--emit` o genera un ejecutable nativo.
+fn build(opts: &Options, source: &str) {
+    if let Some(stage) = opts.emit {
+        let text = driver::emit_stage(source, stage).unwrap_or_else(|e| fail(&e, &opts.input, source));
+        match &opts.output {
+            Some(path) => write_file(path, &text),
+            None => print!("{}", text),
+        }
+        return;
+    }
+
+    let llvm_code = driver::compile_to_llvm(source).unwrap_or_else(|e| fail(&e, &opts.input, source));
+    let out = opts.output.clone().unwrap_or_else(|| default_output(&opts.input));
+    let result = driver::scratch_dir().and_then(|dir| {
+        let ll_path = dir.join("main.ll");
+        write_file(&ll_path, &llvm_code);
+        let linked = driver::link_native(&ll_path, &out);
+        let _ = std::fs::remove_dir_all(&dir);
+        linked
+    });
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
+
+/// `hulk fmt`: reescribe el archivo con el formato canónico o, con `--check`,
+/// solo comprueba que ya lo tenga.
+fn fmt(opts: &Options, source: &str) {
+    let formatted = format::format_source(source)
+        .unwrap_or_else(|errors| fail(&CompileError::Parse(errors), &opts.input, source));
+    let file = if opts.input == "-" { "<stdin>" } else { opts.input.as_str() };
+
+    if opts.check {
+        if formatted != source {
+            eprintln!("error: '{}' is not formatted (run 'hulk fmt {}')", file, opts.input);
+            exit(1);
+        }
+        return;
+    }
+    match &opts.output {
+        Some(path) => write_file(path, &formatted),
+        None if opts.input == "-" => print!("{}", formatted),
+        None if formatted != source => write_file(Path::new(&opts.input), &formatted),
+        None => {}
+    }
+}
+
+/// `hulk repl`: el intérprete recursa por el AST, así que corre en un hilo
+/// con pila suficiente para `interp::MAX_CALL_DEPTH` llamadas anidadas.
+fn run_repl() {
+    let result = std::thread::Builder::new()
+        .stack_size(interp::STACK_SIZE)
+        .spawn(|| repl::run(io::stdin().lock(), io::stdout()))
+        .and_then(|handle| handle.join().expect("el REPL terminó con un pánico"));
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
+
+/// `hulk run --backend=interp`: ejecuta el programa optimizado con el intérprete.
+/// Un error en tiempo de ejecución se muestra como diagnóstico y termina con 1.
+fn run_interpreted(input: &str, source: String) {
+    let file = if input == "-" { "<stdin>" } else { input }.to_string();
+    // El `Context` no es `Send`: todo el pipeline corre dentro del hilo con pila grande
+    let result = std::thread::Builder::new()
+        .stack_size(interp::STACK_SIZE)
+        .spawn(move || {
+            let checked = driver::optimize(&source).map_err(|e| e.render(&source, &file))?;
+            driver::run_interpreted(&checked, io::stdout().lock())
+                .map_err(|e| Diagnostic::from(&e).render(&source, &file))
+        })
+        .map(|handle| handle.join().expect("el intérprete terminó con un pánico"));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(rendered)) => {
+            eprint!("{}", rendered);
+            exit(1);
+        }
         Err(e) => {
-            eprintln!("Parsing failed: {}", e);
-            std::process::exit(1);
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Lee los argumentos de `command`; una opción que no aplica al subcomando
+/// es un error de uso en lugar de ignorarse.
+fn parse_options(command: &str, args: &[String]) -> Options {
+    let mut input = None;
+    let mut output = None;
+    let mut emit = None;
+    let mut backend = None;
+    let mut check = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |what: &str| match inline {
+            Some(value) => value.to_string(),
+            None => iter.next().cloned()
+                .unwrap_or_else(|| usage_error(&format!("'{}' expects {}", name, what))),
+        };
+        match name {
+            "-o" => {
+                option_for(command, name, &["build", "fmt"]);
+                output = Some(PathBuf::from(value("a path")));
+            }
+            "--emit" => {
+                option_for(command, name, &["build"]);
+                let stage = value("a stage");
+                emit = Some(Emit::parse(&stage).unwrap_or_else(|| {
+                    usage_error(&format!("unknown stage '{}' (expected {})", stage, Emit::NAMES))
+                }));
+            }
+            "--backend" => {
+                option_for(command, name, &["run"]);
+                let backend_name = value("a backend");
+                backend = Some(Backend::parse(&backend_name).unwrap_or_else(|| {
+                    usage_error(&format!("unknown backend '{}' (expected {})", backend_name, Backend::NAMES))
+                }));
+            }
+            "--check" if inline.is_none() => {
+                option_for(command, name, &["fmt"]);
+                check = true;
+            }
+            _ if arg.starts_with('-') && arg != "-" => usage_error(&format!("unknown option '{}'", arg)),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => usage_error(&format!("unexpected argument '{}'", arg)),
         }
     }
+    let Some(input) = input else { usage_error("missing input file") };
+    Options { input, output, emit, backend, check }
+}
+
+/// Rechaza `option` si `command` no está entre los subcomandos que la usan.
+fn option_for(command: &str, option: &str, commands: &[&str]) {
+    if !commands.contains(&command) {
+        let valid: Vec<String> = commands.iter().map(|c| format!("'hulk {}'", c)).collect();
+        usage_error(&format!("'{}' is only valid for {}", option, valid.join(" and ")));
+    }
+}
+
+/// Lee el programa desde el archivo indicado o desde stdin si es `-`.
+fn read_source(input: &str) -> String {
+    if input == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer).unwrap_or_else(|e| {
+            eprintln!("error: failed to read stdin: {}", e);
+            exit(1);
+        });
+        return buffer;
+    }
+    std::fs::read_to_string(input).unwrap_or_else(|e| {
+        eprintln!("error: cannot read '{}': {}", input, e);
+        exit(1);
+    })
+}
+
+/// Nombre del ejecutable por defecto: el del archivo fuente sin extensión.
+fn default_output(input: &str) -> PathBuf {
+    match Path::new(input).file_stem() {
+        Some(stem) if input != "-" => PathBuf::from(stem),
+        _ => PathBuf::from("a.out"),
+    }
+}
+
+fn write_file(path: &Path, contents: &str) {
+    if let Err(e) = std::fs::write(path, contents) {
+        eprintln!("error: cannot write '{}': {}", path.display(), e);
+        exit(1);
+    }
+}
+
+/// Muestra los diagnósticos del error sobre el código fuente y termina.
+fn fail(err: &CompileError, input: &str, source: &str) -> ! {
+    let file = if input == "-" { "<stdin>" } else { input };
+    eprint!("{}", err.render(source, file));
+    exit(1);
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("error: {}\n\n{}", msg, USAGE);
+    exit(2);
 }
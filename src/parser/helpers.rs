@@ -1,5 +1,6 @@
 use crate::lexer::tokens::Token;
-use crate::utils::Position;
+use crate::ast::nodes::Expr;
+use crate::utils::{Position, Spanned};
 use crate::errors::ParseError;
 
 impl super::Parser {
@@ -78,10 +79,23 @@ impl super::Parser {
                             crate::errors::LexError::UnexpectedCharacter(_, p) => Some(p.clone()),
                         },
                     })
-                    .unwrap_or(Position { line: 1, column: 1 })
+                    .unwrap_or(Position::new(1, 1, 0))
             })
     }
 
+    /// Posición final del último token consumido.
+    pub(super) fn prev_end(&self) -> Position {
+        match self.current.checked_sub(1) {
+            Some(i) => self.ends[i],
+            None => self.peek_pos(),
+        }
+    }
+
+    /// Envuelve `node` con el rango que va desde `start` hasta el último token consumido.
+    pub(super) fn spanned<T>(&self, node: T, start: Position) -> Spanned<T> {
+        Spanned { node, pos: start, end: self.prev_end() }
+    }
+
     /// Devuelve una descripción textual del token actual (o del error léxico).
     pub(super) fn peek_description(&self) -> String {
         match self.peek() {
@@ -91,6 +105,12 @@ impl super::Parser {
         }
     }
 
+    /// Describe una expresión ya analizada para un mensaje de error: su forma
+    /// en código HULK, en una sola línea.
+    pub(super) fn expr_description(expr: &Expr) -> String {
+        expr.to_string().split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
     /// Verifica si estamos al final de los tokens.
     pub(super) fn at_end(&self) -> bool {
         match self.peek() {
@@ -108,10 +128,83 @@ impl super::Parser {
                 Token::Number(_) | Token::StringLiteral(_) | Token::True | Token::False |
                 Token::Identifier(_) | Token::Minus | Token::Not | Token::LParen |
                 Token::Let | Token::If | Token::While | Token::For | Token::Match |
+                Token::Try | Token::Throw | Token::Break | Token::Continue | Token::Return |
                 Token::LBrace | Token::New | Token::LBracket | Token::Base | Token::Print |
                 Token::Concat,
                 _
             )))
         )
     }
+
+    /// Verifica si el token actual inicia una declaración (`function`, `type`, `protocol`, `variant`, `def`).
+    pub(super) fn at_declaration_keyword(&self) -> bool {
+        self.check(&Token::Function) || self.at_top_level_keyword()
+    }
+
+    /// Como `at_declaration_keyword`, pero sin `function`, que también inicia
+    /// métodos dentro del cuerpo de un tipo.
+    pub(super) fn at_top_level_keyword(&self) -> bool {
+        matches!(self.peek(), Some(Ok((Token::Type | Token::Protocol | Token::Variant | Token::Def, _))))
+    }
+
+    /// Error `UnexpectedToken` sobre el token actual.
+    pub(super) fn unexpected(&self, expected: &str) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected: expected.to_string(),
+            found: self.peek_description(),
+            pos: self.peek_pos(),
+        }
+    }
+
+    /// Descarta el token actual; si era un error léxico, lo registra.
+    pub(super) fn skip_token(&mut self) {
+        if let Err(err) = self.advance() {
+            self.errors.push(err);
+        }
+    }
+
+    /// Recuperación en modo pánico: registra `err` y descarta tokens hasta un
+    /// punto seguro. Se detiene antes de un `;`, de un `}` sin abrir o de una
+    /// palabra clave de declaración, y después del `}` que cierra un bloque
+    /// abierto durante la sincronización. El `;` lo consume quien llama, como
+    /// separador de la secuencia que estaba analizando.
+    pub(super) fn recover(&mut self, err: ParseError) {
+        self.errors.push(err);
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                None | Some(Ok((Token::EOF, _))) => return,
+                Some(Ok((Token::Semicolon, _))) if depth == 0 => return,
+                Some(Ok((Token::RBrace, _))) => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    self.skip_token();
+                    if depth == 0 {
+                        return;
+                    }
+                    continue;
+                }
+                Some(Ok((Token::LBrace, _))) => depth += 1,
+                _ if depth == 0 && self.at_declaration_keyword() => return,
+                _ => {}
+            }
+            self.skip_token();
+        }
+    }
+
+    /// Recupera un error dentro de una secuencia de expresiones y devuelve un
+    /// nodo `Expr::Error` que cubre los tokens descartados.
+    pub(super) fn error_expr(&mut self, err: ParseError, start: usize, pos: Position) -> Spanned<Expr> {
+        self.recover(err);
+        if self.current == start && !self.check(&Token::RBrace) && !self.check(&Token::Semicolon) && !self.at_end() {
+            self.skip_token();
+        }
+        if self.current == start {
+            Spanned::new(Expr::Error, pos)
+        } else {
+            self.spanned(Expr::Error, pos)
+        }
+    }
 }
@@ -1,14 +1,20 @@
 mod helpers;
 
 use crate::lexer::Lexer;
-use crate::lexer::tokens::Token;
+use crate::lexer::tokens::{Comment, Token};
 use crate::utils::{Position, Spanned};
 use crate::errors::ParseError;
 use crate::ast::nodes::*;
 
 pub struct Parser {
     pub(crate) tokens: Vec<Result<(Token, Position), crate::errors::LexError>>,
+    /// Posición final de cada token (paralelo a `tokens`), para construir rangos.
+    pub(crate) ends: Vec<Position>,
     pub(crate) current: usize,
+    /// Errores ya reportados durante la recuperación en modo pánico.
+    pub(crate) errors: Vec<ParseError>,
+    /// Comentarios del código fuente, conservados por el lexer como trivia.
+    pub(crate) comments: Vec<Comment>,
 }
 
 impl Parser {
@@ -17,9 +23,12 @@ impl Parser {
     pub fn new(input: &str) -> Self {
         let mut lexer = Lexer::new(input);
         let mut tokens = Vec::new();
+        let mut ends = Vec::new();
         while let Some(res) = lexer.next() {
             tokens.push(res);
+            ends.push(lexer.token_end());
         }
+        let comments = lexer.take_comments();
         
         let last_is_eof = matches!(tokens.last(), Some(Ok((Token::EOF, _))));
         if !last_is_eof {
@@ -30,25 +39,49 @@ impl Parser {
                     crate::errors::LexError::UnterminatedBlockComment(p) => p.clone(),
                     crate::errors::LexError::UnexpectedCharacter(_, p) => p.clone(),
                 },
-                None => Position { line: 1, column: 1 },
+                None => Position::new(1, 1, 0),
             };
+            let eof_end = ends.last().copied().unwrap_or(last_pos);
             tokens.push(Ok((Token::EOF, last_pos)));
+            ends.push(eof_end);
         }
 
         Self {
             tokens,
+            ends,
             current: 0,
+            errors: Vec::new(),
+            comments,
         }
     }
 
+    /// Comentarios del código fuente en orden de aparición (no forman parte del AST).
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
     // --- Análisis principal ---
 
-    /// Analiza un programa completo y devuelve el `Program` AST.
+    /// Analiza el programa completo y devuelve el primer error sintáctico, si lo hay.
+    /// Usar `parse_program_recovering` para obtener todos los errores de una pasada.
     pub fn parse_program(&mut self) -> Result<Program, ParseError> {
+        let (program, mut errors) = self.parse_program_recovering();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors.swap_remove(0))
+        }
+    }
+
+    /// Analiza el programa completo recuperándose de los errores sintácticos
+    /// (modo pánico en `;`, `}` y en las palabras clave de declaración).
+    /// Devuelve un `Program` parcial, con nodos `Expr::Error` donde hubo errores,
+    /// junto con todos los errores encontrados en orden de aparición.
+    pub fn parse_program_recovering(&mut self) -> (Program, Vec<ParseError>) {
         let mut declarations = Vec::new();
 
         while !self.at_end() && !self.is_expr_start() {
-            declarations.push(self.parse_declaration()?);
+            self.parse_declaration_recovering(&mut declarations);
         }
 
         // múltiples expresiones en el programa principal
@@ -59,12 +92,24 @@ impl Parser {
             let mut exprs = Vec::new();
             
             // Parsear todas las expresiones restantes
-            loop {
-                if self.at_end() {
-                    break;
+            while !self.at_end() {
+                if self.at_declaration_keyword() {
+                    // Declaración después de la expresión principal: se reporta y se conserva
+                    self.errors.push(self.unexpected("an expression (declarations go before the main expression)"));
+                    self.parse_declaration_recovering(&mut declarations);
+                    continue;
                 }
-                
-                let e = self.parse_spanned_expr(Precedence::Lowest)?;
+                if self.check(&Token::RBrace) {
+                    self.errors.push(self.unexpected("an expression"));
+                    self.skip_token();
+                    continue;
+                }
+
+                let (start, pos) = (self.current, self.peek_pos());
+                let e = match self.parse_spanned_expr(Precedence::Lowest) {
+                    Ok(e) => e,
+                    Err(err) => self.error_expr(err, start, pos),
+                };
                 exprs.push(e);
                 
                 // Si hay punto y coma, consumirlo y continuar parseando
@@ -75,13 +120,9 @@ impl Parser {
                 // Si no hay punto y coma pero aún hay tokens, podría ser la última expresión
                 // Verificar si el siguiente token es el inicio de una nueva expresión
                 if !self.at_end() && self.is_expr_start() {
-                    // Hay otra expresión sin punto y coma previo - error de sintaxis
-                    let pos = self.peek_pos();
-                    return Err(ParseError::UnexpectedToken {
-                        expected: "';' or end of file".to_string(),
-                        found: self.peek_description(),
-                        pos,
-                    });
+                    // Hay otra expresión sin punto y coma previo: se reporta como si faltara el ';'
+                    self.errors.push(self.unexpected("';' or end of file"));
+                    continue;
                 }
                 
                 // No hay más expresiones
@@ -93,14 +134,30 @@ impl Parser {
             if exprs.len() == 1 {
                 exprs.into_iter().next().unwrap()
             } else {
-                Spanned::new(Expr::Block(exprs), start_pos)
+                self.spanned(Expr::Block(exprs), start_pos)
             }
         };
 
-        Ok(Program {
+        let program = Program {
             declarations,
             expr,
-        })
+        };
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// Analiza una declaración; si falla, registra el error y sincroniza.
+    fn parse_declaration_recovering(&mut self, declarations: &mut Vec<Declaration>) {
+        let start = self.current;
+        match self.parse_declaration() {
+            Ok(decl) => declarations.push(decl),
+            Err(err) => {
+                self.recover(err);
+                // Token sin sentido a este nivel (p. ej. un '}' suelto): descartarlo
+                if !self.match_token(&Token::Semicolon) && self.current == start {
+                    self.skip_token();
+                }
+            }
+        }
     }
 
     /// Analiza una declaración (función, tipo o protocolo) y la devuelve.
@@ -111,12 +168,16 @@ impl Parser {
             Ok(Declaration::Type(self.parse_type_decl()?))
         } else if self.match_token(&Token::Protocol) {
             Ok(Declaration::Protocol(self.parse_protocol_decl()?))
+        } else if self.match_token(&Token::Variant) {
+            Ok(Declaration::Variant(self.parse_variant_decl()?))
         } else if self.match_token(&Token::Def) {
             Ok(Declaration::Macro(self.parse_macro_decl()?))
+        } else if matches!(self.peek(), Some(Err(_))) {
+            Err(self.advance().expect_err("token con error léxico"))
         } else {
             let pos = self.peek_pos();
             Err(ParseError::UnexpectedToken {
-                expected: "function, type, protocol, or def".to_string(),
+                expected: "function, type, protocol, variant, or def".to_string(),
                 found: self.peek_description(),
                 pos,
             })
@@ -138,44 +199,49 @@ impl Parser {
 
     /// Analiza expresiones prefijas (literales, identificadores, unarios, etc.).
     fn parse_prefix(&mut self) -> Result<Spanned<Expr>, ParseError> {
+        // No consumir tokens que no inician una expresión (p. ej. un '}' o ';'),
+        // para que la recuperación pueda sincronizar en ellos
+        if matches!(self.peek(), Some(Ok(_))) && !self.is_expr_start() {
+            return Err(ParseError::InvalidExpression(self.peek_pos()));
+        }
         let (token, pos) = self.advance()?;
         match token {
-            Token::Number(val) => Ok(Spanned::new(Expr::Number(val), pos)),
-            Token::StringLiteral(val) => Ok(Spanned::new(Expr::String(val), pos)),
-            Token::True => Ok(Spanned::new(Expr::Boolean(true), pos)),
-            Token::False => Ok(Spanned::new(Expr::Boolean(false), pos)),
+            Token::Number(val) => Ok(self.spanned(Expr::Number(val), pos)),
+            Token::StringLiteral(val) => Ok(self.spanned(Expr::String(val), pos)),
+            Token::True => Ok(self.spanned(Expr::Boolean(true), pos)),
+            Token::False => Ok(self.spanned(Expr::Boolean(false), pos)),
             Token::Identifier(name) => {
                 match name.as_str() {
-                    "PI" => Ok(Spanned::new(Expr::PI, pos)),
-                    "E" => Ok(Spanned::new(Expr::E, pos)),
+                    "PI" => Ok(self.spanned(Expr::PI, pos)),
+                    "E" => Ok(self.spanned(Expr::E, pos)),
                     "rand" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
                         self.consume(&Token::RParen, "Expected ')' after rand")?;
-                        Ok(Spanned::new(Expr::Rand, pos))
+                        Ok(self.spanned(Expr::Rand, pos))
                     }
                     "sqrt" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
                         let val = self.parse_spanned_expr(Precedence::Lowest)?;
                         self.consume(&Token::RParen, "Expected ')' after sqrt arguments")?;
-                        Ok(Spanned::new(Expr::Sqrt(Box::new(val)), pos))
+                        Ok(self.spanned(Expr::Sqrt(Box::new(val)), pos))
                     }
                     "sin" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
                         let val = self.parse_spanned_expr(Precedence::Lowest)?;
                         self.consume(&Token::RParen, "Expected ')' after sin arguments")?;
-                        Ok(Spanned::new(Expr::Sin(Box::new(val)), pos))
+                        Ok(self.spanned(Expr::Sin(Box::new(val)), pos))
                     }
                     "cos" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
                         let val = self.parse_spanned_expr(Precedence::Lowest)?;
                         self.consume(&Token::RParen, "Expected ')' after cos arguments")?;
-                        Ok(Spanned::new(Expr::Cos(Box::new(val)), pos))
+                        Ok(self.spanned(Expr::Cos(Box::new(val)), pos))
                     }
                     "exp" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
                         let val = self.parse_spanned_expr(Precedence::Lowest)?;
                         self.consume(&Token::RParen, "Expected ')' after exp arguments")?;
-                        Ok(Spanned::new(Expr::Exp(Box::new(val)), pos))
+                        Ok(self.spanned(Expr::Exp(Box::new(val)), pos))
                     }
                     "log" if self.check(&Token::LParen) => {
                         self.advance()?; // consumir '('
@@ -183,27 +249,44 @@ impl Parser {
                         self.consume(&Token::Comma, "Expected ',' between log arguments")?;
                         let val = self.parse_spanned_expr(Precedence::Lowest)?;
                         self.consume(&Token::RParen, "Expected ')' after log arguments")?;
-                        Ok(Spanned::new(Expr::Log(Box::new(base), Box::new(val)), pos))
+                        Ok(self.spanned(Expr::Log(Box::new(base), Box::new(val)), pos))
                     }
-                    _ => Ok(Spanned::new(Expr::Identifier(name), pos)),
+                    _ => Ok(self.spanned(Expr::Identifier(name), pos)),
                 }
             }
             Token::Minus => {
                 let expr = self.parse_spanned_expr(Precedence::Unary)?;
-                Ok(Spanned::new(Expr::Unary(UnOp::Neg, Box::new(expr)), pos))
+                Ok(self.spanned(Expr::Unary(UnOp::Neg, Box::new(expr)), pos))
             }
             Token::Not => {
                 let expr = self.parse_spanned_expr(Precedence::Unary)?;
-                Ok(Spanned::new(Expr::Unary(UnOp::Not, Box::new(expr)), pos))
+                Ok(self.spanned(Expr::Unary(UnOp::Not, Box::new(expr)), pos))
             }
             Token::LParen => self.parse_lambda_or_parenthesized(pos),
             Token::Let => self.parse_let_expr(pos),
             Token::If => self.parse_if_expr(pos),
             Token::While => self.parse_while_expr(pos),
             Token::For => self.parse_for_expr(pos),
+            Token::Try => self.parse_try_expr(pos),
+            Token::Throw => {
+                let value = self.parse_spanned_expr(Precedence::Lowest)?;
+                Ok(self.spanned(Expr::Throw(Box::new(value)), pos))
+            }
+            Token::Break => {
+                let value = self.parse_jump_value()?;
+                Ok(self.spanned(Expr::Break(value), pos))
+            }
+            Token::Continue => {
+                let value = self.parse_jump_value()?;
+                Ok(self.spanned(Expr::Continue(value), pos))
+            }
+            Token::Return => {
+                let value = self.parse_spanned_expr(Precedence::Lowest)?;
+                Ok(self.spanned(Expr::Return(Box::new(value)), pos))
+            }
             Token::Match => {
                 let match_expr = self.parse_match_expr()?;
-                Ok(Spanned::new(match_expr, pos))
+                Ok(self.spanned(match_expr, pos))
             }
             Token::LBrace => self.parse_block_expr(pos),
             Token::New => self.parse_instantiation_expr(pos),
@@ -220,7 +303,7 @@ impl Parser {
                     }
                 }
                 self.consume(&Token::RParen, "Expected ')' after base arguments")?;
-                Ok(Spanned::new(Expr::BaseCall { args }, pos))
+                Ok(self.spanned(Expr::BaseCall { args }, pos))
             }
             Token::Print => {
                 self.consume(&Token::LParen, "Expected '(' after print")?;
@@ -234,7 +317,7 @@ impl Parser {
                     }
                 }
                 self.consume(&Token::RParen, "Expected ')' after print arguments")?;
-                Ok(Spanned::new(Expr::Call { func: "print".to_string(), args }, pos))
+                Ok(self.spanned(Expr::Call { func: "print".to_string(), args }, pos))
             }
             Token::Concat => {
                 // Soporte para argumentos simbólicos de macros (@variable)
@@ -242,7 +325,7 @@ impl Parser {
                 // El prefijo @ indica al programador que se pasa simbólicamente,
                 // pero el parser lo trata como identificador.
                 match self.advance()?.0 {
-                    Token::Identifier(name) => Ok(Spanned::new(Expr::Identifier(name), pos)),
+                    Token::Identifier(name) => Ok(self.spanned(Expr::Identifier(name), pos)),
                     t => Err(ParseError::UnexpectedToken {
                         expected: "identifier after @".to_string(),
                         found: format!("{:?}", t),
@@ -269,7 +352,7 @@ impl Parser {
                     };
                     self.consume(&Token::FuncArrow, "Expected '=>' after lambda signature")?;
                     let body = self.parse_spanned_expr(Precedence::Lowest)?;
-                    return Ok(Spanned::new(Expr::Lambda { 
+                    return Ok(self.spanned(Expr::Lambda { 
                         params: p, 
                         return_type, 
                         body: Box::new(body) 
@@ -301,7 +384,8 @@ impl Parser {
                         pos: self.peek_pos(),
                     }),
                 };
-                Ok(Spanned::new(Expr::Is(Box::new(left), ty), pos))
+                let start = left.pos;
+                Ok(self.spanned(Expr::Is(Box::new(left), ty), start))
             }
             Token::As => {
                 let ty = match self.advance()?.0 {
@@ -312,22 +396,26 @@ impl Parser {
                         pos: self.peek_pos(),
                     }),
                 };
-                Ok(Spanned::new(Expr::As(Box::new(left), ty), pos))
+                let start = left.pos;
+                Ok(self.spanned(Expr::As(Box::new(left), ty), start))
             }
             Token::DestructAssign => {
-                // x := expr  OR  obj.attr := expr
+                // x := expr  OR  obj.attr := expr  OR  v[i] := expr
                 if let Expr::Identifier(name) = &left.node {
                     let right = self.parse_spanned_expr(Precedence::Assignment)?;
                     let pos_start = left.pos.clone();
-                    Ok(Spanned::new(Expr::Assignment { target: name.clone(), value: Box::new(right) }, pos_start))
+                    Ok(self.spanned(Expr::Assignment { target: name.clone(), value: Box::new(right) }, pos_start))
                 } else if let Expr::AttributeAccess { obj, attribute } = left.node {
                     let right = self.parse_spanned_expr(Precedence::Assignment)?;
                     let pos_start = left.pos.clone();
-                    Ok(Spanned::new(Expr::AttributeAssignment { obj, attribute, value: Box::new(right) }, pos_start))
+                    Ok(self.spanned(Expr::AttributeAssignment { obj, attribute, value: Box::new(right) }, pos_start))
+                } else if let Expr::Indexing { obj, index } = left.node {
+                    let right = self.parse_spanned_expr(Precedence::Assignment)?;
+                    Ok(self.spanned(Expr::IndexAssignment { obj, index, value: Box::new(right) }, left.pos))
                 } else {
                     Err(ParseError::UnexpectedToken {
-                        expected: "identifier or attribute access".to_string(),
-                        found: format!("{:?}", left.node),
+                        expected: "identifier, attribute access or indexing".to_string(),
+                        found: Self::expr_description(&left.node),
                         pos: left.pos,
                     })
                 }
@@ -343,7 +431,7 @@ impl Parser {
 
                 let right = self.parse_spanned_expr(next_precedence)?;
                 let combined_pos = left.pos.clone(); 
-                Ok(Spanned::new(Expr::Binary(Box::new(left), op, Box::new(right)), combined_pos))
+                Ok(self.spanned(Expr::Binary(Box::new(left), op, Box::new(right)), combined_pos))
             }
         }
     }
@@ -406,7 +494,7 @@ impl Parser {
         self.consume(&Token::In, "Expected 'in' after let bindings")?;
         let body = self.parse_spanned_expr(Precedence::Lowest)?;
 
-        Ok(Spanned::new(Expr::Let { bindings, body: Box::new(body) }, pos))
+        Ok(self.spanned(Expr::Let { bindings, body: Box::new(body) }, pos))
     }
 
     /// Analiza una expresión `if` (condición, then y else/elif).
@@ -430,7 +518,7 @@ impl Parser {
             });
         };
 
-        Ok(Spanned::new(Expr::If {
+        Ok(self.spanned(Expr::If {
             cond: Box::new(cond),
             then_expr: Box::new(then_expr),
             else_expr: Box::new(else_expr),
@@ -445,7 +533,7 @@ impl Parser {
         
         let body = self.parse_spanned_expr(Precedence::Lowest)?;
         
-        Ok(Spanned::new(Expr::While {
+        Ok(self.spanned(Expr::While {
             cond: Box::new(cond),
             body: Box::new(body),
         }, pos))
@@ -470,22 +558,92 @@ impl Parser {
         
         let body = self.parse_spanned_expr(Precedence::Lowest)?;
 
-        Ok(Spanned::new(Expr::For {
+        Ok(self.spanned(Expr::For {
             var,
             iterable: Box::new(iterable),
             body: Box::new(body),
         }, pos))
     }
 
+    /// Analiza `try cuerpo catch (e: Tipo) manejador ...` (al menos un `catch`).
+    fn parse_try_expr(&mut self, pos: Position) -> Result<Spanned<Expr>, ParseError> {
+        let body = self.parse_spanned_expr(Precedence::Lowest)?;
+
+        let mut catches = Vec::new();
+        while self.check(&Token::Catch) || catches.is_empty() {
+            self.consume(&Token::Catch, "Expected 'catch' after try body")?;
+            self.consume(&Token::LParen, "Expected '(' after catch")?;
+            let var = match self.advance()?.0 {
+                Token::Identifier(n) => n,
+                t => return Err(ParseError::UnexpectedToken {
+                    expected: "identifier".to_string(),
+                    found: format!("{:?}", t),
+                    pos: self.peek_pos(),
+                }),
+            };
+            let ty = if self.match_token(&Token::Colon) {
+                match self.advance()?.0 {
+                    Token::Identifier(t) => Some(t),
+                    t => return Err(ParseError::UnexpectedToken {
+                        expected: "type name".to_string(),
+                        found: format!("{:?}", t),
+                        pos: self.peek_pos(),
+                    }),
+                }
+            } else {
+                None
+            };
+            self.consume(&Token::RParen, "Expected ')' after catch variable")?;
+            let body = self.parse_spanned_expr(Precedence::Lowest)?;
+            catches.push(CatchClause { var, ty, body });
+        }
+
+        Ok(self.spanned(Expr::Try { body: Box::new(body), catches }, pos))
+    }
+
+    /// Valor opcional de `break` o `continue`: lo hay si sigue una expresión.
+    fn parse_jump_value(&mut self) -> Result<Option<Box<Spanned<Expr>>>, ParseError> {
+        if !self.is_expr_start() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(self.parse_spanned_expr(Precedence::Lowest)?)))
+    }
+
     /// Analiza un bloque `{ ... }` y devuelve una expresión de bloque.
     fn parse_block_expr(&mut self, pos: Position) -> Result<Spanned<Expr>, ParseError> {
         let mut exprs = Vec::new();
         while !self.check(&Token::RBrace) && !self.at_end() {
-            exprs.push(self.parse_spanned_expr(Precedence::Lowest)?);
+            let (start, stmt_pos) = (self.current, self.peek_pos());
+            let e = match self.parse_spanned_expr(Precedence::Lowest) {
+                Ok(e) => e,
+                Err(err) => self.error_expr(err, start, stmt_pos),
+            };
+            // `{ clave -> ...` abre un diccionario, no un bloque
+            if exprs.is_empty() && self.check(&Token::TypeArrow) {
+                return self.parse_map_literal(e, pos);
+            }
+            exprs.push(e);
             let _ = self.match_token(&Token::Semicolon);
         }
         self.consume(&Token::RBrace, "Expected '}' after block")?;
-        Ok(Spanned::new(Expr::Block(exprs), pos))
+        Ok(self.spanned(Expr::Block(exprs), pos))
+    }
+
+    /// Analiza el resto de un diccionario `{ k -> v, ... }` ya leída su primera clave.
+    fn parse_map_literal(&mut self, first_key: Spanned<Expr>, pos: Position) -> Result<Spanned<Expr>, ParseError> {
+        let mut entries = Vec::new();
+        let mut key = first_key;
+        loop {
+            self.consume(&Token::TypeArrow, "Expected '->' after map key")?;
+            let value = self.parse_spanned_expr(Precedence::Lowest)?;
+            entries.push((key, value));
+            if !self.match_token(&Token::Comma) || self.check(&Token::RBrace) {
+                break;
+            }
+            key = self.parse_spanned_expr(Precedence::Lowest)?;
+        }
+        self.consume(&Token::RBrace, "Expected '}' after map entries")?;
+        Ok(self.spanned(Expr::MapLiteral(entries), pos))
     }
 
     /// Analiza la instanciación de un tipo `Type(args...)`.
@@ -499,6 +657,7 @@ impl Parser {
             }),
         };
 
+        let type_args = self.parse_type_args()?;
         self.consume(&Token::LParen, "Expected '(' in instantiation")?;
         let mut args = Vec::new();
         if !self.check(&Token::RParen) {
@@ -511,14 +670,14 @@ impl Parser {
         }
         self.consume(&Token::RParen, "Expected ')' after instantiation arguments")?;
 
-        Ok(Spanned::new(Expr::Instantiation { ty, args }, pos))
+        Ok(self.spanned(Expr::Instantiation { ty, type_args, args }, pos))
     }
 
     /// Analiza literales de vectores y generadores de vectores.
     fn parse_vector_expr(&mut self, pos: Position) -> Result<Spanned<Expr>, ParseError> {
         if self.check(&Token::RBracket) {
             self.advance()?;
-            return Ok(Spanned::new(Expr::VectorLiteral(Vec::new()), pos));
+            return Ok(self.spanned(Expr::VectorLiteral(Vec::new()), pos));
         }
 
         let first = self.parse_spanned_expr(Precedence::Or)?;
@@ -537,7 +696,7 @@ impl Parser {
             let iterable = self.parse_spanned_expr(Precedence::Lowest)?;
             self.consume(&Token::RBracket, "Expected ']' after generator")?;
 
-            Ok(Spanned::new(Expr::VectorGenerator {
+            Ok(self.spanned(Expr::VectorGenerator {
                 expr: Box::new(first),
                 var,
                 iterable: Box::new(iterable),
@@ -548,7 +707,7 @@ impl Parser {
                 exprs.push(self.parse_spanned_expr(Precedence::Lowest)?);
             }
             self.consume(&Token::RBracket, "Expected ']' after vector")?;
-            Ok(Spanned::new(Expr::VectorLiteral(exprs), pos))
+            Ok(self.spanned(Expr::VectorLiteral(exprs), pos))
         }
     }
 
@@ -558,7 +717,7 @@ impl Parser {
             Expr::Identifier(n) => n,
             _ => return Err(ParseError::UnexpectedToken {
                 expected: "function name".to_string(),
-                found: format!("{:?}", left.node),
+                found: Self::expr_description(&left.node),
                 pos: left.pos,
             }),
         };
@@ -582,7 +741,7 @@ impl Parser {
             args.push(self.parse_block_expr(pos)?);
         }
 
-        Ok(Spanned::new(Expr::Call { func: func_name, args }, left.pos))
+        Ok(self.spanned(Expr::Call { func: func_name, args }, left.pos))
     }
 
     /// Analiza acceso a miembro o llamada de método (`obj.member` o `obj.member(...)`).
@@ -607,17 +766,38 @@ impl Parser {
                 }
             }
             self.consume(&Token::RParen, "Expected ')' after method arguments")?;
-            Ok(Spanned::new(Expr::MethodCall { obj: Box::new(left), method: name, args }, pos))
+            let start = left.pos;
+            Ok(self.spanned(Expr::MethodCall { obj: Box::new(left), method: name, args }, start))
         } else {
-            Ok(Spanned::new(Expr::AttributeAccess { obj: Box::new(left), attribute: name }, pos))
+            let start = left.pos;
+            Ok(self.spanned(Expr::AttributeAccess { obj: Box::new(left), attribute: name }, start))
         }
     }
 
     /// Analiza indexación `obj[index]` y devuelve la expresión correspondiente.
-    fn parse_indexing_expr(&mut self, left: Spanned<Expr>, pos: Position) -> Result<Spanned<Expr>, ParseError> {
+    fn parse_indexing_expr(&mut self, left: Spanned<Expr>, _pos: Position) -> Result<Spanned<Expr>, ParseError> {
+        if self.match_token(&Token::Colon) {
+            return self.parse_slice_end(left, None);
+        }
         let index = self.parse_spanned_expr(Precedence::Lowest)?;
+        if self.match_token(&Token::Colon) {
+            return self.parse_slice_end(left, Some(index));
+        }
         self.consume(&Token::RBracket, "Expected ']' after index")?;
-        Ok(Spanned::new(Expr::Indexing { obj: Box::new(left), index: Box::new(index) }, pos))
+        let start = left.pos;
+        Ok(self.spanned(Expr::Indexing { obj: Box::new(left), index: Box::new(index) }, start))
+    }
+
+    /// Analiza el final de un `v[a:b]` ya leído el `:`; el límite superior es opcional.
+    fn parse_slice_end(&mut self, left: Spanned<Expr>, start: Option<Spanned<Expr>>) -> Result<Spanned<Expr>, ParseError> {
+        let end = if self.check(&Token::RBracket) {
+            None
+        } else {
+            Some(Box::new(self.parse_spanned_expr(Precedence::Lowest)?))
+        };
+        self.consume(&Token::RBracket, "Expected ']' after slice")?;
+        let pos = left.pos;
+        Ok(self.spanned(Expr::Slice { obj: Box::new(left), start: start.map(Box::new), end }, pos))
     }
 
     /// Convierte un `Token` de operador en el enum `Op` correspondiente.
@@ -660,6 +840,7 @@ impl Parser {
 
     /// Analiza la declaración de una función y devuelve su `FunctionDecl`.
     fn parse_function_decl(&mut self) -> Result<FunctionDecl, ParseError> {
+        let name_pos = self.peek_pos();
         let name = match self.advance()?.0 {
             Token::Identifier(n) => n,
             t => return Err(ParseError::UnexpectedToken {
@@ -669,6 +850,7 @@ impl Parser {
             }),
         };
 
+        let type_params = self.parse_type_params()?;
         self.consume(&Token::LParen, "Expected '(' after function name")?;
         let params = self.parse_params()?;
         self.consume(&Token::RParen, "Expected ')' after function parameters")?;
@@ -693,14 +875,17 @@ impl Parser {
 
         Ok(FunctionDecl {
             name,
+            type_params,
             params,
             return_type,
             body,
+            pos: name_pos,
         })
     }
 
     /// Analiza la declaración de un tipo (atributos y métodos) y la devuelve.
     fn parse_type_decl(&mut self) -> Result<TypeDecl, ParseError> {
+        let name_pos = self.peek_pos();
         let name = match self.advance()?.0 {
             Token::Identifier(n) => n,
             t => return Err(ParseError::UnexpectedToken {
@@ -710,6 +895,7 @@ impl Parser {
             }),
         };
 
+        let type_params = self.parse_type_params()?;
         let params = if self.match_token(&Token::LParen) {
             let p = self.parse_params()?;
             self.consume(&Token::RParen, "Expected ')' after type parameters")?;
@@ -728,6 +914,7 @@ impl Parser {
                 }),
             };
 
+            let type_args = self.parse_type_args()?;
             let args = if self.match_token(&Token::LParen) {
                 let mut a = Vec::new();
                 if !self.check(&Token::RParen) {
@@ -744,7 +931,7 @@ impl Parser {
                 Vec::new()
             };
 
-            Some(TypeInit { name: parent_name, args })
+            Some(TypeInit { name: parent_name, type_args, args })
         } else {
             None
         };
@@ -754,61 +941,115 @@ impl Parser {
         let mut attributes = Vec::new();
         let mut methods = Vec::new();
 
-        while !self.check(&Token::RBrace) && !self.at_end() {
-            if self.match_token(&Token::Function) {
-                methods.push(self.parse_function_decl()?);
-            } else {
-                let is_method = if let Some(Ok((Token::Identifier(_), _))) = self.peek() {
-                    if let Some(Ok((Token::LParen, _))) = self.tokens.get(self.current + 1) {
-                        true
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                };
-
-                if is_method {
-                    methods.push(self.parse_function_decl()?);
-                } else {
-                    // Attribute
-                    let attr_name = match self.advance()?.0 {
-                        Token::Identifier(n) => n,
-                        t => return Err(ParseError::UnexpectedToken {
-                            expected: "attribute or method".to_string(),
-                            found: format!("{:?}", t),
-                            pos: self.peek_pos(),
-                        }),
-                    };
-
-                    let type_annotation = if self.match_token(&Token::Colon) {
-                        Some(self.parse_type_annotation()?)
-                    } else {
-                        None
-                    };
-
-                    self.consume(&Token::Assign, "Expected '=' after attribute name")?;
-                    let init = self.parse_spanned_expr(Precedence::Lowest)?;
-                    self.consume(&Token::Semicolon, "Expected ';' after attribute")?;
-                    
-                    attributes.push(Attribute { name: attr_name, type_annotation, init });
+        while !self.check(&Token::RBrace) && !self.at_end() && !self.at_top_level_keyword() {
+            let start = self.current;
+            if let Err(err) = self.parse_type_member(&mut attributes, &mut methods) {
+                self.recover(err);
+                if !self.match_token(&Token::Semicolon) && self.current == start && !self.check(&Token::RBrace) {
+                    self.skip_token();
                 }
             }
         }
 
-        self.consume(&Token::RBrace, "Expected '}' after type body")?;
+        // Sin '}' de cierre se conserva el tipo parcial y se reporta el error
+        if let Err(err) = self.consume(&Token::RBrace, "Expected '}' after type body") {
+            self.errors.push(err);
+        }
 
         Ok(TypeDecl {
             name,
+            type_params,
             params,
             parent,
             attributes,
             methods,
+            pos: name_pos,
         })
     }
 
+    /// Analiza `variant Nombre = Caso(campos) | Caso ...;`. Un caso sin campos
+    /// puede omitir los paréntesis.
+    fn parse_variant_decl(&mut self) -> Result<VariantDecl, ParseError> {
+        let name_pos = self.peek_pos();
+        let name = match self.advance()?.0 {
+            Token::Identifier(n) => n,
+            t => return Err(ParseError::UnexpectedToken {
+                expected: "variant name".to_string(),
+                found: format!("{:?}", t),
+                pos: self.peek_pos(),
+            }),
+        };
+        self.consume(&Token::Assign, "Expected '=' after variant name")?;
+
+        let mut cases = Vec::new();
+        loop {
+            let pos = self.peek_pos();
+            let case_name = match self.advance()?.0 {
+                Token::Identifier(n) => n,
+                t => return Err(ParseError::UnexpectedToken {
+                    expected: "variant case name".to_string(),
+                    found: format!("{:?}", t),
+                    pos,
+                }),
+            };
+            let params = if self.match_token(&Token::LParen) {
+                let p = self.parse_params()?;
+                self.consume(&Token::RParen, "Expected ')' after variant case fields")?;
+                p
+            } else {
+                Vec::new()
+            };
+            cases.push(VariantCase { name: case_name, params, pos });
+            if !self.match_token(&Token::Or) {
+                break;
+            }
+        }
+        self.consume(&Token::Semicolon, "Expected ';' after variant declaration")?;
+
+        Ok(VariantDecl { name, cases, pos: name_pos })
+    }
+
+    /// Analiza un miembro del cuerpo de un tipo (atributo o método).
+    fn parse_type_member(&mut self, attributes: &mut Vec<Attribute>, methods: &mut Vec<FunctionDecl>) -> Result<(), ParseError> {
+        if self.match_token(&Token::Function) {
+            methods.push(self.parse_function_decl()?);
+        } else {
+            let is_method = matches!(self.peek(), Some(Ok((Token::Identifier(_), _))))
+                && matches!(self.tokens.get(self.current + 1), Some(Ok((Token::LParen, _))));
+
+            if is_method {
+                methods.push(self.parse_function_decl()?);
+            } else {
+                // Attribute
+                let attr_pos = self.peek_pos();
+                let attr_name = match self.advance()?.0 {
+                    Token::Identifier(n) => n,
+                    t => return Err(ParseError::UnexpectedToken {
+                        expected: "attribute or method".to_string(),
+                        found: format!("{:?}", t),
+                        pos: self.peek_pos(),
+                    }),
+                };
+
+                let type_annotation = if self.match_token(&Token::Colon) {
+                    Some(self.parse_type_annotation()?)
+                } else {
+                    None
+                };
+
+                self.consume(&Token::Assign, "Expected '=' after attribute name")?;
+                let init = self.parse_spanned_expr(Precedence::Lowest)?;
+                self.consume(&Token::Semicolon, "Expected ';' after attribute")?;
+                
+                attributes.push(Attribute { name: attr_name, type_annotation, init, pos: attr_pos });
+            }
+        }
+        Ok(())
+    }
+
     /// Analiza la declaración de un protocolo y devuelve su firma.
     fn parse_protocol_decl(&mut self) -> Result<ProtocolDecl, ParseError> {
+        let name_pos = self.peek_pos();
         let name = match self.advance()?.0 {
             Token::Identifier(n) => n,
             t => return Err(ParseError::UnexpectedToken {
@@ -818,6 +1059,7 @@ impl Parser {
             }),
         };
 
+        let type_params = self.parse_type_params()?;
         let parent = if self.match_token(&Token::Extends) {
             match self.advance()?.0 {
                 Token::Identifier(n) => Some(n),
@@ -834,36 +1076,55 @@ impl Parser {
         self.consume(&Token::LBrace, "Expected '{' to start protocol body")?;
         
         let mut methods = Vec::new();
-        while !self.check(&Token::RBrace) && !self.at_end() {
-            let method_name = match self.advance()?.0 {
-                Token::Identifier(n) => n,
-                t => return Err(ParseError::UnexpectedToken {
-                    expected: "method name".to_string(),
-                    found: format!("{:?}", t),
-                    pos: self.peek_pos(),
-                }),
-            };
-
-            self.consume(&Token::LParen, "Expected '(' after method name")?;
-            let params = self.parse_params()?;
-            self.consume(&Token::RParen, "Expected ')' after method parameters")?;
-            self.consume(&Token::Colon, "Protocols methods must have a return type")?;
-            
-            let return_type = self.parse_type_annotation()?;
-
-            self.consume(&Token::Semicolon, "Expected ';' after method signature")?;
-            methods.push(MethodSignature { name: method_name, params, return_type });
+        while !self.check(&Token::RBrace) && !self.at_end() && !self.at_top_level_keyword() {
+            let start = self.current;
+            match self.parse_method_signature() {
+                Ok(sig) => methods.push(sig),
+                Err(err) => {
+                    self.recover(err);
+                    if !self.match_token(&Token::Semicolon) && self.current == start && !self.check(&Token::RBrace) {
+                        self.skip_token();
+                    }
+                }
+            }
         }
 
-        self.consume(&Token::RBrace, "Expected '}' after protocol body")?;
+        if let Err(err) = self.consume(&Token::RBrace, "Expected '}' after protocol body") {
+            self.errors.push(err);
+        }
 
         Ok(ProtocolDecl {
             name,
+            type_params,
             parent,
             methods,
+            pos: name_pos,
         })
     }
 
+    /// Analiza la firma de un método de protocolo: `nombre(params): Tipo;`.
+    fn parse_method_signature(&mut self) -> Result<MethodSignature, ParseError> {
+        let method_pos = self.peek_pos();
+        let method_name = match self.advance()?.0 {
+            Token::Identifier(n) => n,
+            t => return Err(ParseError::UnexpectedToken {
+                expected: "method name".to_string(),
+                found: format!("{:?}", t),
+                pos: self.peek_pos(),
+            }),
+        };
+
+        self.consume(&Token::LParen, "Expected '(' after method name")?;
+        let params = self.parse_params()?;
+        self.consume(&Token::RParen, "Expected ')' after method parameters")?;
+        self.consume(&Token::Colon, "Protocols methods must have a return type")?;
+        
+        let return_type = self.parse_type_annotation()?;
+
+        self.consume(&Token::Semicolon, "Expected ';' after method signature")?;
+        Ok(MethodSignature { name: method_name, params, return_type, pos: method_pos })
+    }
+
     /// Analiza anotaciones de tipo (nombres, funciones y iterables).
     fn parse_type_annotation(&mut self) -> Result<TypeAnnotation, ParseError> {
         if self.match_token(&Token::LParen) {
@@ -893,7 +1154,12 @@ impl Parser {
                 }),
             };
             
-            let mut ty = TypeAnnotation::Name(name);
+            let args = self.parse_type_args()?;
+            let mut ty = if args.is_empty() {
+                TypeAnnotation::Name(name)
+            } else {
+                TypeAnnotation::Generic { name, args }
+            };
             while self.match_token(&Token::Star) {
                 ty = TypeAnnotation::Iterable(Box::new(ty));
             }
@@ -906,6 +1172,50 @@ impl Parser {
         }
     }
 
+    /// Analiza los argumentos de tipo opcionales `<A, B>` que siguen al nombre
+    /// de un tipo genérico; sin `<` devuelve una lista vacía.
+    fn parse_type_args(&mut self) -> Result<Vec<TypeAnnotation>, ParseError> {
+        let mut args = Vec::new();
+        if self.match_token(&Token::LessThan) {
+            loop {
+                args.push(self.parse_type_annotation()?);
+                if !self.match_token(&Token::Comma) {
+                    break;
+                }
+            }
+            self.consume(&Token::GreaterThan, "Expected '>' after type arguments")?;
+        }
+        Ok(args)
+    }
+
+    /// Analiza los parámetros de tipo opcionales `<T, U: Cota>` de una declaración.
+    fn parse_type_params(&mut self) -> Result<Vec<TypeParam>, ParseError> {
+        let mut params = Vec::new();
+        if self.match_token(&Token::LessThan) {
+            loop {
+                let name = match self.advance()?.0 {
+                    Token::Identifier(n) => n,
+                    t => return Err(ParseError::UnexpectedToken {
+                        expected: "type parameter name".to_string(),
+                        found: format!("{:?}", t),
+                        pos: self.peek_pos(),
+                    }),
+                };
+                let bound = if self.match_token(&Token::Colon) {
+                    Some(self.parse_type_annotation()?)
+                } else {
+                    None
+                };
+                params.push(TypeParam { name, bound });
+                if !self.match_token(&Token::Comma) {
+                    break;
+                }
+            }
+            self.consume(&Token::GreaterThan, "Expected '>' after type parameters")?;
+        }
+        Ok(params)
+    }
+
     /// Parsea type annotation sin consumir * (para usar en patterns donde * es multiplicación)
     fn parse_type_annotation_no_star(&mut self) -> Result<TypeAnnotation, ParseError> {
         if self.match_token(&Token::LParen) {
@@ -977,6 +1287,7 @@ impl Parser {
         // Consumir 'def' ya está hecho en parse_declaration
         
         // Nombre de la macro
+        let name_pos = self.peek_pos();
         let name = match self.advance()?.0 {
             Token::Identifier(n) => n,
             t => return Err(ParseError::UnexpectedToken {
@@ -1023,6 +1334,7 @@ impl Parser {
             params,
             return_type,
             body,
+            pos: name_pos,
         })
     }
 
@@ -1095,14 +1407,20 @@ impl Parser {
 
         while !self.check(&Token::RBrace) && !self.at_end() {
             if self.match_token(&Token::Case) {
-                // case pattern => expr
+                // case pattern [if guarda] => expr
                 let pattern = self.parse_pattern()?;
+                let guard = if self.match_token(&Token::If) {
+                    Some(self.parse_spanned_expr(Precedence::Lowest)?)
+                } else {
+                    None
+                };
                 self.consume(&Token::FuncArrow, "Expected '=>' after case pattern")?;
                 let case_expr = self.parse_spanned_expr(Precedence::Lowest)?;
                 self.consume(&Token::Semicolon, "Expected ';' after case expression")?;
 
                 cases.push(MatchCase {
                     pattern,
+                    guard,
                     expr: case_expr,
                 });
             } else if self.match_token(&Token::Default) {
@@ -1175,11 +1493,29 @@ impl Parser {
         Ok(left)
     }
 
-    /// Analiza patrón primario (literal, variable, wildcard, unario)
+    /// Analiza patrón primario (literal, variable, wildcard, constructor, unario)
     fn parse_pattern_primary(&mut self) -> Result<Pattern, ParseError> {
         let (token, pos) = self.advance()?;
 
         match token {
+            Token::Underscore => Ok(Pattern::Wildcard),
+
+            // Constructor: Point(x, 0)
+            Token::Identifier(func) if self.check(&Token::LParen) => {
+                self.advance()?;
+                let mut args = Vec::new();
+                if !self.check(&Token::RParen) {
+                    loop {
+                        args.push(self.parse_pattern_expr()?);
+                        if !self.match_token(&Token::Comma) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(&Token::RParen, "Expected ')' after constructor pattern")?;
+                Ok(Pattern::Call { func, args })
+            }
+
             // Literales
             Token::Number(n) => Ok(Pattern::Literal(Expr::Number(n))),
             Token::StringLiteral(s) => Ok(Pattern::Literal(Expr::String(s))),
@@ -1,9 +1,10 @@
 use super::*;
+use crate::ast::walk;
 use crate::utils::{Position, Spanned};
 
 macro_rules! pos {
     ($line:expr, $col:expr) => {
-        Position { line: $line, column: $col }
+        Position { line: $line, column: $col, offset: 0 }
     };
 }
 
@@ -19,6 +20,40 @@ macro_rules! bspan {
     };
 }
 
+/// Deja solo línea/columna de inicio en todas las posiciones del programa, para
+/// comparar con ASTs escritos a mano; los rangos se prueban aparte.
+fn starts_only(mut program: Program) -> Program {
+    fn strip(pos: &mut Position) {
+        pos.offset = 0;
+    }
+    fn strip_expr(expr: &mut Spanned<Expr>) {
+        strip(&mut expr.pos);
+        expr.end = expr.pos;
+        walk::children_mut(&mut expr.node).into_iter().for_each(strip_expr);
+    }
+    for decl in &mut program.declarations {
+        match decl {
+            Declaration::Function(f) => strip(&mut f.pos),
+            Declaration::Macro(m) => strip(&mut m.pos),
+            Declaration::Protocol(p) => {
+                strip(&mut p.pos);
+                p.methods.iter_mut().for_each(|m| strip(&mut m.pos));
+            }
+            Declaration::Type(t) => {
+                strip(&mut t.pos);
+                t.attributes.iter_mut().for_each(|a| strip(&mut a.pos));
+                t.methods.iter_mut().for_each(|m| strip(&mut m.pos));
+            }
+            Declaration::Variant(v) => {
+                strip(&mut v.pos);
+                v.cases.iter_mut().for_each(|c| strip(&mut c.pos));
+            }
+        }
+    }
+    walk::root_exprs_mut(&mut program).into_iter().for_each(strip_expr);
+    program
+}
+
 
 #[test]
 fn test_parse_basic_arithmetic() {
@@ -45,7 +80,7 @@ fn test_parse_basic_arithmetic() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 // ============ MACRO TESTS ============
@@ -243,6 +278,52 @@ fn test_parse_pattern_binary() {
     }
 }
 
+#[test]
+fn test_parse_variant_decl() {
+    let input = "variant Shape = Circle(r: Number) | Rect(w: Number, h) | Empty; 1;";
+    let mut parser = Parser::new(input);
+    let program = parser.parse_program().unwrap();
+
+    match &program.declarations[0] {
+        Declaration::Variant(v) => {
+            assert_eq!(v.name, "Shape");
+            let names: Vec<&str> = v.cases.iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, vec!["Circle", "Rect", "Empty"]);
+            assert_eq!(v.cases[0].params[0].name, "r");
+            assert!(v.cases[1].params[1].type_annotation.is_none());
+            assert!(v.cases[2].params.is_empty());
+        }
+        _ => panic!("Expected variant declaration"),
+    }
+    assert!(Parser::new("variant Shape = ; 1;").parse_program().is_err());
+}
+
+#[test]
+fn test_parse_match_guard_and_constructor_pattern() {
+    let input = "match(s) { case Rect(w, _) if w > 0 => w; case _ => 0; };";
+    let mut parser = Parser::new(input);
+    let program = parser.parse_program().unwrap();
+
+    match &program.expr.node {
+        Expr::Match { cases, default, .. } => {
+            assert_eq!(cases.len(), 2);
+            match &cases[0].pattern {
+                Pattern::Call { func, args } => {
+                    assert_eq!(func, "Rect");
+                    assert!(matches!(&args[0], Pattern::Variable { name, type_annotation: None } if name == "w"));
+                    assert_eq!(args[1], Pattern::Wildcard);
+                }
+                _ => panic!("Expected constructor pattern"),
+            }
+            assert!(matches!(&cases[0].guard, Some(g) if matches!(g.node, Expr::Binary(_, Op::Gt, _))));
+            assert_eq!(cases[1].pattern, Pattern::Wildcard);
+            assert!(cases[1].guard.is_none());
+            assert!(default.is_none());
+        }
+        _ => panic!("Expected match expression"),
+    }
+}
+
 #[test]
 fn test_parse_complete_macro_with_match() {
     let input = r#"def simplify(expr: Number) {
@@ -320,6 +401,7 @@ fn test_parse_function_declaration() {
         declarations: vec![
             Declaration::Function(FunctionDecl {
                 name: "add".to_string(),
+                type_params: vec![],
                 params: vec![
                     Param { name: "a".to_string(), type_annotation: None },
                     Param { name: "b".to_string(), type_annotation: None },
@@ -333,6 +415,7 @@ fn test_parse_function_declaration() {
                     ),
                     1, 23
                 ),
+                pos: pos!(1, 10),
             })
         ],
         expr: span!(
@@ -347,7 +430,7 @@ fn test_parse_function_declaration() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -368,7 +451,7 @@ fn test_parse_if_else() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 #[test]
 fn test_parse_nominal_typing_basics() {
@@ -387,6 +470,7 @@ fn test_parse_nominal_typing_basics() {
                         span!(
                             Expr::Instantiation {
                                 ty: "Point".to_string(),
+                                type_args: vec![],
                                 args: vec![
                                     span!(Expr::Number(3.0), 1, 20),
                                     span!(Expr::Number(4.0), 1, 23),
@@ -414,7 +498,7 @@ fn test_parse_nominal_typing_basics() {
                                                             method: "getX".to_string(),
                                                             args: vec![],
                                                         },
-                                                        1, 45
+                                                        1, 43
                                                     ))
                                                 ),
                                                 1, 35
@@ -431,7 +515,7 @@ fn test_parse_nominal_typing_basics() {
                                             method: "getY".to_string(),
                                             args: vec![],
                                         },
-                                        1, 67
+                                        1, 65
                                     ))
                                 ),
                                 1, 35
@@ -445,7 +529,7 @@ fn test_parse_nominal_typing_basics() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -458,12 +542,14 @@ fn test_parse_type_with_inheritance() {
         declarations: vec![
             Declaration::Type(TypeDecl {
                 name: "PolarPoint".to_string(),
+                type_params: vec![],
                 params: vec![],
-                parent: Some(TypeInit { name: "Point".to_string(), args: vec![] }),
+                parent: Some(TypeInit { name: "Point".to_string(), type_args: vec![], args: vec![] }),
                 attributes: vec![],
                 methods: vec![
                     FunctionDecl {
                         name: "rho".to_string(),
+                        type_params: vec![],
                         params: vec![],
                         return_type: None,
                         body: span!(
@@ -477,12 +563,12 @@ fn test_parse_type_with_inheritance() {
                                                     method: "getX".to_string(),
                                                     args: vec![],
                                                 },
-                                                1, 52
+                                                1, 48
                                             )),
                                             Op::Pow,
                                             Box::new(span!(Expr::Number(2.0), 1, 62)),
                                         ),
-                                        1, 52
+                                        1, 48
                                     )),
                                     Op::Add,
                                     Box::new(span!(
@@ -493,26 +579,28 @@ fn test_parse_type_with_inheritance() {
                                                     method: "getY".to_string(),
                                                     args: vec![],
                                                 },
-                                                1, 70
+                                                1, 66
                                             )),
                                             Op::Pow,
                                             Box::new(span!(Expr::Number(2.0), 1, 80)),
                                         ),
-                                        1, 70
+                                        1, 66
                                     )),
                                 ),
-                                1, 52
+                                1, 48
                             ))),
                             1, 43
                         ),
+                        pos: pos!(1, 34),
                     }
                 ],
+                pos: pos!(1, 6),
             })
         ],
         expr: span!(Expr::Block(vec![]), 1, 84),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 
 }
 #[test]
@@ -525,12 +613,14 @@ fn test_parse_inheritance_with_args() {
         declarations: vec![
             Declaration::Type(TypeDecl {
                 name: "PolarPoint".to_string(),
+                type_params: vec![],
                 params: vec![
                     Param { name: "phi".to_string(), type_annotation: None },
                     Param { name: "rho".to_string(), type_annotation: None },
                 ],
                 parent: Some(TypeInit {
                     name: "Point".to_string(),
+                    type_args: vec![],
                     args: vec![
                         span!(
                             Expr::Binary(
@@ -558,12 +648,13 @@ fn test_parse_inheritance_with_args() {
                 }),
                 attributes: vec![],
                 methods: vec![],
+                pos: pos!(1, 6),
             })
         ],
         expr: span!(Expr::Block(vec![]), 1, 76),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -576,15 +667,18 @@ fn test_parse_base_call() {
         declarations: vec![
             Declaration::Type(TypeDecl {
                 name: "Knight".to_string(),
+                type_params: vec![],
                 params: vec![],
                 parent: Some(TypeInit {
                     name: "Person".to_string(),
+                    type_args: vec![],
                     args: vec![],
                 }),
                 attributes: vec![],
                 methods: vec![
                     FunctionDecl {
                         name: "name".to_string(),
+                        type_params: vec![],
                         params: vec![],
                         return_type: None,
                         body: span!(
@@ -598,14 +692,16 @@ fn test_parse_base_call() {
                             ),
                             1, 41
                         ),
+                        pos: pos!(1, 31),
                     },
                 ],
+                pos: pos!(1, 6),
             }),
         ],
         expr: span!(Expr::Block(vec![]), 1, 58),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -618,6 +714,7 @@ fn test_parse_type_with_attributes() {
         declarations: vec![
             Declaration::Type(TypeDecl {
                 name: "Point".to_string(),
+                type_params: vec![],
                 params: vec![],
                 parent: None,
                 attributes: vec![
@@ -625,16 +722,19 @@ fn test_parse_type_with_attributes() {
                         name: "x".to_string(),
                         type_annotation: None,
                         init: span!(Expr::Number(0.0), 1, 18),
+                        pos: pos!(1, 14),
                     },
                     Attribute {
                         name: "y".to_string(),
                         type_annotation: None,
                         init: span!(Expr::Number(0.0), 1, 25),
+                        pos: pos!(1, 21),
                     },
                 ],
                 methods: vec![
                     FunctionDecl {
                         name: "getX".to_string(),
+                        type_params: vec![],
                         params: vec![],
                         return_type: None,
                         body: span!(
@@ -642,16 +742,18 @@ fn test_parse_type_with_attributes() {
                                 obj: Box::new(span!(Expr::Identifier("self".to_string()), 1, 38)),
                                 attribute: "x".to_string(),
                             },
-                            1, 42
+                            1, 38
                         ),
+                        pos: pos!(1, 28),
                     },
                 ],
+                pos: pos!(1, 6),
             }),
         ],
         expr: span!(Expr::Block(vec![]), 1, 46),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 #[test]
 fn test_parse_lambda_simple() {
@@ -680,7 +782,7 @@ fn test_parse_lambda_simple() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -720,7 +822,7 @@ fn test_parse_lambda_with_types() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -733,6 +835,7 @@ fn test_parse_function_type_annotation() {
         declarations: vec![
             Declaration::Function(FunctionDecl {
                 name: "count_when".to_string(),
+                type_params: vec![],
                 params: vec![
                     Param {
                         name: "numbers".to_string(),
@@ -748,12 +851,13 @@ fn test_parse_function_type_annotation() {
                 ],
                 return_type: None,
                 body: span!(Expr::Number(0.0), 1, 71),
+                pos: pos!(1, 10),
             })
         ],
         expr: span!(Expr::Block(vec![]), 1, 72),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -792,7 +896,7 @@ fn test_parse_nested_lambdas() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -852,7 +956,7 @@ fn test_parse_math_builtins() {
         ),
     };
 
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -906,17 +1010,21 @@ fn test_parse_integral_program() {
         declarations: vec![
             Declaration::Protocol(ProtocolDecl {
                 name: "Hashable".to_string(),
+                type_params: vec![],
                 parent: None,
                 methods: vec![
                     MethodSignature {
                         name: "hash".to_string(),
                         params: vec![],
                         return_type: TypeAnnotation::Name("Number".to_string()),
+                        pos: pos!(3, 13),
                     },
                 ],
+                pos: pos!(2, 18),
             }),
             Declaration::Protocol(ProtocolDecl {
                 name: "Comparable".to_string(),
+                type_params: vec![],
                 parent: Some("Hashable".to_string()),
                 methods: vec![
                     MethodSignature {
@@ -928,11 +1036,14 @@ fn test_parse_integral_program() {
                             },
                         ],
                         return_type: TypeAnnotation::Name("Number".to_string()),
+                        pos: pos!(7, 13),
                     },
                 ],
+                pos: pos!(6, 18),
             }),
             Declaration::Type(TypeDecl {
                 name: "Point".to_string(),
+                type_params: vec![],
                 params: vec![
                     Param { name: "x".to_string(), type_annotation: None },
                     Param { name: "y".to_string(), type_annotation: None },
@@ -943,16 +1054,19 @@ fn test_parse_integral_program() {
                         name: "x".to_string(),
                         type_annotation: None,
                         init: span!(Expr::Identifier("x".to_string()), 11, 17),
+                        pos: pos!(11, 13),
                     },
                     Attribute {
                         name: "y".to_string(),
                         type_annotation: None,
                         init: span!(Expr::Identifier("y".to_string()), 12, 17),
+                        pos: pos!(12, 13),
                     },
                 ],
                 methods: vec![
                     FunctionDecl {
                         name: "hash".to_string(),
+                        type_params: vec![],
                         params: vec![],
                         return_type: None,
                         body: span!(
@@ -964,12 +1078,12 @@ fn test_parse_integral_program() {
                                                 obj: bspan!(Expr::Identifier("self".to_string()), 13, 23),
                                                 attribute: "x".to_string(),
                                             },
-                                            13, 27
+                                            13, 23
                                         ),
                                         Op::Pow,
                                         bspan!(Expr::Number(2.0), 13, 32),
                                     ),
-                                    13, 27
+                                    13, 23
                                 ),
                                 Op::Add,
                                 bspan!(
@@ -979,19 +1093,21 @@ fn test_parse_integral_program() {
                                                 obj: bspan!(Expr::Identifier("self".to_string()), 13, 36),
                                                 attribute: "y".to_string(),
                                             },
-                                            13, 40
+                                            13, 36
                                         ),
                                         Op::Pow,
                                         bspan!(Expr::Number(2.0), 13, 46),
                                     ),
-                                    13, 40
+                                    13, 36
                                 ),
                             ),
-                            13, 27
+                            13, 23
                         ),
+                        pos: pos!(13, 13),
                     },
                     FunctionDecl {
                         name: "distance".to_string(),
+                        type_params: vec![],
                         params: vec![
                             Param {
                                 name: "other".to_string(),
@@ -1011,7 +1127,7 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("self".to_string()), 14, 45),
                                                             attribute: "x".to_string(),
                                                         },
-                                                        14, 49
+                                                        14, 45
                                                     ),
                                                     Op::Sub,
                                                     bspan!(
@@ -1019,15 +1135,15 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("other".to_string()), 14, 54),
                                                             attribute: "x".to_string(),
                                                         },
-                                                        14, 59
+                                                        14, 54
                                                     ),
                                                 ),
-                                                14, 49
+                                                14, 45
                                             ),
                                             Op::Pow,
                                             bspan!(Expr::Number(2.0), 14, 65),
                                         ),
-                                        14, 49
+                                        14, 45
                                     ),
                                     Op::Add,
                                     bspan!(
@@ -1039,7 +1155,7 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("self".to_string()), 14, 70),
                                                             attribute: "y".to_string(),
                                                         },
-                                                        14, 74
+                                                        14, 70
                                                     ),
                                                     Op::Sub,
                                                     bspan!(
@@ -1047,26 +1163,29 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("other".to_string()), 14, 79),
                                                             attribute: "y".to_string(),
                                                         },
-                                                        14, 84
+                                                        14, 79
                                                     ),
                                                 ),
-                                                14, 74
+                                                14, 70
                                             ),
                                             Op::Pow,
                                             bspan!(Expr::Number(2.0), 14, 90),
                                         ),
-                                        14, 74
+                                        14, 70
                                     ),
                                 ),
-                                14, 49
+                                14, 45
                             )),
                             14, 39
                         ),
+                        pos: pos!(14, 13),
                     },
                 ],
+                pos: pos!(10, 14),
             }),
             Declaration::Function(FunctionDecl {
                 name: "solve_quadratic".to_string(),
+                type_params: vec![],
                 params: vec![
                     Param { name: "a".to_string(), type_annotation: None },
                     Param { name: "b".to_string(), type_annotation: None },
@@ -1268,6 +1387,7 @@ fn test_parse_integral_program() {
                     ]),
                     17, 43
                 ),
+                pos: pos!(17, 18),
             }),
         ],
         expr: span!(
@@ -1279,6 +1399,7 @@ fn test_parse_integral_program() {
                         span!(
                             Expr::Instantiation {
                                 ty: "Point".to_string(),
+                                type_args: vec![],
                                 args: vec![
                                     span!(Expr::Number(1.0), 28, 28),
                                     span!(Expr::Number(2.0), 28, 31),
@@ -1293,6 +1414,7 @@ fn test_parse_integral_program() {
                         span!(
                             Expr::Instantiation {
                                 ty: "Point".to_string(),
+                                type_args: vec![],
                                 args: vec![
                                     span!(Expr::Number(3.0), 29, 28),
                                     span!(Expr::Number(4.0), 29, 31),
@@ -1316,6 +1438,7 @@ fn test_parse_integral_program() {
                                 body: bspan!(
                                     Expr::Instantiation {
                                         ty: "Point".to_string(),
+                                        type_args: vec![],
                                         args: vec![
                                             span!(
                                                 Expr::Binary(
@@ -1324,12 +1447,12 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("p".to_string()), 30, 58),
                                                             attribute: "x".to_string(),
                                                         },
-                                                        30, 59
+                                                        30, 58
                                                     ),
                                                     Op::Mul,
                                                     bspan!(Expr::Number(2.0), 30, 64),
                                                 ),
-                                                30, 59
+                                                30, 58
                                             ),
                                             span!(
                                                 Expr::Binary(
@@ -1338,12 +1461,12 @@ fn test_parse_integral_program() {
                                                             obj: bspan!(Expr::Identifier("p".to_string()), 30, 67),
                                                             attribute: "y".to_string(),
                                                         },
-                                                        30, 68
+                                                        30, 67
                                                     ),
                                                     Op::Mul,
                                                     bspan!(Expr::Number(2.0), 30, 73),
                                                 ),
-                                                30, 68
+                                                30, 67
                                             ),
                                         ],
                                     },
@@ -1370,7 +1493,7 @@ fn test_parse_integral_program() {
                                                     method: "distance".to_string(),
                                                     args: vec![span!(Expr::Identifier("p2".to_string()), 32, 46)],
                                                 },
-                                                32, 36
+                                                32, 34
                                             ),
                                         ),
                                         32, 19
@@ -1504,7 +1627,7 @@ fn test_parse_integral_program() {
                                                     bspan!(Expr::Identifier("p1".to_string()), 41, 23),
                                                     "Point".to_string(),
                                                 ),
-                                                41, 26
+                                                41, 23
                                             ),
                                             then_expr: bspan!(Expr::String("Success".to_string()), 41, 36),
                                             else_expr: bspan!(Expr::String("Fail".to_string()), 41, 51),
@@ -1524,7 +1647,7 @@ fn test_parse_integral_program() {
                                             bspan!(Expr::Identifier("p1".to_string()), 42, 19),
                                             "Point".to_string(),
                                         ),
-                                        42, 22
+                                        42, 19
                                     )
                                 ],
                             },
@@ -1539,7 +1662,7 @@ fn test_parse_integral_program() {
     };
 
     let program = parser.parse_program().expect("Failed to parse integral program");
-    assert_eq!(program, expected);
+    assert_eq!(starts_only(program), expected);
 }
 
 #[test]
@@ -1566,7 +1689,7 @@ fn test_parse_error_unclosed_parenthesis() {
         Err(ParseError::UnexpectedToken { expected, found, pos }) => {
             assert!(expected.contains(")"), "Expected error message to mention closing parenthesis");
             assert_eq!(found, "Some(\"EOF\")"); 
-            assert_eq!(pos, pos!(1, 11));
+            assert_eq!(Position { offset: 0, ..pos }, pos!(1, 11));
         }
         _ => panic!("Expected UnexpectedToken error for unclosed parenthesis, got {:?}", result),
     }
@@ -1580,7 +1703,7 @@ fn test_parse_error_lexer_unterminated_string() {
 
     match result {
         Err(ParseError::Lex(crate::errors::LexError::UnterminatedString(pos))) => {
-            assert_eq!(pos, pos!(1, 7));
+            assert_eq!(Position { offset: 0, ..pos }, pos!(1, 7));
         }
         _ => panic!("Expected Lex UnterminatedString error, got {:?}", result),
     }
@@ -1643,3 +1766,238 @@ fn test_parse_error_trailing_garbage() {
         _ => panic!("Expected block with multiple expressions"),
     }
 }
+
+#[test]
+fn test_spans_cover_whole_expression() {
+    let input = "1 + 2 * 3;";
+    let program = Parser::new(input).parse_program().unwrap();
+
+    let span = program.expr.span();
+    assert_eq!((span.start.offset, span.end.offset), (0, 9));
+    assert_eq!((span.end.line, span.end.column), (1, 10));
+    match &program.expr.node {
+        Expr::Binary(left, Op::Add, right) => {
+            assert_eq!((left.pos.offset, left.end.offset), (0, 1));
+            assert_eq!(&input[right.pos.offset..right.end.offset], "2 * 3");
+        }
+        other => panic!("Expected Add, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_spans_of_calls_and_blocks() {
+    let input = "let x = 1 in {\n  print(x.foo(1, 2));\n};";
+    let program = Parser::new(input).parse_program().unwrap();
+    let text = |e: &Spanned<Expr>| &input[e.pos.offset..e.end.offset];
+
+    assert_eq!(text(&program.expr), &input[..input.len() - 1]);
+    let Expr::Let { body, .. } = &program.expr.node else { panic!("Expected let") };
+    assert_eq!((body.pos.line, body.end.line, body.end.column), (1, 3, 2));
+    let Expr::Block(exprs) = &body.node else { panic!("Expected block") };
+    assert_eq!(text(&exprs[0]), "print(x.foo(1, 2))");
+    let Expr::Call { args, .. } = &exprs[0].node else { panic!("Expected call") };
+    assert_eq!(text(&args[0]), "x.foo(1, 2)");
+}
+
+#[test]
+fn test_node_at_offset() {
+    let input = "print(a + bcd * 2);";
+    let program = Parser::new(input).parse_program().unwrap();
+
+    let node = walk::node_at(&program.expr, input.find("bcd").unwrap() + 1).unwrap();
+    assert_eq!(node.node, Expr::Identifier("bcd".to_string()));
+    let node = walk::node_at(&program.expr, input.find('*').unwrap()).unwrap();
+    assert!(matches!(node.node, Expr::Binary(_, Op::Mul, _)));
+    assert!(walk::node_at(&program.expr, input.len()).is_none());
+}
+
+#[test]
+fn test_recovery_reports_every_statement_error() {
+    let input = "let a = in a;\nprint(1 +);\nprint(2);";
+    let (program, errors) = Parser::new(input).parse_program_recovering();
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert_eq!(errors[0].pos().line, 1);
+    assert_eq!(errors[1].pos().line, 2);
+    let Expr::Block(exprs) = &program.expr.node else { panic!("Expected block") };
+    assert_eq!(exprs.len(), 3);
+    assert_eq!(exprs[0].node, Expr::Error);
+    assert_eq!(exprs[1].node, Expr::Error);
+    assert!(matches!(&exprs[2].node, Expr::Call { func, .. } if func == "print"));
+}
+
+#[test]
+fn test_recovery_at_declaration_keywords() {
+    let input = "function f(x => x;\ntype A { x = 1; }\nprotocol P { m(: Number; }\nfunction g() => 2;\ng();";
+    let (program, errors) = Parser::new(input).parse_program_recovering();
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    let names: Vec<&str> = program.declarations.iter().map(|d| match d {
+        Declaration::Function(f) => f.name.as_str(),
+        Declaration::Type(t) => t.name.as_str(),
+        Declaration::Protocol(p) => p.name.as_str(),
+        Declaration::Macro(m) => m.name.as_str(),
+        Declaration::Variant(v) => v.name.as_str(),
+    }).collect();
+    // El protocolo se conserva sin la firma errónea
+    assert_eq!(names, vec!["A", "P", "g"]);
+    assert!(matches!(&program.expr.node, Expr::Call { func, .. } if func == "g"));
+}
+
+#[test]
+fn test_recovery_inside_blocks_and_type_bodies() {
+    let input = "type A {\n  x = ;\n  y = 2;\n  m() => self.y;\n}\n{ print(1); let = 2; print(3); };";
+    let (program, errors) = Parser::new(input).parse_program_recovering();
+
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    let Declaration::Type(ty) = &program.declarations[0] else { panic!("Expected type") };
+    assert_eq!(ty.attributes.len(), 1);
+    assert_eq!(ty.attributes[0].name, "y");
+    assert_eq!(ty.methods.len(), 1);
+    let Expr::Block(exprs) = &program.expr.node else { panic!("Expected block") };
+    assert_eq!(exprs.len(), 3);
+    assert_eq!(exprs[1].node, Expr::Error);
+    assert_eq!(&input[exprs[1].pos.offset..exprs[1].end.offset], "let = 2");
+}
+
+#[test]
+fn test_recovery_keeps_unclosed_type() {
+    let input = "type A {\n  x = 1;\ntype B { }\nprint(1);";
+    let (program, errors) = Parser::new(input).parse_program_recovering();
+
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(matches!(&errors[0], ParseError::UnexpectedToken { expected, .. } if expected.contains("'}'")));
+    assert_eq!(program.declarations.len(), 2);
+}
+
+#[test]
+fn test_parse_program_returns_first_error() {
+    let input = "print(1 +);\nprint(;";
+    let first = Parser::new(input).parse_program().unwrap_err();
+    let (_, all) = Parser::new(input).parse_program_recovering();
+    assert_eq!(all.len(), 2);
+    assert_eq!(first, all[0]);
+}
+
+#[test]
+fn test_generic_declarations() {
+    let input = "protocol Comparable<T> { compare(other: T): Number; }\n\
+                 type Pair<A, B>(a: A, b: B) inherits Base<A>(a) { swap(): Pair<B, A> => new Pair<B, A>(self.b, self.a); }\n\
+                 function max<T: Comparable<T>>(a: T, b: T): T => a;\n\
+                 print(1);";
+    let program = Parser::new(input).parse_program().unwrap();
+
+    let Declaration::Protocol(proto) = &program.declarations[0] else { panic!("Expected protocol") };
+    assert_eq!(proto.type_params, vec![TypeParam { name: "T".to_string(), bound: None }]);
+
+    let Declaration::Type(ty) = &program.declarations[1] else { panic!("Expected type") };
+    let names: Vec<&str> = ty.type_params.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["A", "B"]);
+    let parent = ty.parent.as_ref().unwrap();
+    assert_eq!(parent.type_args, vec![TypeAnnotation::Name("A".to_string())]);
+    let swap = &ty.methods[0];
+    assert_eq!(swap.return_type, Some(TypeAnnotation::Generic {
+        name: "Pair".to_string(),
+        args: vec![TypeAnnotation::Name("B".to_string()), TypeAnnotation::Name("A".to_string())],
+    }));
+    let Expr::Instantiation { ty: class, type_args, args } = &swap.body.node else { panic!("Expected instantiation") };
+    assert_eq!(class, "Pair");
+    assert_eq!(type_args.len(), 2);
+    assert_eq!(args.len(), 2);
+
+    let Declaration::Function(func) = &program.declarations[2] else { panic!("Expected function") };
+    assert_eq!(func.type_params, vec![TypeParam {
+        name: "T".to_string(),
+        bound: Some(TypeAnnotation::Generic {
+            name: "Comparable".to_string(),
+            args: vec![TypeAnnotation::Name("T".to_string())],
+        }),
+    }]);
+}
+
+#[test]
+fn test_generic_annotations_nest() {
+    let input = "let s: Stack<Box<Number>[]> = new Stack<Box<Number>[]>() in s;";
+    let program = Parser::new(input).parse_program().unwrap();
+    let Expr::Let { bindings, .. } = &program.expr.node else { panic!("Expected let") };
+    let expected = TypeAnnotation::Generic {
+        name: "Stack".to_string(),
+        args: vec![TypeAnnotation::Vector(Box::new(TypeAnnotation::Generic {
+            name: "Box".to_string(),
+            args: vec![TypeAnnotation::Name("Number".to_string())],
+        }))],
+    };
+    assert_eq!(bindings[0].1, Some(expected.clone()));
+    assert_eq!(expected.to_string(), "Stack<Box<Number>[]>");
+}
+
+#[test]
+fn test_unclosed_type_arguments() {
+    let err = Parser::new("let b: Box<Number = 1 in b;").parse_program().unwrap_err();
+    assert!(matches!(err, ParseError::UnexpectedToken { ref expected, .. } if expected.contains("'>'")));
+}
+
+#[test]
+fn test_parse_try_catch_and_throw() {
+    let input = "try f(x) catch (e: IndexError) 0 catch (e) throw e;";
+    let program = Parser::new(input).parse_program().unwrap();
+    let Expr::Try { body, catches } = &program.expr.node else { panic!("Expected try") };
+    assert!(matches!(&body.node, Expr::Call { .. }));
+    assert_eq!(catches.len(), 2);
+    assert_eq!((catches[0].var.as_str(), catches[0].ty.as_deref()), ("e", Some("IndexError")));
+    assert_eq!(catches[1].ty, None);
+    assert!(matches!(&catches[1].body.node, Expr::Throw(e) if matches!(&e.node, Expr::Identifier(n) if n == "e")));
+    // Un `try` sin `catch` no tiene sentido
+    assert!(Parser::new("try f(x);").parse_program().is_err());
+}
+
+#[test]
+fn test_parse_break_continue_and_return() {
+    let input = "while (c) { if (a) break else continue; if (b) break 1 else continue x + 1; }";
+    let program = Parser::new(input).parse_program().unwrap();
+    let Expr::While { body, .. } = &program.expr.node else { panic!("Expected while") };
+    let Expr::Block(exprs) = &body.node else { panic!("Expected block") };
+    let Expr::If { then_expr, else_expr, .. } = &exprs[0].node else { panic!("Expected if") };
+    assert!(matches!(&then_expr.node, Expr::Break(None)));
+    assert!(matches!(&else_expr.node, Expr::Continue(None)));
+    let Expr::If { then_expr, else_expr, .. } = &exprs[1].node else { panic!("Expected if") };
+    assert!(matches!(&then_expr.node, Expr::Break(Some(v)) if matches!(v.node, Expr::Number(n) if n == 1.0)));
+    assert!(matches!(&else_expr.node, Expr::Continue(Some(v)) if matches!(v.node, Expr::Binary(..))));
+    let program = Parser::new("function f(x) { return x * 2; }").parse_program().unwrap();
+    let Declaration::Function(f) = &program.declarations[0] else { panic!("Expected function") };
+    let Expr::Block(exprs) = &f.body.node else { panic!("Expected block") };
+    assert!(matches!(&exprs[0].node, Expr::Return(v) if matches!(v.node, Expr::Binary(..))));
+    // `return` siempre lleva valor
+    assert!(Parser::new("function f() { return; }").parse_program().is_err());
+}
+
+#[test]
+fn test_parse_map_literal() {
+    let program = Parser::new("let m = {\"a\" -> 1, \"b\" -> 2 + 3,} in m;").parse_program().unwrap();
+    let Expr::Let { bindings, .. } = &program.expr.node else { panic!("Expected let") };
+    let Expr::MapLiteral(entries) = &bindings[0].2.node else { panic!("Expected map literal") };
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(&entries[0].0.node, Expr::String(s) if s == "a"));
+    assert!(matches!(&entries[1].1.node, Expr::Binary(..)));
+    // Un bloque sigue siendo un bloque
+    let program = Parser::new("{ a; b; }").parse_program().unwrap();
+    assert!(matches!(&program.expr.node, Expr::Block(exprs) if exprs.len() == 2));
+    assert!(Parser::new("print({1 -> 2; 3 -> 4});").parse_program().is_err());
+}
+
+#[test]
+fn test_parse_index_assignment_and_slices() {
+    let program = Parser::new("{ v[i + 1] := 2; v[1:n]; v[:2]; v[a:]; v[:]; }").parse_program().unwrap();
+    let Expr::Block(exprs) = &program.expr.node else { panic!("Expected block") };
+    let Expr::IndexAssignment { obj, index, value } = &exprs[0].node else { panic!("Expected index assignment") };
+    assert!(matches!(&obj.node, Expr::Identifier(v) if v == "v"));
+    assert!(matches!(&index.node, Expr::Binary(..)));
+    assert!(matches!(&value.node, Expr::Number(n) if *n == 2.0));
+    let bounds: Vec<(bool, bool)> = exprs[1..].iter().map(|e| match &e.node {
+        Expr::Slice { start, end, .. } => (start.is_some(), end.is_some()),
+        other => panic!("Expected slice, got {:?}", other),
+    }).collect();
+    assert_eq!(bounds, vec![(true, true), (false, true), (true, false), (false, false)]);
+    assert!(Parser::new("v[1:2:3];").parse_program().is_err());
+    assert!(Parser::new("v[1:2] := 3;").parse_program().is_err());
+}
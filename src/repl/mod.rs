@@ -0,0 +1,359 @@
+//! `hulk repl`: evalúa expresiones y declaraciones una a una. Las funciones,
+//! tipos, protocolos y macros declarados se conservan entre entradas; cada
+//! entrada se chequea junto con ellos y se ejecuta con el intérprete, así que
+//! no hace falta clang.
+
+#[cfg(test)]
+mod tests;
+
+use crate::ast::nodes::{Declaration, Expr, Program};
+use crate::ast::walk;
+use crate::driver;
+use crate::errors::{Diagnostic, LexError, SemanticError};
+use crate::interp::{Interpreter, Value};
+use crate::lexer::Lexer;
+use crate::lexer::tokens::Token;
+use crate::semantic::{check_program, TypeChecker};
+use crate::utils::{Position, Spanned};
+
+use std::io::{self, BufRead, Write};
+
+const PROMPT: &str = "hulk> ";
+const CONTINUATION: &str = "...   ";
+
+const HELP: &str = "\
+Type an expression to evaluate it, or a function, type, protocol or macro
+declaration to add it to the session (a new declaration replaces an older
+one with the same name). Incomplete input continues on the next line; an
+empty line evaluates it as is.
+
+Commands:
+  :type <expr>   show the inferred type of <expr> without evaluating it
+  :reset         forget every declaration
+  :help          show this message
+  :quit          leave the REPL (also Ctrl-D)";
+
+/// Resultado de evaluar una entrada.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Outcome {
+    /// Declaraciones añadidas a la sesión, p. ej. `function f`.
+    pub defined: Vec<String>,
+    /// Valor de la expresión y su tipo inferido, si hay algo que mostrar.
+    pub value: Option<(String, String)>,
+}
+
+/// Texto de una entrada y posición en la que empieza dentro de la sesión.
+#[derive(Debug)]
+struct Input {
+    source: String,
+    start: Position,
+}
+
+/// Estado de una sesión. Las posiciones del AST son relativas a la sesión
+/// completa (como si las entradas estuvieran una tras otra en un archivo), así
+/// que un error dentro de una función declarada antes se muestra sobre el
+/// texto de la entrada que la declaró.
+#[derive(Debug, Default)]
+pub struct Session {
+    /// Declaraciones tal como se escribieron (sin expandir), para volver a
+    /// chequearlas junto con cada entrada.
+    declarations: Vec<Declaration>,
+    inputs: Vec<Input>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Olvida todas las declaraciones.
+    pub fn reset(&mut self) {
+        self.declarations.clear();
+    }
+
+    /// Chequea y ejecuta `input`; lo que imprima el programa va a `out`. Las
+    /// declaraciones de la entrada se guardan solo si el chequeo tiene éxito.
+    pub fn eval(&mut self, input: &str, out: impl Write) -> Result<Outcome, Vec<Diagnostic>> {
+        let parsed = self.parse(input)?;
+        let defined = parsed.declarations.iter().map(describe).collect();
+        let echo = !is_silent(&parsed.expr);
+        let declarations = self.merged(parsed.declarations);
+
+        let program = driver::expand_program(Program { declarations: declarations.clone(), expr: parsed.expr });
+        let context = check_program(&program).map_err(|e| e.iter().map(Diagnostic::from).collect::<Vec<_>>())?;
+        let ty = TypeChecker::new(&context)
+            .infer_type(&program.expr)
+            .map_or_else(|_| "Object".to_string(), |t| t.borrow().name.clone());
+        self.declarations = declarations;
+
+        let value = Interpreter::new(&program, &context, out)
+            .run()
+            .map_err(|e| vec![Diagnostic::from(&e)])?;
+        Ok(Outcome { defined, value: echo.then(|| (show(&value), ty)) })
+    }
+
+    /// Tipo inferido de la expresión `input` en el contexto de la sesión.
+    pub fn type_of(&mut self, input: &str) -> Result<String, Vec<Diagnostic>> {
+        let parsed = self.parse(input)?;
+        let declarations = self.merged(parsed.declarations);
+        let program = driver::expand_program(Program { declarations, expr: parsed.expr });
+        let errors = |e: Vec<SemanticError>| e.iter().map(Diagnostic::from).collect::<Vec<_>>();
+        let context = check_program(&program).map_err(errors)?;
+        let ty = TypeChecker::new(&context).infer_type(&program.expr).map_err(errors)?;
+        let name = ty.borrow().name.clone();
+        Ok(name)
+    }
+
+    /// Renderiza diagnósticos de la sesión, cada uno sobre el texto de la
+    /// entrada a la que apunta (`<repl:N>`, numeradas desde 1).
+    pub fn render(&self, diagnostics: &[Diagnostic]) -> String {
+        diagnostics.iter()
+            .map(|diag| {
+                let line = diag.span.start.line;
+                let found = self.inputs.iter().enumerate().rev().find(|(_, input)| line > input.start.line);
+                let Some((index, input)) = found.filter(|_| line > 0) else {
+                    return diag.render("", "<repl>");
+                };
+                let mut local = diag.clone();
+                unshift(&mut local.span.start, input.start);
+                unshift(&mut local.span.end, input.start);
+                local.render(&input.source, &format!("<repl:{}>", index + 1))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Parsea `input` y lo registra como entrada nueva, con posiciones
+    /// desplazadas detrás de las entradas anteriores.
+    fn parse(&mut self, input: &str) -> Result<Program, Vec<Diagnostic>> {
+        let start = self.inputs.last().map_or_else(Position::default, |last| Position {
+            line: last.start.line + last.source.lines().count().max(1),
+            column: 0,
+            offset: last.start.offset + last.source.len(),
+        });
+        self.inputs.push(Input { source: input.to_string(), start });
+
+        match driver::parse(input) {
+            Ok(mut program) => {
+                shift_program(&mut program, start);
+                Ok(program)
+            }
+            Err(e) => Err(e.diagnostics().into_iter()
+                .map(|mut d| {
+                    shift(&mut d.span.start, start);
+                    shift(&mut d.span.end, start);
+                    d
+                })
+                .collect()),
+        }
+    }
+
+    /// Declaraciones de la sesión más las nuevas, que reemplazan a las
+    /// anteriores con el mismo nombre.
+    fn merged(&self, new: Vec<Declaration>) -> Vec<Declaration> {
+        let mut merged: Vec<Declaration> = self.declarations.iter()
+            .filter(|old| !new.iter().any(|d| same_name(old, d)))
+            .cloned()
+            .collect();
+        merged.extend(new);
+        merged
+    }
+}
+
+/// Bucle interactivo: lee entradas de `input` y escribe prompts, resultados
+/// y errores en `out`.
+pub fn run(input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    let mut session = Session::new();
+    let mut buffer = String::new();
+    write!(out, "{}", PROMPT)?;
+    out.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        if buffer.is_empty() && line.trim_start().starts_with(':') {
+            if !command(&mut session, line.trim(), &mut out)? {
+                return Ok(());
+            }
+        } else if !line.trim().is_empty() || !buffer.is_empty() {
+            buffer.push_str(&line);
+            buffer.push('\n');
+            // Una línea vacía fuerza la evaluación de una entrada incompleta
+            if !line.trim().is_empty() && is_incomplete(&buffer) {
+                write!(out, "{}", CONTINUATION)?;
+                out.flush()?;
+                continue;
+            }
+            let source = std::mem::take(&mut buffer);
+            evaluate(&mut session, &source, &mut out)?;
+        }
+        write!(out, "{}", PROMPT)?;
+        out.flush()?;
+    }
+
+    if !buffer.is_empty() {
+        evaluate(&mut session, &buffer, &mut out)?;
+    }
+    writeln!(out)
+}
+
+fn evaluate(session: &mut Session, source: &str, out: &mut impl Write) -> io::Result<()> {
+    match session.eval(source, &mut *out) {
+        Ok(outcome) => {
+            for name in &outcome.defined {
+                writeln!(out, "defined {}", name)?;
+            }
+            if let Some((value, ty)) = &outcome.value {
+                writeln!(out, "{} : {}", value, ty)?;
+            }
+            Ok(())
+        }
+        Err(diagnostics) => write!(out, "{}", session.render(&diagnostics)),
+    }
+}
+
+/// Ejecuta un comando `:...`; devuelve `false` si hay que salir.
+fn command(session: &mut Session, line: &str, out: &mut impl Write) -> io::Result<bool> {
+    let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    match name {
+        ":quit" | ":q" => return Ok(false),
+        ":help" | ":h" => writeln!(out, "{}", HELP)?,
+        ":reset" => {
+            session.reset();
+            writeln!(out, "session cleared")?;
+        }
+        ":type" | ":t" if !arg.trim().is_empty() => match session.type_of(arg) {
+            Ok(ty) => writeln!(out, "{}", ty)?,
+            Err(diagnostics) => write!(out, "{}", session.render(&diagnostics))?,
+        },
+        ":type" | ":t" => writeln!(out, "usage: :type <expr>")?,
+        other => writeln!(out, "unknown command '{}' (try :help)", other)?,
+    }
+    Ok(true)
+}
+
+/// Indica si `source` está a medias: un string o comentario sin cerrar,
+/// paréntesis o llaves abiertos, o una última línea que termina en un
+/// operador o palabra clave que exige continuación.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth = 0i32;
+    let mut last = None;
+    for tok in Lexer::new(source) {
+        match tok {
+            Ok((Token::EOF, _)) => break,
+            Ok((tok, _)) => {
+                match tok {
+                    Token::LParen | Token::LBrace | Token::LBracket => depth += 1,
+                    Token::RParen | Token::RBrace | Token::RBracket => depth -= 1,
+                    _ => {}
+                }
+                last = Some(tok);
+            }
+            Err(LexError::UnterminatedString(_) | LexError::UnterminatedBlockComment(_)) => return true,
+            Err(LexError::UnexpectedCharacter(..)) => return false,
+        }
+    }
+    depth > 0 || last.as_ref().is_some_and(expects_more)
+}
+
+fn expects_more(tok: &Token) -> bool {
+    matches!(
+        tok,
+        Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent | Token::Power
+            | Token::Equal | Token::NotEqual | Token::LessThan | Token::GreaterThan
+            | Token::LessThanEq | Token::GreaterThanEq | Token::And | Token::Or | Token::Not
+            | Token::Concat | Token::ConcatSpace | Token::Assign | Token::DestructAssign
+            | Token::FuncArrow | Token::TypeArrow | Token::Comma | Token::Colon | Token::Dot
+            | Token::In | Token::Else | Token::Elif | Token::Inherits | Token::Extends
+            | Token::Is | Token::As | Token::New
+    )
+}
+
+/// Las entradas cuyo valor final sale de `print`, de un bucle o de una
+/// secuencia vacía (solo declaraciones) no muestran resultado.
+fn is_silent(expr: &Spanned<Expr>) -> bool {
+    match &expr.node {
+        Expr::Block(exprs) => exprs.last().is_none_or(is_silent),
+        Expr::Let { body, .. } => is_silent(body),
+        Expr::Call { func, .. } => func == "print",
+        Expr::While { .. } | Expr::For { .. } => true,
+        _ => false,
+    }
+}
+
+/// Valor tal como se muestra en el REPL: los strings, entre comillas.
+fn show(value: &Value<'_>) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        other => other.to_string(),
+    }
+}
+
+fn describe(decl: &Declaration) -> String {
+    match decl {
+        Declaration::Function(f) => format!("function {}", f.name),
+        Declaration::Type(t) => format!("type {}", t.name),
+        Declaration::Protocol(p) => format!("protocol {}", p.name),
+        Declaration::Macro(m) => format!("macro {}", m.name),
+        Declaration::Variant(v) => format!("variant {}", v.name),
+    }
+}
+
+/// Funciones y macros comparten un espacio de nombres; tipos y protocolos, otro.
+fn same_name(a: &Declaration, b: &Declaration) -> bool {
+    let key = |d: &Declaration| match d {
+        Declaration::Function(f) => (0, f.name.clone()),
+        Declaration::Macro(m) => (0, m.name.clone()),
+        Declaration::Type(t) => (1, t.name.clone()),
+        Declaration::Protocol(p) => (1, p.name.clone()),
+        Declaration::Variant(v) => (1, v.name.clone()),
+    };
+    key(a) == key(b)
+}
+
+/// Desplaza todas las posiciones de `program` para que empiecen en `start`.
+fn shift_program(program: &mut Program, start: Position) {
+    fn shift_expr(expr: &mut Spanned<Expr>, start: Position) {
+        shift(&mut expr.pos, start);
+        shift(&mut expr.end, start);
+        for child in walk::children_mut(&mut expr.node) {
+            shift_expr(child, start);
+        }
+    }
+    for decl in &mut program.declarations {
+        match decl {
+            Declaration::Function(f) => shift(&mut f.pos, start),
+            Declaration::Macro(m) => shift(&mut m.pos, start),
+            Declaration::Protocol(p) => {
+                shift(&mut p.pos, start);
+                p.methods.iter_mut().for_each(|m| shift(&mut m.pos, start));
+            }
+            Declaration::Type(t) => {
+                shift(&mut t.pos, start);
+                t.attributes.iter_mut().for_each(|a| shift(&mut a.pos, start));
+                t.methods.iter_mut().for_each(|m| shift(&mut m.pos, start));
+            }
+            Declaration::Variant(v) => {
+                shift(&mut v.pos, start);
+                v.cases.iter_mut().for_each(|c| shift(&mut c.pos, start));
+            }
+        }
+    }
+    for expr in walk::root_exprs_mut(program) {
+        shift_expr(expr, start);
+    }
+}
+
+/// Las posiciones desconocidas (0:0) no se desplazan.
+fn shift(pos: &mut Position, start: Position) {
+    if pos.line > 0 {
+        pos.line += start.line;
+        pos.offset += start.offset;
+    }
+}
+
+fn unshift(pos: &mut Position, start: Position) {
+    if pos.line > start.line {
+        pos.line -= start.line;
+        pos.offset = pos.offset.saturating_sub(start.offset);
+    }
+}
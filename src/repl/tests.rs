@@ -0,0 +1,93 @@
+use super::*;
+
+/// Evalúa `input` en la sesión y devuelve el resultado junto con lo impreso.
+fn eval(session: &mut Session, input: &str) -> (Outcome, String) {
+    let mut printed = Vec::new();
+    let outcome = session.eval(input, &mut printed)
+        .unwrap_or_else(|d| panic!("la entrada falló:\n{}", session.render(&d)));
+    (outcome, String::from_utf8(printed).unwrap())
+}
+
+fn value(session: &mut Session, input: &str) -> (String, String) {
+    eval(session, input).0.value.expect("se esperaba un valor")
+}
+
+/// Ejecuta el bucle completo sobre `input` y devuelve todo lo que escribió.
+fn transcript(input: &str) -> String {
+    let mut out = Vec::new();
+    run(input.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn shows_values_with_their_inferred_type() {
+    let mut session = Session::new();
+    assert_eq!(value(&mut session, "1 + 2"), ("3".to_string(), "Number".to_string()));
+    assert_eq!(value(&mut session, "\"a\" @ 1;"), ("\"a1\"".to_string(), "String".to_string()));
+    assert_eq!(value(&mut session, "[1, 2]"), ("[1, 2]".to_string(), "Vector<Number>".to_string()));
+}
+
+#[test]
+fn keeps_declarations_between_inputs() {
+    let mut session = Session::new();
+    let (outcome, _) = eval(&mut session, "type Point(x: Number) { x: Number = x; getX(): Number => self.x; }");
+    assert_eq!(outcome.defined, vec!["type Point"]);
+    assert_eq!(outcome.value, None);
+
+    eval(&mut session, "function twice(p: Point): Number => 2 * p.getX();");
+    assert_eq!(value(&mut session, "twice(new Point(21))"), ("42".to_string(), "Number".to_string()));
+    assert_eq!(session.type_of("new Point(1)").unwrap(), "Point");
+}
+
+#[test]
+fn new_declarations_replace_old_ones() {
+    let mut session = Session::new();
+    eval(&mut session, "function f(): Number => 1;");
+    eval(&mut session, "function f(): Number => 2;");
+    assert_eq!(value(&mut session, "f()").0, "2");
+}
+
+#[test]
+fn failed_inputs_do_not_change_the_session() {
+    let mut session = Session::new();
+    let errors = session.eval("function f(): Number => y; f()", Vec::new()).unwrap_err();
+    assert_eq!(errors[0].code, "E0206");
+    assert!(session.eval("f()", Vec::new()).is_err());
+}
+
+#[test]
+fn print_writes_output_without_echo() {
+    let mut session = Session::new();
+    let (outcome, printed) = eval(&mut session, "print(1); print(\"dos\");");
+    assert_eq!(outcome.value, None);
+    assert_eq!(printed, "1\ndos\n");
+}
+
+#[test]
+fn errors_point_into_the_input_that_declared_the_code() {
+    let mut session = Session::new();
+    eval(&mut session, "1");
+    eval(&mut session, "function at(v: Number[], i: Number): Number =>\n    v[i];");
+    let errors = session.eval("at([1], 3)", Vec::new()).unwrap_err();
+    let rendered = session.render(&errors);
+    assert!(rendered.contains("--> <repl:2>:2:5"), "{}", rendered);
+    assert!(rendered.contains("2 |     v[i];"), "{}", rendered);
+}
+
+#[test]
+fn detects_incomplete_input() {
+    assert!(is_incomplete("function f(x) {\n"));
+    assert!(is_incomplete("let x = 1 in\n"));
+    assert!(is_incomplete("1 +\n"));
+    assert!(is_incomplete("print(\"abc\n"));
+    assert!(!is_incomplete("print(1);\n"));
+    assert!(!is_incomplete("type A { }\n"));
+}
+
+#[test]
+fn runs_a_whole_session() {
+    let out = transcript("function sq(x: Number): Number =>\n  x * x;\nsq(3)\n:type sq(1) > 2\n:help\n:quit\nsq(4)\n");
+    assert!(out.starts_with("hulk> ...   defined function sq\nhulk> 9 : Number\nhulk> Boolean\nhulk> "), "{}", out);
+    assert!(out.contains(":reset"));
+    assert!(!out.contains("16"));
+}
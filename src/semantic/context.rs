@@ -1,40 +1,135 @@
 use crate::ast::nodes::*;
 use crate::errors::SemanticError;
-use super::types::{Type, TypeKind, TypeFactory};
+use crate::utils::{Position, Span};
+use super::types::{Type, TypeKind, TypeFactory, MethodInfo};
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 /// Contexto global del análisis semántico
 pub struct Context {
     pub types: HashMap<String, Rc<RefCell<Type>>>,
     pub functions: HashMap<String, (Vec<Rc<RefCell<Type>>>, Rc<RefCell<Type>>)>,
+    /// Parámetros de tipo de cada función genérica.
+    pub function_type_params: HashMap<String, Vec<Rc<RefCell<Type>>>>,
+    /// Instancias de tipos genéricos ya creadas, por nombre (`Stack<Number>`).
+    pub instances: RefCell<HashMap<String, Rc<RefCell<Type>>>>,
+    /// Se activa cuando todos los miembros están registrados: desde entonces
+    /// resolver una instancia comprueba las cotas de sus argumentos.
+    pub check_bounds: Cell<bool>,
+    /// Tipo concreto de las expresiones cuyo tipo sale de instanciar un
+    /// genérico (`b.get()` con `b: Box<String>`). El codegen borra los
+    /// parámetros de tipo y lo usa para desempaquetar esos resultados.
+    pub generic_sites: HashMap<Span, Rc<RefCell<Type>>>,
+    /// Tipo estrechado de cada uso de una variable dentro de la rama donde un
+    /// `is` lo comprobó (`x` en `if (x is Dog) x.bark() else ...`).
+    pub narrowed_sites: HashMap<Span, Rc<RefCell<Type>>>,
+    /// Tipo del elemento de cada `for` o comprensión que recorre un iterable
+    /// genérico (`xs` con `xs: String*`), por el rango del iterable. El codegen
+    /// desempaqueta a ese tipo lo que devuelve `current()`.
+    pub element_sites: HashMap<Span, Rc<RefCell<Type>>>,
+    /// Tipos de los parámetros de cada lambda, anotados o inferidos, por el
+    /// rango de la lambda.
+    pub lambda_types: HashMap<Span, Vec<Rc<RefCell<Type>>>>,
+    /// Casos de cada `variant`, por el nombre de su tipo base. La jerarquía es
+    /// cerrada: solo los casos heredan del tipo base, que no se instancia.
+    pub variants: HashMap<String, Vec<String>>,
 }
 
 impl Context {
     pub fn new() -> Self {
         let mut types = HashMap::new();
         let object = TypeFactory::object();
+        let number = TypeFactory::number(object.clone());
+        let boolean = TypeFactory::boolean(object.clone());
+        let string = TypeFactory::string(object.clone(), number.clone(), boolean.clone());
         types.insert("Object".to_string(), object.clone());
-        types.insert("Number".to_string(), TypeFactory::number(object.clone()));
-        types.insert("Boolean".to_string(), TypeFactory::boolean(object.clone()));
-        types.insert("String".to_string(), TypeFactory::string(object.clone()));
+        types.insert("Number".to_string(), number);
+        types.insert("Boolean".to_string(), boolean);
+        types.insert("String".to_string(), string.clone());
+        types.insert("Never".to_string(), TypeFactory::never(object.clone()));
         
-        Context { 
+        let mut context = Context { 
             types,
             functions: HashMap::new(),
+            function_type_params: HashMap::new(),
+            instances: RefCell::new(HashMap::new()),
+            check_bounds: Cell::new(false),
+            generic_sites: HashMap::new(),
+            narrowed_sites: HashMap::new(),
+            element_sites: HashMap::new(),
+            lambda_types: HashMap::new(),
+            variants: HashMap::new(),
+        };
+        let strings = context.vector_type(string.clone());
+        string.borrow_mut().define_method("split".to_string(), vec![("sep".to_string(), string.clone())], strings);
+        let iterable = context.iterable_declaration();
+        context.types.insert("Iterable".to_string(), iterable);
+        let map = context.map_declaration();
+        context.types.insert("Map".to_string(), map);
+        context
+    }
+
+    /// Declaración del protocolo genérico `Iterable<T>`, el que recorre un
+    /// `for`: `next()` avanza y dice si queda un elemento, `current()` lo
+    /// devuelve. `T*` es azúcar para `Iterable<T>`.
+    fn iterable_declaration(&self) -> Rc<RefCell<Type>> {
+        let iterable = Rc::new(RefCell::new(Type::new("Iterable", TypeKind::Protocol, None)));
+        let t = TypeFactory::param("T", self.types["Object"].clone());
+        let mut i = iterable.borrow_mut();
+        i.define_method("next".to_string(), vec![], self.types["Boolean"].clone());
+        i.define_method("current".to_string(), vec![], t.clone());
+        i.type_params = vec![t];
+        drop(i);
+        iterable
+    }
+
+    /// Declaración genérica del diccionario `Map<K, V>`. Se recorre como un
+    /// iterable de sus claves, en el orden en que se insertaron.
+    fn map_declaration(&self) -> Rc<RefCell<Type>> {
+        let object = self.types["Object"].clone();
+        let number = self.types["Number"].clone();
+        let boolean = self.types["Boolean"].clone();
+        let map = Rc::new(RefCell::new(Type::new("Map", TypeKind::Basic, Some(object.clone()))));
+        let k = TypeFactory::param("K", object.clone());
+        let v = TypeFactory::param("V", object);
+        let key = || ("key".to_string(), k.clone());
+        let methods = [
+            ("size", vec![], number),
+            ("get", vec![key()], v.clone()),
+            ("set", vec![key(), ("value".to_string(), v.clone())], v.clone()),
+            ("has", vec![key()], boolean.clone()),
+            ("remove", vec![key()], boolean.clone()),
+            ("keys", vec![], self.vector_type(k.clone())),
+            ("values", vec![], self.vector_type(v.clone())),
+            ("next", vec![], boolean),
+            ("current", vec![], k.clone()),
+            ("get_current", vec![], k.clone()),
+        ];
+        for (name, params, ret) in methods {
+            map.borrow_mut().define_method(name.to_string(), params, ret);
         }
+        map.borrow_mut().type_params = vec![k, v];
+        map
+    }
+
+    /// Instancia `Map<K, V>`.
+    pub fn map_type(&self, key: Rc<RefCell<Type>>, value: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
+        let map = self.types["Map"].clone();
+        self.instantiate(&map, vec![key, value]).expect("Map tiene dos parámetros sin cotas")
     }
 
+    /// Busca un tipo por nombre. Los errores salen sin posición; el llamador
+    /// la asigna con `SemanticError::at`.
     pub fn get_type(&self, name: &str) -> Result<Rc<RefCell<Type>>, SemanticError> {
         self.types.get(name)
             .cloned()
-            .ok_or(SemanticError::TypeNotFound(name.to_string()))
+            .ok_or(SemanticError::TypeNotFound(name.to_string(), Position::default()))
     }
     
     pub fn create_type(&mut self, name: &str) -> Result<Rc<RefCell<Type>>, SemanticError> {
         if self.types.contains_key(name) {
-             return Err(SemanticError::TypeDefined(name.to_string()));
+             return Err(SemanticError::TypeDefined(name.to_string(), Position::default()));
         }
         let t = Rc::new(RefCell::new(Type::new(name, TypeKind::Basic, None)));
         self.types.insert(name.to_string(), t.clone());
@@ -43,7 +138,7 @@ impl Context {
 
     pub fn define_function(&mut self, name: &str, params: Vec<Rc<RefCell<Type>>>, ret: Rc<RefCell<Type>>) -> Result<(), SemanticError> {
         if self.functions.contains_key(name) {
-            return Err(SemanticError::FunctionDefined(name.to_string()));
+            return Err(SemanticError::FunctionDefined(name.to_string(), Position::default()));
         }
         self.functions.insert(name.to_string(), (params, ret));
         Ok(())
@@ -74,49 +169,212 @@ impl Context {
 
     /// Resuelve una anotación de tipo al tipo real
     pub fn resolve_type(&self, annotation: &TypeAnnotation) -> Result<Rc<RefCell<Type>>, SemanticError> {
+        self.resolve_type_in(annotation, &[])
+    }
+
+    /// Resuelve una anotación con los parámetros de tipo `scope` visibles
+    /// (los de la función o el tipo genérico que la contiene).
+    pub fn resolve_type_in(&self, annotation: &TypeAnnotation, scope: &[Rc<RefCell<Type>>]) -> Result<Rc<RefCell<Type>>, SemanticError> {
         match annotation {
-            TypeAnnotation::Name(name) => self.get_type(name),
+            TypeAnnotation::Name(name) => {
+                if let Some(param) = scope.iter().find(|p| p.borrow().name == *name) {
+                    return Ok(param.clone());
+                }
+                let ty = self.get_type(name)?;
+                let expected = ty.borrow().type_params.len();
+                if expected > 0 {
+                    return Err(SemanticError::TypeArgumentCountMismatch(name.clone(), expected, 0, Position::default()));
+                }
+                Ok(ty)
+            }
+            TypeAnnotation::Generic { name, args } => {
+                let base = self.get_type(name)?;
+                let args = args.iter()
+                    .map(|a| self.resolve_type_in(a, scope))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.instantiate(&base, args)
+            }
             TypeAnnotation::Iterable(inner) => {
-                 let inner_type = self.resolve_type(inner)?;
-                 let name = format!("Iterable<{}>", inner_type.borrow().name);
-                 
-                 // Crear 'al vuelo' el protocolo Iterable<T>
-                 let iter = Rc::new(RefCell::new(Type::new(&name, TypeKind::Protocol, None)));
-                 
-                 let bool_type = self.get_type("Boolean")?;
-                 iter.borrow_mut().define_method("next".to_string(), vec![], bool_type);
-                 iter.borrow_mut().define_method("current".to_string(), vec![], inner_type);
-                 
-                 Ok(iter)
+                let inner_type = self.resolve_type_in(inner, scope)?;
+                let iterable = self.get_type("Iterable")?;
+                self.instantiate(&iterable, vec![inner_type])
             },
             TypeAnnotation::Vector(inner) => {
-                // Sistema de tipos genéricos para Vector<T>
-                let inner_type = self.resolve_type(inner)?;
-                let name = format!("Vector<{}>", inner_type.borrow().name);
-                
-                // Crear tipo Vector<T> al vuelo con métodos del protocolo Iterable
-                let vec_type = Rc::new(RefCell::new(Type::new(&name, TypeKind::Basic, None)));
-                
-                let num_type = self.get_type("Number")?;
-                let bool_type = self.get_type("Boolean")?;
-                
-                // Métodos del vector (implementa protocolo Iterable<T>)
-                vec_type.borrow_mut().define_method("size".to_string(), vec![], num_type.clone());
-                vec_type.borrow_mut().define_method("next".to_string(), vec![], bool_type);
-                vec_type.borrow_mut().define_method("get_current".to_string(), vec![], inner_type.clone());
-                
-                // Almacenar el tipo de elemento como metadata para indexing
-                vec_type.borrow_mut().define_attribute("__element_type".to_string(), inner_type);
-                
-                Ok(vec_type)
+                let inner_type = self.resolve_type_in(inner, scope)?;
+                Ok(self.vector_type(inner_type))
             },
             TypeAnnotation::Function { params, return_type } => {
-                for p in params { self.resolve_type(p)?; }
-                self.resolve_type(return_type)?;
+                for p in params { self.resolve_type_in(p, scope)?; }
+                self.resolve_type_in(return_type, scope)?;
                 self.get_type("Object") // Tratar tipo función como Object
             }
         }
     }
+
+    /// Tipo `Vector<T>` con los métodos del protocolo Iterable, los que lo
+    /// hacen crecer (`push`, `pop`, `insert`, `remove_at`) y el tipo del
+    /// elemento como metadata para la indexación.
+    pub fn vector_type(&self, elem: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
+        let name = format!("Vector<{}>", elem.borrow().name);
+        let vec_type = Rc::new(RefCell::new(Type::new(&name, TypeKind::Basic, None)));
+
+        let num_type = self.types["Number"].clone();
+        let bool_type = self.types["Boolean"].clone();
+        let index = ("index".to_string(), num_type.clone());
+        let value = ("value".to_string(), elem.clone());
+        let mut v = vec_type.borrow_mut();
+        v.define_method("size".to_string(), vec![], num_type);
+        v.define_method("next".to_string(), vec![], bool_type);
+        v.define_method("current".to_string(), vec![], elem.clone());
+        v.define_method("get_current".to_string(), vec![], elem.clone());
+        v.define_method("push".to_string(), vec![value.clone()], elem.clone());
+        v.define_method("pop".to_string(), vec![], elem.clone());
+        v.define_method("insert".to_string(), vec![index.clone(), value], elem.clone());
+        v.define_method("remove_at".to_string(), vec![index], elem.clone());
+        v.define_attribute("__element_type".to_string(), elem);
+        drop(v);
+
+        vec_type
+    }
+
+    /// Instancia la declaración genérica `base` con `args` (`Stack<Number>`).
+    /// Las instancias se memorizan por nombre; si todavía faltan miembros de
+    /// la declaración, `complete_instances` las vuelve a llenar.
+    pub fn instantiate(&self, base: &Rc<RefCell<Type>>, args: Vec<Rc<RefCell<Type>>>) -> Result<Rc<RefCell<Type>>, SemanticError> {
+        let (base_name, params) = {
+            let b = base.borrow();
+            (b.name.clone(), b.type_params.clone())
+        };
+        if params.len() != args.len() {
+            return Err(SemanticError::TypeArgumentCountMismatch(base_name, params.len(), args.len(), Position::default()));
+        }
+        if self.check_bounds.get() {
+            self.check_type_args(&params, &args)?;
+        }
+        // `Stack<T>` dentro de la propia declaración es la declaración misma
+        if params.iter().zip(&args).all(|(p, a)| Rc::ptr_eq(p, a)) {
+            return Ok(base.clone());
+        }
+
+        let names: Vec<String> = args.iter().map(|a| a.borrow().name.clone()).collect();
+        let name = format!("{}<{}>", base_name, names.join(", "));
+        if let Some(inst) = self.instances.borrow().get(&name) {
+            return Ok(inst.clone());
+        }
+
+        let kind = base.borrow().kind.clone();
+        let inst = Rc::new(RefCell::new(Type::new(&name, kind, None)));
+        inst.borrow_mut().instance_of = Some((base_name, args));
+        // Registrar antes de llenar: los miembros pueden mencionar la propia instancia
+        self.instances.borrow_mut().insert(name, inst.clone());
+        self.fill_instance(&inst, base);
+        Ok(inst)
+    }
+
+    /// Comprueba que cada argumento de tipo conforme a la cota de su parámetro.
+    pub fn check_type_args(&self, params: &[Rc<RefCell<Type>>], args: &[Rc<RefCell<Type>>]) -> Result<(), SemanticError> {
+        let map = Self::bindings(params, args);
+        for (param, arg) in params.iter().zip(args) {
+            let bound = match param.borrow().parent.clone() {
+                Some(b) if b.borrow().name != "Object" => b,
+                _ => continue,
+            };
+            let bound = self.substitute(&bound, &map);
+            if !arg.borrow().conforms_to(&bound) {
+                return Err(SemanticError::BoundNotSatisfied {
+                    ty: arg.borrow().name.clone(),
+                    param: param.borrow().name.clone(),
+                    bound: bound.borrow().name.clone(),
+                    pos: Position::default(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Asociación nombre de parámetro -> argumento.
+    pub fn bindings(params: &[Rc<RefCell<Type>>], args: &[Rc<RefCell<Type>>]) -> HashMap<String, Rc<RefCell<Type>>> {
+        params.iter().zip(args)
+            .map(|(p, a)| (p.borrow().name.clone(), a.clone()))
+            .collect()
+    }
+
+    /// Copia en `inst` los miembros de `base` con los parámetros sustituidos.
+    fn fill_instance(&self, inst: &Rc<RefCell<Type>>, base: &Rc<RefCell<Type>>) {
+        let args = match &inst.borrow().instance_of {
+            Some((_, args)) => args.clone(),
+            None => return,
+        };
+        let b = base.borrow().clone();
+        let map = Self::bindings(&b.type_params, &args);
+
+        let parent = b.parent.as_ref().map(|p| self.substitute(p, &map));
+        let params = b.params.iter().map(|(n, t)| (n.clone(), self.substitute(t, &map))).collect();
+        let attributes = b.attributes.iter().map(|(n, t)| (n.clone(), self.substitute(t, &map))).collect();
+        let methods = b.methods.iter().map(|(n, m)| {
+            (n.clone(), MethodInfo {
+                name: m.name.clone(),
+                params: m.params.iter().map(|(pn, t)| (pn.clone(), self.substitute(t, &map))).collect(),
+                return_type: self.substitute(&m.return_type, &map),
+            })
+        }).collect();
+
+        let mut i = inst.borrow_mut();
+        i.parent = parent;
+        i.params = params;
+        i.attributes = attributes;
+        i.methods = methods;
+    }
+
+    /// Reemplaza los parámetros de tipo de `ty` según `map`.
+    pub fn substitute(&self, ty: &Rc<RefCell<Type>>, map: &HashMap<String, Rc<RefCell<Type>>>) -> Rc<RefCell<Type>> {
+        if map.is_empty() {
+            return ty.clone();
+        }
+        let t = ty.borrow();
+        if t.kind == TypeKind::Param {
+            return map.get(&t.name).cloned().unwrap_or_else(|| ty.clone());
+        }
+        if let Some((base, args)) = &t.instance_of {
+            let args = args.iter().map(|a| self.substitute(a, map)).collect();
+            return self.get_type(base)
+                .and_then(|b| self.instantiate(&b, args))
+                .unwrap_or_else(|_| ty.clone());
+        }
+        if !t.type_params.is_empty() {
+            // La declaración genérica vista desde dentro equivale a `Base<T, ...>`
+            let args: Vec<_> = t.type_params.iter().map(|p| self.substitute(p, map)).collect();
+            if t.type_params.iter().zip(&args).any(|(p, a)| !Rc::ptr_eq(p, a)) {
+                return self.instantiate(ty, args).unwrap_or_else(|_| ty.clone());
+            }
+        }
+        if t.name.starts_with("Vector<") && let Some(elem) = t.get_attribute("__element_type") {
+            let new_elem = self.substitute(&elem, map);
+            if !Rc::ptr_eq(&elem, &new_elem) {
+                return self.vector_type(new_elem);
+            }
+        }
+        ty.clone()
+    }
+
+    /// Vuelve a llenar las instancias creadas antes de registrar todos los
+    /// miembros de sus declaraciones, hasta que no aparezcan instancias nuevas.
+    pub fn complete_instances(&self) {
+        let mut done = 0;
+        loop {
+            let pending: Vec<_> = self.instances.borrow().values().cloned().collect();
+            if pending.len() == done {
+                break;
+            }
+            done = pending.len();
+            for inst in pending {
+                let base = inst.borrow().instance_of.as_ref().and_then(|(b, _)| self.types.get(b).cloned());
+                if let Some(base) = base {
+                    self.fill_instance(&inst, &base);
+                }
+            }
+        }
+    }
 }
 
 /// Verifica si type_a conforma a type_b
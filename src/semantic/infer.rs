@@ -0,0 +1,1046 @@
+//! Inferencia de tipos por restricciones.
+//!
+//! Cada parámetro, retorno, atributo o parámetro de lambda sin anotar es una
+//! *incógnita*. Recorrer los cuerpos de todo el programa genera restricciones
+//! sobre ellas:
+//!
+//! - **cotas superiores**: cómo se usa el valor (`x + 1` pide `Number`,
+//!   pasarlo a un parámetro `Dog` pide `Dog`);
+//! - **cotas inferiores**: qué valores le llegan (los argumentos de cada
+//!   llamada, el cuerpo de una función, el inicializador de un atributo);
+//! - **métodos requeridos**: `x.area()` pide un tipo que tenga `area`.
+//!
+//! Las incógnitas se resuelven por rondas hasta que ninguna cambia, y el
+//! resultado se escribe en el `Context`: el chequeo de cuerpos y el codegen
+//! ven los tipos inferidos como si el programa los hubiera anotado.
+
+use super::context::Context;
+use super::visitor::narrowing_tests;
+use super::types::{Type, TypeKind, lowest_common_ancestor};
+use crate::ast::nodes::*;
+use crate::errors::SemanticError;
+use crate::utils::{Position, Span, Spanned};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+type TypeRef = Rc<RefCell<Type>>;
+
+/// Cantidad máxima de rondas; en la práctica se estabiliza en pocas.
+const MAX_ROUNDS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SlotKind {
+    /// Entrada (parámetro): la decide el uso y, si no hay, lo que le llega.
+    Param,
+    /// Salida (retorno o rama de un `if`): la decide lo que le llega.
+    Result,
+    /// Como una salida, pero lo que se le asigna debe conformar a su tipo.
+    Attribute,
+}
+
+struct Slot {
+    name: String,
+    pos: Position,
+    kind: SlotKind,
+    uppers: Vec<TypeRef>,
+    lowers: Vec<TypeRef>,
+    methods: Vec<String>,
+    resolved: Option<TypeRef>,
+}
+
+/// Tipo de una expresión durante la inferencia.
+#[derive(Clone)]
+enum Ty {
+    Known(TypeRef),
+    Slot(usize),
+    /// Sin información estática: se trata como `Object`.
+    Unknown,
+}
+
+#[derive(Clone)]
+struct Sig {
+    params: Vec<Ty>,
+    ret: Ty,
+}
+
+struct Inference<'a> {
+    context: &'a Context,
+    slots: Vec<Slot>,
+    /// `(a, b)`: el valor de la incógnita `a` fluye hacia `b`.
+    edges: Vec<(usize, usize)>,
+    /// Retornos de métodos que sobrescriben hacia el del padre; valen en todas las rondas.
+    override_edges: Vec<(usize, usize)>,
+    functions: HashMap<String, Sig>,
+    /// Métodos por (tipo que los declara, nombre).
+    methods: HashMap<(String, String), Sig>,
+    attributes: HashMap<(String, String), Ty>,
+    ctors: HashMap<String, Vec<Ty>>,
+    lambdas: HashMap<Span, Vec<Ty>>,
+    joins: HashMap<Span, usize>,
+    scopes: Vec<HashMap<String, Ty>>,
+    /// Índices de `scopes` que estrechan variables tras un `is` (ver `TypeChecker`).
+    narrowing_frames: Vec<usize>,
+    current_type: Option<TypeRef>,
+    type_params: Vec<TypeRef>,
+    /// Retorno de la función, método o lambda que se recorre: a él llegan los `return`.
+    returns: Ty,
+}
+
+/// Infiere los tipos que el programa no anota y los escribe en `context`.
+/// Devuelve los casos ambiguos: usos incompatibles entre sí o un método que
+/// declaran varios tipos sin relación.
+pub fn infer_program(program: &Program, context: &mut Context) -> Vec<SemanticError> {
+    let mut inference = Inference::new(program, context);
+    for _ in 0..MAX_ROUNDS {
+        inference.round(program);
+        if !inference.resolve_all() {
+            break;
+        }
+    }
+    let errors = inference.final_errors();
+    let resolved = inference.finish();
+    resolved.apply(program, context);
+    errors
+}
+
+/// Tipos finales de las incógnitas, listos para escribir en el contexto.
+struct Resolved {
+    functions: HashMap<String, (Vec<TypeRef>, TypeRef)>,
+    methods: HashMap<(String, String), (Vec<TypeRef>, TypeRef)>,
+    attributes: HashMap<(String, String), TypeRef>,
+    ctors: HashMap<String, Vec<TypeRef>>,
+    lambdas: HashMap<Span, Vec<TypeRef>>,
+}
+
+impl<'a> Inference<'a> {
+    fn new(program: &Program, context: &'a Context) -> Self {
+        let mut inference = Inference {
+            context,
+            slots: Vec::new(),
+            edges: Vec::new(),
+            override_edges: Vec::new(),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            attributes: HashMap::new(),
+            ctors: HashMap::new(),
+            lambdas: HashMap::new(),
+            joins: HashMap::new(),
+            scopes: Vec::new(),
+            narrowing_frames: Vec::new(),
+            current_type: None,
+            type_params: Vec::new(),
+            returns: Ty::Unknown,
+        };
+
+        for decl in &program.declarations {
+            if let Declaration::Function(f) = decl {
+                let (param_types, ret_type) = context.functions[&f.name].clone();
+                let params = f.params.iter().zip(param_types)
+                    .map(|(p, t)| inference.declared(&p.type_annotation, t, &p.name, f.pos, SlotKind::Param))
+                    .collect();
+                let ret = inference.declared(&f.return_type, ret_type, &f.name, f.pos, SlotKind::Result);
+                inference.functions.insert(f.name.clone(), Sig { params, ret });
+            }
+        }
+
+        let decls: HashMap<&str, &TypeDecl> = program.declarations.iter()
+            .filter_map(|d| match d { Declaration::Type(td) => Some((td.name.as_str(), td)), _ => None })
+            .collect();
+        let mut done = HashSet::new();
+        for decl in &program.declarations {
+            if let Declaration::Type(td) = decl {
+                inference.declare_type(td, &decls, &mut done);
+            }
+        }
+        inference
+    }
+
+    /// Tipo de un miembro: el anotado o una incógnita nueva.
+    fn declared(&mut self, ann: &Option<TypeAnnotation>, ty: TypeRef, name: &str, pos: Position, kind: SlotKind) -> Ty {
+        if ann.is_some() {
+            Ty::Known(ty)
+        } else {
+            Ty::Slot(self.new_slot(name, pos, kind))
+        }
+    }
+
+    fn new_slot(&mut self, name: &str, pos: Position, kind: SlotKind) -> usize {
+        self.slots.push(Slot {
+            name: name.to_string(),
+            pos,
+            kind,
+            uppers: Vec::new(),
+            lowers: Vec::new(),
+            methods: Vec::new(),
+            resolved: None,
+        });
+        self.slots.len() - 1
+    }
+
+    /// Registra las incógnitas de un tipo después de las de su padre: un
+    /// método que sobrescribe sin anotar comparte los parámetros del padre.
+    fn declare_type(&mut self, td: &TypeDecl, decls: &HashMap<&str, &TypeDecl>, done: &mut HashSet<String>) {
+        if !done.insert(td.name.clone()) {
+            return;
+        }
+        let type_rc = self.context.types[&td.name].clone();
+        let parent = type_rc.borrow().parent.clone();
+        let parent_decl = parent.as_ref()
+            .filter(|p| p.borrow().instance_of.is_none())
+            .and_then(|p| decls.get(p.borrow().name.as_str()).copied());
+        if let Some(parent_decl) = parent_decl {
+            self.declare_type(parent_decl, decls, done);
+        }
+
+        let ctor_types = type_rc.borrow().params.clone();
+        let ctor = td.params.iter().zip(ctor_types)
+            .map(|(p, (_, t))| self.declared(&p.type_annotation, t, &p.name, td.pos, SlotKind::Param))
+            .collect();
+        self.ctors.insert(td.name.clone(), ctor);
+
+        for attr in &td.attributes {
+            let Some(t) = type_rc.borrow().attributes.get(&attr.name).cloned() else { continue };
+            let ty = self.declared(&attr.type_annotation, t, &attr.name, attr.pos, SlotKind::Attribute);
+            self.attributes.insert((td.name.clone(), attr.name.clone()), ty);
+        }
+
+        for method in &td.methods {
+            let Some(info) = type_rc.borrow().methods.get(&method.name).cloned() else { continue };
+            let overridden = parent_decl
+                .and_then(|p| self.context.types.get(&p.name))
+                .and_then(|p| self.method_sig(p, &method.name));
+            let params = method.params.iter().zip(info.params).enumerate()
+                .map(|(i, (p, (_, t)))| match overridden.as_ref().and_then(|o| o.params.get(i)) {
+                    Some(inherited) if p.type_annotation.is_none() => inherited.clone(),
+                    _ => self.declared(&p.type_annotation, t, &p.name, method.pos, SlotKind::Param),
+                })
+                .collect();
+            let ret = self.declared(&method.return_type, info.return_type, &method.name, method.pos, SlotKind::Result);
+            // El retorno de quien sobrescribe también es un retorno posible del método del padre
+            if let (Ty::Slot(child), Some(Sig { ret: Ty::Slot(base), .. })) = (&ret, &overridden) {
+                self.override_edges.push((*child, *base));
+            }
+            self.methods.insert((td.name.clone(), method.name.clone()), Sig { params, ret });
+        }
+    }
+
+    /// Firma del método `name` visto desde `ty`: la de sus incógnitas si lo
+    /// declara un tipo del programa, o la registrada en el contexto.
+    fn method_sig(&self, ty: &TypeRef, name: &str) -> Option<Sig> {
+        let mut curr = Some(ty.clone());
+        while let Some(c) = curr {
+            let c_ref = c.borrow();
+            if c_ref.instance_of.is_none()
+                && let Some(sig) = self.methods.get(&(c_ref.name.clone(), name.to_string()))
+            {
+                return Some(sig.clone());
+            }
+            if let Some(info) = c_ref.methods.get(name) {
+                return Some(Sig {
+                    params: info.params.iter().map(|(_, t)| Ty::Known(t.clone())).collect(),
+                    ret: Ty::Known(info.return_type.clone()),
+                });
+            }
+            curr = c_ref.parent.clone();
+        }
+        None
+    }
+
+    fn attribute_ty(&self, ty: &TypeRef, name: &str) -> Ty {
+        let mut curr = Some(ty.clone());
+        while let Some(c) = curr {
+            let c_ref = c.borrow();
+            if c_ref.instance_of.is_none()
+                && let Some(t) = self.attributes.get(&(c_ref.name.clone(), name.to_string()))
+            {
+                return t.clone();
+            }
+            if let Some(t) = c_ref.attributes.get(name) {
+                return Ty::Known(t.clone());
+            }
+            curr = c_ref.parent.clone();
+        }
+        Ty::Unknown
+    }
+
+    /// Parámetros del constructor de `name` (no los de una declaración genérica).
+    fn ctor_params(&self, name: &str) -> Option<Vec<Ty>> {
+        let ty = self.context.types.get(name)?;
+        if !ty.borrow().type_params.is_empty() {
+            return None;
+        }
+        self.ctors.get(name).cloned()
+    }
+
+    fn known(&self, name: &str) -> Ty {
+        Ty::Known(self.context.types[name].clone())
+    }
+
+    /// Tipo concreto de `ty` si ya se conoce (una incógnita, el de la ronda anterior).
+    fn current(&self, ty: &Ty) -> Option<TypeRef> {
+        match ty {
+            Ty::Known(t) => Some(t.clone()),
+            Ty::Slot(i) => self.slots[*i].resolved.clone(),
+            Ty::Unknown => None,
+        }
+    }
+
+    fn define(&mut self, name: &str, ty: Ty) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Ty {
+        self.scopes.iter().rev()
+            .find_map(|s| s.get(name).cloned())
+            .unwrap_or(Ty::Unknown)
+    }
+
+    /// Como `lookup`, pero ignora los estrechamientos: a una variable se le
+    /// asigna con el tipo que se declaró.
+    fn lookup_declared(&self, name: &str) -> Ty {
+        self.scopes.iter().enumerate().rev()
+            .filter(|(i, _)| !self.narrowing_frames.contains(i))
+            .find_map(|(_, s)| s.get(name).cloned())
+            .unwrap_or(Ty::Unknown)
+    }
+
+    /// Recorre `expr` con las variables que `cond` comprueba con `is` fijadas
+    /// al tipo comprobado, para que sus usos no restrinjan la incógnita.
+    fn expr_narrowed(&mut self, cond: &Expr, expr: &Spanned<Expr>) -> Ty {
+        let frame: HashMap<String, Ty> = narrowing_tests(cond).into_iter()
+            .filter_map(|(name, ty)| {
+                let t = self.context.get_type(ty).ok()?;
+                t.borrow().type_params.is_empty().then(|| (name.to_string(), Ty::Known(t.clone())))
+            })
+            .collect();
+        if frame.is_empty() {
+            return self.expr(expr);
+        }
+        self.scopes.push(frame);
+        self.narrowing_frames.push(self.scopes.len() - 1);
+        let t = self.expr(expr);
+        self.narrowing_frames.pop();
+        self.scopes.pop();
+        t
+    }
+
+    //  Restricciones
+
+    /// El valor de `from` llega a donde se espera `to`.
+    fn flow(&mut self, from: &Ty, to: &Ty) {
+        match (from, to) {
+            (Ty::Slot(a), Ty::Slot(b)) if a != b => self.edges.push((*a, *b)),
+            (Ty::Slot(a), Ty::Known(t)) => {
+                let t = erase(t);
+                if !is_object(&t) {
+                    self.slots[*a].uppers.push(t);
+                }
+            }
+            (Ty::Known(t), Ty::Slot(b)) => {
+                let t = erase(t);
+                self.slots[*b].lowers.push(t);
+            }
+            (Ty::Unknown, Ty::Slot(b)) => {
+                let object = self.context.types["Object"].clone();
+                self.slots[*b].lowers.push(object);
+            }
+            _ => {}
+        }
+    }
+
+    fn expect(&mut self, ty: &Ty, name: &str) {
+        let expected = self.known(name);
+        self.flow(ty, &expected);
+    }
+
+    /// Recorre todos los cuerpos del programa y regenera las restricciones.
+    fn round(&mut self, program: &Program) {
+        for slot in &mut self.slots {
+            slot.uppers.clear();
+            slot.lowers.clear();
+            slot.methods.clear();
+        }
+        self.edges = self.override_edges.clone();
+
+        for decl in &program.declarations {
+            match decl {
+                Declaration::Function(f) => {
+                    let sig = self.functions[&f.name].clone();
+                    self.type_params = self.context.function_type_params.get(&f.name).cloned().unwrap_or_default();
+                    self.current_type = None;
+                    self.scopes = vec![f.params.iter().map(|p| p.name.clone()).zip(sig.params).collect()];
+                    self.returns = sig.ret.clone();
+                    let body = self.expr(&f.body);
+                    self.flow(&body, &sig.ret);
+                }
+                Declaration::Macro(m) => {
+                    let (param_types, _) = self.context.functions[&m.name].clone();
+                    self.type_params = Vec::new();
+                    self.current_type = None;
+                    self.scopes = vec![m.params.iter().map(macro_param_name).zip(param_types.into_iter().map(Ty::Known)).collect()];
+                    self.returns = Ty::Unknown;
+                    self.expr(&m.body);
+                }
+                Declaration::Type(td) => self.round_type(td),
+                Declaration::Protocol(_) | Declaration::Variant(_) => {}
+            }
+        }
+
+        self.type_params = Vec::new();
+        self.current_type = None;
+        self.returns = Ty::Unknown;
+        self.scopes = vec![HashMap::new()];
+        self.expr(&program.expr);
+    }
+
+    fn round_type(&mut self, td: &TypeDecl) {
+        let type_rc = self.context.types[&td.name].clone();
+        self.type_params = type_rc.borrow().type_params.clone();
+        self.current_type = Some(type_rc.clone());
+        self.returns = Ty::Unknown;
+        let ctor = self.ctors[&td.name].clone();
+        let ctor_scope: HashMap<String, Ty> = td.params.iter().map(|p| p.name.clone()).zip(ctor).collect();
+
+        if let Some(parent) = &td.parent {
+            self.scopes = vec![ctor_scope.clone()];
+            let expected = self.ctor_params(&parent.name);
+            for (i, arg) in parent.args.iter().enumerate() {
+                let t = self.expr(arg);
+                if let Some(p) = expected.as_ref().and_then(|e| e.get(i)) {
+                    self.flow(&t, p);
+                }
+            }
+        }
+
+        for attr in &td.attributes {
+            self.scopes = vec![ctor_scope.clone()];
+            let t = self.expr(&attr.init);
+            if let Some(target) = self.attributes.get(&(td.name.clone(), attr.name.clone())).cloned() {
+                self.flow(&t, &target);
+            }
+        }
+
+        for method in &td.methods {
+            let Some(sig) = self.methods.get(&(td.name.clone(), method.name.clone())).cloned() else { continue };
+            self.scopes = vec![method.params.iter().map(|p| p.name.clone()).zip(sig.params).collect()];
+            self.returns = sig.ret.clone();
+            let body = self.expr(&method.body);
+            self.flow(&body, &sig.ret);
+        }
+    }
+
+    fn expr(&mut self, expr: &Spanned<Expr>) -> Ty {
+        match &expr.node {
+            Expr::Number(_) | Expr::PI | Expr::E | Expr::Rand => self.known("Number"),
+            Expr::String(_) => self.known("String"),
+            Expr::Boolean(_) => self.known("Boolean"),
+            Expr::Identifier(name) if name == "self" => match &self.current_type {
+                Some(t) => Ty::Known(t.clone()),
+                None => Ty::Unknown,
+            },
+            Expr::Identifier(name) => self.lookup(name),
+            Expr::Binary(left, op, right) => {
+                let l = self.expr(left);
+                let r = if *op == Op::And { self.expr_narrowed(&left.node, right) } else { self.expr(right) };
+                // `v + w` concatena vectores: no se fuerza `Number` si un lado ya es vector
+                if *op == Op::Add {
+                    let vectors: Vec<TypeRef> = [&l, &r].into_iter().filter_map(|t| self.vector_of(t)).collect();
+                    if let Some(v) = vectors.iter().find(|t| t.borrow().name != "Vector<Object>").or(vectors.first()) {
+                        return Ty::Known(v.clone());
+                    }
+                }
+                let (operand, result) = match op {
+                    Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod | Op::Pow => (Some("Number"), "Number"),
+                    Op::Lt | Op::Gt | Op::Le | Op::Ge => (Some("Number"), "Boolean"),
+                    Op::And | Op::Or => (Some("Boolean"), "Boolean"),
+                    Op::Eq | Op::Neq => (None, "Boolean"),
+                    Op::Concat | Op::ConcatSpace => (None, "String"),
+                };
+                if let Some(operand) = operand {
+                    self.expect(&l, operand);
+                    self.expect(&r, operand);
+                }
+                self.known(result)
+            }
+            Expr::Unary(op, operand) => {
+                let t = self.expr(operand);
+                let name = match op { UnOp::Neg => "Number", UnOp::Not => "Boolean" };
+                self.expect(&t, name);
+                self.known(name)
+            }
+            Expr::If { cond, then_expr, else_expr } => {
+                let c = self.expr(cond);
+                self.expect(&c, "Boolean");
+                let a = self.expr_narrowed(&cond.node, then_expr);
+                let b = self.expr(else_expr);
+                self.join(&a, &b, expr.span())
+            }
+            Expr::While { cond, body } => {
+                let c = self.expr(cond);
+                self.expect(&c, "Boolean");
+                self.expr(body);
+                self.known("Object")
+            }
+            Expr::For { var, iterable, body } => {
+                let it = self.expr(iterable);
+                let elem = self.element_of(&it);
+                self.scopes.push(HashMap::from([(var.clone(), elem)]));
+                self.expr(body);
+                self.scopes.pop();
+                self.known("Object")
+            }
+            Expr::Block(exprs) => {
+                let mut last = self.known("Object");
+                for e in exprs {
+                    last = self.expr(e);
+                }
+                last
+            }
+            Expr::Let { bindings, body } => {
+                self.scopes.push(HashMap::new());
+                for (name, ann, init) in bindings {
+                    let mut t = self.expr(init);
+                    if let Some(ann) = ann
+                        && let Ok(expected) = self.context.resolve_type_in(ann, &self.type_params)
+                    {
+                        self.flow(&t, &Ty::Known(expected.clone()));
+                        t = Ty::Known(expected);
+                    }
+                    self.define(name, t);
+                }
+                let result = self.expr(body);
+                self.scopes.pop();
+                result
+            }
+            Expr::Assignment { target, value } => {
+                let v = self.expr(value);
+                let t = self.lookup_declared(target);
+                self.flow(&v, &t);
+                v
+            }
+            Expr::AttributeAssignment { obj, attribute, value } => {
+                let o = self.expr(obj);
+                let v = self.expr(value);
+                if let Some(t) = self.current(&o) {
+                    let target = self.attribute_ty(&t, attribute);
+                    self.flow(&v, &target);
+                }
+                v
+            }
+            Expr::AttributeAccess { obj, attribute } => {
+                let o = self.expr(obj);
+                match self.current(&o) {
+                    Some(t) => self.attribute_ty(&t, attribute),
+                    None => Ty::Unknown,
+                }
+            }
+            Expr::Call { func, args } => self.call(func, args),
+            Expr::MethodCall { obj, method, args } => {
+                let o = self.expr(obj);
+                if let Ty::Slot(i) = o {
+                    self.slots[i].methods.push(method.clone());
+                }
+                let sig = self.current(&o).and_then(|t| self.method_sig(&t, method));
+                self.args(args, sig.as_ref().map(|s| s.params.as_slice()));
+                sig.map_or(Ty::Unknown, |s| s.ret)
+            }
+            Expr::Instantiation { ty, type_args, args } => {
+                let params = self.ctor_params(ty);
+                self.args(args, params.as_deref());
+                if !type_args.is_empty() {
+                    let ann = TypeAnnotation::Generic { name: ty.clone(), args: type_args.clone() };
+                    return self.context.resolve_type_in(&ann, &self.type_params).map_or(Ty::Unknown, Ty::Known);
+                }
+                match self.context.types.get(ty) {
+                    Some(t) if t.borrow().type_params.is_empty() => Ty::Known(t.clone()),
+                    _ => Ty::Unknown,
+                }
+            }
+            Expr::BaseCall { args } => {
+                self.args(args, None);
+                Ty::Unknown
+            }
+            Expr::Lambda { params, return_type, body } => {
+                let types = self.lambda_params(params, expr);
+                self.scopes.push(params.iter().map(|p| p.name.clone()).zip(types).collect());
+                let expected = return_type.as_ref()
+                    .and_then(|ann| self.context.resolve_type_in(ann, &self.type_params).ok())
+                    .map_or(Ty::Unknown, Ty::Known);
+                let outer = std::mem::replace(&mut self.returns, expected.clone());
+                let b = self.expr(body);
+                self.flow(&b, &expected);
+                self.returns = outer;
+                self.scopes.pop();
+                self.known("Object")
+            }
+            Expr::Is(e, ty) => {
+                let t = self.expr(e);
+                if let Ok(tested) = self.context.get_type(ty) {
+                    self.tested(&tested, &t);
+                }
+                self.known("Boolean")
+            }
+            Expr::As(e, ty) => {
+                self.expr(e);
+                self.context.types.get(ty).cloned().map_or(Ty::Unknown, Ty::Known)
+            }
+            Expr::VectorLiteral(elems) => {
+                let types: Vec<Ty> = elems.iter().map(|e| self.expr(e)).collect();
+                match types.first() {
+                    Some(Ty::Known(first)) => Ty::Known(self.context.vector_type(first.clone())),
+                    _ => Ty::Unknown,
+                }
+            }
+            Expr::MapLiteral(entries) => {
+                let types: Vec<(Ty, Ty)> = entries.iter().map(|(k, v)| (self.expr(k), self.expr(v))).collect();
+                match types.first() {
+                    Some((Ty::Known(k), Ty::Known(v))) => Ty::Known(self.context.map_type(k.clone(), v.clone())),
+                    _ => Ty::Unknown,
+                }
+            }
+            Expr::VectorGenerator { expr: body, var, iterable } => {
+                let it = self.expr(iterable);
+                let elem = self.element_of(&it);
+                self.scopes.push(HashMap::from([(var.clone(), elem)]));
+                let t = self.expr(body);
+                self.scopes.pop();
+                match t {
+                    Ty::Known(t) => Ty::Known(self.context.vector_type(t)),
+                    _ => Ty::Unknown,
+                }
+            }
+            Expr::Indexing { obj, index } => {
+                let o = self.expr(obj);
+                let i = self.expr(index);
+                self.expect(&i, "Number");
+                match self.vector_of(&o) {
+                    Some(t) => t.borrow().get_attribute("__element_type").map_or(Ty::Unknown, Ty::Known),
+                    None => Ty::Unknown,
+                }
+            }
+            Expr::IndexAssignment { obj, index, value } => {
+                let o = self.expr(obj);
+                let i = self.expr(index);
+                self.expect(&i, "Number");
+                let v = self.expr(value);
+                if let Some(elem) = self.vector_of(&o).and_then(|t| t.borrow().get_attribute("__element_type")) {
+                    self.flow(&v, &Ty::Known(elem));
+                }
+                v
+            }
+            Expr::Slice { obj, start, end } => {
+                let o = self.expr(obj);
+                for bound in start.iter().chain(end) {
+                    let t = self.expr(bound);
+                    self.expect(&t, "Number");
+                }
+                o
+            }
+            Expr::Sqrt(a) | Expr::Sin(a) | Expr::Cos(a) | Expr::Exp(a) => {
+                let t = self.expr(a);
+                self.expect(&t, "Number");
+                self.known("Number")
+            }
+            Expr::Log(base, val) => {
+                let b = self.expr(base);
+                let v = self.expr(val);
+                self.expect(&b, "Number");
+                self.expect(&v, "Number");
+                self.known("Number")
+            }
+            Expr::Match { expr: scrutinee, cases, default } => {
+                let s = self.expr(scrutinee);
+                let mut arms = Vec::new();
+                for case in cases {
+                    self.scopes.push(HashMap::new());
+                    self.bind_pattern(&case.pattern, &s);
+                    if let Some(guard) = &case.guard {
+                        let g = self.expr(guard);
+                        self.expect(&g, "Boolean");
+                    }
+                    arms.push(self.expr(&case.expr));
+                    self.scopes.pop();
+                }
+                if let Some(d) = default {
+                    arms.push(self.expr(d));
+                }
+                let mut arms = arms.into_iter();
+                match arms.next() {
+                    Some(first) => arms.fold(first, |acc, arm| self.join(&acc, &arm, expr.span())),
+                    None => Ty::Unknown,
+                }
+            }
+            Expr::Try { body, catches } => {
+                let mut result = self.expr(body);
+                for c in catches {
+                    let caught = self.context.types.get(c.ty.as_deref().unwrap_or("Error")).cloned();
+                    self.scopes.push(HashMap::from([(c.var.clone(), caught.map_or(Ty::Unknown, Ty::Known))]));
+                    let arm = self.expr(&c.body);
+                    self.scopes.pop();
+                    result = self.join(&result, &arm, expr.span());
+                }
+                result
+            }
+            Expr::Throw(value) => {
+                let t = self.expr(value);
+                if let Some(error) = self.context.types.get("Error").cloned() {
+                    self.flow(&t, &Ty::Known(error));
+                }
+                self.known("Never")
+            }
+            Expr::Break(value) | Expr::Continue(value) => {
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                self.known("Never")
+            }
+            Expr::Return(value) => {
+                let t = self.expr(value);
+                let returns = self.returns.clone();
+                self.flow(&t, &returns);
+                self.known("Never")
+            }
+            Expr::Error => Ty::Unknown,
+        }
+    }
+
+    /// Declara las variables de `pattern` comparado con un valor de tipo `ty`.
+    /// Como `is`, cada tipo que el patrón comprueba es una cota inferior de `ty`.
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &Ty) {
+        match pattern {
+            Pattern::Variable { name, type_annotation: None } => self.define(name, ty.clone()),
+            Pattern::Variable { name, type_annotation: Some(ann) } => {
+                match self.context.resolve_type_in(ann, &self.type_params) {
+                    Ok(tested) => {
+                        self.tested(&tested, ty);
+                        self.define(name, Ty::Known(tested));
+                    }
+                    Err(_) => self.define(name, Ty::Unknown),
+                }
+            }
+            Pattern::Literal(lit) => {
+                let name = match lit {
+                    Expr::Number(_) => "Number",
+                    Expr::String(_) => "String",
+                    Expr::Boolean(_) => "Boolean",
+                    _ => return,
+                };
+                let tested = self.context.get_type(name).unwrap();
+                self.tested(&tested, ty);
+            }
+            Pattern::Call { func, args } => match self.context.get_type(func) {
+                Ok(tested) => {
+                    self.tested(&tested, ty);
+                    let params: Vec<String> = tested.borrow().params.iter().map(|(n, _)| n.clone()).collect();
+                    for (param, arg) in params.iter().zip(args) {
+                        let attr = self.attribute_ty(&tested, param);
+                        self.bind_pattern(arg, &attr);
+                    }
+                }
+                Err(_) => args.iter().for_each(|a| self.bind_pattern(a, &Ty::Unknown)),
+            },
+            Pattern::Binary { left, right, .. } => {
+                self.bind_pattern(left, &Ty::Unknown);
+                self.bind_pattern(right, &Ty::Unknown);
+            }
+            Pattern::Unary { operand, .. } => self.bind_pattern(operand, &Ty::Unknown),
+            Pattern::Wildcard => {}
+        }
+    }
+
+    /// Comprobar si un valor de tipo `ty` es un `tested` supone que puede serlo.
+    fn tested(&mut self, tested: &TypeRef, ty: &Ty) {
+        if matches!(ty, Ty::Slot(_)) && tested.borrow().type_params.is_empty() {
+            self.flow(&Ty::Known(tested.clone()), ty);
+        }
+    }
+
+    /// Recorre los argumentos y, si se conoce la firma, los hace fluir a sus parámetros.
+    fn args(&mut self, args: &[Spanned<Expr>], params: Option<&[Ty]>) {
+        for (i, arg) in args.iter().enumerate() {
+            let t = self.expr(arg);
+            if let Some(p) = params.and_then(|ps| ps.get(i)) {
+                let p = p.clone();
+                self.flow(&t, &p);
+            }
+        }
+    }
+
+    fn call(&mut self, func: &str, args: &[Spanned<Expr>]) -> Ty {
+        if let Some(sig) = self.functions.get(func).cloned() {
+            self.args(args, Some(&sig.params));
+            // El retorno de una función genérica depende de cada llamada
+            if self.context.function_type_params.contains_key(func) {
+                return Ty::Unknown;
+            }
+            return sig.ret;
+        }
+        if let Some((params, ret)) = self.context.get_function(func) {
+            let params: Vec<Ty> = params.into_iter().map(Ty::Known).collect();
+            self.args(args, Some(&params));
+            return Ty::Known(ret);
+        }
+        // Una variable que guarda una lambda: se llama sin firma conocida
+        self.args(args, None);
+        Ty::Unknown
+    }
+
+    /// Tipos de los parámetros de una lambda; las incógnitas se conservan
+    /// entre rondas por la posición de la lambda.
+    fn lambda_params(&mut self, params: &[Param], expr: &Spanned<Expr>) -> Vec<Ty> {
+        let span = expr.span();
+        if expr.pos != Position::default()
+            && let Some(types) = self.lambdas.get(&span)
+            && types.len() == params.len()
+        {
+            return types.clone();
+        }
+        let types: Vec<Ty> = params.iter().map(|p| match &p.type_annotation {
+            Some(ann) => self.context.resolve_type_in(ann, &self.type_params).map_or(Ty::Unknown, Ty::Known),
+            None => Ty::Slot(self.new_slot(&p.name, expr.pos, SlotKind::Param)),
+        }).collect();
+        if expr.pos != Position::default() {
+            self.lambdas.insert(span, types.clone());
+        }
+        types
+    }
+
+    /// Tipo de un `if`: el ancestro común de las ramas si ambas se conocen, o
+    /// una incógnita a la que fluyen las dos.
+    fn join(&mut self, a: &Ty, b: &Ty, span: Span) -> Ty {
+        match (a, b) {
+            // La rama que lanza no aporta nada al tipo del resultado
+            (Ty::Known(x), other) | (other, Ty::Known(x)) if x.borrow().kind == TypeKind::Never => other.clone(),
+            (Ty::Known(x), Ty::Known(y)) => Ty::Known(join_types(x, y)),
+            (Ty::Unknown, _) | (_, Ty::Unknown) => Ty::Unknown,
+            _ if span.start == Position::default() => Ty::Unknown,
+            _ => {
+                let slot = match self.joins.get(&span) {
+                    Some(slot) => *slot,
+                    None => {
+                        let slot = self.new_slot("if", span.start, SlotKind::Result);
+                        self.joins.insert(span, slot);
+                        slot
+                    }
+                };
+                self.flow(a, &Ty::Slot(slot));
+                self.flow(b, &Ty::Slot(slot));
+                Ty::Slot(slot)
+            }
+        }
+    }
+
+    /// Tipo vector (`Vector<T>`) ya conocido de `ty`, si lo es.
+    fn vector_of(&self, ty: &Ty) -> Option<TypeRef> {
+        self.current(ty).filter(|t| t.borrow().name.starts_with("Vector<"))
+    }
+
+    /// Tipo de la variable de un `for` o de una comprensión sobre `iterable`.
+    fn element_of(&self, iterable: &Ty) -> Ty {
+        self.current(iterable)
+            .and_then(|t| element_type(&t))
+            .map_or(Ty::Unknown, Ty::Known)
+    }
+
+    //  Resolución
+
+    /// Propaga lo resuelto en la ronda anterior por las aristas y vuelve a
+    /// resolver cada incógnita. Devuelve si alguna cambió.
+    fn resolve_all(&mut self) -> bool {
+        self.propagate();
+        let mut changed = false;
+        for i in 0..self.slots.len() {
+            let new = self.resolve(i).unwrap_or(None);
+            let old = &self.slots[i].resolved;
+            let same = match (old, &new) {
+                (Some(a), Some(b)) => a.borrow().name == b.borrow().name,
+                (None, None) => true,
+                _ => false,
+            };
+            if !same {
+                changed = true;
+                self.slots[i].resolved = new;
+            }
+        }
+        changed
+    }
+
+    fn propagate(&mut self) {
+        for (a, b) in self.edges.clone() {
+            if let Some(t) = self.slots[a].resolved.clone() {
+                self.slots[b].lowers.push(t);
+            }
+            // Lo que llega a un parámetro o a un atributo debe conformar a su tipo
+            if self.slots[b].kind != SlotKind::Result
+                && let Some(t) = self.slots[b].resolved.clone()
+                && !is_object(&t)
+            {
+                self.slots[a].uppers.push(t);
+            }
+        }
+    }
+
+    /// Tipo de la incógnita `i` según sus restricciones. `Err` lleva los
+    /// candidatos cuando son incompatibles o no hay forma de elegir uno.
+    fn resolve(&self, i: usize) -> Result<Option<TypeRef>, Vec<String>> {
+        let slot = &self.slots[i];
+        let lower = slot.lowers.iter().cloned().reduce(|a, b| join_types(&a, &b));
+        if slot.kind != SlotKind::Param {
+            return Ok(lower);
+        }
+        // Si llega un valor dinámico el parámetro tiene que aceptar cualquier cosa
+        if slot.lowers.iter().any(is_object) {
+            return Ok(lower);
+        }
+        if !slot.uppers.is_empty() {
+            return most_specific(&slot.uppers).map(Some);
+        }
+        if !slot.methods.is_empty() {
+            return self.by_methods(&slot.methods, lower);
+        }
+        Ok(lower)
+    }
+
+    /// Tipo con todos los `methods`: el de los argumentos si los tiene, o el
+    /// único tipo más general del programa que los declara.
+    fn by_methods(&self, methods: &[String], lower: Option<TypeRef>) -> Result<Option<TypeRef>, Vec<String>> {
+        let has_all = |t: &TypeRef| methods.iter().all(|m| t.borrow().get_method(m).is_some());
+        if let Some(l) = &lower && has_all(l) {
+            return Ok(lower);
+        }
+        let candidates: Vec<TypeRef> = self.context.types.values()
+            .filter(|t| !matches!(t.borrow().kind, TypeKind::Param | TypeKind::Never) && t.borrow().type_params.is_empty())
+            .filter(|t| has_all(t))
+            .cloned()
+            .collect();
+        let mut maximal: Vec<TypeRef> = candidates.iter()
+            .filter(|c| !candidates.iter().any(|d| !Rc::ptr_eq(c, d) && c.borrow().conforms_to(d)))
+            .cloned()
+            .collect();
+        match maximal.len() {
+            0 => Ok(lower),
+            1 => Ok(maximal.pop()),
+            _ => {
+                let mut names: Vec<String> = maximal.iter().map(|t| t.borrow().name.clone()).collect();
+                names.sort();
+                Err(names)
+            }
+        }
+    }
+
+    fn final_errors(&mut self) -> Vec<SemanticError> {
+        self.propagate();
+        (0..self.slots.len())
+            .filter_map(|i| self.resolve(i).err().map(|candidates| {
+                let slot = &self.slots[i];
+                SemanticError::AmbiguousType { name: slot.name.clone(), candidates, pos: slot.pos }
+            }))
+            .collect()
+    }
+
+    fn finish(self) -> Resolved {
+        let object = self.context.types["Object"].clone();
+        let ty = |t: &Ty| match t {
+            Ty::Known(t) => t.clone(),
+            Ty::Slot(i) => self.slots[*i].resolved.clone().unwrap_or_else(|| object.clone()),
+            Ty::Unknown => object.clone(),
+        };
+        let sig = |s: &Sig| (s.params.iter().map(ty).collect(), ty(&s.ret));
+        Resolved {
+            functions: self.functions.iter().map(|(k, s)| (k.clone(), sig(s))).collect(),
+            methods: self.methods.iter().map(|(k, s)| (k.clone(), sig(s))).collect(),
+            attributes: self.attributes.iter().map(|(k, t)| (k.clone(), ty(t))).collect(),
+            ctors: self.ctors.iter().map(|(k, ps)| (k.clone(), ps.iter().map(ty).collect())).collect(),
+            lambdas: self.lambdas.iter().map(|(k, ps)| (*k, ps.iter().map(ty).collect())).collect(),
+        }
+    }
+}
+
+impl Resolved {
+    fn apply(self, program: &Program, context: &mut Context) {
+        for (name, sig) in self.functions {
+            context.functions.insert(name, sig);
+        }
+        for decl in &program.declarations {
+            let Declaration::Type(td) = decl else { continue };
+            let mut ty = context.types[&td.name].borrow_mut();
+            if let Some(params) = self.ctors.get(&td.name) {
+                for ((_, t), new) in ty.params.iter_mut().zip(params) {
+                    *t = new.clone();
+                }
+            }
+            for attr in &td.attributes {
+                if let Some(new) = self.attributes.get(&(td.name.clone(), attr.name.clone())) {
+                    ty.attributes.insert(attr.name.clone(), new.clone());
+                }
+            }
+            for method in &td.methods {
+                if let Some((params, ret)) = self.methods.get(&(td.name.clone(), method.name.clone()))
+                    && let Some(info) = ty.methods.get_mut(&method.name)
+                {
+                    for ((_, t), new) in info.params.iter_mut().zip(params) {
+                        *t = new.clone();
+                    }
+                    info.return_type = ret.clone();
+                }
+            }
+        }
+        context.lambda_types = self.lambdas;
+        context.complete_instances();
+    }
+}
+
+fn macro_param_name(p: &MacroParam) -> String {
+    match p {
+        MacroParam::Normal { name, .. }
+        | MacroParam::Symbolic { name, .. }
+        | MacroParam::Placeholder { name, .. }
+        | MacroParam::Body { name, .. } => name.clone(),
+    }
+}
+
+fn is_object(t: &TypeRef) -> bool {
+    t.borrow().name == "Object"
+}
+
+/// Un parámetro de tipo no sale de su declaración: se reemplaza por su cota.
+fn erase(t: &TypeRef) -> TypeRef {
+    let mut t = t.clone();
+    loop {
+        let parent = match &*t.borrow() {
+            Type { kind: TypeKind::Param, parent: Some(p), .. } => p.clone(),
+            _ => break,
+        };
+        t = parent;
+    }
+    t
+}
+
+fn join_types(a: &TypeRef, b: &TypeRef) -> TypeRef {
+    lowest_common_ancestor(a.clone(), b.clone()).unwrap_or_else(|_| a.clone())
+}
+
+/// El tipo al que conforman todos los demás, o los candidatos si no hay uno.
+fn most_specific(uppers: &[TypeRef]) -> Result<TypeRef, Vec<String>> {
+    uppers.iter()
+        .find(|u| uppers.iter().all(|o| u.borrow().conforms_to(o)))
+        .cloned()
+        .ok_or_else(|| {
+            let mut names: Vec<String> = uppers.iter().map(|t| t.borrow().name.clone()).collect();
+            names.sort();
+            names.dedup();
+            names
+        })
+}
+
+/// Tipo de los elementos que produce un iterable (`current`, o
+/// `get_current` en los tipos que no lo tienen).
+pub fn element_type(iterable: &TypeRef) -> Option<TypeRef> {
+    let t = iterable.borrow();
+    t.get_method("current").or_else(|| t.get_method("get_current"))
+        .filter(|m| m.params.is_empty())
+        .map(|m| m.return_type)
+}
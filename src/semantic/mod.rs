@@ -2,6 +2,7 @@
 pub mod types;
 pub mod context;
 pub mod visitor;
+pub mod infer;
 
 pub use context::Context;
 pub use visitor::TypeChecker;
@@ -11,43 +12,213 @@ mod tests;
 
 use crate::ast::nodes::*;
 use crate::errors::SemanticError;
-use types::{TypeKind, MethodInfo};
-use context::conforms_to;
+use crate::utils::{Position, Span};
+use types::{Type, TypeKind, TypeFactory, MethodInfo};
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::rc::Rc;
 
 
+/// Posición de la declaración del tipo o protocolo `name` (0:0 si no existe).
+fn decl_pos(program: &Program, name: &str) -> Position {
+    program.declarations.iter().find_map(|decl| match decl {
+        Declaration::Type(td) if td.name == name => Some(td.pos),
+        Declaration::Protocol(pd) if pd.name == name => Some(pd.pos),
+        _ => None,
+    }).unwrap_or_default()
+}
+
+/// Crea los tipos de los parámetros `<T, U>` de una declaración genérica,
+/// todavía sin cota (heredan de `Object`).
+fn new_type_params(context: &Context, params: &[TypeParam]) -> Vec<Rc<RefCell<Type>>> {
+    let object = context.get_type("Object").unwrap();
+    params.iter().map(|p| TypeFactory::param(&p.name, object.clone())).collect()
+}
+
+/// Resuelve la cota de cada parámetro de tipo y la pone como su padre.
+fn resolve_bounds(context: &Context, params: &[TypeParam], scope: &[Rc<RefCell<Type>>], pos: Position, errors: &mut Vec<SemanticError>) {
+    for (param, ty) in params.iter().zip(scope) {
+        if let Some(ann) = &param.bound {
+            match context.resolve_type_in(ann, scope) {
+                Ok(bound) => ty.borrow_mut().parent = Some(bound),
+                Err(e) => errors.push(e.at(pos)),
+            }
+        }
+    }
+}
+
+/// Vuelve a resolver las anotaciones de las firmas ahora que se conocen todos
+/// los miembros, para comprobar las cotas de los argumentos de tipo.
+fn check_signature_bounds(program: &Program, context: &Context, errors: &mut Vec<SemanticError>) {
+    let mut check = |ann: &TypeAnnotation, scope: &[Rc<RefCell<Type>>], pos: Position| {
+        if let Err(e) = context.resolve_type_in(ann, scope) {
+            errors.push(e.at(pos));
+        }
+    };
+    for decl in &program.declarations {
+        match decl {
+            Declaration::Function(f) => {
+                let scope = context.function_type_params.get(&f.name).cloned().unwrap_or_default();
+                for ann in f.params.iter().filter_map(|p| p.type_annotation.as_ref()).chain(&f.return_type) {
+                    check(ann, &scope, f.pos);
+                }
+            }
+            Declaration::Type(td) => {
+                let scope = context.get_type(&td.name).unwrap().borrow().type_params.clone();
+                if let Some(parent) = &td.parent {
+                    check(&parent.annotation(), &scope, td.pos);
+                }
+                for ann in td.params.iter().filter_map(|p| p.type_annotation.as_ref()) {
+                    check(ann, &scope, td.pos);
+                }
+                for attr in &td.attributes {
+                    if let Some(ann) = &attr.type_annotation {
+                        check(ann, &scope, attr.pos);
+                    }
+                }
+                for m in &td.methods {
+                    for ann in m.params.iter().filter_map(|p| p.type_annotation.as_ref()).chain(&m.return_type) {
+                        check(ann, &scope, m.pos);
+                    }
+                }
+            }
+            Declaration::Protocol(pd) => {
+                let scope = context.get_type(&pd.name).unwrap().borrow().type_params.clone();
+                for m in &pd.methods {
+                    for ann in m.params.iter().filter_map(|p| p.type_annotation.as_ref()) {
+                        check(ann, &scope, m.pos);
+                    }
+                    check(&m.return_type, &scope, m.pos);
+                }
+            }
+            Declaration::Macro(_) | Declaration::Variant(_) => {}
+        }
+    }
+}
+
+/// Comprueba que cada método que sobrescribe respete la firma de los de sus
+/// ancestros. Corre después de la inferencia, con las firmas ya completas.
+fn check_overrides(program: &Program, context: &Context, errors: &mut Vec<SemanticError>) {
+    for decl in &program.declarations {
+        let Declaration::Type(type_decl) = decl else { continue };
+        let current_type_rc = context.get_type(&type_decl.name).unwrap();
+        for method in &type_decl.methods {
+            let Some(method_info) = current_type_rc.borrow().methods.get(&method.name).cloned() else { continue };
+            let mut parent = current_type_rc.borrow().parent.clone();
+            while let Some(p) = parent {
+                if let Some(parent_method) = p.borrow().methods.get(&method.name) {
+                    if parent_method.params.len() != method_info.params.len() {
+                        errors.push(SemanticError::SignatureMismatch(format!("{}.{} sobreescribe con diferente cantidad de argumentos", type_decl.name, method.name), method.pos));
+                    } else {
+                        // Chequear covarianza de retorno (Impl <= Base)
+                        if !method_info.return_type.borrow().conforms_to(&parent_method.return_type) {
+                            errors.push(SemanticError::SignatureMismatch(format!(
+                                "El tipo de retorno de {}.{} ({}) no conforma al del padre ({})",
+                                type_decl.name, method.name,
+                                method_info.return_type.borrow().name,
+                                parent_method.return_type.borrow().name
+                            ), method.pos));
+                        }
+
+                        // Chequear contravarianza de argumentos (Base <= Impl)
+                        for (i, (_, param_type)) in method_info.params.iter().enumerate() {
+                            let (_, parent_param_type) = &parent_method.params[i];
+                            if !parent_param_type.borrow().conforms_to(param_type) {
+                                errors.push(SemanticError::SignatureMismatch(format!(
+                                    "El argumento {} de {}.{} ({}) no conforma al del padre ({}) (se requiere contravarianza)",
+                                    i, type_decl.name, method.name,
+                                    param_type.borrow().name,
+                                    parent_param_type.borrow().name
+                                ), method.pos));
+                            }
+                        }
+                    }
+                }
+                parent = p.borrow().parent.clone();
+            }
+        }
+    }
+}
+
+/// Resultado completo del análisis semántico, aunque haya errores.
+pub struct Analysis {
+    /// Contexto con los tipos y funciones registrados (parcial si hubo errores).
+    pub context: Context,
+    pub errors: Vec<SemanticError>,
+    /// Tipo inferido de cada expresión que se pudo chequear, con su rango.
+    pub types: Vec<(Span, Rc<RefCell<Type>>)>,
+}
+
+/// Chequea el programa y devuelve el contexto de tipos o todos los errores encontrados.
 pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
+    let analysis = analyze_program(program);
+    if analysis.errors.is_empty() {
+        Ok(analysis.context)
+    } else {
+        Err(analysis.errors)
+    }
+}
+
+/// Como `check_program`, pero conserva el contexto y los tipos inferidos aun
+/// cuando hay errores; lo usan las herramientas de edición (LSP).
+pub fn analyze_program(program: &Program) -> Analysis {
     let mut context = Context::new();
     let mut errors = Vec::new();
+    let mut types = Vec::new();
+    let mut generic_sites = Vec::new();
+    let mut narrowed_sites = Vec::new();
+    let mut element_sites = Vec::new();
 
     //  Recolectar nombres de Tipos y Protocolos 
     for decl in &program.declarations {
         match decl {
             Declaration::Type(type_decl) => {
-                if let Err(e) = context.create_type(&type_decl.name) {
-                    errors.push(e);
+                match context.create_type(&type_decl.name) {
+                    Ok(t) => { t.borrow_mut().type_params = new_type_params(&context, &type_decl.type_params); }
+                    Err(e) => errors.push(e.at(type_decl.pos)),
                 }
             }
             Declaration::Protocol(proto_decl) => {
                 match context.create_type(&proto_decl.name) {
-                    Ok(t) => { t.borrow_mut().kind = TypeKind::Protocol; }
-                    Err(e) => errors.push(e),
+                    Ok(t) => {
+                        let mut t = t.borrow_mut();
+                        t.kind = TypeKind::Protocol;
+                        t.type_params = new_type_params(&context, &proto_decl.type_params);
+                    }
+                    Err(e) => errors.push(e.at(proto_decl.pos)),
                 }
             }
+            // Sus tipos ya vienen bajados (`lower_variants`) como declaraciones aparte
+            Declaration::Variant(variant) => {
+                let cases = variant.cases.iter().map(|c| c.name.clone()).collect();
+                context.variants.insert(variant.name.clone(), cases);
+            }
             _ => {}
         }
     }
-    if !errors.is_empty() { return Err(errors); }
+    if !errors.is_empty() { return Analysis { context, errors, types }; }
+
+    //  Cotas de los parámetros de tipo (pueden mencionar al propio parámetro: `T: Comparable<T>`)
+    for decl in &program.declarations {
+        let (name, params, pos) = match decl {
+            Declaration::Type(td) => (&td.name, &td.type_params, td.pos),
+            Declaration::Protocol(pd) => (&pd.name, &pd.type_params, pd.pos),
+            _ => continue,
+        };
+        let scope = context.get_type(name).unwrap().borrow().type_params.clone();
+        resolve_bounds(&context, params, &scope, pos, &mut errors);
+    }
 
     //  Establecer Jerarquía (Padres)
     for decl in &program.declarations {
         match decl {
             Declaration::Type(type_decl) => {
                 if let Some(parent_init) = &type_decl.parent {
-                    match context.get_type(&parent_init.name) {
+                    let scope = context.get_type(&type_decl.name).unwrap().borrow().type_params.clone();
+                    match context.resolve_type_in(&parent_init.annotation(), &scope) {
                         Ok(parent_type) => {
                              if parent_type.borrow().kind == TypeKind::Protocol {
-                                 errors.push(SemanticError::GenericError(format!("El tipo {} no puede heredar del protocolo {}", type_decl.name, parent_init.name)));
+                                 errors.push(SemanticError::GenericError(format!("El tipo {} no puede heredar del protocolo {}", type_decl.name, parent_init.name), type_decl.pos));
                              } else {
                                   match context.get_type(&type_decl.name) {
                                       Ok(child) => child.borrow_mut().parent = Some(parent_type.clone()),
@@ -55,7 +226,7 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                                   }
                              }
                         },
-                        Err(e) => errors.push(e),
+                        Err(e) => errors.push(e.at(type_decl.pos)),
                     }
                 } else {
                     // Por defecto hereda de Object (excepto Object mismo)
@@ -72,22 +243,33 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                       match context.get_type(parent_name) {
                            Ok(p) => {
                                if p.borrow().kind != TypeKind::Protocol {
-                                   errors.push(SemanticError::GenericError(format!("El protocolo {} no puede heredar del tipo básico {}", proto_decl.name, parent_name)));
+                                   errors.push(SemanticError::GenericError(format!("El protocolo {} no puede heredar del tipo básico {}", proto_decl.name, parent_name), proto_decl.pos));
                                } else {
                                    if let Ok(child) = context.get_type(&proto_decl.name) {
                                        child.borrow_mut().parent = Some(p.clone());
                                    }
                                }
                            },
-                           Err(e) => errors.push(e),
+                           Err(e) => errors.push(e.at(proto_decl.pos)),
                       }
                  }
             }
             _ => {}
         }
     }
-    
-    
+
+    //  Un `variant` es cerrado: nadie más hereda de su tipo base
+    for decl in &program.declarations {
+        let Declaration::Type(type_decl) = decl else { continue };
+        let Some(parent) = &type_decl.parent else { continue };
+        if context.variants.get(&parent.name).is_some_and(|cases| !cases.contains(&type_decl.name)) {
+            errors.push(SemanticError::GenericError(
+                format!("type '{}' cannot inherit from variant '{}'", type_decl.name, parent.name),
+                type_decl.pos,
+            ));
+        }
+    }
+
     // Chequear ciclos
     for (name, type_rc) in &context.types {
         let mut curr = type_rc.borrow().parent.clone();
@@ -96,7 +278,7 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
         while let Some(p) = curr {
             let pname = p.borrow().name.clone();
             if visited.contains(&pname) {
-                errors.push(SemanticError::CircularInheritance(name.clone()));
+                errors.push(SemanticError::CircularInheritance(name.clone(), decl_pos(program, name)));
                 break;
             }
             visited.insert(pname);
@@ -104,20 +286,22 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
         }
     }
     
-    if !errors.is_empty() { return Err(errors); }
+    if !errors.is_empty() { return Analysis { context, errors, types }; }
 
     //  Recolectar Miembros (Métodos/Atributos) y Funciones Globales
     
     // Funciones Globales
     for decl in &program.declarations {
         if let Declaration::Function(func_decl) = decl {
+            let scope = new_type_params(&context, &func_decl.type_params);
+            resolve_bounds(&context, &func_decl.type_params, &scope, func_decl.pos, &mut errors);
             let mut params = Vec::new();
             for p in &func_decl.params {
                 let p_type = match &p.type_annotation {
                     Some(ann) => {
-                        match context.resolve_type(ann) {
+                        match context.resolve_type_in(ann, &scope) {
                             Ok(t) => t,
-                            Err(e) => { errors.push(e); context.get_type("Object").unwrap() }
+                            Err(e) => { errors.push(e.at(func_decl.pos)); context.get_type("Object").unwrap() }
                         }
                     },
                     None => context.get_type("Object").unwrap(),
@@ -127,16 +311,18 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
             
             let ret_type = match &func_decl.return_type {
                 Some(ann) => {
-                    match context.resolve_type(ann) {
+                    match context.resolve_type_in(ann, &scope) {
                         Ok(t) => t,
-                        Err(e) => { errors.push(e); context.get_type("Object").unwrap() }
+                        Err(e) => { errors.push(e.at(func_decl.pos)); context.get_type("Object").unwrap() }
                     }
                 },
                 None => context.get_type("Object").unwrap(), // Retorno por defecto Object? 
             };
             
             if let Err(e) = context.define_function(&func_decl.name, params, ret_type) {
-                errors.push(e);
+                errors.push(e.at(func_decl.pos));
+            } else if !scope.is_empty() {
+                context.function_type_params.insert(func_decl.name.clone(), scope);
             }
         }
     }
@@ -154,7 +340,7 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                 };
                 let p_type = match context.resolve_type(ann) {
                     Ok(t) => t,
-                    Err(e) => { errors.push(e); context.get_type("Object").unwrap() }
+                    Err(e) => { errors.push(e.at(macro_decl.pos)); context.get_type("Object").unwrap() }
                 };
                 params.push(p_type);
             }
@@ -163,14 +349,14 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                 Some(ann) => {
                     match context.resolve_type(ann) {
                         Ok(t) => t,
-                        Err(e) => { errors.push(e); context.get_type("Object").unwrap() }
+                        Err(e) => { errors.push(e.at(macro_decl.pos)); context.get_type("Object").unwrap() }
                     }
                 },
                 None => context.get_type("Object").unwrap(),
             };
 
             if let Err(e) = context.define_function(&macro_decl.name, params, ret_type) {
-                errors.push(e);
+                errors.push(e.at(macro_decl.pos));
             }
         }
     }
@@ -179,12 +365,13 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
     for decl in &program.declarations {
          if let Declaration::Type(type_decl) = decl {
              let current_type_rc = context.get_type(&type_decl.name).unwrap();
+             let scope = current_type_rc.borrow().type_params.clone();
              
              // Parametros del Constructor
              let mut ctor_params = Vec::new();
              for p in &type_decl.params {
                   let p_type = match &p.type_annotation {
-                    Some(ann) => match context.resolve_type(ann) { Ok(t) => t, Err(e) => { errors.push(e); context.get_type("Object").unwrap() } },
+                    Some(ann) => match context.resolve_type_in(ann, &scope) { Ok(t) => t, Err(e) => { errors.push(e.at(type_decl.pos)); context.get_type("Object").unwrap() } },
                     None => context.get_type("Object").unwrap(),
                   };
                   ctor_params.push((p.name.clone(), p_type));
@@ -194,15 +381,15 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
              // Atributos
              for attr in &type_decl.attributes {
                  let attr_type = match &attr.type_annotation {
-                     Some(ann) => match context.resolve_type(ann) {
+                     Some(ann) => match context.resolve_type_in(ann, &scope) {
                          Ok(t) => t,
-                         Err(e) => { errors.push(e); context.get_type("Object").unwrap() }
+                         Err(e) => { errors.push(e.at(attr.pos)); context.get_type("Object").unwrap() }
                      },
                      None => context.get_type("Object").unwrap(),
                  };
                  // Agregar atributo al tipo
                  if current_type_rc.borrow().attributes.contains_key(&attr.name) {
-                     errors.push(SemanticError::AttributeDefined(format!("{}.{}", type_decl.name, attr.name)));
+                     errors.push(SemanticError::AttributeDefined(format!("{}.{}", type_decl.name, attr.name), attr.pos));
                  } else {
                      current_type_rc.borrow_mut().attributes.insert(attr.name.clone(), attr_type);
                  }
@@ -213,13 +400,13 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                  let mut params = Vec::new();
                  for p in &method.params {
                       let p_type = match &p.type_annotation {
-                        Some(ann) => match context.resolve_type(ann) { Ok(t) => t, Err(e) => { errors.push(e); context.get_type("Object").unwrap() } },
+                        Some(ann) => match context.resolve_type_in(ann, &scope) { Ok(t) => t, Err(e) => { errors.push(e.at(method.pos)); context.get_type("Object").unwrap() } },
                         None => context.get_type("Object").unwrap(),
                       };
                       params.push((p.name.clone(), p_type));
                  }
                  let ret_type = match &method.return_type {
-                     Some(ann) => match context.resolve_type(ann) { Ok(t) => t, Err(e) => { errors.push(e); context.get_type("Object").unwrap() } },
+                     Some(ann) => match context.resolve_type_in(ann, &scope) { Ok(t) => t, Err(e) => { errors.push(e.at(method.pos)); context.get_type("Object").unwrap() } },
                      None => context.get_type("Object").unwrap(),
                  };
                  
@@ -230,41 +417,8 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                  };
                  
                  if current_type_rc.borrow().methods.contains_key(&method.name) {
-                      errors.push(SemanticError::MethodDefined(format!("{}.{}", type_decl.name, method.name)));
+                      errors.push(SemanticError::MethodDefined(format!("{}.{}", type_decl.name, method.name), method.pos));
                  } else {
-                     // Chequear override (sobreescritura)
-                     let mut parent = current_type_rc.borrow().parent.clone();
-                     while let Some(p) = parent {
-                         if let Some(parent_method) = p.borrow().methods.get(&method.name) {
-                             if parent_method.params.len() != method_info.params.len() {
-                                 errors.push(SemanticError::SignatureMismatch(format!("{}.{} sobreescribe con diferente cantidad de argumentos", type_decl.name, method.name)));
-                             } else {
-                                // Chequear covarianza de retorno (Impl <= Base)
-                                if !method_info.return_type.borrow().conforms_to(&parent_method.return_type) {
-                                     errors.push(SemanticError::SignatureMismatch(format!(
-                                         "El tipo de retorno de {}.{} ({}) no conforma al del padre ({})", 
-                                         type_decl.name, method.name, 
-                                         method_info.return_type.borrow().name, 
-                                         parent_method.return_type.borrow().name
-                                     )));
-                                }
-                                
-                                // Chequear contravarianza de argumentos (Base <= Impl)
-                                for (i, (_, param_type)) in method_info.params.iter().enumerate() {
-                                    let (_, parent_param_type) = &parent_method.params[i];
-                                    if !parent_param_type.borrow().conforms_to(param_type) {
-                                         errors.push(SemanticError::SignatureMismatch(format!(
-                                             "El argumento {} de {}.{} ({}) no conforma al del padre ({}) (se requiere contravarianza)", 
-                                             i, type_decl.name, method.name, 
-                                             param_type.borrow().name, 
-                                             parent_param_type.borrow().name
-                                         )));
-                                    }
-                                }
-                             }
-                         }
-                         parent = p.borrow().parent.clone();
-                     }
                      current_type_rc.borrow_mut().methods.insert(method.name.clone(), method_info);
                  }
              }
@@ -272,16 +426,17 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
          
          if let Declaration::Protocol(proto) = decl {
              let current_type_rc = context.get_type(&proto.name).unwrap();
+             let scope = current_type_rc.borrow().type_params.clone();
              for method in &proto.methods {
                   let mut params = Vec::new();
                  for p in &method.params {
                       let p_type = match &p.type_annotation {
-                        Some(ann) => match context.resolve_type(ann) { Ok(t) => t, Err(e) => { errors.push(e); context.get_type("Object").unwrap() } },
+                        Some(ann) => match context.resolve_type_in(ann, &scope) { Ok(t) => t, Err(e) => { errors.push(e.at(method.pos)); context.get_type("Object").unwrap() } },
                         None => context.get_type("Object").unwrap(),
                       };
                       params.push((p.name.clone(), p_type));
                  }
-                 let ret_type = match context.resolve_type(&method.return_type) { Ok(t) => t, Err(e) => { errors.push(e); context.get_type("Object").unwrap() } };
+                 let ret_type = match context.resolve_type_in(&method.return_type, &scope) { Ok(t) => t, Err(e) => { errors.push(e.at(method.pos)); context.get_type("Object").unwrap() } };
                  
                  let method_info = MethodInfo {
                      name: method.name.clone(),
@@ -293,7 +448,20 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
          }
     }
     
-    if !errors.is_empty() { return Err(errors); }
+    if !errors.is_empty() { return Analysis { context, errors, types }; }
+
+    // Inferir los tipos que el programa no anota; las firmas que sobrescriben
+    // se comprueban con el resultado
+    errors.extend(infer::infer_program(program, &mut context));
+    check_overrides(program, &context, &mut errors);
+    if !errors.is_empty() { return Analysis { context, errors, types }; }
+
+    // Con todos los miembros registrados, llenar las instancias genéricas y
+    // comprobar las cotas de los argumentos de tipo escritos en las firmas
+    context.complete_instances();
+    context.check_bounds.set(true);
+    check_signature_bounds(program, &context, &mut errors);
+    if !errors.is_empty() { return Analysis { context, errors, types }; }
 
     // Chequear Cuerpos (Funciones y Métodos)     
     // Chequear Expresión Global
@@ -302,22 +470,32 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
         if let Err(mut body_errors) = checker.infer_type(&program.expr) {
             errors.append(&mut body_errors);
         }
+        types.append(&mut checker.inferred);
+        generic_sites.append(&mut checker.generic_sites);
+        narrowed_sites.append(&mut checker.narrowed_sites);
+        element_sites.append(&mut checker.element_sites);
     }
     
     // Chequear Funciones Globales
     for decl in &program.declarations {
          if let Declaration::Function(func) = decl {
              let mut checker = TypeChecker::new(&context);
+             checker.type_params = context.function_type_params.get(&func.name).cloned().unwrap_or_default();
              // Agregar parámetros al scope
-             let (params_types, _) = context.functions.get(&func.name).unwrap();
+             let (params_types, ret_type) = context.functions.get(&func.name).unwrap();
              for (i, p_decl) in func.params.iter().enumerate() {
                  let t = params_types[i].clone();
                  checker.define(p_decl.name.clone(), t);
              }
+             checker.return_type = Some(ret_type.clone());
              
              if let Err(mut body_errors) = checker.infer_type(&func.body) {
                 errors.append(&mut body_errors);
              }
+             types.append(&mut checker.inferred);
+             generic_sites.append(&mut checker.generic_sites);
+             narrowed_sites.append(&mut checker.narrowed_sites);
+             element_sites.append(&mut checker.element_sites);
          }
     }
 
@@ -340,6 +518,10 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
              if let Err(mut body_errors) = checker.infer_type(&macro_decl.body) {
                 errors.append(&mut body_errors);
              }
+             types.append(&mut checker.inferred);
+             generic_sites.append(&mut checker.generic_sites);
+             narrowed_sites.append(&mut checker.narrowed_sites);
+             element_sites.append(&mut checker.element_sites);
          }
     }
     
@@ -358,15 +540,20 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                  }
                  
                  checker.current_type = Some(type_rc.clone());
+                 checker.type_params = type_rc.borrow().type_params.clone();
 
                  let init_type = match checker.infer_type(&attr.init) {
                      Ok(t) => t,
                      Err(mut e) => { errors.append(&mut e); context.get_type("Object").unwrap() }
                  };
+                 types.append(&mut checker.inferred);
+                 generic_sites.append(&mut checker.generic_sites);
+                 narrowed_sites.append(&mut checker.narrowed_sites);
+                 element_sites.append(&mut checker.element_sites);
                  
                  let attr_type = type_rc.borrow().attributes.get(&attr.name).unwrap().clone();
-                 if !conforms_to(init_type.clone(), attr_type.clone()) {
-                      errors.push(SemanticError::TypeMismatch{ expected: attr_type.borrow().name.clone(), found: init_type.borrow().name.clone(), pos: attr.init.pos });
+                 if !TypeChecker::is_compatible(&init_type, &attr_type) {
+                      errors.push(SemanticError::TypeMismatch{ expected: attr_type.borrow().name.clone(), found: init_type.borrow().name.clone(), span: attr.init.span() });
                  }
             }
 
@@ -381,20 +568,27 @@ pub fn check_program(program: &Program) -> Result<Context, Vec<SemanticError>> {
                 }
                 
                 checker.current_type = Some(type_rc.clone()); // 'self' disponible
+                checker.type_params = type_rc.borrow().type_params.clone();
+                checker.return_type = Some(method_info.return_type.clone());
                 
                 let body_type = match checker.infer_type(&method.body) {
                      Ok(t) => t,
                      Err(mut e) => { errors.append(&mut e); context.get_type("Object").unwrap() }
                  };
+                 types.append(&mut checker.inferred);
+                 generic_sites.append(&mut checker.generic_sites);
+                 narrowed_sites.append(&mut checker.narrowed_sites);
+                 element_sites.append(&mut checker.element_sites);
                  
-                 if !conforms_to(body_type.clone(), method_info.return_type.clone()) {
-                      errors.push(SemanticError::TypeMismatch{ expected: method_info.return_type.borrow().name.clone(), found: body_type.borrow().name.clone(), pos: method.body.pos });
+                 if !TypeChecker::is_compatible(&body_type, &method_info.return_type) {
+                      errors.push(SemanticError::TypeMismatch{ expected: method_info.return_type.borrow().name.clone(), found: body_type.borrow().name.clone(), span: method.body.span() });
                  }
             }
         }
     }
 
-    if !errors.is_empty() { return Err(errors); }
-
-    Ok(context)
+    context.generic_sites = generic_sites.into_iter().collect();
+    context.narrowed_sites = narrowed_sites.into_iter().collect();
+    context.element_sites = element_sites.into_iter().collect();
+    Analysis { context, errors, types }
 }
@@ -62,7 +62,10 @@ fn test_scope_shadowing() {
 fn test_undefined_variable() {
     let errors = check_fail("print(x);");
      match &errors[0] {
-        SemanticError::VariableNotFound(name) => assert_eq!(name, "x"),
+        SemanticError::VariableNotFound(name, pos) => {
+            assert_eq!(name, "x");
+            assert_eq!((pos.line, pos.column), (1, 7));
+        }
         _ => panic!("Expected VariableNotFound"),
     }
 }
@@ -122,7 +125,7 @@ fn test_circular_inheritance() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-        SemanticError::CircularInheritance(_) => {}, // Pass
+        SemanticError::CircularInheritance(_, pos) => assert!(pos.line == 2 || pos.line == 3),
         _ => panic!("Expected CircularInheritance error, got {:?}", errors[0]),
     }
 }
@@ -131,7 +134,7 @@ fn test_circular_inheritance() {
 fn test_method_not_found() {
     let errors = check_fail("print(1.substring(0));");
      match &errors[0] {
-        SemanticError::MethodNotFound(_) => {},
+        SemanticError::MethodNotFound(_, pos) => assert_eq!(pos.line, 1),
         _ => panic!("Expected MethodNotFound"),
     }
 }
@@ -167,7 +170,10 @@ fn test_type_redefinition() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-        SemanticError::TypeDefined(name) => assert_eq!(name, "A"),
+        SemanticError::TypeDefined(name, pos) => {
+            assert_eq!(name, "A");
+            assert_eq!((pos.line, pos.column), (3, 10));
+        }
         _ => panic!("Expected TypeDefined"),
     }
 }
@@ -180,7 +186,10 @@ fn test_function_redefinition() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-        SemanticError::FunctionDefined(name) => assert_eq!(name, "foo"),
+        SemanticError::FunctionDefined(name, pos) => {
+            assert_eq!(name, "foo");
+            assert_eq!((pos.line, pos.column), (3, 14));
+        }
         _ => panic!("Expected FunctionDefined"),
     }
 }
@@ -192,7 +201,10 @@ fn test_parent_type_not_found() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-        SemanticError::TypeNotFound(name) => assert_eq!(name, "B"),
+        SemanticError::TypeNotFound(name, pos) => {
+            assert_eq!(name, "B");
+            assert_eq!((pos.line, pos.column), (2, 10));
+        }
         _ => panic!("Expected TypeNotFound"),
     }
 }
@@ -201,7 +213,7 @@ fn test_parent_type_not_found() {
 fn test_self_outside_class() {
     let errors = check_fail("print(self);");
     match &errors[0] {
-        SemanticError::SelfReference => {},
+        SemanticError::SelfReference(pos) => assert_eq!(pos.column, 7),
         _ => panic!("Expected SelfReference"),
     }
 }
@@ -216,7 +228,10 @@ fn test_attribute_redefinition() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-        SemanticError::AttributeDefined(name) => assert!(name.contains("A.x")),
+        SemanticError::AttributeDefined(name, pos) => {
+            assert!(name.contains("A.x"));
+            assert_eq!(pos.line, 4);
+        }
         _ => panic!("Expected AttributeDefined"),
     }
 }
@@ -233,7 +248,7 @@ fn test_signature_mismatch() {
     ";
     let errors = check_fail(code);
     match &errors[0] {
-         SemanticError::SignatureMismatch(_) => {},
+         SemanticError::SignatureMismatch(_, pos) => assert_eq!(pos.line, 6),
          _ => panic!("Expected SignatureMismatch"),
     }
 }
@@ -244,3 +259,342 @@ fn test_condition_must_be_boolean() {
      let errors = check_fail(code);
      assert!(!errors.is_empty());
 }
+
+const GENERIC_DECLS: &str = "
+    protocol Comparable<T> { compare(other: T): Number; }
+    type Money(amount: Number) {
+        amount: Number = amount;
+        compare(other: Money): Number => self.amount - other.amount;
+    }
+    type Box<T>(value: T) {
+        value: T = value;
+        get(): T => self.value;
+    }
+    function max<T: Comparable<T>>(a: T, b: T): T => if (a.compare(b) > 0) a else b;
+    function first<T>(xs: T[]): T => xs[0];
+";
+
+#[test]
+fn test_generics_substitute_type_arguments() {
+    check(&format!("{} let b = new Box<Number>(1) in b.get() + 1;", GENERIC_DECLS));
+    check(&format!("{} let b: Box<String> = new Box(\"a\") in b.get() @ \"b\";", GENERIC_DECLS));
+    check(&format!("{} first([1, 2]) + max(new Money(1), new Money(2)).amount;", GENERIC_DECLS));
+    check(&format!("{} type NumBox inherits Box<Number>(0) {{ twice(): Number => self.get() * 2; }} 1;", GENERIC_DECLS));
+
+    // El resultado de `get` es `String`, no `Object`: sumar un número falla
+    let errors = check_fail(&format!("{} let b = new Box(\"a\") in b.get() + 1;", GENERIC_DECLS));
+    assert!(matches!(&errors[0], SemanticError::GenericError(msg, _) if msg.contains("String")), "{:?}", errors);
+}
+
+#[test]
+fn test_generic_attribute_initialized_with_empty_vector() {
+    check("type Bag<T> { items: T[] = []; add(x: T): T[] { self.items.push(x); self.items; } } \
+        let b = new Bag<String>() in print(b.add(\"a\").size());");
+    let err = check_fail("type Bag<T> { items: T[] = []; add(x: T): T => self.items.push(x); } new Bag<String>().add(1);");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "String" && found == "Number"), "{:?}", err);
+}
+
+#[test]
+fn test_generic_argument_mismatch() {
+    let errors = check_fail(&format!("{} new Box<Number>(\"a\");", GENERIC_DECLS));
+    assert!(matches!(&errors[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "Number" && found == "String"));
+}
+
+#[test]
+fn test_bound_not_satisfied() {
+    let errors = check_fail(&format!("{}\nmax(1, 2);", GENERIC_DECLS));
+    match &errors[0] {
+        SemanticError::BoundNotSatisfied { ty, param, bound, pos } => {
+            assert_eq!((ty.as_str(), param.as_str(), bound.as_str()), ("Number", "T", "Comparable<Number>"));
+            assert_eq!(pos.line, 14);
+        }
+        other => panic!("Expected BoundNotSatisfied, got {:?}", other),
+    }
+
+    let errors = check_fail(&format!("{} type Sorted<T: Comparable<T>> {{ }} function f(s: Sorted<Number>) => s;", GENERIC_DECLS));
+    assert!(matches!(&errors[0], SemanticError::BoundNotSatisfied { ty, .. } if ty == "Number"));
+    check(&format!("{} type Sorted<T: Comparable<T>> {{ }} function f(s: Sorted<Money>) => s; 1;", GENERIC_DECLS));
+}
+
+#[test]
+fn test_type_argument_count() {
+    let errors = check_fail(&format!("{} let b: Box<Number, String> = new Box(1) in b;", GENERIC_DECLS));
+    assert!(matches!(&errors[0], SemanticError::TypeArgumentCountMismatch(name, 1, 2, _) if name == "Box"));
+
+    // Un tipo genérico no se puede usar sin argumentos
+    let errors = check_fail(&format!("{} function f(b: Box) => b;", GENERIC_DECLS));
+    assert!(matches!(&errors[0], SemanticError::TypeArgumentCountMismatch(name, 1, 0, _) if name == "Box"));
+    let errors = check_fail("type A { } let a = new A<Number>() in a;");
+    assert!(matches!(&errors[0], SemanticError::TypeArgumentCountMismatch(name, 0, 1, _) if name == "A"));
+}
+
+#[test]
+fn test_type_parameters_are_scoped() {
+    let errors = check_fail("type Box<T>(value: T) { value: T = value; } function f(x: T) => x;");
+    assert!(matches!(&errors[0], SemanticError::TypeNotFound(name, _) if name == "T"));
+    // Sin cota, `T` no admite aritmética
+    let errors = check_fail("function add<T>(a: T, b: T) => a + b;");
+    assert!(!errors.is_empty());
+}
+
+/// Firma de `name` en el contexto, como nombres de tipos.
+fn signature(input: &str, name: &str) -> (Vec<String>, String) {
+    let mut parser = Parser::new(input);
+    let program = parser.parse_program().expect("Failed to parse program");
+    let context = check_program(&program).unwrap_or_else(|e| panic!("Semantic check failed: {:?}", e));
+    let (params, ret) = context.functions[name].clone();
+    (params.iter().map(|t| t.borrow().name.clone()).collect(), ret.borrow().name.clone())
+}
+
+const SHAPES: &str = "
+    type Circle(r) { r = r; area() => 3 * self.r * self.r; }
+    type Square(side) { side = side; area() => self.side * self.side; }
+";
+
+#[test]
+fn test_infers_parameters_and_returns_from_usage() {
+    let sig = |input: &str, name: &str| signature(input, name);
+    assert_eq!(sig("function inc(x) => x + 1; 1;", "inc"), (vec!["Number".into()], "Number".into()));
+    // Sin usos que la restrinjan, el parámetro toma el tipo de lo que le pasan
+    assert_eq!(sig("function greet(n) => \"Hi \" @ n; greet(\"Ana\");", "greet"), (vec!["String".into()], "String".into()));
+    assert_eq!(sig("function id(x) => x; id(1) + id(2);", "id"), (vec!["Number".into()], "Number".into()));
+    // Llamadas con tipos distintos dejan el parámetro en su ancestro común
+    assert_eq!(sig("function show(x) => x; show(1); show(\"a\");", "show"), (vec!["Object".into()], "Object".into()));
+    // La recursión se resuelve por rondas
+    assert_eq!(sig("function fib(n) => if (n < 2) n else fib(n - 1) + fib(n - 2); 1;", "fib"), (vec!["Number".into()], "Number".into()));
+    // Un parámetro que se pasa a otra función hereda lo que esta exige
+    assert_eq!(sig("function half(x) => x / 2; function quarter(y) => half(half(y)); 1;", "quarter"), (vec!["Number".into()], "Number".into()));
+}
+
+#[test]
+fn test_infers_receivers_from_methods_and_call_sites() {
+    // `area` existe en dos tipos, pero la llamada decide
+    let input = format!("{} function area_of(s) => s.area(); area_of(new Circle(2));", SHAPES);
+    assert_eq!(signature(&input, "area_of"), (vec!["Circle".into()], "Number".into()));
+    // Un único tipo declara `perimeter`
+    let input = format!("{} type Rect(w, h) {{ w = w; h = h; perimeter() => 2 * (self.w + self.h); }} function p(r) => r.perimeter(); 1;", SHAPES);
+    assert_eq!(signature(&input, "p"), (vec!["Rect".into()], "Number".into()));
+    // Variables de `for` y lambdas usan los tipos inferidos
+    check(&format!("{} for (c in [new Circle(1), new Circle(2)]) print(c.area());", SHAPES));
+    check("type Circle(r) { r = r; area() => 3 * self.r * self.r; } let f = (c) => c.area() * 2 in f(new Circle(1));");
+}
+
+#[test]
+fn test_ambiguous_inference_is_reported() {
+    let errors = check_fail(&format!("{} function area_of(s) => s.area(); 1;", SHAPES));
+    match &errors[0] {
+        SemanticError::AmbiguousType { name, candidates, pos } => {
+            assert_eq!(name, "s");
+            assert_eq!(candidates, &vec!["Circle".to_string(), "Square".to_string()]);
+            assert_eq!(pos.line, 4);
+        }
+        other => panic!("Expected AmbiguousType, got {:?}", other),
+    }
+
+    // Usos incompatibles entre sí
+    let errors = check_fail("function g(x) => if (x) x + 1 else 0; 1;");
+    assert!(matches!(&errors[0], SemanticError::AmbiguousType { candidates, .. } if candidates == &vec!["Boolean".to_string(), "Number".to_string()]), "{:?}", errors);
+}
+
+#[test]
+fn test_inferred_members_and_overrides() {
+    // Atributos y parámetros del constructor
+    check("type P(x) { x = x; next() => self.x + 1; } new P(1).next() + 1;");
+    let errors = check_fail("type P(x) { x = x; } let p = new P(\"a\") in p.x + 1;");
+    assert!(matches!(&errors[0], SemanticError::GenericError(msg, _) if msg.contains("String")), "{:?}", errors);
+    // Quien sobrescribe sin anotar comparte los parámetros del padre
+    check("type A { f(x) => x + 1; } type B inherits A { f(y) => y * 2; } new B().f(1) + 1;");
+    // y su retorno es uno de los posibles del método del padre
+    check("type A { f() => 1; } type B inherits A { f() => \"b\"; } print(new B().f());");
+}
+
+#[test]
+fn test_lambda_return_annotation_is_checked() {
+    check("let f = (x: Number): Number => x + 1 in f(1);");
+    let errors = check_fail("let f = (x: Number): String => x + 1 in f(1);");
+    assert!(matches!(&errors[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "String" && found == "Number"), "{:?}", errors);
+}
+
+#[test]
+fn test_is_narrows_variable_in_branch() {
+    let animals = "type Animal { speak() => \"...\"; } type Dog inherits Animal { bark() => \"woof\"; } type Cat inherits Animal { meow() => \"meow\"; }";
+    check(&format!("{} function f(a: Animal) => if (a is Dog) a.bark() elif (a is Cat) a.meow() else a.speak(); 1;", animals));
+    check(&format!("{} function f(a: Animal, b: Animal) => a is Dog & b is Cat & a.bark() == b.meow(); 1;", animals));
+    // Un parámetro sin anotar que se pregunta con `is` no se fija al tipo preguntado
+    check(&format!("{} function f(x) => if (x is Dog) x.bark() else \"?\"; f(new Cat());", animals));
+    // Fuera de la rama, o tras asignar otro valor, la variable recupera su tipo
+    let errors = check_fail(&format!("{} function f(a: Animal) => if (a is Dog) \"d\" else a.bark(); 1;", animals));
+    assert!(matches!(&errors[0], SemanticError::MethodNotFound(msg, _) if msg.contains("bark") && msg.contains("Animal")), "{:?}", errors);
+    let errors = check_fail(&format!("{} function f(a: Animal) => if (a is Dog) {{ a := new Cat(); a.bark(); }} else \"\"; 1;", animals));
+    assert!(matches!(&errors[0], SemanticError::MethodNotFound(msg, _) if msg.contains("bark")), "{:?}", errors);
+}
+
+#[test]
+fn test_match_binds_patterns_and_checks_exhaustiveness() {
+    let shapes = "type Shape { } type Circle(r: Number) inherits Shape { r = r; } type Rect(w: Number, h: Number) inherits Shape { w = w; h = h; }";
+    check(&format!("{} function f(s: Shape): Number => match (s) {{ case Circle(r) if r > 1 => r; case c: Circle => c.r; case Rect(w, _) => w; case _ => 0; }}; 1;", shapes));
+    check("function f(b: Boolean) => match (b) { case true => 1; case false => 0; }; 1;");
+    // Sin `default`, cada tipo concreto debe tener un caso sin guarda
+    let errors = check_fail(&format!("{} function f(s: Shape) => match (s) {{ case Circle(r) => r; case r: Rect if r.w > 0 => r.w; }}; 1;", shapes));
+    assert!(matches!(&errors[0], SemanticError::NonExhaustiveMatch { missing, .. } if missing == &["Shape"]), "{:?}", errors);
+    let errors = check_fail("function f(b: Boolean) => match (b) { case true => 1; }; 1;");
+    assert!(matches!(&errors[0], SemanticError::NonExhaustiveMatch { missing, .. } if missing == &["false"]), "{:?}", errors);
+    // Un patrón de constructor necesita un atributo por parámetro
+    let errors = check_fail(&format!("{} function f(s: Shape) => match (s) {{ case Rect(w) => w; default => 0; }}; 1;", shapes));
+    assert!(matches!(&errors[0], SemanticError::ArgumentCountMismatch(..)), "{:?}", errors);
+    let errors = check_fail("function f(s: String) => match (s) { case 1 => 1; default => 0; }; 1;");
+    assert!(matches!(&errors[0], SemanticError::TypeMismatch { .. }), "{:?}", errors);
+}
+
+#[test]
+fn test_variant_is_a_closed_hierarchy() {
+    let errors_of = |src: &str| match crate::driver::check(src) {
+        Ok(_) => Vec::new(),
+        Err(crate::driver::CompileError::Semantic(errors)) => errors,
+        Err(e) => panic!("{}", e),
+    };
+    let shape = "variant Shape = Circle(r: Number) | Rect(w: Number, h: Number) | Empty;";
+    let ok = errors_of(&format!("{} function area(s: Shape): Number => match (s) {{ case Circle(r) => r * r; case Rect(w, h) => w * h; case Empty => 0; }}; print(area(new Rect(2, 3)));", shape));
+    assert!(ok.is_empty(), "{:?}", ok);
+    // Sin tipo base en la cobertura: basta con nombrar cada caso
+    let errors = errors_of(&format!("{} function f(s: Shape) => match (s) {{ case Circle(r) => r; case Empty => 0; }}; 1;", shape));
+    assert!(matches!(&errors[..], [SemanticError::NonExhaustiveMatch { missing, .. }] if missing == &["Rect"]), "{:?}", errors);
+    // Una variable anotada con el tipo base admite cualquier caso
+    let ok = errors_of("variant Light = Red | Green; let l: Light = new Red() in l := new Green();");
+    assert!(ok.is_empty(), "{:?}", ok);
+    let errors = errors_of(&format!("{} print(new Shape());", shape));
+    assert!(matches!(&errors[0], SemanticError::GenericError(msg, _) if msg.contains("cannot instantiate variant 'Shape'")), "{:?}", errors);
+    let errors = errors_of(&format!("{} type Triangle inherits Shape {{ }} 1;", shape));
+    assert!(matches!(&errors[0], SemanticError::GenericError(msg, _) if msg.contains("cannot inherit from variant")), "{:?}", errors);
+}
+
+#[test]
+fn test_try_catch_and_throw() {
+    let errors_of = |src: &str| match crate::driver::check(src) {
+        Ok(_) => Vec::new(),
+        Err(crate::driver::CompileError::Semantic(errors)) => errors,
+        Err(e) => panic!("{}", e),
+    };
+    let ok = errors_of("function f(x: Number): Number => if (x < 0) throw new Error(\"neg\") else x; \
+        print(try f(1) catch (e: IndexError) e.line catch (e) 0);");
+    assert!(ok.is_empty(), "{:?}", ok);
+    // `throw` es de tipo `Never`: no aporta nada al tipo del `if`
+    let ok = errors_of("let x: Number = if (true) 1 else throw new Error(\"x\") in print(x);");
+    assert!(ok.is_empty(), "{:?}", ok);
+    let errors = errors_of("throw 1;");
+    assert!(matches!(&errors[0], SemanticError::TypeMismatch { expected, .. } if expected == "Error"), "{:?}", errors);
+    let errors = errors_of("try 1 catch (e: Number) 2;");
+    assert!(matches!(&errors[0], SemanticError::TypeMismatch { expected, .. } if expected == "Error"), "{:?}", errors);
+    let errors = errors_of("try 1 catch (e: Oops) 2;");
+    assert!(matches!(&errors[0], SemanticError::TypeNotFound(..)), "{:?}", errors);
+}
+
+#[test]
+fn test_break_continue_and_return() {
+    check("function f(n: Number): Number { for (x in [1, 2, 3]) if (x == n) return x else continue; 0; } \
+        print(while (true) break 1);");
+    check("type A { f(): Number { return 1; 2; } } print(new A().f());");
+    let err = check_fail("break;");
+    assert!(matches!(&err[0], SemanticError::MisplacedJump { keyword, .. } if keyword == "break"), "{:?}", err);
+    let err = check_fail("return 1;");
+    assert!(matches!(&err[0], SemanticError::MisplacedJump { keyword, .. } if keyword == "return"), "{:?}", err);
+    // El cuerpo de una lambda no forma parte del bucle que la rodea
+    let err = check_fail("while (true) { let g = () => continue in g(); };");
+    assert!(matches!(&err[0], SemanticError::MisplacedJump { keyword, .. } if keyword == "continue"), "{:?}", err);
+    let err = check_fail("function f(): Number { return \"a\"; }");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, .. } if expected == "Number"), "{:?}", err);
+}
+
+#[test]
+fn test_string_methods() {
+    check("let s = \" a,b \".trim() in { \
+        let n: Number = s.size() + s.index_of(\",\") + s.parse_number() in print(n); \
+        let parts: String[] = s.split(\",\") in print(parts[0].upper() @ s.substring(0, 1) @ s.at(2)); \
+        let b: Boolean = s.starts_with(\"a\") in print(b); \
+        print(s.replace(\",\", \";\").lower()); }");
+    // El tipo del parámetro sale de los métodos que se le llaman
+    check("function shout(s) => s.trim().upper(); print(shout(\" hi \"));");
+    let err = check_fail("print(\"abc\".substring(\"1\", 2));");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, .. } if expected == "Number"), "{:?}", err);
+    let err = check_fail("print(\"abc\".size(1));");
+    assert!(matches!(&err[0], SemanticError::ArgumentCountMismatch(..)), "{:?}", err);
+    let err = check_fail("print(\"abc\".reverse());");
+    assert!(matches!(&err[0], SemanticError::MethodNotFound(..)), "{:?}", err);
+    // Las cadenas no pasan por los thunks de los protocolos
+    let err = check_fail("protocol Sized { size(): Number; } let x: Sized = \"abc\" in print(x.size());");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, .. } if expected == "Sized"), "{:?}", err);
+}
+
+#[test]
+fn test_string_comparison_and_hash() {
+    check("let a = \"x\", b = \"y\" in { \
+        let lt: Boolean = a < b, ge: Boolean = a >= b, eq: Boolean = a == b in print(lt & ge | eq); \
+        let h: Number = a.hash() in print(h); }");
+    // Solo se ordenan dos cadenas o dos números
+    let err = check_fail("print(\"a\" < 1);");
+    assert!(matches!(&err[0], SemanticError::GenericError(..) | SemanticError::OperationNotDefined(..)), "{:?}", err);
+    let err = check_fail("print(true <= true);");
+    assert!(matches!(&err[0], SemanticError::OperationNotDefined(..)), "{:?}", err);
+}
+
+
+#[test]
+fn test_map_type() {
+    check("function total(m: Map<String, Number>): Number { let s = 0 in { for (k in m) s := s + m.get(k); s; }; } \
+        let m = {\"a\" -> 1, \"b\" -> 2}, e = new Map<Number, Boolean>() in { \
+        let n: Number = m.set(\"c\", 3), h: Boolean = m.has(\"a\") & m.remove(\"b\"), ks: String[] = m.keys(), \
+            vs: Number[] = m.values(), flag: Boolean = e.set(1, true) in print(total(m) + n + e.size()); }");
+    // Claves y valores del literal tienen el tipo del primer par
+    let err = check_fail("let m = {\"a\" -> 1, \"b\" -> \"2\"} in m;");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "Number" && found == "String"), "{:?}", err);
+    let err = check_fail("let m = new Map<String, Number>() in m.get(1);");
+    assert!(!err.is_empty());
+    let err = check_fail("let m = {1 -> 2} in let s: String = m.get(1) in s;");
+    assert!(!err.is_empty());
+}
+
+#[test]
+fn test_growable_vectors() {
+    check("function last(v: Number[]): Number => v[v.size() - 1]; \
+        let v = [1, 2], w: Number[] = [], s = [\"a\"] in { \
+        let n: Number = v.push(3) + v.pop() + v.insert(0, 4) + v.remove_at(1), \
+            t: String = s.pop(), u: Number[] = v[1:] + w + [] in { v[0] := n; print(last(u[:1]) @ t); }; }");
+    let err = check_fail("let v = [1] in v.push(\"a\");");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "Number" && found == "String"), "{:?}", err);
+    let err = check_fail("let v = [1] in v[0] := true;");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, found, .. } if expected == "Number" && found == "Boolean"), "{:?}", err);
+    let err = check_fail("print([1] + [\"a\"]);");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { .. }), "{:?}", err);
+    let err = check_fail("print(\"abc\"[1:2]);");
+    assert!(matches!(&err[0], SemanticError::OperationNotDefined(..)), "{:?}", err);
+    let err = check_fail("let v = [1] in v[\"a\":];");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { .. }), "{:?}", err);
+
+}
+
+#[test]
+fn test_attribute_initialized_with_empty_vector() {
+    check("type Stack { items: Number[] = []; push(x: Number): Number[] { self.items.push(x); self.items; } }         print(new Stack().push(1));");
+    let err = check_fail("type Stack { items: Number[] = [\"a\"]; } print(new Stack());");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { expected, .. } if expected == "Vector<Number>"), "{:?}", err);
+}
+
+#[test]
+fn test_iterable_protocol() {
+    check("type Count(n: Number) { n: Number = n; next(): Boolean => (self.n := self.n - 1) >= 0; current(): Number => self.n; } \
+        function total(xs: Number*): Number => let s = 0 in { for (x in xs) s := s + x; s; }; \
+        function first(xs: Iterable<String>): String => let w = \"\" in { for (x in xs) w := x; w; }; \
+        let m = new Map<String, Number>() in { \
+            print(total(new Count(3)) + total([1, 2]) + [x * 2 | x in new Count(2)][0]); \
+            print(first([\"a\"]) @ first(m) @ (new Count(1) is Iterable)); }");
+    // La variable del `for` toma el tipo de `current()`
+    let err = check_fail("type Words { next(): Boolean => false; current(): String => \"a\"; } \
+        for (w in new Words()) print(w + 1);");
+    assert!(matches!(&err[0], SemanticError::GenericError(msg, _) if msg.contains("String and Number")), "{:?}", err);
+    let err = check_fail("function total(xs: Number*): Number => 0; print(total([\"a\"]));");
+    assert!(matches!(&err[0], SemanticError::TypeMismatch { .. }), "{:?}", err);
+    let err = check_fail("for (x in 5) print(x);");
+    assert!(matches!(&err[0], SemanticError::NotIterable { ty, .. } if ty == "Number"), "{:?}", err);
+    let err = check_fail("type A { next(): Number => 1; current(): Number => 2; } print([x | x in new A()]);");
+    assert!(matches!(&err[0], SemanticError::NotIterable { ty, .. } if ty == "A"), "{:?}", err);
+}
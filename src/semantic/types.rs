@@ -8,6 +8,8 @@ use crate::errors::SemanticError;
 pub enum TypeKind {
     Basic,      // Tipos primitivos y clases definidas
     Protocol,   // Protocolos
+    Param,      // Parámetros de tipo (`T`); el padre es su cota u `Object`
+    Never,      // Tipo de `throw`: no produce valor y conforma a cualquier tipo
 }
 
 #[derive(Debug, Clone)]
@@ -18,6 +20,10 @@ pub struct Type {
     pub methods: HashMap<String, MethodInfo>,
     pub attributes: HashMap<String, Rc<RefCell<Type>>>,
     pub params: Vec<(String, Rc<RefCell<Type>>)>, // Constructor params
+    /// Parámetros de tipo de una declaración genérica (`Stack<T>`).
+    pub type_params: Vec<Rc<RefCell<Type>>>,
+    /// Declaración y argumentos de los que sale una instancia (`Stack<Number>`).
+    pub instance_of: Option<(String, Vec<Rc<RefCell<Type>>>)>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +42,8 @@ impl Type {
             methods: HashMap::new(),
             attributes: HashMap::new(),
             params: Vec::new(),
+            type_params: Vec::new(),
+            instance_of: None,
         }
     }
 
@@ -56,7 +64,25 @@ impl Type {
             return true;
         }
 
+        // Una expresión que nunca termina (`throw`) encaja en cualquier contexto
+        if self.kind == TypeKind::Never {
+            return true;
+        }
+
+        // Un parámetro de tipo conforma a lo que conforme su cota
+        if self.kind == TypeKind::Param {
+            return match &self.parent {
+                Some(bound) => bound.borrow().conforms_to(&Rc::new(RefCell::new(other.clone()))),
+                None => false,
+            };
+        }
+
+        // Contra un protocolo genérico sin instanciar (`Comparable`) los
+        // parámetros de tipo de su firma aceptan cualquier tipo
+        let erased = |t: &Rc<RefCell<Type>>| !other.type_params.is_empty() && t.borrow().kind == TypeKind::Param;
+
         match other.kind {
+            TypeKind::Param | TypeKind::Never => false,
             TypeKind::Basic => {
                 // Herencia: subir por la cadena de padres
                 if let Some(parent) = &self.parent {
@@ -64,6 +90,9 @@ impl Type {
                 }
                 false
             }
+            // Las cadenas no llevan type-id en tiempo de ejecución: sus métodos
+            // no se pueden despachar a través de un protocolo
+            TypeKind::Protocol if self.name == "String" && !other.methods.is_empty() => false,
             TypeKind::Protocol => {
                 // Implementación estructural: verificar que tenemos todos los métodos del protocolo
                 for (method_name, method_info) in &other.methods {
@@ -78,7 +107,7 @@ impl Type {
                         //    El retorno de la implementación debe ser un subtipo del retorno de la definición (protocolo).
                         //    Retorno_Impl <= Retorno_Proto
                         
-                        if !my_method.return_type.borrow().conforms_to(&method_info.return_type) {
+                        if !erased(&method_info.return_type) && !my_method.return_type.borrow().conforms_to(&method_info.return_type) {
                             return false;
                         }
 
@@ -88,7 +117,7 @@ impl Type {
                         for (i, (_, param_type)) in my_method.params.iter().enumerate() {
                             let (_, expected_param_type) = &method_info.params[i];
         
-                            if !expected_param_type.borrow().conforms_to(param_type) {
+                            if !erased(expected_param_type) && !expected_param_type.borrow().conforms_to(param_type) {
                                 return false;
                             }
                         }
@@ -152,13 +181,45 @@ impl TypeFactory {
         Rc::new(RefCell::new(Type::new("Number", TypeKind::Basic, Some(object))))
     }
     
-    pub fn string(object: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
-        Rc::new(RefCell::new(Type::new("String", TypeKind::Basic, Some(object))))
+    /// `String` con sus métodos nativos. `split` devuelve un `Vector<String>`
+    /// y lo agrega `Context::new`, que es quien construye los vectores.
+    /// `size()`, `at(i)`, `substring(i, n)` e `index_of(s)` cuentan caracteres
+    /// (code points), no bytes; `upper()` y `lower()` solo cambian letras ASCII.
+    pub fn string(object: Rc<RefCell<Type>>, number: Rc<RefCell<Type>>, boolean: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
+        let string = Rc::new(RefCell::new(Type::new("String", TypeKind::Basic, Some(object))));
+        let s = || ("s".to_string(), string.clone());
+        let methods = [
+            ("size", vec![], number.clone()),
+            ("substring", vec![("i".to_string(), number.clone()), ("n".to_string(), number.clone())], string.clone()),
+            ("at", vec![("i".to_string(), number.clone())], string.clone()),
+            ("index_of", vec![s()], number.clone()),
+            ("trim", vec![], string.clone()),
+            ("upper", vec![], string.clone()),
+            ("lower", vec![], string.clone()),
+            ("replace", vec![("old".to_string(), string.clone()), ("new".to_string(), string.clone())], string.clone()),
+            ("starts_with", vec![s()], boolean),
+            ("hash", vec![], number.clone()),
+            ("parse_number", vec![], number),
+        ];
+        for (name, params, ret) in methods {
+            string.borrow_mut().define_method(name.to_string(), params, ret);
+        }
+        string
     }
 
     pub fn boolean(object: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
         Rc::new(RefCell::new(Type::new("Boolean", TypeKind::Basic, Some(object))))
     }
+
+    /// Tipo vacío de las expresiones que no terminan (`throw`).
+    pub fn never(object: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
+        Rc::new(RefCell::new(Type::new("Never", TypeKind::Never, Some(object))))
+    }
+
+    /// Parámetro de tipo `name`; la cota se asigna después como padre.
+    pub fn param(name: &str, object: Rc<RefCell<Type>>) -> Rc<RefCell<Type>> {
+        Rc::new(RefCell::new(Type::new(name, TypeKind::Param, Some(object))))
+    }
 }
 
 pub fn lowest_common_ancestor(type_a: Rc<RefCell<Type>>, type_b: Rc<RefCell<Type>>) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
@@ -1,8 +1,9 @@
 use crate::ast::nodes::*;
-use crate::utils::Spanned;
+use crate::utils::{Position, Span, Spanned};
 use crate::errors::SemanticError;
 use super::context::{Context, conforms_to};
 use super::types::{Type, TypeKind, lowest_common_ancestor};
+use super::infer::element_type;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
@@ -12,6 +13,56 @@ pub struct TypeChecker<'a> {
     context: &'a Context,
     scope: Vec<HashMap<String, Rc<RefCell<Type>>>>,
     pub current_type: Option<Rc<RefCell<Type>>>,
+    /// Parámetros de tipo visibles en el cuerpo (los de la función o el tipo genérico).
+    pub type_params: Vec<Rc<RefCell<Type>>>,
+    /// Tipo inferido de cada expresión visitada con éxito, con su rango.
+    pub inferred: Vec<(Span, Rc<RefCell<Type>>)>,
+    /// Expresiones cuyo tipo sale de instanciar un genérico (ver `Context::generic_sites`).
+    pub generic_sites: Vec<(Span, Rc<RefCell<Type>>)>,
+    /// Usos de variables cuyo tipo estrechó un `is` (ver `Context::narrowed_sites`).
+    pub narrowed_sites: Vec<(Span, Rc<RefCell<Type>>)>,
+    /// Iterables genéricos y el tipo de sus elementos (ver `Context::element_sites`).
+    pub element_sites: Vec<(Span, Rc<RefCell<Type>>)>,
+    /// Lo activa el último `visit_*` si su resultado vino de una instancia genérica.
+    instantiated: bool,
+    /// Índices de `scope` que no declaran variables, sino que estrechan su tipo.
+    narrowing_frames: Vec<usize>,
+    /// Lo activa `visit_identifier` si la variable se leyó de un estrechamiento.
+    narrowed_use: bool,
+    /// Tipo de retorno de la función, método o lambda que se chequea; fuera de
+    /// ellos es `None` y `return` es un error.
+    pub return_type: Option<Rc<RefCell<Type>>>,
+    /// Bucles que encierran la expresión actual sin salir de su función o lambda.
+    loop_depth: usize,
+}
+
+/// Tipo del atributo `name` de `ty` o de alguno de sus ancestros.
+fn attribute_of(ty: &Rc<RefCell<Type>>, name: &str) -> Option<Rc<RefCell<Type>>> {
+    let mut current = Some(ty.clone());
+    while let Some(t) = current {
+        if let Some(attr) = t.borrow().get_attribute(name) {
+            return Some(attr);
+        }
+        current = t.borrow().parent.clone();
+    }
+    None
+}
+
+/// Variables que una condición verdadera garantiza de cierto tipo: las de
+/// `x is T` y las de cada lado de una conjunción `&`.
+pub(super) fn narrowing_tests(cond: &Expr) -> Vec<(&str, &str)> {
+    match cond {
+        Expr::Is(e, ty) => match &e.node {
+            Expr::Identifier(name) if name != "self" => vec![(name.as_str(), ty.as_str())],
+            _ => Vec::new(),
+        },
+        Expr::Binary(l, Op::And, r) => {
+            let mut tests = narrowing_tests(&l.node);
+            tests.extend(narrowing_tests(&r.node));
+            tests
+        }
+        _ => Vec::new(),
+    }
 }
 
 impl<'a> TypeChecker<'a> {
@@ -20,6 +71,16 @@ impl<'a> TypeChecker<'a> {
             context, 
             scope: vec![HashMap::new()],
             current_type: None,
+            type_params: Vec::new(),
+            inferred: Vec::new(),
+            generic_sites: Vec::new(),
+            narrowed_sites: Vec::new(),
+            element_sites: Vec::new(),
+            instantiated: false,
+            narrowing_frames: Vec::new(),
+            narrowed_use: false,
+            return_type: None,
+            loop_depth: 0,
         }
     }
     
@@ -38,15 +99,55 @@ impl<'a> TypeChecker<'a> {
     }
     
     pub fn lookup(&self, name: &str) -> Option<Rc<RefCell<Type>>> {
-        for scope in self.scope.iter().rev() {
+        self.lookup_in(name).map(|(ty, _)| ty)
+    }
+
+    /// Como `lookup`, pero indica también si el tipo sale de un estrechamiento.
+    fn lookup_in(&self, name: &str) -> Option<(Rc<RefCell<Type>>, bool)> {
+        for (i, scope) in self.scope.iter().enumerate().rev() {
             if let Some(ty) = scope.get(name) {
-                return Some(ty.clone());
+                return Some((ty.clone(), self.narrowing_frames.contains(&i)));
             }
         }
         None
     }
+
+    /// Tipo con el que se declaró la variable, ignorando los estrechamientos.
+    fn lookup_declared(&self, name: &str) -> Option<Rc<RefCell<Type>>> {
+        self.scope.iter().enumerate().rev()
+            .filter(|(i, _)| !self.narrowing_frames.contains(i))
+            .find_map(|(_, scope)| scope.get(name).cloned())
+    }
+
+    /// Estrechamientos que impone `cond` si es verdadera. Solo cuentan los
+    /// tipos no genéricos que conforman al tipo actual de la variable.
+    fn narrowings(&self, cond: &Expr) -> Vec<(String, Rc<RefCell<Type>>)> {
+        narrowing_tests(cond).into_iter().filter_map(|(name, ty)| {
+            let current = self.lookup(name)?;
+            let target = self.context.get_type(ty).ok()?;
+            let narrower = target.borrow().type_params.is_empty()
+                && target.borrow().name != current.borrow().name
+                && conforms_to(target.clone(), current);
+            narrower.then(|| (name.to_string(), target))
+        }).collect()
+    }
+
+    /// Infiere `expr` con los tipos de `narrowings` en vigor.
+    fn infer_narrowed(&mut self, expr: &Spanned<Expr>, narrowings: Vec<(String, Rc<RefCell<Type>>)>) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        if narrowings.is_empty() {
+            return self.infer_type(expr);
+        }
+        self.scope.push(narrowings.into_iter().collect());
+        self.narrowing_frames.push(self.scope.len() - 1);
+        let result = self.infer_type(expr);
+        self.narrowing_frames.pop();
+        self.scope.pop();
+        result
+    }
     
-    fn is_compatible(type_a: &Rc<RefCell<Type>>, type_b: &Rc<RefCell<Type>>) -> bool {
+    /// Si un valor de `type_a` puede ocupar un lugar declarado de `type_b`:
+    /// conformidad, salvo que el vector vacío `[]` sirve para cualquier vector.
+    pub(crate) fn is_compatible(type_a: &Rc<RefCell<Type>>, type_b: &Rc<RefCell<Type>>) -> bool {
         let name_a = &type_a.borrow().name;
         let name_b = &type_b.borrow().name;
         
@@ -64,6 +165,20 @@ impl<'a> TypeChecker<'a> {
     }
     
     pub fn infer_type(&mut self, expr: &Spanned<Expr>) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let result = self.infer_node(expr);
+        if let Ok(ty) = &result {
+            if std::mem::take(&mut self.instantiated) && expr.pos != Position::default() {
+                self.generic_sites.push((expr.span(), ty.clone()));
+            }
+            if std::mem::take(&mut self.narrowed_use) && expr.pos != Position::default() {
+                self.narrowed_sites.push((expr.span(), ty.clone()));
+            }
+            self.inferred.push((expr.span(), ty.clone()));
+        }
+        result
+    }
+
+    fn infer_node(&mut self, expr: &Spanned<Expr>) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         match &expr.node {
             Expr::Number(_) => self.visit_number(expr.pos),
             Expr::String(_) => self.visit_string(expr.pos),
@@ -77,15 +192,18 @@ impl<'a> TypeChecker<'a> {
             Expr::If { cond, then_expr, else_expr } => self.visit_if(cond, then_expr, else_expr, expr.pos),
             Expr::While { cond, body } => self.visit_while(cond, body, expr.pos),
             Expr::For { var, iterable, body } => self.visit_for(var, iterable, body, expr.pos),
-            Expr::Lambda { params, return_type, body } => self.visit_lambda(params, return_type, body, expr.pos),
+            Expr::Lambda { params, return_type, body } => self.visit_lambda(params, return_type, body, expr.span()),
             Expr::Assignment { target, value } => self.visit_assignment(target, value, expr.pos),
             Expr::AttributeAssignment { obj, attribute, value } => self.visit_attribute_assignment(obj, attribute, value, expr.pos),
-            Expr::Instantiation { ty, args } => self.visit_instantiation(ty, args, expr.pos),
+            Expr::Instantiation { ty, type_args, args } => self.visit_instantiation(ty, type_args, args, expr.pos),
             Expr::MethodCall { obj, method, args } => self.visit_method_call(obj, method, args, expr.pos),
             Expr::AttributeAccess { obj, attribute } => self.visit_attribute_access(obj, attribute, expr.pos),
             Expr::VectorLiteral(elems) => self.visit_vector_literal(elems, expr.pos),
             Expr::VectorGenerator { expr, var, iterable } => self.visit_vector_generator(expr, var, iterable, expr.pos),
             Expr::Indexing { obj, index } => self.visit_indexing(obj, index, expr.pos),
+            Expr::IndexAssignment { obj, index, value } => self.visit_index_assignment(obj, index, value, expr.pos),
+            Expr::Slice { obj, start, end } => self.visit_slice(obj, start.as_deref(), end.as_deref(), expr.pos),
+            Expr::MapLiteral(entries) => self.visit_map_literal(entries),
             Expr::Match { expr, cases, default } => self.visit_match(expr, cases, default, expr.pos),
             Expr::Is(e, ty) => self.visit_is(e, ty, expr.pos),
             Expr::As(e, ty) => self.visit_as(e, ty, expr.pos),
@@ -98,6 +216,11 @@ impl<'a> TypeChecker<'a> {
             Expr::Exp(e) => self.visit_exp(e, expr.pos),
             Expr::Log(base, val) => self.visit_log(base, val, expr.pos),
             Expr::BaseCall { args } => self.visit_base_call(args, expr.pos),
+            Expr::Try { body, catches } => self.visit_try(body, catches, expr.pos),
+            Expr::Throw(value) => self.visit_throw(value, expr.pos),
+            Expr::Break(value) => self.visit_loop_jump("break", value.as_deref(), expr.pos),
+            Expr::Continue(value) => self.visit_loop_jump("continue", value.as_deref(), expr.pos),
+            Expr::Return(value) => self.visit_return(value, expr.pos),
             _ => Ok(self.context.get_type("Object").unwrap()),
         }
     }
@@ -115,25 +238,32 @@ impl<'a> TypeChecker<'a> {
         Ok(self.context.get_type("Boolean").unwrap())
     }
     
-    fn visit_identifier(&mut self, name: &str, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+    fn visit_identifier(&mut self, name: &str, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         if name == "self" {
             if let Some(t) = &self.current_type {
                 Ok(t.clone())
             } else {
-                Err(vec![SemanticError::SelfReference])
+                Err(vec![SemanticError::SelfReference(pos)])
             }
-        } else if let Some(t) = self.lookup(name) {
+        } else if let Some((t, narrowed)) = self.lookup_in(name) {
+            self.narrowed_use = narrowed;
             Ok(t)
         } else if name == "PI" || name == "E" {
             Ok(self.context.get_type("Number").unwrap())
         } else {
-            Err(vec![SemanticError::VariableNotFound(name.to_string())])
+            Err(vec![SemanticError::VariableNotFound(name.to_string(), pos)])
         }
     }
     
-    fn visit_binary(&mut self, left: &Spanned<Expr>, op: &Op, right: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+    fn visit_binary(&mut self, left: &Spanned<Expr>, op: &Op, right: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         let t_left = self.infer_type(left);
-        let t_right = self.infer_type(right);
+        // En `a & b`, `b` solo se evalúa si `a` es verdadera
+        let t_right = if *op == Op::And {
+            let narrowings = self.narrowings(&left.node);
+            self.infer_narrowed(right, narrowings)
+        } else {
+            self.infer_type(right)
+        };
         
         if t_left.is_ok() && t_right.is_ok() {
             let l = t_left.unwrap();
@@ -145,14 +275,30 @@ impl<'a> TypeChecker<'a> {
                         Ok(self.context.get_type("Number").unwrap()),
                     Op::Eq | Op::Neq | Op::Lt | Op::Gt | Op::Le | Op::Ge => 
                         Ok(self.context.get_type("Boolean").unwrap()),
-                    _ => Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), "Number".to_string())])
+                    _ => Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), "Number".to_string(), pos)])
                 }
             } else if l.borrow().name == "Boolean" && r.borrow().name == "Boolean" {
                 match op {
                     Op::And | Op::Or | Op::Eq | Op::Neq => 
                         Ok(self.context.get_type("Boolean").unwrap()),
-                    _ => Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), "Boolean".to_string())])
+                    _ => Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), "Boolean".to_string(), pos)])
+                }
+            } else if *op == Op::Add && l.borrow().name.starts_with("Vector<") && r.borrow().name.starts_with("Vector<") {
+                // `v + w` concatena; el vector vacío `[]` se une a cualquiera
+                if Self::is_compatible(&r, &l) {
+                    Ok(l)
+                } else if Self::is_compatible(&l, &r) {
+                    Ok(r)
+                } else {
+                    Err(vec![SemanticError::TypeMismatch{
+                        expected: l.borrow().name.clone(),
+                        found: r.borrow().name.clone(),
+                        span: right.span()
+                    }])
                 }
+            } else if l.borrow().name == "String" && r.borrow().name == "String" && matches!(op, Op::Lt | Op::Gt | Op::Le | Op::Ge) {
+                // Orden lexicográfico, byte a byte
+                Ok(self.context.get_type("Boolean").unwrap())
             } else if matches!(op, Op::Concat | Op::ConcatSpace) {
                 Ok(self.context.get_type("String").unwrap())
             } else if l.borrow().name == "Object" || r.borrow().name == "Object" {
@@ -166,7 +312,7 @@ impl<'a> TypeChecker<'a> {
             } else if matches!(op, Op::Eq | Op::Neq) {
                 Ok(self.context.get_type("Boolean").unwrap())
             } else {
-                Err(vec![SemanticError::GenericError(format!("Operation {:?} is not defined for types {} and {}", op, l.borrow().name, r.borrow().name))])
+                Err(vec![SemanticError::GenericError(format!("Operation {:?} is not defined for types {} and {}", op, l.borrow().name, r.borrow().name), pos)])
             }
         } else {
             let mut errs = Vec::new();
@@ -176,14 +322,14 @@ impl<'a> TypeChecker<'a> {
         }
     }
     
-    fn visit_unary(&mut self, op: &UnOp, operand: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+    fn visit_unary(&mut self, op: &UnOp, operand: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         let t_op = self.infer_type(operand)?;
         if t_op.borrow().name == "Number" && matches!(op, UnOp::Neg) {
             Ok(t_op)
         } else if t_op.borrow().name == "Boolean" && matches!(op, UnOp::Not) {
             Ok(t_op)
         } else {
-            Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), t_op.borrow().name.clone())])
+            Err(vec![SemanticError::OperationNotDefined(format!("{:?}", op), t_op.borrow().name.clone(), pos)])
         }
     }
     
@@ -193,18 +339,24 @@ impl<'a> TypeChecker<'a> {
         for (name, type_ann, expr_span) in bindings {
             let t_expr = self.infer_type(expr_span)?;
             
-            if let Some(ann) = type_ann {
-                let t_ann = self.context.resolve_type(ann).map_err(|e| vec![e])?;
-                if !Self::is_compatible(&t_expr, &t_ann) {
-                    self.exit_scope();
-                    return Err(vec![SemanticError::TypeMismatch { 
-                        expected: t_ann.borrow().name.clone(), 
-                        found: t_expr.borrow().name.clone(), 
-                        pos: expr_span.pos 
-                    }]);
+            // Con anotación, la variable tiene el tipo declarado: `let l: Light = new Red()`
+            // admite luego `l := new Green()`
+            let t_var = match type_ann {
+                Some(ann) => {
+                    let t_ann = self.context.resolve_type_in(ann, &self.type_params).map_err(|e| vec![e.at(expr_span.pos)])?;
+                    if !Self::is_compatible(&t_expr, &t_ann) {
+                        self.exit_scope();
+                        return Err(vec![SemanticError::TypeMismatch { 
+                            expected: t_ann.borrow().name.clone(), 
+                            found: t_expr.borrow().name.clone(), 
+                            span: expr_span.span() 
+                        }]);
+                    }
+                    t_ann
                 }
-            }
-            self.define(name.clone(), t_expr);
+                None => t_expr,
+            };
+            self.define(name.clone(), t_var);
         }
         
         let result = self.infer_type(body);
@@ -220,10 +372,33 @@ impl<'a> TypeChecker<'a> {
         Ok(last_type)
     }
     
-    fn visit_call(&mut self, name: &str, args: &[Spanned<Expr>], _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+    fn visit_call(&mut self, name: &str, args: &[Spanned<Expr>], pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        if let Some(type_params) = self.context.function_type_params.get(name)
+            && let Some((param_types, ret_type)) = self.context.get_function(name)
+        {
+            if args.len() != param_types.len() {
+                return Err(vec![SemanticError::ArgumentCountMismatch(name.to_string(), param_types.len(), args.len(), pos)]);
+            }
+            let arg_types = args.iter().map(|a| self.infer_type(a)).collect::<Result<Vec<_>, _>>()?;
+            let bindings = self.infer_type_args(type_params, &param_types, &arg_types, pos)?;
+
+            for ((param_ty, arg_type), arg_expr) in param_types.iter().zip(&arg_types).zip(args) {
+                let expected = self.context.substitute(param_ty, &bindings);
+                if !Self::is_compatible(arg_type, &expected) {
+                    return Err(vec![SemanticError::TypeMismatch{
+                        expected: expected.borrow().name.clone(),
+                        found: arg_type.borrow().name.clone(),
+                        span: arg_expr.span()
+                    }]);
+                }
+            }
+            self.instantiated = true;
+            return Ok(self.context.substitute(&ret_type, &bindings));
+        }
+
         if let Some((param_types, ret_type)) = self.context.get_function(name) {
             if args.len() != param_types.len() {
-                return Err(vec![SemanticError::ArgumentCountMismatch(name.to_string(), param_types.len(), args.len())]);
+                return Err(vec![SemanticError::ArgumentCountMismatch(name.to_string(), param_types.len(), args.len(), pos)]);
             }
             
             for (i, arg_expr) in args.iter().enumerate() {
@@ -232,7 +407,7 @@ impl<'a> TypeChecker<'a> {
                     return Err(vec![SemanticError::TypeMismatch{ 
                         expected: param_types[i].borrow().name.clone(), 
                         found: arg_type.borrow().name.clone(), 
-                        pos: arg_expr.pos 
+                        span: arg_expr.span() 
                     }]);
                 }
             }
@@ -244,7 +419,7 @@ impl<'a> TypeChecker<'a> {
             }
             Ok(self.context.get_type("Object").unwrap())
         } else {
-            Err(vec![SemanticError::FunctionNotFound(name.to_string(), args.len())])
+            Err(vec![SemanticError::FunctionNotFound(name.to_string(), args.len(), pos)])
         }
     }
     
@@ -255,11 +430,12 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{ 
                 expected: "Boolean".to_string(), 
                 found: t_cond.borrow().name.clone(), 
-                pos: cond.pos 
+                span: cond.span() 
             }]);
         }
         
-        let t_then = self.infer_type(then_expr)?;
+        let narrowings = self.narrowings(&cond.node);
+        let t_then = self.infer_narrowed(then_expr, narrowings)?;
         let t_else = self.infer_type(else_expr)?;
         
         lowest_common_ancestor(t_then, t_else)
@@ -272,61 +448,125 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{ 
                 expected: "Boolean".to_string(), 
                 found: t_cond.borrow().name.clone(), 
-                pos: cond.pos 
+                span: cond.span() 
             }]);
         }
         
-        let _ = self.infer_type(body)?;
+        self.loop_depth += 1;
+        let result = self.infer_type(body);
+        self.loop_depth -= 1;
+        result?;
         Ok(self.context.get_type("Object").unwrap())
     }
     
     fn visit_for(&mut self, var: &str, iterable: &Spanned<Expr>, body: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        let _ = self.infer_type(iterable)?;
+        let elem_ty = self.visit_iterable(iterable)?;
         
         self.enter_scope();
-        self.define(var.to_string(), self.context.get_type("Object").unwrap());
+        self.define(var.to_string(), elem_ty);
+        self.loop_depth += 1;
         let result = self.infer_type(body);
+        self.loop_depth -= 1;
         self.exit_scope();
         
         result?;
         Ok(self.context.get_type("Object").unwrap())
     }
     
-    fn visit_lambda(&mut self, params: &[Param], _return_type: &Option<TypeAnnotation>, body: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        self.enter_scope();
-        
-        for p in params {
-            let p_type = if let Some(ann) = &p.type_annotation {
-                self.context.resolve_type(ann).unwrap_or_else(|_| self.context.get_type("Object").unwrap())
-            } else {
-                self.context.get_type("Object").unwrap()
+    /// Tipo del elemento que recorre un `for` o una comprensión. El iterable
+    /// debe implementar el protocolo `Iterable` (`next(): Boolean` y
+    /// `current()`, o `get_current()` como los vectores); de un `Object` no se
+    /// sabe nada y se recorre tal cual.
+    fn visit_iterable(&mut self, iterable: &Spanned<Expr>) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let ty = self.infer_type(iterable)?;
+        if ty.borrow().name == "Object" {
+            return Ok(ty);
+        }
+        let has_next = ty.borrow().get_method("next")
+            .is_some_and(|m| m.params.is_empty() && m.return_type.borrow().name == "Boolean");
+        match element_type(&ty) {
+            Some(elem) if has_next => {
+                if ty.borrow().instance_of.is_some() && iterable.pos != Position::default() {
+                    self.element_sites.push((iterable.span(), elem.clone()));
+                }
+                Ok(elem)
+            }
+            _ => Err(vec![SemanticError::NotIterable { ty: ty.borrow().name.clone(), pos: iterable.pos }]),
+        }
+    }
+    
+    fn visit_lambda(&mut self, params: &[Param], return_type: &Option<TypeAnnotation>, body: &Spanned<Expr>, span: Span) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        // Los parámetros sin anotar toman el tipo que les dio la inferencia
+        let inferred = self.context.lambda_types.get(&span);
+        let mut p_types = Vec::new();
+        for (i, p) in params.iter().enumerate() {
+            let p_type = match (&p.type_annotation, inferred.and_then(|ts| ts.get(i))) {
+                (Some(ann), _) => self.context.resolve_type_in(ann, &self.type_params).unwrap_or_else(|_| self.context.get_type("Object").unwrap()),
+                (None, Some(t)) => t.clone(),
+                (None, None) => self.context.get_type("Object").unwrap(),
             };
-            self.define(p.name.clone(), p_type);
+            p_types.push((p.name.clone(), p_type));
         }
-        
+
+        let expected = match return_type {
+            Some(ann) => Some(self.context.resolve_type_in(ann, &self.type_params).map_err(|e| vec![e.at(span.start)])?),
+            None => None,
+        };
+
+        // El cuerpo es una función aparte: sus `return` son suyos y no está en
+        // los bucles de afuera
+        self.enter_scope();
+        for (name, ty) in p_types {
+            self.define(name, ty);
+        }
+        let outer_return = self.return_type.replace(expected.clone().unwrap_or_else(|| self.context.get_type("Object").unwrap()));
+        let outer_loops = std::mem::take(&mut self.loop_depth);
         let result = self.infer_type(body);
+        self.return_type = outer_return;
+        self.loop_depth = outer_loops;
         self.exit_scope();
-        
-        result?;
+        let body_type = result?;
+
+        if let Some(expected) = expected
+            && !Self::is_compatible(&body_type, &expected)
+        {
+            return Err(vec![SemanticError::TypeMismatch {
+                expected: expected.borrow().name.clone(),
+                found: body_type.borrow().name.clone(),
+                span: body.span(),
+            }]);
+        }
         Ok(self.context.get_type("Object").unwrap())
     }
     
-    fn visit_assignment(&mut self, target: &str, value: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        if let Some(t_var) = self.lookup(target) {
+    fn visit_assignment(&mut self, target: &str, value: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        if let Some(t_var) = self.lookup_declared(target) {
             let t_val = self.infer_type(value)?;
+            self.drop_narrowing(target, &t_val);
             if !conforms_to(t_val.clone(), t_var.clone()) {
                 return Err(vec![SemanticError::TypeMismatch{ 
                     expected: t_var.borrow().name.clone(), 
                     found: t_val.borrow().name.clone(), 
-                    pos: value.pos 
+                    span: value.span() 
                 }]);
             }
             Ok(t_val)
         } else {
-            Err(vec![SemanticError::VariableNotFound(target.to_string())])
+            Err(vec![SemanticError::VariableNotFound(target.to_string(), pos)])
         }
     }
     
+    /// Tras `x := v`, un estrechamiento de `x` que `v` no cumple deja de valer.
+    fn drop_narrowing(&mut self, name: &str, value: &Rc<RefCell<Type>>) {
+        for &i in &self.narrowing_frames {
+            if let Some(ty) = self.scope[i].get(name)
+                && !conforms_to(value.clone(), ty.clone())
+            {
+                self.scope[i].remove(name);
+            }
+        }
+    }
+
     fn visit_attribute_assignment(&mut self, obj: &Spanned<Expr>, attribute: &str, value: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         let t_obj = self.infer_type(obj)?;
         let t_val = self.infer_type(value)?;
@@ -338,7 +578,7 @@ impl<'a> TypeChecker<'a> {
                     return Err(vec![SemanticError::TypeMismatch{ 
                         expected: attr_type.borrow().name.clone(), 
                         found: t_val.borrow().name.clone(), 
-                        pos: value.pos 
+                        span: value.span() 
                     }]);
                 }
                 return Ok(t_val);
@@ -349,21 +589,46 @@ impl<'a> TypeChecker<'a> {
         Ok(t_val)
     }
     
-    fn visit_instantiation(&mut self, ty: &str, args: &[Spanned<Expr>], _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        let type_rc = self.context.get_type(ty).map_err(|e| vec![e])?;
-        let params = type_rc.borrow().params.clone();
+    fn visit_instantiation(&mut self, ty: &str, type_args: &[TypeAnnotation], args: &[Spanned<Expr>], pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let base = self.context.get_type(ty).map_err(|e| vec![e.at(pos)])?;
+        if let Some(cases) = self.context.variants.get(ty) {
+            return Err(vec![SemanticError::GenericError(
+                format!("cannot instantiate variant '{}'; build one of its cases: {}", ty, cases.join(", ")),
+                pos,
+            )]);
+        }
+        let type_params = base.borrow().type_params.clone();
+        let base_params = base.borrow().params.clone();
         
-        if args.len() != params.len() {
-            return Err(vec![SemanticError::ArgumentCountMismatch(ty.to_string(), params.len(), args.len())]);
+        if args.len() != base_params.len() {
+            return Err(vec![SemanticError::ArgumentCountMismatch(ty.to_string(), base_params.len(), args.len(), pos)]);
         }
+        let arg_types = args.iter().map(|a| self.infer_type(a)).collect::<Result<Vec<_>, _>>()?;
+
+        // `new Stack<Number>()` instancia explícitamente; `new Box(3)` infiere
+        // los argumentos de tipo a partir de los del constructor
+        let type_rc = if !type_args.is_empty() {
+            let resolved = type_args.iter()
+                .map(|a| self.context.resolve_type_in(a, &self.type_params))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| vec![e.at(pos)])?;
+            self.context.instantiate(&base, resolved).map_err(|e| vec![e.at(pos)])?
+        } else if !type_params.is_empty() {
+            let ctor_types: Vec<_> = base_params.iter().map(|(_, t)| t.clone()).collect();
+            let bindings = self.infer_type_args(&type_params, &ctor_types, &arg_types, pos)?;
+            let resolved = type_params.iter().map(|p| bindings[&p.borrow().name].clone()).collect();
+            self.context.instantiate(&base, resolved).map_err(|e| vec![e.at(pos)])?
+        } else {
+            base
+        };
+        let params = type_rc.borrow().params.clone();
         
-        for (i, arg_expr) in args.iter().enumerate() {
-            let arg_type = self.infer_type(arg_expr)?;
-            if !conforms_to(arg_type.clone(), params[i].1.clone()) {
+        for (i, (arg_expr, arg_type)) in args.iter().zip(arg_types).enumerate() {
+            if !Self::is_compatible(&arg_type, &params[i].1) {
                 return Err(vec![SemanticError::TypeMismatch{ 
                     expected: params[i].1.borrow().name.clone(), 
                     found: arg_type.borrow().name.clone(), 
-                    pos: arg_expr.pos 
+                    span: arg_expr.span() 
                 }]);
             }
         }
@@ -371,15 +636,80 @@ impl<'a> TypeChecker<'a> {
         Ok(type_rc)
     }
     
-    fn visit_method_call(&mut self, obj: &Spanned<Expr>, method: &str, args: &[Spanned<Expr>], _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+    /// Infiere los argumentos de tipo de una llamada genérica unificando cada
+    /// parámetro con el tipo de su argumento. Los que no aparecen quedan en
+    /// `Object`; después se comprueban las cotas.
+    fn infer_type_args(&self, type_params: &[Rc<RefCell<Type>>], param_types: &[Rc<RefCell<Type>>], arg_types: &[Rc<RefCell<Type>>], pos: crate::utils::Position) -> Result<HashMap<String, Rc<RefCell<Type>>>, Vec<SemanticError>> {
+        let mut bindings = HashMap::new();
+        for (param_ty, arg_ty) in param_types.iter().zip(arg_types) {
+            Self::unify(type_params, param_ty, arg_ty, &mut bindings);
+        }
+        let args: Vec<_> = type_params.iter()
+            .map(|p| bindings.get(&p.borrow().name).cloned().unwrap_or_else(|| self.context.get_type("Object").unwrap()))
+            .collect();
+        self.context.check_type_args(type_params, &args).map_err(|e| vec![e.at(pos)])?;
+        Ok(Context::bindings(type_params, &args))
+    }
+
+    fn unify(type_params: &[Rc<RefCell<Type>>], param: &Rc<RefCell<Type>>, arg: &Rc<RefCell<Type>>, bindings: &mut HashMap<String, Rc<RefCell<Type>>>) {
+        if type_params.iter().any(|p| Rc::ptr_eq(p, param)) {
+            let name = param.borrow().name.clone();
+            let bound = match bindings.get(&name) {
+                Some(prev) => lowest_common_ancestor(prev.clone(), arg.clone()).unwrap_or_else(|_| arg.clone()),
+                None => arg.clone(),
+            };
+            bindings.insert(name, bound);
+            return;
+        }
+
+        let p = param.borrow();
+        if let Some((base, p_args)) = &p.instance_of {
+            // Buscar la misma declaración en la cadena de padres del argumento
+            let mut curr = Some(arg.clone());
+            while let Some(c) = curr {
+                if let Some((a_base, a_args)) = &c.borrow().instance_of
+                    && a_base == base
+                {
+                    for (pa, aa) in p_args.iter().zip(a_args) {
+                        Self::unify(type_params, pa, aa, bindings);
+                    }
+                    return;
+                }
+                curr = c.borrow().parent.clone();
+            }
+            // Protocolo genérico: unificar con la firma de los métodos del argumento
+            if p.kind == TypeKind::Protocol {
+                for (name, method) in &p.methods {
+                    if let Some(own) = arg.borrow().get_method(name) {
+                        for ((_, pt), (_, at)) in method.params.iter().zip(&own.params) {
+                            Self::unify(type_params, pt, at, bindings);
+                        }
+                        Self::unify(type_params, &method.return_type, &own.return_type, bindings);
+                    }
+                }
+            }
+            return;
+        }
+
+        if p.name.starts_with("Vector<")
+            && let Some(pe) = p.get_attribute("__element_type")
+            && let Some(ae) = arg.borrow().get_attribute("__element_type")
+        {
+            Self::unify(type_params, &pe, &ae, bindings);
+        }
+    }
+    
+    fn visit_method_call(&mut self, obj: &Spanned<Expr>, method: &str, args: &[Spanned<Expr>], pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         let t_obj = self.infer_type(obj)?;
         
         let mut curr = Some(t_obj.clone());
         let mut found_method = None;
+        let mut from_instance = false;
         
         while let Some(c) = curr {
             if let Some(m) = c.borrow().methods.get(method) {
                 found_method = Some(m.clone());
+                from_instance = c.borrow().instance_of.is_some();
                 break;
             }
             curr = c.borrow().parent.clone();
@@ -387,7 +717,7 @@ impl<'a> TypeChecker<'a> {
         
         if let Some(m) = found_method {
             if args.len() != m.params.len() {
-                return Err(vec![SemanticError::ArgumentCountMismatch(method.to_string(), m.params.len(), args.len())]);
+                return Err(vec![SemanticError::ArgumentCountMismatch(method.to_string(), m.params.len(), args.len(), pos)]);
             }
             
             for (i, arg_expr) in args.iter().enumerate() {
@@ -396,14 +726,15 @@ impl<'a> TypeChecker<'a> {
                     return Err(vec![SemanticError::TypeMismatch{ 
                         expected: m.params[i].1.borrow().name.clone(), 
                         found: arg_type.borrow().name.clone(), 
-                        pos: arg_expr.pos 
+                        span: arg_expr.span() 
                     }]);
                 }
             }
             
+            self.instantiated = from_instance;
             Ok(m.return_type.clone())
         } else {
-            Err(vec![SemanticError::MethodNotFound(format!("Method {} not found in {}", method, t_obj.borrow().name))])
+            Err(vec![SemanticError::MethodNotFound(format!("Method {} not found in {}", method, t_obj.borrow().name), pos)])
         }
     }
     
@@ -413,6 +744,7 @@ impl<'a> TypeChecker<'a> {
         let mut curr = Some(t_obj.clone());
         while let Some(c) = curr {
             if let Some(attr_type) = c.borrow().attributes.get(attribute) {
+                self.instantiated = c.borrow().instance_of.is_some();
                 return Ok(attr_type.clone());
             }
             curr = c.borrow().parent.clone();
@@ -424,17 +756,7 @@ impl<'a> TypeChecker<'a> {
     fn visit_vector_literal(&mut self, elems: &[Spanned<Expr>], _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         if elems.is_empty() {
             // Vector vacío por defecto es Vector<Object>
-            let obj_ty = self.context.get_type("Object").unwrap();
-            let vec_ty = Rc::new(RefCell::new(Type::new("Vector<Object>", TypeKind::Basic, None)));
-            vec_ty.borrow_mut().define_attribute("__element_type".to_string(), obj_ty.clone());
-            
-            let num_type = self.context.get_type("Number").unwrap();
-            let bool_type = self.context.get_type("Boolean").unwrap();
-            vec_ty.borrow_mut().define_method("size".to_string(), vec![], num_type);
-            vec_ty.borrow_mut().define_method("next".to_string(), vec![], bool_type);
-            vec_ty.borrow_mut().define_method("get_current".to_string(), vec![], obj_ty);
-            
-            return Ok(vec_ty);
+            return Ok(self.context.vector_type(self.context.get_type("Object").unwrap()));
         }
         
         // Inferir el tipo del elemento desde el primer elemento
@@ -447,64 +769,48 @@ impl<'a> TypeChecker<'a> {
                 return Err(vec![SemanticError::TypeMismatch{ 
                     expected: first_ty.borrow().name.clone(), 
                     found: elem_ty.borrow().name.clone(), 
-                    pos: e.pos 
+                    span: e.span() 
                 }]);
             }
         }
         
-        // Crear tipo Vector<T> con el tipo del elemento
-        let elem_name = first_ty.borrow().name.clone();
-        let vec_name = format!("Vector<{}>", elem_name);
-        let vec_ty = Rc::new(RefCell::new(Type::new(&vec_name, TypeKind::Basic, None)));
-        vec_ty.borrow_mut().define_attribute("__element_type".to_string(), first_ty.clone());
-        
-        let num_type = self.context.get_type("Number").unwrap();
-        let bool_type = self.context.get_type("Boolean").unwrap();
-        vec_ty.borrow_mut().define_method("size".to_string(), vec![], num_type);
-        vec_ty.borrow_mut().define_method("next".to_string(), vec![], bool_type);
-        vec_ty.borrow_mut().define_method("get_current".to_string(), vec![], first_ty.clone());
-        
-        Ok(vec_ty)
+        Ok(self.context.vector_type(first_ty))
     }
     
-    fn visit_vector_generator(&mut self, expr: &Spanned<Expr>, var: &str, iterable: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        let iterable_ty = self.infer_type(iterable)?;
-        
-        // Verificar que el iterable implemente el protocolo Iterable (métodos next y get_current)
-        let has_next = iterable_ty.borrow().methods.contains_key("next");
-        let has_current = iterable_ty.borrow().methods.contains_key("get_current");
-        
-        if !has_next || !has_current {
-            return Err(vec![SemanticError::MethodNotFound(
-                format!("{}::next/get_current", iterable_ty.borrow().name)
-            )]);
+    /// `{ k -> v, ... }`: las claves y los valores toman el tipo de los del
+    /// primer par, como los elementos de un vector.
+    fn visit_map_literal(&mut self, entries: &[(Spanned<Expr>, Spanned<Expr>)]) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let mut key_ty: Option<Rc<RefCell<Type>>> = None;
+        let mut value_ty: Option<Rc<RefCell<Type>>> = None;
+        for (key, value) in entries {
+            for (expr, expected) in [(key, &mut key_ty), (value, &mut value_ty)] {
+                let ty = self.infer_type(expr)?;
+                match expected {
+                    Some(first) if !conforms_to(ty.clone(), first.clone()) => {
+                        return Err(vec![SemanticError::TypeMismatch {
+                            expected: first.borrow().name.clone(),
+                            found: ty.borrow().name.clone(),
+                            span: expr.span(),
+                        }]);
+                    }
+                    Some(_) => {}
+                    None => *expected = Some(ty),
+                }
+            }
         }
+        let object = || self.context.get_type("Object").unwrap();
+        Ok(self.context.map_type(key_ty.unwrap_or_else(object), value_ty.unwrap_or_else(object)))
+    }
+
+    fn visit_vector_generator(&mut self, expr: &Spanned<Expr>, var: &str, iterable: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let elem_ty = self.visit_iterable(iterable)?;
         
         self.enter_scope();
-        // Usar el tipo de retorno de get_current como tipo de la variable iteradora
-        let elem_ty = if let Some(current_method) = iterable_ty.borrow().methods.get("get_current") {
-            current_method.return_type.clone()
-        } else {
-            self.context.get_type("Object").unwrap()
-        };
-        
         self.define(var.to_string(), elem_ty);
         let result_ty = self.infer_type(expr)?;
         self.exit_scope();
         
-        // Crear tipo Vector<result_ty> donde result_ty es el tipo de la expresión generada
-        let result_name = result_ty.borrow().name.clone();
-        let vec_name = format!("Vector<{}>", result_name);
-        let vec_ty = Rc::new(RefCell::new(Type::new(&vec_name, TypeKind::Basic, None)));
-        vec_ty.borrow_mut().define_attribute("__element_type".to_string(), result_ty.clone());
-        
-        let num_type = self.context.get_type("Number").unwrap();
-        let bool_type = self.context.get_type("Boolean").unwrap();
-        vec_ty.borrow_mut().define_method("size".to_string(), vec![], num_type);
-        vec_ty.borrow_mut().define_method("next".to_string(), vec![], bool_type);
-        vec_ty.borrow_mut().define_method("get_current".to_string(), vec![], result_ty.clone());
-        
-        Ok(vec_ty)
+        Ok(self.context.vector_type(result_ty))
     }
     
     fn visit_indexing(&mut self, obj: &Spanned<Expr>, index: &Spanned<Expr>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
@@ -515,7 +821,7 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{ 
                 expected: "Number".to_string(), 
                 found: t_idx.borrow().name.clone(), 
-                pos: index.pos 
+                span: index.span() 
             }]);
         }
         
@@ -541,20 +847,312 @@ impl<'a> TypeChecker<'a> {
         Ok(self.context.get_type("Object").unwrap())
     }
     
-    fn visit_match(&mut self, expr: &Spanned<Expr>, cases: &[MatchCase], default: &Option<Box<Spanned<Expr>>>, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
-        let _ = self.infer_type(expr)?;
-        
+    /// `v[i] := x`: el valor debe conformar al tipo del elemento del vector.
+    fn visit_index_assignment(&mut self, obj: &Spanned<Expr>, index: &Spanned<Expr>, value: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let elem_ty = self.visit_indexing(obj, index, pos)?;
+        let t_val = self.infer_type(value)?;
+        if !conforms_to(t_val.clone(), elem_ty.clone()) {
+            return Err(vec![SemanticError::TypeMismatch{
+                expected: elem_ty.borrow().name.clone(),
+                found: t_val.borrow().name.clone(),
+                span: value.span()
+            }]);
+        }
+        Ok(t_val)
+    }
+
+    /// `v[a:b]`: los límites son números y el resultado es un vector nuevo
+    /// del mismo tipo.
+    fn visit_slice(&mut self, obj: &Spanned<Expr>, start: Option<&Spanned<Expr>>, end: Option<&Spanned<Expr>>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let obj_ty = self.infer_type(obj)?;
+        for bound in start.into_iter().chain(end) {
+            let t = self.infer_type(bound)?;
+            if t.borrow().name != "Number" {
+                return Err(vec![SemanticError::TypeMismatch{
+                    expected: "Number".to_string(),
+                    found: t.borrow().name.clone(),
+                    span: bound.span()
+                }]);
+            }
+        }
+        if !obj_ty.borrow().name.starts_with("Vector<") {
+            return Err(vec![SemanticError::OperationNotDefined("slicing".to_string(), obj_ty.borrow().name.clone(), pos)]);
+        }
+        Ok(obj_ty)
+    }
+
+    fn visit_match(&mut self, expr: &Spanned<Expr>, cases: &[MatchCase], default: &Option<Box<Spanned<Expr>>>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let scrutinee = self.infer_type(expr)?;
+        let mut errors = Vec::new();
+        let mut arms = Vec::new();
+
         for case in cases {
-            let _ = self.infer_type(&case.expr)?;
+            self.enter_scope();
+            let result = self.bind_pattern(&case.pattern, &scrutinee, case.expr.pos)
+                .and_then(|()| match &case.guard {
+                    Some(guard) => self.check_condition(guard),
+                    None => Ok(()),
+                })
+                .and_then(|()| self.infer_type(&case.expr));
+            self.exit_scope();
+            match result {
+                Ok(t) => arms.push(t),
+                Err(mut e) => errors.append(&mut e),
+            }
         }
-        
-        if let Some(d) = default {
-            let _ = self.infer_type(d)?;
+
+        match default {
+            Some(d) => match self.infer_type(d) {
+                Ok(t) => arms.push(t),
+                Err(mut e) => errors.append(&mut e),
+            },
+            None => {
+                let missing = self.uncovered(&scrutinee, cases);
+                if !missing.is_empty() {
+                    errors.push(SemanticError::NonExhaustiveMatch { missing, pos });
+                }
+            }
         }
-        
-        Ok(self.context.get_type("Object").unwrap())
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut arms = arms.into_iter();
+        let first = arms.next().unwrap_or_else(|| self.context.get_type("Object").unwrap());
+        arms.try_fold(first, lowest_common_ancestor)
+    }
+
+    /// Una condición (guarda de un `case`) debe ser `Boolean`.
+    fn check_condition(&mut self, cond: &Spanned<Expr>) -> Result<(), Vec<SemanticError>> {
+        let t_cond = self.infer_type(cond)?;
+        if t_cond.borrow().name != "Boolean" && t_cond.borrow().name != "Object" {
+            return Err(vec![SemanticError::TypeMismatch {
+                expected: "Boolean".to_string(),
+                found: t_cond.borrow().name.clone(),
+                span: cond.span(),
+            }]);
+        }
+        Ok(())
+    }
+
+    /// Declara las variables que enlaza `pattern` al compararlo con un valor de tipo `ty`.
+    fn bind_pattern(&mut self, pattern: &Pattern, ty: &Rc<RefCell<Type>>, pos: Position) -> Result<(), Vec<SemanticError>> {
+        match pattern {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Variable { name, type_annotation: None } => {
+                self.define(name.clone(), ty.clone());
+                Ok(())
+            }
+            Pattern::Variable { name, type_annotation: Some(ann) } => {
+                let tested = self.context.resolve_type_in(ann, &self.type_params).map_err(|e| vec![e.at(pos)])?;
+                Self::check_testable(&tested, ty, pos)?;
+                self.define(name.clone(), tested);
+                Ok(())
+            }
+            Pattern::Literal(lit) => {
+                let name = match lit {
+                    Expr::Number(_) => "Number",
+                    Expr::String(_) => "String",
+                    Expr::Boolean(_) => "Boolean",
+                    _ => "Object",
+                };
+                Self::check_testable(&self.context.get_type(name).unwrap(), ty, pos)
+            }
+            Pattern::Call { func, args } => {
+                let tested = self.context.get_type(func).map_err(|e| vec![e.at(pos)])?;
+                Self::check_testable(&tested, ty, pos)?;
+                let params = tested.borrow().params.clone();
+                if params.len() != args.len() {
+                    return Err(vec![SemanticError::ArgumentCountMismatch(func.clone(), params.len(), args.len(), pos)]);
+                }
+                for ((param, _), arg) in params.iter().zip(args) {
+                    let attr = attribute_of(&tested, param).ok_or_else(|| vec![SemanticError::GenericError(
+                        format!("type '{}' has no attribute '{}' to match its constructor parameter", func, param), pos)])?;
+                    self.bind_pattern(arg, &attr, pos)?;
+                }
+                Ok(())
+            }
+            Pattern::Binary { .. } | Pattern::Unary { .. } => Err(vec![SemanticError::GenericError(
+                format!("pattern '{}' can only match code inside a macro", pattern), pos)]),
+        }
+    }
+
+    /// Un patrón que comprueba `tested` tiene sentido si algún valor de tipo
+    /// `ty` puede serlo. Contra protocolos y parámetros de tipo se acepta siempre.
+    fn check_testable(tested: &Rc<RefCell<Type>>, ty: &Rc<RefCell<Type>>, pos: Position) -> Result<(), Vec<SemanticError>> {
+        let related = tested.borrow().conforms_to(ty)
+            || ty.borrow().conforms_to(tested)
+            || tested.borrow().kind != TypeKind::Basic
+            || ty.borrow().kind != TypeKind::Basic;
+        if related {
+            Ok(())
+        } else {
+            Err(vec![SemanticError::TypeMismatch {
+                expected: ty.borrow().name.clone(),
+                found: tested.borrow().name.clone(),
+                span: Span::point(pos),
+            }])
+        }
+    }
+
+    /// Si `pattern` encaja con cualquier valor de tipo `ty`.
+    fn irrefutable(&self, pattern: &Pattern, ty: &Rc<RefCell<Type>>) -> bool {
+        match pattern {
+            Pattern::Wildcard | Pattern::Variable { type_annotation: None, .. } => true,
+            Pattern::Variable { .. } | Pattern::Call { .. } => match self.covered_type(pattern) {
+                Some(tested) => ty.borrow().conforms_to(&tested),
+                None => false,
+            },
+            Pattern::Literal(_) | Pattern::Binary { .. } | Pattern::Unary { .. } => false,
+        }
+    }
+
+    /// Tipo cuyos valores encajan todos con `pattern`, si lo hay.
+    fn covered_type(&self, pattern: &Pattern) -> Option<Rc<RefCell<Type>>> {
+        match pattern {
+            Pattern::Variable { type_annotation: Some(ann), .. } => self.context.resolve_type_in(ann, &self.type_params).ok(),
+            Pattern::Call { func, args } => {
+                let tested = self.context.get_type(func).ok()?;
+                let params = tested.borrow().params.clone();
+                let all = params.len() == args.len() && params.iter().zip(args).all(|((param, _), arg)| {
+                    attribute_of(&tested, param).is_some_and(|attr| self.irrefutable(arg, &attr))
+                });
+                all.then_some(tested)
+            }
+            _ => None,
+        }
+    }
+
+    /// Tipos concretos que pueden llegar a un `match` sobre `ty` y que ningún
+    /// caso sin guarda cubre. De una rama de la jerarquía solo se nombra la raíz.
+    /// El tipo base de un `variant` no cuenta: solo existen sus casos.
+    fn uncovered(&self, ty: &Rc<RefCell<Type>>, cases: &[MatchCase]) -> Vec<String> {
+        let patterns: Vec<&Pattern> = cases.iter().filter(|c| c.guard.is_none()).map(|c| &c.pattern).collect();
+        if patterns.iter().any(|p| self.irrefutable(p, ty)) {
+            return Vec::new();
+        }
+        let covers: Vec<_> = patterns.iter().filter_map(|p| self.covered_type(p)).collect();
+        let booleans: Vec<bool> = [true, false].into_iter()
+            .filter(|b| !patterns.contains(&&Pattern::Literal(Expr::Boolean(*b))))
+            .collect();
+
+        let mut concrete: Vec<_> = self.context.types.values()
+            .filter(|t| t.borrow().kind == TypeKind::Basic && t.borrow().type_params.is_empty())
+            .filter(|t| t.borrow().conforms_to(ty) && !self.context.variants.contains_key(&t.borrow().name))
+            .cloned()
+            .collect();
+        if ty.borrow().kind == TypeKind::Basic && !self.context.variants.contains_key(&ty.borrow().name) && !concrete.iter().any(|t| t.borrow().name == ty.borrow().name) {
+            concrete.push(ty.clone());
+        }
+        let missing: Vec<_> = concrete.into_iter().filter(|t| {
+            let covered = (booleans.is_empty() && t.borrow().name == "Boolean")
+                || covers.iter().any(|c| t.borrow().conforms_to(c));
+            !covered
+        }).collect();
+
+        let mut roots: Vec<String> = missing.iter()
+            .filter(|t| match &t.borrow().parent {
+                Some(p) => !missing.iter().any(|m| m.borrow().name == p.borrow().name),
+                None => true,
+            })
+            .map(|t| match t.borrow().name.as_str() {
+                // Con uno de los dos literales, falta el otro
+                "Boolean" if booleans.len() == 1 => booleans[0].to_string(),
+                name => name.to_string(),
+            })
+            .collect();
+        roots.sort();
+        roots
     }
     
+    /// El tipo de un `try` es el ancestro común del cuerpo y de los manejadores.
+    /// Cada `catch` enlaza su variable a un subtipo de `Error` (por defecto, `Error`).
+    fn visit_try(&mut self, body: &Spanned<Expr>, catches: &[CatchClause], _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let mut errors = Vec::new();
+        let mut arms = Vec::new();
+        match self.infer_type(body) {
+            Ok(t) => arms.push(t),
+            Err(mut e) => errors.append(&mut e),
+        }
+
+        for c in catches {
+            let result = self.caught_type(c).and_then(|caught| {
+                self.enter_scope();
+                self.define(c.var.clone(), caught);
+                let result = self.infer_type(&c.body);
+                self.exit_scope();
+                result
+            });
+            match result {
+                Ok(t) => arms.push(t),
+                Err(mut e) => errors.append(&mut e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut arms = arms.into_iter();
+        let first = arms.next().unwrap();
+        arms.try_fold(first, lowest_common_ancestor)
+    }
+
+    fn caught_type(&self, c: &CatchClause) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let name = c.ty.as_deref().unwrap_or("Error");
+        let caught = self.context.get_type(name).map_err(|e| vec![e.at(c.body.pos)])?;
+        let error = self.context.get_type("Error").map_err(|e| vec![e.at(c.body.pos)])?;
+        if !caught.borrow().conforms_to(&error) {
+            return Err(vec![SemanticError::TypeMismatch {
+                expected: "Error".to_string(),
+                found: name.to_string(),
+                span: c.body.span(),
+            }]);
+        }
+        Ok(caught)
+    }
+
+    /// `throw` acepta cualquier subtipo de `Error` y nunca produce un valor.
+    fn visit_throw(&mut self, value: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let t = self.infer_type(value)?;
+        let error = self.context.get_type("Error").map_err(|e| vec![e.at(pos)])?;
+        if !t.borrow().conforms_to(&error) {
+            return Err(vec![SemanticError::TypeMismatch {
+                expected: "Error".to_string(),
+                found: t.borrow().name.clone(),
+                span: value.span(),
+            }]);
+        }
+        Ok(self.context.get_type("Never").unwrap())
+    }
+
+    /// `break` y `continue` solo valen dentro de un bucle; su valor, si lo
+    /// hay, se chequea como cualquier expresión (los bucles son `Object`).
+    fn visit_loop_jump(&mut self, keyword: &str, value: Option<&Spanned<Expr>>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        if self.loop_depth == 0 {
+            return Err(vec![SemanticError::MisplacedJump { keyword: keyword.to_string(), pos }]);
+        }
+        if let Some(value) = value {
+            self.infer_type(value)?;
+        }
+        Ok(self.context.get_type("Never").unwrap())
+    }
+
+    /// El valor de `return` debe conformar al tipo de retorno de la función.
+    fn visit_return(&mut self, value: &Spanned<Expr>, pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
+        let Some(expected) = self.return_type.clone() else {
+            return Err(vec![SemanticError::MisplacedJump { keyword: "return".to_string(), pos }]);
+        };
+        let t = self.infer_type(value)?;
+        if !Self::is_compatible(&t, &expected) {
+            return Err(vec![SemanticError::TypeMismatch {
+                expected: expected.borrow().name.clone(),
+                found: t.borrow().name.clone(),
+                span: value.span(),
+            }]);
+        }
+        Ok(self.context.get_type("Never").unwrap())
+    }
+
     fn visit_is(&mut self, expr: &Spanned<Expr>, _ty: &str, _pos: crate::utils::Position) -> Result<Rc<RefCell<Type>>, Vec<SemanticError>> {
         let _ = self.infer_type(expr)?;
         Ok(self.context.get_type("Boolean").unwrap())
@@ -583,7 +1181,7 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t.borrow().name.clone(), 
-                pos: e.pos
+                span: e.span()
             }]);
         }
         Ok(self.context.get_type("Number").unwrap())
@@ -595,7 +1193,7 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t.borrow().name.clone(), 
-                pos: e.pos
+                span: e.span()
             }]);
         }
         Ok(self.context.get_type("Number").unwrap())
@@ -607,7 +1205,7 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t.borrow().name.clone(), 
-                pos: e.pos
+                span: e.span()
             }]);
         }
         Ok(self.context.get_type("Number").unwrap())
@@ -619,7 +1217,7 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t.borrow().name.clone(), 
-                pos: e.pos
+                span: e.span()
             }]);
         }
         Ok(self.context.get_type("Number").unwrap())
@@ -633,14 +1231,14 @@ impl<'a> TypeChecker<'a> {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t1.borrow().name.clone(), 
-                pos: base.pos
+                span: base.span()
             }]);
         }
         if t2.borrow().name != "Number" {
             return Err(vec![SemanticError::TypeMismatch{
                 expected: "Number".into(), 
                 found: t2.borrow().name.clone(), 
-                pos: val.pos
+                span: val.span()
             }]);
         }
         
@@ -1,9 +1,19 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Posición en el código fuente: línea y columna (1-based) y desplazamiento en
+/// bytes (0-based). `Position::default()` (0:0) indica una posición desconocida,
+/// p. ej. en nodos sintetizados por el compilador.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
+    pub offset: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize, offset: usize) -> Self {
+        Self { line, column, offset }
+    }
 }
 
 impl fmt::Display for Position {
@@ -12,14 +22,75 @@ impl fmt::Display for Position {
     }
 }
 
+/// Rango `[start, end)` del código fuente; `end` apunta justo después del último carácter.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// Rango vacío en `pos`, para nodos de los que solo se conoce el inicio.
+    pub fn point(pos: Position) -> Self {
+        Self { start: pos, end: pos }
+    }
+
+    /// Rango que cubre desde el inicio de `self` hasta el final de `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start, end: other.end }
+    }
+
+    /// Longitud en bytes.
+    pub fn len(&self) -> usize {
+        self.end.offset.saturating_sub(self.start.offset)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Indica si el desplazamiento `offset` cae dentro del rango (inclusive al final,
+    /// para que un cursor justo detrás de un identificador lo siga seleccionando).
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start.offset <= offset && offset <= self.end.offset
+    }
+}
+
+impl From<Position> for Span {
+    fn from(pos: Position) -> Self {
+        Span::point(pos)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Spanned<T> {
     pub node: T,
     pub pos: Position,
+    /// Final del nodo (posición justo después de su último token).
+    pub end: Position,
 }
 
 impl<T> Spanned<T> {
+    /// Crea un nodo del que solo se conoce la posición de inicio.
     pub fn new(node: T, pos: Position) -> Self {
-        Self { node, pos }
+        Self { node, pos, end: pos }
+    }
+
+    pub fn with_span(node: T, span: Span) -> Self {
+        Self { node, pos: span.start, end: span.end }
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.pos, self.end)
     }
 }
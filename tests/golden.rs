@@ -0,0 +1,178 @@
+//! Tests dorados: cada `tests/programs/<nombre>.hulk` se ejecuta con el
+//! intérprete y, si hay clang, con el backend LLVM; la salida de ambos debe
+//! coincidir con `<nombre>.out`. El binario nativo se ejecuta también con
+//! `HULK_GC_STRESS=1` y debe imprimir lo mismo que sin forzar el GC.
+//!
+//! - `HULK_BLESS=1 cargo test --test golden` reescribe los `.out` con la salida del intérprete.
+//! - `// known-llvm-failure: <motivo>` en un programa marca un fallo conocido del
+//!   backend LLVM: se exige que siga fallando, para acordarse de quitar la marca.
+
+use hulk_compiler::driver::{self, ToolchainError};
+use hulk_compiler::errors::Diagnostic;
+use hulk_compiler::interp;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const KNOWN_LLVM_FAILURE: &str = "// known-llvm-failure:";
+const GC_STRESS_BACKEND: &str = "llvm, HULK_GC_STRESS=1";
+
+struct Program {
+    name: String,
+    path: PathBuf,
+    source: String,
+}
+
+impl Program {
+    fn expected_path(&self) -> PathBuf {
+        self.path.with_extension("out")
+    }
+
+    fn known_llvm_failure(&self) -> bool {
+        self.source.lines().any(|line| line.starts_with(KNOWN_LLVM_FAILURE))
+    }
+}
+
+fn programs() -> Vec<Program> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("no se puede leer {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hulk"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| Program {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            source: std::fs::read_to_string(&path).unwrap(),
+            path,
+        })
+        .collect()
+}
+
+/// Ejecuta el programa con el intérprete; los errores se devuelven renderizados.
+fn run_interpreted(program: &Program) -> Result<String, String> {
+    let source = program.source.clone();
+    let file = format!("{}.hulk", program.name);
+    std::thread::Builder::new()
+        .stack_size(interp::STACK_SIZE)
+        .spawn(move || {
+            let checked = driver::optimize(&source).map_err(|e| e.render(&source, &file))?;
+            let mut out = Vec::new();
+            driver::run_interpreted(&checked, &mut out)
+                .map_err(|e| Diagnostic::from(&e).render(&source, &file))?;
+            Ok(String::from_utf8_lossy(&out).into_owned())
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+/// Compila el programa con `LlvmGenerator` y clang y ejecuta el binario dos veces:
+/// normal y con `HULK_GC_STRESS=1`, que fuerza una colección en cada asignación.
+/// `None` si no hay compilador de C disponible.
+fn run_llvm(program: &Program) -> Option<[(&'static str, Result<String, String>); 2]> {
+    let llvm_code = match driver::compile_to_llvm(&program.source) {
+        Ok(code) => code,
+        Err(e) => {
+            let err = e.render(&program.source, &program.name);
+            return Some([("llvm", Err(err.clone())), (GC_STRESS_BACKEND, Err(err))]);
+        }
+    };
+    let dir = driver::scratch_dir().unwrap();
+    let (ll_path, bin_path) = (dir.join("main.ll"), dir.join("main"));
+    std::fs::write(&ll_path, &llvm_code).unwrap();
+    let linked = driver::link_native(&ll_path, &bin_path);
+    let result = match linked {
+        Err(ToolchainError::Spawn(_, e)) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => Some([("llvm", Err(e.to_string())), (GC_STRESS_BACKEND, Err(e.to_string()))]),
+        Ok(()) => Some([
+            ("llvm", run_native(&bin_path, None)),
+            (GC_STRESS_BACKEND, run_native(&bin_path, Some("HULK_GC_STRESS"))),
+        ]),
+    };
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+fn run_native(bin: &Path, env: Option<&str>) -> Result<String, String> {
+    let mut command = Command::new(bin);
+    command.stdin(Stdio::null());
+    if let Some(var) = env {
+        command.env(var, "1");
+    }
+    let output = command.output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!(
+            "{}\n{}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+fn mismatch(backend: &str, program: &Program, expected: &str, actual: &Result<String, String>) -> String {
+    match actual {
+        Ok(out) => format!(
+            "{} ({}): la salida no coincide\n--- esperado\n{}--- obtenido\n{}",
+            program.name, backend, expected, out
+        ),
+        Err(e) => format!("{} ({}): falló\n{}", program.name, backend, e),
+    }
+}
+
+#[test]
+fn golden_programs() {
+    let programs = programs();
+    assert!(!programs.is_empty(), "no hay programas en tests/programs");
+    let bless = std::env::var_os("HULK_BLESS").is_some();
+    let mut failures = Vec::new();
+    let mut llvm_available = true;
+
+    for program in &programs {
+        let interpreted = run_interpreted(program);
+        if bless {
+            if let Ok(out) = &interpreted {
+                std::fs::write(program.expected_path(), out).unwrap();
+            }
+        }
+        let Ok(expected) = std::fs::read_to_string(program.expected_path()) else {
+            failures.push(format!("{}: falta {}.out (HULK_BLESS=1 lo genera)", program.name, program.name));
+            continue;
+        };
+
+        if interpreted.as_deref() != Ok(expected.as_str()) {
+            failures.push(mismatch("interp", program, &expected, &interpreted));
+        }
+
+        if !llvm_available {
+            continue;
+        }
+        let Some([(_, native), stressed]) = run_llvm(program) else {
+            eprintln!("golden: no hay clang ($HULK_CC); solo se comprueba el intérprete");
+            llvm_available = false;
+            continue;
+        };
+        let passes = native.as_deref() == Ok(expected.as_str());
+        match (passes, program.known_llvm_failure()) {
+            (true, true) => failures.push(format!(
+                "{} (llvm): ya funciona; quita la marca '{}'",
+                program.name, KNOWN_LLVM_FAILURE
+            )),
+            (false, false) => failures.push(mismatch("llvm", program, &expected, &native)),
+            _ => {}
+        }
+        // Con el GC forzado la salida debe ser la misma que sin él. Los fallos
+        // conocidos se saltan: algunos leen memoria fuera de los objetos.
+        let (backend, stressed) = stressed;
+        if passes && stressed != native {
+            failures.push(mismatch(backend, program, &expected, &stressed));
+        }
+    }
+
+    assert!(failures.is_empty(), "{} fallo(s):\n\n{}", failures.len(), failures.join("\n\n"));
+}